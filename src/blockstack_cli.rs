@@ -5,23 +5,39 @@
 #![allow(non_upper_case_globals)]
 
 extern crate blockstack_lib;
+extern crate serde_json;
+extern crate libc;
 
 use std::{io, fs, env};
 use std::io::prelude::*;
 use std::convert::TryFrom;
 use std::io::Read;
+use std::path::Path;
 use blockstack_lib::util::{log, strings::StacksString, hash::hex_bytes, hash::to_hex};
 use blockstack_lib::vm;
 use blockstack_lib::vm::{
     Value, ClarityName, ContractName, errors::RuntimeErrorType, errors::Error as ClarityError };
 use blockstack_lib::chainstate::stacks::{
     C32_ADDRESS_VERSION_MAINNET_SINGLESIG, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+    C32_ADDRESS_VERSION_MAINNET_MULTISIG, C32_ADDRESS_VERSION_TESTNET_MULTISIG,
+    TransactionAuthFlags, TransactionAuthField, MultisigHashMode, MultisigSpendingCondition,
+    TransactionPostCondition, TransactionPostConditionMode,
     StacksPrivateKey, TransactionSpendingCondition, TransactionAuth, TransactionVersion,
     StacksPublicKey, TransactionPayload, StacksTransactionSigner,
-    StacksTransaction, TransactionSmartContract, TransactionContractCall, StacksAddress, TokenTransferMemo };
-use blockstack_lib::burnchains::Address;
+    StacksTransaction, TransactionSmartContract, TransactionContractCall, StacksAddress, TokenTransferMemo,
+    Error as ChainstateError };
+use blockstack_lib::burnchains::{Address, PublicKey};
+use blockstack_lib::util::hash::Hash160;
+use blockstack_lib::util::secp256k1::MessageSignature;
+use blockstack_lib::util::hdkeys::{Mnemonic, ExtendedPrivateKey, DerivationPath, BIP32_HARDENED_INDEX, Error as HDKeysError};
+use blockstack_lib::util::vrf::{VRFPrivateKey, VRFPublicKey};
+use blockstack_lib::util::keystore::{Keystore, KeystoreKey, KEYSTORE_DEFAULT_ROUNDS, Error as KeystoreError};
 use blockstack_lib::address::AddressHashMode;
 use blockstack_lib::net::{Error as NetError, StacksMessageCodec};
+use blockstack_lib::burnchains::{Txid, BurnchainHeaderHash};
+use blockstack_lib::chainstate::burn::{BlockHeaderHash, ConsensusHash};
+use blockstack_lib::chainstate::stacks::db::StacksChainState;
+use blockstack_lib::core::FIRST_STACKS_BLOCK_HASH;
 
 const USAGE: &str = "blockstack-cli (options) [method] [args...]
 
@@ -33,13 +49,51 @@ This CLI has these methods:
   publish          used to generate and sign a contract publish transaction
   contract-call    used to generate and sign a contract-call transaction
   generate-sk      used to generate a secret key for transaction signing
+  generate-mnemonic used to generate a mnemonic phrase from which keys can be derived
+  derive-keys      used to derive account, microblock-signing and VRF keys from a mnemonic phrase
+  keystore-create  used to encrypt a secret key into a new keystore file
+  keystore-create-vrf used to encrypt a VRF secret key into a new keystore file
+  keystore-show    used to decrypt a keystore file and show its key
   token-transfer   used to generate and sign a transfer transaction
+  sponsor          used to sign a sponsored transaction as its sponsor, paying its fee
+  multisig-address  used to derive the address of a multisig account from its public keys
+  multisig-tx       used to generate an unsigned transaction from a multisig account
+  multisig-sign     used to add one signer's signature to a multisig transaction
+  multisig-finalize used to verify a multisig transaction and complete it once enough signers have signed
+  unsigned-tx       used to generate an unsigned transaction from a single-sig account, for signing offline
+  sighash           used to get the hash that the next signer of a transaction must sign offline
+  append-signature  used to add a signature made offline to a transaction
+  set-sponsor       used to set the sponsor of a sponsored transaction without signing it
+  simulate          used to find out what a signed transaction would do if it were mined on a given chain tip
+  export-snapshot   used to export a node's chain state at a given block, so a new node can start from it
+  import-snapshot   used to verify an exported chain state snapshot and set up a new node's chain state from it
 
 For usage information on those methods, call `blockstack-cli [method] -h`
 
 `blockstack-cli` accepts flag options as well:
 
    --testnet       instruct the transaction generator to use a testnet version byte instead of MAINNET (default)
+   --sponsored     instruct the transaction generator to create a sponsored transaction.  The origin signs it,
+                   and the output must then be passed to `sponsor` so the sponsor can set the fee and sign it.
+                   The fee-rate argument is ignored, since the sponsor pays the fee.
+   --p2wsh         instruct the multisig methods to use a P2WSH multisig account instead of P2SH (default)
+   --postcondition [post-condition]
+                   add a post-condition to the transaction.  This flag may be given more than once.  A
+                   post-condition is written as `[principal] [asset] [condition-code] [amount-or-value]`:
+                     [principal]       `origin`, a standard principal's address, or `address.contract-name`
+                     [asset]           `stx`, or a fungible or non-fungible asset's `address.contract-name::asset-name`
+                     [condition-code]  `eq`, `gt`, `ge`, `lt`, or `le` for STX and fungible assets,
+                                       and `sent` or `not-sent` for non-fungible assets
+                     [amount-or-value] the amount of STX or of a fungible asset, or a Clarity expression
+                                       for the value of a non-fungible asset
+                   e.g., --postcondition \"origin stx le 1000\"
+   --allow-mode    instruct the transaction generator to use the Allow post-condition mode, so that the
+                   transaction may transfer assets not covered by a post-condition.  By default, it uses
+                   the Deny mode, and the transaction aborts if it transfers any such asset
+
+Wherever a method takes a secret key, it may be given as `keystore:[path]` instead of as hex, to load it
+from a keystore file made by keystore-create.  The keystore's passphrase is read from the
+STACKS_KEYSTORE_PASSPHRASE environment variable if it is set, and from stdin otherwise.
 
 ";
 
@@ -77,11 +131,147 @@ The transfer command generates and signs a STX transfer transaction. If successf
 this command outputs the hex string encoding of the transaction to stdout, and exits with
 code 0";
 
+const SPONSOR_USAGE: &str = "blockstack-cli (options) sponsor [sponsor-secret-key-hex] [fee-rate] [nonce] [origin-signed-tx-hex]
+
+The sponsor command takes a sponsored transaction that has been signed by its origin (i.e. one
+generated with --sponsored), sets the sponsor's fee rate and nonce, and signs it as the sponsor.
+If successful, this command outputs the hex string encoding of the finished transaction to stdout,
+and exits with code 0";
+
+const MULTISIG_ADDRESS_USAGE: &str = "blockstack-cli (options) multisig-address [required-signatures] [public-keys]
+
+The multisig-address command derives the address of a multisig account, where [public-keys] is a
+comma-separated list of hex-encoded public keys, in the order in which they will sign.  If successful,
+this command outputs the address to stdout, and exits with code 0";
+
+const MULTISIG_TX_USAGE: &str = "blockstack-cli (options) multisig-tx [required-signatures] [public-keys] [fee-rate] [nonce] [method] [method-args...]
+
+The multisig-tx command generates an unsigned transaction whose origin is the multisig account
+given by [required-signatures] and [public-keys] (see multisig-address).  [method] is one of
+publish, contract-call, or token-transfer, and [method-args] are the arguments that method takes
+after its nonce.  e.g.,
+
+   blockstack-cli multisig-tx 2 $pubkey_1,$pubkey_2,$pubkey_3 10 0 token-transfer $recipient 1000
+
+If successful, this command outputs the hex string encoding of the unsigned transaction to stdout,
+and exits with code 0.  Pass it to multisig-sign to collect the signatures.
+
+A multisig transaction cannot be broadcast until it has all of its required signatures, so until
+then, its hex encoding records only the signatures it has so far.  This is why multisig-sign and
+multisig-finalize must be given [required-signatures] again.";
+
+const MULTISIG_SIGN_USAGE: &str = "blockstack-cli (options) multisig-sign [signer-secret-key-hex] [required-signatures] [public-keys] [partially-signed-tx-hex]
+
+The multisig-sign command adds one signer's signature to a multisig transaction.  Signers must sign
+in the order in which their public keys appear in [public-keys].  The public keys of any signers who
+were skipped over are appended to the transaction first.  If successful, this command outputs the
+hex string encoding of the partially signed transaction to stdout, and exits with code 0";
+
+const MULTISIG_FINALIZE_USAGE: &str = "blockstack-cli (options) multisig-finalize [required-signatures] [public-keys] [partially-signed-tx-hex]
+
+The multisig-finalize command appends the public keys of the signers who did not sign, and verifies
+the transaction.  It fails if not enough signers have signed.  If successful, this command outputs
+the hex string encoding of the finished transaction to stdout, and exits with code 0";
+
+const UNSIGNED_TX_USAGE: &str = "blockstack-cli (options) unsigned-tx [origin-public-key-hex] [fee-rate] [nonce] [method] [method-args...]
+
+The unsigned-tx command generates an unsigned transaction whose origin is the single-sig account of
+[origin-public-key-hex], so that it can be signed by a key that is not available to this CLI (e.g.
+one in an HSM).  [method] and [method-args] are given as for multisig-tx.  If successful, this command
+outputs the hex string encoding of the unsigned transaction to stdout, and exits with code 0.
+Use sighash and append-signature to sign it.";
+
+const SIGHASH_USAGE: &str = "blockstack-cli (options) sighash [tx-hex] ([required-signatures])
+
+The sighash command outputs the exact hash that the next signer of a transaction must sign, along
+with the auth flag, fee rate and nonce that the hash commits to.  The signer must make a recoverable
+secp256k1 signature over it, which append-signature then adds to the transaction.  If the origin is a
+multisig account that has not finished signing, give its [required-signatures] (see multisig-tx).
+If successful, this command outputs JSON to stdout, and exits with code 0";
+
+const APPEND_SIGNATURE_USAGE: &str = "blockstack-cli (options) append-signature [public-key-hash-hex] [signature-hex] [tx-hex] ([required-signatures] [public-keys])
+
+The append-signature command adds a signature over the hash output by sighash to a transaction.  The
+signer's public key is recovered from the signature, and its Hash160 must be [public-key-hash-hex].
+If a multisig origin is signing, give its [required-signatures] and [public-keys] as for multisig-sign.
+If successful, this command outputs the hex string encoding of the transaction to stdout, and exits
+with code 0";
+
+const SET_SPONSOR_USAGE: &str = "blockstack-cli (options) set-sponsor [sponsor-public-key-hex] [fee-rate] [nonce] [origin-signed-tx-hex]
+
+The set-sponsor command sets the sponsor's fee rate and nonce on a sponsored transaction that has been
+signed by its origin, like sponsor does, but without signing it.  Use sighash and append-signature to
+sign it as the sponsor.  If successful, this command outputs the hex string encoding of the transaction
+to stdout, and exits with code 0";
+
+const SIMULATE_USAGE: &str = "blockstack-cli (options) simulate [chainstate-path] [burn-header-hash] [block-hash] [signed-tx-hex]
+
+This method runs a signed transaction against a node's chain state at [chainstate-path], as if it were
+mined in a block built on the Stacks block [block-hash] (whose burnchain block is [burn-header-hash]).
+Nothing is written to the chain state.  If the transaction could be mined there, this command outputs
+the receipt it would get as JSON: its status and result, whether its post-conditions pass, its fee and
+execution cost, and the assets it would attempt to move (even if its post-conditions would stop them).";
+
+const EXPORT_SNAPSHOT_USAGE: &str = "blockstack-cli (options) export-snapshot [chainstate-path] [chain-id] [burnchain-path] [burn-header-hash] [block-hash] [snapshot-path]
+
+This method exports a snapshot of a node's chain state at [chainstate-path] (for the chain with the
+decimal ID [chain-id]) and burn database at [burnchain-path] to the new directory [snapshot-path], from
+which a new node can start at the Stacks block [block-hash] (whose burnchain block is [burn-header-hash])
+instead of replaying the chain.  The block can be any block the node has processed; the snapshot only
+holds the state of that block, its ancestors, and the burnchain blocks up to [burn-header-hash].  The
+node must not be running.  If successful, this command outputs the snapshot's manifest of root hashes
+as JSON.";
+
+const IMPORT_SNAPSHOT_USAGE: &str = "blockstack-cli (options) import-snapshot [snapshot-path] [chainstate-path] [burnchain-path] [index-block-hash] [consensus-hash]
+
+This method verifies the snapshot at [snapshot-path], and sets up a new node's chain state at
+[chainstate-path] and burn database at [burnchain-path] from it.  Neither may exist yet.  The snapshot
+is not trusted: [index-block-hash] and [consensus-hash] must identify the Stacks block it was taken at
+and the burnchain block that selected it, and come from a source you trust.  The snapshot is only
+imported if it was taken at that block and its state matches.  If successful, this command outputs the
+snapshot's manifest as JSON.";
+
 const GENERATE_USAGE: &str = "blockstack-cli (options) generate-sk
 
 This method generates a secret key, outputting the hex encoding of the
 secret key, the corresponding public key, and the corresponding P2PKH Stacks address.";
 
+const KEYSTORE_CREATE_USAGE: &str = "blockstack-cli (options) keystore-create [path] ([secret-key-hex])
+
+This method encrypts [secret-key-hex] (or a newly generated secret key, if none is given) with a
+passphrase, and writes it to a new keystore file at [path].  The passphrase is read from the
+STACKS_KEYSTORE_PASSPHRASE environment variable if it is set, and from stdin otherwise, where it
+must be entered twice.  The passphrase may not be empty.  If successful, this command outputs the
+public key and the P2PKH Stacks address of the key.";
+
+const KEYSTORE_CREATE_VRF_USAGE: &str = "blockstack-cli (options) keystore-create-vrf [path] ([vrf-secret-key-hex])
+
+This method encrypts the VRF secret key [vrf-secret-key-hex] (or a newly generated one, if none is
+given) into a new keystore file at [path], in the same way as keystore-create.  If successful, this
+command outputs the VRF public key.";
+
+const KEYSTORE_SHOW_USAGE: &str = "blockstack-cli (options) keystore-show [path]
+
+This method decrypts the keystore file at [path], and outputs its secret key and public key, along
+with the P2PKH Stacks address if it holds a secp256k1 key.";
+
+const GENERATE_MNEMONIC_USAGE: &str = "blockstack-cli (options) generate-mnemonic ([num-words])
+
+This method generates a random BIP39 mnemonic phrase of [num-words] words (12, 15, 18, 21 or 24;
+the default is 24), and outputs it along with the first account key derived from it, as derive-keys
+does.  Back up the mnemonic phrase: every key derived from it can be re-derived from it.";
+
+const DERIVE_KEYS_USAGE: &str = "blockstack-cli (options) derive-keys [mnemonic] ([account] [index] [passphrase])
+
+This method derives keys from a BIP39 mnemonic phrase (given as one quoted argument) and optional
+passphrase.  It outputs the [index]th account key of [account] (both default to 0), along with the
+corresponding public key and P2PKH Stacks address, and the [index]th microblock-signing and VRF keys
+of the account.  The keys are derived with BIP32 along these paths:
+
+   account keys:          m/44'/5757'/[account]'/0/[index]
+   microblock keys:       m/44'/5757'/[account]'/1/[index]
+   VRF keys:              m/44'/5757'/[account]'/2/[index]";
+
 
 #[derive(Debug)]
 enum CliError {
@@ -130,12 +320,30 @@ impl From<ClarityError> for CliError {
     }
 }
 
+impl From<ChainstateError> for CliError {
+    fn from(value: ChainstateError) -> Self {
+        CliError::Message(format!("Stacks chainstate error: {}", value))
+    }
+}
+
 impl From<NetError> for CliError {
     fn from(value: NetError) -> Self {
         CliError::Message(format!("Stacks NetError: {}", value))
     }
 }
 
+impl From<KeystoreError> for CliError {
+    fn from(value: KeystoreError) -> Self {
+        CliError::Message(format!("Keystore error: {}", value))
+    }
+}
+
+impl From<HDKeysError> for CliError {
+    fn from(value: HDKeysError) -> Self {
+        CliError::Message(format!("Key derivation error: {}", value))
+    }
+}
+
 impl From<std::num::ParseIntError> for CliError {
     fn from(value: std::num::ParseIntError) -> Self {
         CliError::Message(format!("Failed to parse integer: {}", value))
@@ -189,6 +397,35 @@ fn make_standard_single_sig_tx(version: TransactionVersion, payload: Transaction
     StacksTransaction::new(version, auth, payload)
 }
 
+/// Post-conditions given as flags, which apply to every transaction this CLI makes.  Transactions
+/// use the Deny post-condition mode unless `--allow-mode` is given.
+struct PostConditionOptions {
+    allow_mode: bool,
+    post_conditions: Vec<TransactionPostCondition>
+}
+
+impl PostConditionOptions {
+    fn apply(&self, tx: &mut StacksTransaction) {
+        if self.allow_mode {
+            tx.set_post_condition_mode(TransactionPostConditionMode::Allow);
+        }
+        for post_condition in self.post_conditions.iter() {
+            tx.add_post_condition(post_condition.clone());
+        }
+    }
+}
+
+/// The origin of a sponsored transaction does not know who the sponsor is, so it commits to the
+/// sentinel sponsor spending condition.  The sponsor fills in its own when it signs.
+fn make_sponsored_single_sig_tx(version: TransactionVersion, payload: TransactionPayload,
+                                publicKey: &StacksPublicKey, nonce: u64) -> StacksTransaction {
+    let mut spending_condition = TransactionSpendingCondition::new_singlesig_p2pkh(publicKey.clone())
+        .expect("Failed to create p2pkh spending condition from public key.");
+    spending_condition.set_nonce(nonce);
+    let auth = TransactionAuth::Sponsored(spending_condition, TransactionSpendingCondition::new_initial_sighash());
+    StacksTransaction::new(version, auth, payload)
+}
+
 fn sign_transaction_single_sig_standard(transaction: &str, secret_key: &StacksPrivateKey) -> Result<StacksTransaction, CliError> {
     let transaction = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(transaction)?))?;
 
@@ -199,57 +436,273 @@ fn sign_transaction_single_sig_standard(transaction: &str, secret_key: &StacksPr
        .ok_or("TX did not finish signing -- was this a standard single signature transaction?")?)
 }
 
-fn handle_contract_publish(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
-    if args.len() >= 1 && args[0] == "-h" {
-        return Err(CliError::Message(format!("USAGE:\n {}", PUBLISH_USAGE)))
+/// Sign a sponsored transaction as its origin.  The result is not complete until the sponsor signs it.
+fn sign_transaction_single_sig_sponsored_origin(transaction: &str, secret_key: &StacksPrivateKey) -> Result<StacksTransaction, CliError> {
+    let transaction = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(transaction)?))?;
+    if !transaction.auth.is_sponsored() {
+        return Err("TX is not a sponsored transaction".into());
     }
-    if args.len() != 5 {
-        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", PUBLISH_USAGE)))
+
+    let mut tx_signer = StacksTransactionSigner::new(&transaction);
+    tx_signer.sign_origin(secret_key)?;
+
+    Ok(tx_signer.get_tx_incomplete())
+}
+
+/// Sign an origin-signed sponsored transaction as its sponsor, setting the sponsor's nonce and fee rate.
+fn sign_transaction_single_sig_sponsor(transaction: &str, secret_key: &StacksPrivateKey, nonce: u64, fee_rate: u64) -> Result<StacksTransaction, CliError> {
+    let transaction = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(transaction)?))?;
+    if !transaction.auth.is_sponsored() {
+        return Err("TX is not a sponsored transaction".into());
     }
-    let sk_publisher = &args[0];
-    let fee_rate = args[1].parse()?;
-    let nonce = args[2].parse()?;
-    let contract_name = &args[3];
-    let contract_file = &args[4];
 
-    let contract_contents = if contract_file == "-" {
-        let mut buffer = String::new();
-        io::stdin().read_to_string(&mut buffer)?;
-        buffer
-    } else {
-        fs::read_to_string(contract_file)?
-    };
+    let mut sponsor_condition = TransactionSpendingCondition::new_singlesig_p2pkh(StacksPublicKey::from_private(secret_key))
+        .expect("Failed to create p2pkh spending condition from public key.");
+    sponsor_condition.set_nonce(nonce);
+    sponsor_condition.set_fee_rate(fee_rate);
+
+    let mut tx_signer = StacksTransactionSigner::new_sponsor(&transaction, sponsor_condition)?;
+    tx_signer.sign_sponsor(secret_key)?;
+
+    Ok(tx_signer.get_tx()
+       .ok_or("TX did not finish signing -- has the origin signed this transaction?")?)
+}
 
-    let sk_publisher = StacksPrivateKey::from_hex(sk_publisher)?;
+/// Make and sign a single-sig transaction, and return its hex encoding.  If it is sponsored, only
+/// the origin signs it.
+fn make_signed_single_sig_tx_hex(version: TransactionVersion, payload: TransactionPayload, secret_key: &StacksPrivateKey,
+                                 nonce: u64, fee_rate: u64, sponsored: bool, post_conditions: &PostConditionOptions) -> Result<String, CliError> {
+    let public_key = StacksPublicKey::from_private(secret_key);
+    let mut unsigned_tx =
+        if sponsored {
+            make_sponsored_single_sig_tx(version, payload, &public_key, nonce)
+        }
+        else {
+            make_standard_single_sig_tx(version, payload, &public_key, nonce, fee_rate)
+        };
+    post_conditions.apply(&mut unsigned_tx);
 
-    let payload = make_contract_publish(contract_name.clone(), contract_contents)?;
-    let unsigned_tx = make_standard_single_sig_tx(version, payload.into(), &StacksPublicKey::from_private(&sk_publisher),
-                                                  nonce, fee_rate);
     let mut unsigned_tx_bytes = vec![];
     unsigned_tx.consensus_serialize(&mut unsigned_tx_bytes).expect("FATAL: invalid transaction");
-    let signed_tx = sign_transaction_single_sig_standard(
-        &to_hex(&unsigned_tx_bytes), &sk_publisher)?;
+    let signed_tx =
+        if sponsored {
+            sign_transaction_single_sig_sponsored_origin(&to_hex(&unsigned_tx_bytes), secret_key)?
+        }
+        else {
+            sign_transaction_single_sig_standard(&to_hex(&unsigned_tx_bytes), secret_key)?
+        };
 
     let mut signed_tx_bytes = vec![];
     signed_tx.consensus_serialize(&mut signed_tx_bytes).expect("FATAL: invalid signed transaction");
     Ok(to_hex(&signed_tx_bytes))
 }
 
-fn handle_contract_call(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
-    if args.len() >= 1 && args[0] == "-h" {
-        return Err(CliError::Message(format!("USAGE:\n {}", CALL_USAGE)))
+fn parse_public_keys(public_keys: &str) -> Result<Vec<StacksPublicKey>, CliError> {
+    let mut pubkeys = vec![];
+    for pubkey_hex in public_keys.split(',') {
+        pubkeys.push(StacksPublicKey::from_hex(pubkey_hex)?);
     }
-    if args.len() < 6 {
+    Ok(pubkeys)
+}
+
+fn multisig_hash_mode(p2wsh: bool) -> MultisigHashMode {
+    if p2wsh { MultisigHashMode::P2WSH } else { MultisigHashMode::P2SH }
+}
+
+fn make_multisig_address(version: TransactionVersion, num_sigs: u16, pubkeys: &Vec<StacksPublicKey>, p2wsh: bool) -> Result<StacksAddress, CliError> {
+    let version = match version {
+        TransactionVersion::Mainnet => C32_ADDRESS_VERSION_MAINNET_MULTISIG,
+        TransactionVersion::Testnet => C32_ADDRESS_VERSION_TESTNET_MULTISIG,
+    };
+    if num_sigs == 0 || (num_sigs as usize) > pubkeys.len() {
+        return Err(CliError::Message(format!("Cannot require {} signatures from {} public keys", num_sigs, pubkeys.len())));
+    }
+    Ok(StacksAddress::from_public_keys(version, &multisig_hash_mode(p2wsh).to_address_hash_mode(), num_sigs as usize, pubkeys)
+       .ok_or("Failed to generate multisig address from public keys (P2WSH requires compressed public keys)")?)
+}
+
+fn make_unsigned_multisig_tx(version: TransactionVersion, payload: TransactionPayload, num_sigs: u16, pubkeys: Vec<StacksPublicKey>,
+                             p2wsh: bool, nonce: u64, fee_rate: u64, sponsored: bool, post_conditions: &PostConditionOptions) -> Result<StacksTransaction, CliError> {
+    make_multisig_address(version, num_sigs, &pubkeys, p2wsh)?;
+    let mut spending_condition =
+        if p2wsh {
+            TransactionSpendingCondition::new_multisig_p2wsh(num_sigs, pubkeys)
+        }
+        else {
+            TransactionSpendingCondition::new_multisig_p2sh(num_sigs, pubkeys)
+        }
+        .ok_or("Failed to create multisig spending condition from public keys")?;
+
+    spending_condition.set_nonce(nonce);
+    let auth =
+        if sponsored {
+            TransactionAuth::Sponsored(spending_condition, TransactionSpendingCondition::new_initial_sighash())
+        }
+        else {
+            spending_condition.set_fee_rate(fee_rate);
+            TransactionAuth::Standard(spending_condition)
+        };
+    let mut unsigned_tx = StacksTransaction::new(version, auth, payload);
+    post_conditions.apply(&mut unsigned_tx);
+    Ok(unsigned_tx)
+}
+
+fn multisig_origin_mut(tx: &mut StacksTransaction) -> Result<&mut MultisigSpendingCondition, CliError> {
+    let origin = match tx.auth {
+        TransactionAuth::Standard(ref mut origin) => origin,
+        TransactionAuth::Sponsored(ref mut origin, _) => origin
+    };
+    match origin {
+        TransactionSpendingCondition::Multisig(ref mut data) => Ok(data),
+        _ => Err("TX origin is not a multisig spending condition".into())
+    }
+}
+
+/// A multisig spending condition only decodes if it has exactly as many signatures as it
+/// requires.  So, a partially signed multisig transaction is encoded as if it required only the
+/// signatures it has so far, and the real number is restored when it is decoded.
+fn serialize_partial_multisig_tx(tx: &StacksTransaction) -> Result<String, CliError> {
+    let mut tx = tx.clone();
+    {
+        let origin = multisig_origin_mut(&mut tx)?;
+        origin.signatures_required = TransactionSpendingCondition::Multisig(origin.clone()).num_signatures();
+    }
+
+    let mut tx_bytes = vec![];
+    tx.consensus_serialize(&mut tx_bytes).expect("FATAL: invalid transaction");
+    Ok(to_hex(&tx_bytes))
+}
+
+fn deserialize_partial_multisig_tx(transaction: &str, num_sigs: u16) -> Result<StacksTransaction, CliError> {
+    let mut tx = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(transaction)?))?;
+    {
+        let origin = multisig_origin_mut(&mut tx)?;
+        if origin.signatures_required > num_sigs {
+            return Err(CliError::Message(format!("TX has {} signatures, but only {} are required", origin.signatures_required, num_sigs)));
+        }
+        origin.signatures_required = num_sigs;
+    }
+    Ok(tx)
+}
+
+/// Replay the auth fields the multisig origin has so far, checking that they follow the order
+/// of `pubkeys`.  Returns the sighash the next signer must sign, and how many of `pubkeys` have
+/// been used.
+fn get_multisig_origin_progress(tx: &StacksTransaction, pubkeys: &Vec<StacksPublicKey>) -> Result<(Txid, usize), CliError> {
+    let origin = match tx.auth.origin() {
+        TransactionSpendingCondition::Multisig(ref data) => data.clone(),
+        _ => {
+            return Err("TX origin is not a multisig spending condition".into());
+        }
+    };
+
+    let addr_bytes = StacksAddress::from_public_keys(0, &origin.hash_mode.to_address_hash_mode(), origin.signatures_required as usize, pubkeys)
+        .ok_or("Failed to generate multisig address from public keys")?
+        .bytes;
+    if addr_bytes != origin.signer {
+        return Err("Public keys do not match the TX's multisig origin (are they in the right order?)".into());
+    }
+    if origin.fields.len() > pubkeys.len() {
+        return Err("TX has more auth fields than public keys".into());
+    }
+
+    let mut sighash = {
+        let mut initial_tx = tx.clone();
+        initial_tx.auth = initial_tx.auth.into_initial_sighash_auth();
+        initial_tx.txid()
+    };
+
+    for (field, expected_pubkey) in origin.fields.iter().zip(pubkeys.iter()) {
+        let pubkey = match field {
+            TransactionAuthField::PublicKey(ref pubkey) => pubkey.clone(),
+            TransactionAuthField::Signature(ref key_encoding, ref sig) => {
+                let (pubkey, next_sighash) = TransactionSpendingCondition::next_verification(
+                    &sighash, &TransactionAuthFlags::AuthStandard, origin.fee_rate, origin.nonce, key_encoding, sig)?;
+                sighash = next_sighash;
+                pubkey
+            }
+        };
+        if pubkey != *expected_pubkey {
+            return Err(CliError::Message(format!("TX auth field for public key {} was made by {}", expected_pubkey.to_hex(), pubkey.to_hex())));
+        }
+    }
+
+    Ok((sighash, origin.fields.len()))
+}
+
+fn sign_transaction_multisig(transaction: &str, secret_key: &StacksPrivateKey, num_sigs: u16, pubkeys: &Vec<StacksPublicKey>) -> Result<StacksTransaction, CliError> {
+    let mut transaction = deserialize_partial_multisig_tx(transaction, num_sigs)?;
+    let (sighash, num_used) = get_multisig_origin_progress(&transaction, pubkeys)?;
+
+    let origin = transaction.auth.origin();
+    if origin.num_signatures() >= origin.signatures_required() {
+        return Err("TX already has enough signatures".into());
+    }
+
+    let signer_pubkey = StacksPublicKey::from_private(secret_key);
+    let signer_index = pubkeys.iter().position(|pubkey| *pubkey == signer_pubkey)
+        .ok_or("Secret key does not belong to any of the public keys")?;
+    if signer_index < num_used {
+        return Err("This signer has already signed or been skipped over".into());
+    }
+
+    for pubkey in pubkeys[num_used..signer_index].iter() {
+        transaction.append_next_origin(pubkey)?;
+    }
+    transaction.sign_next_origin(&sighash, secret_key)?;
+    Ok(transaction)
+}
+
+fn finalize_transaction_multisig(transaction: &str, num_sigs: u16, pubkeys: &Vec<StacksPublicKey>) -> Result<StacksTransaction, CliError> {
+    let mut transaction = deserialize_partial_multisig_tx(transaction, num_sigs)?;
+    let (_, num_used) = get_multisig_origin_progress(&transaction, pubkeys)?;
+
+    let origin = transaction.auth.origin();
+    if origin.num_signatures() < origin.signatures_required() {
+        return Err(CliError::Message(format!("TX has {} of {} required signatures", origin.num_signatures(), origin.signatures_required())));
+    }
+
+    for pubkey in pubkeys[num_used..].iter() {
+        transaction.append_next_origin(pubkey)?;
+    }
+
+    // a sponsored transaction is finished by its sponsor
+    if transaction.auth.is_sponsored() {
+        transaction.verify_origin()?;
+    }
+    else if !transaction.verify()? {
+        return Err("TX failed to verify".into());
+    }
+    Ok(transaction)
+}
+
+fn parse_contract_publish_payload(payload_args: &[String]) -> Result<TransactionPayload, CliError> {
+    if payload_args.len() != 2 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", PUBLISH_USAGE)))
+    }
+    let contract_name = &payload_args[0];
+    let contract_file = &payload_args[1];
+
+    let contract_contents = if contract_file == "-" {
+        let mut buffer = String::new();
+        io::stdin().read_to_string(&mut buffer)?;
+        buffer
+    } else {
+        fs::read_to_string(contract_file)?
+    };
+
+    Ok(make_contract_publish(contract_name.clone(), contract_contents)?.into())
+}
+
+fn parse_contract_call_payload(payload_args: &[String]) -> Result<TransactionPayload, CliError> {
+    if payload_args.len() < 3 {
         return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", CALL_USAGE)))
     }
-    let sk_origin = &args[0];
-    let fee_rate = args[1].parse()?;
-    let nonce = args[2].parse()?;
-    let contract_address = &args[3];
-    let contract_name = &args[4];
-    let function_name = &args[5];
+    let contract_address = &payload_args[0];
+    let contract_name = &payload_args[1];
+    let function_name = &payload_args[2];
 
-    let val_args = &args[6..];
+    let val_args = &payload_args[3..];
 
     if val_args.len() % 2 != 0 {
         return Err("contract-call arguments must be supplied as a list of `-e ...` or `-x 0000...` pairs".into())
@@ -277,49 +730,84 @@ fn handle_contract_call(args: &[String], version: TransactionVersion) -> Result<
         arg_iterator += 2;
     }
 
-    let sk_origin = StacksPrivateKey::from_hex(sk_origin)?;
+    Ok(make_contract_call(contract_address.clone(), contract_name.clone(), function_name.clone(), values)?.into())
+}
 
-    let payload = make_contract_call(contract_address.clone(), contract_name.clone(), function_name.clone(), values)?;
-    let unsigned_tx = make_standard_single_sig_tx(version, payload.into(), &StacksPublicKey::from_private(&sk_origin),
-                                                  nonce, fee_rate);
-    
-    let mut unsigned_tx_bytes = vec![];
-    unsigned_tx.consensus_serialize(&mut unsigned_tx_bytes).expect("FATAL: invalid transaction");
-    let signed_tx = sign_transaction_single_sig_standard(
-        &to_hex(&unsigned_tx_bytes), &sk_origin)?;
+fn parse_token_transfer_payload(payload_args: &[String]) -> Result<TransactionPayload, CliError> {
+    if payload_args.len() < 2 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", TOKEN_TRANSFER_USAGE)))
+    }
+    let recipient_address = StacksAddress::from_string(&payload_args[0]).ok_or("Failed to parse contract address")?;
+    let amount = &payload_args[1].parse()?;
+    let memo = {
+        let mut memo = [0; 34];
+        let mut bytes = if payload_args.len() == 3 { payload_args[2].as_bytes().to_vec() } else { vec![] };
+        bytes.resize(34, 0);
+        memo.copy_from_slice(&bytes);
+        TokenTransferMemo(memo)
+    };
 
-    let mut signed_tx_bytes = vec![];
-    signed_tx.consensus_serialize(&mut signed_tx_bytes).expect("FATAL: invalid signed transaction");
-    Ok(to_hex(&signed_tx_bytes))
+    Ok(TransactionPayload::TokenTransfer(recipient_address, *amount, memo))
+}
+
+fn handle_contract_publish(args: &[String], version: TransactionVersion, sponsored: bool, post_conditions: &PostConditionOptions) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", PUBLISH_USAGE)))
+    }
+    if args.len() != 5 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", PUBLISH_USAGE)))
+    }
+    let sk_publisher = parse_secret_key(&args[0])?;
+    let fee_rate = args[1].parse()?;
+    let nonce = args[2].parse()?;
+
+    let payload = parse_contract_publish_payload(&args[3..])?;
+    make_signed_single_sig_tx_hex(version, payload, &sk_publisher, nonce, fee_rate, sponsored, post_conditions)
+}
+
+fn handle_contract_call(args: &[String], version: TransactionVersion, sponsored: bool, post_conditions: &PostConditionOptions) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", CALL_USAGE)))
+    }
+    if args.len() < 6 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", CALL_USAGE)))
+    }
+    let sk_origin = parse_secret_key(&args[0])?;
+    let fee_rate = args[1].parse()?;
+    let nonce = args[2].parse()?;
+
+    let payload = parse_contract_call_payload(&args[3..])?;
+    make_signed_single_sig_tx_hex(version, payload, &sk_origin, nonce, fee_rate, sponsored, post_conditions)
 }
 
-fn handle_token_transfer(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
+fn handle_token_transfer(args: &[String], version: TransactionVersion, sponsored: bool, post_conditions: &PostConditionOptions) -> Result<String, CliError> {
     if args.len() >= 1 && args[0] == "-h" {
         return Err(CliError::Message(format!("USAGE:\n {}", TOKEN_TRANSFER_USAGE)))
     }
     if args.len() < 5 {
         return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", CALL_USAGE)))
     }
-    let sk_origin = StacksPrivateKey::from_hex(&args[0])?;
+    let sk_origin = parse_secret_key(&args[0])?;
     let fee_rate = args[1].parse()?;
     let nonce = args[2].parse()?;
-    let recipient_address = StacksAddress::from_string(&args[3]).ok_or("Failed to parse contract address")?;
-    let amount = &args[4].parse()?;
-    let memo = {
-        let mut memo = [0; 34];
-        let mut bytes = if args.len() == 6 { args[5].as_bytes().to_vec() } else { vec![] };
-        bytes.resize(34, 0);
-        memo.copy_from_slice(&bytes);
-        TokenTransferMemo(memo)
-    };
 
-    let payload = TransactionPayload::TokenTransfer(recipient_address, *amount, memo);
-    let unsigned_tx = make_standard_single_sig_tx(version, payload, &StacksPublicKey::from_private(&sk_origin),
-                                                  nonce, fee_rate);
-    let mut unsigned_tx_bytes = vec![];
-    unsigned_tx.consensus_serialize(&mut unsigned_tx_bytes).expect("FATAL: invalid transaction");
-    let signed_tx = sign_transaction_single_sig_standard(
-        &to_hex(&unsigned_tx_bytes), &sk_origin)?;
+    let payload = parse_token_transfer_payload(&args[3..])?;
+    make_signed_single_sig_tx_hex(version, payload, &sk_origin, nonce, fee_rate, sponsored, post_conditions)
+}
+
+fn handle_sponsor(args: &[String]) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", SPONSOR_USAGE)))
+    }
+    if args.len() != 4 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", SPONSOR_USAGE)))
+    }
+    let sk_sponsor = parse_secret_key(&args[0])?;
+    let fee_rate = args[1].parse()?;
+    let nonce = args[2].parse()?;
+    let origin_signed_tx = &args[3];
+
+    let signed_tx = sign_transaction_single_sig_sponsor(origin_signed_tx, &sk_sponsor, nonce, fee_rate)?;
 
     let mut signed_tx_bytes = vec![];
     signed_tx.consensus_serialize(&mut signed_tx_bytes).expect("FATAL: invalid signed transaction");
@@ -327,62 +815,646 @@ fn handle_token_transfer(args: &[String], version: TransactionVersion) -> Result
 }
 
 
-fn generate_secret_key(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
+fn handle_multisig_address(args: &[String], version: TransactionVersion, p2wsh: bool) -> Result<String, CliError> {
     if args.len() >= 1 && args[0] == "-h" {
-        return Err(CliError::Message(format!("USAGE:\n {}", GENERATE_USAGE)))
+        return Err(CliError::Message(format!("USAGE:\n {}", MULTISIG_ADDRESS_USAGE)))
     }
+    if args.len() != 2 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", MULTISIG_ADDRESS_USAGE)))
+    }
+    let num_sigs = args[0].parse()?;
+    let pubkeys = parse_public_keys(&args[1])?;
 
-    let sk = StacksPrivateKey::new();
-    let pk = StacksPublicKey::from_private(&sk);
-    let version = match version {
-        TransactionVersion::Mainnet => C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
-        TransactionVersion::Testnet => C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
-    };
-
-    let address = StacksAddress::from_public_keys(
-        version, &AddressHashMode::SerializeP2PKH, 1, &vec![pk.clone()])
-        .expect("Failed to generate address from public key");
-    Ok(format!("{{ 
-  secretKey: \"{}\",
-  publicKey: \"{}\",
-  stacksAddress: \"{}\"
-}}",
-             sk.to_hex(),
-             pk.to_hex(),
-             address.to_string()))
+    Ok(make_multisig_address(version, num_sigs, &pubkeys, p2wsh)?.to_string())
 }
 
-fn main() {
-    log::set_loglevel(log::LOG_DEBUG).unwrap();
-    let mut argv : Vec<String> = env::args().collect();
-
-    argv.remove(0);
-
-    match main_handler(argv) {
-        Ok(s) => {
-            println!("{}", s);
-        },
-        Err(e) => {
-            eprintln!("{}", e);
-            std::process::exit(1);
+fn parse_method_payload(method: &str, payload_args: &[String], usage: &str) -> Result<TransactionPayload, CliError> {
+    match method {
+        "publish" => parse_contract_publish_payload(payload_args),
+        "contract-call" => parse_contract_call_payload(payload_args),
+        "token-transfer" => parse_token_transfer_payload(payload_args),
+        _ => {
+            Err(CliError::Message(format!("Unsupported method {} \n\nUSAGE:\n {}", method, usage)))
         }
     }
 }
 
-fn main_handler(mut argv: Vec<String>) -> Result<String, CliError> {
-    let tx_version = if let Some(ix) = argv.iter().position(|x| x == "--testnet") {
+fn handle_multisig_tx(args: &[String], version: TransactionVersion, sponsored: bool, p2wsh: bool, post_conditions: &PostConditionOptions) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", MULTISIG_TX_USAGE)))
+    }
+    if args.len() < 5 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", MULTISIG_TX_USAGE)))
+    }
+    let num_sigs = args[0].parse()?;
+    let pubkeys = parse_public_keys(&args[1])?;
+    let fee_rate = args[2].parse()?;
+    let nonce = args[3].parse()?;
+
+    let payload = parse_method_payload(&args[4], &args[5..], MULTISIG_TX_USAGE)?;
+
+    let unsigned_tx = make_unsigned_multisig_tx(version, payload, num_sigs, pubkeys, p2wsh, nonce, fee_rate, sponsored, post_conditions)?;
+    serialize_partial_multisig_tx(&unsigned_tx)
+}
+
+fn handle_multisig_sign(args: &[String]) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", MULTISIG_SIGN_USAGE)))
+    }
+    if args.len() != 4 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", MULTISIG_SIGN_USAGE)))
+    }
+    let sk_signer = parse_secret_key(&args[0])?;
+    let num_sigs = args[1].parse()?;
+    let pubkeys = parse_public_keys(&args[2])?;
+
+    let signed_tx = sign_transaction_multisig(&args[3], &sk_signer, num_sigs, &pubkeys)?;
+    serialize_partial_multisig_tx(&signed_tx)
+}
+
+fn handle_multisig_finalize(args: &[String]) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", MULTISIG_FINALIZE_USAGE)))
+    }
+    if args.len() != 3 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", MULTISIG_FINALIZE_USAGE)))
+    }
+    let num_sigs = args[0].parse()?;
+    let pubkeys = parse_public_keys(&args[1])?;
+
+    let signed_tx = finalize_transaction_multisig(&args[2], num_sigs, &pubkeys)?;
+
+    let mut signed_tx_bytes = vec![];
+    signed_tx.consensus_serialize(&mut signed_tx_bytes).expect("FATAL: invalid signed transaction");
+    Ok(to_hex(&signed_tx_bytes))
+}
+
+fn handle_unsigned_tx(args: &[String], version: TransactionVersion, sponsored: bool, post_conditions: &PostConditionOptions) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", UNSIGNED_TX_USAGE)))
+    }
+    if args.len() < 4 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", UNSIGNED_TX_USAGE)))
+    }
+    let pk_origin = StacksPublicKey::from_hex(&args[0])?;
+    let fee_rate = args[1].parse()?;
+    let nonce = args[2].parse()?;
+
+    let payload = parse_method_payload(&args[3], &args[4..], UNSIGNED_TX_USAGE)?;
+    let mut unsigned_tx =
+        if sponsored {
+            make_sponsored_single_sig_tx(version, payload, &pk_origin, nonce)
+        }
+        else {
+            make_standard_single_sig_tx(version, payload, &pk_origin, nonce, fee_rate)
+        };
+    post_conditions.apply(&mut unsigned_tx);
+
+    let mut unsigned_tx_bytes = vec![];
+    unsigned_tx.consensus_serialize(&mut unsigned_tx_bytes).expect("FATAL: invalid transaction");
+    Ok(to_hex(&unsigned_tx_bytes))
+}
+
+/// Decode a transaction that may have a partially signed multisig origin, if its
+/// required signatures are given
+fn deserialize_offline_tx(transaction: &str, required_sigs: Option<&String>) -> Result<StacksTransaction, CliError> {
+    match required_sigs {
+        Some(num_sigs) => deserialize_partial_multisig_tx(transaction, num_sigs.parse()?),
+        None => Ok(StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(transaction)?))?)
+    }
+}
+
+fn serialize_offline_tx(tx: &StacksTransaction) -> Result<String, CliError> {
+    match tx.auth.origin() {
+        TransactionSpendingCondition::Multisig(_) => serialize_partial_multisig_tx(tx),
+        TransactionSpendingCondition::Singlesig(_) => {
+            let mut tx_bytes = vec![];
+            tx.consensus_serialize(&mut tx_bytes).expect("FATAL: invalid transaction");
+            Ok(to_hex(&tx_bytes))
+        }
+    }
+}
+
+fn handle_sighash(args: &[String]) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", SIGHASH_USAGE)))
+    }
+    if args.len() != 1 && args.len() != 2 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", SIGHASH_USAGE)))
+    }
+    let transaction = deserialize_offline_tx(&args[0], args.get(1))?;
+    let tx_signer = StacksTransactionSigner::new_partial(&transaction)?;
+
+    let (signer, condition, auth_flag) =
+        if tx_signer.is_sponsor_next() {
+            let sponsor = transaction.auth.sponsor().expect("FATAL: sponsor is signing a standard transaction");
+            ("sponsor", sponsor, TransactionAuthFlags::AuthSponsored)
+        }
+        else {
+            ("origin", transaction.auth.origin(), TransactionAuthFlags::AuthStandard)
+        };
+
+    if condition.num_signatures() >= condition.signatures_required() {
+        if transaction.auth.is_sponsored() && !tx_signer.is_sponsor_next() {
+            return Err("TX origin has finished signing -- set its sponsor with set-sponsor or sponsor".into());
+        }
+        return Err("TX is already signed".into());
+    }
+
+    Ok(format!("{{
+  \"signer\": \"{}\",
+  \"sighash\": \"{}\",
+  \"authFlag\": {},
+  \"feeRate\": {},
+  \"nonce\": {}
+}}",
+               signer,
+               to_hex(tx_signer.get_presign_sighash().as_bytes()),
+               auth_flag as u8,
+               condition.fee_rate(),
+               condition.nonce()))
+}
+
+fn handle_append_signature(args: &[String]) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", APPEND_SIGNATURE_USAGE)))
+    }
+    if args.len() != 3 && args.len() != 5 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", APPEND_SIGNATURE_USAGE)))
+    }
+    let pubkey_hash = Hash160::from_hex(&args[0])?;
+    let signature = MessageSignature::from_hex(&args[1])?;
+    let transaction = deserialize_offline_tx(&args[2], args.get(3))?;
+
+    let mut tx_signer = StacksTransactionSigner::new_partial(&transaction)?;
+    if tx_signer.is_sponsor_next() {
+        tx_signer.append_sponsor_signature(&signature, &pubkey_hash)?;
+    }
+    else {
+        if let TransactionSpendingCondition::Multisig(_) = transaction.auth.origin() {
+            let pubkeys = parse_public_keys(args.get(4).ok_or("Multisig origins must be given their required signatures and public keys")?)?;
+            let (_, num_used) = get_multisig_origin_progress(&transaction, &pubkeys)?;
+            let signer_index = pubkeys.iter().position(|pubkey| Hash160::from_data(&pubkey.to_bytes()) == pubkey_hash)
+                .ok_or("Public key hash does not belong to any of the public keys")?;
+            if signer_index < num_used {
+                return Err("This signer has already signed or been skipped over".into());
+            }
+            for pubkey in pubkeys[num_used..signer_index].iter() {
+                tx_signer.append_origin(pubkey)?;
+            }
+        }
+        tx_signer.append_origin_signature(&signature, &pubkey_hash)?;
+    }
+
+    serialize_offline_tx(&tx_signer.get_tx_incomplete())
+}
+
+fn handle_set_sponsor(args: &[String]) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", SET_SPONSOR_USAGE)))
+    }
+    if args.len() != 4 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", SET_SPONSOR_USAGE)))
+    }
+    let pk_sponsor = StacksPublicKey::from_hex(&args[0])?;
+    let fee_rate = args[1].parse()?;
+    let nonce = args[2].parse()?;
+    let transaction = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(&args[3])?))?;
+    if !transaction.auth.is_sponsored() {
+        return Err("TX is not a sponsored transaction".into());
+    }
+
+    let mut sponsor_condition = TransactionSpendingCondition::new_singlesig_p2pkh(pk_sponsor)
+        .expect("Failed to create p2pkh spending condition from public key.");
+    sponsor_condition.set_nonce(nonce);
+    sponsor_condition.set_fee_rate(fee_rate);
+
+    let tx_signer = StacksTransactionSigner::new_sponsor(&transaction, sponsor_condition)?;
+    serialize_offline_tx(&tx_signer.get_tx_incomplete())
+}
+
+fn simulate(args: &[String]) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", SIMULATE_USAGE)))
+    }
+    if args.len() != 4 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", SIMULATE_USAGE)))
+    }
+    let chainstate_path = &args[0];
+    let burn_header_hash = BurnchainHeaderHash::from_hex(&args[1])?;
+    let block_hash = BlockHeaderHash::from_hex(&args[2])?;
+    let transaction = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(&args[3])?))?;
+
+    // opening the chain state would create it if it didn't exist
+    let chain_dir = Path::new(chainstate_path).join(StacksChainState::chain_dir_name(transaction.is_mainnet(), transaction.chain_id));
+    if !chain_dir.exists() {
+        return Err(CliError::Message(format!("No chain state for chain ID {} at {}", transaction.chain_id, chainstate_path)));
+    }
+
+    let mut chainstate = StacksChainState::open(transaction.is_mainnet(), transaction.chain_id, chainstate_path)?;
+    let simulation = chainstate.simulate_transaction(&burn_header_hash, &block_hash, &transaction)?;
+    serde_json::to_string_pretty(&simulation.to_json())
+        .map_err(|e| CliError::Message(format!("Failed to encode simulation as JSON: {}", e)))
+}
+
+fn export_snapshot(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", EXPORT_SNAPSHOT_USAGE)))
+    }
+    if args.len() != 6 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", EXPORT_SNAPSHOT_USAGE)))
+    }
+    let chainstate_path = &args[0];
+    let chain_id = args[1].parse()?;
+    let burnchain_path = &args[2];
+    let burn_header_hash = BurnchainHeaderHash::from_hex(&args[3])?;
+    let block_hash = BlockHeaderHash::from_hex(&args[4])?;
+    let snapshot_path = &args[5];
+    let mainnet = version == TransactionVersion::Mainnet;
+
+    // opening the chain state would create it if it didn't exist
+    let chain_dir = Path::new(chainstate_path).join(StacksChainState::chain_dir_name(mainnet, chain_id));
+    if !chain_dir.exists() {
+        return Err(CliError::Message(format!("No chain state for chain ID {} at {}", chain_id, chainstate_path)));
+    }
+    if !Path::new(burnchain_path).exists() {
+        return Err(CliError::Message(format!("No burn database at {}", burnchain_path)));
+    }
+
+    let mut chainstate = StacksChainState::open(mainnet, chain_id, chainstate_path)?;
+    let manifest = chainstate.export_snapshot(burnchain_path, &burn_header_hash, &block_hash, snapshot_path)?;
+    Ok(manifest.to_json())
+}
+
+fn import_snapshot(args: &[String]) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", IMPORT_SNAPSHOT_USAGE)))
+    }
+    if args.len() != 5 {
+        return Err(CliError::Message(format!("Incorrect argument count supplied \n\nUSAGE:\n {}", IMPORT_SNAPSHOT_USAGE)))
+    }
+    let trusted_index_block_hash = BlockHeaderHash::from_hex(&args[3])?;
+    let trusted_consensus_hash = ConsensusHash::from_hex(&args[4])?;
+
+    let (_chainstate, manifest) = StacksChainState::import_snapshot(&args[0], &args[1], &args[2], &trusted_index_block_hash, &trusted_consensus_hash)?;
+    Ok(manifest.to_json())
+}
+
+fn generate_secret_key(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", GENERATE_USAGE)))
+    }
+
+    let sk = StacksPrivateKey::new();
+    let pk = StacksPublicKey::from_private(&sk);
+    let version = match version {
+        TransactionVersion::Mainnet => C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+        TransactionVersion::Testnet => C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+    };
+
+    let address = StacksAddress::from_public_keys(
+        version, &AddressHashMode::SerializeP2PKH, 1, &vec![pk.clone()])
+        .expect("Failed to generate address from public key");
+    Ok(format!("{{ 
+  secretKey: \"{}\",
+  publicKey: \"{}\",
+  stacksAddress: \"{}\"
+}}",
+             sk.to_hex(),
+             pk.to_hex(),
+             address.to_string()))
+}
+
+/// Turn terminal echo on stdin on or off, if stdin is a terminal.  Returns whether or not echo
+/// was changed.
+#[cfg(unix)]
+fn set_stdin_echo(echo: bool) -> bool {
+    unsafe {
+        if libc::isatty(libc::STDIN_FILENO) != 1 {
+            return false;
+        }
+        let mut termios : libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return false;
+        }
+        if echo {
+            termios.c_lflag |= libc::ECHO;
+        }
+        else {
+            termios.c_lflag &= !libc::ECHO;
+        }
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) == 0
+    }
+}
+
+#[cfg(not(unix))]
+fn set_stdin_echo(_echo: bool) -> bool {
+    false
+}
+
+/// Prompt for a passphrase on stdin, without echoing it
+fn prompt_passphrase(prompt: &str) -> Result<String, CliError> {
+    eprintln!("{}", prompt);
+    let echo_off = set_stdin_echo(false);
+    let mut passphrase = String::new();
+    let res = io::stdin().read_line(&mut passphrase);
+    if echo_off {
+        set_stdin_echo(true);
+    }
+    res?;
+    Ok(passphrase.trim_end_matches(|c| c == '\n' || c == '\r').to_string())
+}
+
+/// Get the passphrase for a keystore
+fn read_keystore_passphrase() -> Result<String, CliError> {
+    if let Ok(passphrase) = env::var("STACKS_KEYSTORE_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    prompt_passphrase("Keystore passphrase:")
+}
+
+/// Get the passphrase for a new keystore.  When read from stdin, it must be entered twice.
+fn read_new_keystore_passphrase() -> Result<String, CliError> {
+    let passphrase = match env::var("STACKS_KEYSTORE_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) => {
+            let passphrase = prompt_passphrase("New keystore passphrase:")?;
+            let confirmation = prompt_passphrase("Confirm keystore passphrase:")?;
+            if passphrase != confirmation {
+                return Err(CliError::Message("Keystore passphrases do not match".into()));
+            }
+            passphrase
+        }
+    };
+    if passphrase.is_empty() {
+        return Err(CliError::Message("Keystore passphrase may not be empty".into()));
+    }
+    Ok(passphrase)
+}
+
+/// Parse a secret key given either as hex, or as `keystore:[path]`
+fn parse_secret_key(arg: &str) -> Result<StacksPrivateKey, CliError> {
+    if arg.starts_with("keystore:") {
+        let passphrase = read_keystore_passphrase()?;
+        Ok(Keystore::load_secp256k1_private_key(&arg["keystore:".len()..], &passphrase)?)
+    }
+    else {
+        Ok(StacksPrivateKey::from_hex(arg)?)
+    }
+}
+
+fn p2pkh_address_string(pk: &StacksPublicKey, version: TransactionVersion) -> String {
+    let version = match version {
+        TransactionVersion::Mainnet => C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+        TransactionVersion::Testnet => C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+    };
+    StacksAddress::from_public_keys(version, &AddressHashMode::SerializeP2PKH, 1, &vec![pk.clone()])
+        .expect("Failed to generate address from public key")
+        .to_string()
+}
+
+fn keystore_create(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
+    if args.len() < 1 || args.len() > 2 || args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", KEYSTORE_CREATE_USAGE)))
+    }
+
+    let sk = match args.get(1) {
+        Some(sk_hex) => StacksPrivateKey::from_hex(sk_hex)?,
+        None => StacksPrivateKey::new()
+    };
+    let pk = StacksPublicKey::from_private(&sk);
+
+    let passphrase = read_new_keystore_passphrase()?;
+    Keystore::encrypt(&KeystoreKey::Secp256k1(sk), &passphrase, KEYSTORE_DEFAULT_ROUNDS).save(&args[0])?;
+    Ok(format!("{{
+  keystore: \"{}\",
+  publicKey: \"{}\",
+  stacksAddress: \"{}\"
+}}",
+             &args[0],
+             pk.to_hex(),
+             p2pkh_address_string(&pk, version)))
+}
+
+fn keystore_create_vrf(args: &[String]) -> Result<String, CliError> {
+    if args.len() < 1 || args.len() > 2 || args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", KEYSTORE_CREATE_VRF_USAGE)))
+    }
+
+    let sk = match args.get(1) {
+        Some(sk_hex) => VRFPrivateKey::from_hex(sk_hex)
+            .ok_or(CliError::Message("Invalid VRF secret key".into()))?,
+        None => VRFPrivateKey::new()
+    };
+    let pk = VRFPublicKey::from_private(&sk);
+
+    let passphrase = read_new_keystore_passphrase()?;
+    Keystore::encrypt(&KeystoreKey::VRF(sk), &passphrase, KEYSTORE_DEFAULT_ROUNDS).save(&args[0])?;
+    Ok(format!("{{
+  keystore: \"{}\",
+  vrfPublicKey: \"{}\"
+}}",
+             &args[0],
+             pk.to_hex()))
+}
+
+fn keystore_show(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
+    if args.len() != 1 || args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", KEYSTORE_SHOW_USAGE)))
+    }
+
+    let passphrase = read_keystore_passphrase()?;
+    match Keystore::load(&args[0])?.decrypt(&passphrase)? {
+        KeystoreKey::Secp256k1(sk) => {
+            let pk = StacksPublicKey::from_private(&sk);
+            Ok(format!("{{
+  secretKey: \"{}\",
+  publicKey: \"{}\",
+  stacksAddress: \"{}\"
+}}",
+                       sk.to_hex(),
+                       pk.to_hex(),
+                       p2pkh_address_string(&pk, version)))
+        },
+        KeystoreKey::VRF(sk) => {
+            Ok(format!("{{
+  vrfSecretKey: \"{}\",
+  vrfPublicKey: \"{}\"
+}}",
+                       sk.to_hex(),
+                       VRFPublicKey::from_private(&sk).to_hex()))
+        }
+    }
+}
+
+/// Derive and format the keys at `index` of `account`
+fn format_derived_keys(master: &ExtendedPrivateKey, account: u32, index: u32, version: TransactionVersion) -> Result<String, CliError> {
+    let account_path = DerivationPath::stacks_account_key(account, index)?;
+    let microblock_path = DerivationPath::stacks_microblock_key(account, index)?;
+    let vrf_path = DerivationPath::stacks_vrf_key(account, index)?;
+
+    let sk = master.derive_path(&account_path)?.to_secp256k1_private_key();
+    let pk = StacksPublicKey::from_private(&sk);
+    let microblock_sk = master.derive_path(&microblock_path)?.to_secp256k1_private_key();
+    let vrf_sk = master.derive_path(&vrf_path)?.to_vrf_private_key();
+    let version = match version {
+        TransactionVersion::Mainnet => C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+        TransactionVersion::Testnet => C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+    };
+
+    let address = StacksAddress::from_public_keys(
+        version, &AddressHashMode::SerializeP2PKH, 1, &vec![pk.clone()])
+        .expect("Failed to generate address from public key");
+    Ok(format!("  path: \"{}\",
+  secretKey: \"{}\",
+  publicKey: \"{}\",
+  stacksAddress: \"{}\",
+  microblockPath: \"{}\",
+  microblockSecretKey: \"{}\",
+  vrfPath: \"{}\",
+  vrfSecretKey: \"{}\",
+  vrfPublicKey: \"{}\"",
+             account_path,
+             sk.to_hex(),
+             pk.to_hex(),
+             address.to_string(),
+             microblock_path,
+             microblock_sk.to_hex(),
+             vrf_path,
+             vrf_sk.to_hex(),
+             VRFPublicKey::from_private(&vrf_sk).to_hex()))
+}
+
+fn generate_mnemonic(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
+    if args.len() >= 1 && args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", GENERATE_MNEMONIC_USAGE)))
+    }
+
+    let num_words = match args.get(0) {
+        Some(num_words) => num_words.parse::<usize>()?,
+        None => 24
+    };
+
+    let mnemonic = Mnemonic::generate(num_words)?;
+    let master = ExtendedPrivateKey::from_mnemonic(&mnemonic, "")?;
+    Ok(format!("{{
+  mnemonic: \"{}\",
+{}
+}}",
+             mnemonic.phrase(),
+             format_derived_keys(&master, 0, 0, version)?))
+}
+
+fn derive_keys(args: &[String], version: TransactionVersion) -> Result<String, CliError> {
+    if args.len() < 1 || args.len() > 4 || args[0] == "-h" {
+        return Err(CliError::Message(format!("USAGE:\n {}", DERIVE_KEYS_USAGE)))
+    }
+
+    let mnemonic = Mnemonic::from_phrase(&args[0])?;
+    let account = match args.get(1) {
+        Some(account) => account.parse::<u32>()?,
+        None => 0
+    };
+    let index = match args.get(2) {
+        Some(index) => index.parse::<u32>()?,
+        None => 0
+    };
+    if account >= BIP32_HARDENED_INDEX || index >= BIP32_HARDENED_INDEX {
+        return Err(CliError::Message(format!("Account and index must be less than {}", BIP32_HARDENED_INDEX)));
+    }
+    let passphrase = args.get(3).map(|p| p.as_str()).unwrap_or("");
+
+    let master = ExtendedPrivateKey::from_mnemonic(&mnemonic, passphrase)?;
+    Ok(format!("{{
+{}
+}}",
+             format_derived_keys(&master, account, index, version)?))
+}
+
+fn main() {
+    log::set_loglevel(log::LOG_DEBUG).unwrap();
+    let mut argv : Vec<String> = env::args().collect();
+
+    argv.remove(0);
+
+    match main_handler(argv) {
+        Ok(s) => {
+            println!("{}", s);
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main_handler(mut argv: Vec<String>) -> Result<String, CliError> {
+    let tx_version = if let Some(ix) = argv.iter().position(|x| x == "--testnet") {
         argv.remove(ix);
         TransactionVersion::Testnet
     } else {
         TransactionVersion::Mainnet
     };
 
+    let sponsored = if let Some(ix) = argv.iter().position(|x| x == "--sponsored") {
+        argv.remove(ix);
+        true
+    } else {
+        false
+    };
+
+    let p2wsh = if let Some(ix) = argv.iter().position(|x| x == "--p2wsh") {
+        argv.remove(ix);
+        true
+    } else {
+        false
+    };
+
+    let allow_mode = if let Some(ix) = argv.iter().position(|x| x == "--allow-mode") {
+        argv.remove(ix);
+        true
+    } else {
+        false
+    };
+
+    let mut post_conditions = vec![];
+    while let Some(ix) = argv.iter().position(|x| x == "--postcondition") {
+        argv.remove(ix);
+        if ix >= argv.len() {
+            return Err("--postcondition must be followed by a post-condition".into());
+        }
+        let post_condition = TransactionPostCondition::from_string(&argv.remove(ix))?;
+        post_conditions.push(post_condition);
+    }
+
+    let post_conditions = PostConditionOptions {
+        allow_mode,
+        post_conditions
+    };
+
     if let Some((method, args)) = argv.split_first() {
         match method.as_str() {
-            "contract-call" => handle_contract_call(args, tx_version),
-            "publish" => handle_contract_publish(args, tx_version),
-            "token-transfer" => handle_token_transfer(args, tx_version),
+            "contract-call" => handle_contract_call(args, tx_version, sponsored, &post_conditions),
+            "publish" => handle_contract_publish(args, tx_version, sponsored, &post_conditions),
+            "token-transfer" => handle_token_transfer(args, tx_version, sponsored, &post_conditions),
+            "sponsor" => handle_sponsor(args),
+            "multisig-address" => handle_multisig_address(args, tx_version, p2wsh),
+            "multisig-tx" => handle_multisig_tx(args, tx_version, sponsored, p2wsh, &post_conditions),
+            "multisig-sign" => handle_multisig_sign(args),
+            "multisig-finalize" => handle_multisig_finalize(args),
+            "unsigned-tx" => handle_unsigned_tx(args, tx_version, sponsored, &post_conditions),
+            "sighash" => handle_sighash(args),
+            "append-signature" => handle_append_signature(args),
+            "set-sponsor" => handle_set_sponsor(args),
+            "simulate" => simulate(args),
+            "export-snapshot" => export_snapshot(args, tx_version),
+            "import-snapshot" => import_snapshot(args),
             "generate-sk" => generate_secret_key(args, tx_version),
+            "generate-mnemonic" => generate_mnemonic(args, tx_version),
+            "derive-keys" => derive_keys(args, tx_version),
+            "keystore-create" => keystore_create(args, tx_version),
+            "keystore-create-vrf" => keystore_create_vrf(args),
+            "keystore-show" => keystore_show(args, tx_version),
             _ => Err(CliError::Usage)
         }
     } else {
@@ -400,6 +1472,81 @@ mod test {
         assert!(generate_secret_key(&vec!["-h".into()], TransactionVersion::Mainnet).is_err());
     }
 
+    #[test]
+    fn keystore_should_work() {
+        let sk_path = "/tmp/blockstack-cli-keystore-sk.json";
+        let vrf_path = "/tmp/blockstack-cli-keystore-vrf.json";
+        for path in [sk_path, vrf_path].iter() {
+            if fs::metadata(path).is_ok() {
+                fs::remove_file(path).unwrap();
+            }
+        }
+
+        env::set_var("STACKS_KEYSTORE_PASSPHRASE", "correct horse battery staple");
+
+        let sk_hex = "043ff5004e3d695060fa48ac94c96049b8c14ef441c50a184a6a3875d2a000f301";
+        main_handler(to_string_vec(&["keystore-create", sk_path, sk_hex])).unwrap();
+        assert!(main_handler(to_string_vec(&["keystore-create", sk_path, sk_hex])).is_err());
+
+        let out = main_handler(to_string_vec(&["keystore-show", sk_path])).unwrap();
+        assert!(out.contains(&format!("secretKey: \"{}\"", sk_hex)));
+
+        // signing with the keystore gives the same transaction as signing with the hex key
+        let keystore_arg = format!("keystore:{}", sk_path);
+        let tx_args = ["token-transfer", sk_hex, "1", "0", "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM", "10"];
+        let mut keystore_tx_args = tx_args.clone();
+        keystore_tx_args[1] = &keystore_arg;
+        assert_eq!(main_handler(to_string_vec(&tx_args)).unwrap(),
+                   main_handler(to_string_vec(&keystore_tx_args)).unwrap());
+
+        let vrf_sk_hex = "9ec59abf8e8ef3cf5fd8ea4219e09f61ad2ea84bfe38bd7c2f54e0e8d1c5ba5a";
+        main_handler(to_string_vec(&["keystore-create-vrf", vrf_path, vrf_sk_hex])).unwrap();
+        let out = main_handler(to_string_vec(&["keystore-show", vrf_path])).unwrap();
+        assert!(out.contains(&format!("vrfSecretKey: \"{}\"", vrf_sk_hex)));
+
+        // a VRF keystore can't sign transactions
+        let vrf_keystore_arg = format!("keystore:{}", vrf_path);
+        keystore_tx_args[1] = &vrf_keystore_arg;
+        assert!(main_handler(to_string_vec(&keystore_tx_args)).is_err());
+
+        // new keystores need a passphrase
+        fs::remove_file(sk_path).unwrap();
+        env::set_var("STACKS_KEYSTORE_PASSPHRASE", "");
+        assert!(main_handler(to_string_vec(&["keystore-create", sk_path, sk_hex])).is_err());
+        assert!(fs::metadata(sk_path).is_err());
+
+        fs::remove_file(vrf_path).unwrap();
+    }
+
+    #[test]
+    fn derive_keys_should_work() {
+        let phrase = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let master = ExtendedPrivateKey::from_mnemonic(&Mnemonic::from_phrase(phrase).unwrap(), "").unwrap();
+        let sk = master.derive_path(&DerivationPath::stacks_account_key(2, 3).unwrap()).unwrap().to_secp256k1_private_key();
+
+        let out = main_handler(to_string_vec(&["derive-keys", phrase, "2", "3"])).unwrap();
+        assert!(out.contains("path: \"m/44'/5757'/2'/0/3\""));
+        assert!(out.contains(&format!("secretKey: \"{}\"", sk.to_hex())));
+        assert!(out.contains("stacksAddress: \"SP"));
+
+        let out = main_handler(to_string_vec(&["--testnet", "derive-keys", phrase, "2", "3"])).unwrap();
+        assert!(out.contains(&format!("secretKey: \"{}\"", sk.to_hex())));
+        assert!(out.contains("stacksAddress: \"ST"));
+
+        // passphrase changes the keys
+        let out = main_handler(to_string_vec(&["derive-keys", phrase, "2", "3", "TREZOR"])).unwrap();
+        assert!(!out.contains(&sk.to_hex()));
+
+        assert!(main_handler(to_string_vec(&["derive-keys", "legal winner thank year"])).is_err());
+        assert!(main_handler(to_string_vec(&["derive-keys", phrase, "x"])).is_err());
+        assert!(main_handler(to_string_vec(&["derive-keys", phrase, "2147483648"])).is_err());
+        assert!(main_handler(to_string_vec(&["derive-keys", phrase, "0", "2147483648"])).is_err());
+
+        let out = main_handler(to_string_vec(&["generate-mnemonic", "12"])).unwrap();
+        assert!(out.contains("mnemonic: "));
+        assert!(main_handler(to_string_vec(&["generate-mnemonic", "13"])).is_err());
+    }
+
     fn to_string_vec(x: &[&str]) -> Vec<String> {
         x.iter().map(|&x| x.into()).collect()
     }
@@ -429,6 +1576,90 @@ mod test {
 
     }
 
+    #[test]
+    fn simulate_should_work() {
+        let chainstate_path = "/tmp/blockstack-cli-simulate";
+        if fs::metadata(chainstate_path).is_ok() {
+            fs::remove_dir_all(chainstate_path).unwrap();
+        }
+
+        let publish_args = [
+            "publish",
+            "043ff5004e3d695060fa48ac94c96049b8c14ef441c50a184a6a3875d2a000f3",
+            "0",
+            "0",
+            "foo-contract",
+            "./sample-programs/tokens.clar",
+            "--testnet"];
+        let tx_hex = main_handler(to_string_vec(&publish_args)).unwrap();
+        let tx = decode_tx(&tx_hex);
+
+        let first_burn_hash = BurnchainHeaderHash([0u8; 32]).to_hex();
+        let first_block_hash = FIRST_STACKS_BLOCK_HASH.to_hex();
+
+        // no chain state yet
+        let simulate_args = ["simulate", chainstate_path, &first_burn_hash, &first_block_hash, &tx_hex];
+        assert!(format!("{}", main_handler(to_string_vec(&simulate_args)).unwrap_err())
+                .contains("No chain state"));
+
+        // ...and simulating didn't create one
+        fs::create_dir_all(chainstate_path).unwrap();
+        assert!(format!("{}", main_handler(to_string_vec(&simulate_args)).unwrap_err())
+                .contains("No chain state"));
+        assert!(!Path::new(chainstate_path).join(StacksChainState::chain_dir_name(false, tx.chain_id)).exists());
+
+        StacksChainState::open(false, tx.chain_id, chainstate_path).unwrap();
+
+        let output = main_handler(to_string_vec(&simulate_args)).unwrap();
+        let simulation : serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(simulation["txid"], tx.txid().to_hex());
+        assert_eq!(simulation["status"], "success");
+        assert_eq!(simulation["post_conditions_passed"], true);
+
+        // unknown tip
+        let simulate_args = ["simulate", chainstate_path, &first_burn_hash, &BlockHeaderHash([9u8; 32]).to_hex(), &tx_hex];
+        assert!(format!("{}", main_handler(to_string_vec(&simulate_args)).unwrap_err())
+                .contains("No such Stacks block"));
+    }
+
+    #[test]
+    fn snapshot_commands_check_paths() {
+        let chainstate_path = "/tmp/blockstack-cli-snapshot-chainstate";
+        let burnchain_path = "/tmp/blockstack-cli-snapshot-burnchain";
+        let snapshot_path = "/tmp/blockstack-cli-snapshot";
+        for path in [chainstate_path, burnchain_path, snapshot_path].iter() {
+            if fs::metadata(path).is_ok() {
+                fs::remove_dir_all(path).unwrap();
+            }
+        }
+
+        let first_burn_hash = BurnchainHeaderHash([0u8; 32]).to_hex();
+        let first_block_hash = FIRST_STACKS_BLOCK_HASH.to_hex();
+
+        let export_args = ["--testnet", "export-snapshot", chainstate_path, "1", burnchain_path, &first_burn_hash, &first_block_hash, snapshot_path];
+        assert!(format!("{}", main_handler(to_string_vec(&export_args)).unwrap_err())
+                .contains("No chain state"));
+
+        // the chain state is not created as a side effect
+        assert!(fs::metadata(chainstate_path).is_err());
+
+        // only the chain state of the given chain ID is exported
+        StacksChainState::open(false, 0, chainstate_path).unwrap();
+        assert!(format!("{}", main_handler(to_string_vec(&export_args)).unwrap_err())
+                .contains("No chain state for chain ID 1"));
+
+        StacksChainState::open(false, 1, chainstate_path).unwrap();
+        assert!(format!("{}", main_handler(to_string_vec(&export_args)).unwrap_err())
+                .contains("No burn database"));
+
+        let index_block_hash = BlockHeaderHash([0x11; 32]).to_hex();
+        let consensus_hash = ConsensusHash([0x22; 20]).to_hex();
+        let import_args = ["import-snapshot", snapshot_path, chainstate_path, burnchain_path, &index_block_hash, &consensus_hash];
+        assert!(format!("{}", main_handler(to_string_vec(&import_args)).unwrap_err())
+                .contains("Failed to read snapshot manifest"));
+        assert!(fs::metadata(burnchain_path).is_err());
+    }
+
     #[test]
     fn simple_token_transfer() {
         let tt_args = [
@@ -476,6 +1707,344 @@ mod test {
                 .contains("Failed to parse contract address"));        
     }
 
+    fn decode_tx(tx_hex: &str) -> StacksTransaction {
+        StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&hex_bytes(tx_hex).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn sponsored_tx() {
+        let origin_sk = "043ff5004e3d695060fa48ac94c96049b8c14ef441c50a184a6a3875d2a000f3";
+        let sponsor_sk = "b8d99fd45da58038d630d9855d3ca2466e8e0f89d3894c4724f0efc9ff4b51f001";
+
+        let tt_args = [
+            "token-transfer",
+            "--sponsored",
+            "--testnet",
+            origin_sk,
+            "0",
+            "3",
+            "ST1A14RBKJ289E3DP89QAZE2RRHDPWP5RHMYFRCHV",
+            "10"];
+
+        let origin_signed_hex = main_handler(to_string_vec(&tt_args)).unwrap();
+        let origin_signed_tx = decode_tx(&origin_signed_hex);
+        assert!(origin_signed_tx.auth.is_sponsored());
+        assert_eq!(origin_signed_tx.auth.get_origin_nonce(), 3);
+        assert!(origin_signed_tx.verify_origin().is_ok());
+
+        // not finished until the sponsor signs
+        assert!(origin_signed_tx.verify().is_err());
+
+        let sponsor_args = [
+            "sponsor",
+            sponsor_sk,
+            "100",
+            "7",
+            &origin_signed_hex];
+
+        let sponsored_hex = main_handler(to_string_vec(&sponsor_args)).unwrap();
+        let sponsored_tx = decode_tx(&sponsored_hex);
+        assert_eq!(sponsored_tx.verify().unwrap(), true);
+        assert_eq!(sponsored_tx.auth.get_origin_nonce(), 3);
+        assert_eq!(sponsored_tx.auth.get_sponsor_nonce(), Some(7));
+        assert_eq!(sponsored_tx.get_fee_rate(), 100);
+        assert_eq!(sponsored_tx.origin_address(), origin_signed_tx.origin_address());
+        assert_eq!(sponsored_tx.sponsor_address(),
+                   Some(StacksAddress::from_public_keys(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, &AddressHashMode::SerializeP2PKH, 1,
+                                                        &vec![StacksPublicKey::from_private(&StacksPrivateKey::from_hex(sponsor_sk).unwrap())]).unwrap()));
+
+        // publish and contract-call can be sponsored too
+        let publish_args = [
+            "--sponsored",
+            "publish",
+            origin_sk,
+            "0",
+            "0",
+            "foo-contract",
+            "./sample-programs/tokens.clar"];
+
+        let origin_signed_hex = main_handler(to_string_vec(&publish_args)).unwrap();
+        let sponsor_args = ["sponsor", sponsor_sk, "1", "0", &origin_signed_hex];
+        assert!(decode_tx(&main_handler(to_string_vec(&sponsor_args)).unwrap()).verify().unwrap());
+
+        let cc_args = [
+            "--sponsored",
+            "contract-call",
+            origin_sk,
+            "0",
+            "0",
+            "SPJT598WY1RJN792HRKRHRQYFB7RJ5ZCG6J6GEZ4",
+            "foo-contract",
+            "transfer-fookens",
+            "-e",
+            "1"];
+
+        let origin_signed_hex = main_handler(to_string_vec(&cc_args)).unwrap();
+        let sponsor_args = ["sponsor", sponsor_sk, "1", "0", &origin_signed_hex];
+        assert!(decode_tx(&main_handler(to_string_vec(&sponsor_args)).unwrap()).verify().unwrap());
+
+        // can't sponsor a standard transaction
+        let tt_args = [
+            "token-transfer",
+            origin_sk,
+            "1",
+            "0",
+            "ST1A14RBKJ289E3DP89QAZE2RRHDPWP5RHMYFRCHV",
+            "10"];
+
+        let standard_hex = main_handler(to_string_vec(&tt_args)).unwrap();
+        let sponsor_args = ["sponsor", sponsor_sk, "1", "0", &standard_hex];
+        assert!(format!("{}", main_handler(to_string_vec(&sponsor_args)).unwrap_err())
+                .contains("not a sponsored transaction"));
+
+        let sponsor_args = ["sponsor", sponsor_sk, "1", "0"];
+        assert!(format!("{}", main_handler(to_string_vec(&sponsor_args)).unwrap_err())
+                .contains("Incorrect argument count"));
+    }
+
+    #[test]
+    fn multisig_tx() {
+        let sks : Vec<_> = (0..3).map(|_| StacksPrivateKey::new()).collect();
+        let pubkeys = sks.iter()
+            .map(|sk| StacksPublicKey::from_private(sk).to_hex())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        for p2wsh in [false, true].iter() {
+            let mut flags = vec!["--testnet"];
+            if *p2wsh {
+                flags.push("--p2wsh");
+            }
+
+            let mut addr_args = flags.clone();
+            addr_args.extend_from_slice(&["multisig-address", "2", &pubkeys]);
+            let address = main_handler(to_string_vec(&addr_args)).unwrap();
+
+            let mut tx_args = flags.clone();
+            tx_args.extend_from_slice(&["multisig-tx", "2", &pubkeys, "10", "4",
+                                        "token-transfer", "ST1A14RBKJ289E3DP89QAZE2RRHDPWP5RHMYFRCHV", "100"]);
+            let unsigned_hex = main_handler(to_string_vec(&tx_args)).unwrap();
+            let unsigned_tx = decode_tx(&unsigned_hex);
+            assert_eq!(unsigned_tx.origin_address().to_string(), address);
+            assert_eq!(unsigned_tx.get_origin_nonce(), 4);
+            assert_eq!(unsigned_tx.get_fee_rate(), 10);
+
+            // not enough signatures yet
+            let finalize_args = ["multisig-finalize", "2", &pubkeys, &unsigned_hex];
+            assert!(format!("{}", main_handler(to_string_vec(&finalize_args)).unwrap_err())
+                    .contains("0 of 2 required signatures"));
+
+            // the first and third signers sign, skipping over the second
+            let sign_args = ["multisig-sign", &sks[0].to_hex(), "2", &pubkeys, &unsigned_hex];
+            let signed_1_hex = main_handler(to_string_vec(&sign_args)).unwrap();
+
+            let sign_args = ["multisig-sign", &sks[2].to_hex(), "2", &pubkeys, &signed_1_hex];
+            let signed_2_hex = main_handler(to_string_vec(&sign_args)).unwrap();
+
+            // can't sign twice, and the skipped signer can't sign after the third
+            let sign_args = ["multisig-sign", &sks[1].to_hex(), "2", &pubkeys, &signed_1_hex];
+            let signed_bad_hex = main_handler(to_string_vec(&sign_args)).unwrap();
+            let sign_args = ["multisig-sign", &sks[0].to_hex(), "2", &pubkeys, &signed_bad_hex];
+            assert!(main_handler(to_string_vec(&sign_args)).is_err());
+
+            let sign_args = ["multisig-sign", &sks[1].to_hex(), "2", &pubkeys, &signed_2_hex];
+            assert!(main_handler(to_string_vec(&sign_args)).is_err());
+
+            // public keys must be given in the same order
+            let reordered_pubkeys = sks.iter().rev()
+                .map(|sk| StacksPublicKey::from_private(sk).to_hex())
+                .collect::<Vec<_>>()
+                .join(",");
+            let finalize_args = ["multisig-finalize", "2", &reordered_pubkeys, &signed_2_hex];
+            assert!(main_handler(to_string_vec(&finalize_args)).is_err());
+
+            let finalize_args = ["multisig-finalize", "2", &pubkeys, &signed_2_hex];
+            let final_tx = decode_tx(&main_handler(to_string_vec(&finalize_args)).unwrap());
+            assert_eq!(final_tx.verify().unwrap(), true);
+            assert_eq!(final_tx.origin_address().to_string(), address);
+
+            // same for the pair that did not skip anyone
+            let finalize_args = ["multisig-finalize", "2", &pubkeys, &signed_bad_hex];
+            let final_tx = decode_tx(&main_handler(to_string_vec(&finalize_args)).unwrap());
+            assert_eq!(final_tx.verify().unwrap(), true);
+        }
+
+        // a multisig origin can be sponsored
+        let tx_args = ["--testnet", "--sponsored", "multisig-tx", "1", &pubkeys, "0", "0",
+                       "contract-call", "SPJT598WY1RJN792HRKRHRQYFB7RJ5ZCG6J6GEZ4", "foo-contract", "transfer-fookens", "-e", "1"];
+        let unsigned_hex = main_handler(to_string_vec(&tx_args)).unwrap();
+
+        let sign_args = ["multisig-sign", &sks[1].to_hex(), "1", &pubkeys, &unsigned_hex];
+        let signed_hex = main_handler(to_string_vec(&sign_args)).unwrap();
+        let finalize_args = ["multisig-finalize", "1", &pubkeys, &signed_hex];
+        let origin_signed_hex = main_handler(to_string_vec(&finalize_args)).unwrap();
+
+        let sponsor_args = ["sponsor", &sks[0].to_hex(), "10", "0", &origin_signed_hex];
+        let sponsored_tx = decode_tx(&main_handler(to_string_vec(&sponsor_args)).unwrap());
+        assert_eq!(sponsored_tx.verify().unwrap(), true);
+
+        let addr_args = ["multisig-address", "4", &pubkeys];
+        assert!(main_handler(to_string_vec(&addr_args)).is_err());
+    }
+
+    #[test]
+    fn post_conditions() {
+        let sk = "b8d99fd45da58038d630d9855d3ca2466e8e0f89d3894c4724f0efc9ff4b51f001";
+
+        let tt_args = [
+            "--postcondition",
+            "origin stx le 10",
+            "token-transfer",
+            sk,
+            "1",
+            "0",
+            "--postcondition",
+            "SPJT598WY1RJN792HRKRHRQYFB7RJ5ZCG6J6GEZ4.foo-contract SPJT598WY1RJN792HRKRHRQYFB7RJ5ZCG6J6GEZ4.foo-contract::fookens ge 1",
+            "SPJT598WY1RJN792HRKRHRQYFB7RJ5ZCG6J6GEZ4",
+            "10"];
+
+        let tx = decode_tx(&main_handler(to_string_vec(&tt_args)).unwrap());
+        assert_eq!(tx.verify().unwrap(), true);
+        assert_eq!(tx.post_condition_mode, TransactionPostConditionMode::Deny);
+        assert_eq!(tx.post_conditions.len(), 2);
+        assert_eq!(format!("{}", &tx.post_conditions[0]), "origin stx le 10");
+        assert_eq!(format!("{}", &tx.post_conditions[1]),
+                   "SPJT598WY1RJN792HRKRHRQYFB7RJ5ZCG6J6GEZ4.foo-contract SPJT598WY1RJN792HRKRHRQYFB7RJ5ZCG6J6GEZ4.foo-contract::fookens ge 1");
+
+        let cc_args = [
+            "--allow-mode",
+            "--postcondition",
+            "origin SPJT598WY1RJN792HRKRHRQYFB7RJ5ZCG6J6GEZ4.foo-contract::names not-sent (tuple (name 0x616c696365))",
+            "contract-call",
+            sk,
+            "1",
+            "0",
+            "SPJT598WY1RJN792HRKRHRQYFB7RJ5ZCG6J6GEZ4",
+            "foo-contract",
+            "transfer-fookens",
+            "-e",
+            "1"];
+
+        let tx = decode_tx(&main_handler(to_string_vec(&cc_args)).unwrap());
+        assert_eq!(tx.verify().unwrap(), true);
+        assert_eq!(tx.post_condition_mode, TransactionPostConditionMode::Allow);
+        assert_eq!(tx.post_conditions.len(), 1);
+        match tx.post_conditions[0] {
+            TransactionPostCondition::Nonfungible(_, _, Value::Tuple(_), _) => {},
+            _ => panic!("Expected a non-fungible post-condition on a tuple")
+        }
+
+        // transactions use the Deny mode unless told otherwise
+        let publish_args = [
+            "publish",
+            sk,
+            "0",
+            "0",
+            "foo-contract",
+            "./sample-programs/tokens.clar"];
+
+        let tx = decode_tx(&main_handler(to_string_vec(&publish_args)).unwrap());
+        assert_eq!(tx.post_condition_mode, TransactionPostConditionMode::Deny);
+        assert_eq!(tx.post_conditions.len(), 0);
+
+        let bad_args = ["--postcondition", "origin stx sent 10", "publish", sk, "0", "0", "foo-contract", "./sample-programs/tokens.clar"];
+        assert!(main_handler(to_string_vec(&bad_args)).is_err());
+
+        let bad_args = ["publish", sk, "0", "0", "foo-contract", "./sample-programs/tokens.clar", "--postcondition"];
+        assert!(main_handler(to_string_vec(&bad_args)).is_err());
+    }
+
+    /// What an HSM would do with the output of `sighash`
+    fn sign_sighash_output(sighash_output: &str, sk: &StacksPrivateKey) -> String {
+        use blockstack_lib::burnchains::PrivateKey;
+
+        let start = sighash_output.find("\"sighash\": \"").unwrap() + 12;
+        let sighash = hex_bytes(&sighash_output[start..start+64]).unwrap();
+        to_hex(sk.sign(&sighash).unwrap().as_bytes())
+    }
+
+    fn pubkey_hash_hex(sk: &StacksPrivateKey) -> String {
+        to_hex(Hash160::from_data(&StacksPublicKey::from_private(sk).to_bytes()).as_bytes())
+    }
+
+    #[test]
+    fn offline_signing() {
+        let sks : Vec<_> = (0..3).map(|_| StacksPrivateKey::new()).collect();
+        let pubkeys = sks.iter()
+            .map(|sk| StacksPublicKey::from_private(sk).to_hex())
+            .collect::<Vec<_>>()
+            .join(",");
+        let tt_args = ["token-transfer", "ST1A14RBKJ289E3DP89QAZE2RRHDPWP5RHMYFRCHV", "100"];
+
+        // single-sig signs the same as in-process
+        let mut unsigned_args = vec!["--testnet", "unsigned-tx", &pubkeys[0..66], "10", "3"];
+        unsigned_args.extend_from_slice(&tt_args);
+        let unsigned_hex = main_handler(to_string_vec(&unsigned_args)).unwrap();
+
+        let sighash_output = main_handler(to_string_vec(&["sighash", &unsigned_hex])).unwrap();
+        assert!(sighash_output.contains("\"signer\": \"origin\""));
+        assert!(sighash_output.contains("\"authFlag\": 4"));
+        assert!(sighash_output.contains("\"feeRate\": 10"));
+        assert!(sighash_output.contains("\"nonce\": 3"));
+        let sig = sign_sighash_output(&sighash_output, &sks[0]);
+
+        assert!(main_handler(to_string_vec(&["append-signature", &pubkey_hash_hex(&sks[1]), &sig, &unsigned_hex])).is_err());
+        let signed_hex = main_handler(to_string_vec(&["append-signature", &pubkey_hash_hex(&sks[0]), &sig, &unsigned_hex])).unwrap();
+        assert!(decode_tx(&signed_hex).verify().unwrap());
+
+        let sk_hex = sks[0].to_hex();
+        let signed_args = ["--testnet", "token-transfer", &sk_hex, "10", "3", tt_args[1], tt_args[2]];
+        assert_eq!(signed_hex, main_handler(to_string_vec(&signed_args)).unwrap());
+
+        assert!(main_handler(to_string_vec(&["sighash", &signed_hex])).unwrap_err().to_string().contains("already signed"));
+
+        // multisig signs the same as multisig-sign
+        let mut unsigned_args = vec!["--testnet", "multisig-tx", "2", &pubkeys, "10", "3"];
+        unsigned_args.extend_from_slice(&tt_args);
+        let unsigned_hex = main_handler(to_string_vec(&unsigned_args)).unwrap();
+
+        let mut partial_hex = unsigned_hex.clone();
+        for sk in [&sks[0], &sks[2]].iter() {
+            let sighash_output = main_handler(to_string_vec(&["sighash", &partial_hex, "2"])).unwrap();
+            let sig = sign_sighash_output(&sighash_output, sk);
+            partial_hex = main_handler(to_string_vec(&["append-signature", &pubkey_hash_hex(sk), &sig, &partial_hex, "2", &pubkeys])).unwrap();
+        }
+
+        let mut expected_hex = unsigned_hex.clone();
+        for sk in [&sks[0], &sks[2]].iter() {
+            expected_hex = main_handler(to_string_vec(&["multisig-sign", &sk.to_hex(), "2", &pubkeys, &expected_hex])).unwrap();
+        }
+        assert_eq!(partial_hex, expected_hex);
+
+        let final_tx = decode_tx(&main_handler(to_string_vec(&["multisig-finalize", "2", &pubkeys, &partial_hex])).unwrap());
+        assert!(final_tx.verify().unwrap());
+
+        // sponsored: origin and sponsor both sign offline
+        let mut unsigned_args = vec!["--testnet", "--sponsored", "unsigned-tx", &pubkeys[0..66], "0", "3"];
+        unsigned_args.extend_from_slice(&tt_args);
+        let unsigned_hex = main_handler(to_string_vec(&unsigned_args)).unwrap();
+
+        let sighash_output = main_handler(to_string_vec(&["sighash", &unsigned_hex])).unwrap();
+        let sig = sign_sighash_output(&sighash_output, &sks[0]);
+        let origin_signed_hex = main_handler(to_string_vec(&["append-signature", &pubkey_hash_hex(&sks[0]), &sig, &unsigned_hex])).unwrap();
+
+        assert!(main_handler(to_string_vec(&["sighash", &origin_signed_hex])).unwrap_err().to_string().contains("set-sponsor"));
+
+        let sponsor_pubkey = StacksPublicKey::from_private(&sks[1]).to_hex();
+        let sponsor_set_hex = main_handler(to_string_vec(&["set-sponsor", &sponsor_pubkey, "20", "7", &origin_signed_hex])).unwrap();
+        let sighash_output = main_handler(to_string_vec(&["sighash", &sponsor_set_hex])).unwrap();
+        assert!(sighash_output.contains("\"signer\": \"sponsor\""));
+        assert!(sighash_output.contains("\"authFlag\": 5"));
+        assert!(sighash_output.contains("\"feeRate\": 20"));
+        assert!(sighash_output.contains("\"nonce\": 7"));
+
+        let sig = sign_sighash_output(&sighash_output, &sks[1]);
+        let sponsored_hex = main_handler(to_string_vec(&["append-signature", &pubkey_hash_hex(&sks[1]), &sig, &sponsor_set_hex])).unwrap();
+        let sponsored_tx = decode_tx(&sponsored_hex);
+        assert!(sponsored_tx.verify().unwrap());
+        assert_eq!(sponsored_hex, main_handler(to_string_vec(&["sponsor", &sks[1].to_hex(), "20", "7", &origin_signed_hex])).unwrap());
+    }
+
     #[test]
     fn simple_cc() {
         let cc_args = [
@@ -58,6 +58,8 @@ use chainstate::burn::operations::{
     LeaderBlockCommitOp,
     LeaderKeyRegisterOp,
     UserBurnSupportOp,
+    TransferStxOp,
+    StackStxOp,
     BlockstackOperation,
     BlockstackOperationType,
 };
@@ -102,13 +104,17 @@ impl BurnchainStateTransition {
         let mut all_user_burns : HashMap<Txid, UserBurnSupportOp> = HashMap::new();
         let mut all_block_commits : HashMap<Txid, LeaderBlockCommitOp> = HashMap::new();
 
-        // accept all leader keys we found.
+        // accept all leader keys and STX operations we found.
         // don't treat block commits and user burn supports just yet.
         for i in 0..block_ops.len() {
             match block_ops[i] {
                 BlockstackOperationType::LeaderKeyRegister(_) => {
                     accepted_ops.push(block_ops[i].clone());
                 },
+                BlockstackOperationType::TransferStx(_) | BlockstackOperationType::StackStx(_) => {
+                    // these don't affect sortition; they get applied to the chainstate later
+                    accepted_ops.push(block_ops[i].clone());
+                },
                 BlockstackOperationType::LeaderBlockCommit(ref op) => {
                     // we don't yet know which block commits are going to be accepted until we have
                     // the burn distribution, so just account for them for now.
@@ -299,7 +305,8 @@ impl Burnchain {
             consensus_hash_lifetime: params.consensus_hash_lifetime,
             stable_confirmations: params.stable_confirmations,
             first_block_height: params.first_block_height,
-            first_block_hash: params.first_block_hash.clone()
+            first_block_hash: params.first_block_hash.clone(),
+            reward_cycle_length: params.reward_cycle_length
         })
     }
 
@@ -311,6 +318,28 @@ impl Burnchain {
         ret
     }
 
+    /// Which PoX reward cycle does this burnchain block height fall into?
+    /// Returns None if the height is before the first block.
+    pub fn block_height_to_reward_cycle(&self, block_height: u64) -> Option<u64> {
+        if block_height < self.first_block_height {
+            return None;
+        }
+        Some((block_height - self.first_block_height) / (self.reward_cycle_length as u64))
+    }
+
+    /// Burnchain block height of the first block in the given reward cycle
+    pub fn reward_cycle_to_block_height(&self, reward_cycle: u64) -> u64 {
+        self.first_block_height + reward_cycle * (self.reward_cycle_length as u64)
+    }
+
+    /// Is this burnchain block the first block in a reward cycle?
+    pub fn is_reward_cycle_start(&self, block_height: u64) -> bool {
+        match self.block_height_to_reward_cycle(block_height) {
+            Some(reward_cycle) => self.reward_cycle_to_block_height(reward_cycle) == block_height,
+            None => false
+        }
+    }
+
     pub fn get_chainstate_path(working_dir: &String, chain_name: &String, network_name: &String) -> String {
         let mut chainstate_dir_path = PathBuf::from(working_dir);
         chainstate_dir_path.push(chain_name);
@@ -449,6 +478,28 @@ impl Burnchain {
                     }
                 }
             },
+            x if x == Opcodes::TransferStx as u8 => {
+                match TransferStxOp::from_tx(block_header, burn_tx) {
+                    Ok(op) => {
+                        Some(BlockstackOperationType::TransferStx(op))
+                    },
+                    Err(e) => {
+                        warn!("Failed to parse transfer stx tx {} data {}: {:?}", &burn_tx.txid(), &to_hex(&burn_tx.data()[..]), e);
+                        None
+                    }
+                }
+            },
+            x if x == Opcodes::StackStx as u8 => {
+                match StackStxOp::from_tx(block_header, burn_tx) {
+                    Ok(op) => {
+                        Some(BlockstackOperationType::StackStx(op))
+                    },
+                    Err(e) => {
+                        warn!("Failed to parse stack stx tx {} data {}: {:?}", &burn_tx.txid(), &to_hex(&burn_tx.data()[..]), e);
+                        None
+                    }
+                }
+            },
             _ => {
                 None
             }
@@ -478,6 +529,20 @@ impl Burnchain {
                         warn!("REJECTED({}) user burn support {} at {},{}: {:?}", op.block_height, &op.txid, op.block_height, op.vtxindex, &e);
                         burnchain_error::OpError(e)
                     })
+            },
+            BlockstackOperationType::TransferStx(ref op) => {
+                op.check(burnchain, block_header, tx)
+                    .map_err(|e| {
+                        warn!("REJECTED({}) transfer stx {} at {},{}: {:?}", op.block_height, &op.txid, op.block_height, op.vtxindex, &e);
+                        burnchain_error::OpError(e)
+                    })
+            },
+            BlockstackOperationType::StackStx(ref op) => {
+                op.check(burnchain, block_header, tx)
+                    .map_err(|e| {
+                        warn!("REJECTED({}) stack stx {} at {},{}: {:?}", op.block_height, &op.txid, op.block_height, op.vtxindex, &e);
+                        burnchain_error::OpError(e)
+                    })
             }
         }
     }
@@ -922,7 +987,8 @@ pub mod tests {
             consensus_hash_lifetime: 24,
             stable_confirmations: 7,
             first_block_height: first_block_height,
-            first_block_hash: first_burn_hash.clone()
+            first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5
         };
         
         let block_121_hash = BurnchainHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000012").unwrap();
@@ -1075,6 +1141,7 @@ pub mod tests {
                 num_sigs: 1,
                 hash_mode: AddressHashMode::SerializeP2PKH
             },
+            commit_outs: vec![],
 
             txid: Txid::from_bytes(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
             vtxindex: 444,
@@ -1099,6 +1166,7 @@ pub mod tests {
                 num_sigs: 1,
                 hash_mode: AddressHashMode::SerializeP2PKH
             },
+            commit_outs: vec![],
 
             txid: Txid::from_bytes(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27d0").unwrap()).unwrap(),
             vtxindex: 445,
@@ -1123,6 +1191,7 @@ pub mod tests {
                 num_sigs: 1,
                 hash_mode: AddressHashMode::SerializeP2PKH
             },
+            commit_outs: vec![],
 
             txid: Txid::from_bytes(&hex_bytes("301dc687a9f06a1ae87a013f27133e9cec0843c2983567be73e185827c7c13de").unwrap()).unwrap(),
             vtxindex: 446,
@@ -1294,7 +1363,8 @@ pub mod tests {
                     },
                     BlockstackOperationType::UserBurnSupport(ref mut op) => {
                         op.burn_header_hash = block_124_hash.clone();
-                    }
+                    },
+                    _ => {}
                 }
             }
 
@@ -1307,7 +1377,8 @@ pub mod tests {
                     match bo {
                         BlockstackOperationType::LeaderBlockCommit(ref op) => op.txid.clone(),
                         BlockstackOperationType::LeaderKeyRegister(ref op) => op.txid.clone(),
-                        BlockstackOperationType::UserBurnSupport(ref op) => op.txid.clone()
+                        BlockstackOperationType::UserBurnSupport(ref op) => op.txid.clone(),
+                        _ => unreachable!()
                     }
                 })
                 .collect()
@@ -1409,7 +1480,8 @@ pub mod tests {
             consensus_hash_lifetime: 24,
             stable_confirmations: 7,
             first_block_height: first_block_height,
-            first_block_hash: first_burn_hash.clone()
+            first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5
         };
 
         let mut leader_private_keys = vec![];
@@ -1474,6 +1546,7 @@ pub mod tests {
                         num_sigs: 1,
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes(&vec![i,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,i]).unwrap(),
                     vtxindex: (2 * i) as u32,
@@ -1535,6 +1608,27 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_reward_cycles() {
+        let first_burn_hash = BurnchainHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000123").unwrap();
+        let mut burnchain = Burnchain::default_unittest(120, &first_burn_hash);
+        burnchain.reward_cycle_length = 5;
+
+        assert_eq!(burnchain.block_height_to_reward_cycle(119), None);
+        assert_eq!(burnchain.block_height_to_reward_cycle(120), Some(0));
+        assert_eq!(burnchain.block_height_to_reward_cycle(124), Some(0));
+        assert_eq!(burnchain.block_height_to_reward_cycle(125), Some(1));
+        assert_eq!(burnchain.block_height_to_reward_cycle(131), Some(2));
+
+        assert_eq!(burnchain.reward_cycle_to_block_height(0), 120);
+        assert_eq!(burnchain.reward_cycle_to_block_height(2), 130);
+
+        assert!(!burnchain.is_reward_cycle_start(119));
+        assert!(burnchain.is_reward_cycle_start(120));
+        assert!(!burnchain.is_reward_cycle_start(121));
+        assert!(burnchain.is_reward_cycle_start(125));
+    }
+
     // TODO: test VRF key duplication check
     // TODO; test that all but the first of the block commits committing to the same key are
     // dropped
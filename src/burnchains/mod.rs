@@ -107,6 +107,7 @@ pub struct BurnchainParameters {
     first_block_hash: BurnchainHeaderHash,
     stable_confirmations: u32,
     consensus_hash_lifetime: u32,
+    reward_cycle_length: u32,
 }
 
 impl BurnchainParameters {
@@ -119,6 +120,7 @@ impl BurnchainParameters {
             first_block_hash: FIRST_BURNCHAIN_BLOCK_HASH.clone(),
             stable_confirmations: 7,
             consensus_hash_lifetime: 24,
+            reward_cycle_length: POX_REWARD_CYCLE_LENGTH,
         }
     }
 
@@ -131,6 +133,7 @@ impl BurnchainParameters {
             first_block_hash: FIRST_BURNCHAIN_BLOCK_HASH_TESTNET.clone(),
             stable_confirmations: 7,
             consensus_hash_lifetime: 24,
+            reward_cycle_length: POX_REWARD_CYCLE_LENGTH,
         }
     }
 
@@ -142,7 +145,8 @@ impl BurnchainParameters {
             first_block_height: BITCOIN_FIRST_BLOCK_REGTEST,
             first_block_hash: FIRST_BURNCHAIN_BLOCK_HASH_REGTEST.clone(),
             stable_confirmations: 1,
-            consensus_hash_lifetime: 24
+            consensus_hash_lifetime: 24,
+            reward_cycle_length: POX_REWARD_CYCLE_LENGTH
         }
     }
 }
@@ -257,7 +261,8 @@ pub struct Burnchain {
     pub consensus_hash_lifetime: u32,
     pub stable_confirmations: u32,
     pub first_block_height: u64,
-    pub first_block_hash: BurnchainHeaderHash
+    pub first_block_hash: BurnchainHeaderHash,
+    pub reward_cycle_length: u32
 }
 
 /// Structure for encoding our view of the network 
@@ -676,6 +681,7 @@ pub mod test {
                                            miner: &mut TestMiner, 
                                            block_hash: &BlockHeaderHash, 
                                            burn_fee: u64, 
+                                           commit_outs: Vec<StacksAddress>,
                                            leader_key: &LeaderKeyRegisterOp, 
                                            fork_snapshot: Option<&BlockSnapshot>, 
                                            parent_block_snapshot: Option<&BlockSnapshot>) -> LeaderBlockCommitOp 
@@ -722,12 +728,38 @@ pub mod test {
             txop.burn_header_hash = BurnchainHeaderHash::from_test_data(txop.block_height, &self.parent_snapshot.index_root, self.fork_id);
             txop.txid = Txid::from_test_data(txop.block_height, txop.vtxindex, &txop.burn_header_hash, 0);
 
+            txop.commit_outs = commit_outs;
+
             self.txs.push(BlockstackOperationType::LeaderBlockCommit(txop.clone()));
 
             miner.block_commits.push(txop.clone());
             txop
         }
 
+        pub fn add_transfer_stx(&mut self, sender: &StacksAddress, recipient: &StacksAddress, transfered_ustx: u128) -> TransferStxOp {
+            let mut txop = TransferStxOp::new(sender, recipient, transfered_ustx);
+
+            txop.block_height = self.block_height;
+            txop.vtxindex = self.txs.len() as u32;
+            txop.burn_header_hash = BurnchainHeaderHash::from_test_data(txop.block_height, &self.parent_snapshot.index_root, self.fork_id);
+            txop.txid = Txid::from_test_data(txop.block_height, txop.vtxindex, &txop.burn_header_hash, 0);
+
+            self.txs.push(BlockstackOperationType::TransferStx(txop.clone()));
+            txop
+        }
+
+        pub fn add_stack_stx(&mut self, sender: &StacksAddress, reward_addr: &StacksAddress, stacked_ustx: u128, num_cycles: u8) -> StackStxOp {
+            let mut txop = StackStxOp::new(sender, reward_addr, stacked_ustx, num_cycles);
+
+            txop.block_height = self.block_height;
+            txop.vtxindex = self.txs.len() as u32;
+            txop.burn_header_hash = BurnchainHeaderHash::from_test_data(txop.block_height, &self.parent_snapshot.index_root, self.fork_id);
+            txop.txid = Txid::from_test_data(txop.block_height, txop.vtxindex, &txop.burn_header_hash, 0);
+
+            self.txs.push(BlockstackOperationType::StackStx(txop.clone()));
+            txop
+        }
+
         // TODO: user burn support
 
         pub fn patch_from_chain_tip(&mut self, parent_snapshot: &BlockSnapshot) -> () {
@@ -868,7 +900,7 @@ pub mod test {
                 let block_commit_op = {
                     let mut tx = node.burndb.tx_begin().unwrap();
                     let hash = block_hashes[j].clone();
-                    block.add_leader_block_commit(&mut tx, &mut miners[j], &hash, ((j + 1) as u64) * 1000, &prev_keys[j], None, None)
+                    block.add_leader_block_commit(&mut tx, &mut miners[j], &hash, ((j + 1) as u64) * 1000, vec![], &prev_keys[j], None, None)
                 };
                 next_commits.push(block_commit_op);
             }
@@ -46,6 +46,8 @@ use chainstate::burn::operations::{
     LeaderBlockCommitOp,
     LeaderKeyRegisterOp,
     UserBurnSupportOp,
+    TransferStxOp,
+    StackStxOp,
     BlockstackOperation,
     BlockstackOperationType
 };
@@ -204,6 +206,7 @@ impl FromRow<LeaderBlockCommitOp> for LeaderBlockCommitOp {
         let memo_hex : String = row.get("memo");
         let burn_fee_str : String = row.get("burn_fee");
         let input_json : String = row.get("input");
+        let commit_outs_json : String = row.get("commit_outs");
         
         let memo_bytes = hex_bytes(&memo_hex)
             .map_err(|_e| db_error::ParseError)?;
@@ -213,6 +216,9 @@ impl FromRow<LeaderBlockCommitOp> for LeaderBlockCommitOp {
         let input = serde_json::from_str::<BurnchainSigner>(&input_json)
             .map_err(|e| db_error::SerializationError(e))?;
 
+        let commit_outs = serde_json::from_str::<Vec<StacksAddress>>(&commit_outs_json)
+            .map_err(|e| db_error::SerializationError(e))?;
+
         let burn_fee = burn_fee_str.parse::<u64>()
             .map_err(|_e| db_error::ParseError)?;
 
@@ -231,6 +237,7 @@ impl FromRow<LeaderBlockCommitOp> for LeaderBlockCommitOp {
 
             burn_fee: burn_fee,
             input: input,
+            commit_outs: commit_outs,
 
             txid: txid,
             vtxindex: vtxindex,
@@ -282,6 +289,75 @@ impl FromRow<UserBurnSupportOp> for UserBurnSupportOp {
     }
 }
 
+impl FromRow<TransferStxOp> for TransferStxOp {
+    fn from_row<'a>(row: &'a Row) -> Result<TransferStxOp, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let vtxindex : u32 = row.get("vtxindex");
+        let block_height : i64 = row.get("block_height");
+        let burn_header_hash = BurnchainHeaderHash::from_column(row, "burn_header_hash")?;
+
+        let sender = StacksAddress::from_column(row, "sender")?;
+        let recipient = StacksAddress::from_column(row, "recipient")?;
+        let transfered_ustx_str : String = row.get("transfered_ustx");
+        let memo_hex : String = row.get("memo");
+
+        let transfered_ustx = transfered_ustx_str.parse::<u128>()
+            .map_err(|_e| db_error::ParseError)?;
+
+        let memo = hex_bytes(&memo_hex)
+            .map_err(|_e| db_error::ParseError)?;
+
+        if block_height < 0 {
+            return Err(db_error::ParseError);
+        }
+
+        Ok(TransferStxOp {
+            sender: sender,
+            recipient: recipient,
+            transfered_ustx: transfered_ustx,
+            memo: memo,
+
+            txid: txid,
+            vtxindex: vtxindex,
+            block_height: block_height as u64,
+            burn_header_hash: burn_header_hash
+        })
+    }
+}
+
+impl FromRow<StackStxOp> for StackStxOp {
+    fn from_row<'a>(row: &'a Row) -> Result<StackStxOp, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let vtxindex : u32 = row.get("vtxindex");
+        let block_height : i64 = row.get("block_height");
+        let burn_header_hash = BurnchainHeaderHash::from_column(row, "burn_header_hash")?;
+
+        let sender = StacksAddress::from_column(row, "sender")?;
+        let reward_addr = StacksAddress::from_column(row, "reward_addr")?;
+        let stacked_ustx_str : String = row.get("stacked_ustx");
+        let num_cycles : u8 = row.get("num_cycles");
+
+        let stacked_ustx = stacked_ustx_str.parse::<u128>()
+            .map_err(|_e| db_error::ParseError)?;
+
+        if block_height < 0 {
+            return Err(db_error::ParseError);
+        }
+
+        Ok(StackStxOp {
+            sender: sender,
+            reward_addr: reward_addr,
+            stacked_ustx: stacked_ustx,
+            num_cycles: num_cycles,
+
+            txid: txid,
+            vtxindex: vtxindex,
+            block_height: block_height as u64,
+            burn_header_hash: burn_header_hash
+        })
+    }
+}
+
 const BURNDB_SETUP : &'static [&'static str]= &[
     r#"
     PRAGMA foreign_keys = ON;
@@ -345,6 +421,7 @@ const BURNDB_SETUP : &'static [&'static str]= &[
         
         burn_fee TEXT NOT NULL,     -- use text to encode really big numbers
         input TEXT NOT NULL,        -- must match `address` in leader_keys
+        commit_outs TEXT NOT NULL,  -- JSON-encoded list of PoX reward addresses paid (empty if burnt)
 
         PRIMARY KEY(txid,burn_header_hash),
         FOREIGN KEY(burn_header_hash) REFERENCES snapshots(burn_header_hash)
@@ -369,6 +446,36 @@ const BURNDB_SETUP : &'static [&'static str]= &[
         FOREIGN KEY(burn_header_hash) REFERENCES snapshots(burn_header_hash)
     );"#,
     r#"
+    CREATE TABLE transfer_stx(
+        txid TEXT NOT NULL,
+        vtxindex INTEGER NOT NULL,
+        block_height INTEGER NOT NULL,
+        burn_header_hash TEXT NOT NULL,
+
+        sender TEXT NOT NULL,
+        recipient TEXT NOT NULL,
+        transfered_ustx TEXT NOT NULL,
+        memo TEXT,
+
+        PRIMARY KEY(txid,burn_header_hash),
+        FOREIGN KEY(burn_header_hash) REFERENCES snapshots(burn_header_hash)
+    );"#,
+    r#"
+    CREATE TABLE stack_stx(
+        txid TEXT NOT NULL,
+        vtxindex INTEGER NOT NULL,
+        block_height INTEGER NOT NULL,
+        burn_header_hash TEXT NOT NULL,
+
+        sender TEXT NOT NULL,
+        reward_addr TEXT NOT NULL,
+        stacked_ustx TEXT NOT NULL,
+        num_cycles INTEGER NOT NULL,
+
+        PRIMARY KEY(txid,burn_header_hash),
+        FOREIGN KEY(burn_header_hash) REFERENCES snapshots(burn_header_hash)
+    );"#,
+    r#"
     CREATE TABLE db_config(
         version TEXT NOT NULL
     );
@@ -481,6 +588,13 @@ impl BurnDB {
             BurnDB::instantiate(&mut conn, &index_path, first_block_height, first_burn_hash, first_burn_header_timestamp)?;
         }
         else {
+            // validate -- must have been created with this schema version
+            let num_matching = query_count(&conn, &"SELECT COUNT(*) FROM db_config WHERE version = ?1".to_string(), &[&CHAINSTATE_VERSION])?;
+            if num_matching != 1 {
+                error!("Invalid burn database: expected version = {}", CHAINSTATE_VERSION);
+                return Err(db_error::VersionMismatch);
+            }
+
             // validate -- must contain the given first block and first block hash 
             let snapshot_opt = BurnDB::get_block_snapshot(&conn, first_burn_hash)?;
             match snapshot_opt {
@@ -615,6 +729,16 @@ impl BurnDB {
                 info!("ACCEPTED({}) user burn support {} at {},{}", op.block_height, &op.txid, op.block_height, op.vtxindex);
                 BurnDB::insert_user_burn(tx, op)
                     .expect("FATAL: failed to store user burn support to Sqlite");
+            },
+            BlockstackOperationType::TransferStx(ref op) => {
+                info!("ACCEPTED({}) transfer stx {} at {},{}", op.block_height, &op.txid, op.block_height, op.vtxindex);
+                BurnDB::insert_transfer_stx(tx, op)
+                    .expect("FATAL: failed to store transfer stx to Sqlite");
+            },
+            BlockstackOperationType::StackStx(ref op) => {
+                info!("ACCEPTED({}) stack stx {} at {},{}", op.block_height, &op.txid, op.block_height, op.vtxindex);
+                BurnDB::insert_stack_stx(tx, op)
+                    .expect("FATAL: failed to store stack stx to Sqlite");
             }
         }
         Ok(())
@@ -665,6 +789,34 @@ impl BurnDB {
             }
         }
 
+        // STX transfer?
+        let transfer_stx_sql = "SELECT * FROM transfer_stx WHERE txid = ?1 LIMIT 1".to_string();
+
+        let transfer_stx_rows = query_rows::<TransferStxOp, _>(conn, &transfer_stx_sql, &args)?;
+        match transfer_stx_rows.len() {
+            0 => {},
+            1 => {
+                return Ok(Some(BlockstackOperationType::TransferStx(transfer_stx_rows[0].clone())));
+            },
+            _ => {
+                panic!("Multiple STX transfers with the same txid");
+            }
+        }
+
+        // stacking?
+        let stack_stx_sql = "SELECT * FROM stack_stx WHERE txid = ?1 LIMIT 1".to_string();
+
+        let stack_stx_rows = query_rows::<StackStxOp, _>(conn, &stack_stx_sql, &args)?;
+        match stack_stx_rows.len() {
+            0 => {},
+            1 => {
+                return Ok(Some(BlockstackOperationType::StackStx(stack_stx_rows[0].clone())));
+            },
+            _ => {
+                panic!("Multiple stack-stx ops with the same txid");
+            }
+        }
+
         Ok(None)
     }
 
@@ -844,14 +996,18 @@ impl BurnDB {
         let tx_input_str = serde_json::to_string(&block_commit.input)
             .map_err(|e| db_error::SerializationError(e))?;
 
+        // serialize PoX reward outputs to JSON
+        let commit_outs_str = serde_json::to_string(&block_commit.commit_outs)
+            .map_err(|e| db_error::SerializationError(e))?;
+
         // represent burn fee as TEXT 
         let burn_fee_str = format!("{}", block_commit.burn_fee);
 
-        tx.execute("INSERT INTO block_commits (txid, vtxindex, block_height, burn_header_hash, block_header_hash, new_seed, parent_block_ptr, parent_vtxindex, key_block_ptr, key_vtxindex, memo, burn_fee, input) \
-                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        tx.execute("INSERT INTO block_commits (txid, vtxindex, block_height, burn_header_hash, block_header_hash, new_seed, parent_block_ptr, parent_vtxindex, key_block_ptr, key_vtxindex, memo, burn_fee, input, commit_outs) \
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
                     &[&block_commit.txid, &block_commit.vtxindex as &dyn ToSql, &(block_commit.block_height as i64) as &dyn ToSql, &block_commit.burn_header_hash, 
                     &block_commit.block_header_hash, &block_commit.new_seed, &block_commit.parent_block_ptr as &dyn ToSql, &block_commit.parent_vtxindex as &dyn ToSql,
-                    &block_commit.key_block_ptr as &dyn ToSql, &block_commit.key_vtxindex as &dyn ToSql, &to_hex(&block_commit.memo[..]), &burn_fee_str as &dyn ToSql, &tx_input_str])
+                    &block_commit.key_block_ptr as &dyn ToSql, &block_commit.key_vtxindex as &dyn ToSql, &to_hex(&block_commit.memo[..]), &burn_fee_str as &dyn ToSql, &tx_input_str, &commit_outs_str])
             .map_err(db_error::SqliteError)?;
 
         Ok(())
@@ -878,6 +1034,40 @@ impl BurnDB {
         Ok(())
     }
     
+    /// Insert a burnchain STX transfer.
+    /// No validity checking will be done, beyond the usual ops checks.
+    pub fn insert_transfer_stx<'a>(tx: &mut BurnDBTx<'a>, op: &TransferStxOp) -> Result<(), db_error> {
+        assert!(op.block_height < BLOCK_HEIGHT_MAX);
+
+        // represent amount as TEXT
+        let transfered_ustx_str = format!("{}", op.transfered_ustx);
+
+        tx.execute("INSERT INTO transfer_stx (txid, vtxindex, block_height, burn_header_hash, sender, recipient, transfered_ustx, memo) \
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                   &[&op.txid, &op.vtxindex as &dyn ToSql, &(op.block_height as i64), &op.burn_header_hash,
+                   &op.sender.to_string(), &op.recipient.to_string(), &transfered_ustx_str, &to_hex(&op.memo[..])])
+            .map_err(db_error::SqliteError)?;
+
+        Ok(())
+    }
+
+    /// Insert a burnchain stack-stx operation.
+    /// No validity checking will be done, beyond the usual ops checks.
+    pub fn insert_stack_stx<'a>(tx: &mut BurnDBTx<'a>, op: &StackStxOp) -> Result<(), db_error> {
+        assert!(op.block_height < BLOCK_HEIGHT_MAX);
+
+        // represent amount as TEXT
+        let stacked_ustx_str = format!("{}", op.stacked_ustx);
+
+        tx.execute("INSERT INTO stack_stx (txid, vtxindex, block_height, burn_header_hash, sender, reward_addr, stacked_ustx, num_cycles) \
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                   &[&op.txid, &op.vtxindex as &dyn ToSql, &(op.block_height as i64), &op.burn_header_hash,
+                   &op.sender.to_string(), &op.reward_addr.to_string(), &stacked_ustx_str, &op.num_cycles])
+            .map_err(db_error::SqliteError)?;
+
+        Ok(())
+    }
+
     /// Get the first snapshot 
     pub fn get_first_block_snapshot(conn: &Connection) -> Result<BlockSnapshot, db_error> {
         let qry = "SELECT * FROM snapshots WHERE consensus_hash = ?1".to_string();
@@ -1088,6 +1278,58 @@ impl BurnDB {
         query_rows::<UserBurnSupportOp, _>(conn, &qry.to_string(), &args)
     }
 
+    /// Get all STX transfers that were accepted in a particular burnchain block.
+    /// Returns the list of transfers in order by vtxindex.
+    pub fn get_transfer_stx_ops(conn: &Connection, burn_header_hash: &BurnchainHeaderHash) -> Result<Vec<TransferStxOp>, db_error> {
+        let qry = "SELECT * FROM transfer_stx WHERE burn_header_hash = ?1 ORDER BY vtxindex ASC".to_string();
+        let args = [&burn_header_hash];
+        query_rows::<TransferStxOp, _>(conn, &qry, &args)
+    }
+
+    /// Get all stack-stx ops that were accepted in a particular burnchain block.
+    /// Returns the list of stack-stx ops in order by vtxindex.
+    pub fn get_stack_stx_ops(conn: &Connection, burn_header_hash: &BurnchainHeaderHash) -> Result<Vec<StackStxOp>, db_error> {
+        let qry = "SELECT * FROM stack_stx WHERE burn_header_hash = ?1 ORDER BY vtxindex ASC".to_string();
+        let args = [&burn_header_hash];
+        query_rows::<StackStxOp, _>(conn, &qry, &args)
+    }
+
+    /// Get all STX transfers and stack-stx ops in the burnchain blocks from parent_burn_header_hash
+    /// through tip_burn_header_hash, inclusive, in this fork.  These are the STX operations that a
+    /// Stacks block must apply if its parent was elected in parent_burn_header_hash and it was
+    /// elected in the child of tip_burn_header_hash.  If parent_burn_header_hash is not an
+    /// ancestor of the tip, then all ops back to the first burnchain block are returned.
+    /// Ops are returned in the order they were mined -- by block, then by vtxindex.
+    pub fn get_burnchain_stx_ops(conn: &Connection, parent_burn_header_hash: &BurnchainHeaderHash, tip_burn_header_hash: &BurnchainHeaderHash) -> Result<Vec<BlockstackOperationType>, db_error> {
+        let mut burn_header_hashes = vec![];
+        let mut cursor = tip_burn_header_hash.clone();
+        loop {
+            let sn = match BurnDB::get_block_snapshot(conn, &cursor)? {
+                Some(sn) => sn,
+                None => break
+            };
+            burn_header_hashes.push(sn.burn_header_hash.clone());
+            if sn.burn_header_hash == *parent_burn_header_hash {
+                break;
+            }
+            cursor = sn.parent_burn_header_hash;
+        }
+
+        let mut ops = vec![];
+        for burn_header_hash in burn_header_hashes.iter().rev() {
+            let mut block_ops = vec![];
+            for op in BurnDB::get_transfer_stx_ops(conn, burn_header_hash)?.drain(..) {
+                block_ops.push((op.vtxindex, BlockstackOperationType::TransferStx(op)));
+            }
+            for op in BurnDB::get_stack_stx_ops(conn, burn_header_hash)?.drain(..) {
+                block_ops.push((op.vtxindex, BlockstackOperationType::StackStx(op)));
+            }
+            block_ops.sort_by_key(|&(vtxindex, _)| vtxindex);
+            ops.extend(block_ops.drain(..).map(|(_, op)| op));
+        }
+        Ok(ops)
+    }
+
     /// Find out how any burn tokens were destroyed in a given block on a given fork.
     pub fn get_block_burn_amount<'a>(tx: &mut BurnDBTx<'a>, block_height: u64, tip_block_hash: &BurnchainHeaderHash) -> Result<u64, db_error> {
         assert!(block_height < BLOCK_HEIGHT_MAX);
@@ -1522,6 +1764,25 @@ mod tests {
         tx.commit().unwrap();
     }
 
+    #[test]
+    fn test_connect_version_mismatch() {
+        let path = "/tmp/test-blockstack-burndb-version-mismatch".to_string();
+        if fs::metadata(&path).is_ok() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+
+        let first_burn_hash = BurnchainHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+        {
+            let db = BurnDB::connect(&path, 123, &first_burn_hash, get_epoch_time_secs(), true).unwrap();
+            db.conn.execute("UPDATE db_config SET version = ?1", &[&"0.0.0.0"]).unwrap();
+        }
+
+        match BurnDB::connect(&path, 123, &first_burn_hash, get_epoch_time_secs(), true) {
+            Err(db_error::VersionMismatch) => {},
+            _ => panic!("Opened a burn database with a stale schema version")
+        }
+    }
+
     #[test]
     fn test_insert_leader_key() {
         let block_height = 123;
@@ -1630,6 +1891,7 @@ mod tests {
                 num_sigs: 1, 
                 hash_mode: AddressHashMode::SerializeP2PKH
             },
+            commit_outs: vec![],
 
             txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
             vtxindex: vtxindex,
@@ -2061,6 +2323,94 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_burnchain_stx_ops() {
+        let first_burn_hash = BurnchainHeaderHash::from_hex("10000000000000000000000000000000000000000000000000000000000000ff").unwrap();
+        let sender = StacksAddress { version: 26, bytes: Hash160([0x01; 20]) };
+        let recipient = StacksAddress { version: 26, bytes: Hash160([0x02; 20]) };
+
+        let mut db = BurnDB::connect_memory(0, &first_burn_hash).unwrap();
+        let mut tx = db.tx_begin().unwrap();
+        let mut last_snapshot = BurnDB::get_first_block_snapshot(&tx).unwrap();
+        let mut transfer_ops = vec![];
+        let mut stack_ops = vec![];
+        for i in 0..4 {
+            let burn_header_hash = BurnchainHeaderHash::from_bytes(&[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,(i+1) as u8]).unwrap();
+            let transfer_op = TransferStxOp {
+                sender: sender.clone(),
+                recipient: recipient.clone(),
+                transfered_ustx: (i + 1) as u128,
+                memo: vec![i as u8],
+
+                txid: Txid([(2*i + 1) as u8; 32]),
+                vtxindex: 1,
+                block_height: i + 1,
+                burn_header_hash: burn_header_hash.clone()
+            };
+            let stack_op = StackStxOp {
+                sender: sender.clone(),
+                reward_addr: recipient.clone(),
+                stacked_ustx: (1000 * (i + 1)) as u128,
+                num_cycles: 1,
+
+                txid: Txid([(2*i + 2) as u8; 32]),
+                vtxindex: 2,
+                block_height: i + 1,
+                burn_header_hash: burn_header_hash.clone()
+            };
+
+            let snapshot_row = BlockSnapshot {
+                block_height: i+1,
+                burn_header_timestamp: get_epoch_time_secs(),
+                burn_header_hash: burn_header_hash.clone(),
+                parent_burn_header_hash: last_snapshot.burn_header_hash.clone(),
+                consensus_hash: ConsensusHash::from_bytes(&[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,(i+1) as u8]).unwrap(),
+                ops_hash: OpsHash::from_bytes(&[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,i as u8]).unwrap(),
+                total_burn: i,
+                sortition: true,
+                sortition_hash: SortitionHash::initial(),
+                winning_block_txid: Txid::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap(),
+                winning_stacks_block_hash: BlockHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap(),
+                index_root: TrieHash::from_empty_data(),
+                num_sortitions: i+1,
+            };
+            let block_ops = vec![BlockstackOperationType::TransferStx(transfer_op.clone()), BlockstackOperationType::StackStx(stack_op.clone())];
+            let index_root = BurnDB::append_chain_tip_snapshot(&mut tx, &last_snapshot, &snapshot_row, &block_ops, &vec![]).unwrap();
+            last_snapshot = snapshot_row;
+            last_snapshot.index_root = index_root;
+
+            transfer_ops.push(transfer_op);
+            stack_ops.push(stack_op);
+        }
+
+        match BurnDB::get_burnchain_transaction(&tx, &transfer_ops[0].txid).unwrap() {
+            Some(BlockstackOperationType::TransferStx(op)) => assert_eq!(op, transfer_ops[0]),
+            x => panic!("Expected a transfer-stx op, got {:?}", &x)
+        }
+        match BurnDB::get_burnchain_transaction(&tx, &stack_ops[0].txid).unwrap() {
+            Some(BlockstackOperationType::StackStx(op)) => assert_eq!(op, stack_ops[0]),
+            x => panic!("Expected a stack-stx op, got {:?}", &x)
+        }
+
+        assert_eq!(BurnDB::get_transfer_stx_ops(&tx, &transfer_ops[2].burn_header_hash).unwrap(), vec![transfer_ops[2].clone()]);
+        assert_eq!(BurnDB::get_stack_stx_ops(&tx, &stack_ops[2].burn_header_hash).unwrap(), vec![stack_ops[2].clone()]);
+
+        // transfers and stacks are interleaved in the order they were mined
+        let mut all_ops = vec![];
+        for (transfer_op, stack_op) in transfer_ops.iter().zip(stack_ops.iter()) {
+            all_ops.push(BlockstackOperationType::TransferStx(transfer_op.clone()));
+            all_ops.push(BlockstackOperationType::StackStx(stack_op.clone()));
+        }
+
+        // parent through tip, inclusive, in order
+        let tip = last_snapshot.burn_header_hash.clone();
+        assert_eq!(BurnDB::get_burnchain_stx_ops(&tx, &transfer_ops[1].burn_header_hash, &tip).unwrap(), all_ops[2..].to_vec());
+        assert_eq!(BurnDB::get_burnchain_stx_ops(&tx, &tip, &tip).unwrap(), all_ops[6..].to_vec());
+
+        // parent not in this fork, so everything back to the first block
+        assert_eq!(BurnDB::get_burnchain_stx_ops(&tx, &BurnchainHeaderHash([0xfe; 32]), &tip).unwrap(), all_ops);
+    }
+
     #[test]
     fn get_block_burn_amount() {
         let block_height = 123;
@@ -2096,6 +2446,7 @@ mod tests {
                 num_sigs: 1, 
                 hash_mode: AddressHashMode::SerializeP2PKH
             },
+            commit_outs: vec![],
 
             txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
             vtxindex: vtxindex,
@@ -84,6 +84,7 @@ impl LeaderBlockCommitOp {
             memo: vec![0x00],
             burn_fee: burn_fee,
             input: input.clone(),
+            commit_outs: vec![],
             block_header_hash: block_header_hash.clone(),
 
             // to be filled in 
@@ -104,6 +105,7 @@ impl LeaderBlockCommitOp {
             memo: vec![],
             burn_fee: burn_fee,
             input: input.clone(),
+            commit_outs: vec![],
             block_header_hash: block_header_hash.clone(),
 
             // to be filled in
@@ -174,16 +176,19 @@ impl LeaderBlockCommitOp {
             return Err(op_error::InvalidInput);
         }
 
-        // outputs[0] should be the burn output
-        if !outputs[0].address.is_burn() {
-            // wrong burn output
-            warn!("Invalid tx: burn output missing (got {:?})", outputs[0]);
-            return Err(op_error::ParseError);
-        }
+        // outputs[0] should be either the burn output, or the PoX reward output.
+        // Whether or not it is the *right* output is determined by check().
+        let commit_outs =
+            if outputs[0].address.is_burn() {
+                vec![]
+            }
+            else {
+                vec![outputs[0].address.clone()]
+            };
 
         let burn_fee = outputs[0].amount;
         if burn_fee == 0 {
-            // didn't burn
+            // didn't burn or transfer
             warn!("Invalid tx: no burn quantity");
             return Err(op_error::ParseError);
         }
@@ -232,6 +237,7 @@ impl LeaderBlockCommitOp {
 
             burn_fee: burn_fee,
             input: inputs[0].clone(),
+            commit_outs: commit_outs,
 
             txid: tx.txid(),
             vtxindex: tx.vtxindex(),
@@ -246,7 +252,7 @@ impl BlockstackOperation for LeaderBlockCommitOp {
         LeaderBlockCommitOp::parse_from_tx(block_header.block_height, &block_header.block_hash, tx)
     }
         
-    fn check<'a>(&self, _burnchain: &Burnchain, block_header: &BurnchainBlockHeader, tx: &mut BurnDBTx<'a>) -> Result<(), op_error> {
+    fn check<'a>(&self, burnchain: &Burnchain, block_header: &BurnchainBlockHeader, tx: &mut BurnDBTx<'a>) -> Result<(), op_error> {
         let leader_key_block_height = self.key_block_ptr as u64;
         let parent_block_height = self.parent_block_ptr as u64;
         
@@ -361,6 +367,18 @@ impl BlockstackOperation for LeaderBlockCommitOp {
             return Err(op_error::BlockCommitBadInput);
         }
 
+        /////////////////////////////////////////////////////////////////////////////////////
+        // This LeaderBlockCommit must either burn, or pay a single PoX reward address.  Whether
+        // or not it paid the *right* one depends on the Stacks chain state its block builds on,
+        // so that's checked when the block is processed (see
+        // StacksChainState::process_pox_reward_slot()).
+        /////////////////////////////////////////////////////////////////////////////////////
+
+        if self.commit_outs.len() > 1 {
+            warn!("Invalid block commit: paid {} PoX reward addresses", self.commit_outs.len());
+            return Err(op_error::BlockCommitBadOutputs);
+        }
+
         Ok(())
     }
 }
@@ -393,7 +411,7 @@ mod tests {
     };
 
     use util::vrf::VRFPublicKey;
-    use util::hash::hex_bytes;
+    use util::hash::{hex_bytes, Hash160};
     use util::log;
     use util::get_epoch_time_secs;
     
@@ -449,11 +467,12 @@ mod tests {
                         num_sigs: 1, 
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: vtxindex,
                     block_height: block_height,
-                    burn_header_hash: burn_header_hash,
+                    burn_header_hash: burn_header_hash.clone(),
                 })
             },
             OpFixture {
@@ -462,9 +481,34 @@ mod tests {
                 result: None,
             },
             OpFixture {
-                // invalid -- wrong burn address
+                // valid -- pays a PoX reward address instead of burning
                 txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006b483045022100e25f5f9f660339cd665caba231d5bdfc3f0885bcc0b3f85dc35564058c9089d702206aa142ea6ccd89e56fdc0743cdcf3a2744e133f335e255e9370e4f8a6d0f6ffd012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d000000000030000000000000000536a4c5069645b222222222222222222222222222222222222222222222222222222222222222233333333333333333333333333333333333333333333333333333333333333334041424350516061626370718039300000000000001976a914000000000000000000000000000000000000000188aca05b0000000000001976a9140be3e286a15ea85882761618e366586b5574100d88ac00000000".to_string(),
-                result: None,
+                result: Some(LeaderBlockCommitOp {
+                    block_header_hash: BlockHeaderHash::from_bytes(&hex_bytes("2222222222222222222222222222222222222222222222222222222222222222").unwrap()).unwrap(),
+                    new_seed: VRFSeed::from_bytes(&hex_bytes("3333333333333333333333333333333333333333333333333333333333333333").unwrap()).unwrap(),
+                    parent_block_ptr: 0x40414243,
+                    parent_vtxindex: 0x5051,
+                    key_block_ptr: 0x60616263,
+                    key_vtxindex: 0x7071,
+                    memo: vec![0x80],
+
+                    burn_fee: 12345,
+                    input: BurnchainSigner {
+                        public_keys: vec![
+                            StacksPublicKey::from_hex("02d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0").unwrap(),
+                        ],
+                        num_sigs: 1,
+                        hash_mode: AddressHashMode::SerializeP2PKH
+                    },
+                    commit_outs: vec![
+                        StacksAddress::from_bitcoin_address(&BitcoinAddress::from_scriptpubkey(BitcoinNetworkType::Testnet, &hex_bytes("76a914000000000000000000000000000000000000000188ac").unwrap()).unwrap())
+                    ],
+
+                    txid: Txid::from_bytes_be(&hex_bytes("f1658040d0cb93e388752b2eb6e9eca89a8f4655c1da1fd90ea8561c1beb5de9").unwrap()).unwrap(),
+                    vtxindex: vtxindex,
+                    block_height: block_height,
+                    burn_header_hash: burn_header_hash.clone(),
+                })
             },
             OpFixture {
                 // invalid -- bad OP_RETURN (missing memo)
@@ -547,7 +591,8 @@ mod tests {
             consensus_hash_lifetime: 24,
             stable_confirmations: 7,
             first_block_height: first_block_height,
-            first_block_hash: first_burn_hash.clone()
+            first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5
         };
         
         let leader_key_1 = LeaderKeyRegisterOp { 
@@ -592,6 +637,7 @@ mod tests {
                 num_sigs: 1, 
                 hash_mode: AddressHashMode::SerializeP2PKH
             },
+            commit_outs: vec![],
 
             txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
             vtxindex: 444,
@@ -684,6 +730,7 @@ mod tests {
                         num_sigs: 1, 
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: 444,
@@ -711,6 +758,7 @@ mod tests {
                         num_sigs: 1,
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: 444,
@@ -738,6 +786,7 @@ mod tests {
                         num_sigs: 1,
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: 445,
@@ -765,6 +814,7 @@ mod tests {
                         num_sigs: 1,
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: 445,
@@ -792,6 +842,7 @@ mod tests {
                         num_sigs: 1,
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: 445,
@@ -819,6 +870,7 @@ mod tests {
                         num_sigs: 1,
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: 445,
@@ -846,6 +898,7 @@ mod tests {
                         num_sigs: 1,
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: 445,
@@ -873,6 +926,7 @@ mod tests {
                         num_sigs: 1,
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: 445,
@@ -900,6 +954,7 @@ mod tests {
                         num_sigs: 1,
                         hash_mode: AddressHashMode::SerializeP2PKH
                     },
+                    commit_outs: vec![],
 
                     txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
                     vtxindex: 445,
@@ -922,6 +977,69 @@ mod tests {
             };
             assert_eq!(fixture.res, fixture.op.check(&burnchain, &header, &mut tx));
         }
+
+        // block 126 starts reward cycle 1.  Whether a commit in it pays the right reward address
+        // is checked when its block is processed, so any single reward address is accepted here.
+        let reward_addr = StacksAddress { version: 26, bytes: Hash160([0x01; 20]) };
+        let other_addr = StacksAddress { version: 26, bytes: Hash160([0x02; 20]) };
+
+        let pox_commit = |commit_outs: Vec<StacksAddress>| {
+            LeaderBlockCommitOp {
+                block_header_hash: BlockHeaderHash::from_bytes(&hex_bytes("2222222222222222222222222222222222222222222222222222222222222222").unwrap()).unwrap(),
+                new_seed: VRFSeed::from_bytes(&hex_bytes("3333333333333333333333333333333333333333333333333333333333333333").unwrap()).unwrap(),
+                parent_block_ptr: 125,
+                parent_vtxindex: 444,
+                key_block_ptr: 124,
+                key_vtxindex: 457,
+                memo: vec![0x80],
+
+                burn_fee: 12345,
+                input: BurnchainSigner {
+                    public_keys: vec![
+                        StacksPublicKey::from_hex("02d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0").unwrap(),
+                    ],
+                    num_sigs: 1,
+                    hash_mode: AddressHashMode::SerializeP2PKH
+                },
+                commit_outs: commit_outs,
+
+                txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
+                vtxindex: 445,
+                block_height: 126,
+                burn_header_hash: block_126_hash.clone(),
+            }
+        };
+
+        let pox_fixtures = vec![
+            CheckFixture {
+                // accept -- burns
+                op: pox_commit(vec![]),
+                res: Ok(())
+            },
+            CheckFixture {
+                // accept -- pays a reward address
+                op: pox_commit(vec![reward_addr.clone()]),
+                res: Ok(())
+            },
+            CheckFixture {
+                // reject -- pays more than one reward address
+                op: pox_commit(vec![reward_addr.clone(), other_addr.clone()]),
+                res: Err(op_error::BlockCommitBadOutputs)
+            }
+        ];
+
+        for fixture in pox_fixtures {
+            let mut tx = db.tx_begin().unwrap();
+            let header = BurnchainBlockHeader {
+                block_height: fixture.op.block_height,
+                block_hash: fixture.op.burn_header_hash.clone(),
+                parent_block_hash: fixture.op.burn_header_hash.clone(),
+                num_txs: 1,
+                parent_index_root: tip_index_root.clone(),
+                timestamp: get_epoch_time_secs()
+            };
+            assert_eq!(fixture.res, fixture.op.check(&burnchain, &header, &mut tx));
+        }
     }
 }
 
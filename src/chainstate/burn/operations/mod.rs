@@ -22,6 +22,8 @@
 pub mod leader_key_register;
 pub mod leader_block_commit;
 pub mod user_burn_support;
+pub mod transfer_stx;
+pub mod stack_stx;
 
 use std::fmt;
 use std::error;
@@ -52,6 +54,10 @@ use chainstate::burn::Opcodes;
 use chainstate::burn::VRFSeed;
 use chainstate::stacks::StacksAddress;
 use chainstate::stacks::index::TrieHash;
+use chainstate::stacks::{
+    C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+    C32_ADDRESS_VERSION_MAINNET_MULTISIG
+};
 
 use util::secp256k1::MessageSignature;
 use util::vrf::VRFPublicKey;
@@ -70,6 +76,7 @@ pub enum Error {
     BlockCommitLeaderKeyAlreadyUsed,
     BlockCommitNoParent,
     BlockCommitBadInput,
+    BlockCommitBadOutputs,
     
     // all the things that can go wrong with leader key register
     LeaderKeyAlreadyRegistered,
@@ -78,6 +85,14 @@ pub enum Error {
     // all the things that can go wrong with user burn supports
     UserBurnSupportBadConsensusHash,
     UserBurnSupportNoLeaderKey,
+
+    // all the things that can go wrong with burnchain STX transfers
+    TransferStxMustBePositive,
+    TransferStxSelfSend,
+
+    // all the things that can go wrong with burnchain stacking
+    StackStxBelowThreshold,
+    StackStxInvalidCycles,
 }
 
 impl fmt::Display for Error {
@@ -92,12 +107,19 @@ impl fmt::Display for Error {
             Error::BlockCommitLeaderKeyAlreadyUsed => write!(f, "Block commit register key already used"),
             Error::BlockCommitNoParent => write!(f, "Block commit parent does not exist"),
             Error::BlockCommitBadInput => write!(f, "Block commit tx input does not match register key tx output"),
+            Error::BlockCommitBadOutputs => write!(f, "Block commit does not pay the expected PoX reward address, or burn"),
 
             Error::LeaderKeyAlreadyRegistered => write!(f, "Leader key has already been registered"),
             Error::LeaderKeyBadConsensusHash => write!(f, "Leader key has an invalid consensus hash"),
 
             Error::UserBurnSupportBadConsensusHash => write!(f, "User burn support has an invalid consensus hash"),
             Error::UserBurnSupportNoLeaderKey => write!(f, "User burn support does not match a registered leader key"),
+
+            Error::TransferStxMustBePositive => write!(f, "Transfer STX must be positive amount"),
+            Error::TransferStxSelfSend => write!(f, "Transfer STX must not send to self"),
+
+            Error::StackStxBelowThreshold => write!(f, "Stack STX amount is below the stacking threshold"),
+            Error::StackStxInvalidCycles => write!(f, "Stack STX must lock for a valid number of reward cycles"),
         }
     }
 }
@@ -114,12 +136,19 @@ impl error::Error for Error {
             Error::BlockCommitLeaderKeyAlreadyUsed => None,
             Error::BlockCommitNoParent => None,
             Error::BlockCommitBadInput => None,
+            Error::BlockCommitBadOutputs => None,
 
             Error::LeaderKeyAlreadyRegistered => None,
             Error::LeaderKeyBadConsensusHash => None,
 
             Error::UserBurnSupportBadConsensusHash => None,
             Error::UserBurnSupportNoLeaderKey => None,
+
+            Error::TransferStxMustBePositive => None,
+            Error::TransferStxSelfSend => None,
+
+            Error::StackStxBelowThreshold => None,
+            Error::StackStxInvalidCycles => None,
         }
     }
 }
@@ -134,8 +163,9 @@ pub struct LeaderBlockCommitOp {
     pub key_vtxindex: u16,                  // offset in the block where the leader key can be found
     pub memo: Vec<u8>,                      // extra unused byte
 
-    pub burn_fee: u64,                      // how many burn tokens (e.g. satoshis) were destroyed to produce this block
+    pub burn_fee: u64,                      // how many burn tokens (e.g. satoshis) were destroyed or transferred to produce this block
     pub input: BurnchainSigner,             // burn chain keys that must match the key registration
+    pub commit_outs: Vec<StacksAddress>,    // PoX reward addresses paid by this commit (empty if the tokens were burnt)
 
     // common to all transactions
     pub txid: Txid,                         // transaction ID
@@ -175,17 +205,47 @@ pub struct UserBurnSupportOp {
     pub burn_header_hash: BurnchainHeaderHash,   // hash of burnchain block with this tx
 }
 
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct TransferStxOp {
+    pub sender: StacksAddress,              // hash of the public key(s) that signed the burnchain tx
+    pub recipient: StacksAddress,           // who receives the STX
+    pub transfered_ustx: u128,              // how many microSTX to send
+    pub memo: Vec<u8>,                      // extra bytes in the op-return
+
+    // common to all transactions
+    pub txid: Txid,                         // transaction ID
+    pub vtxindex: u32,                      // index in the block where this tx occurs
+    pub block_height: u64,                  // block height at which this tx occurs
+    pub burn_header_hash: BurnchainHeaderHash,   // hash of burnchain block with this tx
+}
+
+#[derive(Debug, PartialEq, Clone, Eq)]
+pub struct StackStxOp {
+    pub sender: StacksAddress,              // hash of the public key(s) that signed the burnchain tx
+    pub reward_addr: StacksAddress,         // PoX reward address for the stacked STX
+    pub stacked_ustx: u128,                 // how many microSTX to lock
+    pub num_cycles: u8,                     // how many reward cycles to lock them for
+
+    // common to all transactions
+    pub txid: Txid,                         // transaction ID
+    pub vtxindex: u32,                      // index in the block where this tx occurs
+    pub block_height: u64,                  // block height at which this tx occurs
+    pub burn_header_hash: BurnchainHeaderHash,   // hash of burnchain block with this tx
+}
+
 pub trait BlockstackOperation {
     fn check<'a>(&self, burnchain: &Burnchain, block_header: &BurnchainBlockHeader, tx: &mut BurnDBTx<'a>) -> Result<(), Error>;
     fn from_tx(block_header: &BurnchainBlockHeader, tx: &BurnchainTransaction) -> Result<Self, Error>
         where Self: Sized;
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BlockstackOperationType {
     LeaderKeyRegister(LeaderKeyRegisterOp),
     LeaderBlockCommit(LeaderBlockCommitOp),
-    UserBurnSupport(UserBurnSupportOp)
+    UserBurnSupport(UserBurnSupportOp),
+    TransferStx(TransferStxOp),
+    StackStx(StackStxOp)
 }
 
 impl BlockstackOperationType {
@@ -193,7 +253,9 @@ impl BlockstackOperationType {
         match *self {
             BlockstackOperationType::LeaderKeyRegister(_) => Opcodes::LeaderKeyRegister,
             BlockstackOperationType::LeaderBlockCommit(_) => Opcodes::LeaderBlockCommit,
-            BlockstackOperationType::UserBurnSupport(_) => Opcodes::UserBurnSupport
+            BlockstackOperationType::UserBurnSupport(_) => Opcodes::UserBurnSupport,
+            BlockstackOperationType::TransferStx(_) => Opcodes::TransferStx,
+            BlockstackOperationType::StackStx(_) => Opcodes::StackStx
         }
     }
 
@@ -201,7 +263,9 @@ impl BlockstackOperationType {
         match *self {
             BlockstackOperationType::LeaderKeyRegister(ref data) => data.txid.clone(),
             BlockstackOperationType::LeaderBlockCommit(ref data) => data.txid.clone(),
-            BlockstackOperationType::UserBurnSupport(ref data) => data.txid.clone()
+            BlockstackOperationType::UserBurnSupport(ref data) => data.txid.clone(),
+            BlockstackOperationType::TransferStx(ref data) => data.txid.clone(),
+            BlockstackOperationType::StackStx(ref data) => data.txid.clone()
         }
     }
 
@@ -210,6 +274,8 @@ impl BlockstackOperationType {
             BlockstackOperationType::LeaderKeyRegister(ref data) => data.vtxindex,
             BlockstackOperationType::LeaderBlockCommit(ref data) => data.vtxindex,
             BlockstackOperationType::UserBurnSupport(ref data) => data.vtxindex,
+            BlockstackOperationType::TransferStx(ref data) => data.vtxindex,
+            BlockstackOperationType::StackStx(ref data) => data.vtxindex,
         }
     }
 
@@ -217,7 +283,9 @@ impl BlockstackOperationType {
         match *self {
             BlockstackOperationType::LeaderKeyRegister(ref data) => data.block_height,
             BlockstackOperationType::LeaderBlockCommit(ref data) => data.block_height,
-            BlockstackOperationType::UserBurnSupport(ref data) => data.block_height
+            BlockstackOperationType::UserBurnSupport(ref data) => data.block_height,
+            BlockstackOperationType::TransferStx(ref data) => data.block_height,
+            BlockstackOperationType::StackStx(ref data) => data.block_height
         }
     }
 
@@ -225,7 +293,9 @@ impl BlockstackOperationType {
         match *self {
             BlockstackOperationType::LeaderKeyRegister(ref data) => data.burn_header_hash.clone(),
             BlockstackOperationType::LeaderBlockCommit(ref data) => data.burn_header_hash.clone(),
-            BlockstackOperationType::UserBurnSupport(ref data) => data.burn_header_hash.clone()
+            BlockstackOperationType::UserBurnSupport(ref data) => data.burn_header_hash.clone(),
+            BlockstackOperationType::TransferStx(ref data) => data.burn_header_hash.clone(),
+            BlockstackOperationType::StackStx(ref data) => data.burn_header_hash.clone()
         }
     }
 }
@@ -236,7 +306,9 @@ impl fmt::Display for BlockstackOperationType {
         match *self {
             BlockstackOperationType::LeaderKeyRegister(ref leader_key_register) => fmt::Display::fmt(&format!("{:?}", leader_key_register), f),
             BlockstackOperationType::LeaderBlockCommit(ref leader_block_commit) => fmt::Display::fmt(&format!("{:?}", leader_block_commit), f),
-            BlockstackOperationType::UserBurnSupport(ref user_burn_support) => fmt::Display::fmt(&format!("{:?}", user_burn_support), f)
+            BlockstackOperationType::UserBurnSupport(ref user_burn_support) => fmt::Display::fmt(&format!("{:?}", user_burn_support), f),
+            BlockstackOperationType::TransferStx(ref transfer_stx) => fmt::Display::fmt(&format!("{:?}", transfer_stx), f),
+            BlockstackOperationType::StackStx(ref stack_stx) => fmt::Display::fmt(&format!("{:?}", stack_stx), f)
         }
     }
 }
@@ -250,3 +322,25 @@ pub fn parse_u32_from_be(bytes: &[u8]) -> Option<u32> {
 pub fn parse_u16_from_be(bytes: &[u8]) -> Option<u16> {
     bytes.try_into().ok().map(u16::from_be_bytes)
 }
+
+pub fn parse_u128_from_be(bytes: &[u8]) -> Option<u128> {
+    bytes.try_into().ok().map(u128::from_be_bytes)
+}
+
+/// Get the Stacks address of a burnchain transaction's signer.  Burnchain signers don't carry a
+/// network, so the address is put on the same network as network_addr (i.e. one of the
+/// transaction's outputs).
+pub fn parse_signer_address(signer: &BurnchainSigner, network_addr: &StacksAddress) -> StacksAddress {
+    let version =
+        if network_addr.version == C32_ADDRESS_VERSION_MAINNET_SINGLESIG || network_addr.version == C32_ADDRESS_VERSION_MAINNET_MULTISIG {
+            signer.hash_mode.to_version_mainnet()
+        }
+        else {
+            signer.hash_mode.to_version_testnet()
+        };
+
+    StacksAddress {
+        version: version,
+        bytes: Hash160::from_bytes(&signer.to_address_bits()).expect("FATAL: burnchain signer address is not a Hash160")
+    }
+}
@@ -0,0 +1,368 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use chainstate::burn::operations::Error as op_error;
+use chainstate::burn::Opcodes;
+
+use chainstate::burn::operations::{
+    StackStxOp,
+    BlockstackOperation,
+    parse_u128_from_be,
+    parse_signer_address
+};
+
+use chainstate::burn::db::burndb::BurnDBTx;
+
+use burnchains::BurnchainTransaction;
+use burnchains::Txid;
+use burnchains::BurnchainHeaderHash;
+use burnchains::BurnchainBlockHeader;
+use burnchains::Burnchain;
+
+use chainstate::stacks::StacksAddress;
+use chainstate::stacks::db::pox::{
+    POX_STACKING_THRESHOLD,
+    POX_MAX_LOCK_PERIOD
+};
+
+use util::log;
+
+struct ParsedData {
+    pub stacked_ustx: u128,
+    pub num_cycles: u8
+}
+
+impl StackStxOp {
+    #[cfg(test)]
+    pub fn new(sender: &StacksAddress, reward_addr: &StacksAddress, stacked_ustx: u128, num_cycles: u8) -> StackStxOp {
+        StackStxOp {
+            sender: sender.clone(),
+            reward_addr: reward_addr.clone(),
+            stacked_ustx: stacked_ustx,
+            num_cycles: num_cycles,
+
+            // will be filled in
+            txid: Txid([0u8; 32]),
+            vtxindex: 0,
+            block_height: 0,
+            burn_header_hash: BurnchainHeaderHash([0u8; 32]),
+        }
+    }
+
+    fn parse_data(data: &Vec<u8>) -> Option<ParsedData> {
+        /*
+            Wire format:
+
+            0      2  3                             19        20
+            |------|--|------------------------------|---------|
+             magic  op   uSTX to lock (big-endian)    num cycles
+
+             Note that `data` is missing the first 3 bytes -- the magic and op have been stripped
+        */
+        if data.len() < 17 {
+            // too short to have an amount and a lock period
+            warn!("STACK_STX payload is malformed ({} bytes)", data.len());
+            return None;
+        }
+
+        let stacked_ustx = parse_u128_from_be(&data[0..16]).expect("FATAL: invalid byte slice for stacked amount");
+        let num_cycles = data[16];
+
+        Some(ParsedData {
+            stacked_ustx,
+            num_cycles
+        })
+    }
+
+    fn parse_from_tx(block_height: u64, block_hash: &BurnchainHeaderHash, tx: &BurnchainTransaction) -> Result<StackStxOp, op_error> {
+        // can't be too careful...
+        let inputs = tx.get_signers();
+        let outputs = tx.get_recipients();
+
+        if inputs.len() == 0 {
+            test_debug!("Invalid tx: inputs: {}, outputs: {}", inputs.len(), outputs.len());
+            return Err(op_error::InvalidInput);
+        }
+
+        if outputs.len() < 1 {
+            test_debug!("Invalid tx: inputs: {}, outputs: {}", inputs.len(), outputs.len());
+            return Err(op_error::InvalidInput);
+        }
+
+        if tx.opcode() != Opcodes::StackStx as u8 {
+            test_debug!("Invalid tx: invalid opcode {}", tx.opcode());
+            return Err(op_error::InvalidInput);
+        }
+
+        let data = match StackStxOp::parse_data(&tx.data()) {
+            Some(data) => {
+                data
+            },
+            None => {
+                test_debug!("Invalid tx data");
+                return Err(op_error::ParseError);
+            }
+        };
+
+        // the first output is the PoX reward address, and the first input is the stacker
+        let reward_addr = outputs[0].address.clone();
+        let sender = parse_signer_address(&inputs[0], &reward_addr);
+
+        Ok(StackStxOp {
+            sender: sender,
+            reward_addr: reward_addr,
+            stacked_ustx: data.stacked_ustx,
+            num_cycles: data.num_cycles,
+
+            txid: tx.txid(),
+            vtxindex: tx.vtxindex(),
+            block_height: block_height,
+            burn_header_hash: block_hash.clone(),
+        })
+    }
+}
+
+impl BlockstackOperation for StackStxOp {
+    fn from_tx(block_header: &BurnchainBlockHeader, tx: &BurnchainTransaction) -> Result<StackStxOp, op_error> {
+        StackStxOp::parse_from_tx(block_header.block_height, &block_header.block_hash, tx)
+    }
+
+    fn check<'a>(&self, _burnchain: &Burnchain, _block_header: &BurnchainBlockHeader, _tx: &mut BurnDBTx<'a>) -> Result<(), op_error> {
+        // whether or not the sender has enough unlocked STX is decided when the op is applied to
+        // the chainstate, since the burn DB does not know account balances.
+        if self.num_cycles < 1 || (self.num_cycles as u64) > POX_MAX_LOCK_PERIOD {
+            warn!("Invalid stack-stx: lock period must be between 1 and {} reward cycles (got {})", POX_MAX_LOCK_PERIOD, self.num_cycles);
+            return Err(op_error::StackStxInvalidCycles);
+        }
+
+        if self.stacked_ustx < (POX_STACKING_THRESHOLD as u128) {
+            warn!("Invalid stack-stx: must stack at least {} microSTX (got {})", POX_STACKING_THRESHOLD, self.stacked_ustx);
+            return Err(op_error::StackStxBelowThreshold);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burnchains::bitcoin::address::BitcoinAddress;
+    use burnchains::bitcoin::blocks::BitcoinBlockParser;
+    use burnchains::bitcoin::BitcoinNetworkType;
+    use burnchains::BLOCKSTACK_MAGIC_MAINNET;
+
+    use deps::bitcoin::network::serialize::deserialize;
+    use deps::bitcoin::blockdata::transaction::Transaction;
+
+    use chainstate::burn::db::burndb::BurnDB;
+    use chainstate::stacks::index::TrieHash;
+    use chainstate::stacks::StacksPublicKey;
+    use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+
+    use address::AddressHashMode;
+
+    use util::hash::hex_bytes;
+    use util::get_epoch_time_secs;
+
+    struct OpFixture {
+        txstr: String,
+        result: Option<StackStxOp>,
+    }
+
+    struct CheckFixture {
+        op: StackStxOp,
+        res: Result<(), op_error>
+    }
+
+    fn make_tx(hex_str: &str) -> Result<Transaction, &'static str> {
+        let tx_bin = hex_bytes(hex_str)
+            .map_err(|_e| "failed to decode hex string")?;
+        let tx = deserialize(&tx_bin.to_vec())
+            .map_err(|_e| "failed to deserialize")?;
+        Ok(tx)
+    }
+
+    fn test_sender() -> StacksAddress {
+        StacksAddress::from_public_keys(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, &AddressHashMode::SerializeP2PKH, 1,
+                                        &vec![StacksPublicKey::from_hex("02d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0").unwrap()]).unwrap()
+    }
+
+    fn test_reward_addr() -> StacksAddress {
+        StacksAddress::from_bitcoin_address(&BitcoinAddress::from_scriptpubkey(BitcoinNetworkType::Testnet, &hex_bytes("76a9140be3e286a15ea85882761618e366586b5574100d88ac").unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        let vtxindex = 1;
+        let block_height = 694;
+        let burn_header_hash = BurnchainHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+
+        let tx_fixtures: Vec<OpFixture> = vec![
+            OpFixture {
+                txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006a47304402203a176d95803e8d51e7884d38750322c4bfa55307a71291ef8db65191edd665f1022056f5d1720d1fde8d6a163c79f73f22f874ef9e186e98e5b60fa8ac64d298e77a012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d000000000020000000000000000166a14696478000000000000000000000000000f42400610270000000000001976a9140be3e286a15ea85882761618e366586b5574100d88ac00000000".to_string(),
+                result: Some(StackStxOp {
+                    sender: test_sender(),
+                    reward_addr: test_reward_addr(),
+                    stacked_ustx: 1000000,
+                    num_cycles: 6,
+
+                    txid: Txid::from_bytes_be(&hex_bytes("74e215f4331ff8ed7ee9c9b148d0e346a7df1c63dc1be9f7e4ad312b3b3b0621").unwrap()).unwrap(),
+                    vtxindex: vtxindex,
+                    block_height: block_height,
+                    burn_header_hash: burn_header_hash.clone(),
+                })
+            },
+            OpFixture {
+                // too short
+                txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006a47304402203a176d95803e8d51e7884d38750322c4bfa55307a71291ef8db65191edd665f1022056f5d1720d1fde8d6a163c79f73f22f874ef9e186e98e5b60fa8ac64d298e77a012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d000000000020000000000000000156a13696478000000000000000000000000000f424010270000000000001976a9140be3e286a15ea85882761618e366586b5574100d88ac00000000".to_string(),
+                result: None,
+            },
+            OpFixture {
+                // not enough outputs
+                txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006a47304402203a176d95803e8d51e7884d38750322c4bfa55307a71291ef8db65191edd665f1022056f5d1720d1fde8d6a163c79f73f22f874ef9e186e98e5b60fa8ac64d298e77a012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d000000000010000000000000000166a14696478000000000000000000000000000f42400600000000".to_string(),
+                result: None,
+            },
+            OpFixture {
+                // wrong opcode
+                txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006a47304402203a176d95803e8d51e7884d38750322c4bfa55307a71291ef8db65191edd665f1022056f5d1720d1fde8d6a163c79f73f22f874ef9e186e98e5b60fa8ac64d298e77a012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d000000000020000000000000000166a14696424000000000000000000000000000f42400610270000000000001976a9140be3e286a15ea85882761618e366586b5574100d88ac00000000".to_string(),
+                result: None,
+            }
+        ];
+
+        let parser = BitcoinBlockParser::new(BitcoinNetworkType::Testnet, BLOCKSTACK_MAGIC_MAINNET);
+
+        for tx_fixture in tx_fixtures {
+            let tx = make_tx(&tx_fixture.txstr).unwrap();
+            let header = match tx_fixture.result {
+                Some(ref op) => {
+                    BurnchainBlockHeader {
+                        block_height: op.block_height,
+                        block_hash: op.burn_header_hash.clone(),
+                        parent_block_hash: op.burn_header_hash.clone(),
+                        num_txs: 1,
+                        parent_index_root: TrieHash::from_empty_data(),
+                        timestamp: get_epoch_time_secs()
+                    }
+                },
+                None => {
+                    BurnchainBlockHeader {
+                        block_height: 0,
+                        block_hash: BurnchainHeaderHash([0u8; 32]),
+                        parent_block_hash: BurnchainHeaderHash([0u8; 32]),
+                        num_txs: 0,
+                        parent_index_root: TrieHash::from_empty_data(),
+                        timestamp: get_epoch_time_secs()
+                    }
+                }
+            };
+            let burnchain_tx = BurnchainTransaction::Bitcoin(parser.parse_tx(&tx, vtxindex as usize).unwrap());
+            let op = StackStxOp::from_tx(&header, &burnchain_tx);
+
+            match (op, tx_fixture.result) {
+                (Ok(parsed_tx), Some(result)) => {
+                    assert_eq!(parsed_tx, result);
+                },
+                (Err(_e), None) => {},
+                (Ok(_parsed_tx), None) => {
+                    test_debug!("Parsed a tx when we should not have: {}", tx_fixture.txstr);
+                    assert!(false);
+                },
+                (Err(_e), Some(_result)) => {
+                    test_debug!("Did not parse a tx when we should have: {}", tx_fixture.txstr);
+                    assert!(false);
+                }
+            };
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let first_block_height = 121;
+        let first_burn_hash = BurnchainHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000123").unwrap();
+        let block_122_hash = BurnchainHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000002").unwrap();
+
+        let burnchain = Burnchain {
+            peer_version: 0x012345678,
+            network_id: 0x9abcdef0,
+            chain_name: "bitcoin".to_string(),
+            network_name: "testnet".to_string(),
+            working_dir: "/nope".to_string(),
+            consensus_hash_lifetime: 24,
+            stable_confirmations: 7,
+            first_block_height: first_block_height,
+            first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5
+        };
+
+        let mut db = BurnDB::connect_memory(first_block_height, &first_burn_hash).unwrap();
+
+        let mut op_no_cycles = StackStxOp::new(&test_sender(), &test_reward_addr(), POX_STACKING_THRESHOLD as u128, 0);
+        op_no_cycles.block_height = 122;
+        op_no_cycles.burn_header_hash = block_122_hash.clone();
+
+        let mut op_too_many_cycles = StackStxOp::new(&test_sender(), &test_reward_addr(), POX_STACKING_THRESHOLD as u128, (POX_MAX_LOCK_PERIOD + 1) as u8);
+        op_too_many_cycles.block_height = 122;
+        op_too_many_cycles.burn_header_hash = block_122_hash.clone();
+
+        let mut op_too_little = StackStxOp::new(&test_sender(), &test_reward_addr(), (POX_STACKING_THRESHOLD - 1) as u128, 1);
+        op_too_little.block_height = 122;
+        op_too_little.burn_header_hash = block_122_hash.clone();
+
+        let mut op_ok = StackStxOp::new(&test_sender(), &test_reward_addr(), POX_STACKING_THRESHOLD as u128, POX_MAX_LOCK_PERIOD as u8);
+        op_ok.block_height = 122;
+        op_ok.burn_header_hash = block_122_hash.clone();
+
+        let check_fixtures = vec![
+            CheckFixture {
+                // reject -- must lock for at least one cycle
+                op: op_no_cycles,
+                res: Err(op_error::StackStxInvalidCycles),
+            },
+            CheckFixture {
+                // reject -- locked for too long
+                op: op_too_many_cycles,
+                res: Err(op_error::StackStxInvalidCycles),
+            },
+            CheckFixture {
+                // reject -- below the stacking threshold
+                op: op_too_little,
+                res: Err(op_error::StackStxBelowThreshold),
+            },
+            CheckFixture {
+                // accept
+                op: op_ok,
+                res: Ok(())
+            }
+        ];
+
+        for fixture in check_fixtures {
+            let mut tx = db.tx_begin().unwrap();
+            let header = BurnchainBlockHeader {
+                block_height: fixture.op.block_height,
+                block_hash: fixture.op.burn_header_hash.clone(),
+                parent_block_hash: first_burn_hash.clone(),
+                num_txs: 1,
+                parent_index_root: TrieHash::from_empty_data(),
+                timestamp: get_epoch_time_secs()
+            };
+            assert_eq!(fixture.res, fixture.op.check(&burnchain, &header, &mut tx));
+        }
+    }
+}
@@ -0,0 +1,371 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use chainstate::burn::operations::Error as op_error;
+use chainstate::burn::Opcodes;
+
+use chainstate::burn::operations::{
+    TransferStxOp,
+    BlockstackOperation,
+    parse_u128_from_be,
+    parse_signer_address
+};
+
+use chainstate::burn::db::burndb::BurnDBTx;
+
+use burnchains::BurnchainTransaction;
+use burnchains::Txid;
+use burnchains::BurnchainHeaderHash;
+use burnchains::BurnchainBlockHeader;
+use burnchains::Burnchain;
+use burnchains::Address;
+
+use chainstate::stacks::StacksAddress;
+
+use util::log;
+
+struct ParsedData {
+    pub transfered_ustx: u128,
+    pub memo: Vec<u8>
+}
+
+impl TransferStxOp {
+    #[cfg(test)]
+    pub fn new(sender: &StacksAddress, recipient: &StacksAddress, transfered_ustx: u128) -> TransferStxOp {
+        TransferStxOp {
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            transfered_ustx: transfered_ustx,
+            memo: vec![],
+
+            // will be filled in
+            txid: Txid([0u8; 32]),
+            vtxindex: 0,
+            block_height: 0,
+            burn_header_hash: BurnchainHeaderHash([0u8; 32]),
+        }
+    }
+
+    fn parse_data(data: &Vec<u8>) -> Option<ParsedData> {
+        /*
+            Wire format:
+
+            0      2  3                             19                        80
+            |------|--|------------------------------|-------------------------|
+             magic  op  uSTX to transfer (big-endian)            memo
+
+             Note that `data` is missing the first 3 bytes -- the magic and op have been stripped
+        */
+        if data.len() < 16 {
+            // too short to have a transfer amount
+            warn!("TRANSFER_STX payload is malformed ({} bytes)", data.len());
+            return None;
+        }
+
+        let transfered_ustx = parse_u128_from_be(&data[0..16]).expect("FATAL: invalid byte slice for transfer amount");
+        let memo = &data[16..];
+
+        Some(ParsedData {
+            transfered_ustx,
+            memo: memo.to_vec()
+        })
+    }
+
+    fn parse_from_tx(block_height: u64, block_hash: &BurnchainHeaderHash, tx: &BurnchainTransaction) -> Result<TransferStxOp, op_error> {
+        // can't be too careful...
+        let inputs = tx.get_signers();
+        let outputs = tx.get_recipients();
+
+        if inputs.len() == 0 {
+            test_debug!("Invalid tx: inputs: {}, outputs: {}", inputs.len(), outputs.len());
+            return Err(op_error::InvalidInput);
+        }
+
+        if outputs.len() < 1 {
+            test_debug!("Invalid tx: inputs: {}, outputs: {}", inputs.len(), outputs.len());
+            return Err(op_error::InvalidInput);
+        }
+
+        if tx.opcode() != Opcodes::TransferStx as u8 {
+            test_debug!("Invalid tx: invalid opcode {}", tx.opcode());
+            return Err(op_error::InvalidInput);
+        }
+
+        let data = match TransferStxOp::parse_data(&tx.data()) {
+            Some(data) => {
+                data
+            },
+            None => {
+                test_debug!("Invalid tx data");
+                return Err(op_error::ParseError);
+            }
+        };
+
+        // the first output is the recipient, and the first input is the sender
+        let recipient = outputs[0].address.clone();
+        let sender = parse_signer_address(&inputs[0], &recipient);
+
+        Ok(TransferStxOp {
+            sender: sender,
+            recipient: recipient,
+            transfered_ustx: data.transfered_ustx,
+            memo: data.memo,
+
+            txid: tx.txid(),
+            vtxindex: tx.vtxindex(),
+            block_height: block_height,
+            burn_header_hash: block_hash.clone(),
+        })
+    }
+}
+
+impl BlockstackOperation for TransferStxOp {
+    fn from_tx(block_header: &BurnchainBlockHeader, tx: &BurnchainTransaction) -> Result<TransferStxOp, op_error> {
+        TransferStxOp::parse_from_tx(block_header.block_height, &block_header.block_hash, tx)
+    }
+
+    fn check<'a>(&self, _burnchain: &Burnchain, _block_header: &BurnchainBlockHeader, _tx: &mut BurnDBTx<'a>) -> Result<(), op_error> {
+        // whether or not the sender can afford this is decided when the op is applied to the
+        // chainstate, since the burn DB does not know account balances.
+        if self.transfered_ustx == 0 {
+            warn!("Invalid STX transfer: must transfer a positive amount");
+            return Err(op_error::TransferStxMustBePositive);
+        }
+
+        if self.sender == self.recipient {
+            warn!("Invalid STX transfer: sender {} cannot send to itself", &self.sender.to_string());
+            return Err(op_error::TransferStxSelfSend);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burnchains::bitcoin::address::BitcoinAddress;
+    use burnchains::bitcoin::blocks::BitcoinBlockParser;
+    use burnchains::bitcoin::BitcoinNetworkType;
+    use burnchains::BLOCKSTACK_MAGIC_MAINNET;
+
+    use deps::bitcoin::network::serialize::deserialize;
+    use deps::bitcoin::blockdata::transaction::Transaction;
+
+    use chainstate::burn::db::burndb::BurnDB;
+    use chainstate::stacks::index::TrieHash;
+    use chainstate::stacks::StacksPublicKey;
+    use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+
+    use address::AddressHashMode;
+
+    use util::hash::hex_bytes;
+    use util::get_epoch_time_secs;
+
+    struct OpFixture {
+        txstr: String,
+        result: Option<TransferStxOp>,
+    }
+
+    struct CheckFixture {
+        op: TransferStxOp,
+        res: Result<(), op_error>
+    }
+
+    fn make_tx(hex_str: &str) -> Result<Transaction, &'static str> {
+        let tx_bin = hex_bytes(hex_str)
+            .map_err(|_e| "failed to decode hex string")?;
+        let tx = deserialize(&tx_bin.to_vec())
+            .map_err(|_e| "failed to deserialize")?;
+        Ok(tx)
+    }
+
+    fn test_sender() -> StacksAddress {
+        StacksAddress::from_public_keys(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, &AddressHashMode::SerializeP2PKH, 1,
+                                        &vec![StacksPublicKey::from_hex("02d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0").unwrap()]).unwrap()
+    }
+
+    fn test_recipient() -> StacksAddress {
+        StacksAddress::from_bitcoin_address(&BitcoinAddress::from_scriptpubkey(BitcoinNetworkType::Testnet, &hex_bytes("76a9140be3e286a15ea85882761618e366586b5574100d88ac").unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        let vtxindex = 1;
+        let block_height = 694;
+        let burn_header_hash = BurnchainHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+
+        let tx_fixtures: Vec<OpFixture> = vec![
+            OpFixture {
+                txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006a47304402203a176d95803e8d51e7884d38750322c4bfa55307a71291ef8db65191edd665f1022056f5d1720d1fde8d6a163c79f73f22f874ef9e186e98e5b60fa8ac64d298e77a012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0000000000200000000000000001a6a1869642400000000000000000000000000003039010203040510270000000000001976a9140be3e286a15ea85882761618e366586b5574100d88ac00000000".to_string(),
+                result: Some(TransferStxOp {
+                    sender: test_sender(),
+                    recipient: test_recipient(),
+                    transfered_ustx: 12345,
+                    memo: vec![01, 02, 03, 04, 05],
+
+                    txid: Txid::from_bytes_be(&hex_bytes("bc9d20a974afe0ee16bd5d80b2a55f07b0efebd1f922d76e51d13948882303c2").unwrap()).unwrap(),
+                    vtxindex: vtxindex,
+                    block_height: block_height,
+                    burn_header_hash: burn_header_hash.clone(),
+                })
+            },
+            OpFixture {
+                // no memo
+                txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006a47304402203a176d95803e8d51e7884d38750322c4bfa55307a71291ef8db65191edd665f1022056f5d1720d1fde8d6a163c79f73f22f874ef9e186e98e5b60fa8ac64d298e77a012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d000000000020000000000000000156a136964240000000000000000000000000000303910270000000000001976a9140be3e286a15ea85882761618e366586b5574100d88ac00000000".to_string(),
+                result: Some(TransferStxOp {
+                    sender: test_sender(),
+                    recipient: test_recipient(),
+                    transfered_ustx: 12345,
+                    memo: vec![],
+
+                    txid: Txid::from_bytes_be(&hex_bytes("0fec6833d9cf821d597d81501d5ef46a2df25c16604d7dc6e79c169ead275f1c").unwrap()).unwrap(),
+                    vtxindex: vtxindex,
+                    block_height: block_height,
+                    burn_header_hash: burn_header_hash.clone(),
+                })
+            },
+            OpFixture {
+                // too short
+                txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006a47304402203a176d95803e8d51e7884d38750322c4bfa55307a71291ef8db65191edd665f1022056f5d1720d1fde8d6a163c79f73f22f874ef9e186e98e5b60fa8ac64d298e77a012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d000000000020000000000000000146a1269642400000000000000000000000000003010270000000000001976a9140be3e286a15ea85882761618e366586b5574100d88ac00000000".to_string(),
+                result: None,
+            },
+            OpFixture {
+                // not enough outputs
+                txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006a47304402203a176d95803e8d51e7884d38750322c4bfa55307a71291ef8db65191edd665f1022056f5d1720d1fde8d6a163c79f73f22f874ef9e186e98e5b60fa8ac64d298e77a012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0000000000100000000000000001a6a1869642400000000000000000000000000003039010203040500000000".to_string(),
+                result: None,
+            },
+            OpFixture {
+                // wrong opcode
+                txstr: "01000000011111111111111111111111111111111111111111111111111111111111111111000000006a47304402203a176d95803e8d51e7884d38750322c4bfa55307a71291ef8db65191edd665f1022056f5d1720d1fde8d6a163c79f73f22f874ef9e186e98e5b60fa8ac64d298e77a012102d8015134d9db8178ac93acbc43170a2f20febba5087a5b0437058765ad5133d0000000000200000000000000001a6a1869645f00000000000000000000000000003039010203040510270000000000001976a9140be3e286a15ea85882761618e366586b5574100d88ac00000000".to_string(),
+                result: None,
+            }
+        ];
+
+        let parser = BitcoinBlockParser::new(BitcoinNetworkType::Testnet, BLOCKSTACK_MAGIC_MAINNET);
+
+        for tx_fixture in tx_fixtures {
+            let tx = make_tx(&tx_fixture.txstr).unwrap();
+            let header = match tx_fixture.result {
+                Some(ref op) => {
+                    BurnchainBlockHeader {
+                        block_height: op.block_height,
+                        block_hash: op.burn_header_hash.clone(),
+                        parent_block_hash: op.burn_header_hash.clone(),
+                        num_txs: 1,
+                        parent_index_root: TrieHash::from_empty_data(),
+                        timestamp: get_epoch_time_secs()
+                    }
+                },
+                None => {
+                    BurnchainBlockHeader {
+                        block_height: 0,
+                        block_hash: BurnchainHeaderHash([0u8; 32]),
+                        parent_block_hash: BurnchainHeaderHash([0u8; 32]),
+                        num_txs: 0,
+                        parent_index_root: TrieHash::from_empty_data(),
+                        timestamp: get_epoch_time_secs()
+                    }
+                }
+            };
+            let burnchain_tx = BurnchainTransaction::Bitcoin(parser.parse_tx(&tx, vtxindex as usize).unwrap());
+            let op = TransferStxOp::from_tx(&header, &burnchain_tx);
+
+            match (op, tx_fixture.result) {
+                (Ok(parsed_tx), Some(result)) => {
+                    assert_eq!(parsed_tx, result);
+                },
+                (Err(_e), None) => {},
+                (Ok(_parsed_tx), None) => {
+                    test_debug!("Parsed a tx when we should not have: {}", tx_fixture.txstr);
+                    assert!(false);
+                },
+                (Err(_e), Some(_result)) => {
+                    test_debug!("Did not parse a tx when we should have: {}", tx_fixture.txstr);
+                    assert!(false);
+                }
+            };
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let first_block_height = 121;
+        let first_burn_hash = BurnchainHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000123").unwrap();
+        let block_122_hash = BurnchainHeaderHash::from_hex("0000000000000000000000000000000000000000000000000000000000000002").unwrap();
+
+        let burnchain = Burnchain {
+            peer_version: 0x012345678,
+            network_id: 0x9abcdef0,
+            chain_name: "bitcoin".to_string(),
+            network_name: "testnet".to_string(),
+            working_dir: "/nope".to_string(),
+            consensus_hash_lifetime: 24,
+            stable_confirmations: 7,
+            first_block_height: first_block_height,
+            first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5
+        };
+
+        let mut db = BurnDB::connect_memory(first_block_height, &first_burn_hash).unwrap();
+
+        let mut op_zero = TransferStxOp::new(&test_sender(), &test_recipient(), 0);
+        op_zero.block_height = 122;
+        op_zero.burn_header_hash = block_122_hash.clone();
+
+        let mut op_self = TransferStxOp::new(&test_sender(), &test_sender(), 12345);
+        op_self.block_height = 122;
+        op_self.burn_header_hash = block_122_hash.clone();
+
+        let mut op_ok = TransferStxOp::new(&test_sender(), &test_recipient(), 12345);
+        op_ok.block_height = 122;
+        op_ok.burn_header_hash = block_122_hash.clone();
+
+        let check_fixtures = vec![
+            CheckFixture {
+                // reject -- nothing transferred
+                op: op_zero,
+                res: Err(op_error::TransferStxMustBePositive),
+            },
+            CheckFixture {
+                // reject -- sending to self
+                op: op_self,
+                res: Err(op_error::TransferStxSelfSend),
+            },
+            CheckFixture {
+                // accept
+                op: op_ok,
+                res: Ok(())
+            }
+        ];
+
+        for fixture in check_fixtures {
+            let mut tx = db.tx_begin().unwrap();
+            let header = BurnchainBlockHeader {
+                block_height: fixture.op.block_height,
+                block_hash: fixture.op.burn_header_hash.clone(),
+                parent_block_hash: first_burn_hash.clone(),
+                num_txs: 1,
+                parent_index_root: TrieHash::from_empty_data(),
+                timestamp: get_epoch_time_secs()
+            };
+            assert_eq!(fixture.res, fixture.op.check(&burnchain, &header, &mut tx));
+        }
+    }
+}
@@ -320,7 +320,8 @@ mod test {
             consensus_hash_lifetime: 24,
             stable_confirmations: 7,
             first_block_height: first_block_height,
-            first_block_hash: first_burn_hash.clone()
+            first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5
         };
 
         let mut db = BurnDB::connect_memory(first_block_height, &first_burn_hash).unwrap();
@@ -53,6 +53,7 @@ use net::STACKS_PUBLIC_KEY_ENCODED_SIZE;
 use burnchains::Txid;
 use burnchains::PrivateKey;
 use burnchains::PublicKey;
+use burnchains::Address;
 use util::hash::Sha512Trunc256Sum;
 use util::hash::to_hex;
 use util::hash::Hash160;
@@ -61,6 +62,8 @@ use util::secp256k1::MESSAGE_SIGNATURE_ENCODED_SIZE;
 use util::retry::RetryReader;
 use util::retry::BoundReader;
 
+use serde_json::Value as JSONValue;
+
 impl StacksMessageCodec for TransactionAuthField {
     fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), net_error> {
         match *self {
@@ -579,6 +582,49 @@ impl TransactionSpendingCondition {
         }
     }
 
+    /// Human-readable JSON encoding of this spending condition.  The signer's address is
+    /// encoded for mainnet or testnet, depending on the transaction version.
+    pub fn to_json(&self, mainnet: bool) -> JSONValue {
+        let address = if mainnet { self.address_mainnet() } else { self.address_testnet() };
+        match *self {
+            TransactionSpendingCondition::Singlesig(ref data) => {
+                json!({
+                    "hash_mode": format!("{:?}", &data.hash_mode),
+                    "signer": data.signer.to_hex(),
+                    "address": address.to_string(),
+                    "nonce": data.nonce,
+                    "fee_rate": data.fee_rate,
+                    "key_encoding": format!("{:?}", &data.key_encoding),
+                    "signature": data.signature.to_hex()
+                })
+            },
+            TransactionSpendingCondition::Multisig(ref data) => {
+                let fields : Vec<JSONValue> = data.fields
+                    .iter()
+                    .map(|field| match *field {
+                        TransactionAuthField::PublicKey(ref pubkey) => json!({
+                            "public_key": pubkey.to_hex()
+                        }),
+                        TransactionAuthField::Signature(ref key_encoding, ref sig) => json!({
+                            "key_encoding": format!("{:?}", key_encoding),
+                            "signature": sig.to_hex()
+                        })
+                    })
+                    .collect();
+
+                json!({
+                    "hash_mode": format!("{:?}", &data.hash_mode),
+                    "signer": data.signer.to_hex(),
+                    "address": address.to_string(),
+                    "nonce": data.nonce,
+                    "fee_rate": data.fee_rate,
+                    "fields": fields,
+                    "signatures_required": data.signatures_required
+                })
+            }
+        }
+    }
+
     /// Clear fee rate, nonces, signatures, and public keys
     pub fn clear(&mut self) -> () {
         match *self {
@@ -681,6 +727,70 @@ impl TransactionSpendingCondition {
         Ok((pubk, next_sighash))
     }
 
+    /// Replay the signatures this spending condition has so far, without requiring that it have
+    /// all of them.  Returns the sighash that the next signer must sign.
+    pub fn next_sighash(&self, initial_sighash: &Txid, cond_code: &TransactionAuthFlags) -> Result<Txid, net_error> {
+        match *self {
+            TransactionSpendingCondition::Singlesig(ref data) => {
+                if data.signature == MessageSignature::empty() {
+                    return Ok(initial_sighash.clone());
+                }
+                let (_, next_sighash) = TransactionSpendingCondition::next_verification(initial_sighash, cond_code, data.fee_rate, data.nonce, &data.key_encoding, &data.signature)?;
+                Ok(next_sighash)
+            },
+            TransactionSpendingCondition::Multisig(ref data) => {
+                let mut cur_sighash = initial_sighash.clone();
+                for field in data.fields.iter() {
+                    if let TransactionAuthField::Signature(ref key_encoding, ref sig) = field {
+                        let (_, next_sighash) = TransactionSpendingCondition::next_verification(&cur_sighash, cond_code, data.fee_rate, data.nonce, key_encoding, sig)?;
+                        cur_sighash = next_sighash;
+                    }
+                }
+                Ok(cur_sighash)
+            }
+        }
+    }
+
+    /// Append a signature that was made elsewhere (e.g. by a hardware wallet) over the presign
+    /// sighash of `cur_sighash`.  The signer's public key is recovered from the signature, and
+    /// must hash to `pubkey_hash`.
+    /// Returns the next sighash.
+    pub fn append_signature(&mut self, cur_sighash: &Txid, cond_code: &TransactionAuthFlags, sig: &MessageSignature, pubkey_hash: &Hash160) -> Result<Txid, net_error> {
+        let mut recovered = None;
+        for key_encoding in [TransactionPublicKeyEncoding::Compressed, TransactionPublicKeyEncoding::Uncompressed].iter() {
+            let (pubkey, next_sighash) = TransactionSpendingCondition::next_verification(cur_sighash, cond_code, self.fee_rate(), self.nonce(), key_encoding, sig)?;
+            if Hash160::from_data(&pubkey.to_bytes()) == *pubkey_hash {
+                recovered = Some((*key_encoding, pubkey, next_sighash));
+                break;
+            }
+        }
+
+        let (key_encoding, pubkey, next_sighash) = recovered
+            .ok_or(net_error::SigningError(format!("Signature was not made by public key hash {}", pubkey_hash)))?;
+
+        match *self {
+            TransactionSpendingCondition::Singlesig(ref mut data) => {
+                if data.signature != MessageSignature::empty() {
+                    return Err(net_error::SigningError("Spending condition is already signed".to_string()));
+                }
+                let addr = StacksAddress::from_public_keys(0, &data.hash_mode.to_address_hash_mode(), 1, &vec![pubkey])
+                    .ok_or(net_error::SigningError("Failed to generate address from public key".to_string()))?;
+                if addr.bytes != data.signer {
+                    return Err(net_error::SigningError(format!("Signer hash does not equal hash of public key: {} != {}", &addr.bytes, &data.signer)));
+                }
+                data.key_encoding = key_encoding;
+                data.signature = sig.clone();
+            },
+            TransactionSpendingCondition::Multisig(ref mut data) => {
+                if TransactionSpendingCondition::Multisig(data.clone()).num_signatures() >= data.signatures_required {
+                    return Err(net_error::SigningError("Spending condition would have too many signatures".to_string()));
+                }
+                data.push_signature(key_encoding, sig.clone());
+            }
+        }
+        Ok(next_sighash)
+    }
+
     /// Verify all signatures
     pub fn verify(&self, initial_sighash: &Txid, cond_code: &TransactionAuthFlags) -> Result<Txid, net_error> {
         match *self {
@@ -788,6 +898,21 @@ impl TransactionAuth {
         }
     }
 
+    /// Human-readable JSON encoding of this authorization structure
+    pub fn to_json(&self, mainnet: bool) -> JSONValue {
+        match *self {
+            TransactionAuth::Standard(ref origin) => json!({
+                "type": "standard",
+                "origin": origin.to_json(mainnet)
+            }),
+            TransactionAuth::Sponsored(ref origin, ref sponsor) => json!({
+                "type": "sponsored",
+                "origin": origin.to_json(mainnet),
+                "sponsor": sponsor.to_json(mainnet)
+            })
+        }
+    }
+
     pub fn is_standard(&self) -> bool {
         match *self {
             TransactionAuth::Standard(_) => true,
@@ -56,6 +56,8 @@ use util::vrf::*;
 
 use util::retry::BoundReader;
 
+use serde_json::Value as JSONValue;
+
 impl StacksMessageCodec for VRFProof {
     fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), net_error> {
         fd.write_all(&self.to_bytes()).map_err(net_error::WriteError)
@@ -171,6 +173,25 @@ impl StacksBlockHeader {
         BlockHeaderHash::from_serialized_header(&buf[..])
     }
 
+    /// Human-readable JSON encoding of this header
+    pub fn to_json(&self) -> JSONValue {
+        json!({
+            "block_hash": self.block_hash().to_hex(),
+            "version": self.version,
+            "total_work": {
+                "burn": self.total_work.burn,
+                "work": self.total_work.work
+            },
+            "proof": self.proof.to_hex(),
+            "parent_block": self.parent_block.to_hex(),
+            "parent_microblock": self.parent_microblock.to_hex(),
+            "parent_microblock_sequence": self.parent_microblock_sequence,
+            "tx_merkle_root": self.tx_merkle_root.to_hex(),
+            "state_index_root": self.state_index_root.to_hex(),
+            "microblock_pubkey_hash": self.microblock_pubkey_hash.to_hex()
+        })
+    }
+
     /// This is the "block hash" used for extending the state index root.
     /// This method is necessary because the index root must be globally unique (but, the same stacks
     /// block header can show up multiple times on different burn chain forks).
@@ -389,6 +410,14 @@ impl StacksBlock {
     pub fn block_hash(&self) -> BlockHeaderHash {
         self.header.block_hash()
     }
+
+    /// Human-readable JSON encoding of this block, including all of its transactions
+    pub fn to_json(&self) -> JSONValue {
+        json!({
+            "header": self.header.to_json(),
+            "txs": self.txs.iter().map(|tx| tx.to_json()).collect::<Vec<JSONValue>>()
+        })
+    }
     
     pub fn index_block_hash(&self, burn_hash: &BurnchainHeaderHash) -> BlockHeaderHash {
         self.header.index_block_hash(burn_hash)
@@ -594,29 +623,33 @@ impl StacksMicroblockHeader {
     }
 
     pub fn verify(&mut self, pubk_hash: &Hash160) -> Result<(), net_error> {
+        let pubk = self.recover_pubkey()?;
+        if StacksBlockHeader::pubkey_hash(&pubk) != *pubk_hash {
+            return Err(net_error::VerifyingError(format!("Failed to verify signature: public key {} did not recover to expected hash", pubk.to_hex())));
+        }
+
+        Ok(())
+    }
+
+    /// Recover the (compressed) public key of the leader that signed this header
+    pub fn recover_pubkey(&self) -> Result<StacksPublicKey, net_error> {
         let mut digest_bits = [0u8; 32];
         let mut sha2 = Sha512Trunc256::new();
 
-        let sig_bits = self.signature.clone();
-        self.signature = MessageSignature::empty();
+        let mut unsigned = self.clone();
+        unsigned.signature = MessageSignature::empty();
 
         let mut bytes = vec![];
-        self.consensus_serialize(&mut bytes).expect("BUG: failed to serialize to a vec");
-        self.signature = sig_bits;
-        
+        unsigned.consensus_serialize(&mut bytes).expect("BUG: failed to serialize to a vec");
+
         sha2.input(&bytes[..]);
         digest_bits.copy_from_slice(sha2.result().as_slice());
 
         let mut pubk = StacksPublicKey::recover_to_pubkey(&digest_bits, &self.signature)
             .map_err(|_ve| net_error::VerifyingError("Failed to verify signature: failed to recover public key".to_string()))?;
-       
-        pubk.set_compressed(true);
 
-        if StacksBlockHeader::pubkey_hash(&pubk) != *pubk_hash {
-            return Err(net_error::VerifyingError(format!("Failed to verify signature: public key {} did not recover to expected hash", pubk.to_hex())));
-        }
-
-        Ok(())
+        pubk.set_compressed(true);
+        Ok(pubk)
     }
 
     pub fn block_hash(&self) -> BlockHeaderHash {
@@ -624,6 +657,18 @@ impl StacksMicroblockHeader {
         self.consensus_serialize(&mut bytes).expect("BUG: failed to serialize to a vec");
         BlockHeaderHash::from_serialized_header(&bytes[..])
     }
+
+    /// Human-readable JSON encoding of this header
+    pub fn to_json(&self) -> JSONValue {
+        json!({
+            "block_hash": self.block_hash().to_hex(),
+            "version": self.version,
+            "sequence": self.sequence,
+            "prev_block": self.prev_block.to_hex(),
+            "tx_merkle_root": self.tx_merkle_root.to_hex(),
+            "signature": self.signature.to_hex()
+        })
+    }
     
     /// Create the genesis block microblock header
     pub fn genesis() -> StacksMicroblockHeader {
@@ -776,6 +821,14 @@ impl StacksMicroblock {
         self.header.block_hash()
     }
 
+    /// Human-readable JSON encoding of this microblock, including all of its transactions
+    pub fn to_json(&self) -> JSONValue {
+        json!({
+            "header": self.header.to_json(),
+            "txs": self.txs.iter().map(|tx| tx.to_json()).collect::<Vec<JSONValue>>()
+        })
+    }
+
     /// static sanity checks on transactions.
     pub fn validate_transactions_static(&self, mainnet: bool, chain_id: u32) -> bool {
         if !StacksBlock::validate_transactions_unique(&self.txs) {
@@ -1212,6 +1265,7 @@ mod test {
                 num_sigs: 1, 
                 hash_mode: AddressHashMode::SerializeP2PKH
             },
+            commit_outs: vec![],
 
             txid: Txid::from_bytes_be(&hex_bytes("3c07a0a93360bc85047bbaadd49e30c8af770f73a37e10fec400174d2e5f27cf").unwrap()).unwrap(),
             vtxindex: 444,
@@ -1446,6 +1500,76 @@ mod test {
         }
     }
 
+    #[test]
+    fn stacks_block_to_json() {
+        let proof_bytes = hex_bytes("9275df67a68c8745c0ff97b48201ee6db447f7c93b23ae24cdc2400f52fdb08a1a6ac7ec71bf9c9c76e96ee4675ebff60625af28718501047bfd87b810c2d2139b73c23bd69de66360953a642c2a330a").unwrap();
+        let proof = VRFProof::from_bytes(&proof_bytes[..].to_vec()).unwrap();
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let origin_auth = TransactionAuth::Standard(TransactionSpendingCondition::new_singlesig_p2pkh(StacksPublicKey::from_private(&privk)).unwrap());
+        let tx_coinbase = StacksTransaction::new(TransactionVersion::Testnet,
+                                                 origin_auth.clone(),
+                                                 TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])));
+
+        let header = StacksBlockHeader {
+            version: 0x12,
+            total_work: StacksWorkScore {
+                burn: 123,
+                work: 456,
+            },
+            proof: proof,
+            parent_block: FIRST_STACKS_BLOCK_HASH.clone(),
+            parent_microblock: BlockHeaderHash([1u8; 32]),
+            parent_microblock_sequence: 3,
+            tx_merkle_root: Sha512Trunc256Sum([2u8; 32]),
+            state_index_root: TrieHash([3u8; 32]),
+            microblock_pubkey_hash: Hash160([4u8; 20])
+        };
+
+        let block = StacksBlock {
+            header: header.clone(),
+            txs: vec![tx_coinbase.clone()]
+        };
+
+        let block_json = block.to_json();
+        assert_eq!(block_json["header"], json!({
+            "block_hash": block.block_hash().to_hex(),
+            "version": 0x12,
+            "total_work": { "burn": 123, "work": 456 },
+            "proof": "9275df67a68c8745c0ff97b48201ee6db447f7c93b23ae24cdc2400f52fdb08a1a6ac7ec71bf9c9c76e96ee4675ebff60625af28718501047bfd87b810c2d2139b73c23bd69de66360953a642c2a330a",
+            "parent_block": FIRST_STACKS_BLOCK_HASH.to_hex(),
+            "parent_microblock": to_hex(&[1u8; 32]),
+            "parent_microblock_sequence": 3,
+            "tx_merkle_root": to_hex(&[2u8; 32]),
+            "state_index_root": to_hex(&[3u8; 32]),
+            "microblock_pubkey_hash": to_hex(&[4u8; 20])
+        }));
+        assert_eq!(block_json["txs"], json!([tx_coinbase.to_json()]));
+
+        let mblock = StacksMicroblock {
+            header: StacksMicroblockHeader {
+                version: 0x12,
+                sequence: 0x34,
+                prev_block: EMPTY_MICROBLOCK_PARENT_HASH.clone(),
+                tx_merkle_root: Sha512Trunc256Sum([1u8; 32]),
+                signature: MessageSignature([2u8; 65]),
+            },
+            txs: vec![]
+        };
+
+        assert_eq!(mblock.to_json(), json!({
+            "header": {
+                "block_hash": mblock.block_hash().to_hex(),
+                "version": 0x12,
+                "sequence": 0x34,
+                "prev_block": EMPTY_MICROBLOCK_PARENT_HASH.to_hex(),
+                "tx_merkle_root": to_hex(&[1u8; 32]),
+                "signature": to_hex(&[2u8; 65])
+            },
+            "txs": []
+        }));
+    }
+
     // TODO:
     // * size limits
 }
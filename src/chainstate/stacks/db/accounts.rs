@@ -26,10 +26,12 @@ use chainstate::stacks::Error;
 use chainstate::stacks::*;
 use chainstate::stacks::db::*;
 use chainstate::stacks::db::blocks::*;
+use chainstate::burn::operations::TransferStxOp;
 use vm::database::*;
 use vm::database::marf::*;
 
 use vm::types::*;
+use vm::clarity::Error as clarity_error;
 
 use util::db::*;
 use util::db::Error as db_error;
@@ -45,6 +47,14 @@ pub struct MinerReward {
     pub vtxindex: u32       // will be 0 for the reward to the miner, and >0 for user burn supports
 }
 
+/// The fungible token balances and non-fungible tokens a principal owns, across all contracts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountAssets {
+    pub principal: PrincipalData,
+    pub fungible_tokens: Vec<(QualifiedContractIdentifier, String, u128)>,     // (contract, token name, balance)
+    pub non_fungible_tokens: Vec<(QualifiedContractIdentifier, String, Value)>  // (contract, asset name, asset)
+}
+
 impl FromRow<MinerPaymentSchedule> for MinerPaymentSchedule {
     fn from_row<'a>(row: &'a Row) -> Result<MinerPaymentSchedule, db_error> {
         let address = StacksAddress::from_column(row, "address")?;
@@ -132,10 +142,13 @@ impl StacksChainState {
     pub fn get_account<'a>(clarity_tx: &mut ClarityTx<'a>, principal: &PrincipalData) -> StacksAccount {
         clarity_tx.connection().with_clarity_db_readonly(|ref mut db| {
             let stx_balance = db.get_account_stx_balance(principal);
+            let (stx_locked, stx_unlock_height) = db.get_account_stx_locked(principal);
             let nonce = db.get_account_nonce(principal);
             Ok(StacksAccount {
                 principal: principal.clone(),
                 stx_balance,
+                stx_locked,
+                stx_unlock_height,
                 nonce
             })
         }).expect("FATAL: failed to query account")
@@ -157,17 +170,64 @@ impl StacksChainState {
         .map_err(Error::ClarityError)
     }
 
+    /// Get all fungible token balances and non-fungible tokens owned by a principal, including
+    /// contract principals.
+    pub fn get_account_assets<'a>(clarity_tx: &mut ClarityTx<'a>, principal: &PrincipalData) -> Result<AccountAssets, Error> {
+        clarity_tx.connection().with_clarity_db_readonly(|ref mut db| {
+            let holdings = db.get_account_asset_holdings(principal);
+
+            let mut fungible_tokens = vec![];
+            for (contract_id, token_name) in holdings.fungible_tokens.into_iter() {
+                let balance = db.get_ft_balance(&contract_id, &token_name, principal)?;
+                fungible_tokens.push((contract_id, token_name, balance));
+            }
+
+            let mut non_fungible_tokens = vec![];
+            for (contract_id, asset_name, asset_hex) in holdings.non_fungible_tokens.into_iter() {
+                let key_type = db.get_nft_key_type(&contract_id, &asset_name)?;
+                let asset = Value::deserialize(&asset_hex, &key_type);
+                non_fungible_tokens.push((contract_id, asset_name, asset));
+            }
+
+            Ok(AccountAssets {
+                principal: principal.clone(),
+                fungible_tokens,
+                non_fungible_tokens
+            })
+        })
+        .map_err(Error::ClarityError)
+    }
+
+    /// Get a principal's account as of the given Stacks block.
+    pub fn get_account_at_block(&mut self, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, principal: &PrincipalData) -> Result<StacksAccount, Error> {
+        let mut clarity_tx = self.read_only_block_begin(burn_header_hash, block_hash)?;
+        let account = StacksChainState::get_account(&mut clarity_tx, principal);
+        clarity_tx.rollback_block();
+        Ok(account)
+    }
+
+    /// Get all fungible token balances and non-fungible tokens owned by a principal as of the
+    /// given Stacks block.
+    pub fn get_account_assets_at_block(&mut self, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, principal: &PrincipalData) -> Result<AccountAssets, Error> {
+        let mut clarity_tx = self.read_only_block_begin(burn_header_hash, block_hash)?;
+        let assets = StacksChainState::get_account_assets(&mut clarity_tx, principal);
+        clarity_tx.rollback_block();
+        assets
+    }
+
     /// Called each time a transaction is invoked from this principal, to e.g.
     /// debit the STX-denominated tx fee or transfer/burn STX.
     /// DOES NOT UPDATE THE NONCE
+    /// Locked STX cannot be debited.
     pub fn account_debit<'a>(clarity_tx: &mut ClarityTx<'a>, principal: &PrincipalData, amount: u64) {
         clarity_tx.connection().with_clarity_db(|ref mut db| {
             let cur_balance = db.get_account_stx_balance(principal);
+            let unlocked_balance = db.get_account_stx_unlocked_balance(principal);
             
             // last line of defense: if we don't have sufficient funds, panic.
             // This should be checked by the block validation logic.
-            if cur_balance < (amount as u128) {
-                panic!("Tried to debit {} from account {} (which only has {})", amount, principal, cur_balance);
+            if unlocked_balance < (amount as u128) {
+                panic!("Tried to debit {} from account {} (which only has {} unlocked)", amount, principal, unlocked_balance);
             }
 
             let final_balance = cur_balance - (amount as u128);
@@ -187,6 +247,57 @@ impl StacksChainState {
         }).expect("FATAL: failed to credit account")
     }
    
+    /// Apply an STX transfer sent on the burnchain.  The transfer is skipped if the sender cannot
+    /// afford it with their unlocked STX.
+    /// Must be applied identically by miners and block validators, since it alters the state root.
+    pub fn process_burnchain_transfer_stx_op<'a>(clarity_tx: &mut ClarityTx<'a>, op: &TransferStxOp) -> () {
+        let sender = op.sender.to_account_principal();
+        let recipient = op.recipient.to_account_principal();
+
+        let unlocked_balance = clarity_tx.connection().with_clarity_db_readonly(|ref mut db| {
+            Ok(db.get_account_stx_unlocked_balance(&sender))
+        }).expect("FATAL: failed to query account balance");
+
+        if op.transfered_ustx > (u64::max_value() as u128) || unlocked_balance < op.transfered_ustx {
+            warn!("Skipping burnchain STX transfer {}: {} has {} unlocked microSTX; cannot send {}", &op.txid, &sender, unlocked_balance, op.transfered_ustx);
+            return;
+        }
+
+        debug!("Burnchain STX transfer {}: send {} microSTX from {} to {}", &op.txid, op.transfered_ustx, &sender, &recipient);
+        StacksChainState::account_debit(clarity_tx, &sender, op.transfered_ustx as u64);
+        StacksChainState::account_credit(clarity_tx, &recipient, op.transfered_ustx as u64);
+    }
+
+    /// Lock some of an account's STX until the burnchain reaches the given block height.
+    /// The locked STX must already be unlocked and in the account, and the account must not
+    /// already have an active lock.
+    pub fn account_lock<'a>(clarity_tx: &mut ClarityTx<'a>, principal: &PrincipalData, amount: u64, unlock_height: u64) -> Result<(), Error> {
+        clarity_tx.connection().with_clarity_db(|ref mut db| {
+            let (cur_locked, cur_unlock_height) = db.get_account_stx_locked(principal);
+            if cur_locked > 0 {
+                return Err(clarity_error::BadTransaction(format!("Account {} already has {} microSTX locked until {}", principal, cur_locked, cur_unlock_height)));
+            }
+
+            let unlocked_balance = db.get_account_stx_unlocked_balance(principal);
+            if unlocked_balance < (amount as u128) {
+                return Err(clarity_error::BadTransaction(format!("Account {} has {} microSTX; cannot lock {}", principal, unlocked_balance, amount)));
+            }
+
+            if db.get_burnchain_block_height() >= unlock_height {
+                return Err(clarity_error::BadTransaction(format!("Unlock height {} has already passed", unlock_height)));
+            }
+
+            db.set_account_stx_locked(principal, amount as u128, unlock_height);
+            Ok(())
+        })
+        .map_err(|e| {
+            match e {
+                clarity_error::BadTransaction(s) => Error::InvalidStacksTransaction(s),
+                _ => Error::ClarityError(e)
+            }
+        })
+    }
+
     /// Increment an account's nonce
     pub fn update_account_nonce<'a>(clarity_tx: &mut ClarityTx<'a>, account: &StacksAccount) {
         clarity_tx.connection().with_clarity_db(|ref mut db| {
@@ -360,6 +471,13 @@ impl StacksChainState {
         miner_reward
     }
 
+    /// Withhold a scheduled miner payment from a leader that equivocated on its microblock stream.
+    fn apply_microblock_poison_penalty(block_reward: &mut MinerPaymentSchedule) -> () {
+        block_reward.coinbase = 0;
+        block_reward.tx_fees_anchored = 0;
+        block_reward.tx_fees_streamed = 0;
+    }
+
     /// Find the latest miner reward to mature, assuming that there are mature rewards.
     /// Returns a list of payments to make to each address -- miners and user-support burners
     pub fn find_mature_miner_rewards<'a>(tx: &mut StacksDBTx<'a>, tip: &StacksHeaderInfo) -> Result<Option<Vec<MinerReward>>, Error> {
@@ -368,9 +486,22 @@ impl StacksChainState {
             return Ok(None);
         }
 
-        let matured_miners = StacksChainState::get_scheduled_block_rewards_in_fork(tx, tip, tip.block_height - MINER_REWARD_MATURITY - MINER_REWARD_WINDOW)?;
+        let mut matured_miners = StacksChainState::get_scheduled_block_rewards_in_fork(tx, tip, tip.block_height - MINER_REWARD_MATURITY - MINER_REWARD_WINDOW)?;
         assert!(matured_miners.len() > 0);
 
+        // a leader caught equivocating on its microblock stream (before its reward matured)
+        // forfeits its coinbase and transaction fees.  Its user-support burners are unaffected.
+        for matured_miner in matured_miners.iter_mut() {
+            if !matured_miner.miner {
+                continue;
+            }
+            let index_block_hash = StacksBlockHeader::make_index_block_hash(&matured_miner.burn_header_hash, &matured_miner.block_hash);
+            if let Some(txid) = StacksChainState::get_microblock_poison_report(tx, &tip.burn_header_hash, &tip.anchored_header, &index_block_hash)? {
+                debug!("Miner {} of {}/{} was reported for microblock equivocation in {}; forfeiting its reward", matured_miner.address.to_string(), matured_miner.burn_header_hash, matured_miner.block_hash, txid);
+                StacksChainState::apply_microblock_poison_penalty(matured_miner);
+            }
+        }
+
         let mut scheduled_payments = vec![];
         for i in 0..MINER_REWARD_WINDOW {
             let height = tip.block_height - MINER_REWARD_MATURITY - MINER_REWARD_WINDOW + i;      // safe due to the above check
@@ -462,6 +593,10 @@ mod test {
     }
 
     fn advance_tip(chainstate: &mut StacksChainState, parent_header_info: &StacksHeaderInfo, block_reward: &mut MinerPaymentSchedule, user_burns: &mut Vec<StagingUserBurnSupport>) -> StacksHeaderInfo {
+        advance_tip_with_poisons(chainstate, parent_header_info, block_reward, user_burns, &vec![])
+    }
+
+    fn advance_tip_with_poisons(chainstate: &mut StacksChainState, parent_header_info: &StacksHeaderInfo, block_reward: &mut MinerPaymentSchedule, user_burns: &mut Vec<StagingUserBurnSupport>, microblock_poisons: &Vec<(BlockHeaderHash, Txid)>) -> StacksHeaderInfo {
         let mut new_tip = parent_header_info.clone();
 
         new_tip.anchored_header.parent_block = parent_header_info.anchored_header.block_hash();
@@ -490,7 +625,8 @@ mod test {
                                                 new_tip.burn_header_timestamp, 
                                                 new_tip.microblock_tail.clone(), 
                                                 &block_reward, 
-                                                &user_burns).unwrap();
+                                                &user_burns,
+                                                microblock_poisons).unwrap();
         tx.commit().unwrap();
         tip
     }
@@ -581,6 +717,53 @@ mod test {
         };
     }
 
+    #[test]
+    fn miner_reward_forfeited_after_microblock_poison() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "miner_reward_forfeited_after_microblock_poison");
+        let poison_txid = Txid([0x22; 32]);
+
+        let mut tip = StacksHeaderInfo::genesis();
+        let mut offender_index_block_hash : Option<BlockHeaderHash> = None;
+        let mut matured_rewards = vec![];
+
+        for i in 1..(MINER_REWARD_MATURITY + MINER_REWARD_WINDOW + 3) {
+            let miner = StacksAddress { version: 22, bytes: Hash160([i as u8; 20]) };
+            let mut block_reward = make_dummy_miner_payment_schedule(&miner, 500, 0, 0, 1000, 1000);
+
+            // the second block reports the first block's leader for equivocating
+            let microblock_poisons = match offender_index_block_hash {
+                Some(ref index_block_hash) if i == 2 => vec![(index_block_hash.clone(), poison_txid.clone())],
+                _ => vec![]
+            };
+
+            tip = advance_tip_with_poisons(&mut chainstate, &tip, &mut block_reward, &mut vec![], &microblock_poisons);
+
+            let mut tx = chainstate.headers_tx_begin().unwrap();
+            let tip_index_block_hash = StacksBlockHeader::make_index_block_hash(&tip.burn_header_hash, &tip.anchored_header.block_hash());
+
+            // the leader's microblock public key hash identifies its block
+            assert_eq!(StacksChainState::get_microblock_pubkey_hash_block(&mut tx, &tip.burn_header_hash, &tip.anchored_header, &tip.anchored_header.microblock_pubkey_hash).unwrap(), Some(tip_index_block_hash.clone()));
+            if i == 1 {
+                offender_index_block_hash = Some(tip_index_block_hash);
+            }
+            else {
+                let offender = offender_index_block_hash.as_ref().unwrap();
+                assert_eq!(StacksChainState::get_microblock_poison_report(&mut tx, &tip.burn_header_hash, &tip.anchored_header, offender).unwrap(), Some(poison_txid.clone()));
+            }
+
+            if let Some(rewards) = StacksChainState::find_mature_miner_rewards(&mut tx, &tip).unwrap() {
+                matured_rewards.extend(rewards);
+            }
+        }
+
+        // the offending leader forfeits its reward; the next leader does not
+        assert!(matured_rewards.len() >= 2);
+        assert_eq!(matured_rewards[0].address, StacksAddress { version: 22, bytes: Hash160([1u8; 20]) });
+        assert_eq!(matured_rewards[0].coinbase, 0);
+        assert_eq!(matured_rewards[1].address, StacksAddress { version: 22, bytes: Hash160([2u8; 20]) });
+        assert_eq!(matured_rewards[1].coinbase, 500);
+    }
+
     #[test]
     fn miner_reward_one_miner_no_tx_fees_no_users() {
         let mut sample = vec![];
@@ -21,6 +21,7 @@ use std::io;
 use std::io::prelude::*;
 use std::fmt;
 use std::fs;
+use std::cmp;
 use std::collections::{HashMap, HashSet};
 
 use core::*;
@@ -30,6 +31,8 @@ use chainstate::burn::operations::*;
 use chainstate::stacks::Error;
 use chainstate::stacks::db::StacksChainState;
 use chainstate::stacks::db::accounts::MinerReward;
+use chainstate::stacks::db::pox::PoxRewardSlot;
+use chainstate::stacks::db::receipts::StacksTransactionReceipt;
 use chainstate::stacks::*;
 use chainstate::stacks::db::*;
 
@@ -48,7 +51,7 @@ use util::db::{
 
 use util::strings::StacksString;
 use util::get_epoch_time_secs;
-use util::hash::to_hex;
+use util::hash::{to_hex, MerkleTree, Sha512Trunc256Sum};
 
 use chainstate::burn::db::burndb::*;
 
@@ -99,6 +102,7 @@ pub struct StagingMicroblock {
 pub struct StagingBlock {
     pub burn_header_hash: BurnchainHeaderHash,
     pub burn_header_timestamp: u64,
+    pub burn_header_height: u64,
     pub anchored_block_hash: BlockHeaderHash,
     pub parent_burn_header_hash: BurnchainHeaderHash,
     pub parent_anchored_block_hash: BlockHeaderHash,
@@ -122,6 +126,65 @@ pub struct StagingUserBurnSupport {
     pub vtxindex: u32,
 }
 
+/// The PoX reward slot of the block commit that elected a staging block, and the reward
+/// addresses the commit paid (empty if it burnt).  Checked when the block is processed, since
+/// only its chain state determines what the commit had to pay (see
+/// StacksChainState::process_pox_reward_slot()).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StagingPoxPayout {
+    pub reward_slot: PoxRewardSlot,
+    pub commit_outs: Vec<StacksAddress>
+}
+
+/// Two distinct microblocks that build off of the same parent anchored block with the same
+/// sequence number.  Since both were signed by that block's leader, the leader equivocated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MicroblockConflict {
+    pub burn_header_hash: BurnchainHeaderHash,
+    pub anchored_block_hash: BlockHeaderHash,
+    pub header_1: StacksMicroblockHeader,
+    pub header_2: StacksMicroblockHeader,
+    pub reported: bool
+}
+
+/// The verdict on a proposed anchored block that was validated without being stored
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProposedBlockVerdict {
+    /// The block is valid.  Executing it on its parent reaches `state_index_root`, which matches
+    /// its header.
+    Valid { state_index_root: TrieHash, receipts: Vec<StacksTransactionReceipt> },
+    /// The block is malformed, or does not build on the given parent
+    InvalidBlock(String),
+    /// The given microblock stream does not connect the block to its parent, or one of its
+    /// microblocks contains a transaction that could not be mined
+    InvalidMicroblocks(String),
+    /// One of the block's transactions could not be mined
+    InvalidTransactions(String),
+    /// Executing the block on its parent reaches `computed`, but its header claims `expected`
+    StateRootMismatch { expected: TrieHash, computed: TrieHash },
+    /// No sortition this node has processed elected the block, so the burnchain STX operations
+    /// and burnchain height it would be executed with are not known yet
+    Unelected
+}
+
+impl ProposedBlockVerdict {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            ProposedBlockVerdict::Valid { .. } => true,
+            _ => false
+        }
+    }
+
+    /// State root reached by executing the block, if it could be executed
+    pub fn computed_state_index_root(&self) -> Option<&TrieHash> {
+        match *self {
+            ProposedBlockVerdict::Valid { ref state_index_root, .. } => Some(state_index_root),
+            ProposedBlockVerdict::StateRootMismatch { ref computed, .. } => Some(computed),
+            _ => None
+        }
+    }
+}
+
 impl StagingBlock {
     pub fn is_genesis(&self) -> bool {
         self.parent_anchored_block_hash == FIRST_STACKS_BLOCK_HASH
@@ -164,6 +227,7 @@ impl FromRow<StagingBlock> for StagingBlock {
         let parent_anchored_block_hash : BlockHeaderHash = BlockHeaderHash::from_column(row, "parent_anchored_block_hash")?;
         let burn_header_hash : BurnchainHeaderHash = BurnchainHeaderHash::from_column(row, "burn_header_hash")?;
         let burn_header_timestamp_i64 : i64 = row.get("burn_header_timestamp");
+        let burn_header_height_i64 : i64 = row.get("burn_header_height");
         let parent_burn_header_hash: BurnchainHeaderHash = BurnchainHeaderHash::from_column(row, "parent_burn_header_hash")?;
         let parent_microblock_hash : BlockHeaderHash = BlockHeaderHash::from_column(row, "parent_microblock_hash")?;
         let parent_microblock_seq : u16 = row.get("parent_microblock_seq");
@@ -184,6 +248,9 @@ impl FromRow<StagingBlock> for StagingBlock {
         if burn_header_timestamp_i64 < 0 {
             return Err(db_error::ParseError);
         }
+        if burn_header_height_i64 < 0 {
+            return Err(db_error::ParseError);
+        }
 
         let processed = if processed_i64 != 0 { true } else { false };
         let attacheable = if attacheable_i64 != 0 { true } else { false };
@@ -192,12 +259,14 @@ impl FromRow<StagingBlock> for StagingBlock {
         let commit_burn = commit_burn_i64 as u64;
         let sortition_burn = sortition_burn_i64 as u64;
         let burn_header_timestamp = burn_header_timestamp_i64 as u64;
+        let burn_header_height = burn_header_height_i64 as u64;
 
         Ok(StagingBlock {
             anchored_block_hash,
             parent_anchored_block_hash,
             burn_header_hash,
             burn_header_timestamp,
+            burn_header_height,
             parent_burn_header_hash,
             parent_microblock_hash,
             parent_microblock_seq,
@@ -212,6 +281,33 @@ impl FromRow<StagingBlock> for StagingBlock {
     }
 }
 
+impl FromRow<StagingPoxPayout> for StagingPoxPayout {
+    fn from_row<'a>(row: &'a Row) -> Result<StagingPoxPayout, db_error> {
+        let anchor_burn_header_hash = BurnchainHeaderHash::from_column(row, "anchor_burn_header_hash")?;
+        let anchor_block_hash = BlockHeaderHash::from_column(row, "anchor_block_hash")?;
+        let reward_cycle_start_height_i64 : i64 = row.get("reward_cycle_start_height");
+        let slot_i64 : i64 = row.get("slot");
+        let commit_outs_json : String = row.get("commit_outs");
+
+        if reward_cycle_start_height_i64 < 0 || slot_i64 < 0 {
+            return Err(db_error::ParseError);
+        }
+
+        let commit_outs = serde_json::from_str::<Vec<StacksAddress>>(&commit_outs_json)
+            .map_err(|e| db_error::SerializationError(e))?;
+
+        Ok(StagingPoxPayout {
+            reward_slot: PoxRewardSlot {
+                anchor_burn_header_hash,
+                anchor_block_hash,
+                reward_cycle_start_height: reward_cycle_start_height_i64 as u64,
+                slot: slot_i64 as u64
+            },
+            commit_outs
+        })
+    }
+}
+
 impl FromRow<StagingUserBurnSupport> for StagingUserBurnSupport {
     fn from_row<'a>(row: &'a Row) -> Result<StagingUserBurnSupport, db_error> {
         let anchored_block_hash : BlockHeaderHash = BlockHeaderHash::from_column(row, "anchored_block_hash")?;
@@ -236,6 +332,28 @@ impl FromRow<StagingUserBurnSupport> for StagingUserBurnSupport {
     }
 }
 
+impl FromRow<MicroblockConflict> for MicroblockConflict {
+    fn from_row<'a>(row: &'a Row) -> Result<MicroblockConflict, db_error> {
+        let anchored_block_hash : BlockHeaderHash = BlockHeaderHash::from_column(row, "anchored_block_hash")?;
+        let burn_header_hash : BurnchainHeaderHash = BurnchainHeaderHash::from_column(row, "burn_header_hash")?;
+        let header_1_bytes : Vec<u8> = row.get("header_1");
+        let header_2_bytes : Vec<u8> = row.get("header_2");
+        let reported_i64 : i64 = row.get("reported");
+
+        let header_1 = StacksMicroblockHeader::consensus_deserialize(&mut &header_1_bytes[..]).map_err(|_e| db_error::ParseError)?;
+        let header_2 = StacksMicroblockHeader::consensus_deserialize(&mut &header_2_bytes[..]).map_err(|_e| db_error::ParseError)?;
+        let reported = if reported_i64 != 0 { true } else { false };
+
+        Ok(MicroblockConflict {
+            burn_header_hash,
+            anchored_block_hash,
+            header_1,
+            header_2,
+            reported
+        })
+    }
+}
+
 impl StagingMicroblock {
     #[cfg(test)]
     pub fn try_into_microblock(self) -> Result<StacksMicroblock, StagingMicroblock> {
@@ -263,11 +381,25 @@ const STACKS_BLOCK_INDEX_SQL : &'static [&'static str]= &[
     );
     "#,
     r#"
+    -- Conflicting microblocks -- pairs of staging microblocks that a leader signed with the same parent anchored block and sequence number.
+    CREATE TABLE staging_microblock_conflicts(anchored_block_hash TEXT NOT NULL,
+                                              burn_header_hash TEXT NOT NULL,
+                                              sequence INT NOT NULL,
+                                              microblock_hash_1 TEXT NOT NULL,
+                                              microblock_hash_2 TEXT NOT NULL,
+                                              header_1 BLOB NOT NULL,
+                                              header_2 BLOB NOT NULL,
+                                              reported INT NOT NULL,        -- set to 1 once a PoisonMicroblock transaction has been queued for this conflict
+                                              PRIMARY KEY(anchored_block_hash,burn_header_hash,microblock_hash_1,microblock_hash_2)
+    );
+    "#,
+    r#"
     -- Staging blocks -- preprocessed blocks queued up for subsequent processing and inclusion in the chunk store.
     CREATE TABLE staging_blocks(anchored_block_hash TEXT NOT NULL,
                                 parent_anchored_block_hash TEXT NOT NULL,
                                 burn_header_hash TEXT NOT NULL,
                                 burn_header_timestamp INT NOT NULL,
+                                burn_header_height INT NOT NULL,
                                 parent_burn_header_hash TEXT NOT NULL,
                                 parent_microblock_hash TEXT NOT NULL,
                                 parent_microblock_seq INT NOT NULL,
@@ -296,6 +428,52 @@ const STACKS_BLOCK_INDEX_SQL : &'static [&'static str]= &[
                                            vtxindex INT NOT NULL
     );
     "#,
+    r#"
+    -- burnchain STX transfers to apply when a block is processed.
+    -- burn_header_hash is the burnchain block that contains the transfer, and
+    -- block_burn_header_hash is the burnchain block that elected the Stacks block.
+    -- A block applies its transfers and stack-stx operations together, in the order in
+    -- which they were mined (by block_height, then vtxindex).
+    CREATE TABLE staging_transfer_stx(anchored_block_hash TEXT NOT NULL,
+                                      block_burn_header_hash TEXT NOT NULL,
+                                      txid TEXT NOT NULL,
+                                      vtxindex INT NOT NULL,
+                                      block_height INT NOT NULL,
+                                      burn_header_hash TEXT NOT NULL,
+                                      sender TEXT NOT NULL,
+                                      recipient TEXT NOT NULL,
+                                      transfered_ustx TEXT NOT NULL,
+                                      memo TEXT NOT NULL
+    );
+    "#,
+    r#"
+    -- burnchain stack-stx operations to apply when a block is processed
+    CREATE TABLE staging_stack_stx(anchored_block_hash TEXT NOT NULL,
+                                   block_burn_header_hash TEXT NOT NULL,
+                                   txid TEXT NOT NULL,
+                                   vtxindex INT NOT NULL,
+                                   block_height INT NOT NULL,
+                                   burn_header_hash TEXT NOT NULL,
+                                   sender TEXT NOT NULL,
+                                   reward_addr TEXT NOT NULL,
+                                   stacked_ustx TEXT NOT NULL,
+                                   num_cycles INT NOT NULL
+    );
+    "#,
+    r#"
+    -- the PoX reward slot of the block commit that elected a block, and the JSON-encoded list of
+    -- PoX reward addresses the commit paid (empty if burnt).  Checked when the block is processed.
+    -- Blocks elected outside of any reward slot have no row, since their commits must burn.
+    CREATE TABLE staging_pox_payouts(anchored_block_hash TEXT NOT NULL,
+                                     block_burn_header_hash TEXT NOT NULL,
+                                     anchor_burn_header_hash TEXT NOT NULL,
+                                     anchor_block_hash TEXT NOT NULL,
+                                     reward_cycle_start_height INT NOT NULL,
+                                     slot INT NOT NULL,
+                                     commit_outs TEXT NOT NULL,
+                                     PRIMARY KEY(anchored_block_hash,block_burn_header_hash)
+    );
+    "#,
 ];
 
 
@@ -719,6 +897,17 @@ impl StacksChainState {
         }
     }
 
+    /// Load up the staging records of every (non-orphaned) sortition that elected the given block
+    /// on top of the given parent, in burnchain order.
+    fn load_staging_block_sortitions(block_conn: &DBConn, parent_tip: &StacksHeaderInfo, block: &StacksBlock) -> Result<Vec<StagingBlock>, Error> {
+        let sql = "SELECT * FROM staging_blocks WHERE anchored_block_hash = ?1 AND orphaned = 0 ORDER BY burn_header_height, burn_header_hash".to_string();
+        let args: &[&dyn ToSql] = &[&block.block_hash()];
+        let rows = query_rows::<StagingBlock, _>(block_conn, &sql, args).map_err(Error::DBError)?;
+        Ok(rows.into_iter()
+           .filter(|staging_block| block.header.is_genesis() || staging_block.parent_burn_header_hash == parent_tip.burn_header_hash)
+           .collect())
+    }
+
     /// Load up the list of users who burned for an unprocessed block.
     fn load_staging_block_user_supports(block_conn: &DBConn, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash) -> Result<Vec<StagingUserBurnSupport>, Error> {
         let sql = "SELECT * FROM staging_user_burn_support WHERE anchored_block_hash = ?1 AND burn_header_hash = ?2".to_string();
@@ -727,6 +916,38 @@ impl StacksChainState {
         Ok(rows)
     }
     
+    /// Load up the burnchain STX operations an unprocessed block must apply, in the order in
+    /// which they were mined.
+    fn load_staging_block_burnchain_stx_ops(block_conn: &DBConn, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash) -> Result<Vec<BlockstackOperationType>, Error> {
+        let args: &[&dyn ToSql] = &[&block_hash, &burn_header_hash];
+
+        let transfer_sql = "SELECT * FROM staging_transfer_stx WHERE anchored_block_hash = ?1 AND block_burn_header_hash = ?2".to_string();
+        let transfer_ops = query_rows::<TransferStxOp, _>(block_conn, &transfer_sql, args).map_err(Error::DBError)?;
+
+        let stack_sql = "SELECT * FROM staging_stack_stx WHERE anchored_block_hash = ?1 AND block_burn_header_hash = ?2".to_string();
+        let stack_ops = query_rows::<StackStxOp, _>(block_conn, &stack_sql, args).map_err(Error::DBError)?;
+
+        let mut ops = vec![];
+        for op in transfer_ops.into_iter() {
+            ops.push(((op.block_height, op.vtxindex), BlockstackOperationType::TransferStx(op)));
+        }
+        for op in stack_ops.into_iter() {
+            ops.push(((op.block_height, op.vtxindex), BlockstackOperationType::StackStx(op)));
+        }
+
+        ops.sort_by_key(|&(order, _)| order);
+        Ok(ops.into_iter().map(|(_, op)| op).collect())
+    }
+
+    /// Load up the PoX reward slot and payouts of the block commit that elected an unprocessed
+    /// block, if it was elected in a reward slot.
+    fn load_staging_block_pox_payout(block_conn: &DBConn, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash) -> Result<Option<StagingPoxPayout>, Error> {
+        let sql = "SELECT * FROM staging_pox_payouts WHERE anchored_block_hash = ?1 AND block_burn_header_hash = ?2".to_string();
+        let args: &[&dyn ToSql] = &[&block_hash, &burn_header_hash];
+        let mut rows = query_rows::<StagingPoxPayout, _>(block_conn, &sql, args).map_err(Error::DBError)?;
+        Ok(rows.pop())
+    }
+
     /// Load up a queued block's queued pubkey hash
     fn load_staging_block_pubkey_hash(block_conn: &DBConn, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash) -> Result<Option<Hash160>, Error> {
         let sql = format!("SELECT microblock_pubkey_hash FROM staging_blocks WHERE anchored_block_hash = ?1 AND burn_header_hash = ?2 AND processed = 0 AND orphaned = 0");
@@ -868,10 +1089,11 @@ impl StacksChainState {
     /// Store a preprocessed block, queuing it up for subsequent processing.
     /// The caller should at least verify that the block is attached to some fork in the burn
     /// chain.
-    fn store_staging_block<'a>(tx: &mut BlocksDBTx<'a>, burn_hash: &BurnchainHeaderHash, burn_header_timestamp: u64, block: &StacksBlock, parent_burn_header_hash: &BurnchainHeaderHash, commit_burn: u64, sortition_burn: u64) -> Result<(), Error> {
+    fn store_staging_block<'a>(tx: &mut BlocksDBTx<'a>, burn_hash: &BurnchainHeaderHash, burn_header_timestamp: u64, burn_header_height: u64, block: &StacksBlock, parent_burn_header_hash: &BurnchainHeaderHash, commit_burn: u64, sortition_burn: u64) -> Result<(), Error> {
         assert!(commit_burn < i64::max_value() as u64);
         assert!(sortition_burn < i64::max_value() as u64);
         assert!(burn_header_timestamp < i64::max_value() as u64);
+        assert!(burn_header_height < i64::max_value() as u64);
 
         let block_hash = block.block_hash();
         let mut block_bytes = vec![];
@@ -894,10 +1116,10 @@ impl StacksChainState {
 
         // store block metadata
         let sql = "INSERT OR REPLACE INTO staging_blocks \
-                   (anchored_block_hash, parent_anchored_block_hash, burn_header_hash, burn_header_timestamp, parent_burn_header_hash, parent_microblock_hash, parent_microblock_seq, microblock_pubkey_hash, attacheable, processed, orphaned, commit_burn, sortition_burn) \
-                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)";
+                   (anchored_block_hash, parent_anchored_block_hash, burn_header_hash, burn_header_timestamp, burn_header_height, parent_burn_header_hash, parent_microblock_hash, parent_microblock_seq, microblock_pubkey_hash, attacheable, processed, orphaned, commit_burn, sortition_burn) \
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)";
         let args: &[&dyn ToSql] = &[
-            &block_hash, &block.header.parent_block, &burn_hash, &(burn_header_timestamp as i64), &parent_burn_header_hash,
+            &block_hash, &block.header.parent_block, &burn_hash, &(burn_header_timestamp as i64), &(burn_header_height as i64), &parent_burn_header_hash,
             &block.header.parent_microblock, &block.header.parent_microblock_sequence,
             &block.header.microblock_pubkey_hash, &attacheable, &0, &0, &(commit_burn as i64), &(sortition_burn as i64)];
 
@@ -953,6 +1175,90 @@ impl StacksChainState {
         Ok(())
     }
 
+    /// Record any staging microblocks that conflict with the given (signed) microblock -- i.e. that
+    /// build off of the same parent anchored block with the same sequence number, but are
+    /// different.  Returns the number of conflicts found.
+    fn store_staging_microblock_conflicts<'a>(tx: &mut BlocksDBTx<'a>, burn_header_hash: &BurnchainHeaderHash, anchored_block_hash: &BlockHeaderHash, microblock: &StacksMicroblock) -> Result<usize, Error> {
+        let microblock_hash = microblock.block_hash();
+        let sql = "SELECT * FROM staging_microblocks WHERE anchored_block_hash = ?1 AND burn_header_hash = ?2 AND sequence = ?3 AND microblock_hash != ?4".to_string();
+        let args: &[&dyn ToSql] = &[&anchored_block_hash, &burn_header_hash, &microblock.header.sequence, &microblock_hash];
+        let conflicting_microblocks = query_rows::<StagingMicroblock, _>(tx, &sql, args).map_err(Error::DBError)?;
+
+        let mut header_bytes = vec![];
+        microblock.header.consensus_serialize(&mut header_bytes).map_err(Error::NetError)?;
+
+        let mut num_conflicts = 0;
+        for conflicting_microblock in conflicting_microblocks.iter() {
+            let conflicting_header = match StacksChainState::load_staging_microblock_bytes(tx, &conflicting_microblock.microblock_hash)? {
+                Some(bytes) => {
+                    let mblock = StacksMicroblock::consensus_deserialize(&mut &bytes[..]).map_err(Error::NetError)?;
+                    mblock.header
+                },
+                None => {
+                    // already processed and moved to the chunk store
+                    test_debug!("No staging data for conflicting microblock {}", &conflicting_microblock.microblock_hash);
+                    continue;
+                }
+            };
+
+            warn!("Leader of {}/{} equivocated: microblocks {} and {} both have sequence {}", burn_header_hash, anchored_block_hash, &conflicting_microblock.microblock_hash, &microblock_hash, microblock.header.sequence);
+
+            let mut conflicting_header_bytes = vec![];
+            conflicting_header.consensus_serialize(&mut conflicting_header_bytes).map_err(Error::NetError)?;
+
+            let insert_sql = "INSERT OR IGNORE INTO staging_microblock_conflicts (anchored_block_hash, burn_header_hash, sequence, microblock_hash_1, microblock_hash_2, header_1, header_2, reported) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)";
+            let insert_args: &[&dyn ToSql] = &[&anchored_block_hash, &burn_header_hash, &microblock.header.sequence, &conflicting_microblock.microblock_hash, &microblock_hash, &conflicting_header_bytes, &header_bytes, &0];
+
+            tx.execute(&insert_sql, insert_args)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+            num_conflicts += 1;
+        }
+
+        Ok(num_conflicts)
+    }
+
+    /// Get the microblock conflicts that have not yet been reported in a PoisonMicroblock
+    /// transaction.
+    pub fn get_unreported_microblock_conflicts(blocks_conn: &DBConn) -> Result<Vec<MicroblockConflict>, Error> {
+        let sql = "SELECT * FROM staging_microblock_conflicts WHERE reported = 0".to_string();
+        let rows = query_rows::<MicroblockConflict, _>(blocks_conn, &sql, NO_PARAMS).map_err(Error::DBError)?;
+        Ok(rows)
+    }
+
+    /// Mark a microblock conflict as reported, so we don't report it again.
+    pub fn set_microblock_conflict_reported(&mut self, conflict: &MicroblockConflict) -> Result<(), Error> {
+        let blocks_tx = self.blocks_tx_begin()?;
+
+        let sql = "UPDATE staging_microblock_conflicts SET reported = 1 WHERE anchored_block_hash = ?1 AND burn_header_hash = ?2 AND microblock_hash_1 = ?3 AND microblock_hash_2 = ?4";
+        let args: &[&dyn ToSql] = &[&conflict.anchored_block_hash, &conflict.burn_header_hash, &conflict.header_1.block_hash(), &conflict.header_2.block_hash()];
+
+        blocks_tx.execute(&sql, args)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        blocks_tx.commit().map_err(Error::DBError)?;
+        Ok(())
+    }
+
+    /// Make a signed PoisonMicroblock transaction that reports a microblock conflict.  The
+    /// reporter pays the fee from the account of the given private key.
+    pub fn make_poison_microblock_tx(&self, conflict: &MicroblockConflict, privk: &StacksPrivateKey, nonce: u64, fee_rate: u64) -> Result<StacksTransaction, Error> {
+        let version = if self.mainnet { TransactionVersion::Mainnet } else { TransactionVersion::Testnet };
+        let auth = TransactionAuth::from_p2pkh(privk)
+            .ok_or(Error::InvalidStacksTransaction("Failed to create transaction authorization from private key".to_string()))?;
+
+        let mut tx = StacksTransaction::new(version, auth, TransactionPayload::PoisonMicroblock(conflict.header_1.clone(), conflict.header_2.clone()));
+        tx.chain_id = self.chain_id;
+        tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
+        tx.auth.set_origin_nonce(nonce);
+        tx.set_fee_rate(fee_rate);
+
+        let mut tx_signer = StacksTransactionSigner::new(&tx);
+        tx_signer.sign_origin(privk).map_err(Error::NetError)?;
+
+        tx_signer.get_tx().ok_or(Error::InvalidStacksTransaction("Failed to sign poison microblock transaction".to_string()))
+    }
+
     /// Store users who burned in support of a block
     fn store_staging_block_user_burn_supports<'a>(tx: &mut BlocksDBTx<'a>, burn_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, burn_supports: &Vec<UserBurnSupportOp>) -> Result<(), Error> {
         for burn_support in burn_supports.iter() {
@@ -970,6 +1276,55 @@ impl StacksChainState {
         Ok(())
     }
 
+    /// Store the burnchain STX operations a block must apply when it is processed.
+    fn store_staging_block_burnchain_stx_ops<'a>(tx: &mut BlocksDBTx<'a>, burn_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, ops: &Vec<BlockstackOperationType>) -> Result<(), Error> {
+        for op in ops.iter() {
+            match op {
+                BlockstackOperationType::TransferStx(ref op) => {
+                    let sql = "INSERT INTO staging_transfer_stx (anchored_block_hash, block_burn_header_hash, txid, vtxindex, block_height, burn_header_hash, sender, recipient, transfered_ustx, memo) \
+                               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)";
+                    let args: &[&dyn ToSql] = &[&block_hash, &burn_hash, &op.txid, &op.vtxindex, &(op.block_height as i64), &op.burn_header_hash,
+                                                &op.sender.to_string(), &op.recipient.to_string(), &format!("{}", op.transfered_ustx), &to_hex(&op.memo[..])];
+
+                    tx.execute(&sql, args)
+                        .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+                },
+                BlockstackOperationType::StackStx(ref op) => {
+                    let sql = "INSERT INTO staging_stack_stx (anchored_block_hash, block_burn_header_hash, txid, vtxindex, block_height, burn_header_hash, sender, reward_addr, stacked_ustx, num_cycles) \
+                               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)";
+                    let args: &[&dyn ToSql] = &[&block_hash, &burn_hash, &op.txid, &op.vtxindex, &(op.block_height as i64), &op.burn_header_hash,
+                                                &op.sender.to_string(), &op.reward_addr.to_string(), &format!("{}", op.stacked_ustx), &op.num_cycles];
+
+                    tx.execute(&sql, args)
+                        .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+                },
+                _ => {
+                    // not an STX operation
+                    continue;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Store the PoX reward slot and payouts of the block commit that elected a block, to be
+    /// checked when the block is processed.
+    fn store_staging_block_pox_payout<'a>(tx: &mut BlocksDBTx<'a>, burn_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, pox_payout: &StagingPoxPayout) -> Result<(), Error> {
+        let commit_outs_str = serde_json::to_string(&pox_payout.commit_outs)
+            .map_err(|e| Error::DBError(db_error::SerializationError(e)))?;
+
+        let sql = "INSERT OR REPLACE INTO staging_pox_payouts (anchored_block_hash, block_burn_header_hash, anchor_burn_header_hash, anchor_block_hash, reward_cycle_start_height, slot, commit_outs) \
+                   VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+        let args: &[&dyn ToSql] = &[&block_hash, &burn_hash, &pox_payout.reward_slot.anchor_burn_header_hash, &pox_payout.reward_slot.anchor_block_hash,
+                                    &(pox_payout.reward_slot.reward_cycle_start_height as i64), &(pox_payout.reward_slot.slot as i64), &commit_outs_str];
+
+        tx.execute(&sql, args)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        Ok(())
+    }
+
     /// Do we have a block queued up, and if so, is it being processed?.
     /// Return Some(processed) if the block is queued up -- true if processed, false if not
     /// Return None if the block is not queued up
@@ -1123,6 +1478,48 @@ impl StacksChainState {
         Ok(())
     }
 
+    /// Remove a block or microblock stream from the chunk store, if it is there.
+    fn delete_block_file(blocks_path: &String, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash) -> Result<(), Error> {
+        let block_path = StacksChainState::get_block_path(blocks_path, burn_header_hash, block_hash)?;
+        match fs::remove_file(&block_path) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    Ok(())
+                }
+                else {
+                    Err(Error::DBError(db_error::IOError(e)))
+                }
+            }
+        }
+    }
+
+    /// Delete a processed anchored block from the chunk store, along with all microblock data
+    /// built on it.  The staging rows are kept, so the block and its microblocks are recognized
+    /// (and not stored again) if they are received again.
+    fn delete_pruned_epoch_data<'a>(tx: &mut BlocksDBTx<'a>, burn_hash: &BurnchainHeaderHash, anchored_block_hash: &BlockHeaderHash) -> Result<(), Error> {
+        let microblocks_sql = "SELECT microblock_hash FROM staging_microblocks WHERE burn_header_hash = ?1 AND anchored_block_hash = ?2".to_string();
+        let microblocks_args: &[&dyn ToSql] = &[&burn_hash, &anchored_block_hash];
+        let microblock_hashes = query_row_columns::<BlockHeaderHash, _>(tx, &microblocks_sql, microblocks_args, "microblock_hash")
+            .map_err(Error::DBError)?;
+
+        // confirmed streams are stored under the hash of their first microblock (there can be
+        // more than one if the leader equivocated)
+        let stream_heads_sql = "SELECT microblock_hash FROM staging_microblocks WHERE burn_header_hash = ?1 AND anchored_block_hash = ?2 AND sequence = 0".to_string();
+        let stream_heads = query_row_columns::<BlockHeaderHash, _>(tx, &stream_heads_sql, microblocks_args, "microblock_hash")
+            .map_err(Error::DBError)?;
+
+        for mblock_hash in microblock_hashes.iter() {
+            StacksChainState::delete_staging_microblock_data(tx, mblock_hash)?;
+        }
+
+        for stream_head in stream_heads.iter() {
+            StacksChainState::delete_block_file(tx.get_blocks_path(), burn_hash, stream_head)?;
+        }
+
+        StacksChainState::delete_block_file(tx.get_blocks_path(), burn_hash, anchored_block_hash)
+    }
+
     /// Clear out a staging block -- move it to the chunk store (either wholly, or mark it invalid
     /// by writing an empty file).
     /// Mark its children as attacheable.
@@ -1500,6 +1897,33 @@ impl StacksChainState {
         Ok(Some((block_commit.burn_fee, sortition_burns)))
     }
 
+    /// Verify that the block commit that elected a block paid the PoX reward address it had to,
+    /// given the block's chain state (see StacksChainState::process_pox_reward_slot()).  Call
+    /// this with the block's Clarity transaction before any of its transactions are applied.
+    /// Must be applied identically by miners and block validators, since it alters the state root.
+    fn check_pox_payout<'a>(clarity_tx: &mut ClarityTx<'a>, parent_burn_header_hash: &BurnchainHeaderHash, parent_block_hash: &BlockHeaderHash, pox_payout: &Option<StagingPoxPayout>) -> Result<(), Error> {
+        let pox_payout = match pox_payout {
+            Some(ref pox_payout) => pox_payout,
+            None => {
+                // elected outside of any reward slot, so the block commit was checked to burn
+                return Ok(());
+            }
+        };
+
+        let expected_commit_outs = match StacksChainState::process_pox_reward_slot(clarity_tx, parent_burn_header_hash, parent_block_hash, &pox_payout.reward_slot)? {
+            Some(reward_address) => vec![reward_address],
+            None => vec![]
+        };
+
+        if expected_commit_outs != pox_payout.commit_outs {
+            let msg = format!("Block commit must pay {:?}, but paid {:?}", &expected_commit_outs, &pox_payout.commit_outs);
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksBlock(msg));
+        }
+
+        Ok(())
+    }
+
     /// Pre-process and store an anchored block to staging, queuing it up for
     /// subsequent processing once all of its ancestors have been processed.
     ///
@@ -1532,14 +1956,55 @@ impl StacksChainState {
         let user_burns = BurnDB::get_winning_user_burns_by_block(burn_tx, burn_header_hash)
             .map_err(Error::DBError)?;
 
+        // find all burnchain STX operations this block must apply -- i.e. the ones sent after
+        // its parent's sortition (inclusive) and before its own sortition.
+        let sortition_snapshot = match BurnDB::get_block_snapshot(burn_tx, burn_header_hash).map_err(Error::DBError)? {
+            Some(sn) => sn,
+            None => {
+                let msg = format!("Invalid block {}: no snapshot for burnchain block {}", block.block_hash(), burn_header_hash);
+                warn!("{}", &msg);
+                return Err(Error::InvalidStacksBlock(msg));
+            }
+        };
+        let burn_header_height = sortition_snapshot.block_height;
+        let burnchain_stx_ops = BurnDB::get_burnchain_stx_ops(burn_tx, parent_burn_header_hash, &sortition_snapshot.parent_burn_header_hash).map_err(Error::DBError)?;
+
+        // find the PoX reward slot of the block commit that elected this block.  Whether the
+        // commit paid the right reward address depends on this block's chain state, so that's
+        // checked when the block is processed.
+        let commit_outs = BurnDB::get_block_commit_for_stacks_block(burn_tx, burn_header_hash, &block.block_hash())
+            .map_err(Error::DBError)?
+            .expect("FATAL: validated block has no block commit")
+            .commit_outs;
+
+        let pox_payout = match self.get_pox_reward_slot(burn_tx, burn_header_height, &sortition_snapshot.parent_burn_header_hash)? {
+            Some(reward_slot) => Some(StagingPoxPayout { reward_slot, commit_outs }),
+            None => {
+                if commit_outs.len() > 0 {
+                    let msg = format!("Invalid block {}: block commit must burn, but paid {:?}", block.block_hash(), &commit_outs);
+                    warn!("{}", &msg);
+                    return Err(Error::InvalidStacksBlock(msg));
+                }
+                None
+            }
+        };
+
         let mut block_tx = self.blocks_tx_begin()?;
      
         // queue block up for processing
-        StacksChainState::store_staging_block(&mut block_tx, burn_header_hash, burn_header_timestamp, &block, parent_burn_header_hash, commit_burn, sortition_burn)?;
+        StacksChainState::store_staging_block(&mut block_tx, burn_header_hash, burn_header_timestamp, burn_header_height, &block, parent_burn_header_hash, commit_burn, sortition_burn)?;
 
         // store users who burned for this block so they'll get rewarded if we process it
         StacksChainState::store_staging_block_user_burn_supports(&mut block_tx, burn_header_hash, &block.block_hash(), &user_burns)?;
 
+        // store the burnchain STX operations this block must apply when we process it
+        StacksChainState::store_staging_block_burnchain_stx_ops(&mut block_tx, burn_header_hash, &block.block_hash(), &burnchain_stx_ops)?;
+
+        // store what the block commit paid, so we can check it when we process the block
+        if let Some(ref pox_payout) = pox_payout {
+            StacksChainState::store_staging_block_pox_payout(&mut block_tx, burn_header_hash, &block.block_hash(), pox_payout)?;
+        }
+
         block_tx.commit().map_err(Error::DBError)?;
 
         // ready to go
@@ -1598,6 +2063,9 @@ impl StacksChainState {
             return Ok(false);
         }
 
+        // did the leader equivocate?  Remember it so it can be reported.
+        StacksChainState::store_staging_microblock_conflicts(&mut blocks_tx, burn_header_hash, anchored_block_hash, microblock)?;
+
         // add to staging
         StacksChainState::store_staging_microblock(&mut blocks_tx, burn_header_hash, anchored_block_hash, microblock)?;
         
@@ -1735,6 +2203,8 @@ impl StacksChainState {
     fn find_next_staging_block(blocks_conn: &DBConn, blocks_path: &String, headers_conn: &DBConn) -> Result<Option<(Vec<StacksMicroblock>, StagingBlock)>, Error> {
         test_debug!("Find next staging block");
 
+        let pruned_horizon = StacksChainState::get_pruned_horizon(headers_conn)?;
+
         // go through staging blocks and see if any of them match headers and are attacheable.
         // pick randomly -- don't allow the network sender to choose the processing order!
         let sql = "SELECT * FROM staging_blocks WHERE processed = 0 AND attacheable = 1 AND orphaned = 0 ORDER BY RANDOM()".to_string();
@@ -1774,8 +2244,15 @@ impl StacksChainState {
                                     false
                                 }
                                 1 => {
-                                    // can process this block 
-                                    true
+                                    if hdr_rows[0].block_height < pruned_horizon {
+                                        // the parent's block data and state history were pruned
+                                        debug!("Cannot attach {}/{}: parent {}/{} was pruned", &candidate.burn_header_hash, &candidate.anchored_block_hash, &candidate.parent_burn_header_hash, &candidate.parent_anchored_block_hash);
+                                        false
+                                    }
+                                    else {
+                                        // can process this block 
+                                        true
+                                    }
                                 },
                                 _ => {
                                     // should be impossible -- stored the same block twice
@@ -1822,34 +2299,43 @@ impl StacksChainState {
     }
 
     /// Process a stream of microblocks
-    /// Return the fees and burns.
+    /// Return the fees, burns, and transaction receipts.
     /// TODO: if we find an invalid Stacks microblock, then punish the miner who produced it
-    pub fn process_microblocks_transactions<'a>(clarity_tx: &mut ClarityTx<'a>, microblocks: &Vec<StacksMicroblock>) -> Result<(u128, u128), (Error, BlockHeaderHash)> {
+    pub fn process_microblocks_transactions<'a>(clarity_tx: &mut ClarityTx<'a>, microblocks: &Vec<StacksMicroblock>) -> Result<(u128, u128, Vec<StacksTransactionReceipt>), (Error, BlockHeaderHash)> {
         let mut fees = 0u128;
         let mut burns = 0u128;
+        let mut receipts = vec![];
         for microblock in microblocks.iter() {
-            for tx in microblock.txs.iter() {
-                let (tx_fee, tx_burns) = StacksChainState::process_transaction(clarity_tx, tx)
+            for (i, tx) in microblock.txs.iter().enumerate() {
+                let (tx_fee, mut receipt) = StacksChainState::process_transaction(clarity_tx, tx)
                     .map_err(|e| (e, microblock.block_hash()))?;
 
                 fees = fees.checked_add(tx_fee as u128).expect("Fee overflow");
-                burns = burns.checked_add(tx_burns as u128).expect("Burns overflow");
+                burns = burns.checked_add(receipt.stx_burned).expect("Burns overflow");
+
+                receipt.microblock_hash = Some(microblock.block_hash());
+                receipt.tx_index = i as u32;
+                receipts.push(receipt);
             }
         }
-        Ok((fees, burns))
+        Ok((fees, burns, receipts))
     }
 
     /// Process a single anchored block.
-    /// Return the fees and burns.
-    fn process_block_transactions<'a>(clarity_tx: &mut ClarityTx<'a>, block: &StacksBlock) -> Result<(u128, u128), Error> {
+    /// Return the fees, burns, and transaction receipts.
+    fn process_block_transactions<'a>(clarity_tx: &mut ClarityTx<'a>, block: &StacksBlock) -> Result<(u128, u128, Vec<StacksTransactionReceipt>), Error> {
         let mut fees = 0u128;
         let mut burns = 0u128;
-        for tx in block.txs.iter() {
-            let (tx_fee, tx_burns) = StacksChainState::process_transaction(clarity_tx, tx)?;
+        let mut receipts = vec![];
+        for (i, tx) in block.txs.iter().enumerate() {
+            let (tx_fee, mut receipt) = StacksChainState::process_transaction(clarity_tx, tx)?;
             fees = fees.checked_add(tx_fee as u128).expect("Fee overflow");
-            burns = burns.checked_add(tx_burns as u128).expect("Burns overflow");
+            burns = burns.checked_add(receipt.stx_burned).expect("Burns overflow");
+
+            receipt.tx_index = i as u32;
+            receipts.push(receipt);
         }
-        Ok((fees, burns))
+        Ok((fees, burns, receipts))
     }
 
     /// Process a single matured miner reward.
@@ -1944,11 +2430,14 @@ impl StacksChainState {
                         parent_chain_tip: &StacksHeaderInfo, 
                         chain_tip_burn_header_hash: &BurnchainHeaderHash,
                         chain_tip_burn_header_timestamp: u64,
+                        chain_tip_burn_header_height: u64,
                         block: &StacksBlock, 
                         microblocks: &Vec<StacksMicroblock>,  // parent microblocks 
                         burnchain_commit_burn: u64, 
                         burnchain_sortition_burn: u64, 
-                        user_burns: &Vec<StagingUserBurnSupport>) -> Result<StacksHeaderInfo, Error>
+                        user_burns: &Vec<StagingUserBurnSupport>,
+                        burnchain_stx_ops: &Vec<BlockstackOperationType>,
+                        pox_payout: &Option<StagingPoxPayout>) -> Result<StacksHeaderInfo, Error>
     {
 
         debug!("Process block {:?} with {} transactions", &block.block_hash().to_hex(), block.txs.len());
@@ -1961,7 +2450,13 @@ impl StacksChainState {
             StacksChainState::find_mature_miner_rewards(&mut chainstate_tx.headers_tx, parent_chain_tip)?
         };
 
-        let scheduled_miner_reward = {
+        // find leaders reported for equivocating by this block's PoisonMicroblock transactions
+        let microblock_poisons = StacksChainState::find_microblock_poison_offenders(&mut chainstate_tx.headers_tx, parent_chain_tip, block)?;
+
+        // find genesis STX that vest in this block
+        let genesis_vesting_unlocks = StacksChainState::get_genesis_vesting_unlocks(&chainstate_tx.headers_tx, next_block_height)?;
+
+        let (scheduled_miner_reward, receipts) = {
             let (parent_burn_header_hash, parent_block_hash) = 
                 if block.header.is_genesis() {
                     // has to be the sentinal hashes if this block has no parent
@@ -1993,8 +2488,20 @@ impl StacksChainState {
             
             let mut clarity_tx = StacksChainState::chainstate_block_begin(chainstate_tx, clarity_instance, &parent_burn_header_hash, &parent_block_hash, &MINER_BLOCK_BURN_HEADER_HASH, &MINER_BLOCK_HEADER_HASH);
 
+            // STX locks are measured in burnchain blocks
+            StacksChainState::set_burnchain_block_height(&mut clarity_tx, chain_tip_burn_header_height);
+
+            // the block commit that elected this block must have paid its PoX reward address
+            if let Err(e) = StacksChainState::check_pox_payout(&mut clarity_tx, &parent_burn_header_hash, &parent_block_hash, pox_payout) {
+                let msg = format!("Invalid Stacks block {}: {:?}", block.block_hash(), &e);
+                warn!("{}", &msg);
+
+                clarity_tx.rollback_block();
+                return Err(Error::InvalidStacksBlock(msg));
+            }
+
             // process microblock stream
-            let (microblock_fees, _microblock_burns) = match StacksChainState::process_microblocks_transactions(&mut clarity_tx, &microblocks) {
+            let (microblock_fees, _microblock_burns, mut microblock_receipts) = match StacksChainState::process_microblocks_transactions(&mut clarity_tx, &microblocks) {
                 Err((e, offending_mblock_header_hash)) => {
                     let msg = format!("Invalid Stacks microblocks {},{} (offender {}): {:?}", block.header.parent_microblock, block.header.parent_microblock_sequence, offending_mblock_header_hash, &e);
                     warn!("{}", &msg);
@@ -2002,8 +2509,8 @@ impl StacksChainState {
                     clarity_tx.rollback_block();
                     return Err(Error::InvalidStacksMicroblock(msg, offending_mblock_header_hash));
                 },
-                Ok((fees, burns)) => {
-                    (fees, burns)
+                Ok((fees, burns, receipts)) => {
+                    (fees, burns, receipts)
                 }
             };
             
@@ -2013,7 +2520,7 @@ impl StacksChainState {
                         last_microblock_hash, last_microblock_seq, microblocks.len());
 
             // process anchored block
-            let (block_fees, block_burns) = match StacksChainState::process_block_transactions(&mut clarity_tx, &block) {
+            let (block_fees, block_burns, mut block_receipts) = match StacksChainState::process_block_transactions(&mut clarity_tx, &block) {
                 Err(e) => {
                     let msg = format!("Invalid Stacks block {}: {:?}", block.block_hash(), &e);
                     warn!("{}", &msg);
@@ -2021,9 +2528,21 @@ impl StacksChainState {
                     clarity_tx.rollback_block();
                     return Err(Error::InvalidStacksBlock(msg));
                 },
-                Ok((block_fees, block_burns)) => (block_fees, block_burns)
+                Ok((block_fees, block_burns, block_receipts)) => (block_fees, block_burns, block_receipts)
             };
 
+            // grant vested genesis STX
+            StacksChainState::process_genesis_vesting_unlocks(&mut clarity_tx, &genesis_vesting_unlocks);
+
+            // apply STX operations sent on the burnchain
+            if let Err(e) = StacksChainState::process_burnchain_stx_ops(&mut clarity_tx, burnchain_stx_ops) {
+                let msg = format!("Failed to apply burnchain STX operations for block {}: {:?}", block.block_hash(), &e);
+                warn!("{}", &msg);
+
+                clarity_tx.rollback_block();
+                return Err(Error::InvalidStacksBlock(msg));
+            }
+
             // grant matured miner rewards
             if let Some(mature_miner_rewards) = matured_miner_rewards_opt {
                 // grant in order by miner, then users
@@ -2051,7 +2570,7 @@ impl StacksChainState {
                                                                                        &block, 
                                                                                        chain_tip_burn_header_hash, 
                                                                                        next_block_height, 
-                                                                                       block_fees,                // TODO: calculate (STX/compute unit) * (compute used) 
+                                                                                       block_fees,
                                                                                        microblock_fees, 
                                                                                        block_burns,
                                                                                        burnchain_commit_burn,
@@ -2059,7 +2578,16 @@ impl StacksChainState {
                                                                                        0xffffffffffffffff)        // TODO: calculate total compute budget and scale up
                 .expect("FATAL: parsed and processed a block without a coinbase");
 
-             scheduled_miner_reward
+            // this block confirms the microblock stream's transactions and its own
+            let mut receipts = vec![];
+            receipts.append(&mut microblock_receipts);
+            receipts.append(&mut block_receipts);
+            for receipt in receipts.iter_mut() {
+                receipt.burn_header_hash = chain_tip_burn_header_hash.clone();
+                receipt.block_hash = block.block_hash();
+            }
+
+             (scheduled_miner_reward, receipts)
         };
        
         let microblock_tail_opt = match microblocks.len() {
@@ -2075,9 +2603,24 @@ impl StacksChainState {
                                                     chain_tip_burn_header_timestamp,
                                                     microblock_tail_opt,
                                                     &scheduled_miner_reward, 
-                                                    user_burns)
+                                                    user_burns,
+                                                    &microblock_poisons)
             .expect("FATAL: failed to advance chain tip");
 
+        StacksChainState::insert_confirmed_fee_rates(&mut chainstate_tx.headers_tx, &new_tip, block, microblocks)
+            .expect("FATAL: failed to record confirmed fee rates");
+
+        StacksChainState::insert_transaction_receipts(&mut chainstate_tx.headers_tx, &new_tip, &receipts)
+            .expect("FATAL: failed to record transaction receipts");
+
+        StacksChainState::insert_transaction_index(&mut chainstate_tx.headers_tx, &new_tip, block, microblocks)
+            .expect("FATAL: failed to index confirmed transactions");
+
+        if chainstate_tx.get_config().account_history {
+            StacksChainState::insert_account_history(&mut chainstate_tx.headers_tx, parent_chain_tip, &new_tip, block, microblocks, &receipts)
+                .expect("FATAL: failed to record account history");
+        }
+
         Ok(new_tip)
     }
 
@@ -2200,6 +2743,12 @@ impl StacksChainState {
         // find users that burned in support of this block, so we can calculate the miner reward
         let user_supports = StacksChainState::load_staging_block_user_supports(&chainstate_tx.blocks_tx, &next_staging_block.burn_header_hash, &next_staging_block.anchored_block_hash)?;
 
+        // find the STX operations sent on the burnchain that this block applies
+        let burnchain_stx_ops = StacksChainState::load_staging_block_burnchain_stx_ops(&chainstate_tx.blocks_tx, &next_staging_block.burn_header_hash, &next_staging_block.anchored_block_hash)?;
+
+        // find what the block commit that elected this block paid, so we can check it
+        let pox_payout = StacksChainState::load_staging_block_pox_payout(&chainstate_tx.blocks_tx, &next_staging_block.burn_header_hash, &next_staging_block.anchored_block_hash)?;
+
         // attach the block to the chain state and calculate the next chain tip.
         // Execute the confirmed microblocks' transactions against the chain state, and then
         // execute the anchored block's transactions against the chain state.
@@ -2208,11 +2757,14 @@ impl StacksChainState {
                                                                   &parent_block_header_info, 
                                                                   &next_staging_block.burn_header_hash, 
                                                                   next_staging_block.burn_header_timestamp,
+                                                                  next_staging_block.burn_header_height,
                                                                   &block, 
                                                                   &next_microblocks, 
                                                                   next_staging_block.commit_burn, 
                                                                   next_staging_block.sortition_burn, 
-                                                                  &user_supports) {
+                                                                  &user_supports,
+                                                                  &burnchain_stx_ops,
+                                                                  &pox_payout) {
             Ok(next_chain_tip) => next_chain_tip,
             Err(e) => {
                 // something's wrong with this epoch -- either a microblock was invalid, or the
@@ -2292,6 +2844,15 @@ impl StacksChainState {
             }
         }
 
+        if let Some(prune_depth) = self.prune_depth {
+            // only keep the data of the most recent prune_depth blocks, and never fewer than is
+            // safe to keep across a burnchain reorg
+            let prune_depth = cmp::max(prune_depth, MIN_PRUNE_DEPTH);
+            let max_height_sql = "SELECT IFNULL(MAX(block_height), 0) FROM block_headers".to_string();
+            let max_height = query_int(&self.headers_db, &max_height_sql, NO_PARAMS).map_err(Error::DBError)? as u64;
+            self.prune_history((max_height + 1).saturating_sub(prune_depth))?;
+        }
+
         let mut block_tx = self.blocks_tx_begin()?;
         for _ in 0..max_blocks {
             // delete up to max_blocks blocks
@@ -2303,6 +2864,252 @@ impl StacksChainState {
 
         Ok(ret)
     }
+
+    /// Get the pruned horizon: the lowest Stacks block height whose block data and Clarity state
+    /// history are still kept.  0 if nothing was ever pruned.
+    pub fn get_pruned_horizon(headers_conn: &DBConn) -> Result<u64, Error> {
+        let sql = "SELECT IFNULL(MAX(block_height), 0) FROM pruned_horizon".to_string();
+        let horizon = query_int(headers_conn, &sql, NO_PARAMS).map_err(Error::DBError)?;
+        Ok(horizon as u64)
+    }
+
+    /// Check that the chain state as of the given Stacks block can still be read, and return the
+    /// pruned horizon.  Blocks we don't know about are not considered pruned.
+    pub fn check_pruned_horizon(headers_conn: &DBConn, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash) -> Result<u64, Error> {
+        let horizon = StacksChainState::get_pruned_horizon(headers_conn)?;
+        if horizon == 0 {
+            return Ok(0);
+        }
+
+        let block_height = 
+            if *block_hash == FIRST_STACKS_BLOCK_HASH {
+                0
+            }
+            else {
+                match StacksChainState::get_anchored_block_header_info(headers_conn, burn_header_hash, block_hash)? {
+                    Some(header_info) => header_info.block_height,
+                    None => {
+                        return Ok(horizon);
+                    }
+                }
+            };
+
+        if block_height < horizon {
+            return Err(Error::BlockPrunedError(block_height, horizon));
+        }
+        Ok(horizon)
+    }
+
+    /// Prune the block data of all blocks below the given Stacks block height, in all forks, and
+    /// record the new pruned horizon.  Their anchored blocks and microblock streams are deleted
+    /// from the chunk store, but their headers are kept.  Blocks that build on pruned blocks can
+    /// no longer be processed, and the chain state as of a pruned block can no longer be read
+    /// (including via `at-block`).
+    ///
+    /// The Clarity state MARF keeps the full history of the remaining blocks: the trie nodes of
+    /// pruned blocks that no remaining block's trie shares are discarded, but each pruned trie's
+    /// root hash is kept, so state roots and Merkle proofs of later blocks are unaffected.  The
+    /// headers index is not pruned.
+    ///
+    /// Returns the number of blocks pruned.  Does nothing if the horizon would not advance.
+    pub fn prune_history(&mut self, horizon: u64) -> Result<u64, Error> {
+        let prior_horizon = StacksChainState::get_pruned_horizon(&self.headers_db)?;
+        if horizon <= prior_horizon {
+            return Ok(0);
+        }
+
+        if horizon >= (i64::max_value() as u64) {
+            return Err(Error::DBError(db_error::Overflow));
+        }
+
+        let sql = "SELECT * FROM block_headers WHERE block_height >= ?1 AND block_height < ?2".to_string();
+        let args: &[&dyn ToSql] = &[&(prior_horizon as i64), &(horizon as i64)];
+        let pruned_headers = query_rows::<StacksHeaderInfo, _>(&self.headers_db, &sql, args).map_err(Error::DBError)?;
+
+        debug!("Prune {} blocks from height {} to {}", pruned_headers.len(), prior_horizon, horizon);
+
+        let mut blocks_tx = self.blocks_tx_begin()?;
+        for header_info in pruned_headers.iter() {
+            StacksChainState::delete_pruned_epoch_data(&mut blocks_tx, &header_info.burn_header_hash, &header_info.anchored_header.block_hash())?;
+        }
+        blocks_tx.commit().map_err(Error::DBError)?;
+
+        let headers_tx = self.headers_tx_begin()?;
+        headers_tx.execute("DELETE FROM pruned_horizon", NO_PARAMS)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        headers_tx.execute("INSERT INTO pruned_horizon (block_height) VALUES (?1)", &[&(horizon as i64)])
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        headers_tx.commit().map_err(Error::DBError)?;
+
+        // only discard trie nodes once the tries can no longer be read (if this is interrupted,
+        // the nodes are just kept)
+        let already_pruned_sql = "SELECT * FROM block_headers WHERE block_height < ?1".to_string();
+        let already_pruned_headers = query_rows::<StacksHeaderInfo, _>(&self.headers_db, &already_pruned_sql, &[&(prior_horizon as i64)]).map_err(Error::DBError)?;
+
+        let pruned_index_hashes : HashSet<BlockHeaderHash> = pruned_headers.iter().map(|header_info| header_info.index_block_hash()).collect();
+        let already_pruned_index_hashes : HashSet<BlockHeaderHash> = already_pruned_headers.iter().map(|header_info| header_info.index_block_hash()).collect();
+
+        let discarded = self.clarity_state.prune_marf(&pruned_index_hashes, &already_pruned_index_hashes)
+            .map_err(|e| Error::DBError(db_error::IndexError(e)))?;
+        debug!("Discarded {} bytes of Clarity MARF nodes below height {}", discarded, horizon);
+
+        Ok(pruned_headers.len() as u64)
+    }
+
+    /// Validate a proposed anchored block without storing it or committing it to the MARF, so
+    /// miners and auditors can check a block before broadcasting it.  The block is checked
+    /// against its parent chain tip and the parent microblock stream it confirms, and executed
+    /// on top of them in a Clarity transaction that is always rolled back.
+    ///
+    /// The block is executed with everything append_block() would apply besides its
+    /// transactions: vested genesis STX, the parent's matured miner rewards (less any poison
+    /// microblock penalties), and the STX operations sent on the burnchain since its parent's
+    /// sortition, at the burnchain height of its own sortition.  The last two come from the
+    /// block's staging record, so the block must have been preprocessed with
+    /// preprocess_anchored_block(); otherwise it is `Unelected`.  A block elected in more than one
+    /// burnchain fork must be valid in each of them.
+    ///
+    /// Returns an error only if the chain state itself could not be read, or if the parent was
+    /// pruned.
+    pub fn validate_proposed_block(&mut self, parent_tip: &StacksHeaderInfo, block: &StacksBlock, microblocks: &Vec<StacksMicroblock>) -> Result<ProposedBlockVerdict, Error> {
+        let block_hash = block.block_hash();
+
+        // can't build on pruned history
+        StacksChainState::check_pruned_horizon(&self.headers_db, &parent_tip.burn_header_hash, &parent_tip.anchored_header.block_hash())?;
+
+        // must build on the parent
+        if block.header.is_genesis() {
+            if !parent_tip.is_genesis() {
+                return Ok(ProposedBlockVerdict::InvalidBlock(format!("Block {} has no parent, but was proposed as a child of {}", &block_hash, parent_tip.anchored_header.block_hash())));
+            }
+        }
+        else if block.header.parent_block != parent_tip.anchored_header.block_hash() {
+            return Ok(ProposedBlockVerdict::InvalidBlock(format!("Block {} builds on {}, not {}", &block_hash, &block.header.parent_block, parent_tip.anchored_header.block_hash())));
+        }
+
+        // static checks, as done on deserialization
+        if !block.validate_transactions_static(self.mainnet, self.chain_id) {
+            return Ok(ProposedBlockVerdict::InvalidBlock(format!("Block {} failed static transaction checks", &block_hash)));
+        }
+
+        let txid_vecs = block.txs
+            .iter()
+            .map(|tx| tx.txid().as_bytes().to_vec())
+            .collect();
+
+        let merkle_tree = MerkleTree::<Sha512Trunc256Sum>::new(&txid_vecs);
+        if merkle_tree.root() != block.header.tx_merkle_root {
+            return Ok(ProposedBlockVerdict::InvalidBlock(format!("Block {} tx Merkle root mismatch", &block_hash)));
+        }
+
+        for microblock in microblocks.iter() {
+            if !microblock.validate_transactions_static(self.mainnet, self.chain_id) {
+                return Ok(ProposedBlockVerdict::InvalidMicroblocks(format!("Microblock {} failed static transaction checks", microblock.block_hash())));
+            }
+        }
+
+        // must connect to the parent through the microblocks it confirms
+        let microblocks = match StacksChainState::validate_parent_microblock_stream(&parent_tip.anchored_header, &block.header, microblocks, true) {
+            Some((terminus, _)) => microblocks[0..terminus].to_vec(),
+            None => {
+                return Ok(ProposedBlockVerdict::InvalidMicroblocks(format!("Microblock stream does not connect block {} to its parent {}", &block_hash, parent_tip.anchored_header.block_hash())));
+            }
+        };
+
+        // find what the block would apply besides its transactions.  These headers DB
+        // transactions are only read from, and are dropped uncommitted.
+        let (matured_miner_rewards_opt, microblock_pubkey_hash_used) = {
+            let mut tx = self.headers_tx_begin()?;
+            let matured_miner_rewards_opt = StacksChainState::find_mature_miner_rewards(&mut tx, parent_tip)?;
+            let used = StacksChainState::has_microblock_pubkey_hash(&mut tx, &parent_tip.burn_header_hash, &parent_tip.anchored_header, &block.header.microblock_pubkey_hash)?;
+            (matured_miner_rewards_opt, used)
+        };
+
+        if microblock_pubkey_hash_used {
+            return Ok(ProposedBlockVerdict::InvalidBlock(format!("Block {} reuses microblock pubkey hash {}", &block_hash, &block.header.microblock_pubkey_hash)));
+        }
+
+        // find the burnchain context it would be processed in
+        let sortitions = StacksChainState::load_staging_block_sortitions(&self.blocks_db, parent_tip, block)?;
+        if sortitions.is_empty() {
+            return Ok(ProposedBlockVerdict::Unelected);
+        }
+
+        let mut verdict = ProposedBlockVerdict::Unelected;
+        for sortition in sortitions.iter() {
+            let burnchain_stx_ops = StacksChainState::load_staging_block_burnchain_stx_ops(&self.blocks_db, &sortition.burn_header_hash, &block_hash)?;
+            let pox_payout = StacksChainState::load_staging_block_pox_payout(&self.blocks_db, &sortition.burn_header_hash, &block_hash)?;
+            verdict = self.execute_proposed_block(parent_tip, block, &microblocks, sortition.burn_header_height, &burnchain_stx_ops, &pox_payout, &matured_miner_rewards_opt)?;
+            if !verdict.is_valid() {
+                break;
+            }
+        }
+        Ok(verdict)
+    }
+
+    /// Execute a proposed block on top of its parent and confirmed microblocks the same way
+    /// append_block() does, but never commit.
+    fn execute_proposed_block(&mut self, parent_tip: &StacksHeaderInfo, block: &StacksBlock, microblocks: &Vec<StacksMicroblock>, burn_header_height: u64, burnchain_stx_ops: &Vec<BlockstackOperationType>,
+                              pox_payout: &Option<StagingPoxPayout>, matured_miner_rewards_opt: &Option<Vec<MinerReward>>) -> Result<ProposedBlockVerdict, Error> {
+        let block_hash = block.block_hash();
+        let genesis_vesting_unlocks = StacksChainState::get_genesis_vesting_unlocks(&self.headers_db, block.header.total_work.work)?;
+        let (parent_burn_header_hash, parent_block_hash) =
+            if block.header.is_genesis() {
+                (FIRST_BURNCHAIN_BLOCK_HASH.clone(), FIRST_STACKS_BLOCK_HASH.clone())
+            }
+            else {
+                (parent_tip.burn_header_hash.clone(), parent_tip.anchored_header.block_hash())
+            };
+
+        let mut clarity_tx = self.block_begin(&parent_burn_header_hash, &parent_block_hash, &MINER_BLOCK_BURN_HEADER_HASH, &MINER_BLOCK_HEADER_HASH);
+        StacksChainState::set_burnchain_block_height(&mut clarity_tx, burn_header_height);
+
+        if let Err(e) = StacksChainState::check_pox_payout(&mut clarity_tx, &parent_burn_header_hash, &parent_block_hash, pox_payout) {
+            clarity_tx.rollback_block();
+            return Ok(ProposedBlockVerdict::InvalidBlock(format!("Invalid Stacks block {}: {:?}", &block_hash, &e)));
+        }
+
+        let mut receipts = match StacksChainState::process_microblocks_transactions(&mut clarity_tx, microblocks) {
+            Ok((_, _, receipts)) => receipts,
+            Err((e, offending_mblock_header_hash)) => {
+                clarity_tx.rollback_block();
+                return Ok(ProposedBlockVerdict::InvalidMicroblocks(format!("Invalid Stacks microblock {}: {:?}", offending_mblock_header_hash, &e)));
+            }
+        };
+
+        match StacksChainState::process_block_transactions(&mut clarity_tx, block) {
+            Ok((_, _, mut block_receipts)) => {
+                receipts.append(&mut block_receipts);
+            },
+            Err(e) => {
+                clarity_tx.rollback_block();
+                return Ok(ProposedBlockVerdict::InvalidTransactions(format!("Invalid Stacks block {}: {:?}", &block_hash, &e)));
+            }
+        }
+
+        StacksChainState::process_genesis_vesting_unlocks(&mut clarity_tx, &genesis_vesting_unlocks);
+
+        if let Err(e) = StacksChainState::process_burnchain_stx_ops(&mut clarity_tx, burnchain_stx_ops) {
+            clarity_tx.rollback_block();
+            return Ok(ProposedBlockVerdict::InvalidBlock(format!("Failed to apply burnchain STX operations for block {}: {:?}", &block_hash, &e)));
+        }
+
+        if let Some(ref mature_miner_rewards) = matured_miner_rewards_opt {
+            if let Err(e) = StacksChainState::process_matured_miner_rewards(&mut clarity_tx, mature_miner_rewards) {
+                clarity_tx.rollback_block();
+                return Err(e);
+            }
+        }
+
+        let computed = clarity_tx.get_root_hash();
+        clarity_tx.rollback_block();
+
+        if computed != block.header.state_index_root {
+            return Ok(ProposedBlockVerdict::StateRootMismatch { expected: block.header.state_index_root.clone(), computed });
+        }
+
+        Ok(ProposedBlockVerdict::Valid { state_index_root: computed, receipts })
+    }
 }
 
 #[cfg(test)]
@@ -2485,7 +3292,7 @@ pub mod test {
 
     fn store_staging_block(chainstate: &mut StacksChainState, burn_header: &BurnchainHeaderHash, burn_header_timestamp: u64, block: &StacksBlock, parent_burn_header: &BurnchainHeaderHash, commit_burn: u64, sortition_burn: u64) {
         let mut tx = chainstate.blocks_tx_begin().unwrap();
-        StacksChainState::store_staging_block(&mut tx, burn_header, burn_header_timestamp, block, parent_burn_header, commit_burn, sortition_burn).unwrap();
+        StacksChainState::store_staging_block(&mut tx, burn_header, burn_header_timestamp, 0, block, parent_burn_header, commit_burn, sortition_burn).unwrap();
         tx.commit().unwrap();
     }
 
@@ -3438,7 +4245,169 @@ pub mod test {
             }
         }
     }
-   
+
+    #[test]
+    fn stacks_db_staging_microblock_conflicts() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "stacks_db_staging_microblock_conflicts");
+        let privk = StacksPrivateKey::from_hex("eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01").unwrap();
+        let reporter_privk = StacksPrivateKey::from_hex("59e4d5e18351d6027a37920efe53c2f1cbadc50dca7d77169b7291dff936ed6d01").unwrap();
+
+        let block = make_empty_coinbase_block(&privk);
+
+        let make_transfer = |nonce: u64| {
+            let auth = TransactionAuth::from_p2pkh(&reporter_privk).unwrap();
+            let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth, TransactionPayload::TokenTransfer(StacksAddress { version: 1, bytes: Hash160([0xff; 20]) }, 123, TokenTransferMemo([0u8; 34])));
+            tx.chain_id = 0x80000000;
+            tx.auth.set_origin_nonce(nonce);
+            let mut tx_signer = StacksTransactionSigner::new(&tx);
+            tx_signer.sign_origin(&reporter_privk).unwrap();
+            tx_signer.get_tx().unwrap()
+        };
+
+        let mut mblock_0 = StacksMicroblock::first_unsigned(&block.block_hash(), vec![make_transfer(0)]);
+        mblock_0.sign(&privk).unwrap();
+        let mut mblock_1 = StacksMicroblock::from_parent_unsigned(&mblock_0.header, vec![make_transfer(1)]).unwrap();
+        mblock_1.sign(&privk).unwrap();
+        let mblocks = vec![mblock_0, mblock_1];
+
+        // same parent and sequence as mblocks[1], but with different transactions
+        let mut conflicting_mblock = StacksMicroblock::from_parent_unsigned(&mblocks[0].header, vec![make_transfer(2)]).unwrap();
+        conflicting_mblock.sign(&privk).unwrap();
+        assert!(conflicting_mblock.block_hash() != mblocks[1].block_hash());
+
+        let burn_header = BurnchainHeaderHash([2u8; 32]);
+        let parent_burn_header = BurnchainHeaderHash([1u8; 32]);
+
+        store_staging_block(&mut chainstate, &burn_header, get_epoch_time_secs(), &block, &parent_burn_header, 1, 2);
+
+        // honest stream -- no conflicts
+        for mblock in mblocks.iter() {
+            assert!(chainstate.preprocess_streamed_microblock(&burn_header, &block.block_hash(), mblock).unwrap());
+        }
+        assert_eq!(StacksChainState::get_unreported_microblock_conflicts(&chainstate.blocks_db).unwrap(), vec![]);
+
+        // re-sending a microblock is not a conflict
+        assert!(chainstate.preprocess_streamed_microblock(&burn_header, &block.block_hash(), &mblocks[1]).unwrap());
+        assert_eq!(StacksChainState::get_unreported_microblock_conflicts(&chainstate.blocks_db).unwrap(), vec![]);
+
+        // equivocation is detected and recorded
+        assert!(chainstate.preprocess_streamed_microblock(&burn_header, &block.block_hash(), &conflicting_mblock).unwrap());
+        let conflicts = StacksChainState::get_unreported_microblock_conflicts(&chainstate.blocks_db).unwrap();
+        assert_eq!(conflicts, vec![MicroblockConflict {
+            burn_header_hash: burn_header.clone(),
+            anchored_block_hash: block.block_hash(),
+            header_1: mblocks[1].header.clone(),
+            header_2: conflicting_mblock.header.clone(),
+            reported: false
+        }]);
+
+        // the poison microblock transaction identifies the offending leader
+        let poison_tx = chainstate.make_poison_microblock_tx(&conflicts[0], &reporter_privk, 3, 5).unwrap();
+        assert_eq!(poison_tx.anchor_mode, TransactionAnchorMode::OnChainOnly);
+        assert_eq!(poison_tx.get_fee_rate(), 5);
+        assert_eq!(poison_tx.get_origin_nonce(), 3);
+        assert!(poison_tx.verify().unwrap());
+        match poison_tx.payload {
+            TransactionPayload::PoisonMicroblock(ref h1, ref h2) => {
+                assert_eq!(StacksChainState::validate_poison_microblock(h1, h2).unwrap(), block.header.microblock_pubkey_hash);
+            },
+            _ => panic!("not a poison microblock transaction")
+        }
+
+        // only reported once
+        chainstate.set_microblock_conflict_reported(&conflicts[0]).unwrap();
+        assert_eq!(StacksChainState::get_unreported_microblock_conflicts(&chainstate.blocks_db).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn stacks_db_prune_history() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "stacks_db_prune_history");
+        let privk = StacksPrivateKey::from_hex("eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01").unwrap();
+
+        // a chain of 4 blocks, each with a confirmed microblock stream whose tail is still staging
+        let mut tips = vec![];
+        let mut parent = StacksHeaderInfo::genesis();
+        for _ in 0..4 {
+            let tip = make_dummy_child_tip(&mut chainstate, &parent, 0);
+            let block = StacksBlock {
+                header: tip.anchored_header.clone(),
+                txs: vec![]
+            };
+            StacksChainState::store_block(&chainstate.blocks_path, &tip.burn_header_hash, &block).unwrap();
+
+            let mblocks = make_sample_microblock_stream(&privk, &block.block_hash());
+            for mblock in mblocks.iter() {
+                store_staging_microblock(&mut chainstate, &tip.burn_header_hash, &block.block_hash(), mblock);
+            }
+            set_microblocks_confirmed(&mut chainstate, &tip.burn_header_hash, &block.block_hash(), mblocks[mblocks.len() - 2].header.sequence);
+
+            tips.push((tip.clone(), mblocks));
+            parent = tip;
+        }
+
+        // a staging block that builds on the first block
+        let mut child_block = make_empty_coinbase_block(&privk);
+        child_block.header.parent_block = tips[0].0.anchored_header.block_hash();
+        child_block.header.total_work.work = 2;
+        let child_burn_header = BurnchainHeaderHash([0x99; 32]);
+        store_staging_block(&mut chainstate, &child_burn_header, get_epoch_time_secs(), &child_block, &tips[0].0.burn_header_hash, 1, 2);
+
+        assert!(StacksChainState::find_next_staging_block(&chainstate.blocks_db, &chainstate.blocks_path, &chainstate.headers_db).unwrap().is_some());
+        assert_eq!(StacksChainState::get_pruned_horizon(&chainstate.headers_db).unwrap(), 0);
+
+        // prune the boot block and the first two blocks
+        let horizon = tips[2].0.block_height;
+        assert_eq!(chainstate.prune_history(horizon).unwrap(), 3);
+        assert_eq!(StacksChainState::get_pruned_horizon(&chainstate.headers_db).unwrap(), horizon);
+
+        for (i, (tip, mblocks)) in tips.iter().enumerate() {
+            let block_hash = tip.anchored_header.block_hash();
+            let pruned = i < 2;
+
+            assert_eq!(StacksChainState::has_stored_block(&chainstate.blocks_path, &tip.burn_header_hash, &block_hash).unwrap(), !pruned);
+            assert_eq!(StacksChainState::load_microblock_stream(&chainstate.blocks_path, &tip.burn_header_hash, &mblocks[0].block_hash()).is_ok(), !pruned);
+            assert_eq!(StacksChainState::load_staging_microblock_bytes(&chainstate.blocks_db, &mblocks[mblocks.len() - 1].block_hash()).unwrap().is_some(), !pruned);
+
+            // staging records are kept
+            assert!(StacksChainState::has_staging_microblock(&chainstate.blocks_db, &tip.burn_header_hash, &block_hash, &mblocks[0].block_hash()).unwrap());
+
+            match StacksChainState::check_pruned_horizon(&chainstate.headers_db, &tip.burn_header_hash, &block_hash) {
+                Ok(h) => {
+                    assert!(!pruned);
+                    assert_eq!(h, horizon);
+                },
+                Err(::chainstate::stacks::Error::BlockPrunedError(height, h)) => {
+                    assert!(pruned);
+                    assert_eq!(height, tip.block_height);
+                    assert_eq!(h, horizon);
+                },
+                Err(e) => panic!("Unexpected error: {:?}", &e)
+            }
+        }
+
+        // can't read the chain state as of a pruned block
+        let contract_id = QualifiedContractIdentifier::transient();
+        match chainstate.eval_read_only(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &contract_id, "block-height") {
+            Err(::chainstate::stacks::Error::BlockPrunedError(0, h)) => assert_eq!(h, horizon),
+            res => panic!("Unexpected result: {:?}", &res)
+        }
+
+        // can't process a block that builds on a pruned block
+        assert!(StacksChainState::find_next_staging_block(&chainstate.blocks_db, &chainstate.blocks_path, &chainstate.headers_db).unwrap().is_none());
+
+        // the horizon never moves backwards
+        assert_eq!(chainstate.prune_history(horizon - 1).unwrap(), 0);
+        assert_eq!(StacksChainState::get_pruned_horizon(&chainstate.headers_db).unwrap(), horizon);
+
+        // a configured prune depth keeps only the most recent blocks (and a depth below the
+        // minimum is raised to it)
+        chainstate.prune_depth = Some(0);
+        chainstate.process_blocks(1).unwrap();
+        assert_eq!(StacksChainState::get_pruned_horizon(&chainstate.headers_db).unwrap(), tips[3].0.block_height);
+        assert!(!StacksChainState::has_stored_block(&chainstate.blocks_path, &tips[2].0.burn_header_hash, &tips[2].0.anchored_header.block_hash()).unwrap());
+        assert!(StacksChainState::has_stored_block(&chainstate.blocks_path, &tips[3].0.burn_header_hash, &tips[3].0.anchored_header.block_hash()).unwrap());
+    }
+
     // TODO: test multiple anchored blocks confirming the same microblock stream (in the same
     // place, and different places, with/without orphans)
     // TODO: process_next_staging_block
@@ -0,0 +1,252 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::cmp;
+use std::collections::HashMap;
+
+use rusqlite::types::ToSql;
+
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+use chainstate::stacks::db::*;
+
+use util::db::Error as db_error;
+use util::db::FromColumn;
+
+/// Fee rates paid by recently-confirmed transactions, at a few useful percentiles.
+/// Rates are in microSTX per byte (and per unit of execution cost).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeRateEstimate {
+    /// 25th percentile
+    pub low: u64,
+    /// 50th percentile
+    pub medium: u64,
+    /// 75th percentile
+    pub high: u64,
+    /// number of confirmed transactions the estimate was drawn from
+    pub num_samples: u64
+}
+
+impl StacksChainState {
+    /// Record the fee rates paid by the transactions confirmed by a newly-processed block
+    /// (including those in the microblock stream it confirms).  Coinbases pay no fee and are
+    /// left out.
+    pub fn insert_confirmed_fee_rates<'a>(tx: &mut StacksDBTx<'a>, tip_info: &StacksHeaderInfo, block: &StacksBlock, microblocks: &Vec<StacksMicroblock>) -> Result<(), Error> {
+        let index_block_hash = tip_info.index_block_hash();
+        let block_height = tip_info.block_height;
+        assert!(block_height < i64::max_value() as u64);
+
+        let confirmed_txs = microblocks.iter().flat_map(|mblock| mblock.txs.iter()).chain(block.txs.iter());
+        for stacks_tx in confirmed_txs {
+            if let TransactionPayload::Coinbase(_) = stacks_tx.payload {
+                continue;
+            }
+
+            // clamp so it fits into an i64; no one will ever pay this much
+            let fee_rate = cmp::min(stacks_tx.get_fee_rate(), i64::max_value() as u64);
+            let args: &[&dyn ToSql] = &[&(block_height as i64), &index_block_hash, &stacks_tx.txid(), &(fee_rate as i64)];
+            tx.execute("INSERT INTO confirmed_fee_rates (block_height,index_block_hash,txid,fee_rate) VALUES (?1,?2,?3,?4)", args)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Get the fee rates paid by transactions confirmed in the `num_blocks` blocks up to and
+    /// including `tip`, in ascending order.  Only blocks in the fork ending at `tip` are sampled.
+    pub fn get_confirmed_fee_rates<'a>(tx: &mut StacksDBTx<'a>, tip: &StacksHeaderInfo, num_blocks: u64) -> Result<Vec<u64>, Error> {
+        assert!(tip.block_height < i64::max_value() as u64);
+        if num_blocks == 0 {
+            return Ok(vec![]);
+        }
+        let start_height = tip.block_height.saturating_sub(num_blocks - 1);
+        let tip_index_block_hash = tip.index_block_hash();
+
+        let sql = "SELECT index_block_hash,fee_rate FROM confirmed_fee_rates WHERE block_height >= ?1 AND block_height <= ?2 ORDER BY fee_rate ASC".to_string();
+        let args: &[&dyn ToSql] = &[&(start_height as i64), &(tip.block_height as i64)];
+        let mut candidates = vec![];
+        {
+            let mut stmt = tx.prepare(&sql)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            let mut rows = stmt.query(args)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+            while let Some(row_res) = rows.next() {
+                let row = row_res.map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+                let index_block_hash = BlockHeaderHash::from_column(&row, "index_block_hash").map_err(Error::DBError)?;
+                let fee_rate : i64 = row.get("fee_rate");
+                candidates.push((index_block_hash, fee_rate as u64));
+            }
+        }
+
+        // blocks at these heights in other forks don't count
+        let mut in_fork = HashMap::new();
+        let mut fee_rates = vec![];
+        for (index_block_hash, fee_rate) in candidates.into_iter() {
+            let is_ancestor = match in_fork.get(&index_block_hash) {
+                Some(is_ancestor) => *is_ancestor,
+                None => {
+                    let is_ancestor = tx.get_ancestor_block_height(&index_block_hash, &tip_index_block_hash).map_err(Error::DBError)?.is_some();
+                    in_fork.insert(index_block_hash, is_ancestor);
+                    is_ancestor
+                }
+            };
+            if is_ancestor {
+                fee_rates.push(fee_rate);
+            }
+        }
+        Ok(fee_rates)
+    }
+
+    /// Find the fee rate at the given percentile (nearest-rank) of the fee rates paid in the
+    /// `num_blocks` blocks up to and including `tip`.  Returns None if no transactions
+    /// were confirmed in that window.
+    pub fn get_fee_rate_percentile<'a>(tx: &mut StacksDBTx<'a>, tip: &StacksHeaderInfo, num_blocks: u64, percentile: u8) -> Result<Option<u64>, Error> {
+        let fee_rates = StacksChainState::get_confirmed_fee_rates(tx, tip, num_blocks)?;
+        Ok(StacksChainState::fee_rate_at_percentile(&fee_rates, percentile))
+    }
+
+    /// Estimate what it takes to get a transaction confirmed, based on the fee rates paid in the
+    /// `num_blocks` blocks up to and including `tip`.  Returns None if no transactions
+    /// were confirmed in that window.
+    pub fn get_fee_rate_estimate<'a>(tx: &mut StacksDBTx<'a>, tip: &StacksHeaderInfo, num_blocks: u64) -> Result<Option<FeeRateEstimate>, Error> {
+        let fee_rates = StacksChainState::get_confirmed_fee_rates(tx, tip, num_blocks)?;
+        if fee_rates.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(FeeRateEstimate {
+            low: StacksChainState::fee_rate_at_percentile(&fee_rates, 25).expect("BUG: no fee rates"),
+            medium: StacksChainState::fee_rate_at_percentile(&fee_rates, 50).expect("BUG: no fee rates"),
+            high: StacksChainState::fee_rate_at_percentile(&fee_rates, 75).expect("BUG: no fee rates"),
+            num_samples: fee_rates.len() as u64
+        }))
+    }
+
+    /// Nearest-rank percentile of a sorted list of fee rates
+    fn fee_rate_at_percentile(sorted_fee_rates: &Vec<u64>, percentile: u8) -> Option<u64> {
+        if sorted_fee_rates.is_empty() {
+            return None;
+        }
+        let percentile = cmp::min(percentile, 100) as usize;
+        let rank = (percentile * sorted_fee_rates.len() + 99) / 100;
+        let idx = cmp::max(rank, 1) - 1;
+        Some(sorted_fee_rates[idx])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::db::test::*;
+
+    #[test]
+    fn fee_rate_percentiles() {
+        let rates = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        assert_eq!(StacksChainState::fee_rate_at_percentile(&rates, 0), Some(1));
+        assert_eq!(StacksChainState::fee_rate_at_percentile(&rates, 25), Some(3));
+        assert_eq!(StacksChainState::fee_rate_at_percentile(&rates, 50), Some(5));
+        assert_eq!(StacksChainState::fee_rate_at_percentile(&rates, 75), Some(8));
+        assert_eq!(StacksChainState::fee_rate_at_percentile(&rates, 100), Some(10));
+        assert_eq!(StacksChainState::fee_rate_at_percentile(&rates, 255), Some(10));
+        assert_eq!(StacksChainState::fee_rate_at_percentile(&vec![], 50), None);
+    }
+
+    fn make_tx_with_fee_rate(privk: &StacksPrivateKey, nonce: u64, fee_rate: u64, payload: TransactionPayload) -> StacksTransaction {
+        let auth = TransactionAuth::from_p2pkh(privk).unwrap();
+        let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth, payload);
+        tx.chain_id = 0x80000000;
+        tx.auth.set_origin_nonce(nonce);
+        tx.set_fee_rate(fee_rate);
+        tx
+    }
+
+    fn advance_tip(chainstate: &mut StacksChainState, parent: &StacksHeaderInfo, fork_seed: u8, fee_rate_base: u64) -> StacksHeaderInfo {
+        let privk = StacksPrivateKey::new();
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+
+        // confirms a coinbase and transactions paying fee_rate_base+1 ... fee_rate_base+4
+        let mut txs = vec![make_tx_with_fee_rate(&privk, 0, 0, TransactionPayload::Coinbase(CoinbasePayload([fork_seed; 32])))];
+        for j in 1..5u64 {
+            txs.push(make_tx_with_fee_rate(&privk, j, fee_rate_base + j, TransactionPayload::TokenTransfer(recv_addr.clone(), 1, TokenTransferMemo([0u8; 34]))));
+        }
+
+        let tip = make_dummy_child_tip(chainstate, parent, fork_seed);
+        let block = StacksBlock { header: tip.anchored_header.clone(), txs: txs };
+
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+        StacksChainState::insert_confirmed_fee_rates(&mut tx, &tip, &block, &vec![]).unwrap();
+        tx.commit().unwrap();
+        tip
+    }
+
+    #[test]
+    fn fee_rate_estimates_from_confirmed_blocks() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "fee-rate-estimates-from-confirmed-blocks");
+        let genesis = StacksHeaderInfo::genesis();
+
+        {
+            let mut tx = chainstate.headers_tx_begin().unwrap();
+            assert_eq!(StacksChainState::get_fee_rate_estimate(&mut tx, &genesis, 10).unwrap(), None);
+        }
+
+        // block i pays fee rates i*10+1 ... i*10+4
+        let tip_1 = advance_tip(&mut chainstate, &genesis, 0, 10);
+        let tip_2 = advance_tip(&mut chainstate, &tip_1, 0, 20);
+        let tip_3 = advance_tip(&mut chainstate, &tip_2, 0, 30);
+
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+
+        // coinbases are not counted
+        assert_eq!(StacksChainState::get_confirmed_fee_rates(&mut tx, &tip_3, 3).unwrap(), vec![11, 12, 13, 14, 21, 22, 23, 24, 31, 32, 33, 34]);
+
+        // only the last block
+        assert_eq!(StacksChainState::get_confirmed_fee_rates(&mut tx, &tip_3, 1).unwrap(), vec![31, 32, 33, 34]);
+        assert_eq!(StacksChainState::get_fee_rate_percentile(&mut tx, &tip_3, 1, 50).unwrap(), Some(32));
+
+        // window reaching back past the first block
+        let estimate = StacksChainState::get_fee_rate_estimate(&mut tx, &tip_2, 100).unwrap().unwrap();
+        assert_eq!(estimate, FeeRateEstimate { low: 12, medium: 14, high: 22, num_samples: 8 });
+
+        // empty window
+        assert_eq!(StacksChainState::get_fee_rate_estimate(&mut tx, &tip_3, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn fee_rate_estimates_from_tip_fork() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "fee-rate-estimates-from-tip-fork");
+        let ancestor = advance_tip(&mut chainstate, &StacksHeaderInfo::genesis(), 0, 10);
+
+        // two forks at the same heights, paying very different fee rates
+        let tip_a_1 = advance_tip(&mut chainstate, &ancestor, 1, 20);
+        let tip_a_2 = advance_tip(&mut chainstate, &tip_a_1, 1, 30);
+        let tip_b_1 = advance_tip(&mut chainstate, &ancestor, 2, 1000);
+        let tip_b_2 = advance_tip(&mut chainstate, &tip_b_1, 2, 2000);
+
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+
+        assert_eq!(StacksChainState::get_confirmed_fee_rates(&mut tx, &tip_a_2, 3).unwrap(), vec![11, 12, 13, 14, 21, 22, 23, 24, 31, 32, 33, 34]);
+        assert_eq!(StacksChainState::get_confirmed_fee_rates(&mut tx, &tip_b_2, 3).unwrap(), vec![11, 12, 13, 14, 1001, 1002, 1003, 1004, 2001, 2002, 2003, 2004]);
+
+        // a shorter fork only sees its own blocks
+        assert_eq!(StacksChainState::get_confirmed_fee_rates(&mut tx, &tip_b_1, 3).unwrap(), vec![11, 12, 13, 14, 1001, 1002, 1003, 1004]);
+
+        let estimate = StacksChainState::get_fee_rate_estimate(&mut tx, &tip_a_2, 2).unwrap().unwrap();
+        assert_eq!(estimate, FeeRateEstimate { low: 22, medium: 24, high: 32, num_samples: 8 });
+    }
+}
@@ -0,0 +1,372 @@
+/*
+ copyright: (c) 2013-2019 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::fs;
+use std::path::Path;
+
+use rusqlite::Row;
+use rusqlite::Connection;
+use rusqlite::types::ToSql;
+
+use burnchains::Address;
+
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+use chainstate::stacks::db::*;
+
+use util::db::*;
+use util::db::Error as db_error;
+
+/// A single scheduled release of genesis STX to an account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenesisVestingEntry {
+    pub unlock_height: u64,
+    pub amount: u64
+}
+
+/// An initial STX allocation, credited to an account in the boot block.
+/// `amount` is spendable immediately; each vesting entry is credited once the Stacks chain
+/// reaches its unlock height.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenesisAllocation {
+    pub address: StacksAddress,
+    pub amount: u64,
+    pub vesting: Vec<GenesisVestingEntry>
+}
+
+/// A vesting entry that becomes available at a particular block height
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenesisVestingUnlock {
+    pub address: StacksAddress,
+    pub amount: u64,
+    pub unlock_height: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct GenesisVestingEntryJSON {
+    unlock_height: u64,
+    amount: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct GenesisAllocationJSON {
+    address: String,
+    amount: u64,
+    #[serde(default)]
+    vesting: Vec<GenesisVestingEntryJSON>
+}
+
+impl FromRow<GenesisVestingUnlock> for GenesisVestingUnlock {
+    fn from_row<'a>(row: &'a Row) -> Result<GenesisVestingUnlock, db_error> {
+        let address = StacksAddress::from_column(row, "address")?;
+        let amount_text : String = row.get("amount");
+        let unlock_height_i64 : i64 = row.get("unlock_height");
+
+        if unlock_height_i64 < 0 {
+            return Err(db_error::ParseError);
+        }
+
+        let amount = amount_text.parse::<u64>().map_err(|_e| db_error::ParseError)?;
+        Ok(GenesisVestingUnlock {
+            address,
+            amount,
+            unlock_height: unlock_height_i64 as u64
+        })
+    }
+}
+
+impl GenesisAllocation {
+    /// Total number of microSTX this allocation will ever grant
+    pub fn total(&self) -> Option<u64> {
+        let mut total = self.amount;
+        for entry in self.vesting.iter() {
+            total = total.checked_add(entry.amount)?;
+        }
+        Some(total)
+    }
+
+    fn parse_address(addr_str: &str) -> Result<StacksAddress, Error> {
+        StacksAddress::from_string(&addr_str.trim().to_string())
+            .ok_or(Error::InvalidGenesisAllocation(format!("Invalid address '{}'", addr_str)))
+    }
+
+    fn parse_u64(field: &str, value_str: &str) -> Result<u64, Error> {
+        value_str.trim().parse::<u64>()
+            .map_err(|_e| Error::InvalidGenesisAllocation(format!("Invalid {} '{}'", field, value_str)))
+    }
+
+    fn from_json(alloc: GenesisAllocationJSON) -> Result<GenesisAllocation, Error> {
+        let address = GenesisAllocation::parse_address(&alloc.address)?;
+        let vesting = alloc.vesting
+            .into_iter()
+            .map(|entry| GenesisVestingEntry { unlock_height: entry.unlock_height, amount: entry.amount })
+            .collect();
+
+        Ok(GenesisAllocation {
+            address,
+            amount: alloc.amount,
+            vesting
+        })
+    }
+
+    /// Parse a list of allocations from a JSON array of objects, each of the form
+    /// `{"address": "ST...", "amount": 123, "vesting": [{"unlock_height": 10, "amount": 45}]}`.
+    /// The `vesting` field is optional.
+    pub fn parse_json(json_str: &str) -> Result<Vec<GenesisAllocation>, Error> {
+        let allocs : Vec<GenesisAllocationJSON> = serde_json::from_str(json_str)
+            .map_err(|e| Error::InvalidGenesisAllocation(format!("Invalid JSON: {:?}", &e)))?;
+
+        allocs.into_iter()
+            .map(GenesisAllocation::from_json)
+            .collect()
+    }
+
+    /// Parse a list of allocations from CSV.  Each line has the form
+    /// `address,amount[,unlock_height:amount unlock_height:amount ...]`.
+    /// Blank lines and lines beginning with `#` are ignored.
+    pub fn parse_csv(csv_str: &str) -> Result<Vec<GenesisAllocation>, Error> {
+        let mut allocs = vec![];
+        for line in csv_str.lines() {
+            let line = line.trim();
+            if line.len() == 0 || line.starts_with("#") {
+                continue;
+            }
+
+            let fields : Vec<&str> = line.split(",").collect();
+            if fields.len() < 2 || fields.len() > 3 {
+                return Err(Error::InvalidGenesisAllocation(format!("Invalid line '{}'", line)));
+            }
+
+            let address = GenesisAllocation::parse_address(fields[0])?;
+            let amount = GenesisAllocation::parse_u64("amount", fields[1])?;
+
+            let mut vesting = vec![];
+            if fields.len() == 3 {
+                for entry_str in fields[2].split_whitespace() {
+                    let parts : Vec<&str> = entry_str.split(":").collect();
+                    if parts.len() != 2 {
+                        return Err(Error::InvalidGenesisAllocation(format!("Invalid vesting entry '{}'", entry_str)));
+                    }
+                    vesting.push(GenesisVestingEntry {
+                        unlock_height: GenesisAllocation::parse_u64("unlock height", parts[0])?,
+                        amount: GenesisAllocation::parse_u64("vesting amount", parts[1])?
+                    });
+                }
+            }
+
+            allocs.push(GenesisAllocation {
+                address,
+                amount,
+                vesting
+            });
+        }
+        Ok(allocs)
+    }
+
+    /// Load a list of allocations from a file.  Files ending in `.json` are parsed as JSON; all
+    /// others are parsed as CSV.
+    pub fn from_file(path: &str) -> Result<Vec<GenesisAllocation>, Error> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| Error::DBError(db_error::IOError(e)))?;
+
+        let is_json = match Path::new(path).extension() {
+            Some(ext) => ext == "json",
+            None => false
+        };
+
+        if is_json {
+            GenesisAllocation::parse_json(&contents)
+        }
+        else {
+            GenesisAllocation::parse_csv(&contents)
+        }
+    }
+}
+
+impl StacksChainState {
+    /// Credit each genesis allocation's immediately-available balance.
+    /// Runs within the boot block's Clarity transaction.
+    pub fn process_genesis_allocations<'a>(clarity_tx: &mut ClarityTx<'a>, allocations: &Vec<GenesisAllocation>) -> Result<(), Error> {
+        for alloc in allocations.iter() {
+            if alloc.total().is_none() {
+                return Err(Error::InvalidGenesisAllocation(format!("Allocation to {} overflows", alloc.address.to_string())));
+            }
+            // caught here too, so the boot block is rolled back before the schedule is stored
+            StacksChainState::check_genesis_vesting_schedule(alloc)?;
+            if alloc.amount > 0 {
+                debug!("Genesis allocation: grant {} {} microSTX", alloc.address.to_string(), alloc.amount);
+                StacksChainState::account_credit(clarity_tx, &alloc.address.to_account_principal(), alloc.amount);
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the vesting schedules of the genesis allocations, so they can be credited as the
+    /// chain advances.
+    pub fn insert_genesis_vesting_schedule<'a>(tx: &mut StacksDBTx<'a>, allocations: &Vec<GenesisAllocation>) -> Result<(), Error> {
+        for alloc in allocations.iter() {
+            StacksChainState::check_genesis_vesting_schedule(alloc)?;
+            for entry in alloc.vesting.iter() {
+                let args: &[&dyn ToSql] = &[&alloc.address.to_string(), &format!("{}", entry.amount), &(entry.unlock_height as i64)];
+                tx.execute("INSERT INTO vesting_schedule (address,amount,unlock_height) VALUES (?1,?2,?3)", args)
+                    .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Unlock heights are stored as SQLite integers, so they must fit in an i64.
+    fn check_genesis_vesting_schedule(alloc: &GenesisAllocation) -> Result<(), Error> {
+        for entry in alloc.vesting.iter() {
+            if entry.unlock_height >= i64::max_value() as u64 {
+                return Err(Error::InvalidGenesisAllocation(format!("Allocation to {} unlocks at invalid height {}", alloc.address.to_string(), entry.unlock_height)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the genesis vesting entries that unlock at a particular Stacks block height
+    pub fn get_genesis_vesting_unlocks(conn: &Connection, block_height: u64) -> Result<Vec<GenesisVestingUnlock>, Error> {
+        let qry = "SELECT * FROM vesting_schedule WHERE unlock_height = ?1 ORDER BY rowid ASC".to_string();
+        let args: &[&dyn ToSql] = &[&(block_height as i64)];
+        let rows = query_rows::<GenesisVestingUnlock, _>(conn, &qry, args).map_err(Error::DBError)?;
+        Ok(rows)
+    }
+
+    /// Credit vested genesis STX to their owners.
+    /// Must be applied identically by miners and block validators, since it alters the state root.
+    pub fn process_genesis_vesting_unlocks<'a>(clarity_tx: &mut ClarityTx<'a>, unlocks: &Vec<GenesisVestingUnlock>) -> () {
+        for unlock in unlocks.iter() {
+            debug!("Genesis vesting: grant {} {} microSTX at height {}", unlock.address.to_string(), unlock.amount, unlock.unlock_height);
+            StacksChainState::account_credit(clarity_tx, &unlock.address.to_account_principal(), unlock.amount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::db::test::*;
+    use core::*;
+
+    #[test]
+    fn genesis_allocation_parse_csv() {
+        let csv = "# address,amount,vesting\n\
+                   ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH,1000\n\
+                   \n\
+                   ST3REJ5WQ42JGJZ6W77CX79JYMCVTKD73D6R6Z4R3,2000,10:100 20:200\n";
+
+        let allocs = GenesisAllocation::parse_csv(csv).unwrap();
+        assert_eq!(allocs.len(), 2);
+        assert_eq!(allocs[0].address.to_string(), "ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH");
+        assert_eq!(allocs[0].amount, 1000);
+        assert_eq!(allocs[0].vesting, vec![]);
+        assert_eq!(allocs[1].amount, 2000);
+        assert_eq!(allocs[1].vesting, vec![GenesisVestingEntry { unlock_height: 10, amount: 100 }, GenesisVestingEntry { unlock_height: 20, amount: 200 }]);
+        assert_eq!(allocs[1].total(), Some(2300));
+
+        assert!(GenesisAllocation::parse_csv("ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH").is_err());
+        assert!(GenesisAllocation::parse_csv("not-an-address,1000").is_err());
+        assert!(GenesisAllocation::parse_csv("ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH,-1").is_err());
+        assert!(GenesisAllocation::parse_csv("ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH,1,10-100").is_err());
+    }
+
+    #[test]
+    fn genesis_allocation_parse_json() {
+        let json = r#"[
+            { "address": "ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH", "amount": 1000 },
+            { "address": "ST3REJ5WQ42JGJZ6W77CX79JYMCVTKD73D6R6Z4R3", "amount": 2000,
+              "vesting": [{ "unlock_height": 10, "amount": 100 }] }
+        ]"#;
+
+        let allocs = GenesisAllocation::parse_json(json).unwrap();
+        assert_eq!(allocs.len(), 2);
+        assert_eq!(allocs[0].amount, 1000);
+        assert_eq!(allocs[0].vesting, vec![]);
+        assert_eq!(allocs[1].address.to_string(), "ST3REJ5WQ42JGJZ6W77CX79JYMCVTKD73D6R6Z4R3");
+        assert_eq!(allocs[1].vesting, vec![GenesisVestingEntry { unlock_height: 10, amount: 100 }]);
+
+        assert!(GenesisAllocation::parse_json(r#"[{ "address": "ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH" }]"#).is_err());
+    }
+
+    #[test]
+    fn genesis_allocation_boot() {
+        let allocs = GenesisAllocation::parse_csv("ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH,1000\n\
+                                                   ST3REJ5WQ42JGJZ6W77CX79JYMCVTKD73D6R6Z4R3,2000,3:100 3:50 7:200\n").unwrap();
+
+        let path = chainstate_path("genesis-allocation-boot");
+        match fs::metadata(&path) {
+            Ok(_) => {
+                fs::remove_dir_all(&path).unwrap();
+            },
+            Err(_) => {}
+        };
+
+        let mut chainstate = StacksChainState::open_with_genesis_allocations(false, 0x80000000, &path, &allocs).unwrap();
+
+        let unlocks = StacksChainState::get_genesis_vesting_unlocks(&chainstate.headers_db, 3).unwrap();
+        assert_eq!(unlocks.len(), 2);
+        assert_eq!(unlocks[0].amount, 100);
+        assert_eq!(unlocks[1].amount, 50);
+        assert_eq!(StacksChainState::get_genesis_vesting_unlocks(&chainstate.headers_db, 7).unwrap().len(), 1);
+        assert_eq!(StacksChainState::get_genesis_vesting_unlocks(&chainstate.headers_db, 4).unwrap().len(), 0);
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &MINER_BLOCK_BURN_HEADER_HASH, &MINER_BLOCK_HEADER_HASH);
+
+        let account_1 = StacksChainState::get_account(&mut conn, &allocs[0].address.to_account_principal());
+        assert_eq!(account_1.stx_balance, 1000);
+        assert_eq!(account_1.nonce, 0);
+
+        let account_2 = StacksChainState::get_account(&mut conn, &allocs[1].address.to_account_principal());
+        assert_eq!(account_2.stx_balance, 2000);
+
+        StacksChainState::process_genesis_vesting_unlocks(&mut conn, &unlocks);
+        let account_2 = StacksChainState::get_account(&mut conn, &allocs[1].address.to_account_principal());
+        assert_eq!(account_2.stx_balance, 2150);
+
+        conn.rollback_block();
+    }
+
+    #[test]
+    fn genesis_allocation_bad_unlock_height() {
+        let allocs = GenesisAllocation::parse_csv(&format!("ST3REJ5WQ42JGJZ6W77CX79JYMCVTKD73D6R6Z4R3,2000,3:100 {}:50\n", u64::max_value())).unwrap();
+
+        let path = chainstate_path("genesis-allocation-bad-unlock-height");
+        match fs::metadata(&path) {
+            Ok(_) => {
+                fs::remove_dir_all(&path).unwrap();
+            },
+            Err(_) => {}
+        };
+
+        match StacksChainState::open_with_genesis_allocations(false, 0x80000000, &path, &allocs) {
+            Err(Error::InvalidGenesisAllocation(_)) => {},
+            Err(e) => panic!("Expected an invalid genesis allocation, got {:?}", &e),
+            Ok(_) => panic!("Expected an invalid genesis allocation")
+        }
+
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "genesis-allocation-bad-unlock-height-insert");
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+        match StacksChainState::insert_genesis_vesting_schedule(&mut tx, &allocs) {
+            Err(Error::InvalidGenesisAllocation(_)) => {},
+            res => panic!("Expected an invalid genesis allocation, got {:?}", &res)
+        }
+    }
+}
@@ -0,0 +1,415 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::cmp;
+
+use rusqlite::Row;
+use rusqlite::types::ToSql;
+
+use burnchains::Txid;
+
+use chainstate::burn::BlockHeaderHash;
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+use chainstate::stacks::db::*;
+use chainstate::stacks::db::receipts::{StacksTransactionReceipt, StacksTransactionEvent};
+
+use util::db::{
+    FromRow,
+    FromColumn,
+    query_rows
+};
+use util::db::Error as db_error;
+
+use vm::types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData};
+
+/// A transaction that touched an account, and how it touched it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountHistoryEntry {
+    pub principal: PrincipalData,
+    pub txid: Txid,
+    /// anchored block that confirmed this transaction
+    pub index_block_hash: BlockHeaderHash,
+    pub block_height: u64,
+    /// order in which the anchored block processed this transaction (its parent microblock
+    /// stream's transactions come first)
+    pub position: u32,
+    /// the principal sent the transaction
+    pub sender: bool,
+    /// the principal paid the transaction's fee on the sender's behalf
+    pub sponsor: bool,
+    /// the principal received the STX transfer, or is the contract called or instantiated
+    pub recipient: bool,
+    /// the principal sent, received, or was minted assets while the transaction ran
+    pub asset_event: bool
+}
+
+/// Principals are stored as their address (standard) or address.name (contract), without the
+/// leading quote they have in Clarity code.
+fn principal_to_column(principal: &PrincipalData) -> String {
+    match principal {
+        PrincipalData::Standard(ref standard) => standard.to_address(),
+        PrincipalData::Contract(ref contract_id) => contract_id.to_string()
+    }
+}
+
+fn principal_from_column(principal_str: &str) -> Result<PrincipalData, db_error> {
+    if principal_str.contains('.') {
+        PrincipalData::parse_qualified_contract_principal(principal_str).map_err(|_e| db_error::ParseError)
+    }
+    else {
+        PrincipalData::parse_standard_principal(principal_str).map(PrincipalData::Standard).map_err(|_e| db_error::ParseError)
+    }
+}
+
+impl FromRow<AccountHistoryEntry> for AccountHistoryEntry {
+    fn from_row<'a>(row: &'a Row) -> Result<AccountHistoryEntry, db_error> {
+        let principal_str : String = row.get("principal");
+        let txid = Txid::from_column(row, "txid")?;
+        let index_block_hash = BlockHeaderHash::from_column(row, "index_block_hash")?;
+        let block_height_i64 : i64 = row.get("block_height");
+        let position_i64 : i64 = row.get("position");
+        let sender : bool = row.get("sender");
+        let sponsor : bool = row.get("sponsor");
+        let recipient : bool = row.get("recipient");
+        let asset_event : bool = row.get("asset_event");
+
+        if block_height_i64 < 0 || position_i64 < 0 || position_i64 > (u32::max_value() as i64) {
+            return Err(db_error::ParseError);
+        }
+
+        let principal = principal_from_column(&principal_str)?;
+
+        Ok(AccountHistoryEntry {
+            principal,
+            txid,
+            index_block_hash,
+            block_height: block_height_i64 as u64,
+            position: position_i64 as u32,
+            sender,
+            sponsor,
+            recipient,
+            asset_event
+        })
+    }
+}
+
+impl AccountHistoryEntry {
+    fn new(principal: PrincipalData, txid: &Txid, index_block_hash: &BlockHeaderHash, block_height: u64, position: u32) -> AccountHistoryEntry {
+        AccountHistoryEntry {
+            principal,
+            txid: txid.clone(),
+            index_block_hash: index_block_hash.clone(),
+            block_height,
+            position,
+            sender: false,
+            sponsor: false,
+            recipient: false,
+            asset_event: false
+        }
+    }
+
+    /// Find every account a confirmed transaction touched.  Entries are in the order the accounts
+    /// were first encountered.
+    pub fn from_transaction(tx: &StacksTransaction, receipt: &StacksTransactionReceipt, index_block_hash: &BlockHeaderHash, block_height: u64, position: u32) -> Vec<AccountHistoryEntry> {
+        let mut entries : Vec<AccountHistoryEntry> = vec![];
+        {
+            let mut touch = |principal: PrincipalData, set_role: &dyn Fn(&mut AccountHistoryEntry)| {
+                match entries.iter_mut().find(|entry| entry.principal == principal) {
+                    Some(entry) => set_role(entry),
+                    None => {
+                        let mut entry = AccountHistoryEntry::new(principal, &receipt.txid, index_block_hash, block_height, position);
+                        set_role(&mut entry);
+                        entries.push(entry);
+                    }
+                }
+            };
+
+            touch(tx.origin_address().to_account_principal(), &|entry| entry.sender = true);
+            if let Some(sponsor_address) = tx.sponsor_address() {
+                touch(sponsor_address.to_account_principal(), &|entry| entry.sponsor = true);
+            }
+
+            match tx.payload {
+                TransactionPayload::TokenTransfer(ref recipient, _, _) => {
+                    touch(recipient.to_account_principal(), &|entry| entry.recipient = true);
+                },
+                TransactionPayload::ContractCall(ref contract_call) => {
+                    let contract_id = QualifiedContractIdentifier::new(StandardPrincipalData::from(contract_call.address.clone()), contract_call.contract_name.clone());
+                    touch(PrincipalData::Contract(contract_id), &|entry| entry.recipient = true);
+                },
+                TransactionPayload::SmartContract(ref smart_contract) => {
+                    let contract_id = QualifiedContractIdentifier::new(StandardPrincipalData::from(tx.origin_address()), smart_contract.name.clone());
+                    touch(PrincipalData::Contract(contract_id), &|entry| entry.recipient = true);
+                },
+                _ => {}
+            }
+
+            for event in receipt.events.iter() {
+                match event {
+                    StacksTransactionEvent::STXTransfer { ref sender, ref recipient, .. } |
+                    StacksTransactionEvent::FungibleTokenTransfer { ref sender, ref recipient, .. } |
+                    StacksTransactionEvent::NonFungibleTokenTransfer { ref sender, ref recipient, .. } => {
+                        touch(sender.clone(), &|entry| entry.asset_event = true);
+                        touch(recipient.clone(), &|entry| entry.asset_event = true);
+                    },
+                    StacksTransactionEvent::STXBurn { ref sender, .. } => {
+                        touch(sender.clone(), &|entry| entry.asset_event = true);
+                    },
+                    StacksTransactionEvent::FungibleTokenMint { ref recipient, .. } |
+                    StacksTransactionEvent::NonFungibleTokenMint { ref recipient, .. } => {
+                        touch(recipient.clone(), &|entry| entry.asset_event = true);
+                    }
+                }
+            }
+        }
+        entries
+    }
+}
+
+impl StacksChainState {
+    /// Record which accounts each transaction confirmed by a newly-processed block touched.
+    /// `receipts` are the confirmed transactions' receipts, in the order they were processed
+    /// (the microblock stream's transactions, then the block's).
+    pub fn insert_account_history<'a>(tx: &mut StacksDBTx<'a>, parent_tip_info: &StacksHeaderInfo, tip_info: &StacksHeaderInfo, block: &StacksBlock, microblocks: &Vec<StacksMicroblock>, receipts: &Vec<StacksTransactionReceipt>) -> Result<(), Error> {
+        let index_block_hash = tip_info.index_block_hash();
+        let block_height = tip_info.block_height;
+        assert!(block_height < i64::max_value() as u64);
+
+        let args: &[&dyn ToSql] = &[&index_block_hash, &parent_tip_info.index_block_hash(), &(block_height as i64)];
+        tx.execute("INSERT INTO account_history_blocks (index_block_hash,parent_index_block_hash,block_height) VALUES (?1,?2,?3)", args)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        let confirmed_txs = microblocks.iter().map(|mblock| mblock.txs.iter()).flatten().chain(block.txs.iter());
+        for (position, (stacks_tx, receipt)) in confirmed_txs.zip(receipts.iter()).enumerate() {
+            assert_eq!(stacks_tx.txid(), receipt.txid);
+
+            for entry in AccountHistoryEntry::from_transaction(stacks_tx, receipt, &index_block_hash, block_height, position as u32).into_iter() {
+                let args: &[&dyn ToSql] = &[&principal_to_column(&entry.principal), &entry.txid, &entry.index_block_hash, &(entry.block_height as i64), &entry.position,
+                                            &entry.sender, &entry.sponsor, &entry.recipient, &entry.asset_event];
+                tx.execute("INSERT INTO account_history (principal,txid,index_block_hash,block_height,position,sender,sponsor,recipient,asset_event) \
+                            VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9)", args)
+                    .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the transactions that touched an account in the fork ending at the given chain tip
+    /// (identified by its index block hash), newest first.  Skips the first `offset` of them and
+    /// returns at most `limit`.
+    pub fn get_account_history<'a>(tx: &mut StacksDBTx<'a>, principal: &PrincipalData, tip_index_block_hash: &BlockHeaderHash, offset: u64, limit: u64) -> Result<Vec<AccountHistoryEntry>, Error> {
+        // walk back from the tip, but no further than the principal's oldest entry
+        let sql = "WITH RECURSIVE fork(index_block_hash, parent_index_block_hash) AS ( \
+                       SELECT index_block_hash, parent_index_block_hash FROM account_history_blocks WHERE index_block_hash = ?2 \
+                       UNION ALL \
+                       SELECT b.index_block_hash, b.parent_index_block_hash FROM account_history_blocks b JOIN fork ON b.index_block_hash = fork.parent_index_block_hash \
+                       WHERE b.block_height >= (SELECT IFNULL(MIN(block_height), 0) FROM account_history WHERE principal = ?1) \
+                   ) \
+                   SELECT account_history.* FROM account_history JOIN fork ON account_history.index_block_hash = fork.index_block_hash \
+                   WHERE account_history.principal = ?1 \
+                   ORDER BY account_history.block_height DESC, account_history.position DESC LIMIT ?3 OFFSET ?4".to_string();
+
+        let limit = cmp::min(limit, i64::max_value() as u64) as i64;
+        let offset = cmp::min(offset, i64::max_value() as u64) as i64;
+        let args: &[&dyn ToSql] = &[&principal_to_column(principal), tip_index_block_hash, &limit, &offset];
+        query_rows::<AccountHistoryEntry, _>(tx, &sql, args).map_err(Error::DBError)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::db::test::*;
+    use util::hash::Hash160;
+    use vm::types::Value;
+
+    fn make_receipt(tx: &StacksTransaction, events: Vec<StacksTransactionEvent>) -> StacksTransactionReceipt {
+        StacksTransactionReceipt::from_success(tx, Value::okay(Value::Bool(true)), events, 0)
+    }
+
+    fn advance_tip(chainstate: &mut StacksChainState, parent: &StacksHeaderInfo, fork_seed: u8, txs: Vec<(StacksTransaction, Vec<StacksTransactionEvent>)>) -> StacksHeaderInfo {
+        let tip = make_dummy_child_tip(chainstate, parent, fork_seed);
+        let receipts = txs.iter().map(|(tx, events)| make_receipt(tx, events.clone())).collect();
+        let block = StacksBlock { header: tip.anchored_header.clone(), txs: txs.into_iter().map(|(tx, _)| tx).collect() };
+
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+        StacksChainState::insert_account_history(&mut tx, parent, &tip, &block, &vec![], &receipts).unwrap();
+        tx.commit().unwrap();
+        tip
+    }
+
+    #[test]
+    fn account_history_roles() {
+        let privk = StacksPrivateKey::new();
+        let sponsor_privk = StacksPrivateKey::new();
+        let sender = TransactionAuth::from_p2pkh(&privk).unwrap().origin().address_testnet();
+        let sponsor = TransactionAuth::from_p2pkh(&sponsor_privk).unwrap().origin().address_testnet();
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+        let other_addr = StacksAddress { version: 1, bytes: Hash160([0xee; 20]) };
+
+        let auth = TransactionAuth::Sponsored(TransactionSpendingCondition::new_singlesig_p2pkh(StacksPublicKey::from_private(&privk)).unwrap(),
+                                              TransactionSpendingCondition::new_singlesig_p2pkh(StacksPublicKey::from_private(&sponsor_privk)).unwrap());
+        let tx = StacksTransaction::new(TransactionVersion::Testnet, auth,
+                                        TransactionPayload::new_contract_call(other_addr.clone(), "hello-world", "send", vec![]).unwrap());
+        let contract_id = QualifiedContractIdentifier::new(StandardPrincipalData::from(other_addr.clone()), "hello-world".into());
+
+        let receipt = make_receipt(&tx, vec![StacksTransactionEvent::STXTransfer { sender: sender.to_account_principal(), recipient: recv_addr.to_account_principal(), amount: 1 }]);
+
+        let entries = AccountHistoryEntry::from_transaction(&tx, &receipt, &BlockHeaderHash([1u8; 32]), 1, 0);
+        let roles : Vec<(PrincipalData, bool, bool, bool, bool)> = entries.into_iter().map(|e| (e.principal, e.sender, e.sponsor, e.recipient, e.asset_event)).collect();
+        assert_eq!(roles, vec![
+            (sender.to_account_principal(), true, false, false, true),
+            (sponsor.to_account_principal(), false, true, false, false),
+            (PrincipalData::Contract(contract_id), false, false, true, false),
+            (recv_addr.to_account_principal(), false, false, false, true)
+        ]);
+    }
+
+    #[test]
+    fn account_history_contract_call_recipients() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "account-history-contract-call-recipients");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let addr = auth.origin().address_testnet();
+        let stx_recipient = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) }.to_account_principal();
+        let ft_recipient = StacksAddress { version: 1, bytes: Hash160([0xee; 20]) }.to_account_principal();
+
+        let contract = "
+        (define-fungible-token stackaroos)
+        (define-public (pay (amount uint) (stx-recipient principal) (ft-recipient principal))
+          (begin
+            (unwrap-panic (stx-transfer? amount tx-sender stx-recipient))
+            (ft-mint? stackaroos amount ft-recipient)))";
+
+        let sign = |payload: TransactionPayload, nonce: u64| {
+            let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth.clone(), payload);
+            tx.chain_id = 0x80000000;
+            tx.post_condition_mode = TransactionPostConditionMode::Allow;
+            tx.auth.set_origin_nonce(nonce);
+
+            let mut signer = StacksTransactionSigner::new(&tx);
+            signer.sign_origin(&privk).unwrap();
+            signer.get_tx().unwrap()
+        };
+
+        let tx_contract = sign(TransactionPayload::new_smart_contract(&"hello-world".to_string(), &contract.to_string()).unwrap(), 0);
+        let tx_pay = sign(TransactionPayload::new_contract_call(addr.clone(), "hello-world", "pay",
+                                                                vec![Value::UInt(10), Value::Principal(stx_recipient.clone()), Value::Principal(ft_recipient.clone())]).unwrap(), 1);
+
+        let tip = make_dummy_child_tip(&mut chainstate, &StacksHeaderInfo::genesis(), 0);
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &tip.burn_header_hash, &tip.anchored_header.block_hash());
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 100);
+        let (_, contract_receipt) = StacksChainState::process_transaction(&mut conn, &tx_contract).unwrap();
+        let (_, pay_receipt) = StacksChainState::process_transaction(&mut conn, &tx_pay).unwrap();
+        conn.commit_block();
+        assert!(pay_receipt.is_success());
+
+        let block = StacksBlock { header: tip.anchored_header.clone(), txs: vec![tx_contract.clone(), tx_pay.clone()] };
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+        StacksChainState::insert_account_history(&mut tx, &StacksHeaderInfo::genesis(), &tip, &block, &vec![], &vec![contract_receipt, pay_receipt]).unwrap();
+
+        // neither recipient is named by the transaction itself, only by what the contract did
+        for principal in [stx_recipient, ft_recipient].iter() {
+            let entries = StacksChainState::get_account_history(&mut tx, principal, &tip.index_block_hash(), 0, 10).unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].txid, tx_pay.txid());
+            assert!(entries[0].asset_event);
+            assert!(!entries[0].sender && !entries[0].recipient);
+        }
+    }
+
+    #[test]
+    fn account_history_forks_and_pages() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "account-history-forks-and-pages");
+        let privk = StacksPrivateKey::new();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let sender = auth.origin().address_testnet().to_account_principal();
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+        let recipient = recv_addr.to_account_principal();
+
+        let make_tx = |nonce: u64| {
+            let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth.clone(), TransactionPayload::TokenTransfer(recv_addr.clone(), 1, TokenTransferMemo([0u8; 34])));
+            tx.auth.set_origin_nonce(nonce);
+            tx
+        };
+
+        // common ancestor with two transfers, then two forks
+        let txs : Vec<StacksTransaction> = (0..5).map(|nonce| make_tx(nonce)).collect();
+        let ancestor = advance_tip(&mut chainstate, &StacksHeaderInfo::genesis(), 0, vec![(txs[0].clone(), vec![]), (txs[1].clone(), vec![])]);
+        let tip_a = advance_tip(&mut chainstate, &ancestor, 1, vec![(txs[2].clone(), vec![])]);
+        let tip_b_1 = advance_tip(&mut chainstate, &ancestor, 2, vec![(txs[3].clone(), vec![])]);
+        let tip_b_2 = advance_tip(&mut chainstate, &tip_b_1, 2, vec![(txs[4].clone(), vec![])]);
+
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+
+        let history_txids = |tx: &mut StacksDBTx, principal: &PrincipalData, tip: &StacksHeaderInfo, offset: u64, limit: u64| -> Vec<Txid> {
+            StacksChainState::get_account_history(tx, principal, &tip.index_block_hash(), offset, limit).unwrap()
+                .into_iter().map(|entry| entry.txid).collect()
+        };
+
+        // newest first, and only from the tip's fork
+        assert_eq!(history_txids(&mut tx, &sender, &tip_a, 0, 10), vec![txs[2].txid(), txs[1].txid(), txs[0].txid()]);
+        assert_eq!(history_txids(&mut tx, &sender, &tip_b_2, 0, 10), vec![txs[4].txid(), txs[3].txid(), txs[1].txid(), txs[0].txid()]);
+        assert_eq!(history_txids(&mut tx, &recipient, &tip_b_1, 0, 10), vec![txs[3].txid(), txs[1].txid(), txs[0].txid()]);
+        assert_eq!(history_txids(&mut tx, &sender, &ancestor, 0, 10), vec![txs[1].txid(), txs[0].txid()]);
+
+        // pages
+        assert_eq!(history_txids(&mut tx, &sender, &tip_b_2, 0, 3), vec![txs[4].txid(), txs[3].txid(), txs[1].txid()]);
+        assert_eq!(history_txids(&mut tx, &sender, &tip_b_2, 3, 3), vec![txs[0].txid()]);
+        assert_eq!(history_txids(&mut tx, &sender, &tip_b_2, 4, 3), vec![]);
+        assert_eq!(history_txids(&mut tx, &sender, &tip_a, 1, 1), vec![txs[1].txid()]);
+
+        // roles
+        let entry = StacksChainState::get_account_history(&mut tx, &recipient, &tip_a.index_block_hash(), 0, 1).unwrap().pop().unwrap();
+        assert_eq!(entry.index_block_hash, tip_a.index_block_hash());
+        assert_eq!(entry.block_height, tip_a.block_height);
+        assert!(entry.recipient);
+        assert!(!entry.sender);
+
+        // untouched account
+        assert_eq!(history_txids(&mut tx, &StacksAddress { version: 1, bytes: Hash160([0x01; 20]) }.to_account_principal(), &tip_a, 0, 10), vec![]);
+    }
+
+    #[test]
+    fn account_history_config_persists() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "account-history-config-persists");
+        assert!(!chainstate.config().account_history);
+
+        chainstate.set_account_history(true).unwrap();
+        assert!(chainstate.config().account_history);
+
+        // remembered across a reopen, whether on or off
+        drop(chainstate);
+        let mut chainstate = open_chainstate(false, 0x80000000, "account-history-config-persists");
+        assert!(chainstate.config().account_history);
+
+        chainstate.set_account_history(false).unwrap();
+        drop(chainstate);
+        let chainstate = open_chainstate(false, 0x80000000, "account-history-config-persists");
+        assert!(!chainstate.config().account_history);
+
+        // can't be turned on once blocks were processed without it
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "account-history-config-after-blocks");
+        make_dummy_child_tip(&mut chainstate, &StacksHeaderInfo::genesis(), 0);
+        assert!(chainstate.set_account_history(true).is_err());
+        assert!(!chainstate.config().account_history);
+    }
+}
@@ -20,8 +20,15 @@
 pub mod accounts;
 pub mod blocks;
 pub mod contracts;
+pub mod fees;
+pub mod genesis;
 pub mod headers;
+pub mod history;
+pub mod pox;
+pub mod receipts;
+pub mod snapshot;
 pub mod transactions;
+pub mod txindex;
 
 use rusqlite::Transaction;
 use rusqlite::Connection;
@@ -43,6 +50,8 @@ use burnchains::Address;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
 use chainstate::stacks::db::blocks::*;
+use chainstate::stacks::db::genesis::*;
+use chainstate::stacks::db::pox::*;
 use chainstate::stacks::index::{
     TrieHash,
     MARFValue
@@ -88,6 +97,7 @@ use vm::representations::ClarityName;
 use vm::representations::ContractName;
 
 use core::CHAINSTATE_VERSION;
+use core::POX_REWARD_CYCLE_LENGTH;
 
 pub struct StacksChainState {
     pub mainnet: bool,
@@ -97,14 +107,27 @@ pub struct StacksChainState {
     pub blocks_db: DBConn,
     pub headers_state_index: MARF,
     pub blocks_path: String,
-    pub clarity_state_index_path: String
+    pub clarity_state_index_path: String,
+    /// maintain the (optional) account history index.  Changed with set_account_history().
+    account_history: bool,
+    /// if set, only keep the block data of this many of the most recently-processed blocks, and
+    /// refuse to read the Clarity state as of older blocks (headers are always kept, and the
+    /// Clarity state MARF only keeps the trie nodes of older blocks that newer blocks still
+    /// share).  Raised to MIN_PRUNE_DEPTH if lower.
+    pub prune_depth: Option<u64>,
+    /// the burnchain's first block height and reward cycle length (see Burnchain), which
+    /// determine when stacked STX unlock.  Must match the burnchain this chainstate follows.
+    pub pox_first_block_height: u64,
+    pub pox_reward_cycle_length: u32
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct StacksAccount {
     pub principal: PrincipalData,
     pub nonce: u64,
-    pub stx_balance: u128
+    pub stx_balance: u128,      // includes locked STX
+    pub stx_locked: u128,       // STX that cannot be spent until the burnchain reaches stx_unlock_height
+    pub stx_unlock_height: u64
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -141,6 +164,14 @@ pub struct DBConfig {
     pub version: String,
     pub mainnet: bool,
     pub chain_id: u32,
+    /// whether or not to record each account's transaction history as blocks are processed.
+    /// This is a node-local choice, but it is stored in the database so the index is never
+    /// missing blocks (see StacksChainState::set_account_history()).
+    pub account_history: bool,
+    /// PoX reward cycle parameters.  These come from the burnchain config, so they are not
+    /// stored in the database either.
+    pub pox_first_block_height: u64,
+    pub pox_reward_cycle_length: u32
 }
 
 impl StacksHeaderInfo {
@@ -167,6 +198,7 @@ impl FromRow<DBConfig> for DBConfig {
         let version : String = row.get("version");
         let mainnet_i64 : i64 = row.get("mainnet");
         let chain_id_i64 : i64 = row.get("chain_id");
+        let account_history : bool = row.get("account_history");
 
         let mainnet = mainnet_i64 != 0;
         let chain_id = chain_id_i64 as u32;
@@ -174,7 +206,10 @@ impl FromRow<DBConfig> for DBConfig {
         Ok(DBConfig {
             version,
             mainnet,
-            chain_id
+            chain_id,
+            account_history,
+            pox_first_block_height: 0,
+            pox_reward_cycle_length: POX_REWARD_CYCLE_LENGTH
         })
     }
 }
@@ -302,8 +337,8 @@ impl<'a> ChainstateTx<'a> {
 }
 
 // TODO: write code to populate the microblock_headers table too!
-// TODO: keep track of when microblock equivocations occur (maybe in the MARF?), so that once we
-// process a PoisonMicroblock transaction, no further blocks may build off of any descendent fork.
+// TODO: once we process a PoisonMicroblock transaction, no further blocks may build off of any
+// descendent fork.
 const STACKS_CHAIN_STATE_SQL : &'static [&'static str]= &[
     "PRAGMA foreign_keys = ON;",
     r#"
@@ -397,15 +432,115 @@ const STACKS_CHAIN_STATE_SQL : &'static [&'static str]= &[
     CREATE INDEX microblock_headers_hash ON microblock_headers(microblock_hash,block_height,parent_block_hash);
     "#,
     r#"
+    -- vesting schedule for genesis STX allocations
+    CREATE TABLE vesting_schedule(
+        address TEXT NOT NULL,
+        amount TEXT NOT NULL,               -- encodes u64
+        unlock_height INTEGER NOT NULL      -- Stacks block height at which the amount is credited
+    );
+    "#,
+    r#"
+    CREATE INDEX vesting_schedule_unlock_height ON vesting_schedule(unlock_height);
+    "#,
+    r#"
+    -- fee rates paid by confirmed transactions, for fee estimation
+    CREATE TABLE confirmed_fee_rates(
+        block_height INTEGER NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        txid TEXT NOT NULL,
+        fee_rate INTEGER NOT NULL
+    );
+    "#,
+    r#"
+    CREATE INDEX confirmed_fee_rates_block_height ON confirmed_fee_rates(block_height);
+    "#,
+    r#"
+    -- outcome of each transaction confirmed by each block
+    CREATE TABLE transaction_receipts(
+        txid TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,     -- anchored block that confirmed the transaction
+        burn_header_hash TEXT NOT NULL,
+        block_hash TEXT NOT NULL,
+        microblock_hash TEXT,               -- NULL unless the transaction was streamed
+        tx_index INTEGER NOT NULL,          -- position in the (micro)block
+        status TEXT NOT NULL,               -- "success" or "abort"
+        result TEXT NOT NULL,               -- hex-encoded Clarity value
+        post_condition_aborted INTEGER NOT NULL,
+        execution_cost TEXT NOT NULL,       -- JSON-encoded ExecutionCost
+        events TEXT NOT NULL,               -- JSON-encoded list of events
+        fee INTEGER NOT NULL,
+        stx_burned TEXT NOT NULL,           -- encodes u128
+
+        PRIMARY KEY(txid,index_block_hash)
+    );
+    "#,
+    r#"
+    CREATE INDEX transaction_receipts_block ON transaction_receipts(index_block_hash);
+    "#,
+    r#"
+    -- where each transaction was confirmed, in each fork
+    CREATE TABLE transaction_index(
+        txid TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,     -- anchored block that confirmed the transaction
+        burn_header_hash TEXT NOT NULL,
+        block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        microblock_hash TEXT,               -- NULL unless the transaction was streamed
+        microblock_sequence INTEGER,        -- NULL unless the transaction was streamed
+        tx_index INTEGER NOT NULL,          -- position in the (micro)block
+
+        PRIMARY KEY(txid,index_block_hash)
+    );
+    "#,
+    r#"
+    -- transactions that touched each account, in each fork.  Only populated if the account
+    -- history index is enabled.
+    CREATE TABLE account_history(
+        principal TEXT NOT NULL,
+        txid TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,     -- anchored block that confirmed the transaction
+        block_height INTEGER NOT NULL,
+        position INTEGER NOT NULL,          -- order in which the block processed the transaction
+        sender INTEGER NOT NULL,
+        sponsor INTEGER NOT NULL,
+        recipient INTEGER NOT NULL,
+        asset_event INTEGER NOT NULL,
+
+        PRIMARY KEY(principal,txid,index_block_hash)
+    );
+    "#,
+    r#"
+    CREATE INDEX account_history_principal ON account_history(principal,block_height);
+    "#,
+    r#"
+    -- parent of each anchored block processed while the account history index is enabled, so
+    -- a fork's history can be found by walking back from its tip
+    CREATE TABLE account_history_blocks(
+        index_block_hash TEXT NOT NULL,
+        parent_index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+
+        PRIMARY KEY(index_block_hash)
+    );
+    "#,
+    r#"
+    -- lowest Stacks block height whose block data and Clarity state history are still kept.
+    -- Absent if nothing was ever pruned.
+    CREATE TABLE pruned_horizon(
+        block_height INTEGER NOT NULL
+    );
+    "#,
+    r#"
     CREATE TABLE db_config(
         version TEXT NOT NULL,
         mainnet INTEGER NOT NULL,
-        chain_id INTEGER NOT NULL
+        chain_id INTEGER NOT NULL,
+        account_history INTEGER NOT NULL
     )"#
 ];
 
 /// Built-in "system-level" smart contracts that are there from the beginning.
-/// Includes BNS and the miner trust fund.
+/// Includes BNS, the miner trust fund, and PoX stacking.
 #[cfg(test)]
 const STACKS_MINER_AUTH_KEY : &'static str = "a5879925788dcb3fe1f2737453e371ba04c4064e6609552ef59a126ac4fa598001";
 
@@ -432,7 +567,8 @@ const STACKS_BOOT_CODE : &'static [&'static str] = &[
 
     ;; TODO: authorize STX withdrawals
     ;; TODO: withdraw STX
-    "#
+    "#,
+    BOOT_CODE_POX_BODY
 ];
 
 
@@ -441,7 +577,8 @@ pub const STACKS_BOOT_CODE_CONTRACT_ADDRESS : &'static str = "ST0000000000000000
 
 #[cfg(test)]
 const STACKS_BOOT_CODE_CONTRACT_NAMES : &'static [&'static str] = &[
-    "miner-rewards"
+    "miner-rewards",
+    BOOT_CODE_POX_CONTRACT_NAME
 ];
 
 pub const BOOT_CODE_MINER_CONTRACT_NAME : &'static str = "miner-rewards";
@@ -462,6 +599,14 @@ pub const MINER_REWARD_WINDOW : u64 = 5;       // small for testing purposes
 #[cfg(not(test))]
 pub const MINER_REWARD_WINDOW : u64 = 1008;
 
+// fewest recent blocks whose data a node may keep if it prunes, so it can still process the
+// blocks of any fork a burnchain reorg could switch it to
+#[cfg(test)]
+pub const MIN_PRUNE_DEPTH : u64 = 1;       // small for testing purposes
+
+#[cfg(not(test))]
+pub const MIN_PRUNE_DEPTH : u64 = 1008;
+
 pub const MINER_FEE_MINIMUM_BLOCK_USAGE : u64 = 80;         // miner must share the first F% of the anchored block tx fees, and gets 100% - F% exclusively
 
 pub const MINER_FEE_WINDOW : u64 = 24;                      // number of blocks (B) used to smooth over the fraction of tx fees they share from anchored blocks
@@ -472,11 +617,13 @@ pub const STACKS_BOOT_CODE_CONTRACT_ADDRESS : &'static str = "SP0000000000000000
 // TODO
 #[cfg(not(test))]
 const STACKS_BOOT_CODE : &'static [&'static str] = &[
+    BOOT_CODE_POX_BODY
 ];
 
 // TODO
 #[cfg(not(test))]
 const STACKS_BOOT_CODE_CONTRACT_NAMES : &'static [&'static str] = &[
+    BOOT_CODE_POX_CONTRACT_NAME
 ];
 
 impl StacksChainState {
@@ -487,7 +634,7 @@ impl StacksChainState {
             tx.execute(cmd, NO_PARAMS).map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
         }
 
-        tx.execute("INSERT INTO db_config (version,mainnet,chain_id) VALUES (?1,?2,?3)", &[&CHAINSTATE_VERSION, &(if mainnet { 1 } else { 0 }) as &dyn ToSql, &chain_id as &dyn ToSql])
+        tx.execute("INSERT INTO db_config (version,mainnet,chain_id,account_history) VALUES (?1,?2,?3,?4)", &[&CHAINSTATE_VERSION, &(if mainnet { 1 } else { 0 }) as &dyn ToSql, &chain_id as &dyn ToSql, &false as &dyn ToSql])
             .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
 
         let mut marf = StacksChainState::open_index(marf_path, None)?;
@@ -519,10 +666,7 @@ impl StacksChainState {
         }
         else {
             // sanity check
-            let rows = query_rows::<DBConfig, _>(&conn, &"SELECT * FROM db_config LIMIT 1".to_string(), NO_PARAMS)
-                .map_err(Error::DBError)?;
-
-            let db_config = rows[0].clone();
+            let db_config = StacksChainState::load_db_config(&conn)?;
 
             if db_config.mainnet != mainnet {
                 error!("Invalid chain state database: expected mainnet = {}, got {}", mainnet, db_config.mainnet);
@@ -543,6 +687,13 @@ impl StacksChainState {
         Ok(conn)
     }
     
+    fn load_db_config(conn: &DBConn) -> Result<DBConfig, Error> {
+        let rows = query_rows::<DBConfig, _>(conn, &"SELECT * FROM db_config LIMIT 1".to_string(), NO_PARAMS)
+            .map_err(Error::DBError)?;
+
+        rows.into_iter().next().ok_or(Error::InvalidChainstateDB)
+    }
+
     pub fn open_index(marf_path: &str, miner_tip: Option<&BlockHeaderHash>) -> Result<MARF, Error> {
         test_debug!("Open MARF index at {}, set miner tip = {:?}", marf_path, miner_tip);
         let marf = MARF::from_path(marf_path, miner_tip).map_err(|e| Error::DBError(db_error::IndexError(e)))?;
@@ -570,9 +721,8 @@ impl StacksChainState {
         Ok(path_str)
     }
 
-    /// Install the boot code into the chain history.
-    /// TODO: instantiate all account balances as well.
-    fn install_boot_code(chainstate: &mut StacksChainState, mainnet: bool, additional_boot_code_contract_names: &Vec<String>, additional_boot_code: &Vec<String>) -> Result<(), Error> {
+    /// Install the boot code and the genesis STX allocations into the chain history.
+    fn install_boot_code(chainstate: &mut StacksChainState, mainnet: bool, additional_boot_code_contract_names: &Vec<String>, additional_boot_code: &Vec<String>, genesis_allocations: &Vec<GenesisAllocation>) -> Result<(), Error> {
         assert_eq!(STACKS_BOOT_CODE.len(), STACKS_BOOT_CODE_CONTRACT_NAMES.len());
         assert_eq!(additional_boot_code_contract_names.len(), additional_boot_code.len());
         
@@ -597,7 +747,9 @@ impl StacksChainState {
         let mut boot_code_account = StacksAccount {
             principal: PrincipalData::Standard(StandardPrincipalData::from(boot_code_address.clone())),
             nonce: 0,
-            stx_balance: 0
+            stx_balance: 0,
+            stx_locked: 0,
+            stx_unlock_height: 0
         };
 
         {
@@ -630,6 +782,11 @@ impl StacksChainState {
                 boot_code_account.nonce += 1;
             }
 
+            if let Err(e) = StacksChainState::process_genesis_allocations(&mut clarity_tx, genesis_allocations) {
+                clarity_tx.rollback_block();
+                return Err(e);
+            }
+
             clarity_tx.commit_to_block(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH);
         }
         
@@ -669,6 +826,7 @@ impl StacksChainState {
             };
 
             StacksChainState::insert_stacks_block_header(&mut headers_tx, &first_tip_info)?;
+            StacksChainState::insert_genesis_vesting_schedule(&mut headers_tx, genesis_allocations)?;
             headers_tx.commit()
                 .map_err(Error::DBError)?;
         }
@@ -676,18 +834,27 @@ impl StacksChainState {
         Ok(())
     }
     
+    /// Name of the directory, under the path the chain state is opened from, that holds the
+    /// chain state of the given chain.
+    pub fn chain_dir_name(mainnet: bool, chain_id: u32) -> String {
+        if mainnet {
+            format!("chain-{}-mainnet", &to_hex(&chain_id.to_le_bytes()))
+        }
+        else {
+            format!("chain-{}-testnet", &to_hex(&chain_id.to_le_bytes()))
+        }
+    }
+
     pub fn open(mainnet: bool, chain_id: u32, path_str: &str) -> Result<StacksChainState, Error> {
-        let mut path = PathBuf::from(path_str);
+        StacksChainState::open_with_genesis_allocations(mainnet, chain_id, path_str, &vec![])
+    }
 
-        let chain_id_str = 
-            if mainnet {
-                format!("chain-{}-mainnet", &to_hex(&chain_id.to_le_bytes()))
-            }
-            else {
-                format!("chain-{}-testnet", &to_hex(&chain_id.to_le_bytes()))
-            };
+    /// Open the chain state, and if it does not yet exist, instantiate it with the given initial
+    /// STX allocations.  The allocations are ignored if the chain state already exists.
+    pub fn open_with_genesis_allocations(mainnet: bool, chain_id: u32, path_str: &str, genesis_allocations: &Vec<GenesisAllocation>) -> Result<StacksChainState, Error> {
+        let mut path = PathBuf::from(path_str);
 
-        path.push(chain_id_str);
+        path.push(StacksChainState::chain_dir_name(mainnet, chain_id));
         StacksChainState::mkdirs(&path)?;
 
         let mut blocks_path = path.clone();
@@ -728,6 +895,7 @@ impl StacksChainState {
 
         let headers_db = StacksChainState::open_headers_db(mainnet, chain_id, &headers_db_path, &header_index_root)?;
         let blocks_db = StacksChainState::open_blocks_db(&blocks_db_path)?;
+        let account_history = StacksChainState::load_db_config(&headers_db)?.account_history;
 
         let headers_state_index = StacksChainState::open_index(&header_index_root, None)?;
 
@@ -744,21 +912,52 @@ impl StacksChainState {
             blocks_db: blocks_db,
             headers_state_index: headers_state_index,
             blocks_path: blocks_path_root,
-            clarity_state_index_path: clarity_state_index_marf
+            clarity_state_index_path: clarity_state_index_marf,
+            account_history: account_history,
+            prune_depth: None,
+            pox_first_block_height: 0,
+            pox_reward_cycle_length: POX_REWARD_CYCLE_LENGTH
         };
 
         if !index_exists {
-            StacksChainState::install_boot_code(&mut chainstate, mainnet, &vec![], &vec![])?;
+            StacksChainState::install_boot_code(&mut chainstate, mainnet, &vec![], &vec![], genesis_allocations)?;
         }
 
         Ok(chainstate)
     }
 
+    /// Turn the account history index on or off, and remember the choice in the database.  It
+    /// can only be turned on before any blocks have been processed, since the history of the
+    /// blocks processed without it would be missing.
+    pub fn set_account_history(&mut self, enabled: bool) -> Result<(), Error> {
+        if enabled == self.account_history {
+            return Ok(());
+        }
+
+        if enabled {
+            let num_blocks = query_count(&self.headers_db, &"SELECT COUNT(*) FROM block_headers WHERE block_height > 0".to_string(), NO_PARAMS)
+                .map_err(Error::DBError)?;
+            if num_blocks > 0 {
+                error!("Cannot enable the account history index after {} block(s) were processed without it", num_blocks);
+                return Err(Error::InvalidChainstateDB);
+            }
+        }
+
+        self.headers_db.execute("UPDATE db_config SET account_history = ?1", &[&enabled])
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        self.account_history = enabled;
+        Ok(())
+    }
+
     pub fn config(&self) -> DBConfig {
         DBConfig {
             mainnet: self.mainnet,
             chain_id: self.chain_id,
-            version: CHAINSTATE_VERSION.to_string()
+            version: CHAINSTATE_VERSION.to_string(),
+            account_history: self.account_history,
+            pox_first_block_height: self.pox_first_block_height,
+            pox_reward_cycle_length: self.pox_reward_cycle_length
         }
     }
     
@@ -802,6 +1001,26 @@ impl StacksChainState {
         result.unwrap()
     }
 
+    /// Begin a Clarity transaction for answering queries about the chain state as of the given
+    /// Stacks block.  Fails if the block was pruned, and makes `at-block` fail for blocks that
+    /// were.  The caller must roll it back.
+    pub fn read_only_block_begin<'a>(&'a mut self, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash) -> Result<ClarityTx<'a>, Error> {
+        let horizon = StacksChainState::check_pruned_horizon(&self.headers_db, burn_header_hash, block_hash)?;
+        let mut clarity_tx = self.block_begin(burn_header_hash, block_hash, &MINER_BLOCK_BURN_HEADER_HASH, &MINER_BLOCK_HEADER_HASH);
+        if horizon > 0 {
+            clarity_tx.connection().set_history_horizon(horizon as u32);
+        }
+        Ok(clarity_tx)
+    }
+
+    /// Evaluate read-only Clarity code in the context of a contract, as of the given Stacks block.
+    pub fn eval_read_only(&mut self, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, contract: &QualifiedContractIdentifier, code: &str) -> Result<Value, Error> {
+        let mut clarity_tx = self.read_only_block_begin(burn_header_hash, block_hash)?;
+        let result = clarity_tx.connection().eval_read_only(contract, code);
+        clarity_tx.rollback_block();
+        result.map_err(Error::ClarityError)
+    }
+
     /// Begin processing an epoch's transactions within the context of a chainstate transaction
     pub fn chainstate_block_begin<'a>(chainstate_tx: &'a ChainstateTx<'a>, clarity_instance: &'a mut ClarityInstance, parent_burn_hash: &BurnchainHeaderHash, parent_block: &BlockHeaderHash, new_burn_hash: &BurnchainHeaderHash, new_block: &BlockHeaderHash) -> ClarityTx<'a> {
         let conf = chainstate_tx.config.clone();
@@ -881,7 +1100,75 @@ impl StacksChainState {
             }
         }
     }
-   
+
+    /// Find the block in this fork whose leader signed microblocks with the given public key hash.
+    /// Returns the index block hash of that block, if it exists.
+    pub fn get_microblock_pubkey_hash_block<'a>(headers_tx: &mut StacksDBTx<'a>, tip_burn_hash: &BurnchainHeaderHash, tip_header: &StacksBlockHeader, pubkey_hash: &Hash160) -> Result<Option<BlockHeaderHash>, Error> {
+        let parent_hash = StacksChainState::get_index_hash(tip_burn_hash, tip_header);
+        match headers_tx.get_indexed(&parent_hash, &format!("chainstate::pubkey_hash::{}", pubkey_hash)).map_err(Error::DBError)? {
+            Some(index_block_hash_hex) => {
+                let index_block_hash = BlockHeaderHash::from_hex(&index_block_hash_hex)
+                    .map_err(|_e| Error::DBError(db_error::ParseError))?;
+                Ok(Some(index_block_hash))
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Find the PoisonMicroblock transaction in this fork, if any, that reported the leader of
+    /// the given block (identified by its index block hash) for equivocating.
+    pub fn get_microblock_poison_report<'a>(headers_tx: &mut StacksDBTx<'a>, tip_burn_hash: &BurnchainHeaderHash, tip_header: &StacksBlockHeader, offender_index_block_hash: &BlockHeaderHash) -> Result<Option<Txid>, Error> {
+        let parent_hash = StacksChainState::get_index_hash(tip_burn_hash, tip_header);
+        match headers_tx.get_indexed(&parent_hash, &format!("chainstate::poison::{}", offender_index_block_hash)).map_err(Error::DBError)? {
+            Some(txid_hex) => {
+                let txid = Txid::from_hex(&txid_hex)
+                    .map_err(|_e| Error::DBError(db_error::ParseError))?;
+                Ok(Some(txid))
+            },
+            None => Ok(None)
+        }
+    }
+
+    /// Find the blocks whose leaders are reported by the PoisonMicroblock transactions in the
+    /// given block, paired with the reporting transaction.  Leaders that are not in this fork, or
+    /// that were already reported in this fork, are skipped -- only the earliest report counts.
+    pub fn find_microblock_poison_offenders<'a>(headers_tx: &mut StacksDBTx<'a>, parent_tip: &StacksHeaderInfo, block: &StacksBlock) -> Result<Vec<(BlockHeaderHash, Txid)>, Error> {
+        let mut offenders : Vec<(BlockHeaderHash, Txid)> = vec![];
+        for tx in block.txs.iter() {
+            let (mblock_header_1, mblock_header_2) = match tx.payload {
+                TransactionPayload::PoisonMicroblock(ref h1, ref h2) => (h1, h2),
+                _ => {
+                    continue;
+                }
+            };
+
+            let pubkey_hash = match StacksChainState::validate_poison_microblock(mblock_header_1, mblock_header_2) {
+                Ok(pubkey_hash) => pubkey_hash,
+                Err(_) => {
+                    continue;
+                }
+            };
+
+            let offender = match StacksChainState::get_microblock_pubkey_hash_block(headers_tx, &parent_tip.burn_header_hash, &parent_tip.anchored_header, &pubkey_hash)? {
+                Some(index_block_hash) => index_block_hash,
+                None => {
+                    debug!("Poison microblock transaction {} reports a leader that is not in this fork", tx.txid());
+                    continue;
+                }
+            };
+
+            if offenders.iter().any(|(index_block_hash, _)| *index_block_hash == offender) {
+                continue;
+            }
+            if StacksChainState::get_microblock_poison_report(headers_tx, &parent_tip.burn_header_hash, &parent_tip.anchored_header, &offender)?.is_some() {
+                continue;
+            }
+
+            offenders.push((offender, tx.txid()));
+        }
+        Ok(offenders)
+    }
+
     /// Append a Stacks block to an existing Stacks block, and grant the miner the block reward.
     /// Return the new Stacks header info.
     pub fn advance_tip<'a>(headers_tx: &mut StacksDBTx<'a>, 
@@ -892,7 +1179,8 @@ impl StacksChainState {
                            new_burn_block_timestamp: u64,
                            microblock_tail_opt: Option<StacksMicroblockHeader>,
                            block_reward: &MinerPaymentSchedule,
-                           user_burns: &Vec<StagingUserBurnSupport>) -> Result<StacksHeaderInfo, Error>
+                           user_burns: &Vec<StagingUserBurnSupport>,
+                           microblock_poisons: &Vec<(BlockHeaderHash, Txid)>) -> Result<StacksHeaderInfo, Error>
     {
         if new_tip.parent_block != FIRST_STACKS_BLOCK_HASH {
             // not the first-ever block, so linkage must occur
@@ -902,16 +1190,23 @@ impl StacksChainState {
         }
         
         let parent_hash = StacksChainState::get_index_hash(parent_burn_block, parent_tip); 
-        let indexed_keys = vec![
+        let new_index_block_hash = new_tip.index_block_hash(new_burn_block);
+        let mut indexed_keys = vec![
             format!("chainstate::pubkey_hash::{}", new_tip.microblock_pubkey_hash)
         ];
 
-        let indexed_values = vec![
-            "1".to_string()
+        let mut indexed_values = vec![
+            new_index_block_hash.to_hex()
         ];
 
+        // remember which leaders were caught equivocating, so their rewards can be withheld
+        for (offender_index_block_hash, txid) in microblock_poisons.iter() {
+            indexed_keys.push(format!("chainstate::poison::{}", offender_index_block_hash));
+            indexed_values.push(txid.to_hex());
+        }
+
         // store each indexed field
-        headers_tx.put_indexed_begin(&parent_hash, &new_tip.index_block_hash(new_burn_block))
+        headers_tx.put_indexed_begin(&parent_hash, &new_index_block_hash)
             .map_err(Error::DBError)?;
         let root_hash = headers_tx.put_indexed_all(&indexed_keys, &indexed_values)
             .map_err(Error::DBError)?;
@@ -964,6 +1259,42 @@ pub mod test {
         format!("/tmp/blockstack-test-chainstate-{}", test_name)
     }
 
+    /// Advance the headers index to a child of `parent` with no block reward.  Its burnchain
+    /// block hash is derived from the parent's and `fork_seed`, so children with different seeds
+    /// are in different forks.
+    pub fn make_dummy_child_tip(chainstate: &mut StacksChainState, parent: &StacksHeaderInfo, fork_seed: u8) -> StacksHeaderInfo {
+        let mut header = parent.anchored_header.clone();
+        header.parent_block = parent.anchored_header.block_hash();
+        header.total_work.work = parent.anchored_header.total_work.work + 1;
+
+        let mut burn_seed = parent.burn_header_hash.0.to_vec();
+        burn_seed.push(fork_seed);
+        let burn_header_hash = BurnchainHeaderHash(Sha512Trunc256Sum::from_data(&burn_seed).0);
+
+        let block_reward = MinerPaymentSchedule {
+            address: StacksAddress { version: 0, bytes: Hash160([0u8; 20]) },
+            block_hash: header.block_hash(),
+            burn_header_hash: burn_header_hash.clone(),
+            parent_block_hash: parent.anchored_header.block_hash(),
+            parent_burn_header_hash: parent.burn_header_hash.clone(),
+            coinbase: 0,
+            tx_fees_anchored: 0,
+            tx_fees_streamed: 0,
+            stx_burns: 0,
+            burnchain_commit_burn: 0,
+            burnchain_sortition_burn: 0,
+            fill: 0xffffffffffffffff,
+            miner: true,
+            stacks_block_height: header.total_work.work,
+            vtxindex: 0
+        };
+
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+        let tip = StacksChainState::advance_tip(&mut tx, &parent.anchored_header, &parent.burn_header_hash, &header, &burn_header_hash, 0, None, &block_reward, &vec![], &vec![]).unwrap();
+        tx.commit().unwrap();
+        tip
+    }
+
     #[test]
     fn test_instantiate_chainstate() {
         let mut chainstate = instantiate_chainstate(false, 0x80000000, "instantiate-chainstate");
@@ -0,0 +1,834 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+use chainstate::stacks::db::*;
+use chainstate::burn::operations::{BlockstackOperationType, StackStxOp};
+use chainstate::burn::BlockHeaderHash;
+use chainstate::burn::db::burndb::{BurnDB, BurnDBTx};
+
+use burnchains::BurnchainHeaderHash;
+
+use vm::types::*;
+use vm::representations::ClarityName;
+use vm::representations::ContractName;
+
+use std::convert::TryFrom;
+
+pub const BOOT_CODE_POX_CONTRACT_NAME : &'static str = "pox-stacking";
+pub const BOOT_CODE_POX_STACK_STX : &'static str = "stack-stx";
+pub const BOOT_CODE_POX_STACKING_STATE_MAP : &'static str = "stacking-state";
+pub const BOOT_CODE_POX_STACKERS_MAP : &'static str = "stackers";
+pub const BOOT_CODE_POX_NUM_STACKERS : &'static str = "num-stackers";
+pub const BOOT_CODE_POX_PENDING_STACKER : &'static str = "pending-stacker";
+pub const BOOT_CODE_POX_REWARD_SET_SIZES_MAP : &'static str = "reward-set-sizes";
+pub const BOOT_CODE_POX_REWARD_SET_ADDRS_MAP : &'static str = "reward-set-pox-addrs";
+
+/// Maximum number of reward cycles a stacker can lock their STX for.
+pub const POX_MAX_LOCK_PERIOD : u64 = 12;
+
+/// Minimum number of microSTX a stacker must lock to be included in the reward set.
+#[cfg(test)]
+pub const POX_STACKING_THRESHOLD : u64 = 1000;      // small for testing purposes
+
+#[cfg(not(test))]
+pub const POX_STACKING_THRESHOLD : u64 = 94_000_000_000;
+
+/// Proof-of-transfer boot contract.  STX holders call `stack-stx` to lock up their STX for a
+/// number of reward cycles and register the Bitcoin address to which block commits should pay
+/// them.  The chainstate enforces the lock itself (see StacksChainState::account_lock()); this contract
+/// only records who is stacking, with what reward address, and until when.  Since the lock is
+/// applied outside of the contract, `stack-stx` only accepts the calls the chainstate is carrying
+/// out -- the ones it has checked and will lock STX for (see set_pox_pending_stacker()).  All
+/// other calls, such as calls from other contracts, are rejected.
+pub const BOOT_CODE_POX_BODY : &'static str = r#"
+    (define-constant ERR-STACKING-INVALID-LOCK-PERIOD 1)
+    (define-constant ERR-STACKING-UNAUTHORIZED 2)
+    (define-constant MAX-LOCK-PERIOD u12)
+
+    (define-map stacking-state
+        ((stacker principal))
+        ((amount-ustx uint)
+         (pox-addr (tuple (version (buff 1)) (hashbytes (buff 20))))
+         (lock-period uint)
+         (first-block-height uint)
+         (unlock-burn-height uint))
+    )
+
+    ;; every principal that has ever stacked, in the order they first stacked
+    (define-map stackers
+        ((index uint))
+        ((stacker principal))
+    )
+    (define-data-var num-stackers uint u0)
+
+    ;; the stacker whose stack-stx call the chainstate is carrying out, and the burnchain block
+    ;; height at which their STX unlock.  Only ever set by the chainstate.
+    (define-data-var pending-stacker (optional (tuple (stacker principal) (unlock-burn-height uint))) none)
+
+    ;; the reward set of each reward cycle, by the burnchain block height at which the cycle
+    ;; begins.  Only ever set by the chainstate, from the chain state of the cycle's anchor block.
+    (define-map reward-set-sizes
+        ((reward-cycle-start uint))
+        ((size uint))
+    )
+    (define-map reward-set-pox-addrs
+        ((reward-cycle-start uint) (index uint))
+        ((pox-addr (tuple (version (buff 1)) (hashbytes (buff 20)))))
+    )
+
+    (define-read-only (get-stacker-info (stacker principal))
+        (map-get? stacking-state ((stacker stacker))))
+
+    (define-read-only (get-num-stackers)
+        (var-get num-stackers))
+
+    (define-public (stack-stx (amount-ustx uint)
+                              (pox-addr (tuple (version (buff 1)) (hashbytes (buff 20))))
+                              (lock-period uint))
+        (let ((pending (unwrap! (var-get pending-stacker) (err ERR-STACKING-UNAUTHORIZED))))
+            (asserts! (is-eq (get stacker pending) tx-sender)
+                (err ERR-STACKING-UNAUTHORIZED))
+            (asserts! (and (>= lock-period u1) (<= lock-period MAX-LOCK-PERIOD))
+                (err ERR-STACKING-INVALID-LOCK-PERIOD))
+            (if (is-none (get-stacker-info tx-sender))
+                (begin
+                    (map-set stackers ((index (var-get num-stackers))) ((stacker tx-sender)))
+                    (var-set num-stackers (+ u1 (var-get num-stackers))))
+                'true)
+            (map-set stacking-state ((stacker tx-sender))
+                ((amount-ustx amount-ustx) (pox-addr pox-addr) (lock-period lock-period) (first-block-height block-height)
+                 (unlock-burn-height (get unlock-burn-height pending))))
+            (ok (tuple (stacker tx-sender) (amount-ustx amount-ustx) (lock-period lock-period)))))
+    "#;
+
+/// Where a block commit falls in its PoX reward cycle.  The cycle's reward set is computed from
+/// the chain state of its anchor block -- the Stacks block elected by the last sortition before
+/// the cycle begins -- so only blocks built on the anchor block can know it.  A block commit pays
+/// the reward set's address at its slot (one slot per burnchain block in the cycle), or burns if
+/// its block does not build on the anchor block or the reward set has no address at its slot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoxRewardSlot {
+    pub anchor_burn_header_hash: BurnchainHeaderHash,
+    pub anchor_block_hash: BlockHeaderHash,
+    /// burnchain block height at which the reward cycle begins
+    pub reward_cycle_start_height: u64,
+    /// offset into the reward cycle of the burnchain block that contains the block commit
+    pub slot: u64
+}
+
+impl StacksChainState {
+    fn pox_contract_id() -> QualifiedContractIdentifier {
+        let boot_code_address = StacksAddress::from_string(&STACKS_BOOT_CODE_CONTRACT_ADDRESS.to_string()).unwrap();
+        QualifiedContractIdentifier::new(StandardPrincipalData::from(boot_code_address), ContractName::try_from(BOOT_CODE_POX_CONTRACT_NAME.to_string()).unwrap())
+    }
+
+    /// Decode a PoX reward address tuple -- (tuple (version (buff 1)) (hashbytes (buff 20))).
+    fn pox_addr_from_value(value: &Value) -> Option<StacksAddress> {
+        let tuple = match value {
+            Value::Tuple(ref tuple) => tuple,
+            _ => {
+                return None;
+            }
+        };
+
+        let version = match tuple.get("version") {
+            Ok(Value::Buffer(ref buff)) if buff.data.len() == 1 => buff.data[0],
+            _ => {
+                return None;
+            }
+        };
+
+        let hashbytes = match tuple.get("hashbytes") {
+            Ok(Value::Buffer(ref buff)) if buff.data.len() == 20 => {
+                let mut bytes = [0u8; 20];
+                bytes.copy_from_slice(&buff.data[..]);
+                bytes
+            },
+            _ => {
+                return None;
+            }
+        };
+
+        Some(StacksAddress { version: version, bytes: Hash160(hashbytes) })
+    }
+
+    /// Encode a PoX reward address as the tuple the PoX contract expects.
+    fn pox_addr_to_value(addr: &StacksAddress) -> Value {
+        Value::Tuple(TupleData::from_data(vec![
+            (ClarityName::try_from("version".to_string()).unwrap(), Value::buff_from(vec![addr.version]).unwrap()),
+            (ClarityName::try_from("hashbytes".to_string()).unwrap(), Value::buff_from(addr.bytes.as_bytes().to_vec()).unwrap())
+        ]).expect("FATAL: failed to construct PoX reward address"))
+    }
+
+    /// If this contract-call is a call to the PoX contract's stack-stx function, then verify that
+    /// the origin account can actually stack the requested amount of STX, and return the amount
+    /// and the burnchain block height at which it would unlock.  Returns None if this is some
+    /// other contract-call, or if the arguments are malformed (in which case the Clarity VM
+    /// rejects the call).
+    pub fn check_stack_stx<'a>(clarity_tx: &mut ClarityTx<'a>, contract_call: &TransactionContractCall, origin_account: &StacksAccount) -> Result<Option<(u64, u64)>, Error> {
+        if contract_call.to_clarity_contract_id() != StacksChainState::pox_contract_id() || contract_call.function_name.as_str() != BOOT_CODE_POX_STACK_STX {
+            return Ok(None);
+        }
+
+        let (amount, lock_period) = match (contract_call.function_args.get(0), contract_call.function_args.get(2)) {
+            (Some(Value::UInt(amount)), Some(Value::UInt(lock_period))) => (*amount, *lock_period),
+            (_, _) => {
+                return Ok(None);
+            }
+        };
+
+        let (amount, lock_period) = StacksChainState::check_stack_stx_amount(clarity_tx, &origin_account.principal, amount, lock_period)?;
+        let unlock_height = StacksChainState::get_stack_stx_unlock_height(clarity_tx, lock_period)?;
+        Ok(Some((amount, unlock_height)))
+    }
+
+    /// Verify that a stacker can lock the given amount of STX for the given number of reward
+    /// cycles.  Shared by stack-stx contract-calls and burnchain stack-stx operations.
+    fn check_stack_stx_amount<'a>(clarity_tx: &mut ClarityTx<'a>, stacker: &PrincipalData, amount: u128, lock_period: u128) -> Result<(u64, u64), Error> {
+        if amount < (POX_STACKING_THRESHOLD as u128) || amount > (u64::max_value() as u128) {
+            let msg = format!("Invalid stack-stx: must stack between {} and {} microSTX", POX_STACKING_THRESHOLD, u64::max_value());
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksTransaction(msg));
+        }
+
+        if lock_period < 1 || lock_period > (POX_MAX_LOCK_PERIOD as u128) {
+            let msg = format!("Invalid stack-stx: lock period must be between 1 and {} reward cycles", POX_MAX_LOCK_PERIOD);
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksTransaction(msg));
+        }
+
+        let (cur_locked, unlocked_balance) = clarity_tx.connection().with_clarity_db_readonly(|ref mut db| {
+            let (cur_locked, _) = db.get_account_stx_locked(stacker);
+            let unlocked_balance = db.get_account_stx_unlocked_balance(stacker);
+            Ok((cur_locked, unlocked_balance))
+        }).map_err(Error::ClarityError)?;
+
+        if cur_locked > 0 {
+            let msg = format!("Invalid stack-stx: account {} is already stacking", stacker);
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksTransaction(msg));
+        }
+
+        if unlocked_balance < amount {
+            let msg = format!("Invalid stack-stx: account {} has {} unlocked microSTX; cannot stack {}", stacker, unlocked_balance, amount);
+            warn!("{}", &msg);
+            return Err(Error::InvalidStacksTransaction(msg));
+        }
+
+        Ok((amount as u64, lock_period as u64))
+    }
+
+    /// Record the height of the burnchain block that elected the block being processed.  STX
+    /// locks are measured against it (see ClarityDatabase::get_account_stx_locked()).
+    /// Must be applied identically by miners and block validators, since it alters the state root.
+    pub fn set_burnchain_block_height<'a>(clarity_tx: &mut ClarityTx<'a>, burn_block_height: u64) -> () {
+        clarity_tx.connection().with_clarity_db(|ref mut db| {
+            db.set_burnchain_block_height(burn_block_height);
+            Ok(())
+        }).expect("FATAL: failed to set burnchain block height")
+    }
+
+    /// Get the burnchain block height at which STX stacked now for lock_period reward cycles
+    /// unlock.  The STX are locked for the remainder of the current reward cycle and for
+    /// lock_period reward cycles after it, so they unlock at the first burnchain block of the
+    /// reward cycle after that.
+    fn get_stack_stx_unlock_height<'a>(clarity_tx: &mut ClarityTx<'a>, lock_period: u64) -> Result<u64, Error> {
+        let burn_block_height = clarity_tx.connection().with_clarity_db_readonly(|ref mut db| {
+            Ok(db.get_burnchain_block_height())
+        }).map_err(Error::ClarityError)?;
+
+        let first_block_height = clarity_tx.config.pox_first_block_height;
+        let reward_cycle_length = clarity_tx.config.pox_reward_cycle_length as u64;
+
+        let cur_reward_cycle = burn_block_height.saturating_sub(first_block_height) / reward_cycle_length;
+        Ok(first_block_height + (cur_reward_cycle + 1 + lock_period) * reward_cycle_length)
+    }
+
+    /// Authorize the PoX contract's stack-stx function to record a stacking by the given stacker,
+    /// whose STX unlock at the given burnchain block height -- or, given None, withdraw the
+    /// authorization.  Set before carrying out a stack-stx call that the chainstate has checked
+    /// and will lock the STX for, and cleared right after, so the contract rejects every stack-stx
+    /// call the chainstate does not lock STX for.
+    /// Must be applied identically by miners and block validators, since it alters the state root.
+    pub fn set_pox_pending_stacker<'a>(clarity_tx: &mut ClarityTx<'a>, pending_stacker: Option<(&PrincipalData, u64)>) -> () {
+        let pox_contract_id = StacksChainState::pox_contract_id();
+        let value = match pending_stacker {
+            Some((stacker, unlock_height)) => Value::some(Value::Tuple(TupleData::from_data(vec![
+                (ClarityName::try_from("stacker".to_string()).unwrap(), Value::Principal(stacker.clone())),
+                (ClarityName::try_from("unlock-burn-height".to_string()).unwrap(), Value::UInt(unlock_height as u128))
+            ]).expect("FATAL: failed to construct PoX pending stacker"))),
+            None => Value::none()
+        };
+
+        clarity_tx.connection().with_clarity_db(|ref mut db| {
+            db.set_variable(&pox_contract_id, BOOT_CODE_POX_PENDING_STACKER, value)?;
+            Ok(())
+        }).expect("FATAL: failed to set PoX pending stacker")
+    }
+
+    /// Apply a stack-stx operation sent on the burnchain.  It is carried out as if the sender had
+    /// called the PoX contract's stack-stx function.  The operation is skipped if the sender
+    /// cannot afford it, or if the contract rejects it.
+    /// Must be applied identically by miners and block validators, since it alters the state root.
+    pub fn process_burnchain_stack_stx_op<'a>(clarity_tx: &mut ClarityTx<'a>, op: &StackStxOp) -> Result<(), Error> {
+        let pox_contract_id = StacksChainState::pox_contract_id();
+        let stacker = op.sender.to_account_principal();
+        let (amount, lock_period) = match StacksChainState::check_stack_stx_amount(clarity_tx, &stacker, op.stacked_ustx, op.num_cycles as u128) {
+            Ok(x) => x,
+            Err(e) => {
+                warn!("Skipping burnchain stack-stx {}: {:?}", &op.txid, &e);
+                return Ok(());
+            }
+        };
+        let unlock_height = StacksChainState::get_stack_stx_unlock_height(clarity_tx, lock_period)?;
+
+        let args = vec![Value::UInt(amount as u128), StacksChainState::pox_addr_to_value(&op.reward_addr), Value::UInt(lock_period as u128)];
+
+        StacksChainState::set_pox_pending_stacker(clarity_tx, Some((&stacker, unlock_height)));
+        let result = clarity_tx.connection().run_contract_call(&stacker, &pox_contract_id, BOOT_CODE_POX_STACK_STX, &args, |_, _| false);
+        StacksChainState::set_pox_pending_stacker(clarity_tx, None);
+
+        let committed = match result {
+            Ok((Value::Response(ref data), _)) => data.committed,
+            Ok((_, _)) => false,
+            Err(e) => {
+                warn!("Skipping burnchain stack-stx {}: {:?}", &op.txid, &e);
+                return Ok(());
+            }
+        };
+
+        if !committed {
+            warn!("Skipping burnchain stack-stx {}: rejected by the PoX contract", &op.txid);
+            return Ok(());
+        }
+
+        debug!("Burnchain stack-stx {}: lock {} microSTX of {} for {} reward cycles", &op.txid, amount, &stacker, lock_period);
+        StacksChainState::account_lock(clarity_tx, &stacker, amount, unlock_height)
+    }
+
+    /// Apply the STX transfers and stack-stx operations sent on the burnchain, in the order in
+    /// which they were mined (see BurnDB::get_burnchain_stx_ops()).  Operations that are not
+    /// STX operations are ignored.
+    /// Must be applied identically by miners and block validators, since it alters the state root.
+    pub fn process_burnchain_stx_ops<'a>(clarity_tx: &mut ClarityTx<'a>, ops: &Vec<BlockstackOperationType>) -> Result<(), Error> {
+        for op in ops.iter() {
+            match op {
+                BlockstackOperationType::TransferStx(ref op) => {
+                    StacksChainState::process_burnchain_transfer_stx_op(clarity_tx, op);
+                },
+                BlockstackOperationType::StackStx(ref op) => {
+                    StacksChainState::process_burnchain_stack_stx_op(clarity_tx, op)?;
+                },
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Compute the reward set for the reward cycle that begins at the given burnchain block
+    /// height, as of this Clarity transaction's parent block.  Each stacker whose STX stay locked
+    /// past the start of the cycle gets one reward slot, with larger stackers first (ties broken
+    /// by the order in which they first stacked).  There are at most as many slots as there are
+    /// blocks in a reward cycle, since each burnchain block in the cycle pays one address.
+    pub fn get_reward_set<'a>(clarity_tx: &mut ClarityTx<'a>, reward_cycle_start_height: u64) -> Result<Vec<StacksAddress>, Error> {
+        let reward_cycle_length = clarity_tx.config.pox_reward_cycle_length;
+        let pox_contract_id = StacksChainState::pox_contract_id();
+        let index_name = ClarityName::try_from("index".to_string()).unwrap();
+        let stacker_name = ClarityName::try_from("stacker".to_string()).unwrap();
+
+        let mut stackers = clarity_tx.connection().with_clarity_db_readonly(|ref mut db| {
+            let num_stackers = match db.lookup_variable(&pox_contract_id, BOOT_CODE_POX_NUM_STACKERS)? {
+                Value::UInt(num_stackers) => num_stackers,
+                x => {
+                    panic!("FATAL: PoX num-stackers is not a uint: {:?}", &x);
+                }
+            };
+
+            let mut stackers = vec![];
+            for i in 0..num_stackers {
+                let index_key = Value::Tuple(TupleData::from_data(vec![(index_name.clone(), Value::UInt(i))])
+                    .expect("FATAL: failed to construct PoX stacker index key"));
+
+                let stacker = match db.fetch_entry(&pox_contract_id, BOOT_CODE_POX_STACKERS_MAP, &index_key)? {
+                    Value::Optional(OptionalData { data: Some(entry) }) => match *entry {
+                        Value::Tuple(tuple) => tuple.get_owned("stacker")?,
+                        x => {
+                            panic!("FATAL: PoX stackers entry is not a tuple: {:?}", &x);
+                        }
+                    },
+                    x => {
+                        panic!("FATAL: PoX stacker {} is missing: {:?}", i, &x);
+                    }
+                };
+
+                let stacker_principal = match stacker {
+                    Value::Principal(ref p) => p.clone(),
+                    ref x => {
+                        panic!("FATAL: PoX stacker is not a principal: {:?}", x);
+                    }
+                };
+
+                let state_key = Value::Tuple(TupleData::from_data(vec![(stacker_name.clone(), stacker)])
+                    .expect("FATAL: failed to construct PoX stacking state key"));
+
+                let (pox_addr, state_unlock_height) = match db.fetch_entry(&pox_contract_id, BOOT_CODE_POX_STACKING_STATE_MAP, &state_key)? {
+                    Value::Optional(OptionalData { data: Some(entry) }) => match *entry {
+                        Value::Tuple(tuple) => {
+                            let pox_addr = StacksChainState::pox_addr_from_value(tuple.get("pox-addr")?)
+                                .expect("FATAL: PoX reward address is malformed");
+                            let state_unlock_height = match tuple.get("unlock-burn-height")? {
+                                Value::UInt(height) => *height,
+                                x => {
+                                    panic!("FATAL: PoX unlock height is not a uint: {:?}", &x);
+                                }
+                            };
+                            (pox_addr, state_unlock_height)
+                        },
+                        x => {
+                            panic!("FATAL: PoX stacking state is not a tuple: {:?}", &x);
+                        }
+                    },
+                    x => {
+                        panic!("FATAL: PoX stacking state is missing for {}: {:?}", &stacker_principal, &x);
+                    }
+                };
+
+                // only STX that are still locked when the cycle begins earn rewards in it, and
+                // only for the stacking that locked them
+                let (locked, unlock_height) = db.get_account_stx_locked(&stacker_principal);
+                if locked == 0 || unlock_height <= reward_cycle_start_height || (unlock_height as u128) != state_unlock_height {
+                    continue;
+                }
+
+                stackers.push((locked, pox_addr));
+            }
+            Ok(stackers)
+        }).map_err(Error::ClarityError)?;
+
+        // stable sort, so stackers with the same amount stay in the order they first stacked
+        stackers.sort_by(|(amount_1, _), (amount_2, _)| amount_2.cmp(amount_1));
+        stackers.truncate(reward_cycle_length as usize);
+
+        Ok(stackers.into_iter().map(|(_, pox_addr)| pox_addr).collect())
+    }
+
+    /// Find the PoX reward slot of a block commit mined in the burnchain block at the given
+    /// height, in the burnchain fork whose tip is the block's parent.  Returns None if there is no
+    /// reward slot, in which case the block commit must burn -- i.e. the block is in the first
+    /// reward cycle, or there was no sortition before the reward cycle began.
+    pub fn get_pox_reward_slot<'a>(&self, burn_tx: &mut BurnDBTx<'a>, block_height: u64, parent_burn_header_hash: &BurnchainHeaderHash) -> Result<Option<PoxRewardSlot>, Error> {
+        let reward_cycle_length = self.pox_reward_cycle_length as u64;
+        if block_height < self.pox_first_block_height + reward_cycle_length {
+            return Ok(None);
+        }
+
+        let reward_cycle = (block_height - self.pox_first_block_height) / reward_cycle_length;
+        let reward_cycle_start_height = self.pox_first_block_height + reward_cycle * reward_cycle_length;
+
+        let prepare_end_snapshot = match BurnDB::get_block_snapshot_in_fork(burn_tx, reward_cycle_start_height - 1, parent_burn_header_hash).map_err(Error::DBError)? {
+            Some(sn) => sn,
+            None => {
+                // reward cycle began before the burnchain did
+                return Ok(None);
+            }
+        };
+
+        let anchor_snapshot = BurnDB::get_last_snapshot_with_sortition(burn_tx, reward_cycle_start_height - 1, &prepare_end_snapshot.burn_header_hash)
+            .map_err(Error::DBError)?;
+
+        if !anchor_snapshot.sortition {
+            // no Stacks blocks yet, so no one is stacking
+            return Ok(None);
+        }
+
+        Ok(Some(PoxRewardSlot {
+            anchor_burn_header_hash: anchor_snapshot.burn_header_hash,
+            anchor_block_hash: anchor_snapshot.winning_stacks_block_hash,
+            reward_cycle_start_height: reward_cycle_start_height,
+            slot: block_height - reward_cycle_start_height
+        }))
+    }
+
+    /// Find the PoX reward address that the block commit electing a block must pay, given the
+    /// commit's reward slot.  Call this with the block's Clarity transaction before any of the
+    /// block's transactions are applied.  Returns None if the block commit must burn.
+    ///
+    /// The reward set is recorded in the chain state of the first block in the reward cycle that
+    /// builds on the cycle's anchor block, computed from the anchor block's chain state (its
+    /// parent's).  Blocks that build on it find it there.  Blocks that do not build on the anchor
+    /// block find no reward set, so their block commits must burn.  Either way, the answer only
+    /// depends on the block's own chain state, so every node reaches the same one no matter when
+    /// it processed which block.
+    /// Must be applied identically by miners and block validators, since it alters the state root.
+    pub fn process_pox_reward_slot<'a>(clarity_tx: &mut ClarityTx<'a>, parent_burn_header_hash: &BurnchainHeaderHash, parent_block_hash: &BlockHeaderHash, reward_slot: &PoxRewardSlot) -> Result<Option<StacksAddress>, Error> {
+        let pox_contract_id = StacksChainState::pox_contract_id();
+        let start_key = Value::Tuple(TupleData::from_data(vec![
+            (ClarityName::try_from("reward-cycle-start".to_string()).unwrap(), Value::UInt(reward_slot.reward_cycle_start_height as u128))
+        ]).expect("FATAL: failed to construct PoX reward set size key"));
+        let slot_key = Value::Tuple(TupleData::from_data(vec![
+            (ClarityName::try_from("reward-cycle-start".to_string()).unwrap(), Value::UInt(reward_slot.reward_cycle_start_height as u128)),
+            (ClarityName::try_from("index".to_string()).unwrap(), Value::UInt(reward_slot.slot as u128))
+        ]).expect("FATAL: failed to construct PoX reward set address key"));
+
+        let recorded = clarity_tx.connection().with_clarity_db_readonly(|ref mut db| {
+            let size = match db.fetch_entry(&pox_contract_id, BOOT_CODE_POX_REWARD_SET_SIZES_MAP, &start_key)? {
+                Value::Optional(OptionalData { data: Some(entry) }) => match *entry {
+                    Value::Tuple(tuple) => match tuple.get("size")? {
+                        Value::UInt(size) => *size,
+                        x => {
+                            panic!("FATAL: PoX reward set size is not a uint: {:?}", x);
+                        }
+                    },
+                    x => {
+                        panic!("FATAL: PoX reward set size is not a tuple: {:?}", &x);
+                    }
+                },
+                _ => {
+                    return Ok(None);
+                }
+            };
+
+            if (reward_slot.slot as u128) >= size {
+                return Ok(Some(None));
+            }
+
+            let pox_addr = match db.fetch_entry(&pox_contract_id, BOOT_CODE_POX_REWARD_SET_ADDRS_MAP, &slot_key)? {
+                Value::Optional(OptionalData { data: Some(entry) }) => match *entry {
+                    Value::Tuple(tuple) => StacksChainState::pox_addr_from_value(tuple.get("pox-addr")?)
+                        .expect("FATAL: PoX reward address is malformed"),
+                    x => {
+                        panic!("FATAL: PoX reward set address is not a tuple: {:?}", &x);
+                    }
+                },
+                x => {
+                    panic!("FATAL: PoX reward set address {} is missing: {:?}", reward_slot.slot, &x);
+                }
+            };
+            Ok(Some(Some(pox_addr)))
+        }).map_err(Error::ClarityError)?;
+
+        if let Some(reward_address) = recorded {
+            return Ok(reward_address);
+        }
+
+        if *parent_burn_header_hash != reward_slot.anchor_burn_header_hash || *parent_block_hash != reward_slot.anchor_block_hash {
+            // does not build on the anchor block
+            return Ok(None);
+        }
+
+        let reward_set = StacksChainState::get_reward_set(clarity_tx, reward_slot.reward_cycle_start_height)?;
+        debug!("Reward set for the reward cycle starting at burnchain block {} (anchored at {}/{}): {:?}",
+               reward_slot.reward_cycle_start_height, &reward_slot.anchor_burn_header_hash, &reward_slot.anchor_block_hash, &reward_set);
+
+        let reward_cycle_start_height = reward_slot.reward_cycle_start_height as u128;
+        clarity_tx.connection().with_clarity_db(|ref mut db| {
+            let size = Value::Tuple(TupleData::from_data(vec![(ClarityName::try_from("size".to_string()).unwrap(), Value::UInt(reward_set.len() as u128))])
+                .expect("FATAL: failed to construct PoX reward set size"));
+            db.set_entry(&pox_contract_id, BOOT_CODE_POX_REWARD_SET_SIZES_MAP, start_key, size)?;
+
+            for (index, pox_addr) in reward_set.iter().enumerate() {
+                let key = Value::Tuple(TupleData::from_data(vec![
+                    (ClarityName::try_from("reward-cycle-start".to_string()).unwrap(), Value::UInt(reward_cycle_start_height)),
+                    (ClarityName::try_from("index".to_string()).unwrap(), Value::UInt(index as u128))
+                ]).expect("FATAL: failed to construct PoX reward set address key"));
+                let value = Value::Tuple(TupleData::from_data(vec![(ClarityName::try_from("pox-addr".to_string()).unwrap(), StacksChainState::pox_addr_to_value(pox_addr))])
+                    .expect("FATAL: failed to construct PoX reward set address"));
+                db.set_entry(&pox_contract_id, BOOT_CODE_POX_REWARD_SET_ADDRS_MAP, key, value)?;
+            }
+            Ok(())
+        }).map_err(Error::ClarityError)?;
+
+        Ok(reward_set.get(reward_slot.slot as usize).cloned())
+    }
+
+    /// Find the PoX reward address that the block commit electing a block built on the given
+    /// parent block must pay, given the commit's reward slot and the height of the burnchain block
+    /// it will be mined in.  Returns None if the block commit must burn.
+    /// Miners use this to make their block commits; see process_pox_reward_slot().
+    pub fn get_pox_reward_address(&mut self, parent_burn_header_hash: &BurnchainHeaderHash, parent_block_hash: &BlockHeaderHash, burn_block_height: u64, reward_slot: &PoxRewardSlot) -> Result<Option<StacksAddress>, Error> {
+        let mut clarity_tx = self.read_only_block_begin(parent_burn_header_hash, parent_block_hash)?;
+        StacksChainState::set_burnchain_block_height(&mut clarity_tx, burn_block_height);
+        let reward_address_res = StacksChainState::process_pox_reward_slot(&mut clarity_tx, parent_burn_header_hash, parent_block_hash, reward_slot);
+        clarity_tx.rollback_block();
+        reward_address_res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use burnchains::*;
+    use chainstate::burn::*;
+    use chainstate::burn::operations::TransferStxOp;
+    use chainstate::stacks::db::test::*;
+    use core::POX_REWARD_CYCLE_LENGTH;
+    use util::hash::*;
+
+    fn make_pox_addr(version: u8, hashbytes: [u8; 20]) -> Value {
+        Value::Tuple(TupleData::from_data(vec![
+            (ClarityName::try_from("version".to_string()).unwrap(), Value::buff_from(vec![version]).unwrap()),
+            (ClarityName::try_from("hashbytes".to_string()).unwrap(), Value::buff_from(hashbytes.to_vec()).unwrap())
+        ]).unwrap())
+    }
+
+    fn make_stack_stx(privk: &StacksPrivateKey, nonce: u64, amount: u64, pox_addr: Value, lock_period: u64) -> StacksTransaction {
+        let boot_code_address = StacksAddress::from_string(&STACKS_BOOT_CODE_CONTRACT_ADDRESS.to_string()).unwrap();
+        let payload = TransactionPayload::new_contract_call(boot_code_address, BOOT_CODE_POX_CONTRACT_NAME, BOOT_CODE_POX_STACK_STX,
+                                                            vec![Value::UInt(amount as u128), pox_addr, Value::UInt(lock_period as u128)]).unwrap();
+        make_tx(privk, nonce, payload)
+    }
+
+    fn make_tx(privk: &StacksPrivateKey, nonce: u64, payload: TransactionPayload) -> StacksTransaction {
+        let auth = TransactionAuth::from_p2pkh(privk).unwrap();
+        let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth, payload);
+        tx.chain_id = 0x80000000;
+        tx.post_condition_mode = TransactionPostConditionMode::Allow;
+        tx.set_fee_rate(0);
+        tx.set_origin_nonce(nonce);
+
+        let mut signer = StacksTransactionSigner::new(&tx);
+        signer.sign_origin(privk).unwrap();
+        signer.get_tx().unwrap()
+    }
+
+    #[test]
+    fn test_stack_stx() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "pox-stack-stx");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let addr = TransactionAuth::from_p2pkh(&privk).unwrap().origin().address_testnet();
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 5000);
+
+        // too little, too long, or too much
+        let tx_too_little = make_stack_stx(&privk, 0, POX_STACKING_THRESHOLD - 1, make_pox_addr(26, [0x01; 20]), 1);
+        assert!(StacksChainState::process_transaction(&mut conn, &tx_too_little).is_err());
+
+        let tx_too_long = make_stack_stx(&privk, 0, POX_STACKING_THRESHOLD, make_pox_addr(26, [0x01; 20]), POX_MAX_LOCK_PERIOD + 1);
+        assert!(StacksChainState::process_transaction(&mut conn, &tx_too_long).is_err());
+
+        let tx_too_much = make_stack_stx(&privk, 0, 5001, make_pox_addr(26, [0x01; 20]), 1);
+        assert!(StacksChainState::process_transaction(&mut conn, &tx_too_much).is_err());
+
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.nonce, 0);
+        assert_eq!(account.stx_locked, 0);
+
+        // stack for two reward cycles
+        let tx_stack = make_stack_stx(&privk, 0, 4000, make_pox_addr(26, [0x01; 20]), 2);
+        StacksChainState::process_transaction(&mut conn, &tx_stack).unwrap();
+
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.nonce, 1);
+        assert_eq!(account.stx_balance, 5000);
+        assert_eq!(account.stx_locked, 4000);
+        assert_eq!(account.stx_unlock_height, 3 * (POX_REWARD_CYCLE_LENGTH as u64));
+
+        // can't stack again while locked
+        let tx_stack_again = make_stack_stx(&privk, 1, POX_STACKING_THRESHOLD, make_pox_addr(26, [0x01; 20]), 1);
+        assert!(StacksChainState::process_transaction(&mut conn, &tx_stack_again).is_err());
+
+        let reward_set = StacksChainState::get_reward_set(&mut conn, POX_REWARD_CYCLE_LENGTH as u64).unwrap();
+        assert_eq!(reward_set, vec![StacksAddress { version: 26, bytes: Hash160([0x01; 20]) }]);
+
+        conn.commit_block();
+    }
+
+    fn get_stacking_state<'a>(conn: &mut ClarityTx<'a>, stacker: &PrincipalData) -> Option<Value> {
+        let state_key = Value::Tuple(TupleData::from_data(vec![
+            (ClarityName::try_from("stacker".to_string()).unwrap(), Value::Principal(stacker.clone()))
+        ]).unwrap());
+
+        let entry = conn.connection().with_clarity_db_readonly(|ref mut db| {
+            Ok(db.fetch_entry(&StacksChainState::pox_contract_id(), BOOT_CODE_POX_STACKING_STATE_MAP, &state_key)?)
+        }).unwrap();
+
+        match entry {
+            Value::Optional(OptionalData { data }) => data.map(|entry| *entry),
+            x => panic!("stacking state is not optional: {:?}", &x)
+        }
+    }
+
+    #[test]
+    fn test_stack_stx_indirect() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "pox-stack-stx-indirect");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let addr = TransactionAuth::from_p2pkh(&privk).unwrap().origin().address_testnet();
+
+        let contract = format!("
+            (define-public (stack-indirect (amount-ustx uint))
+                (contract-call? '{}.{} stack-stx amount-ustx (tuple (version 0x1a) (hashbytes 0x0101010101010101010101010101010101010101)) u1))
+            (define-public (stack-as-contract (amount-ustx uint))
+                (as-contract (contract-call? '{}.{} stack-stx amount-ustx (tuple (version 0x1a) (hashbytes 0x0101010101010101010101010101010101010101)) u1)))
+            ", STACKS_BOOT_CODE_CONTRACT_ADDRESS, BOOT_CODE_POX_CONTRACT_NAME, STACKS_BOOT_CODE_CONTRACT_ADDRESS, BOOT_CODE_POX_CONTRACT_NAME);
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 5000);
+
+        let tx_contract = make_tx(&privk, 0, TransactionPayload::new_smart_contract(&"stacker".to_string(), &contract).unwrap());
+        StacksChainState::process_transaction(&mut conn, &tx_contract).unwrap();
+
+        // the chainstate only locks STX for direct calls to stack-stx, so the PoX contract
+        // rejects stack-stx calls from other contracts -- on behalf of the sender or not
+        for (nonce, function_name) in ["stack-indirect", "stack-as-contract"].iter().enumerate() {
+            let payload = TransactionPayload::new_contract_call(addr.clone(), "stacker", function_name, vec![Value::UInt(4000)]).unwrap();
+            let tx_indirect = make_tx(&privk, (nonce + 1) as u64, payload);
+            let (_, receipt) = StacksChainState::process_transaction(&mut conn, &tx_indirect).unwrap();
+            assert_eq!(receipt.result, Value::error(Value::Int(2)));
+        }
+
+        let contract_principal = PrincipalData::Contract(QualifiedContractIdentifier::new(StandardPrincipalData::from(addr.clone()), ContractName::try_from("stacker".to_string()).unwrap()));
+        assert!(get_stacking_state(&mut conn, &addr.to_account_principal()).is_none());
+        assert!(get_stacking_state(&mut conn, &contract_principal).is_none());
+
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.nonce, 3);
+        assert_eq!(account.stx_locked, 0);
+        assert_eq!(StacksChainState::get_reward_set(&mut conn, POX_REWARD_CYCLE_LENGTH as u64).unwrap(), vec![]);
+
+        // a direct call still works
+        let tx_stack = make_stack_stx(&privk, 3, 4000, make_pox_addr(26, [0x01; 20]), 1);
+        StacksChainState::process_transaction(&mut conn, &tx_stack).unwrap();
+
+        assert!(get_stacking_state(&mut conn, &addr.to_account_principal()).is_some());
+        assert_eq!(StacksChainState::get_account(&mut conn, &addr.to_account_principal()).stx_locked, 4000);
+
+        conn.commit_block();
+    }
+
+    #[test]
+    fn test_stack_stx_unlock_height() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "pox-stack-stx-unlock-height");
+        chainstate.pox_first_block_height = 100;
+        chainstate.pox_reward_cycle_length = 5;
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let addr = TransactionAuth::from_p2pkh(&privk).unwrap().origin().address_testnet();
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 5000);
+
+        // stacking in the middle of reward cycle 1 (burn blocks 105-109) locks the STX through
+        // cycles 2 and 3, so they unlock at the start of cycle 4
+        StacksChainState::set_burnchain_block_height(&mut conn, 107);
+        let tx_stack = make_stack_stx(&privk, 0, 4000, make_pox_addr(26, [0x01; 20]), 2);
+        StacksChainState::process_transaction(&mut conn, &tx_stack).unwrap();
+
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.stx_locked, 4000);
+        assert_eq!(account.stx_unlock_height, 120);
+
+        let reward_addr = StacksAddress { version: 26, bytes: Hash160([0x01; 20]) };
+        assert_eq!(StacksChainState::get_reward_set(&mut conn, 110).unwrap(), vec![reward_addr.clone()]);
+        assert_eq!(StacksChainState::get_reward_set(&mut conn, 115).unwrap(), vec![reward_addr.clone()]);
+        assert_eq!(StacksChainState::get_reward_set(&mut conn, 120).unwrap(), vec![]);
+
+        // locked until the burnchain reaches the unlock height, regardless of Stacks height
+        StacksChainState::set_burnchain_block_height(&mut conn, 119);
+        assert_eq!(StacksChainState::get_account(&mut conn, &addr.to_account_principal()).stx_locked, 4000);
+
+        StacksChainState::set_burnchain_block_height(&mut conn, 120);
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.stx_locked, 0);
+        assert_eq!(account.stx_balance, 5000);
+
+        conn.commit_block();
+    }
+
+    #[test]
+    fn test_reward_set() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "pox-reward-set");
+
+        let privks : Vec<StacksPrivateKey> = (0..(POX_REWARD_CYCLE_LENGTH + 2)).map(|_| StacksPrivateKey::new()).collect();
+        let amounts : Vec<u64> = (0..(POX_REWARD_CYCLE_LENGTH + 2)).map(|i| POX_STACKING_THRESHOLD + ((i % 3) as u64)).collect();
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+
+        // no stackers, no reward set
+        assert_eq!(StacksChainState::get_reward_set(&mut conn, POX_REWARD_CYCLE_LENGTH as u64).unwrap(), vec![]);
+
+        for (i, privk) in privks.iter().enumerate() {
+            let addr = TransactionAuth::from_p2pkh(privk).unwrap().origin().address_testnet();
+            StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), amounts[i]);
+
+            let tx_stack = make_stack_stx(privk, 0, amounts[i], make_pox_addr(26, [i as u8; 20]), 1);
+            StacksChainState::process_transaction(&mut conn, &tx_stack).unwrap();
+        }
+
+        // largest stackers first, then in stacking order, and only one slot per block in the cycle
+        let mut expected : Vec<(u64, usize)> = amounts.iter().enumerate().map(|(i, amount)| (*amount, i)).collect();
+        expected.sort_by(|(amount_1, i_1), (amount_2, i_2)| amount_2.cmp(amount_1).then(i_1.cmp(i_2)));
+        expected.truncate(POX_REWARD_CYCLE_LENGTH as usize);
+
+        let expected_addrs : Vec<StacksAddress> = expected.iter().map(|(_, i)| StacksAddress { version: 26, bytes: Hash160([*i as u8; 20]) }).collect();
+        let reward_set = StacksChainState::get_reward_set(&mut conn, POX_REWARD_CYCLE_LENGTH as u64).unwrap();
+        assert_eq!(reward_set, expected_addrs);
+
+        conn.commit_block();
+    }
+
+    #[test]
+    fn test_burnchain_stx_ops() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "pox-burnchain-stx-ops");
+
+        let sender = StacksAddress { version: 26, bytes: Hash160([0x01; 20]) };
+        let recipient = StacksAddress { version: 26, bytes: Hash160([0x02; 20]) };
+        let stacker = StacksAddress { version: 26, bytes: Hash160([0x03; 20]) };
+        let reward_addr = StacksAddress { version: 26, bytes: Hash160([0x04; 20]) };
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+        StacksChainState::account_credit(&mut conn, &sender.to_account_principal(), 1000);
+        StacksChainState::account_credit(&mut conn, &stacker.to_account_principal(), 5000);
+
+        // the second transfer can't be afforded after the first one, so it's skipped
+        let transfer_ops = vec![
+            BlockstackOperationType::TransferStx(TransferStxOp::new(&sender, &recipient, 600)),
+            BlockstackOperationType::TransferStx(TransferStxOp::new(&sender, &recipient, 600)),
+            BlockstackOperationType::TransferStx(TransferStxOp::new(&sender, &recipient, 400)),
+        ];
+        StacksChainState::process_burnchain_stx_ops(&mut conn, &transfer_ops).unwrap();
+
+        assert_eq!(StacksChainState::get_account(&mut conn, &sender.to_account_principal()).stx_balance, 0);
+        assert_eq!(StacksChainState::get_account(&mut conn, &recipient.to_account_principal()).stx_balance, 1000);
+
+        // the first stack is unaffordable, and the last one happens while the STX are locked.
+        // Neither is applied.
+        let stack_ops = vec![
+            BlockstackOperationType::StackStx(StackStxOp::new(&stacker, &reward_addr, 5001, 1)),
+            BlockstackOperationType::StackStx(StackStxOp::new(&stacker, &reward_addr, 4000, 2)),
+            BlockstackOperationType::StackStx(StackStxOp::new(&stacker, &reward_addr, POX_STACKING_THRESHOLD as u128, 1)),
+        ];
+        StacksChainState::process_burnchain_stx_ops(&mut conn, &stack_ops).unwrap();
+
+        let account = StacksChainState::get_account(&mut conn, &stacker.to_account_principal());
+        assert_eq!(account.nonce, 0);
+        assert_eq!(account.stx_balance, 5000);
+        assert_eq!(account.stx_locked, 4000);
+        assert_eq!(account.stx_unlock_height, 3 * (POX_REWARD_CYCLE_LENGTH as u64));
+
+        let reward_set = StacksChainState::get_reward_set(&mut conn, POX_REWARD_CYCLE_LENGTH as u64).unwrap();
+        assert_eq!(reward_set, vec![reward_addr]);
+
+        conn.commit_block();
+    }
+}
@@ -0,0 +1,365 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use rusqlite::Row;
+use rusqlite::Connection;
+use rusqlite::types::ToSql;
+
+use serde_json;
+
+use burnchains::Txid;
+use burnchains::BurnchainHeaderHash;
+
+use chainstate::burn::BlockHeaderHash;
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+use chainstate::stacks::db::*;
+
+use util::db::{
+    FromRow,
+    FromColumn,
+    query_rows
+};
+use util::db::Error as db_error;
+
+use vm::contexts::{AssetMap, AssetEvent};
+use vm::costs::ExecutionCost;
+use vm::database::ClaritySerializable;
+use vm::types::{PrincipalData, AssetIdentifier, Value};
+
+/// Whether or not a transaction's payload took effect.  An aborted transaction is still
+/// confirmed -- its fee is paid and its nonce is consumed -- but its other changes are rolled back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionReceiptStatus {
+    Success,
+    Abort
+}
+
+/// An asset that moved as a result of a transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StacksTransactionEvent {
+    /// `sender` sent `amount` microSTX to `recipient`
+    STXTransfer { sender: PrincipalData, recipient: PrincipalData, amount: u128 },
+    /// `sender` destroyed `amount` microSTX
+    STXBurn { sender: PrincipalData, amount: u128 },
+    /// `sender` sent `amount` units of a fungible token to `recipient`
+    FungibleTokenTransfer { sender: PrincipalData, recipient: PrincipalData, asset: AssetIdentifier, amount: u128 },
+    /// `amount` units of a fungible token were minted to `recipient`
+    FungibleTokenMint { recipient: PrincipalData, asset: AssetIdentifier, amount: u128 },
+    /// `sender` sent a non-fungible token to `recipient`
+    NonFungibleTokenTransfer { sender: PrincipalData, recipient: PrincipalData, asset: AssetIdentifier, value: Value },
+    /// a non-fungible token was minted to `recipient`
+    NonFungibleTokenMint { recipient: PrincipalData, asset: AssetIdentifier, value: Value }
+}
+
+/// The outcome of a confirmed transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct StacksTransactionReceipt {
+    pub txid: Txid,
+    /// anchored block that confirmed this transaction, either directly or through the microblock
+    /// stream it builds on
+    pub burn_header_hash: BurnchainHeaderHash,
+    pub block_hash: BlockHeaderHash,
+    /// microblock that contained this transaction, if it was streamed
+    pub microblock_hash: Option<BlockHeaderHash>,
+    /// position of this transaction in its (micro)block
+    pub tx_index: u32,
+    pub status: TransactionReceiptStatus,
+    /// value returned by a contract call; (ok true) or none for other payloads
+    pub result: Value,
+    /// true if the transaction was aborted because its post-conditions were not met
+    pub post_condition_aborted: bool,
+    pub execution_cost: ExecutionCost,
+    pub events: Vec<StacksTransactionEvent>,
+    pub fee: u64,
+    pub stx_burned: u128
+}
+
+/// What a transaction would do if it were mined on top of a given chain tip
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionSimulation {
+    /// the receipt the transaction would get
+    pub receipt: StacksTransactionReceipt,
+    /// the assets the transaction would attempt to move -- even if its post-conditions would
+    /// abort it, in which case none of them would actually move
+    pub asset_events: Vec<StacksTransactionEvent>
+}
+
+impl TransactionReceiptStatus {
+    pub fn to_str(&self) -> &'static str {
+        match *self {
+            TransactionReceiptStatus::Success => "success",
+            TransactionReceiptStatus::Abort => "abort"
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<TransactionReceiptStatus> {
+        match s {
+            "success" => Some(TransactionReceiptStatus::Success),
+            "abort" => Some(TransactionReceiptStatus::Abort),
+            _ => None
+        }
+    }
+}
+
+impl StacksTransactionEvent {
+    /// Get the events for the assets that moved during a transaction's execution, in the order
+    /// the Clarity transfer, mint, and burn functions moved them.
+    pub fn from_asset_map(asset_map: AssetMap) -> Vec<StacksTransactionEvent> {
+        asset_map.into_events()
+            .into_iter()
+            .map(|event| match event {
+                AssetEvent::STXTransfer { sender, recipient, amount } => StacksTransactionEvent::STXTransfer { sender, recipient, amount },
+                AssetEvent::STXBurn { sender, amount } => StacksTransactionEvent::STXBurn { sender, amount },
+                AssetEvent::TokenTransfer { sender, recipient, asset, amount } => StacksTransactionEvent::FungibleTokenTransfer { sender, recipient, asset, amount },
+                AssetEvent::TokenMint { recipient, asset, amount } => StacksTransactionEvent::FungibleTokenMint { recipient, asset, amount },
+                AssetEvent::AssetTransfer { sender, recipient, asset, value } => StacksTransactionEvent::NonFungibleTokenTransfer { sender, recipient, asset, value },
+                AssetEvent::AssetMint { recipient, asset, value } => StacksTransactionEvent::NonFungibleTokenMint { recipient, asset, value }
+            })
+            .collect()
+    }
+}
+
+impl StacksTransactionReceipt {
+    fn new(tx: &StacksTransaction, status: TransactionReceiptStatus, result: Value, post_condition_aborted: bool, events: Vec<StacksTransactionEvent>, stx_burned: u128) -> StacksTransactionReceipt {
+        StacksTransactionReceipt {
+            txid: tx.txid(),
+            burn_header_hash: MINER_BLOCK_BURN_HEADER_HASH.clone(),
+            block_hash: MINER_BLOCK_HEADER_HASH.clone(),
+            microblock_hash: None,
+            tx_index: 0,
+            status,
+            result,
+            post_condition_aborted,
+            execution_cost: ExecutionCost::zero(),
+            events,
+            fee: 0,
+            stx_burned
+        }
+    }
+
+    /// Receipt for a transaction whose payload took effect
+    pub fn from_success(tx: &StacksTransaction, result: Value, events: Vec<StacksTransactionEvent>, stx_burned: u128) -> StacksTransactionReceipt {
+        StacksTransactionReceipt::new(tx, TransactionReceiptStatus::Success, result, false, events, stx_burned)
+    }
+
+    /// Receipt for a transaction whose payload was rolled back
+    pub fn from_abort(tx: &StacksTransaction, result: Value, post_condition_aborted: bool, stx_burned: u128) -> StacksTransactionReceipt {
+        StacksTransactionReceipt::new(tx, TransactionReceiptStatus::Abort, result, post_condition_aborted, vec![], stx_burned)
+    }
+
+    /// Index hash of the anchored block that confirmed this transaction
+    pub fn index_block_hash(&self) -> BlockHeaderHash {
+        StacksBlockHeader::make_index_block_hash(&self.burn_header_hash, &self.block_hash)
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == TransactionReceiptStatus::Success
+    }
+}
+
+impl TransactionSimulation {
+    pub fn post_conditions_passed(&self) -> bool {
+        !self.receipt.post_condition_aborted
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "txid": self.receipt.txid.to_hex(),
+            "status": self.receipt.status.to_str(),
+            "result": self.receipt.result.to_json(),
+            "post_conditions_passed": self.post_conditions_passed(),
+            "fee": self.receipt.fee,
+            "execution_cost": serde_json::to_value(&self.receipt.execution_cost).unwrap_or(serde_json::Value::Null),
+            "stx_burned": format!("{}", self.receipt.stx_burned),
+            "events": serde_json::to_value(&self.receipt.events).unwrap_or(serde_json::Value::Null),
+            "asset_events": serde_json::to_value(&self.asset_events).unwrap_or(serde_json::Value::Null)
+        })
+    }
+}
+
+impl FromRow<StacksTransactionReceipt> for StacksTransactionReceipt {
+    fn from_row<'a>(row: &'a Row) -> Result<StacksTransactionReceipt, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let burn_header_hash = BurnchainHeaderHash::from_column(row, "burn_header_hash")?;
+        let block_hash = BlockHeaderHash::from_column(row, "block_hash")?;
+        let microblock_hash_hex : Option<String> = row.get("microblock_hash");
+        let tx_index_i64 : i64 = row.get("tx_index");
+        let status_str : String = row.get("status");
+        let result_hex : String = row.get("result");
+        let post_condition_aborted : bool = row.get("post_condition_aborted");
+        let execution_cost_json : String = row.get("execution_cost");
+        let events_json : String = row.get("events");
+        let fee_i64 : i64 = row.get("fee");
+        let stx_burned_text : String = row.get("stx_burned");
+
+        if tx_index_i64 < 0 || tx_index_i64 > (u32::max_value() as i64) || fee_i64 < 0 {
+            return Err(db_error::ParseError);
+        }
+
+        let microblock_hash = match microblock_hash_hex {
+            Some(hex) => Some(BlockHeaderHash::from_hex(&hex).map_err(|_e| db_error::ParseError)?),
+            None => None
+        };
+
+        let status = TransactionReceiptStatus::from_str(&status_str).ok_or(db_error::ParseError)?;
+        let result = Value::try_deserialize_hex_untyped(&result_hex).map_err(|_e| db_error::ParseError)?;
+        let execution_cost : ExecutionCost = serde_json::from_str(&execution_cost_json).map_err(|_e| db_error::ParseError)?;
+        let events : Vec<StacksTransactionEvent> = serde_json::from_str(&events_json).map_err(|_e| db_error::ParseError)?;
+        let stx_burned = stx_burned_text.parse::<u128>().map_err(|_e| db_error::ParseError)?;
+
+        Ok(StacksTransactionReceipt {
+            txid,
+            burn_header_hash,
+            block_hash,
+            microblock_hash,
+            tx_index: tx_index_i64 as u32,
+            status,
+            result,
+            post_condition_aborted,
+            execution_cost,
+            events,
+            fee: fee_i64 as u64,
+            stx_burned
+        })
+    }
+}
+
+impl StacksChainState {
+    /// Store the receipts for the transactions confirmed by a newly-processed block
+    pub fn insert_transaction_receipts<'a>(tx: &mut StacksDBTx<'a>, tip_info: &StacksHeaderInfo, receipts: &Vec<StacksTransactionReceipt>) -> Result<(), Error> {
+        let index_block_hash = tip_info.index_block_hash();
+        for receipt in receipts.iter() {
+            assert!(receipt.fee < i64::max_value() as u64);
+
+            let microblock_hash_hex = receipt.microblock_hash.as_ref().map(|h| h.to_hex());
+            let execution_cost_json = serde_json::to_string(&receipt.execution_cost).map_err(|e| Error::DBError(db_error::SerializationError(e)))?;
+            let events_json = serde_json::to_string(&receipt.events).map_err(|e| Error::DBError(db_error::SerializationError(e)))?;
+
+            let args: &[&dyn ToSql] = &[&receipt.txid, &index_block_hash, &tip_info.burn_header_hash, &tip_info.anchored_header.block_hash(), &microblock_hash_hex,
+                                        &receipt.tx_index, &receipt.status.to_str(), &receipt.result.serialize(), &receipt.post_condition_aborted,
+                                        &execution_cost_json, &events_json, &(receipt.fee as i64), &format!("{}", receipt.stx_burned)];
+
+            tx.execute("INSERT INTO transaction_receipts (txid,index_block_hash,burn_header_hash,block_hash,microblock_hash,tx_index,status,result,post_condition_aborted,execution_cost,events,fee,stx_burned) \
+                        VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9,?10,?11,?12,?13)", args)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Get the receipts for a transaction, one for each block (in any fork) that confirmed it.
+    pub fn get_transaction_receipts(conn: &Connection, txid: &Txid) -> Result<Vec<StacksTransactionReceipt>, Error> {
+        let sql = "SELECT * FROM transaction_receipts WHERE txid = ?1 ORDER BY index_block_hash".to_string();
+        let args: &[&dyn ToSql] = &[txid];
+        query_rows::<StacksTransactionReceipt, _>(conn, &sql, args).map_err(Error::DBError)
+    }
+
+    /// Get the receipt for a transaction as confirmed by a particular anchored block, if it
+    /// confirmed it.
+    pub fn get_transaction_receipt(conn: &Connection, txid: &Txid, index_block_hash: &BlockHeaderHash) -> Result<Option<StacksTransactionReceipt>, Error> {
+        let sql = "SELECT * FROM transaction_receipts WHERE txid = ?1 AND index_block_hash = ?2".to_string();
+        let args: &[&dyn ToSql] = &[txid, index_block_hash];
+        let mut rows = query_rows::<StacksTransactionReceipt, _>(conn, &sql, args).map_err(Error::DBError)?;
+        Ok(rows.pop())
+    }
+
+    /// Get the receipts for all the transactions an anchored block confirmed, in the order they
+    /// were processed (its parent microblock stream first).
+    pub fn get_block_transaction_receipts(conn: &Connection, index_block_hash: &BlockHeaderHash) -> Result<Vec<StacksTransactionReceipt>, Error> {
+        let sql = "SELECT * FROM transaction_receipts WHERE index_block_hash = ?1 ORDER BY rowid".to_string();
+        let args: &[&dyn ToSql] = &[index_block_hash];
+        query_rows::<StacksTransactionReceipt, _>(conn, &sql, args).map_err(Error::DBError)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::db::test::*;
+    use util::hash::Hash160;
+    use vm::types::QualifiedContractIdentifier;
+
+    #[test]
+    fn transaction_receipts_store_load() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "transaction-receipts-store-load");
+        let privk = StacksPrivateKey::new();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let sender = auth.origin().address_testnet().to_account_principal();
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+
+        let mut tx_transfer = StacksTransaction::new(TransactionVersion::Testnet, auth.clone(), TransactionPayload::TokenTransfer(recv_addr.clone(), 123, TokenTransferMemo([0u8; 34])));
+        tx_transfer.chain_id = 0x80000000;
+        tx_transfer.auth.set_origin_nonce(0);
+
+        let mut tx_call = StacksTransaction::new(TransactionVersion::Testnet, auth.clone(),
+                                                 TransactionPayload::new_contract_call(recv_addr.clone(), "hello-world", "send", vec![]).unwrap());
+        tx_call.chain_id = 0x80000000;
+        tx_call.auth.set_origin_nonce(1);
+
+        let asset = AssetIdentifier {
+            contract_identifier: QualifiedContractIdentifier::parse("ST000000000000000000002AMW42H.hello-world").unwrap(),
+            asset_name: "stackaroos".into()
+        };
+
+        let mut transfer_receipt = StacksTransactionReceipt::from_success(&tx_transfer, Value::okay(Value::Bool(true)),
+                                                                          vec![StacksTransactionEvent::STXTransfer { sender: sender.clone(), recipient: recv_addr.to_account_principal(), amount: 123 }], 0);
+        transfer_receipt.microblock_hash = Some(BlockHeaderHash([2u8; 32]));
+        transfer_receipt.fee = 180;
+
+        let mut call_receipt = StacksTransactionReceipt::from_abort(&tx_call, Value::error(Value::UInt(1)), true, 0);
+        call_receipt.tx_index = 1;
+        call_receipt.execution_cost = ExecutionCost { write_length: 1, write_count: 2, read_length: 3, read_count: 4, runtime: 5 };
+        call_receipt.events = vec![StacksTransactionEvent::FungibleTokenMint { recipient: sender.clone(), asset: asset.clone(), amount: u128::max_value() },
+                                   StacksTransactionEvent::FungibleTokenTransfer { sender: sender.clone(), recipient: recv_addr.to_account_principal(), asset: asset.clone(), amount: u128::max_value() },
+                                   StacksTransactionEvent::NonFungibleTokenMint { recipient: sender.clone(), asset: asset.clone(), value: Value::Int(-1) },
+                                   StacksTransactionEvent::NonFungibleTokenTransfer { sender: sender.clone(), recipient: recv_addr.to_account_principal(), asset: asset.clone(), value: Value::Int(-1) }];
+        call_receipt.stx_burned = u128::max_value();
+
+        let mut header = StacksBlockHeader::genesis();
+        header.total_work.work = 1;
+        let tip_info = StacksHeaderInfo {
+            anchored_header: header.clone(),
+            microblock_tail: None,
+            block_height: 1,
+            index_root: TrieHash([0u8; 32]),
+            burn_header_hash: BurnchainHeaderHash([1u8; 32]),
+            burn_header_timestamp: 0
+        };
+
+        let receipts = vec![transfer_receipt.clone(), call_receipt.clone()];
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+        StacksChainState::insert_transaction_receipts(&mut tx, &tip_info, &receipts).unwrap();
+        tx.commit().unwrap();
+
+        // receipts are stored against the block that confirmed them
+        transfer_receipt.burn_header_hash = tip_info.burn_header_hash.clone();
+        transfer_receipt.block_hash = header.block_hash();
+        call_receipt.burn_header_hash = tip_info.burn_header_hash.clone();
+        call_receipt.block_hash = header.block_hash();
+
+        let index_block_hash = tip_info.index_block_hash();
+        assert_eq!(transfer_receipt.index_block_hash(), index_block_hash);
+
+        assert_eq!(StacksChainState::get_block_transaction_receipts(&chainstate.headers_db, &index_block_hash).unwrap(), vec![transfer_receipt.clone(), call_receipt.clone()]);
+        assert_eq!(StacksChainState::get_transaction_receipts(&chainstate.headers_db, &tx_call.txid()).unwrap(), vec![call_receipt.clone()]);
+        assert_eq!(StacksChainState::get_transaction_receipt(&chainstate.headers_db, &tx_transfer.txid(), &index_block_hash).unwrap(), Some(transfer_receipt));
+        assert_eq!(StacksChainState::get_transaction_receipt(&chainstate.headers_db, &tx_transfer.txid(), &BlockHeaderHash([0u8; 32])).unwrap(), None);
+        assert_eq!(StacksChainState::get_block_transaction_receipts(&chainstate.headers_db, &BlockHeaderHash([0u8; 32])).unwrap(), vec![]);
+    }
+}
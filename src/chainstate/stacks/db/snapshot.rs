@@ -0,0 +1,432 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rusqlite::{Connection, OpenFlags, Transaction, NO_PARAMS};
+use rusqlite::types::ToSql;
+
+use burnchains::BurnchainHeaderHash;
+
+use chainstate::burn::{BlockHeaderHash, ConsensusHash};
+use chainstate::burn::db::burndb::BurnDB;
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+use chainstate::stacks::db::*;
+use chainstate::stacks::index::TrieHash;
+use chainstate::stacks::index::storage::TrieFileStorage;
+
+use core::EMPTY_MICROBLOCK_PARENT_HASH;
+
+use util::db::Error as db_error;
+
+/// Files and directories in a chain state snapshot
+const SNAPSHOT_MANIFEST_FILE: &str = "manifest.json";
+const SNAPSHOT_CHAINSTATE_DIR: &str = "chainstate";
+const SNAPSHOT_BURNCHAIN_DIR: &str = "burnchain";
+
+/// Describes the Stacks block a chain state snapshot was taken at, along with the root hashes
+/// of its state.  An imported snapshot must reproduce all of them before it is used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainstateSnapshotManifest {
+    pub mainnet: bool,
+    pub chain_id: u32,
+    pub burn_header_hash: BurnchainHeaderHash,
+    pub block_hash: BlockHeaderHash,
+    pub block_height: u64,
+    pub index_block_hash: BlockHeaderHash,
+    /// root hash of the Clarity state MARF as of the block
+    pub state_root_hash: TrieHash,
+    /// root hash of the headers index MARF as of the block
+    pub headers_root_hash: TrieHash,
+    pub burn_block_height: u64,
+    pub consensus_hash: ConsensusHash,
+    /// root hash of the sortition MARF as of the burn block that selected the block
+    pub sortition_root_hash: TrieHash
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ChainstateSnapshotManifestJSON {
+    mainnet: bool,
+    chain_id: u32,
+    burn_header_hash: String,
+    block_hash: String,
+    block_height: u64,
+    index_block_hash: String,
+    state_root_hash: String,
+    headers_root_hash: String,
+    burn_block_height: u64,
+    consensus_hash: String,
+    sortition_root_hash: String
+}
+
+fn snapshot_error(msg: String) -> Error {
+    Error::InvalidChainstateSnapshot(msg)
+}
+
+fn parse_hex<T, F>(field: &str, hex_str: &str, parse: F) -> Result<T, Error>
+where F: FnOnce(&str) -> Result<T, ::util::HexError> {
+    parse(hex_str)
+        .map_err(|_e| snapshot_error(format!("Invalid {} '{}' in snapshot manifest", field, hex_str)))
+}
+
+fn open_snapshot_db(path: &Path) -> Result<Connection, Error> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .map_err(|e| Error::DBError(db_error::SqliteError(e)))
+}
+
+fn open_snapshot_trie_storage(path: &Path) -> Result<TrieFileStorage, Error> {
+    let path_str = path.to_str().ok_or_else(|| Error::DBError(db_error::ParseError))?;
+    TrieFileStorage::new(path_str).map_err(Error::MARFError)
+}
+
+/// Detach the tries of all blocks but the given block and its ancestors from a copied MARF.
+/// Returns the blocks that were kept.
+fn detach_snapshot_tries(path: &Path, bhh: &BlockHeaderHash) -> Result<HashSet<BlockHeaderHash>, Error> {
+    open_snapshot_trie_storage(path)?
+        .detach_tries(bhh)
+        .map_err(Error::MARFError)
+}
+
+/// Load the given keys into a temporary `kept_keys` table, for `delete_unkept_rows()`
+fn insert_kept_keys<I: Iterator<Item = String>>(tx: &Transaction, keys: I) -> Result<(), Error> {
+    tx.execute("CREATE TEMP TABLE kept_keys(key TEXT PRIMARY KEY)", NO_PARAMS)
+        .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+    for key in keys {
+        tx.execute("INSERT OR IGNORE INTO kept_keys (key) VALUES (?1)", &[&key])
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+    }
+    Ok(())
+}
+
+fn delete_unkept_rows<P>(tx: &Transaction, sql: &str, args: P) -> Result<(), Error>
+where P: IntoIterator, P::Item: ToSql {
+    tx.execute(sql, args)
+        .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+    Ok(())
+}
+
+/// Recursively copy a directory.  The destination must not exist yet.
+fn copy_dir(src_dir: &Path, dest_dir: &Path) -> Result<(), Error> {
+    fs::create_dir_all(dest_dir).map_err(|e| Error::DBError(db_error::IOError(e)))?;
+    for dirent_res in fs::read_dir(src_dir).map_err(|e| Error::DBError(db_error::IOError(e)))? {
+        let dirent = dirent_res.map_err(|e| Error::DBError(db_error::IOError(e)))?;
+        let src_path = dirent.path();
+        let dest_path = dest_dir.join(dirent.file_name());
+        if src_path.is_dir() {
+            copy_dir(&src_path, &dest_path)?;
+        }
+        else {
+            fs::copy(&src_path, &dest_path).map_err(|e| Error::DBError(db_error::IOError(e)))?;
+        }
+    }
+    Ok(())
+}
+
+impl ChainstateSnapshotManifest {
+    pub fn to_json(&self) -> String {
+        let manifest_json = ChainstateSnapshotManifestJSON {
+            mainnet: self.mainnet,
+            chain_id: self.chain_id,
+            burn_header_hash: self.burn_header_hash.to_hex(),
+            block_hash: self.block_hash.to_hex(),
+            block_height: self.block_height,
+            index_block_hash: self.index_block_hash.to_hex(),
+            state_root_hash: self.state_root_hash.to_hex(),
+            headers_root_hash: self.headers_root_hash.to_hex(),
+            burn_block_height: self.burn_block_height,
+            consensus_hash: self.consensus_hash.to_hex(),
+            sortition_root_hash: self.sortition_root_hash.to_hex()
+        };
+        serde_json::to_string_pretty(&manifest_json)
+            .expect("FATAL: failed to encode snapshot manifest")
+    }
+
+    pub fn from_json(json_str: &str) -> Result<ChainstateSnapshotManifest, Error> {
+        let manifest_json : ChainstateSnapshotManifestJSON = serde_json::from_str(json_str)
+            .map_err(|e| snapshot_error(format!("Invalid snapshot manifest: {:?}", &e)))?;
+
+        Ok(ChainstateSnapshotManifest {
+            mainnet: manifest_json.mainnet,
+            chain_id: manifest_json.chain_id,
+            burn_header_hash: parse_hex("burn header hash", &manifest_json.burn_header_hash, BurnchainHeaderHash::from_hex)?,
+            block_hash: parse_hex("block hash", &manifest_json.block_hash, BlockHeaderHash::from_hex)?,
+            block_height: manifest_json.block_height,
+            index_block_hash: parse_hex("index block hash", &manifest_json.index_block_hash, BlockHeaderHash::from_hex)?,
+            state_root_hash: parse_hex("state root hash", &manifest_json.state_root_hash, TrieHash::from_hex)?,
+            headers_root_hash: parse_hex("headers root hash", &manifest_json.headers_root_hash, TrieHash::from_hex)?,
+            burn_block_height: manifest_json.burn_block_height,
+            consensus_hash: parse_hex("consensus hash", &manifest_json.consensus_hash, ConsensusHash::from_hex)?,
+            sortition_root_hash: parse_hex("sortition root hash", &manifest_json.sortition_root_hash, TrieHash::from_hex)?
+        })
+    }
+
+    /// Load the manifest of the snapshot at the given path
+    pub fn load(snapshot_path: &str) -> Result<ChainstateSnapshotManifest, Error> {
+        let manifest_path = Path::new(snapshot_path).join(SNAPSHOT_MANIFEST_FILE);
+        let json_str = fs::read_to_string(&manifest_path)
+            .map_err(|e| snapshot_error(format!("Failed to read snapshot manifest {:?}: {:?}", &manifest_path, &e)))?;
+        ChainstateSnapshotManifest::from_json(&json_str)
+    }
+}
+
+impl StacksChainState {
+    /// Directory holding this chain's state (i.e. the one named by `chain_dir_name()`)
+    fn chain_dir(&self) -> Result<PathBuf, Error> {
+        PathBuf::from(&self.blocks_path).parent()
+            .map(|p| p.to_path_buf())
+            .ok_or_else(|| Error::DBError(db_error::ParseError))
+    }
+
+    /// Calculate the manifest of a snapshot taken at the given Stacks block, and check that the
+    /// chain state and burn database agree on it:  the Clarity state and headers index roots
+    /// must match the ones the block's header committed to, and the burn block must have
+    /// selected the block.
+    fn make_snapshot_manifest(&mut self, burndb: &mut BurnDB, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash) -> Result<ChainstateSnapshotManifest, Error> {
+        let header_info = StacksChainState::get_anchored_block_header_info(&self.headers_db, burn_header_hash, block_hash)?
+            .ok_or(Error::NoSuchBlockError)?;
+        if header_info.anchored_header.block_hash() != *block_hash {
+            return Err(snapshot_error(format!("Header stored for {}/{} hashes to {}", burn_header_hash, block_hash, header_info.anchored_header.block_hash())));
+        }
+        let index_block_hash = header_info.index_block_hash();
+
+        let state_root_hash = {
+            let mut clarity_tx = self.read_only_block_begin(burn_header_hash, block_hash)?;
+            let root_hash_res = clarity_tx.connection().get_marf().get_root_hash_at(&index_block_hash);
+            clarity_tx.rollback_block();
+            root_hash_res.map_err(Error::MARFError)?
+        };
+        if state_root_hash != header_info.anchored_header.state_index_root {
+            return Err(snapshot_error(format!("Clarity state root of {}/{} is {}, but its header committed to {}",
+                                              burn_header_hash, block_hash, state_root_hash, header_info.anchored_header.state_index_root)));
+        }
+
+        let headers_root_hash = self.headers_state_index.get_root_hash_at(&index_block_hash).map_err(Error::MARFError)?;
+        if headers_root_hash != header_info.index_root {
+            return Err(snapshot_error(format!("Headers index root of {}/{} is {}, but {} was recorded",
+                                              burn_header_hash, block_hash, headers_root_hash, header_info.index_root)));
+        }
+
+        let burn_snapshot = BurnDB::get_block_snapshot(burndb.conn(), burn_header_hash).map_err(Error::DBError)?
+            .ok_or_else(|| snapshot_error(format!("No burn block {} in the burn database", burn_header_hash)))?;
+        if !burn_snapshot.sortition || burn_snapshot.winning_stacks_block_hash != *block_hash {
+            return Err(snapshot_error(format!("Burn block {} did not select Stacks block {}", burn_header_hash, block_hash)));
+        }
+
+        let sortition_root_hash = burndb.marf.get_root_hash_at(&BlockHeaderHash::from(burn_header_hash.clone())).map_err(Error::MARFError)?;
+        if sortition_root_hash != burn_snapshot.index_root {
+            return Err(snapshot_error(format!("Sortition root of burn block {} is {}, but {} was recorded",
+                                              burn_header_hash, sortition_root_hash, burn_snapshot.index_root)));
+        }
+
+        Ok(ChainstateSnapshotManifest {
+            mainnet: self.mainnet,
+            chain_id: self.chain_id,
+            burn_header_hash: burn_header_hash.clone(),
+            block_hash: block_hash.clone(),
+            block_height: header_info.block_height,
+            index_block_hash,
+            state_root_hash,
+            headers_root_hash,
+            burn_block_height: burn_snapshot.block_height,
+            consensus_hash: burn_snapshot.consensus_hash,
+            sortition_root_hash
+        })
+    }
+
+    /// Export a snapshot of the chain state and of the burn database at the given path, from
+    /// which a new node can start at the given Stacks block instead of replaying the chain.  The
+    /// snapshot holds the headers database, the headers index and Clarity state MARFs, the burn
+    /// database, and a manifest of their root hashes as of the block.  It does not hold any
+    /// block data.
+    ///
+    /// The block can be any processed Stacks block.  The snapshot only holds the state of the
+    /// block, its ancestors, and the burnchain blocks up to the one that selected it; state from
+    /// later blocks and other forks is dropped from it (see `trim_snapshot()`).  The node must
+    /// not be processing blocks while a snapshot is exported.
+    pub fn export_snapshot(&mut self, burndb_path: &str, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, snapshot_path: &str) -> Result<ChainstateSnapshotManifest, Error> {
+        if fs::metadata(snapshot_path).is_ok() {
+            return Err(snapshot_error(format!("Snapshot path {} already exists", snapshot_path)));
+        }
+
+        let mut burndb = BurnDB::open(&burndb_path.to_string(), false).map_err(Error::DBError)?;
+        let manifest = self.make_snapshot_manifest(&mut burndb, burn_header_hash, block_hash)?;
+
+        let snapshot_dir = Path::new(snapshot_path);
+        let snapshot_chain_dir = snapshot_dir.join(SNAPSHOT_CHAINSTATE_DIR).join(StacksChainState::chain_dir_name(self.mainnet, self.chain_id));
+        let snapshot_burndb_dir = snapshot_dir.join(SNAPSHOT_BURNCHAIN_DIR);
+
+        debug!("Export snapshot of {}/{} (height {}) to {}", burn_header_hash, block_hash, manifest.block_height, snapshot_path);
+
+        let res = copy_dir(&self.chain_dir()?.join("vm"), &snapshot_chain_dir.join("vm"))
+            .and_then(|_| copy_dir(Path::new(burndb_path), &snapshot_burndb_dir))
+            .and_then(|_| StacksChainState::trim_snapshot(&snapshot_chain_dir, &snapshot_burndb_dir, &manifest))
+            .and_then(|_| fs::write(snapshot_dir.join(SNAPSHOT_MANIFEST_FILE), manifest.to_json())
+                      .map_err(|e| Error::DBError(db_error::IOError(e))));
+
+        if let Err(e) = res {
+            warn!("Failed to export snapshot to {}: {:?}", snapshot_path, &e);
+            let _ = fs::remove_dir_all(snapshot_dir);
+            return Err(e);
+        }
+
+        Ok(manifest)
+    }
+
+    /// Drop the state of all Stacks blocks other than the snapshot's block and its ancestors
+    /// from a copy of the chain state, and the state of all burnchain blocks other than the one
+    /// that selected it and its ancestors from a copy of the burn database.  The tries of the
+    /// dropped blocks are detached from the MARFs, and their rows are deleted.  Clarity values
+    /// that are not in the state as of the snapshot's block are deleted too.
+    fn trim_snapshot(chain_dir: &Path, burndb_dir: &Path, manifest: &ChainstateSnapshotManifest) -> Result<(), Error> {
+        let vm_dir = chain_dir.join("vm");
+
+        // headers index and headers database
+        let kept_blocks = detach_snapshot_tries(&vm_dir.join("index"), &manifest.index_block_hash)?;
+        let mut headers_db = open_snapshot_db(&vm_dir.join("headers.db"))?;
+        {
+            let tx = headers_db.transaction().map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            insert_kept_keys(&tx, kept_blocks.iter().map(|bhh| bhh.to_hex()))?;
+
+            for table in ["payments", "confirmed_fee_rates", "transaction_receipts", "transaction_index", "account_history", "account_history_blocks"].iter() {
+                delete_unkept_rows(&tx, &format!("DELETE FROM {} WHERE index_block_hash NOT IN (SELECT key FROM kept_keys)", table), NO_PARAMS)?;
+            }
+            delete_unkept_rows(&tx, "DELETE FROM user_supporters WHERE burn_header_hash NOT IN \
+                               (SELECT burn_header_hash FROM block_headers WHERE index_block_hash IN (SELECT key FROM kept_keys))", NO_PARAMS)?;
+            delete_unkept_rows(&tx, "DELETE FROM microblock_headers WHERE parent_burn_header_hash NOT IN \
+                               (SELECT burn_header_hash FROM block_headers WHERE index_block_hash IN (SELECT key FROM kept_keys))", NO_PARAMS)?;
+            delete_unkept_rows(&tx, "DELETE FROM block_headers WHERE index_block_hash NOT IN (SELECT key FROM kept_keys)", NO_PARAMS)?;
+
+            // microblocks that no remaining block confirmed
+            delete_unkept_rows(&tx, "DELETE FROM microblock_headers WHERE NOT EXISTS \
+                               (SELECT 1 FROM block_headers AS child WHERE child.parent_block = microblock_headers.parent_block_hash \
+                                AND child.parent_microblock != ?1 AND child.parent_microblock_sequence >= microblock_headers.sequence)", &[&EMPTY_MICROBLOCK_PARENT_HASH])?;
+
+            tx.execute("DROP TABLE kept_keys", NO_PARAMS).map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            tx.commit().map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+
+        // Clarity state
+        let clarity_dir = vm_dir.join("clarity");
+        let kept_state_blocks = detach_snapshot_tries(&clarity_dir.join("marf"), &manifest.index_block_hash)?;
+        let values = {
+            let mut storage = open_snapshot_trie_storage(&clarity_dir.join("marf"))?;
+            storage.get_leaf_values(&manifest.index_block_hash).map_err(Error::MARFError)?
+        };
+        let mut clarity_db = open_snapshot_db(&clarity_dir.join("data.sqlite"))?;
+        {
+            let tx = clarity_db.transaction().map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            insert_kept_keys(&tx, kept_state_blocks.iter().map(|bhh| bhh.to_hex()))?;
+            delete_unkept_rows(&tx, "DELETE FROM metadata_table WHERE blockhash NOT IN (SELECT key FROM kept_keys)", NO_PARAMS)?;
+            tx.execute("DROP TABLE kept_keys", NO_PARAMS).map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+            insert_kept_keys(&tx, values.iter().map(|value| value.to_hex()))?;
+            delete_unkept_rows(&tx, "DELETE FROM data_table WHERE key NOT IN (SELECT key FROM kept_keys)", NO_PARAMS)?;
+            tx.execute("DROP TABLE kept_keys", NO_PARAMS).map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            tx.commit().map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+
+        // burn database
+        let kept_burn_blocks = detach_snapshot_tries(&burndb_dir.join("marf"), &BlockHeaderHash::from(manifest.burn_header_hash))?;
+        let mut burn_db = open_snapshot_db(&burndb_dir.join("data.db"))?;
+        {
+            let tx = burn_db.transaction().map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            insert_kept_keys(&tx, kept_burn_blocks.iter().map(|bhh| bhh.to_hex()))?;
+
+            for table in ["leader_keys", "block_commits", "user_burn_support", "transfer_stx", "stack_stx"].iter() {
+                delete_unkept_rows(&tx, &format!("DELETE FROM {} WHERE burn_header_hash NOT IN (SELECT key FROM kept_keys)", table), NO_PARAMS)?;
+            }
+            delete_unkept_rows(&tx, "DELETE FROM snapshots WHERE burn_header_hash NOT IN (SELECT key FROM kept_keys)", NO_PARAMS)?;
+
+            tx.execute("DROP TABLE kept_keys", NO_PARAMS).map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            tx.commit().map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+
+        debug!("Trimmed snapshot of {}/{}: kept {} headers, {} Clarity states, {} Clarity values, {} burn blocks",
+               &manifest.burn_header_hash, &manifest.block_hash, kept_blocks.len(), kept_state_blocks.len(), values.len(), kept_burn_blocks.len());
+        Ok(())
+    }
+
+    /// Import the snapshot at the given path into a new chain state and burn database at the given
+    /// paths, and verify it against its manifest.  Neither may exist yet.
+    ///
+    /// The snapshot itself is untrusted, so the caller must supply the index block hash of the
+    /// Stacks block it was taken at and the consensus hash of the burnchain block that selected
+    /// it, obtained from a source it trusts.  Nothing is imported unless the manifest names that
+    /// block and consensus hash, and the snapshot's state matches the manifest.
+    ///
+    /// Since the snapshot holds no block data, history before the snapshot's block is pruned.
+    pub fn import_snapshot(snapshot_path: &str, chainstate_path: &str, burndb_path: &str, trusted_index_block_hash: &BlockHeaderHash, trusted_consensus_hash: &ConsensusHash) -> Result<(StacksChainState, ChainstateSnapshotManifest), Error> {
+        let manifest = ChainstateSnapshotManifest::load(snapshot_path)?;
+        if manifest.index_block_hash != *trusted_index_block_hash {
+            return Err(snapshot_error(format!("Snapshot is of block {}, not the trusted block {}", &manifest.index_block_hash, trusted_index_block_hash)));
+        }
+        if manifest.consensus_hash != *trusted_consensus_hash {
+            return Err(snapshot_error(format!("Snapshot has consensus hash {}, not the trusted consensus hash {}", &manifest.consensus_hash, trusted_consensus_hash)));
+        }
+
+        // the index block hash is derived from these, so they must match it too
+        if StacksBlockHeader::make_index_block_hash(&manifest.burn_header_hash, &manifest.block_hash) != manifest.index_block_hash {
+            return Err(snapshot_error(format!("Snapshot manifest's index block hash {} does not match {}/{}", &manifest.index_block_hash, &manifest.burn_header_hash, &manifest.block_hash)));
+        }
+
+        let chain_dir_name = StacksChainState::chain_dir_name(manifest.mainnet, manifest.chain_id);
+        let chain_dir = Path::new(chainstate_path).join(&chain_dir_name);
+        if fs::metadata(&chain_dir).is_ok() {
+            return Err(snapshot_error(format!("Chain state {:?} already exists", &chain_dir)));
+        }
+        if fs::metadata(burndb_path).is_ok() {
+            return Err(snapshot_error(format!("Burn database {} already exists", burndb_path)));
+        }
+
+        let snapshot_dir = Path::new(snapshot_path);
+        let res = copy_dir(&snapshot_dir.join(SNAPSHOT_CHAINSTATE_DIR).join(&chain_dir_name), &chain_dir)
+            .and_then(|_| copy_dir(&snapshot_dir.join(SNAPSHOT_BURNCHAIN_DIR), Path::new(burndb_path)))
+            .and_then(|_| StacksChainState::open_imported_snapshot(&manifest, chainstate_path, burndb_path));
+
+        match res {
+            Ok(chainstate) => {
+                debug!("Imported snapshot of {}/{} (height {}) from {}", &manifest.burn_header_hash, &manifest.block_hash, manifest.block_height, snapshot_path);
+                Ok((chainstate, manifest))
+            },
+            Err(e) => {
+                warn!("Failed to import snapshot from {}: {:?}", snapshot_path, &e);
+                let _ = fs::remove_dir_all(&chain_dir);
+                let _ = fs::remove_dir_all(burndb_path);
+                Err(e)
+            }
+        }
+    }
+
+    fn open_imported_snapshot(manifest: &ChainstateSnapshotManifest, chainstate_path: &str, burndb_path: &str) -> Result<StacksChainState, Error> {
+        let mut chainstate = StacksChainState::open(manifest.mainnet, manifest.chain_id, chainstate_path)?;
+        let mut burndb = BurnDB::open(&burndb_path.to_string(), false).map_err(Error::DBError)?;
+
+        let imported_manifest = chainstate.make_snapshot_manifest(&mut burndb, &manifest.burn_header_hash, &manifest.block_hash)?;
+        if imported_manifest != *manifest {
+            return Err(snapshot_error(format!("Snapshot of {}/{} does not match its manifest: expected {:?}, got {:?}",
+                                              &manifest.burn_header_hash, &manifest.block_hash, manifest, &imported_manifest)));
+        }
+
+        chainstate.prune_history(manifest.block_height)?;
+        Ok(chainstate)
+    }
+}
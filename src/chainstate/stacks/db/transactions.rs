@@ -77,6 +77,13 @@ use vm::clarity::Error as clarity_error;
 use vm::database::ClarityDatabase;
 
 use vm::contracts::Contract;
+use vm::costs::ExecutionCost;
+
+use chainstate::stacks::db::receipts::{StacksTransactionReceipt, StacksTransactionEvent, TransactionSimulation};
+#[cfg(test)]
+use chainstate::stacks::db::receipts::TransactionReceiptStatus;
+
+use core::TX_FEE_RUNTIME_COST_PER_UNIT;
 
 // make it possible to have a set of Values
 impl std::hash::Hash for Value {
@@ -102,16 +109,49 @@ impl StacksChainState {
         StacksChainState::get_account(clarity_tx, &principal_data)
     }
 
-    /// Pay the transaction fee (but don't credit it to the miner yet).
+    /// Pay (part of) the transaction fee (but don't credit it to the miner yet).
     /// Does not touch the account nonce
-    /// TODO: the fee paid here isn't the bare fee in the transaction, but is instead the
-    /// block-wide STX/compute-unit rate, times the compute units used by this tx.
-    fn pay_transaction_fee<'a>(clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction, payer_account: &StacksAccount) -> Result<u64, Error> {
-        if payer_account.stx_balance < tx.get_fee_rate() as u128 {
+    fn pay_transaction_fee<'a>(clarity_tx: &mut ClarityTx<'a>, payer_account: &StacksAccount, fee: u64) -> Result<u64, Error> {
+        if payer_account.stx_balance - payer_account.stx_locked < fee as u128 {
             return Err(Error::InvalidFee);
         }
-        StacksChainState::account_debit(clarity_tx, &payer_account.principal, tx.get_fee_rate());
-        Ok(tx.get_fee_rate())
+        StacksChainState::account_debit(clarity_tx, &payer_account.principal, fee);
+        Ok(fee)
+    }
+
+    /// The part of a transaction's fee that is owed for its size: the fee rate times the length
+    /// of the transaction's encoding.  This is known before the transaction runs.
+    pub fn get_tx_length_fee(tx: &StacksTransaction) -> Result<u64, Error> {
+        let mut tx_bytes = vec![];
+        tx.consensus_serialize(&mut tx_bytes).map_err(Error::NetError)?;
+        tx.get_fee_rate().checked_mul(tx_bytes.len() as u64).ok_or(Error::InvalidFee)
+    }
+
+    /// How many units of execution cost (see TX_FEE_RUNTIME_COST_PER_UNIT) a transaction
+    /// consumed, given the block's running cost total before and after it ran.
+    pub fn get_execution_cost_units(cost_before: &ExecutionCost, cost_after: &ExecutionCost) -> u64 {
+        let write_length = cost_after.write_length.saturating_sub(cost_before.write_length);
+        let runtime = cost_after.runtime.saturating_sub(cost_before.runtime);
+        write_length.saturating_add(runtime / TX_FEE_RUNTIME_COST_PER_UNIT)
+    }
+
+    /// How much execution cost a transaction consumed, given the block's running cost total
+    /// before and after it ran.
+    pub fn get_execution_cost_delta(cost_before: &ExecutionCost, cost_after: &ExecutionCost) -> ExecutionCost {
+        ExecutionCost {
+            write_length: cost_after.write_length.saturating_sub(cost_before.write_length),
+            write_count: cost_after.write_count.saturating_sub(cost_before.write_count),
+            read_length: cost_after.read_length.saturating_sub(cost_before.read_length),
+            read_count: cost_after.read_count.saturating_sub(cost_before.read_count),
+            runtime: cost_after.runtime.saturating_sub(cost_before.runtime)
+        }
+    }
+
+    /// The part of a transaction's fee that is owed for the execution cost it consumed: the fee
+    /// rate times the number of execution cost units.  This is only known after the transaction runs.
+    pub fn get_tx_execution_fee(tx: &StacksTransaction, cost_before: &ExecutionCost, cost_after: &ExecutionCost) -> Result<u64, Error> {
+        let units = StacksChainState::get_execution_cost_units(cost_before, cost_after);
+        tx.get_fee_rate().checked_mul(units).ok_or(Error::InvalidFee)
     }
 
     /// Pre-check a transaction -- make sure it's well-formed
@@ -317,12 +357,16 @@ impl StacksChainState {
         }
 
         clarity_tx.connection().with_clarity_db(|ref mut db| {
-            // does the sender have ths amount?
+            // does the sender have ths amount, and is it unlocked?
             let cur_balance = db.get_account_stx_balance(&origin_account.principal);
+            let unlocked_balance = db.get_account_stx_unlocked_balance(&origin_account.principal);
             let recipient_balance = db.get_account_stx_balance(&recipient_principal);
 
-            let new_balance = cur_balance.checked_sub(amount as u128)
-                .ok_or(clarity_error::BadTransaction(format!("Address {:?} has {} microSTX; needed at least {}", &origin_account.principal, cur_balance, amount)))?;
+            if unlocked_balance < (amount as u128) {
+                return Err(clarity_error::BadTransaction(format!("Address {:?} has {} unlocked microSTX; needed at least {}", &origin_account.principal, unlocked_balance, amount)));
+            }
+
+            let new_balance = cur_balance - (amount as u128);
 
             let new_recipient_balance = recipient_balance.checked_add(amount as u128)
                 .ok_or(clarity_error::BadTransaction(format!("Address {:?} has {} microSTX; cannot add {}", &recipient_principal, recipient_balance, amount)))?;
@@ -349,8 +393,17 @@ impl StacksChainState {
     }
 
     /// Process the transaction's payload, and run the post-conditions against the resulting state.
-    /// Returns the number of STX burned.
-    pub fn process_transaction_payload<'a>(clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction, origin_account: &StacksAccount) -> Result<u128, Error> {
+    /// Returns a receipt describing what happened, including the number of STX burned.  The
+    /// receipt's fee and execution cost are filled in by the caller.
+    pub fn process_transaction_payload<'a>(clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction, origin_account: &StacksAccount) -> Result<StacksTransactionReceipt, Error> {
+        StacksChainState::run_transaction_payload(clarity_tx, tx, origin_account)
+            .map(|(receipt, _)| receipt)
+    }
+
+    /// Process the transaction's payload, and return its receipt along with the assets it
+    /// attempted to move.  These are the receipt's events if the payload took effect, but are
+    /// reported even if the post-conditions aborted it.
+    fn run_transaction_payload<'a>(clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction, origin_account: &StacksAccount) -> Result<(StacksTransactionReceipt, Vec<StacksTransactionEvent>), Error> {
         match tx.payload {
             TransactionPayload::TokenTransfer(ref addr, ref amount, ref _memo) => {
                 // post-conditions are not allowed for this variant, since they're non-sensical.
@@ -365,7 +418,8 @@ impl StacksChainState {
                 StacksChainState::process_transaction_token_transfer(clarity_tx, &tx.txid(), addr, *amount, origin_account)?;
 
                 // no burns
-                Ok(0)
+                let event = StacksTransactionEvent::STXTransfer { sender: origin_account.principal.clone(), recipient: addr.to_account_principal(), amount: *amount as u128 };
+                Ok((StacksTransactionReceipt::from_success(tx, Value::okay(Value::Bool(true)), vec![event.clone()], 0), vec![event]))
             },
             TransactionPayload::ContractCall(ref contract_call) => {
                 // if this calls a function that doesn't exist or is syntactically invalid, then the
@@ -374,18 +428,41 @@ impl StacksChainState {
                 // transaction is still valid, but no changes will materialize besides debiting the
                 // tx fee.
                 let contract_id = contract_call.to_clarity_contract_id();
-                let asset_map = match clarity_tx.connection().run_contract_call(&origin_account.principal, &contract_id, &contract_call.function_name, &contract_call.function_args,
-                                                                                |asset_map, _| { !StacksChainState::check_transaction_postconditions(&tx.post_conditions, &tx.post_condition_mode, origin_account, asset_map) }) {
+
+                // a PoX stack-stx call is invalid if the origin can't lock the STX it asks to stack.
+                let stacking = StacksChainState::check_stack_stx(clarity_tx, contract_call, origin_account)?;
+
+                // the stacking contract only accepts the stack-stx calls we've checked
+                if let Some((_, unlock_height)) = stacking {
+                    StacksChainState::set_pox_pending_stacker(clarity_tx, Some((&origin_account.principal, unlock_height)));
+                }
+
+                let mut aborted = false;
+                let result = clarity_tx.connection().run_contract_call(&origin_account.principal, &contract_id, &contract_call.function_name, &contract_call.function_args,
+                                                                                |asset_map, _| {
+                    aborted = !StacksChainState::check_transaction_postconditions(&tx.post_conditions, &tx.post_condition_mode, origin_account, asset_map);
+                    aborted
+                });
+
+                if stacking.is_some() {
+                    StacksChainState::set_pox_pending_stacker(clarity_tx, None);
+                }
+
+                let (return_value, asset_map, committed) = match result {
                     Ok((return_value, asset_map)) => {
                         debug!("Contract-call to {:?}.{:?} args {:?} returned {:?}", &contract_id, &contract_call.function_name, &contract_call.function_args, &return_value);
-                        Ok(asset_map)
+                        let committed = match return_value {
+                            Value::Response(ref data) => data.committed,
+                            _ => false
+                        };
+                        Ok((return_value, asset_map, committed))
                     },
                     Err(e) => {
                         match e {
                             // runtime errors are okay -- we just have an empty asset map
                             clarity_error::Interpreter(InterpreterError::Runtime(ref runtime_error, ref stack)) => {
                                 debug!("Runtime error {:?} on contract-call {:?}.{:?} {:?}, stack trace {:?}", runtime_error, &contract_id, &contract_call.function_name, &contract_call.function_args, stack);
-                                Ok(AssetMap::new())
+                                Ok((Value::none(), AssetMap::new(), false))
                             },
                             _ => Err(e)
                         }
@@ -395,7 +472,24 @@ impl StacksChainState {
                     Error::ClarityError(e)
                 })?;
 
-                Ok(asset_map.get_stx_burned_total())
+                // lock the stacker's STX only if the stacking contract accepted them
+                if let Some((amount, unlock_height)) = stacking {
+                    if committed && !aborted {
+                        StacksChainState::account_lock(clarity_tx, &origin_account.principal, amount, unlock_height)?;
+                    }
+                }
+
+                let stx_burned = asset_map.get_stx_burned_total();
+                let events = StacksTransactionEvent::from_asset_map(asset_map);
+                let receipt = 
+                    if committed && !aborted {
+                        StacksTransactionReceipt::from_success(tx, return_value, events.clone(), stx_burned)
+                    }
+                    else {
+                        StacksTransactionReceipt::from_abort(tx, return_value, aborted, stx_burned)
+                    };
+
+                Ok((receipt, events))
             },
             TransactionPayload::SmartContract(ref smart_contract) => {
                 let issuer_principal = match origin_account.principal {
@@ -429,24 +523,28 @@ impl StacksChainState {
                         debug!("Runtime error in contract analysis for {:?}: {:?}", &contract_id, &e);
 
                         // abort now -- no burns
-                        return Ok(0);
+                        return Ok((StacksTransactionReceipt::from_abort(tx, Value::none(), false, 0), vec![]));
                     }
                 };
 
                 // execution -- if this fails due to a runtime error, then the transaction is still
                 // accepted, but the contract does not materialize (but the sender is out their fee).
-                let asset_map = match clarity_tx.connection().initialize_smart_contract(
+                let mut aborted = false;
+                let (asset_map, instantiated) = match clarity_tx.connection().initialize_smart_contract(
                     &contract_id, &contract_ast, &contract_code_str,
-                    |asset_map, _| { !StacksChainState::check_transaction_postconditions(&tx.post_conditions, &tx.post_condition_mode, origin_account, asset_map) }) {
+                    |asset_map, _| {
+                        aborted = !StacksChainState::check_transaction_postconditions(&tx.post_conditions, &tx.post_condition_mode, origin_account, asset_map);
+                        aborted
+                    }) {
                     Ok(asset_map) => {
-                        Ok(asset_map)
+                        Ok((asset_map, true))
                     },
                     Err(e) => {
                         match e {
                             // runtime errors are okay -- we just have an empty asset map
                             clarity_error::Interpreter(InterpreterError::Runtime(ref runtime_error, ref stack)) => {
                                 debug!("Runtime error {:?} on instantiating {:?}, code {:?}, stack trace {:?}", runtime_error, &contract_id, &contract_code_str, stack);
-                                Ok(AssetMap::new())
+                                Ok((AssetMap::new(), false))
                             },
                             _ => Err(e)
                         }
@@ -459,22 +557,83 @@ impl StacksChainState {
                 // store analysis -- if this fails, then the have some pretty bad problems
                 clarity_tx.connection().save_analysis(&contract_id, &contract_analysis)
                     .expect("FATAL: failed to store contract analysis");
-                
-                Ok(asset_map.get_stx_burned_total())
+
+                let stx_burned = asset_map.get_stx_burned_total();
+                let events = StacksTransactionEvent::from_asset_map(asset_map);
+                let receipt = 
+                    if instantiated && !aborted {
+                        StacksTransactionReceipt::from_success(tx, Value::okay(Value::Bool(true)), events.clone(), stx_burned)
+                    }
+                    else {
+                        StacksTransactionReceipt::from_abort(tx, Value::none(), aborted, stx_burned)
+                    };
+
+                Ok((receipt, events))
             },
-            TransactionPayload::PoisonMicroblock(ref _mblock_header_1, ref _mblock_header_2) => {
-                // TODO: actually implement this, but not necessarily for this PR
-                panic!("Not implemented yet");
+            TransactionPayload::PoisonMicroblock(ref mblock_header_1, ref mblock_header_2) => {
+                // the penalty itself is applied to the offending leader's scheduled reward once
+                // this transaction's block is appended (see append_block())
+                let _ = StacksChainState::validate_poison_microblock(mblock_header_1, mblock_header_2)?;
+                Ok((StacksTransactionReceipt::from_success(tx, Value::okay(Value::Bool(true)), vec![], 0), vec![]))
             },
             TransactionPayload::Coinbase(_) => {
                 // no-op; not handled here
-                Ok(0)
+                Ok((StacksTransactionReceipt::from_success(tx, Value::okay(Value::Bool(true)), vec![], 0), vec![]))
             }
         }
     }
 
-    /// Process a transaction.  Return the fee and amount of STX destroyed
-    pub fn process_transaction<'a>(clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction) -> Result<(u64, u128), Error> {
+    /// Check that a poison-microblock payload proves that a leader equivocated: the two headers
+    /// must be distinct, must claim the same parent or sequence number, and must have been signed
+    /// by the same key.  Returns the hash of the offending leader's microblock public key.
+    pub fn validate_poison_microblock(mblock_header_1: &StacksMicroblockHeader, mblock_header_2: &StacksMicroblockHeader) -> Result<Hash160, Error> {
+        if mblock_header_1.block_hash() == mblock_header_2.block_hash() {
+            return Err(Error::InvalidStacksTransaction("Poison microblock headers are identical".to_string()));
+        }
+
+        if mblock_header_1.sequence != mblock_header_2.sequence && mblock_header_1.prev_block != mblock_header_2.prev_block {
+            return Err(Error::InvalidStacksTransaction("Poison microblock headers do not share a parent or sequence number".to_string()));
+        }
+
+        let pubkey_1 = mblock_header_1.recover_pubkey()
+            .map_err(|_e| Error::InvalidStacksTransaction("Poison microblock header has an invalid signature".to_string()))?;
+        let pubkey_2 = mblock_header_2.recover_pubkey()
+            .map_err(|_e| Error::InvalidStacksTransaction("Poison microblock header has an invalid signature".to_string()))?;
+
+        let pubkey_hash = StacksBlockHeader::pubkey_hash(&pubkey_1);
+        if pubkey_hash != StacksBlockHeader::pubkey_hash(&pubkey_2) {
+            return Err(Error::InvalidStacksTransaction("Poison microblock headers were signed by different keys".to_string()));
+        }
+
+        Ok(pubkey_hash)
+    }
+
+    /// Process a transaction.  Return the fee and the transaction's receipt (which includes the
+    /// amount of STX destroyed).
+    pub fn process_transaction<'a>(clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction) -> Result<(u64, StacksTransactionReceipt), Error> {
+        StacksChainState::run_transaction(clarity_tx, tx)
+            .map(|(fee, receipt, _)| (fee, receipt))
+    }
+
+    /// Process a transaction.  Return the fee, the transaction's receipt, and the assets its
+    /// payload attempted to move.  If the transaction is invalid, none of its effects are kept.
+    fn run_transaction<'a>(clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction) -> Result<(u64, StacksTransactionReceipt, Vec<StacksTransactionEvent>), Error> {
+        // a transaction can turn out to be invalid after its payload ran (e.g. if the payer can't
+        // afford the execution fee), so only keep its writes once it's known to be valid
+        clarity_tx.connection().begin_savepoint();
+        match StacksChainState::inner_run_transaction(clarity_tx, tx) {
+            Ok(result) => {
+                clarity_tx.connection().commit_savepoint();
+                Ok(result)
+            },
+            Err(e) => {
+                clarity_tx.connection().rollback_savepoint();
+                Err(e)
+            }
+        }
+    }
+
+    fn inner_run_transaction<'a>(clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction) -> Result<(u64, StacksTransactionReceipt, Vec<StacksTransactionEvent>), Error> {
         debug!("Process transaction {}", tx.txid());
 
         StacksChainState::process_transaction_precheck(clarity_tx, tx)?;
@@ -500,12 +659,24 @@ impl StacksChainState {
             return Err(Error::InvalidStacksTransaction(msg));
         }
 
-        // pay fee
-        // TODO: don't do this here; do it when we know what the STX/compute rate will be, and then
-        // debit the account (aborting the _whole block_ if the balance would go negative)
-        let fee = StacksChainState::pay_transaction_fee(clarity_tx, tx, &payer_account)?;
-    
-        let burns = StacksChainState::process_transaction_payload(clarity_tx, tx, &origin_account)?;
+        // pay for the transaction's length up front, and for its execution once we know what it
+        // cost.  The payer must be able to afford both, or the transaction is invalid.
+        let length_fee = StacksChainState::get_tx_length_fee(tx)?;
+        StacksChainState::pay_transaction_fee(clarity_tx, &payer_account, length_fee)?;
+
+        let cost_before = clarity_tx.connection().cost_so_far();
+        let (mut receipt, asset_events) = StacksChainState::run_transaction_payload(clarity_tx, tx, &origin_account)?;
+        let cost_after = clarity_tx.connection().cost_so_far();
+
+        let execution_fee = StacksChainState::get_tx_execution_fee(tx, &cost_before, &cost_after)?;
+        if execution_fee > 0 {
+            let payer_account_after = StacksChainState::get_spending_account(clarity_tx, &payer);
+            StacksChainState::pay_transaction_fee(clarity_tx, &payer_account_after, execution_fee)?;
+        }
+        let fee = length_fee.checked_add(execution_fee).ok_or(Error::InvalidFee)?;
+
+        receipt.fee = fee;
+        receipt.execution_cost = StacksChainState::get_execution_cost_delta(&cost_before, &cost_after);
 
         // update the account nonces
         StacksChainState::update_account_nonce(clarity_tx, &origin_account);
@@ -513,7 +684,28 @@ impl StacksChainState {
             StacksChainState::update_account_nonce(clarity_tx, &payer_account);
         }
 
-        Ok((fee, burns))
+        Ok((fee, receipt, asset_events))
+    }
+
+    /// Simulate ("dry-run") a transaction on top of the given Stacks chain tip, without storing
+    /// any of its effects.  Returns the receipt it would get if it were mined in a block built on
+    /// the tip, along with the assets it would attempt to move.  Fails if the transaction could
+    /// not be mined on the tip at all (e.g. because of a bad signature or nonce), or if the tip is
+    /// not a known Stacks block.
+    pub fn simulate_transaction(&mut self, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, tx: &StacksTransaction) -> Result<TransactionSimulation, Error> {
+        if *block_hash != FIRST_STACKS_BLOCK_HASH && StacksChainState::get_anchored_block_header_info(&self.headers_db, burn_header_hash, block_hash)?.is_none() {
+            return Err(Error::NoSuchBlockError);
+        }
+
+        let mut clarity_tx = self.read_only_block_begin(burn_header_hash, block_hash)?;
+        let res = StacksChainState::run_transaction(&mut clarity_tx, tx);
+        clarity_tx.rollback_block();
+
+        let (_, receipt, asset_events) = res?;
+        Ok(TransactionSimulation {
+            receipt,
+            asset_events
+        })
     }
 }
 
@@ -581,6 +773,232 @@ pub mod test {
         assert_eq!(fee, 0);
     }
     
+    #[test]
+    fn process_poison_microblock_transaction() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "process-poison-microblock-transaction");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let addr = auth.origin().address_testnet();
+
+        let leader_privk = StacksPrivateKey::new();
+        let other_privk = StacksPrivateKey::new();
+        let leader_pubkey_hash = StacksBlockHeader::pubkey_hash(&StacksPublicKey::from_private(&leader_privk));
+
+        let make_header = |privk: &StacksPrivateKey, parent: u8, tx_merkle_root: u8| {
+            let mut header = StacksMicroblockHeader::first_unsigned(&BlockHeaderHash([parent; 32]), &Sha512Trunc256Sum([tx_merkle_root; 32]));
+            header.sign(privk).unwrap();
+            header
+        };
+
+        let header_1 = make_header(&leader_privk, 1, 2);
+        let header_2 = make_header(&leader_privk, 1, 3);
+
+        // leader equivocated
+        assert_eq!(StacksChainState::validate_poison_microblock(&header_1, &header_2).unwrap(), leader_pubkey_hash);
+
+        // not equivocations
+        assert!(StacksChainState::validate_poison_microblock(&header_1, &header_1).is_err());
+        assert!(StacksChainState::validate_poison_microblock(&header_1, &make_header(&other_privk, 1, 3)).is_err());
+
+        let mut unrelated_header = make_header(&leader_privk, 4, 3);
+        unrelated_header.sequence = 1;
+        unrelated_header.sign(&leader_privk).unwrap();
+        assert!(StacksChainState::validate_poison_microblock(&header_1, &unrelated_header).is_err());
+
+        let mut tx_poison = StacksTransaction::new(TransactionVersion::Testnet,
+                                                   auth.clone(),
+                                                   TransactionPayload::PoisonMicroblock(header_1.clone(), header_2.clone()));
+
+        tx_poison.chain_id = 0x80000000;
+        tx_poison.anchor_mode = TransactionAnchorMode::OnChainOnly;
+        tx_poison.set_fee_rate(0);
+
+        let mut signer = StacksTransactionSigner::new(&tx_poison);
+        signer.sign_origin(&privk).unwrap();
+        let signed_tx = signer.get_tx().unwrap();
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+
+        let (fee, receipt) = StacksChainState::process_transaction(&mut conn, &signed_tx).unwrap();
+        assert_eq!(fee, 0);
+        assert_eq!(receipt.stx_burned, 0);
+        assert!(receipt.is_success());
+
+        let account_after = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account_after.nonce, 1);
+
+        conn.commit_block();
+    }
+
+    #[test]
+    fn process_transaction_fees() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "process-transaction-fees");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let addr = auth.origin().address_testnet();
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+
+        let mut tx_stx_transfer = StacksTransaction::new(TransactionVersion::Testnet,
+                                                         auth.clone(),
+                                                         TransactionPayload::TokenTransfer(recv_addr.clone(), 123, TokenTransferMemo([0u8; 34])));
+
+        tx_stx_transfer.chain_id = 0x80000000;
+        tx_stx_transfer.post_condition_mode = TransactionPostConditionMode::Allow;
+        tx_stx_transfer.set_fee_rate(2);
+
+        let mut signer = StacksTransactionSigner::new(&tx_stx_transfer);
+        signer.sign_origin(&privk).unwrap();
+        let signed_transfer_tx = signer.get_tx().unwrap();
+
+        let mut transfer_tx_bytes = vec![];
+        signed_transfer_tx.consensus_serialize(&mut transfer_tx_bytes).unwrap();
+        let transfer_length_fee = 2 * (transfer_tx_bytes.len() as u64);
+        assert_eq!(StacksChainState::get_tx_length_fee(&signed_transfer_tx).unwrap(), transfer_length_fee);
+
+        let contract = "(define-data-var bar int 0) (define-public (set-bar (x int)) (begin (var-set bar x) (ok x)))";
+        let mut tx_contract = StacksTransaction::new(TransactionVersion::Testnet,
+                                                     auth.clone(),
+                                                     TransactionPayload::new_smart_contract(&"hello-world".to_string(), &contract.to_string()).unwrap());
+
+        tx_contract.chain_id = 0x80000000;
+        tx_contract.set_origin_nonce(1);
+        tx_contract.set_fee_rate(1);
+
+        let mut signer = StacksTransactionSigner::new(&tx_contract);
+        signer.sign_origin(&privk).unwrap();
+        let signed_contract_tx = signer.get_tx().unwrap();
+        let contract_length_fee = StacksChainState::get_tx_length_fee(&signed_contract_tx).unwrap();
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+
+        // can't afford the length fee
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), transfer_length_fee - 1);
+        match StacksChainState::process_transaction(&mut conn, &signed_transfer_tx) {
+            Err(Error::InvalidFee) => {},
+            res => panic!("Expected InvalidFee, got {:?}", &res)
+        }
+        conn.rollback_block();
+
+        // can afford the length fee, but not the execution fee, so nothing the payload did is kept
+        let mut tx_contract_first = tx_contract.clone();
+        tx_contract_first.set_origin_nonce(0);
+
+        let mut signer = StacksTransactionSigner::new(&tx_contract_first);
+        signer.sign_origin(&privk).unwrap();
+        let signed_contract_first_tx = signer.get_tx().unwrap();
+        let contract_first_length_fee = StacksChainState::get_tx_length_fee(&signed_contract_first_tx).unwrap();
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), contract_first_length_fee);
+        let cost_before = conn.connection().cost_so_far();
+        match StacksChainState::process_transaction(&mut conn, &signed_contract_first_tx) {
+            Err(Error::InvalidFee) => {},
+            res => panic!("Expected InvalidFee, got {:?}", &res)
+        }
+        assert_eq!(conn.connection().cost_so_far(), cost_before);
+
+        let contract_id = QualifiedContractIdentifier::new(StandardPrincipalData::from(addr.clone()), ContractName::try_from("hello-world".to_string()).unwrap());
+        assert!(StacksChainState::get_contract(&mut conn, &contract_id).unwrap().is_none());
+
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.stx_balance, contract_first_length_fee as u128);
+        assert_eq!(account.nonce, 0);
+        conn.rollback_block();
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 1000000);
+
+        // an STX transfer pays for its length (and whatever execution it needed)
+        let cost_before = conn.connection().cost_so_far();
+        let (transfer_fee, _) = StacksChainState::process_transaction(&mut conn, &signed_transfer_tx).unwrap();
+        let cost_after = conn.connection().cost_so_far();
+
+        let transfer_execution_fee = StacksChainState::get_tx_execution_fee(&signed_transfer_tx, &cost_before, &cost_after).unwrap();
+        assert_eq!(transfer_fee, transfer_length_fee + transfer_execution_fee);
+
+        let account_after = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account_after.stx_balance, 1000000 - 123 - (transfer_fee as u128));
+
+        // publishing a contract writes data, so it also pays for execution
+        let (contract_fee, _) = StacksChainState::process_transaction(&mut conn, &signed_contract_tx).unwrap();
+        assert!(contract_fee > contract_length_fee);
+
+        let account_after_contract = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account_after_contract.stx_balance, account_after.stx_balance - (contract_fee as u128));
+        assert_eq!(account_after_contract.nonce, 2);
+
+        conn.commit_block();
+    }
+
+    #[test]
+    fn process_token_transfer_stx_transaction_locked() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "process-token-transfer-stx-transaction-locked");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let addr = auth.origin().address_testnet();
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+
+        let mut signed_txs = vec![];
+        for (nonce, amount) in [(0, 101), (0, 100)].iter() {
+            let mut tx_stx_transfer = StacksTransaction::new(TransactionVersion::Testnet,
+                                                             auth.clone(),
+                                                             TransactionPayload::TokenTransfer(recv_addr.clone(), *amount, TokenTransferMemo([0u8; 34])));
+
+            tx_stx_transfer.chain_id = 0x80000000;
+            tx_stx_transfer.post_condition_mode = TransactionPostConditionMode::Allow;
+            tx_stx_transfer.set_fee_rate(0);
+            tx_stx_transfer.set_origin_nonce(*nonce);
+
+            let mut signer = StacksTransactionSigner::new(&tx_stx_transfer);
+            signer.sign_origin(&privk).unwrap();
+            signed_txs.push(signer.get_tx().unwrap());
+        }
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 223);
+
+        // can't lock more than the account has, or in the past
+        assert!(StacksChainState::account_lock(&mut conn, &addr.to_account_principal(), 224, 1000).is_err());
+        assert!(StacksChainState::account_lock(&mut conn, &addr.to_account_principal(), 123, 0).is_err());
+
+        StacksChainState::account_lock(&mut conn, &addr.to_account_principal(), 123, 1000).unwrap();
+
+        // can't lock twice
+        assert!(StacksChainState::account_lock(&mut conn, &addr.to_account_principal(), 1, 1000).is_err());
+
+        // locked STX still count toward the balance
+        let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account.stx_balance, 223);
+        assert_eq!(account.stx_locked, 123);
+        assert_eq!(account.stx_unlock_height, 1000);
+
+        // can't spend locked STX
+        let res = StacksChainState::process_transaction(&mut conn, &signed_txs[0]);
+        assert!(res.is_err());
+
+        let account_after = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account_after.nonce, 0);
+        assert_eq!(account_after.stx_balance, 223);
+
+        // can spend unlocked STX
+        let (fee, _) = StacksChainState::process_transaction(&mut conn, &signed_txs[1]).unwrap();
+        assert_eq!(fee, 0);
+
+        let account_after = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
+        assert_eq!(account_after.nonce, 1);
+        assert_eq!(account_after.stx_balance, 123);
+        assert_eq!(account_after.stx_locked, 123);
+
+        let recv_account_after = StacksChainState::get_account(&mut conn, &recv_addr.to_account_principal());
+        assert_eq!(recv_account_after.stx_balance, 100);
+
+        conn.commit_block();
+    }
+    
     #[test]
     fn process_token_transfer_stx_transaction_invalid() {
         let mut chainstate = instantiate_chainstate(false, 0x80000000, "process-token-transfer-stx-transaction-invalid");
@@ -1124,6 +1542,192 @@ pub mod test {
         assert_eq!(var_res, Some(Value::Int(3)));
     }
     
+    #[test]
+    fn process_transaction_receipts() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "process-transaction-receipts");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let addr = auth.origin().address_testnet();
+
+        let contract = format!("
+        (define-fungible-token stackaroos)
+        (begin (ft-mint? stackaroos u100 {}))
+        (define-public (send (amount uint) (recipient principal))
+          (ft-transfer? stackaroos amount tx-sender recipient))
+        (define-public (pay (amount uint) (recipient principal))
+          (stx-transfer? amount tx-sender recipient))
+        (define-public (fail) (err u1))
+        (define-public (divide (x int) (y int)) (ok (/ x y)))", &addr.to_account_principal());
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+
+        let sign = |payload: TransactionPayload, nonce: u64, post_condition_mode: TransactionPostConditionMode| {
+            let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth.clone(), payload);
+            tx.chain_id = 0x80000000;
+            tx.post_condition_mode = post_condition_mode;
+            tx.auth.set_origin_nonce(nonce);
+            tx.set_fee_rate(0);
+
+            let mut signer = StacksTransactionSigner::new(&tx);
+            signer.sign_origin(&privk).unwrap();
+            signer.get_tx().unwrap()
+        };
+
+        let contract_call = |function_name: &str, args: Vec<Value>| {
+            TransactionPayload::new_contract_call(addr.clone(), "hello-world", function_name, args).unwrap()
+        };
+
+        let tx_contract = sign(TransactionPayload::new_smart_contract(&"hello-world".to_string(), &contract).unwrap(), 0, TransactionPostConditionMode::Allow);
+        let tx_send = sign(contract_call("send", vec![Value::UInt(10), Value::Principal(recv_addr.to_account_principal())]), 1, TransactionPostConditionMode::Allow);
+        let tx_fail = sign(contract_call("fail", vec![]), 2, TransactionPostConditionMode::Allow);
+        let tx_send_denied = sign(contract_call("send", vec![Value::UInt(10), Value::Principal(recv_addr.to_account_principal())]), 3, TransactionPostConditionMode::Deny);
+        let tx_divide_by_zero = sign(contract_call("divide", vec![Value::Int(1), Value::Int(0)]), 4, TransactionPostConditionMode::Allow);
+        let tx_stx_transfer = sign(TransactionPayload::TokenTransfer(recv_addr.clone(), 123, TokenTransferMemo([0u8; 34])), 5, TransactionPostConditionMode::Allow);
+        let tx_pay = sign(contract_call("pay", vec![Value::UInt(100), Value::Principal(recv_addr.to_account_principal())]), 6, TransactionPostConditionMode::Allow);
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([1u8; 32]), &BlockHeaderHash([1u8; 32]));
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 223);
+
+        let contract_id = QualifiedContractIdentifier::new(StandardPrincipalData::from(addr.clone()), ContractName::from("hello-world"));
+        let stackaroos = AssetIdentifier { contract_identifier: contract_id.clone(), asset_name: ClarityName::try_from("stackaroos".to_string()).unwrap() };
+
+        // contract deployed
+        let (_, receipt) = StacksChainState::process_transaction(&mut conn, &tx_contract).unwrap();
+        assert_eq!(receipt.txid, tx_contract.txid());
+        assert!(receipt.is_success());
+        assert_eq!(receipt.result, Value::okay(Value::Bool(true)));
+        assert!(receipt.execution_cost.runtime > 0);
+        assert!(receipt.execution_cost.write_length > 0);
+        assert_eq!(receipt.events, vec![StacksTransactionEvent::FungibleTokenMint { recipient: addr.to_account_principal(), asset: stackaroos.clone(), amount: 100 }]);
+
+        // tokens moved
+        let (_, receipt) = StacksChainState::process_transaction(&mut conn, &tx_send).unwrap();
+        assert!(receipt.is_success());
+        assert_eq!(receipt.result, Value::okay(Value::Bool(true)));
+        assert!(!receipt.post_condition_aborted);
+        assert_eq!(receipt.events, vec![StacksTransactionEvent::FungibleTokenTransfer { sender: addr.to_account_principal(), recipient: recv_addr.to_account_principal(), asset: stackaroos.clone(), amount: 10 }]);
+        assert!(receipt.execution_cost.runtime > 0);
+
+        // contract call returned an error
+        let (_, receipt) = StacksChainState::process_transaction(&mut conn, &tx_fail).unwrap();
+        assert_eq!(receipt.status, TransactionReceiptStatus::Abort);
+        assert_eq!(receipt.result, Value::error(Value::UInt(1)));
+        assert!(!receipt.post_condition_aborted);
+        assert_eq!(receipt.events, vec![]);
+
+        // post-conditions forbade the transfer
+        let (_, receipt) = StacksChainState::process_transaction(&mut conn, &tx_send_denied).unwrap();
+        assert_eq!(receipt.status, TransactionReceiptStatus::Abort);
+        assert!(receipt.post_condition_aborted);
+        assert_eq!(receipt.events, vec![]);
+        assert_eq!(StacksChainState::get_account_ft(&mut conn, &contract_id, "stackaroos", &recv_addr.to_account_principal()).unwrap(), 10);
+
+        // runtime error
+        let (_, receipt) = StacksChainState::process_transaction(&mut conn, &tx_divide_by_zero).unwrap();
+        assert_eq!(receipt.status, TransactionReceiptStatus::Abort);
+        assert_eq!(receipt.result, Value::none());
+        assert!(!receipt.post_condition_aborted);
+
+        // STX transfer
+        let (_, receipt) = StacksChainState::process_transaction(&mut conn, &tx_stx_transfer).unwrap();
+        assert!(receipt.is_success());
+        assert_eq!(receipt.events, vec![StacksTransactionEvent::STXTransfer { sender: addr.to_account_principal(), recipient: recv_addr.to_account_principal(), amount: 123 }]);
+
+        // STX sent by a contract call
+        let (_, receipt) = StacksChainState::process_transaction(&mut conn, &tx_pay).unwrap();
+        assert!(receipt.is_success());
+        assert_eq!(receipt.events, vec![StacksTransactionEvent::STXTransfer { sender: addr.to_account_principal(), recipient: recv_addr.to_account_principal(), amount: 100 }]);
+
+        conn.commit_block();
+    }
+
+    #[test]
+    fn simulate_transaction() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "simulate-transaction");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let addr = auth.origin().address_testnet();
+
+        let contract = format!("
+        (define-fungible-token stackaroos)
+        (begin (ft-mint? stackaroos u100 {}))
+        (define-public (send (amount uint) (recipient principal))
+          (ft-transfer? stackaroos amount tx-sender recipient))", &addr.to_account_principal());
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+
+        let sign = |payload: TransactionPayload, nonce: u64, post_condition_mode: TransactionPostConditionMode| {
+            let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth.clone(), payload);
+            tx.chain_id = 0x80000000;
+            tx.post_condition_mode = post_condition_mode;
+            tx.auth.set_origin_nonce(nonce);
+            tx.set_fee_rate(1);
+
+            let mut signer = StacksTransactionSigner::new(&tx);
+            signer.sign_origin(&privk).unwrap();
+            signer.get_tx().unwrap()
+        };
+
+        let send = TransactionPayload::new_contract_call(addr.clone(), "hello-world", "send", vec![Value::UInt(10), Value::Principal(recv_addr.to_account_principal())]).unwrap();
+        let tx_contract = sign(TransactionPayload::new_smart_contract(&"hello-world".to_string(), &contract).unwrap(), 0, TransactionPostConditionMode::Allow);
+        let tx_send = sign(send.clone(), 1, TransactionPostConditionMode::Allow);
+        let tx_send_denied = sign(send.clone(), 1, TransactionPostConditionMode::Deny);
+        let tx_bad_nonce = sign(send.clone(), 2, TransactionPostConditionMode::Allow);
+
+        // the tip must be a known block
+        let tip = make_dummy_child_tip(&mut chainstate, &StacksHeaderInfo::genesis(), 0);
+        let burn_header_hash = tip.burn_header_hash.clone();
+        let block_hash = tip.anchored_header.block_hash();
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &burn_header_hash, &block_hash);
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 1000000);
+        StacksChainState::process_transaction(&mut conn, &tx_contract).unwrap();
+        conn.commit_block();
+
+        let contract_id = QualifiedContractIdentifier::new(StandardPrincipalData::from(addr.clone()), ContractName::from("hello-world"));
+        let stackaroos = AssetIdentifier { contract_identifier: contract_id.clone(), asset_name: ClarityName::try_from("stackaroos".to_string()).unwrap() };
+        let account_before = chainstate.get_account_at_block(&burn_header_hash, &block_hash, &addr.to_account_principal()).unwrap();
+
+        // would succeed, and move tokens
+        let simulation = chainstate.simulate_transaction(&burn_header_hash, &block_hash, &tx_send).unwrap();
+        assert!(simulation.receipt.is_success());
+        assert!(simulation.post_conditions_passed());
+        assert_eq!(simulation.receipt.result, Value::okay(Value::Bool(true)));
+        assert!(simulation.receipt.fee > 0);
+        assert!(simulation.receipt.execution_cost.runtime > 0);
+        assert_eq!(simulation.asset_events, vec![StacksTransactionEvent::FungibleTokenTransfer { sender: addr.to_account_principal(), recipient: recv_addr.to_account_principal(), asset: stackaroos.clone(), amount: 10 }]);
+        assert_eq!(simulation.receipt.events, simulation.asset_events);
+
+        // would be aborted by its post-conditions, but still reports what it tried to move
+        let simulation = chainstate.simulate_transaction(&burn_header_hash, &block_hash, &tx_send_denied).unwrap();
+        assert_eq!(simulation.receipt.status, TransactionReceiptStatus::Abort);
+        assert!(!simulation.post_conditions_passed());
+        assert_eq!(simulation.receipt.events, vec![]);
+        assert_eq!(simulation.asset_events, vec![StacksTransactionEvent::FungibleTokenTransfer { sender: addr.to_account_principal(), recipient: recv_addr.to_account_principal(), asset: stackaroos.clone(), amount: 10 }]);
+
+        let json = simulation.to_json();
+        assert_eq!(json["status"], "abort");
+        assert_eq!(json["post_conditions_passed"], false);
+        assert_eq!(json["fee"], simulation.receipt.fee);
+
+        // could not be mined at all
+        assert!(chainstate.simulate_transaction(&burn_header_hash, &block_hash, &tx_bad_nonce).is_err());
+
+        // unknown tip
+        match chainstate.simulate_transaction(&BurnchainHeaderHash([2u8; 32]), &BlockHeaderHash([2u8; 32]), &tx_send) {
+            Err(Error::NoSuchBlockError) => {},
+            res => panic!("Expected no such block, got {:?}", &res)
+        }
+
+        // nothing was stored
+        let account_after = chainstate.get_account_at_block(&burn_header_hash, &block_hash, &addr.to_account_principal()).unwrap();
+        assert_eq!(account_before, account_after);
+
+        let mut conn = chainstate.block_begin(&burn_header_hash, &block_hash, &MINER_BLOCK_BURN_HEADER_HASH, &MINER_BLOCK_HEADER_HASH);
+        assert_eq!(StacksChainState::get_account_ft(&mut conn, &contract_id, "stackaroos", &recv_addr.to_account_principal()).unwrap(), 0);
+        conn.rollback_block();
+    }
+
     #[test]
     fn process_smart_contract_contract_call_runtime_error() {
         let contract = "
@@ -2250,7 +2854,9 @@ pub mod test {
         StacksAccount {
             principal: principal.clone(),
             nonce: nonce,
-            stx_balance: balance
+            stx_balance: balance,
+            stx_locked: 0,
+            stx_unlock_height: 0
         }
     }
 
@@ -2962,5 +3568,88 @@ pub mod test {
         }
     }
 
-    // TODO: test poison microblock
+    #[test]
+    fn process_contract_owned_assets() {
+        let contract = "
+        (define-fungible-token stackaroos)
+        (define-non-fungible-token names (buff 50))
+        (begin
+            (unwrap-panic (ft-mint? stackaroos u100 (as-contract tx-sender)))
+            (unwrap-panic (nft-mint? names 0x616263 (as-contract tx-sender)))
+            (unwrap-panic (nft-mint? names 0x646566 (as-contract tx-sender))))
+        (define-public (give-all (recipient principal))
+          (as-contract
+            (begin (unwrap-panic (ft-transfer? stackaroos u100 tx-sender recipient))
+                   (unwrap-panic (nft-transfer? names 0x616263 tx-sender recipient))
+                   (ok 'true))))";
+
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "process-contract-owned-assets");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let addr = auth.origin().address_testnet();
+        let recv_principal = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) }.to_account_principal();
+
+        let contract_id = QualifiedContractIdentifier::new(StandardPrincipalData::from(addr.clone()), ContractName::from("hello-world"));
+        let contract_principal = PrincipalData::Contract(contract_id.clone());
+
+        let mut tx_contract = StacksTransaction::new(TransactionVersion::Testnet,
+                                                     auth.clone(),
+                                                     TransactionPayload::new_smart_contract(&"hello-world".to_string(), &contract.to_string()).unwrap());
+
+        tx_contract.chain_id = 0x80000000;
+        tx_contract.set_fee_rate(0);
+
+        let mut signer = StacksTransactionSigner::new(&tx_contract);
+        signer.sign_origin(&privk).unwrap();
+        let signed_contract_tx = signer.get_tx().unwrap();
+
+        let mut tx_contract_call = StacksTransaction::new(TransactionVersion::Testnet,
+                                                          auth.clone(),
+                                                          TransactionPayload::new_contract_call(addr.clone(), "hello-world", "give-all", vec![Value::Principal(recv_principal.clone())]).unwrap());
+
+        tx_contract_call.chain_id = 0x80000000;
+        tx_contract_call.set_fee_rate(0);
+        tx_contract_call.set_origin_nonce(1);
+        tx_contract_call.post_condition_mode = TransactionPostConditionMode::Allow;
+
+        let mut signer = StacksTransactionSigner::new(&tx_contract_call);
+        signer.sign_origin(&privk).unwrap();
+        let signed_contract_call_tx = signer.get_tx().unwrap();
+
+        let name_abc = Value::buff_from(vec![0x61, 0x62, 0x63]).unwrap();
+        let name_def = Value::buff_from(vec![0x64, 0x65, 0x66]).unwrap();
+
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([2u8; 32]), &BlockHeaderHash([2u8; 32]));
+
+        let (_fee, _) = StacksChainState::process_transaction(&mut conn, &signed_contract_tx).unwrap();
+
+        // contract holds everything it minted
+        let assets = StacksChainState::get_account_assets(&mut conn, &contract_principal).unwrap();
+        assert_eq!(assets.fungible_tokens, vec![(contract_id.clone(), "stackaroos".to_string(), 100)]);
+        assert_eq!(assets.non_fungible_tokens, vec![(contract_id.clone(), "names".to_string(), name_abc.clone()),
+                                                    (contract_id.clone(), "names".to_string(), name_def.clone())]);
+
+        let assets = StacksChainState::get_account_assets(&mut conn, &recv_principal).unwrap();
+        assert_eq!(assets.fungible_tokens, vec![]);
+        assert_eq!(assets.non_fungible_tokens, vec![]);
+
+        let (_fee, _) = StacksChainState::process_transaction(&mut conn, &signed_contract_call_tx).unwrap();
+
+        // drained token balances and transferred names are no longer reported for the contract
+        let assets = StacksChainState::get_account_assets(&mut conn, &contract_principal).unwrap();
+        assert_eq!(assets.fungible_tokens, vec![]);
+        assert_eq!(assets.non_fungible_tokens, vec![(contract_id.clone(), "names".to_string(), name_def.clone())]);
+
+        let assets = StacksChainState::get_account_assets(&mut conn, &recv_principal).unwrap();
+        assert_eq!(assets.fungible_tokens, vec![(contract_id.clone(), "stackaroos".to_string(), 100)]);
+        assert_eq!(assets.non_fungible_tokens, vec![(contract_id.clone(), "names".to_string(), name_abc.clone())]);
+
+        conn.commit_block();
+
+        // same answer when queried at the committed block
+        let assets = chainstate.get_account_assets_at_block(&BurnchainHeaderHash([2u8; 32]), &BlockHeaderHash([2u8; 32]), &recv_principal).unwrap();
+        assert_eq!(assets.fungible_tokens, vec![(contract_id.clone(), "stackaroos".to_string(), 100)]);
+        assert_eq!(assets.non_fungible_tokens, vec![(contract_id.clone(), "names".to_string(), name_abc.clone())]);
+    }
 }
@@ -0,0 +1,242 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use rusqlite::Row;
+use rusqlite::Connection;
+use rusqlite::types::ToSql;
+
+use burnchains::Txid;
+use burnchains::BurnchainHeaderHash;
+
+use chainstate::burn::BlockHeaderHash;
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+use chainstate::stacks::db::*;
+
+use util::db::{
+    FromRow,
+    FromColumn,
+    query_rows
+};
+use util::db::Error as db_error;
+
+/// Where a transaction was confirmed.  A transaction can be confirmed once in each fork, so there
+/// can be many of these for the same txid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StacksTransactionLocation {
+    pub txid: Txid,
+    /// anchored block that confirmed this transaction, either directly or through the microblock
+    /// stream it builds on
+    pub index_block_hash: BlockHeaderHash,
+    pub burn_header_hash: BurnchainHeaderHash,
+    pub block_hash: BlockHeaderHash,
+    pub block_height: u64,
+    /// microblock that contained this transaction, if it was streamed
+    pub microblock_hash: Option<BlockHeaderHash>,
+    pub microblock_sequence: Option<u16>,
+    /// position of this transaction in its (micro)block
+    pub tx_index: u32
+}
+
+impl FromRow<StacksTransactionLocation> for StacksTransactionLocation {
+    fn from_row<'a>(row: &'a Row) -> Result<StacksTransactionLocation, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let index_block_hash = BlockHeaderHash::from_column(row, "index_block_hash")?;
+        let burn_header_hash = BurnchainHeaderHash::from_column(row, "burn_header_hash")?;
+        let block_hash = BlockHeaderHash::from_column(row, "block_hash")?;
+        let block_height_i64 : i64 = row.get("block_height");
+        let microblock_hash_hex : Option<String> = row.get("microblock_hash");
+        let microblock_sequence : Option<u16> = row.get("microblock_sequence");
+        let tx_index_i64 : i64 = row.get("tx_index");
+
+        if block_height_i64 < 0 || tx_index_i64 < 0 || tx_index_i64 > (u32::max_value() as i64) {
+            return Err(db_error::ParseError);
+        }
+
+        let microblock_hash = match microblock_hash_hex {
+            Some(hex) => Some(BlockHeaderHash::from_hex(&hex).map_err(|_e| db_error::ParseError)?),
+            None => None
+        };
+
+        Ok(StacksTransactionLocation {
+            txid,
+            index_block_hash,
+            burn_header_hash,
+            block_hash,
+            block_height: block_height_i64 as u64,
+            microblock_hash,
+            microblock_sequence,
+            tx_index: tx_index_i64 as u32
+        })
+    }
+}
+
+impl StacksChainState {
+    /// Index the transactions confirmed by a newly-processed block, including those in the
+    /// microblock stream it confirms.
+    pub fn insert_transaction_index<'a>(tx: &mut StacksDBTx<'a>, tip_info: &StacksHeaderInfo, block: &StacksBlock, microblocks: &Vec<StacksMicroblock>) -> Result<(), Error> {
+        let index_block_hash = tip_info.index_block_hash();
+        let block_hash = block.block_hash();
+        let block_height = tip_info.block_height;
+        assert!(block_height < i64::max_value() as u64);
+
+        let sql = "INSERT INTO transaction_index (txid,index_block_hash,burn_header_hash,block_hash,block_height,microblock_hash,microblock_sequence,tx_index) \
+                   VALUES (?1,?2,?3,?4,?5,?6,?7,?8)";
+
+        for microblock in microblocks.iter() {
+            let microblock_hash = Some(microblock.block_hash());
+            let microblock_sequence = Some(microblock.header.sequence);
+            for (i, stacks_tx) in microblock.txs.iter().enumerate() {
+                let args: &[&dyn ToSql] = &[&stacks_tx.txid(), &index_block_hash, &tip_info.burn_header_hash, &block_hash, &(block_height as i64),
+                                            &microblock_hash, &microblock_sequence, &(i as u32)];
+                tx.execute(sql, args)
+                    .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            }
+        }
+
+        let no_microblock_hash : Option<BlockHeaderHash> = None;
+        let no_microblock_sequence : Option<u16> = None;
+        for (i, stacks_tx) in block.txs.iter().enumerate() {
+            let args: &[&dyn ToSql] = &[&stacks_tx.txid(), &index_block_hash, &tip_info.burn_header_hash, &block_hash, &(block_height as i64),
+                                        &no_microblock_hash, &no_microblock_sequence, &(i as u32)];
+            tx.execute(sql, args)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Get every place a transaction was confirmed, across all forks.
+    pub fn get_transaction_locations(conn: &Connection, txid: &Txid) -> Result<Vec<StacksTransactionLocation>, Error> {
+        let sql = "SELECT * FROM transaction_index WHERE txid = ?1 ORDER BY block_height, index_block_hash".to_string();
+        let args: &[&dyn ToSql] = &[txid];
+        query_rows::<StacksTransactionLocation, _>(conn, &sql, args).map_err(Error::DBError)
+    }
+
+    /// Find where a transaction was confirmed in the fork ending at the given chain tip (identified
+    /// by its index block hash).  Returns None if the transaction is not confirmed in that fork,
+    /// even if it is confirmed in another.
+    pub fn get_transaction_location_in_fork<'a>(tx: &mut StacksDBTx<'a>, txid: &Txid, tip_index_block_hash: &BlockHeaderHash) -> Result<Option<StacksTransactionLocation>, Error> {
+        let locations = StacksChainState::get_transaction_locations(tx, txid)?;
+        for location in locations.into_iter() {
+            if tx.get_ancestor_block_height(&location.index_block_hash, tip_index_block_hash).map_err(Error::DBError)?.is_some() {
+                return Ok(Some(location));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::db::test::*;
+    use util::hash::Hash160;
+
+    fn make_tx(privk: &StacksPrivateKey, nonce: u64) -> StacksTransaction {
+        let auth = TransactionAuth::from_p2pkh(privk).unwrap();
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+        let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth, TransactionPayload::TokenTransfer(recv_addr, 1, TokenTransferMemo([0u8; 34])));
+        tx.chain_id = 0x80000000;
+        tx.auth.set_origin_nonce(nonce);
+        tx
+    }
+
+    fn advance_tip(chainstate: &mut StacksChainState, parent: &StacksHeaderInfo, fork_seed: u8, block_txs: Vec<StacksTransaction>, microblocks: Vec<StacksMicroblock>) -> StacksHeaderInfo {
+        let tip = make_dummy_child_tip(chainstate, parent, fork_seed);
+        let block = StacksBlock { header: tip.anchored_header.clone(), txs: block_txs };
+
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+        StacksChainState::insert_transaction_index(&mut tx, &tip, &block, &microblocks).unwrap();
+        tx.commit().unwrap();
+        tip
+    }
+
+    #[test]
+    fn transaction_index_forks() {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "transaction-index-forks");
+        let privk = StacksPrivateKey::new();
+        let microblock_privk = StacksPrivateKey::new();
+
+        let tx_0 = make_tx(&privk, 0);
+        let tx_1 = make_tx(&privk, 1);
+        let tx_2 = make_tx(&privk, 2);
+        let tx_3 = make_tx(&privk, 3);
+        let tx_4 = make_tx(&privk, 4);
+
+        // common ancestor confirms tx_0
+        let ancestor = advance_tip(&mut chainstate, &StacksHeaderInfo::genesis(), 0, vec![tx_0.clone()], vec![]);
+
+        // fork A confirms tx_4 and tx_1 in a microblock, and tx_2 in its anchored block
+        let mut microblock = StacksMicroblock::first_unsigned(&ancestor.anchored_header.block_hash(), vec![tx_4.clone(), tx_1.clone()]);
+        microblock.sign(&microblock_privk).unwrap();
+        let tip_a = advance_tip(&mut chainstate, &ancestor, 1, vec![tx_2.clone()], vec![microblock.clone()]);
+
+        // fork B confirms tx_1 in its first block, and tx_3 in its second
+        let tip_b_1 = advance_tip(&mut chainstate, &ancestor, 2, vec![tx_1.clone()], vec![]);
+        let tip_b_2 = advance_tip(&mut chainstate, &tip_b_1, 2, vec![tx_3.clone()], vec![]);
+
+        // tx_1 is confirmed in both forks
+        let locations = StacksChainState::get_transaction_locations(&chainstate.headers_db, &tx_1.txid()).unwrap();
+        assert_eq!(locations.len(), 2);
+
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+
+        // the common ancestor's transaction is in both forks
+        for tip in [&ancestor, &tip_a, &tip_b_1, &tip_b_2].iter() {
+            let location = StacksChainState::get_transaction_location_in_fork(&mut tx, &tx_0.txid(), &tip.index_block_hash()).unwrap().unwrap();
+            assert_eq!(location.index_block_hash, ancestor.index_block_hash());
+            assert_eq!(location.block_height, ancestor.block_height);
+            assert_eq!(location.tx_index, 0);
+            assert_eq!(location.microblock_hash, None);
+        }
+
+        // streamed transaction in fork A
+        let location = StacksChainState::get_transaction_location_in_fork(&mut tx, &tx_1.txid(), &tip_a.index_block_hash()).unwrap().unwrap();
+        assert_eq!(location, StacksTransactionLocation {
+            txid: tx_1.txid(),
+            index_block_hash: tip_a.index_block_hash(),
+            burn_header_hash: tip_a.burn_header_hash.clone(),
+            block_hash: tip_a.anchored_header.block_hash(),
+            block_height: tip_a.block_height,
+            microblock_hash: Some(microblock.block_hash()),
+            microblock_sequence: Some(0),
+            tx_index: 1
+        });
+
+        // same transaction, anchored in fork B
+        let location = StacksChainState::get_transaction_location_in_fork(&mut tx, &tx_1.txid(), &tip_b_2.index_block_hash()).unwrap().unwrap();
+        assert_eq!(location.index_block_hash, tip_b_1.index_block_hash());
+        assert_eq!(location.microblock_hash, None);
+        assert_eq!(location.microblock_sequence, None);
+        assert_eq!(location.tx_index, 0);
+
+        // not yet confirmed as of the common ancestor
+        assert_eq!(StacksChainState::get_transaction_location_in_fork(&mut tx, &tx_1.txid(), &ancestor.index_block_hash()).unwrap(), None);
+
+        // fork-specific transactions
+        assert!(StacksChainState::get_transaction_location_in_fork(&mut tx, &tx_2.txid(), &tip_a.index_block_hash()).unwrap().is_some());
+        assert_eq!(StacksChainState::get_transaction_location_in_fork(&mut tx, &tx_2.txid(), &tip_b_2.index_block_hash()).unwrap(), None);
+        assert!(StacksChainState::get_transaction_location_in_fork(&mut tx, &tx_3.txid(), &tip_b_2.index_block_hash()).unwrap().is_some());
+        assert_eq!(StacksChainState::get_transaction_location_in_fork(&mut tx, &tx_3.txid(), &tip_b_1.index_block_hash()).unwrap(), None);
+        assert_eq!(StacksChainState::get_transaction_location_in_fork(&mut tx, &tx_3.txid(), &tip_a.index_block_hash()).unwrap(), None);
+
+        // unknown transaction
+        assert_eq!(StacksChainState::get_transaction_location_in_fork(&mut tx, &Txid([0u8; 32]), &tip_a.index_block_hash()).unwrap(), None);
+    }
+}
@@ -34,6 +34,7 @@ use std::path::{
 
 use std::marker::PhantomData;
 use std::fs;
+use std::collections::HashSet;
 
 use sha2::Digest;
 
@@ -852,6 +853,16 @@ impl MARF {
         root_hash_res
     }
 
+    /// Discard the trie nodes that only the tries of the given blocks still need.  The root
+    /// hashes of all tries are kept, but the pruned tries can no longer be read.
+    /// Returns the number of bytes of node data discarded.
+    pub fn prune(&mut self, pruned: &HashSet<BlockHeaderHash>, already_pruned: &HashSet<BlockHeaderHash>) -> Result<u64, Error> {
+        if self.open_chain_tip.is_some() {
+            return Err(Error::InProgressError);
+        }
+        self.storage.prune_tries(pruned, already_pruned)
+    }
+
     /// Get the path to a block's trie
     pub fn get_block_path(&self, block_hash: &BlockHeaderHash) -> PathBuf {
         TrieFileStorage::block_path(&self.storage.dir_path, block_hash)
@@ -911,7 +922,203 @@ mod test {
 
         merkle_test_marf(marf.borrow_storage_backend(), &block_header, &path_bytes.to_vec(), &[99; 40].to_vec(), None);
     }
+
+    #[test]
+    fn marf_drop_then_commit_to_retargeted_block() {
+        let filename = "/tmp/rust_marf_drop_then_commit_to_retargeted_block";
+
+        let f = TrieFileStorage::new_overwrite(filename).unwrap();
+
+        let block_header_1 = BlockHeaderHash::from_bytes(&[0u8; 32]).unwrap();
+        let miner_header = BlockHeaderHash::from_bytes(&[1u8; 32]).unwrap();
+        let block_header_2 = BlockHeaderHash::from_bytes(&[2u8; 32]).unwrap();
+
+        let mut marf = MARF::from_storage(f);
+        marf.begin(&TrieFileStorage::block_sentinel(), &block_header_1).unwrap();
+        marf.insert("foo", MARFValue::from(1)).unwrap();
+        marf.commit().unwrap();
+
+        let num_blocks = marf.borrow_storage_backend().num_blocks();
+
+        // open and drop the miner's block a few times
+        for i in 0..3 {
+            marf.begin(&block_header_1, &miner_header).unwrap();
+            marf.insert("foo", MARFValue::from(i + 10)).unwrap();
+            marf.drop_current();
+            assert_eq!(marf.borrow_storage_backend().num_blocks(), num_blocks);
+        }
+
+        // the dropped blocks don't interfere with committing it under its real hash
+        marf.begin(&block_header_1, &miner_header).unwrap();
+        marf.insert("foo", MARFValue::from(2)).unwrap();
+        marf.commit_to(&block_header_2).unwrap();
+
+        assert_eq!(marf.get(&block_header_1, "foo").unwrap(), Some(MARFValue::from(1)));
+        assert_eq!(marf.get(&block_header_2, "foo").unwrap(), Some(MARFValue::from(2)));
+
+        // block identifiers are consistent with what's on disk
+        let f = TrieFileStorage::new(filename).unwrap();
+        let mut marf = MARF::from_storage(f);
+        assert_eq!(marf.get(&block_header_1, "foo").unwrap(), Some(MARFValue::from(1)));
+        assert_eq!(marf.get(&block_header_2, "foo").unwrap(), Some(MARFValue::from(2)));
+    }
     
+    #[test]
+    fn marf_prune_tries() {
+        let filename = "/tmp/rust_marf_prune_tries";
+
+        let f = TrieFileStorage::new_overwrite(filename).unwrap();
+        let mut marf = MARF::from_storage(f);
+
+        // a chain of blocks that each overwrite one key and add another, and a fork off of the
+        // fifth block
+        let mut blocks = vec![];
+        let mut parent = TrieFileStorage::block_sentinel();
+        for i in 0..10 {
+            let block = BlockHeaderHash([i as u8 + 1; 32]);
+            marf.begin(&parent, &block).unwrap();
+            marf.insert("counter", MARFValue::from(i)).unwrap();
+            marf.insert(&format!("key-{}", i), MARFValue::from(i)).unwrap();
+            marf.commit().unwrap();
+
+            blocks.push(block.clone());
+            parent = block;
+        }
+
+        let fork = BlockHeaderHash([0xfe; 32]);
+        marf.begin(&blocks[4], &fork).unwrap();
+        marf.insert("counter", MARFValue::from(100)).unwrap();
+        marf.commit().unwrap();
+
+        let mut root_hashes = vec![];
+        for block in blocks.iter().chain(vec![fork.clone()].iter()) {
+            root_hashes.push(marf.get_root_hash_at(block).unwrap());
+        }
+
+        let check_state = |marf: &mut MARF, first_readable: usize, fork_readable: bool| {
+            for (i, block) in blocks.iter().chain(vec![fork.clone()].iter()).enumerate() {
+                // root hashes never change
+                assert_eq!(marf.get_root_hash_at(block).unwrap(), root_hashes[i]);
+            }
+
+            for i in first_readable..10 {
+                assert_eq!(marf.get(&blocks[i], "counter").unwrap(), Some(MARFValue::from(i as u32)));
+                for j in 0..10 {
+                    let expected = if j <= i { Some(MARFValue::from(j as u32)) } else { None };
+                    assert_eq!(marf.get(&blocks[i], &format!("key-{}", j)).unwrap(), expected);
+                }
+            }
+
+            if fork_readable {
+                assert_eq!(marf.get(&fork, "counter").unwrap(), Some(MARFValue::from(100)));
+                for j in 0..5 {
+                    assert_eq!(marf.get(&fork, &format!("key-{}", j)).unwrap(), Some(MARFValue::from(j as u32)));
+                }
+            }
+        };
+
+        // prune the first three blocks
+        let pruned : HashSet<BlockHeaderHash> = blocks[0..3].iter().cloned().collect();
+        assert!(marf.prune(&pruned, &HashSet::new()).unwrap() > 0);
+        check_state(&mut marf, 3, true);
+
+        // pruning them again does nothing
+        assert_eq!(marf.prune(&HashSet::new(), &pruned).unwrap(), 0);
+
+        // prune up to the sixth block, and the fork.  Nodes of the first three blocks that only
+        // the newly-pruned tries needed are discarded too.
+        let mut newly_pruned : HashSet<BlockHeaderHash> = blocks[3..6].iter().cloned().collect();
+        newly_pruned.insert(fork.clone());
+        assert!(marf.prune(&newly_pruned, &pruned).unwrap() > 0);
+        check_state(&mut marf, 6, false);
+
+        // can't prune while a block is open
+        marf.begin(&blocks[9], &BlockHeaderHash([0x11; 32])).unwrap();
+        assert!(marf.prune(&newly_pruned, &pruned).is_err());
+
+        // the remaining history can still be extended
+        marf.insert("counter", MARFValue::from(10)).unwrap();
+        marf.commit().unwrap();
+        assert_eq!(marf.get(&BlockHeaderHash([0x11; 32]), "counter").unwrap(), Some(MARFValue::from(10)));
+        assert_eq!(marf.get(&BlockHeaderHash([0x11; 32]), "key-0").unwrap(), Some(MARFValue::from(0)));
+
+        // and reopened
+        let f = TrieFileStorage::new(filename).unwrap();
+        let mut marf = MARF::from_storage(f);
+        check_state(&mut marf, 6, false);
+    }
+
+    #[test]
+    fn marf_detach_tries() {
+        let filename = "/tmp/rust_marf_detach_tries";
+
+        let f = TrieFileStorage::new_overwrite(filename).unwrap();
+        let mut marf = MARF::from_storage(f);
+
+        // a chain of blocks that each overwrite one key and add another, and a fork off of the
+        // third block
+        let mut blocks = vec![];
+        let mut parent = TrieFileStorage::block_sentinel();
+        for i in 0..5 {
+            let block = BlockHeaderHash([i as u8 + 1; 32]);
+            marf.begin(&parent, &block).unwrap();
+            marf.insert("counter", MARFValue::from(i)).unwrap();
+            marf.insert(&format!("key-{}", i), MARFValue::from(i)).unwrap();
+            marf.commit().unwrap();
+
+            blocks.push(block.clone());
+            parent = block;
+        }
+
+        let fork = BlockHeaderHash([0xfe; 32]);
+        marf.begin(&blocks[2], &fork).unwrap();
+        marf.insert("counter", MARFValue::from(100)).unwrap();
+        marf.commit().unwrap();
+
+        let num_blocks = marf.borrow_storage_backend().num_blocks();
+        let root_hashes : Vec<TrieHash> = blocks[0..4].iter().map(|block| marf.get_root_hash_at(block).unwrap()).collect();
+
+        // keep the fourth block and its ancestors
+        let kept = marf.borrow_storage_backend().detach_tries(&blocks[3]).unwrap();
+        assert_eq!(kept, blocks[0..4].iter().cloned().collect());
+
+        // the values of the kept state are the ones in the fourth block (including the MARF's
+        // own block hash index)
+        let mut values = marf.borrow_storage_backend().get_leaf_values(&blocks[3]).unwrap();
+        values.sort_by_key(|value| value.to_hex());
+        values.dedup();
+        let mut expected_values : Vec<MARFValue> = (0..4).map(|i| MARFValue::from(i))
+            .chain(blocks[0..4].iter().map(|block| MARFValue::from(block.clone())))
+            .collect();
+        expected_values.sort_by_key(|value| value.to_hex());
+        assert_eq!(values, expected_values);
+
+        let f = TrieFileStorage::new(filename).unwrap();
+        let mut marf = MARF::from_storage(f);
+        assert_eq!(marf.borrow_storage_backend().num_blocks(), num_blocks);
+
+        for i in 0..4 {
+            assert_eq!(marf.get_root_hash_at(&blocks[i]).unwrap(), root_hashes[i]);
+            assert_eq!(marf.get(&blocks[i], "counter").unwrap(), Some(MARFValue::from(i as u32)));
+        }
+
+        // the detached blocks are gone...
+        assert_eq!(marf.get(&blocks[4], "counter").ok().and_then(|value| value), None);
+        assert_eq!(marf.get(&fork, "counter").ok().and_then(|value| value), None);
+        assert!(marf.get_root_hash_at(&blocks[4]).is_err());
+        assert!(marf.get_root_hash_at(&fork).is_err());
+
+        // ...and can be appended again
+        marf.begin(&blocks[3], &blocks[4]).unwrap();
+        marf.insert("counter", MARFValue::from(4)).unwrap();
+        marf.commit().unwrap();
+        assert_eq!(marf.get(&blocks[4], "counter").unwrap(), Some(MARFValue::from(4)));
+        assert_eq!(marf.get(&blocks[4], "key-0").unwrap(), Some(MARFValue::from(0)));
+
+        // only blocks in the MARF can be kept
+        assert!(marf.borrow_storage_backend().detach_tries(&fork).is_err());
+    }
+
     #[test]
     fn marf_insert_different_leaf_different_path_different_block_100() {
         let filename = "/tmp/rust_marf_insert_different_leaf_different_path_different_block_100";
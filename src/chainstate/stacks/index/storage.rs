@@ -49,6 +49,7 @@ use chainstate::burn::BlockHeaderHash;
 use chainstate::burn::BLOCK_HEADER_HASH_ENCODED_SIZE;
 
 use chainstate::stacks::index::{
+    MARFValue,
     TrieHash,
     TRIEHASH_ENCODED_SIZE,
 };
@@ -133,6 +134,17 @@ impl BlockHashMap {
         identifier
     }
 
+    /// Forget the most recently-added block, if it is the given block.  Used when a block that
+    /// was being extended is dropped instead of flushed, so its identifier can be reused.
+    pub fn remove_last_block(&mut self, block: &BlockHeaderHash, identifier: u32) -> bool {
+        if identifier + 1 != self.next_identifier || self.map.last() != Some(block) {
+            return false;
+        }
+        self.map.pop();
+        self.next_identifier = identifier;
+        true
+    }
+
     pub fn set_block(&mut self, block: BlockHeaderHash, identifier: u32) {
         if identifier >= self.next_identifier || (identifier as usize) >= self.map.len() {
             panic!("BlockHashMap corruption. Attempted to set block {} to id {}, but map is only length {}.",
@@ -461,6 +473,9 @@ impl NodeHashReader for fs::File {
     }
 }
 
+/// A trie node read from a trie file, with its hash and the pointer it was read from.
+type TrieNodeRecord = (TriePtr, TrieNodeType, TrieHash);
+
 // disk-backed Trie.
 // Keeps the last-extended Trie in-RAM and flushes it to disk on either a call to flush() or a call
 // to extend_to_block() with a different block header hash.
@@ -1366,7 +1381,18 @@ impl TrieFileStorage {
     }
 
     pub fn drop_extending_trie(&mut self) {
-        if let Some((ref bhh, _)) = self.last_extended.take() {
+        if let Some((ref bhh, ref trie_ram)) = self.last_extended.take() {
+            // the dropped block never existed, so don't let a later block resolve to its
+            // identifier (e.g. when the miner's sentinel block hash is re-opened and retargeted).
+            if self.block_map.remove_last_block(bhh, trie_ram.identifier) {
+                self.chain_tips.remove(bhh);
+            }
+
+            // nor let it reuse the dropped block's ancestor hashes, which belong to its parent
+            if self.check_cached_ancestor_hashes_bytes(bhh).is_some() {
+                self.trie_ancestor_hash_bytes_cache = None;
+            }
+
             let block_path_tmp = TrieFileStorage::block_path_tmp(&self.dir_path, bhh);
             match fs::metadata(&block_path_tmp) {
                 Ok(_md) => {
@@ -1403,5 +1429,315 @@ impl TrieFileStorage {
         r.extend(self.chain_tips.iter());
         r
     }
+
+    /// Walk every node reachable from the roots of the given tries, following back-pointers into
+    /// other tries, and skipping nodes in `visited` (which is updated).  Returns the nodes found
+    /// in the tries in `record`, grouped by trie identifier.
+    fn walk_reachable_nodes(&mut self, roots: &[u32], visited: &mut HashSet<(u32, u32)>, record: &HashSet<u32>) -> Result<HashMap<u32, Vec<TrieNodeRecord>>, Error> {
+        let mut pending : HashMap<u32, Vec<TriePtr>> = HashMap::new();
+        for root in roots.iter() {
+            pending.entry(*root).or_default()
+                .push(TriePtr::new(TrieNodeID::Node256, 0, TrieFileStorage::root_ptr_disk()));
+        }
+
+        let mut found : HashMap<u32, Vec<TrieNodeRecord>> = HashMap::new();
+
+        // visit one trie file at a time, so each file is opened as few times as possible
+        while let Some(block_id) = pending.keys().next().cloned() {
+            let mut ptrs = pending.remove(&block_id).expect("BUG: pending trie vanished");
+            let bhh = *self.block_map.get_block_header_hash(block_id)
+                .ok_or(Error::CorruptionError(format!("No block with identifier {}", block_id)))?;
+
+            let block_path = TrieFileStorage::block_path(&self.dir_path, &bhh);
+            let mut fd = fs::OpenOptions::new()
+                        .read(true)
+                        .write(false)
+                        .open(&block_path)
+                        .map_err(|e| {
+                            if e.kind() == io::ErrorKind::NotFound {
+                                error!("File not found: {:?}", &block_path);
+                                Error::NotFoundError
+                            }
+                            else {
+                                Error::IOError(e)
+                            }
+                        })?;
+
+            while let Some(ptr) = ptrs.pop() {
+                if !visited.insert((block_id, ptr.ptr())) {
+                    continue;
+                }
+
+                let (node, hash) = read_nodetype(&mut fd, &ptr)?;
+                if !node.is_leaf() {
+                    for child in node.ptrs().iter() {
+                        if child.id() == TrieNodeID::Empty {
+                            continue;
+                        }
+                        if is_backptr(child.id()) {
+                            pending.entry(child.back_block()).or_default()
+                                .push(child.from_backptr());
+                        }
+                        else {
+                            ptrs.push(*child);
+                        }
+                    }
+                }
+
+                if record.contains(&block_id) {
+                    found.entry(block_id).or_default()
+                        .push((ptr, node, hash));
+                }
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Rewrite a trie file so it only holds its header, its root node, and the given nodes.  Each
+    /// node stays at its offset (so back-pointers into it remain valid), and the space between
+    /// them is left as a hole in the file.  The new file is fsync'ed and atomically put in place.
+    /// Returns the number of bytes written.
+    fn rewrite_pruned_trie(&mut self, bhh: &BlockHeaderHash, mut nodes: Vec<TrieNodeRecord>) -> Result<u64, Error> {
+        let block_path = TrieFileStorage::block_path(&self.dir_path, bhh);
+        let mut prune_path = block_path.clone();
+        prune_path.set_extension("prune");
+
+        let mut header = [0u8; BLOCK_HEADER_HASH_ENCODED_SIZE + 4];
+        let root_ptr = TriePtr::new(TrieNodeID::Node256, 0, TrieFileStorage::root_ptr_disk());
+        {
+            let mut fd = fs::OpenOptions::new()
+                        .read(true)
+                        .write(false)
+                        .open(&block_path)
+                        .map_err(|e| {
+                            if e.kind() == io::ErrorKind::NotFound {
+                                error!("File not found: {:?}", &block_path);
+                                Error::NotFoundError
+                            }
+                            else {
+                                Error::IOError(e)
+                            }
+                        })?;
+
+            fd.read_exact(&mut header)?;
+            let (root, root_hash) = read_nodetype(&mut fd, &root_ptr)?;
+            nodes.push((root_ptr, root, root_hash));
+        }
+
+        nodes.sort_by_key(|(ptr, _, _)| ptr.ptr());
+        nodes.dedup_by_key(|(ptr, _, _)| ptr.ptr());
+
+        let mut written = header.len() as u64;
+        {
+            let mut writer = BufWriter::new(fs::OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(&prune_path)?);
+
+            writer.write_all(&header)?;
+            for (ptr, node, hash) in nodes.into_iter() {
+                fseek(&mut writer, ptr.ptr() as u64)?;
+                written += write_nodetype_bytes(&mut writer, &node, hash)?;
+            }
+
+            let fd = writer.into_inner()
+                .map_err(|e| { io::Error::from(e) })?;
+            fd.sync_all()?;
+        }
+
+        debug!("Rename {:?} to {:?}", &prune_path, &block_path);
+        fs::rename(&prune_path, &block_path)?;
+        Ok(written)
+    }
+
+    /// Discard the nodes of the tries of the `pruned` blocks that the tries of all other blocks
+    /// can no longer reach.  Each pruned trie keeps its root node, so the root hashes of later
+    /// tries (which commit to their ancestors' root hashes) and all Merkle roots are unchanged,
+    /// but the pruned tries themselves can no longer be read.  `already_pruned` names the blocks
+    /// whose tries were pruned before; they are not treated as live, and any of their nodes that
+    /// were only still reachable from the newly-pruned tries are discarded too.  Blocks that are
+    /// not in this MARF are ignored.
+    ///
+    /// This walks every node reachable from the remaining tries, so it is about as expensive as
+    /// reading the whole current state.  No trie may be open for writing.
+    /// Returns the number of bytes of node data discarded.
+    pub fn prune_tries(&mut self, pruned: &HashSet<BlockHeaderHash>, already_pruned: &HashSet<BlockHeaderHash>) -> Result<u64, Error> {
+        if self.last_extended.is_some() {
+            return Err(Error::InProgressError);
+        }
+
+        let sentinel = TrieFileStorage::block_sentinel();
+        let mut live_ids = vec![];
+        let mut pruned_ids = vec![];
+        let mut dead_ids = HashSet::new();
+        let mut already_pruned_ids = HashSet::new();
+
+        for (i, bhh) in self.block_map.iter().enumerate() {
+            let id = i as u32;
+            if *bhh == sentinel {
+                continue;
+            }
+            if pruned.contains(bhh) {
+                pruned_ids.push(id);
+                dead_ids.insert(id);
+            }
+            else if already_pruned.contains(bhh) {
+                already_pruned_ids.insert(id);
+                dead_ids.insert(id);
+            }
+            else {
+                live_ids.push(id);
+            }
+        }
+
+        if pruned_ids.is_empty() {
+            return Ok(0);
+        }
+
+        // nodes in dead tries that the live tries still need
+        let mut visited = HashSet::new();
+        let mut keep = self.walk_reachable_nodes(&live_ids, &mut visited, &dead_ids)?;
+
+        // nodes in already-pruned tries that only the newly-pruned tries needed
+        let dropped = self.walk_reachable_nodes(&pruned_ids, &mut visited, &already_pruned_ids)?;
+
+        let mut discarded = 0;
+        for block_id in pruned_ids.iter() {
+            let bhh = *self.block_map.get_block_header_hash(*block_id)
+                .ok_or(Error::CorruptionError(format!("No block with identifier {}", block_id)))?;
+
+            // newly-pruned trie files have no holes yet
+            let file_len = fs::metadata(TrieFileStorage::block_path(&self.dir_path, &bhh))?.len();
+            let nodes = keep.remove(block_id).unwrap_or(vec![]);
+
+            trace!("Prune trie {:?}: keep {} nodes", &bhh, nodes.len());
+            let kept_len = self.rewrite_pruned_trie(&bhh, nodes)?;
+            discarded += file_len.saturating_sub(kept_len);
+        }
+
+        for (block_id, dropped_nodes) in dropped.iter() {
+            let bhh = *self.block_map.get_block_header_hash(*block_id)
+                .ok_or(Error::CorruptionError(format!("No block with identifier {}", block_id)))?;
+
+            for (_, node, _) in dropped_nodes.iter() {
+                discarded += get_node_byte_len(node) as u64;
+            }
+            let nodes = keep.remove(block_id).unwrap_or(vec![]);
+
+            trace!("Re-prune trie {:?}: keep {} nodes", &bhh, nodes.len());
+            self.rewrite_pruned_trie(&bhh, nodes)?;
+        }
+
+        // don't keep reading a trie file that was replaced
+        if self.cur_block_fd.take().is_some() {
+            let cur_block = self.cur_block;
+            self.open_block(&cur_block)?;
+        }
+
+        debug!("Pruned {} tries, discarding {} bytes of nodes", pruned_ids.len() + dropped.len(), discarded);
+        Ok(discarded)
+    }
+
+    /// Block hash under which the trie of a detached block is kept (see `detach_tries()`)
+    pub fn detached_block_hash(bhh: &BlockHeaderHash) -> BlockHeaderHash {
+        let mut data = b"detached".to_vec();
+        data.extend_from_slice(bhh.as_bytes());
+        BlockHeaderHash(TrieHash::from_data(&data).0)
+    }
+
+    /// Get the given block and all of its ancestors in this MARF, by following the parent
+    /// pointers in the trie files' headers.
+    pub fn get_ancestor_blocks(&self, bhh: &BlockHeaderHash) -> Result<HashSet<BlockHeaderHash>, Error> {
+        let sentinel = TrieFileStorage::block_sentinel();
+        let mut ancestors = HashSet::new();
+        let mut cur = *bhh;
+
+        while cur != sentinel && self.block_map.find_id(&cur).is_some() && ancestors.insert(cur) {
+            let (_, parent) = TrieFileStorage::read_block_identifier_and_parent(&TrieFileStorage::block_path(&self.dir_path, &cur))?;
+            cur = parent;
+        }
+
+        if !ancestors.contains(bhh) {
+            return Err(Error::NotFoundError);
+        }
+        Ok(ancestors)
+    }
+
+    /// Detach the tries of all blocks other than `tip` and its ancestors, so this MARF only holds
+    /// the state as of `tip` and the blocks before it.  Trie identifiers must stay dense, so each
+    /// detached trie is replaced by an empty one stored under `detached_block_hash()` of its
+    /// block, instead of being removed.  The detached blocks are no longer in the MARF, and can be
+    /// appended to it again.  No trie may be open for writing.
+    /// Returns the blocks that were kept.
+    pub fn detach_tries(&mut self, tip: &BlockHeaderHash) -> Result<HashSet<BlockHeaderHash>, Error> {
+        if self.last_extended.is_some() {
+            return Err(Error::InProgressError);
+        }
+
+        let kept = self.get_ancestor_blocks(tip)?;
+        let sentinel = TrieFileStorage::block_sentinel();
+        let detached : Vec<(u32, BlockHeaderHash)> = self.block_map.iter()
+            .enumerate()
+            .filter(|(_, bhh)| **bhh != sentinel && !kept.contains(bhh))
+            .map(|(i, bhh)| (i as u32, *bhh))
+            .collect();
+
+        let root = TrieNode256::new(&vec![]);
+        let root_hash = get_node_hash(&root, &vec![], &self.block_map);
+        let root_end = TrieFileStorage::root_ptr_disk() + (get_node_byte_len(&TrieNodeType::Node256(root.clone())) as u32);
+
+        for (identifier, bhh) in detached.iter() {
+            let detached_bhh = TrieFileStorage::detached_block_hash(bhh);
+            let block_path = TrieFileStorage::block_path(&self.dir_path, bhh);
+            let detached_path = TrieFileStorage::block_path(&self.dir_path, &detached_bhh);
+
+            trace!("Detach trie {:?} as {:?}", bhh, &detached_bhh);
+            fs::create_dir_all(TrieFileStorage::block_dir(&self.dir_path, &detached_bhh))?;
+            {
+                let mut fd = fs::OpenOptions::new()
+                            .write(true)
+                            .create_new(true)
+                            .open(&detached_path)?;
+
+                TrieRAM::write_trie_file(&mut fd, &[(TrieNodeType::Node256(root.clone()), root_hash)], &[root_end], *identifier, &sentinel)?;
+                fd.sync_all()?;
+            }
+            fs::remove_file(&block_path)?;
+        }
+
+        let (block_map, chain_tips) = TrieFileStorage::read_block_hash_map(&self.dir_path)?;
+        self.block_map = block_map;
+        self.chain_tips = chain_tips;
+        self.block_path_cache.clear();
+        self.trie_ancestor_hash_bytes_cache = None;
+        if !kept.contains(&self.cur_block) {
+            self.cur_block_fd = None;
+            self.cur_block = sentinel;
+        }
+
+        debug!("Detached {} tries; kept {}", detached.len(), kept.len());
+        Ok(kept)
+    }
+
+    /// Get the values of all leaves reachable from the given block's trie, i.e. all of the
+    /// values in the MARF as of that block.
+    pub fn get_leaf_values(&mut self, bhh: &BlockHeaderHash) -> Result<Vec<MARFValue>, Error> {
+        let root = self.block_map.find_id(bhh)
+            .ok_or(Error::NotFoundError)?;
+        let all_ids = (0..(self.block_map.len() as u32)).collect();
+
+        let mut visited = HashSet::new();
+        let nodes = self.walk_reachable_nodes(&[root], &mut visited, &all_ids)?;
+
+        let mut values = vec![];
+        for (_, node, _) in nodes.into_values().flatten() {
+            if let TrieNodeType::Leaf(leaf) = node {
+                values.push(leaf.data);
+            }
+        }
+        Ok(values)
+    }
 }
 
@@ -0,0 +1,992 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+use std::fmt;
+use std::error;
+use std::fs;
+use std::cmp;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+use rusqlite::Connection;
+use rusqlite::NO_PARAMS;
+use rusqlite::OpenFlags;
+use rusqlite::Row;
+use rusqlite::types::ToSql;
+
+use burnchains::Txid;
+use burnchains::BurnchainHeaderHash;
+use burnchains::Address;
+
+use chainstate::burn::BlockHeaderHash;
+
+use chainstate::stacks::Error as chainstate_error;
+use chainstate::stacks::{
+    StacksAddress,
+    StacksTransaction,
+    TransactionPayload,
+    TransactionVersion
+};
+use chainstate::stacks::db::{
+    ClarityTx,
+    StacksChainState
+};
+
+use net::StacksMessageCodec;
+
+use vm::types::{
+    QualifiedContractIdentifier,
+    StandardPrincipalData
+};
+
+use util::db::Error as db_error;
+use util::db::{
+    DBConn,
+    query_rows,
+    query_row_columns,
+    FromRow,
+    FromColumn,
+};
+
+use util::get_epoch_time_secs;
+
+use core::MEMPOOL_MIN_RELAY_FEE_RATE;
+
+const MEMPOOL_SQL : &'static [&'static str] = &[
+    r#"
+    CREATE TABLE mempool(
+        txid TEXT NOT NULL,
+        origin_address TEXT NOT NULL,
+        origin_nonce INTEGER NOT NULL,
+        sponsor_address TEXT NOT NULL,      -- same as origin_address if not sponsored
+        sponsor_nonce INTEGER NOT NULL,     -- same as origin_nonce if not sponsored
+        fee_rate INTEGER NOT NULL,
+        length INTEGER NOT NULL,
+        accept_time INTEGER NOT NULL,
+        tx BLOB NOT NULL,
+
+        PRIMARY KEY(txid),
+        UNIQUE(origin_address, origin_nonce)
+    );
+    "#,
+    r#"
+    CREATE INDEX by_sponsor ON mempool(sponsor_address, sponsor_nonce);
+    "#,
+    r#"
+    CREATE INDEX by_fee_rate ON mempool(fee_rate);
+    "#,
+];
+
+/// Reasons why the mempool would not take a transaction
+#[derive(Debug)]
+pub enum MemPoolRejection {
+    /// The transaction's signatures do not verify
+    BadSignature(String),
+    /// The transaction is for a different network (mainnet vs testnet)
+    BadTransactionVersion,
+    /// The transaction is for a different chain.  Contains (expected, actual)
+    BadChainID(u32, u32),
+    /// Coinbases are only created by miners
+    NoCoinbaseViaMempool,
+    /// The fee rate is below the minimum relay fee rate.  Contains (minimum, actual)
+    FeeTooLow(u64, u64),
+    /// The origin nonce has already been used.  Contains (expected minimum, actual)
+    BadOriginNonce(u64, u64),
+    /// The sponsor nonce has already been used.  Contains (expected minimum, actual)
+    BadSponsorNonce(u64, u64),
+    /// The origin or sponsor nonce is too large to be stored.  Contains the nonce
+    NonceTooLarge(u64),
+    /// The paying account cannot cover the fee.  Contains (required, available)
+    NotEnoughFunds(u128, u128),
+    /// A contract with this name was already published by the origin
+    ContractAlreadyExists(QualifiedContractIdentifier),
+    /// The smart contract does not parse or does not type-check
+    BadContract(String),
+    /// A transaction with the same origin and nonce is already queued, and this one does not pay
+    /// a strictly higher fee rate.  Contains the txid of the queued transaction.
+    ConflictingNonceInMempool(Txid),
+    DBError(db_error),
+    /// Some other failure while checking the transaction
+    Other(String),
+}
+
+impl fmt::Display for MemPoolRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MemPoolRejection::BadSignature(ref s) => write!(f, "Invalid signature(s): {}", s),
+            MemPoolRejection::BadTransactionVersion => write!(f, "Transaction is for a different network"),
+            MemPoolRejection::BadChainID(expected, actual) => write!(f, "Invalid chain ID {} (expected {})", actual, expected),
+            MemPoolRejection::NoCoinbaseViaMempool => write!(f, "Coinbase transactions are not accepted"),
+            MemPoolRejection::FeeTooLow(minimum, actual) => write!(f, "Fee rate {} uSTX/byte is below the minimum relay fee rate of {} uSTX/byte", actual, minimum),
+            MemPoolRejection::BadOriginNonce(expected, actual) => write!(f, "Origin nonce {} is stale (expected at least {})", actual, expected),
+            MemPoolRejection::BadSponsorNonce(expected, actual) => write!(f, "Sponsor nonce {} is stale (expected at least {})", actual, expected),
+            MemPoolRejection::NonceTooLarge(nonce) => write!(f, "Nonce {} is too large", nonce),
+            MemPoolRejection::NotEnoughFunds(required, available) => write!(f, "Insufficient funds: need {} uSTX, have {} uSTX", required, available),
+            MemPoolRejection::ContractAlreadyExists(ref contract_id) => write!(f, "Contract {} already exists", contract_id),
+            MemPoolRejection::BadContract(ref s) => write!(f, "Invalid smart contract: {}", s),
+            MemPoolRejection::ConflictingNonceInMempool(ref txid) => write!(f, "Transaction {} with the same origin nonce pays an equal or higher fee", txid),
+            MemPoolRejection::DBError(ref e) => fmt::Display::fmt(e, f),
+            MemPoolRejection::Other(ref s) => fmt::Display::fmt(s, f),
+        }
+    }
+}
+
+impl error::Error for MemPoolRejection {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            MemPoolRejection::DBError(ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl MemPoolRejection {
+    /// Short, stable identifier for this kind of rejection, for API clients
+    pub fn reason_code(&self) -> &'static str {
+        match *self {
+            MemPoolRejection::BadSignature(_) => "BadSignature",
+            MemPoolRejection::BadTransactionVersion => "BadTransactionVersion",
+            MemPoolRejection::BadChainID(_, _) => "BadChainID",
+            MemPoolRejection::NoCoinbaseViaMempool => "NoCoinbaseViaMempool",
+            MemPoolRejection::FeeTooLow(_, _) => "FeeTooLow",
+            MemPoolRejection::BadOriginNonce(_, _) => "BadOriginNonce",
+            MemPoolRejection::BadSponsorNonce(_, _) => "BadSponsorNonce",
+            MemPoolRejection::NonceTooLarge(_) => "NonceTooLarge",
+            MemPoolRejection::NotEnoughFunds(_, _) => "NotEnoughFunds",
+            MemPoolRejection::ContractAlreadyExists(_) => "ContractAlreadyExists",
+            MemPoolRejection::BadContract(_) => "BadContract",
+            MemPoolRejection::ConflictingNonceInMempool(_) => "ConflictingNonceInMempool",
+            MemPoolRejection::DBError(_) => "ServerFailureDatabase",
+            MemPoolRejection::Other(_) => "ServerFailureOther",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemPoolTxMetadata {
+    pub txid: Txid,
+    pub origin_address: StacksAddress,
+    pub origin_nonce: u64,
+    pub sponsor_address: StacksAddress,
+    pub sponsor_nonce: u64,
+    pub fee_rate: u64,
+    pub len: u64,
+    pub accept_time: u64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemPoolTxInfo {
+    pub tx: StacksTransaction,
+    pub metadata: MemPoolTxMetadata
+}
+
+impl FromRow<MemPoolTxMetadata> for MemPoolTxMetadata {
+    fn from_row<'a>(row: &'a Row) -> Result<MemPoolTxMetadata, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let origin_address = StacksAddress::from_column(row, "origin_address")?;
+        let sponsor_address = StacksAddress::from_column(row, "sponsor_address")?;
+        let origin_nonce_i64 : i64 = row.get("origin_nonce");
+        let sponsor_nonce_i64 : i64 = row.get("sponsor_nonce");
+        let fee_rate_i64 : i64 = row.get("fee_rate");
+        let len_i64 : i64 = row.get("length");
+        let accept_time_i64 : i64 = row.get("accept_time");
+
+        if origin_nonce_i64 < 0 || sponsor_nonce_i64 < 0 || fee_rate_i64 < 0 || len_i64 < 0 || accept_time_i64 < 0 {
+            return Err(db_error::ParseError);
+        }
+
+        Ok(MemPoolTxMetadata {
+            txid,
+            origin_address,
+            origin_nonce: origin_nonce_i64 as u64,
+            sponsor_address,
+            sponsor_nonce: sponsor_nonce_i64 as u64,
+            fee_rate: fee_rate_i64 as u64,
+            len: len_i64 as u64,
+            accept_time: accept_time_i64 as u64
+        })
+    }
+}
+
+impl FromRow<MemPoolTxInfo> for MemPoolTxInfo {
+    fn from_row<'a>(row: &'a Row) -> Result<MemPoolTxInfo, db_error> {
+        let metadata = MemPoolTxMetadata::from_row(row)?;
+        let tx_bytes : Vec<u8> = row.get("tx");
+        let tx = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..])
+            .map_err(|_e| db_error::ParseError)?;
+
+        if tx.txid() != metadata.txid {
+            return Err(db_error::ParseError);
+        }
+
+        Ok(MemPoolTxInfo {
+            tx,
+            metadata
+        })
+    }
+}
+
+/// Iterates over queued transactions in decreasing fee-rate order, such that no transaction is
+/// produced before the transactions that use the preceding nonces of its origin account and of
+/// its sponsor account.  An account's nonce is used both by the transactions it originates and by
+/// the ones it sponsors, so these are chained together.
+pub struct MemPoolTxIterator {
+    /// queued transactions, until they are produced
+    txs: Vec<Option<MemPoolTxInfo>>,
+    /// per-account queues of (nonce, transaction index) for each nonce that a queued transaction
+    /// uses, in increasing nonce order
+    account_queues: HashMap<StacksAddress, VecDeque<(u64, usize)>>,
+    /// last nonce produced for each account
+    last_nonces: HashMap<StacksAddress, u64>,
+    /// (fee rate, accept time, transaction index) of each transaction that can be produced next
+    candidates: BinaryHeap<(u64, Reverse<u64>, Reverse<usize>)>
+}
+
+impl MemPoolTxIterator {
+    fn new(txs: Vec<MemPoolTxInfo>) -> MemPoolTxIterator {
+        let mut account_nonces : HashMap<StacksAddress, Vec<(u64, usize)>> = HashMap::new();
+        for (i, tx_info) in txs.iter().enumerate() {
+            for (address, nonce) in MemPoolTxIterator::nonces_used(tx_info).into_iter() {
+                account_nonces.entry(address).or_default().push((nonce, i));
+            }
+        }
+
+        let mut account_queues = HashMap::new();
+        for (address, mut nonces) in account_nonces.into_iter() {
+            nonces.sort();
+            account_queues.insert(address, VecDeque::from(nonces));
+        }
+
+        let mut iter = MemPoolTxIterator {
+            txs: txs.into_iter().map(Some).collect(),
+            account_queues,
+            last_nonces: HashMap::new(),
+            candidates: BinaryHeap::new()
+        };
+
+        for i in 0..iter.txs.len() {
+            iter.push_if_ready(i);
+        }
+        iter
+    }
+
+    /// The (account, nonce) pairs a transaction uses:  its origin's, and its sponsor's if it is
+    /// sponsored.
+    fn nonces_used(tx_info: &MemPoolTxInfo) -> Vec<(StacksAddress, u64)> {
+        let metadata = &tx_info.metadata;
+        let mut nonces = vec![(metadata.origin_address, metadata.origin_nonce)];
+        if (metadata.sponsor_address, metadata.sponsor_nonce) != (metadata.origin_address, metadata.origin_nonce) {
+            nonces.push((metadata.sponsor_address, metadata.sponsor_nonce));
+        }
+        nonces
+    }
+
+    /// Make a transaction a candidate if, for each account whose nonce it uses, it is the next
+    /// queued transaction and it uses the very next nonce after the last one produced.
+    fn push_if_ready(&mut self, tx_idx: usize) {
+        let (candidate, nonces) = match self.txs[tx_idx] {
+            Some(ref tx_info) => ((tx_info.metadata.fee_rate, Reverse(tx_info.metadata.accept_time), Reverse(tx_idx)), MemPoolTxIterator::nonces_used(tx_info)),
+            None => {
+                return;
+            }
+        };
+
+        for (address, nonce) in nonces.iter() {
+            match self.account_queues.get(address).and_then(|queue| queue.front()) {
+                Some((_, next_idx)) if *next_idx == tx_idx => {},
+                _ => {
+                    return;
+                }
+            }
+            if let Some(last_nonce) = self.last_nonces.get(address) {
+                if last_nonce.checked_add(1) != Some(*nonce) {
+                    return;
+                }
+            }
+        }
+
+        self.candidates.push(candidate);
+    }
+}
+
+impl Iterator for MemPoolTxIterator {
+    type Item = MemPoolTxInfo;
+
+    fn next(&mut self) -> Option<MemPoolTxInfo> {
+        let (_, _, Reverse(tx_idx)) = self.candidates.pop()?;
+        let tx_info = self.txs[tx_idx].take().expect("BUG: candidate transaction already produced");
+
+        // each account's next transaction may now be a candidate
+        let mut next_idxs = vec![];
+        for (address, nonce) in MemPoolTxIterator::nonces_used(&tx_info).into_iter() {
+            let queue = self.account_queues.get_mut(&address).expect("BUG: no queue for candidate's account");
+            queue.pop_front();
+            if let Some((_, next_idx)) = queue.front() {
+                next_idxs.push(*next_idx);
+            }
+            self.last_nonces.insert(address, nonce);
+        }
+
+        next_idxs.sort();
+        next_idxs.dedup();
+        for next_idx in next_idxs.into_iter() {
+            self.push_if_ready(next_idx);
+        }
+
+        Some(tx_info)
+    }
+}
+
+/// SQLite-backed queue of transactions waiting to be mined.
+///
+/// This is only a library for now:  the testnet node still takes its transactions from
+/// `testnet::MemPoolFS`, so nothing in the node calls `submit()`, `evict_confirmed()` or
+/// `iterate_candidates()` yet.
+pub struct MemPoolDB {
+    db: DBConn,
+    path: String
+}
+
+impl MemPoolDB {
+    fn instantiate_mempool_db(conn: &mut DBConn) -> Result<(), db_error> {
+        let tx = conn.transaction().map_err(db_error::SqliteError)?;
+
+        for cmd in MEMPOOL_SQL {
+            tx.execute(cmd, NO_PARAMS).map_err(db_error::SqliteError)?;
+        }
+
+        tx.commit().map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Open the mempool database at the given path, creating it if it does not exist.
+    pub fn open(path: &str) -> Result<MemPoolDB, db_error> {
+        let mut create_flag = false;
+        let open_flags =
+            if fs::metadata(path).is_err() {
+                // need to create
+                create_flag = true;
+                OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
+            }
+            else {
+                // can just open
+                OpenFlags::SQLITE_OPEN_READ_WRITE
+            };
+
+        let mut conn = DBConn::open_with_flags(path, open_flags).map_err(db_error::SqliteError)?;
+
+        if create_flag {
+            // instantiate!
+            MemPoolDB::instantiate_mempool_db(&mut conn)?;
+        }
+
+        Ok(MemPoolDB {
+            db: conn,
+            path: path.to_string()
+        })
+    }
+
+    pub fn conn(&self) -> &Connection {
+        &self.db
+    }
+
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+
+    /// Get a queued transaction by txid
+    pub fn get_tx(conn: &Connection, txid: &Txid) -> Result<Option<MemPoolTxInfo>, db_error> {
+        let sql = "SELECT * FROM mempool WHERE txid = ?1".to_string();
+        let args = [txid];
+        let mut rows = query_rows::<MemPoolTxInfo, _>(conn, &sql, &args)?;
+        Ok(rows.pop())
+    }
+
+    /// Get the queued transaction with the given origin address and nonce
+    pub fn get_tx_by_origin_nonce(conn: &Connection, origin_address: &StacksAddress, origin_nonce: u64) -> Result<Option<MemPoolTxInfo>, db_error> {
+        let sql = "SELECT * FROM mempool WHERE origin_address = ?1 AND origin_nonce = ?2".to_string();
+        let args : &[&dyn ToSql] = &[&origin_address.to_string(), &u64_to_sql(origin_nonce)];
+        let mut rows = query_rows::<MemPoolTxInfo, _>(conn, &sql, args)?;
+        Ok(rows.pop())
+    }
+
+    /// Get all queued transactions from an origin, in nonce order
+    pub fn get_txs_by_origin(conn: &Connection, origin_address: &StacksAddress) -> Result<Vec<MemPoolTxInfo>, db_error> {
+        let sql = "SELECT * FROM mempool WHERE origin_address = ?1 ORDER BY origin_nonce ASC".to_string();
+        let args = [&origin_address.to_string()];
+        query_rows::<MemPoolTxInfo, _>(conn, &sql, &args)
+    }
+
+    /// Get all queued transactions paid for by a sponsor, in sponsor nonce order
+    pub fn get_txs_by_sponsor(conn: &Connection, sponsor_address: &StacksAddress) -> Result<Vec<MemPoolTxInfo>, db_error> {
+        let sql = "SELECT * FROM mempool WHERE sponsor_address = ?1 ORDER BY sponsor_nonce ASC".to_string();
+        let args = [&sponsor_address.to_string()];
+        query_rows::<MemPoolTxInfo, _>(conn, &sql, &args)
+    }
+
+    /// How many transactions are queued?
+    pub fn get_num_txs(conn: &Connection) -> Result<u64, db_error> {
+        let sql = "SELECT txid FROM mempool".to_string();
+        let txids = query_row_columns::<Txid, _>(conn, &sql, NO_PARAMS, "txid")?;
+        Ok(txids.len() as u64)
+    }
+
+    /// Check that a transaction could be mined on top of the given Stacks chain tip: it must be
+    /// well-formed and signed, destined for this chain, pay at least the minimum relay fee rate,
+    /// not reuse an already-confirmed nonce, be paid for by an account that can afford the fee for
+    /// its length, and (if it publishes a smart contract)
+    /// carry a contract that type-checks.
+    pub fn will_admit_tx(chainstate: &mut StacksChainState, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, tx: &StacksTransaction) -> Result<(), MemPoolRejection> {
+        match tx.verify() {
+            Ok(true) => {},
+            Ok(false) => {
+                return Err(MemPoolRejection::BadSignature("signature(s) do not match the spending condition(s)".to_string()));
+            },
+            Err(e) => {
+                return Err(MemPoolRejection::BadSignature(format!("{}", e)));
+            }
+        }
+
+        if tx.is_mainnet() != chainstate.mainnet {
+            return Err(MemPoolRejection::BadTransactionVersion);
+        }
+
+        if tx.chain_id != chainstate.chain_id {
+            return Err(MemPoolRejection::BadChainID(chainstate.chain_id, tx.chain_id));
+        }
+
+        if let TransactionPayload::Coinbase(_) = tx.payload {
+            return Err(MemPoolRejection::NoCoinbaseViaMempool);
+        }
+
+        if tx.get_fee_rate() < MEMPOOL_MIN_RELAY_FEE_RATE {
+            return Err(MemPoolRejection::FeeTooLow(MEMPOOL_MIN_RELAY_FEE_RATE, tx.get_fee_rate()));
+        }
+
+        MemPoolDB::check_nonces_storable(tx)?;
+
+        let mut clarity_tx = chainstate.read_only_block_begin(burn_header_hash, block_hash)
+            .map_err(|e| match e {
+                chainstate_error::DBError(db_err) => MemPoolRejection::DBError(db_err),
+                e => MemPoolRejection::Other(format!("{}", e))
+            })?;
+        let res = MemPoolDB::check_tx_against_chain_tip(&mut clarity_tx, tx);
+        clarity_tx.rollback_block();
+        res
+    }
+
+    /// Nonces are stored as SQLite integers, so they must fit in an i64.
+    fn check_nonces_storable(tx: &StacksTransaction) -> Result<(), MemPoolRejection> {
+        let nonces = [Some(tx.get_origin_nonce()), tx.get_sponsor_nonce()];
+        for nonce in nonces.iter().filter_map(|nonce| *nonce) {
+            if nonce > i64::max_value() as u64 {
+                return Err(MemPoolRejection::NonceTooLarge(nonce));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_tx_against_chain_tip<'a>(clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction) -> Result<(), MemPoolRejection> {
+        let origin_address = tx.origin_address();
+        let origin = StacksChainState::get_account(clarity_tx, &origin_address.to_account_principal());
+        if tx.get_origin_nonce() < origin.nonce {
+            return Err(MemPoolRejection::BadOriginNonce(origin.nonce, tx.get_origin_nonce()));
+        }
+
+        let payer = match (tx.sponsor_address(), tx.get_sponsor_nonce()) {
+            (Some(sponsor_address), Some(sponsor_nonce)) => {
+                let sponsor = StacksChainState::get_account(clarity_tx, &sponsor_address.to_account_principal());
+                if sponsor_nonce < sponsor.nonce {
+                    return Err(MemPoolRejection::BadSponsorNonce(sponsor.nonce, sponsor_nonce));
+                }
+                sponsor
+            },
+            _ => origin.clone()
+        };
+
+        // only the length fee is known before the transaction runs
+        let fee = StacksChainState::get_tx_length_fee(tx)
+            .map_err(|e| MemPoolRejection::Other(format!("{}", e)))? as u128;
+        let payer_available = payer.stx_balance - payer.stx_locked;
+        if payer_available < fee {
+            return Err(MemPoolRejection::NotEnoughFunds(fee, payer_available));
+        }
+
+        match tx.payload {
+            TransactionPayload::TokenTransfer(_, amount, _) => {
+                // the origin must also be able to send the tokens (and pay the fee, if it's paying)
+                let origin_available = origin.stx_balance - origin.stx_locked;
+                let required = if payer.principal == origin.principal { (amount as u128) + fee } else { amount as u128 };
+                if origin_available < required {
+                    return Err(MemPoolRejection::NotEnoughFunds(required, origin_available));
+                }
+            },
+            TransactionPayload::SmartContract(ref smart_contract) => {
+                let contract_id = QualifiedContractIdentifier::new(StandardPrincipalData::from(origin_address), smart_contract.name.clone());
+                if StacksChainState::get_contract(clarity_tx, &contract_id)
+                    .map_err(|e| MemPoolRejection::Other(format!("{}", e)))?
+                    .is_some() {
+                    return Err(MemPoolRejection::ContractAlreadyExists(contract_id));
+                }
+
+                let contract_code_str = smart_contract.code_body.to_string();
+                clarity_tx.connection().analyze_smart_contract(&contract_id, &contract_code_str)
+                    .map_err(|e| MemPoolRejection::BadContract(format!("{}", e)))?;
+            },
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Check a transaction against the given Stacks chain tip with will_admit_tx(), and queue it
+    /// up if it passes.
+    pub fn submit(&mut self, chainstate: &mut StacksChainState, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash, tx: &StacksTransaction) -> Result<(), MemPoolRejection> {
+        MemPoolDB::will_admit_tx(chainstate, burn_header_hash, block_hash, tx)?;
+        self.try_add_tx(tx)
+    }
+
+    /// Queue up a transaction without checking it against the chain state.
+    /// Submitting a transaction that is already queued is a no-op.
+    /// If a transaction with the same origin and nonce is already queued, then it is replaced
+    /// only if this transaction pays a strictly higher fee rate.
+    pub fn try_add_tx(&mut self, tx: &StacksTransaction) -> Result<(), MemPoolRejection> {
+        MemPoolDB::check_nonces_storable(tx)?;
+
+        let txid = tx.txid();
+        let mut tx_bytes = vec![];
+        tx.consensus_serialize(&mut tx_bytes).expect("BUG: failed to serialize to a vec");
+
+        let origin_address = tx.origin_address();
+        let origin_nonce = tx.get_origin_nonce();
+        let (sponsor_address, sponsor_nonce) =
+            match (tx.sponsor_address(), tx.get_sponsor_nonce()) {
+                (Some(addr), Some(nonce)) => (addr, nonce),
+                _ => (origin_address.clone(), origin_nonce)
+            };
+
+        // only the ordering key is clamped; the transaction itself keeps its real fee rate
+        let fee_rate = cmp::min(tx.get_fee_rate(), i64::max_value() as u64);
+
+        let dbtx = self.db.transaction().map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+
+        if MemPoolDB::get_tx(&dbtx, &txid).map_err(MemPoolRejection::DBError)?.is_some() {
+            test_debug!("Already have {}", &txid);
+            return Ok(());
+        }
+
+        if let Some(prior_tx) = MemPoolDB::get_tx_by_origin_nonce(&dbtx, &origin_address, origin_nonce).map_err(MemPoolRejection::DBError)? {
+            if prior_tx.metadata.fee_rate >= fee_rate {
+                return Err(MemPoolRejection::ConflictingNonceInMempool(prior_tx.metadata.txid));
+            }
+
+            debug!("Replace {} with {} ({},{}): fee rate {} --> {}", &prior_tx.metadata.txid, &txid, &origin_address.to_string(), origin_nonce, prior_tx.metadata.fee_rate, fee_rate);
+            dbtx.execute("DELETE FROM mempool WHERE txid = ?1", &[&prior_tx.metadata.txid])
+                .map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+        }
+
+        let args : &[&dyn ToSql] = &[&txid, &origin_address.to_string(), &u64_to_sql(origin_nonce), &sponsor_address.to_string(), &u64_to_sql(sponsor_nonce),
+                                     &u64_to_sql(fee_rate), &u64_to_sql(tx_bytes.len() as u64), &u64_to_sql(get_epoch_time_secs()), &tx_bytes];
+
+        dbtx.execute("INSERT INTO mempool (txid,origin_address,origin_nonce,sponsor_address,sponsor_nonce,fee_rate,length,accept_time,tx) VALUES (?1,?2,?3,?4,?5,?6,?7,?8,?9)", args)
+            .map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+
+        dbtx.commit().map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+        Ok(())
+    }
+
+    /// Remove the given transactions (e.g. once they have been mined)
+    pub fn drop_txs(&mut self, txids: &[Txid]) -> Result<(), db_error> {
+        let dbtx = self.db.transaction().map_err(db_error::SqliteError)?;
+        for txid in txids {
+            dbtx.execute("DELETE FROM mempool WHERE txid = ?1", &[txid]).map_err(db_error::SqliteError)?;
+        }
+        dbtx.commit().map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Remove all transactions whose origin or sponsor nonce has already been used as of the given
+    /// Stacks chain tip.  These can never be mined on top of it.
+    /// Returns the number of transactions evicted.
+    pub fn evict_confirmed(&mut self, chainstate: &mut StacksChainState, burn_header_hash: &BurnchainHeaderHash, block_hash: &BlockHeaderHash) -> Result<u64, chainstate_error> {
+        let origins = query_row_columns::<StacksAddress, _>(&self.db, &"SELECT DISTINCT origin_address FROM mempool".to_string(), NO_PARAMS, "origin_address")
+            .map_err(chainstate_error::DBError)?;
+        let sponsors = query_row_columns::<StacksAddress, _>(&self.db, &"SELECT DISTINCT sponsor_address FROM mempool".to_string(), NO_PARAMS, "sponsor_address")
+            .map_err(chainstate_error::DBError)?;
+
+        let mut origin_nonces = vec![];
+        for origin in origins.into_iter() {
+            let account = chainstate.get_account_at_block(burn_header_hash, block_hash, &origin.to_account_principal())?;
+            origin_nonces.push((origin, account.nonce));
+        }
+
+        let mut sponsor_nonces = vec![];
+        for sponsor in sponsors.into_iter() {
+            let account = chainstate.get_account_at_block(burn_header_hash, block_hash, &sponsor.to_account_principal())?;
+            sponsor_nonces.push((sponsor, account.nonce));
+        }
+
+        let mut num_evicted = 0;
+        let dbtx = self.db.transaction().map_err(|e| chainstate_error::DBError(db_error::SqliteError(e)))?;
+
+        for (origin, nonce) in origin_nonces.iter() {
+            let args : &[&dyn ToSql] = &[&origin.to_string(), &u64_to_sql(*nonce)];
+            num_evicted += dbtx.execute("DELETE FROM mempool WHERE origin_address = ?1 AND origin_nonce < ?2", args)
+                .map_err(|e| chainstate_error::DBError(db_error::SqliteError(e)))?;
+        }
+
+        for (sponsor, nonce) in sponsor_nonces.iter() {
+            let args : &[&dyn ToSql] = &[&sponsor.to_string(), &u64_to_sql(*nonce)];
+            num_evicted += dbtx.execute("DELETE FROM mempool WHERE sponsor_address = ?1 AND sponsor_nonce < ?2", args)
+                .map_err(|e| chainstate_error::DBError(db_error::SqliteError(e)))?;
+        }
+
+        dbtx.commit().map_err(|e| chainstate_error::DBError(db_error::SqliteError(e)))?;
+
+        debug!("Evicted {} confirmed transaction(s) from the mempool as of {}/{}", num_evicted, burn_header_hash, block_hash);
+        Ok(num_evicted as u64)
+    }
+
+    /// Get an iterator over all queued transactions in the order in which a miner should try to
+    /// include them: highest fee rate first, with each account's transactions (the ones it
+    /// originates and the ones it sponsors) in nonce order.
+    pub fn iterate_candidates(&self) -> Result<MemPoolTxIterator, db_error> {
+        let sql = "SELECT * FROM mempool ORDER BY accept_time ASC, txid ASC".to_string();
+        let txs = query_rows::<MemPoolTxInfo, _>(&self.db, &sql, NO_PARAMS)?;
+        Ok(MemPoolTxIterator::new(txs))
+    }
+}
+
+fn u64_to_sql(x: u64) -> i64 {
+    assert!(x <= i64::max_value() as u64);
+    x as i64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::*;
+    use chainstate::stacks::db::test::*;
+    use chainstate::burn::*;
+    use core::*;
+
+    use util::hash::*;
+
+    fn make_mempool(test_name: &str) -> MemPoolDB {
+        let path = format!("/tmp/blockstack-test-mempool-{}.db", test_name);
+        if fs::metadata(&path).is_ok() {
+            fs::remove_file(&path).unwrap();
+        }
+        MemPoolDB::open(&path).unwrap()
+    }
+
+    fn make_unsigned_tx(privk: &StacksPrivateKey, nonce: u64, fee_rate: u64, payload: TransactionPayload) -> StacksTransaction {
+        let auth = TransactionAuth::from_p2pkh(privk).unwrap();
+        let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth, payload);
+
+        tx.chain_id = 0x80000000;
+        tx.post_condition_mode = TransactionPostConditionMode::Allow;
+        tx.set_fee_rate(fee_rate);
+        tx.set_origin_nonce(nonce);
+        tx
+    }
+
+    fn sign_tx(privk: &StacksPrivateKey, tx: &StacksTransaction) -> StacksTransaction {
+        let mut signer = StacksTransactionSigner::new(tx);
+        signer.sign_origin(privk).unwrap();
+        signer.get_tx().unwrap()
+    }
+
+    fn make_tx(privk: &StacksPrivateKey, nonce: u64, fee_rate: u64, amount: u64) -> StacksTransaction {
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+        let tx = make_unsigned_tx(privk, nonce, fee_rate, TransactionPayload::TokenTransfer(recv_addr, amount, TokenTransferMemo([0u8; 34])));
+        sign_tx(privk, &tx)
+    }
+
+    #[test]
+    fn mempool_submit_and_replace() {
+        let mut mempool = make_mempool("submit-and-replace");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let addr = TransactionAuth::from_p2pkh(&privk).unwrap().origin().address_testnet();
+
+        let tx = make_tx(&privk, 0, 10, 123);
+        mempool.try_add_tx(&tx).unwrap();
+
+        // duplicates are ignored
+        mempool.try_add_tx(&tx).unwrap();
+        assert_eq!(MemPoolDB::get_num_txs(mempool.conn()).unwrap(), 1);
+
+        let tx_info = MemPoolDB::get_tx(mempool.conn(), &tx.txid()).unwrap().unwrap();
+        assert_eq!(tx_info.tx, tx);
+        assert_eq!(tx_info.metadata.origin_address, addr);
+        assert_eq!(tx_info.metadata.origin_nonce, 0);
+        assert_eq!(tx_info.metadata.sponsor_address, addr);
+        assert_eq!(tx_info.metadata.sponsor_nonce, 0);
+        assert_eq!(tx_info.metadata.fee_rate, 10);
+
+        // same nonce, same fee rate -- rejected
+        let tx_same_fee = make_tx(&privk, 0, 10, 456);
+        match mempool.try_add_tx(&tx_same_fee) {
+            Err(MemPoolRejection::ConflictingNonceInMempool(txid)) => assert_eq!(txid, tx.txid()),
+            res => panic!("Expected conflicting nonce, got {:?}", &res)
+        }
+
+        // same nonce, lower fee rate -- rejected
+        let tx_low_fee = make_tx(&privk, 0, 9, 456);
+        match mempool.try_add_tx(&tx_low_fee) {
+            Err(MemPoolRejection::ConflictingNonceInMempool(txid)) => assert_eq!(txid, tx.txid()),
+            res => panic!("Expected conflicting nonce, got {:?}", &res)
+        }
+
+        // same nonce, higher fee rate -- replaced
+        let tx_high_fee = make_tx(&privk, 0, 11, 456);
+        mempool.try_add_tx(&tx_high_fee).unwrap();
+
+        assert_eq!(MemPoolDB::get_num_txs(mempool.conn()).unwrap(), 1);
+        assert!(MemPoolDB::get_tx(mempool.conn(), &tx.txid()).unwrap().is_none());
+        assert_eq!(MemPoolDB::get_tx_by_origin_nonce(mempool.conn(), &addr, 0).unwrap().unwrap().tx, tx_high_fee);
+
+        // survives a reopen
+        let path = mempool.path().clone();
+        drop(mempool);
+
+        let mempool = MemPoolDB::open(&path).unwrap();
+        let txs = MemPoolDB::get_txs_by_origin(mempool.conn(), &addr).unwrap();
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].tx, tx_high_fee);
+        assert_eq!(MemPoolDB::get_txs_by_sponsor(mempool.conn(), &addr).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn mempool_iterate_fee_order_with_nonce_chaining() {
+        let mut mempool = make_mempool("iterate-fee-order");
+
+        let privk_1 = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let privk_2 = StacksPrivateKey::from_hex("7e3ee1f2a0ae11b785a1f0e725a9b3ab0a5fd6cc057d43763b0a85f256fdec5d01").unwrap();
+
+        // origin 1: a cheap transaction gating an expensive one
+        let tx_1_0 = make_tx(&privk_1, 0, 1, 1);
+        let tx_1_1 = make_tx(&privk_1, 1, 100, 1);
+
+        // origin 2: moderate fees, plus a transaction after a nonce gap
+        let tx_2_0 = make_tx(&privk_2, 0, 50, 1);
+        let tx_2_1 = make_tx(&privk_2, 1, 40, 1);
+        let tx_2_3 = make_tx(&privk_2, 3, 1000, 1);
+
+        for tx in [&tx_1_1, &tx_2_3, &tx_1_0, &tx_2_1, &tx_2_0].iter() {
+            mempool.try_add_tx(tx).unwrap();
+        }
+
+        let order : Vec<StacksTransaction> = mempool.iterate_candidates().unwrap().map(|tx_info| tx_info.tx).collect();
+        assert_eq!(order, vec![tx_2_0, tx_2_1, tx_1_0, tx_1_1]);
+    }
+
+    fn make_sponsored_tx(privk: &StacksPrivateKey, nonce: u64, privk_sponsor: &StacksPrivateKey, sponsor_nonce: u64, fee_rate: u64) -> StacksTransaction {
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+        let auth = TransactionAuth::from_p2pkh(privk).unwrap()
+            .into_sponsored(TransactionAuth::from_p2pkh(privk_sponsor).unwrap()).unwrap();
+        let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth, TransactionPayload::TokenTransfer(recv_addr, 1, TokenTransferMemo([0u8; 34])));
+
+        tx.chain_id = 0x80000000;
+        tx.post_condition_mode = TransactionPostConditionMode::Allow;
+        tx.set_fee_rate(fee_rate);
+        tx.set_origin_nonce(nonce);
+        tx.set_sponsor_nonce(sponsor_nonce).unwrap();
+
+        let mut signer = StacksTransactionSigner::new(&tx);
+        signer.sign_origin(privk).unwrap();
+        signer.sign_sponsor(privk_sponsor).unwrap();
+        signer.get_tx().unwrap()
+    }
+
+    #[test]
+    fn mempool_iterate_sponsor_nonce_chaining() {
+        let mut mempool = make_mempool("iterate-sponsor-nonce-chaining");
+
+        let privk_1 = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let privk_2 = StacksPrivateKey::from_hex("7e3ee1f2a0ae11b785a1f0e725a9b3ab0a5fd6cc057d43763b0a85f256fdec5d01").unwrap();
+        let privk_sponsor = StacksPrivateKey::from_hex("7e3af4db6af6b3c67e2c6c6d7d5983b519f4d9b3a6e00580ae96dcace3bde8bc01").unwrap();
+
+        // the sponsor pays for a high-fee transaction with its second nonce, and a low-fee one
+        // with its first
+        let tx_1_0 = make_sponsored_tx(&privk_1, 0, &privk_sponsor, 1, 100);
+        let tx_2_0 = make_sponsored_tx(&privk_2, 0, &privk_sponsor, 0, 1);
+
+        // origin 1's next transaction waits on its sponsored one
+        let tx_1_1 = make_tx(&privk_1, 1, 1000, 1);
+
+        // the sponsor's own transaction uses its third nonce
+        let tx_sponsor_2 = make_tx(&privk_sponsor, 2, 50, 1);
+
+        // this one sponsors after a nonce gap
+        let tx_2_1 = make_sponsored_tx(&privk_2, 1, &privk_sponsor, 4, 500);
+
+        for tx in [&tx_1_1, &tx_2_1, &tx_sponsor_2, &tx_1_0, &tx_2_0].iter() {
+            mempool.try_add_tx(tx).unwrap();
+        }
+
+        let order : Vec<StacksTransaction> = mempool.iterate_candidates().unwrap().map(|tx_info| tx_info.tx).collect();
+        assert_eq!(order, vec![tx_2_0, tx_1_0, tx_1_1, tx_sponsor_2]);
+    }
+
+    #[test]
+    fn mempool_evict_confirmed() {
+        let mut mempool = make_mempool("evict-confirmed");
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "mempool-evict-confirmed");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let addr = TransactionAuth::from_p2pkh(&privk).unwrap().origin().address_testnet();
+
+        let tx_0 = make_tx(&privk, 0, 0, 1);
+        let tx_1 = make_tx(&privk, 1, 0, 1);
+        let tx_2 = make_tx(&privk, 2, 0, 1);
+
+        for tx in [&tx_0, &tx_1, &tx_2].iter() {
+            mempool.try_add_tx(tx).unwrap();
+        }
+
+        // confirm the first two
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &BurnchainHeaderHash([2u8; 32]), &BlockHeaderHash([2u8; 32]));
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 100);
+        StacksChainState::process_transaction(&mut conn, &tx_0).unwrap();
+        StacksChainState::process_transaction(&mut conn, &tx_1).unwrap();
+        conn.commit_block();
+
+        let num_evicted = mempool.evict_confirmed(&mut chainstate, &BurnchainHeaderHash([2u8; 32]), &BlockHeaderHash([2u8; 32])).unwrap();
+        assert_eq!(num_evicted, 2);
+
+        let txs = MemPoolDB::get_txs_by_origin(mempool.conn(), &addr).unwrap();
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].tx, tx_2);
+
+        mempool.drop_txs(&[tx_2.txid()]).unwrap();
+        assert_eq!(MemPoolDB::get_num_txs(mempool.conn()).unwrap(), 0);
+    }
+
+    #[test]
+    fn mempool_will_admit_tx() {
+        let mut mempool = make_mempool("will-admit-tx");
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, "mempool-will-admit-tx");
+
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let addr = TransactionAuth::from_p2pkh(&privk).unwrap().origin().address_testnet();
+
+        let burn_header_hash = BurnchainHeaderHash([2u8; 32]);
+        let block_hash = BlockHeaderHash([2u8; 32]);
+
+        // fund the account and use up nonce 0
+        let tx_0 = make_tx(&privk, 0, 0, 1);
+        let mut conn = chainstate.block_begin(&FIRST_BURNCHAIN_BLOCK_HASH, &FIRST_STACKS_BLOCK_HASH, &burn_header_hash, &block_hash);
+        StacksChainState::account_credit(&mut conn, &addr.to_account_principal(), 10001);
+        StacksChainState::process_transaction(&mut conn, &tx_0).unwrap();
+        conn.commit_block();
+
+        // well-formed and fundable
+        let tx_ok = make_tx(&privk, 1, 10, 90);
+        MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_ok).unwrap();
+
+        // nonce from the future is fine -- it can be chained
+        let tx_future = make_tx(&privk, 5, 10, 90);
+        MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_future).unwrap();
+
+        // stale nonce
+        let tx_stale = make_tx(&privk, 0, 10, 90);
+        match MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_stale) {
+            Err(MemPoolRejection::BadOriginNonce(1, 0)) => {},
+            res => panic!("Expected stale nonce, got {:?}", &res)
+        }
+
+        // can't afford the fee and the transfer
+        let tx_broke = make_tx(&privk, 1, 55, 200);
+        let required = (StacksChainState::get_tx_length_fee(&tx_broke).unwrap() as u128) + 200;
+        assert!(required > 10000 && required - 200 <= 10000);
+        match MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_broke) {
+            Err(MemPoolRejection::NotEnoughFunds(req, 10000)) => assert_eq!(req, required),
+            res => panic!("Expected insufficient funds, got {:?}", &res)
+        }
+
+        // doesn't pay the minimum relay fee rate
+        let tx_free = make_tx(&privk, 1, 0, 90);
+        match MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_free) {
+            Err(MemPoolRejection::FeeTooLow(MEMPOOL_MIN_RELAY_FEE_RATE, 0)) => {},
+            res => panic!("Expected fee too low, got {:?}", &res)
+        }
+
+        // nonce can't be stored
+        let tx_huge_nonce = make_tx(&privk, (i64::max_value() as u64) + 1, 10, 90);
+        match MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_huge_nonce) {
+            Err(MemPoolRejection::NonceTooLarge(nonce)) => assert_eq!(nonce, (i64::max_value() as u64) + 1),
+            res => panic!("Expected nonce too large, got {:?}", &res)
+        }
+        match mempool.try_add_tx(&tx_huge_nonce) {
+            Err(MemPoolRejection::NonceTooLarge(_)) => {},
+            res => panic!("Expected nonce too large, got {:?}", &res)
+        }
+
+        // tampered after signing
+        let mut tx_tampered = make_tx(&privk, 1, 10, 90);
+        tx_tampered.set_fee_rate(1);
+        match MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_tampered) {
+            Err(MemPoolRejection::BadSignature(_)) => {},
+            res => panic!("Expected bad signature, got {:?}", &res)
+        }
+
+        // wrong chain
+        let recv_addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
+        let mut tx_wrong_chain = make_unsigned_tx(&privk, 1, 10, TransactionPayload::TokenTransfer(recv_addr, 1, TokenTransferMemo([0u8; 34])));
+        tx_wrong_chain.chain_id = 0x80000001;
+        let tx_wrong_chain = sign_tx(&privk, &tx_wrong_chain);
+        match MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_wrong_chain) {
+            Err(MemPoolRejection::BadChainID(0x80000000, 0x80000001)) => {},
+            res => panic!("Expected bad chain ID, got {:?}", &res)
+        }
+
+        // no coinbases
+        let tx_coinbase = sign_tx(&privk, &make_unsigned_tx(&privk, 1, 0, TransactionPayload::Coinbase(CoinbasePayload([0u8; 32]))));
+        match MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_coinbase) {
+            Err(MemPoolRejection::NoCoinbaseViaMempool) => {},
+            res => panic!("Expected coinbase rejection, got {:?}", &res)
+        }
+
+        // contracts must type-check
+        let good_contract = TransactionPayload::new_smart_contract(&"hello-world".to_string(), &"(define-data-var bar int 0)".to_string()).unwrap();
+        let tx_good_contract = sign_tx(&privk, &make_unsigned_tx(&privk, 1, 1, good_contract));
+        MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_good_contract).unwrap();
+
+        let bad_contract = TransactionPayload::new_smart_contract(&"hello-world".to_string(), &"(define-data-var bar int 0) (+ 1 u1)".to_string()).unwrap();
+        let tx_bad_contract = sign_tx(&privk, &make_unsigned_tx(&privk, 1, 1, bad_contract));
+        let rejection = match MemPoolDB::will_admit_tx(&mut chainstate, &burn_header_hash, &block_hash, &tx_bad_contract) {
+            Err(e @ MemPoolRejection::BadContract(_)) => e,
+            res => panic!("Expected bad contract, got {:?}", &res)
+        };
+
+        assert_eq!(rejection.reason_code(), "BadContract");
+
+        // submit() only queues admissible transactions
+        assert!(mempool.submit(&mut chainstate, &burn_header_hash, &block_hash, &tx_stale).is_err());
+        mempool.submit(&mut chainstate, &burn_header_hash, &block_hash, &tx_ok).unwrap();
+
+        let txs = MemPoolDB::get_txs_by_origin(mempool.conn(), &addr).unwrap();
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].tx, tx_ok);
+    }
+}
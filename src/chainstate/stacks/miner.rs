@@ -24,6 +24,9 @@ use chainstate::stacks::db::{
     ClarityTx,
     StacksChainState
 };
+use chainstate::stacks::db::genesis::GenesisVestingUnlock;
+use chainstate::stacks::db::pox::PoxRewardSlot;
+use chainstate::stacks::mempool::MemPoolDB;
 use chainstate::stacks::index::TrieHash;
 
 use chainstate::burn::BlockHeaderHash;
@@ -44,11 +47,16 @@ use chainstate::burn::operations::*;
 use burnchains::BurnchainHeaderHash;
 use burnchains::PrivateKey;
 use burnchains::PublicKey;
+use burnchains::Address;
 
 use util::vrf::*;
 
 use core::*;
 
+use vm::costs::ExecutionCost;
+
+use std::collections::HashSet;
+
 impl StacksBlockBuilder {
     pub fn from_parent(miner_id: usize, parent_chain_tip: &StacksHeaderInfo, total_work: &StacksWorkScore, proof: &VRFProof, microblock_privkey: &StacksPrivateKey) -> StacksBlockBuilder {
         let mut pubk = StacksPublicKey::from_private(microblock_privkey);
@@ -71,6 +79,12 @@ impl StacksBlockBuilder {
             prev_microblock_header: StacksMicroblockHeader::first_unsigned(&EMPTY_MICROBLOCK_PARENT_HASH, &Sha512Trunc256Sum([0u8; 32])),       // will be updated
             miner_privkey: microblock_privkey.clone(),
             miner_payouts: None,
+            genesis_vesting_unlocks: vec![],
+            burnchain_stx_ops: vec![],
+            burn_block_height: 0,
+            pox_reward_slot: None,
+            pox_reward_address: None,
+            execution_cost_limit: ExecutionCost::max_value(),
             miner_id: miner_id
         }
     }
@@ -101,7 +115,43 @@ impl StacksBlockBuilder {
         self.header.parent_microblock_sequence = parent_mblock_seq;
     }
 
-    /// Append a transaction if doing so won't exceed the epoch data size.
+    /// Assign the STX operations sent on the burnchain that this block must apply.  These are the
+    /// ops from the parent block's sortition (inclusive) up to this block's sortition (see
+    /// BurnDB::get_burnchain_stx_ops()), in the order they were mined.  The block will not be
+    /// accepted unless it applies exactly these.
+    pub fn set_burnchain_stx_ops(&mut self, ops: Vec<BlockstackOperationType>) -> () {
+        self.burnchain_stx_ops = ops;
+    }
+
+    /// Assign the height of the burnchain block in which this block's commit will be mined.
+    /// STX locks are measured against it, so the block will not be accepted unless it is elected
+    /// at this height.  Must be called before epoch_begin().
+    pub fn set_burn_block_height(&mut self, burn_block_height: u64) -> () {
+        self.burn_block_height = burn_block_height;
+    }
+
+    /// Assign the PoX reward slot of the block commit that will elect this block (see
+    /// StacksChainState::get_pox_reward_slot()), if it has one.  The block records the slot's
+    /// reward set if it is the first block to see it.  Must be called before epoch_begin().
+    pub fn set_pox_reward_slot(&mut self, pox_reward_slot: Option<PoxRewardSlot>) -> () {
+        self.pox_reward_slot = pox_reward_slot;
+    }
+
+    /// Get the PoX reward address this block's block commit must pay, or None if it must burn.
+    /// Only known once epoch_begin() has been called.
+    pub fn get_pox_reward_address(&self) -> Option<StacksAddress> {
+        self.pox_reward_address.clone()
+    }
+
+    /// Cap the total execution cost of the transactions this builder will mine.
+    /// Transactions that would push the block past this limit are rejected by try_mine_tx().
+    pub fn set_execution_cost_limit(&mut self, limit: ExecutionCost) -> () {
+        self.execution_cost_limit = limit;
+    }
+
+    /// Try to add a transaction to the block being built.  If the transaction is invalid, or would
+    /// make the block too big or too expensive, then its effects are rolled back and an error is
+    /// returned -- the block under construction is left as it was.
     pub fn try_mine_tx<'a>(&mut self, clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction) -> Result<(), Error> {
         let mut tx_bytes = vec![];
         tx.consensus_serialize(&mut tx_bytes).map_err(Error::NetError)?;
@@ -116,20 +166,20 @@ impl StacksBlockBuilder {
             if tx.anchor_mode != TransactionAnchorMode::OnChainOnly && tx.anchor_mode != TransactionAnchorMode::Any {
                 return Err(Error::InvalidStacksTransaction("Invalid transaction anchor mode for anchored data".to_string()));
             }
-
-            StacksChainState::process_transaction(clarity_tx, tx)?;
-
-            // save
-            self.txs.push(tx.clone());
         }
         else {
-            // building up the anchored blocks
+            // building up the microblocks
             if tx.anchor_mode != TransactionAnchorMode::OffChainOnly && tx.anchor_mode != TransactionAnchorMode::Any {
                 return Err(Error::InvalidStacksTransaction("Invalid transaction anchor mode for streamed data".to_string()));
             }
-            
-            StacksChainState::process_transaction(clarity_tx, tx)?;
+        }
+
+        self.process_transaction_atomic(clarity_tx, tx)?;
 
+        if !self.anchored_done {
+            self.txs.push(tx.clone());
+        }
+        else {
             self.micro_txs.push(tx.clone());
         }
 
@@ -137,12 +187,76 @@ impl StacksBlockBuilder {
         Ok(())
     }
 
+    /// Process a transaction such that either all of its effects are kept, or none of them are.
+    /// process_transaction() keeps nothing if the transaction is invalid, but a valid transaction
+    /// can still exceed the block's execution budget, so its writes are made inside a savepoint
+    /// that is only committed if it fits.
+    fn process_transaction_atomic<'a>(&self, clarity_tx: &mut ClarityTx<'a>, tx: &StacksTransaction) -> Result<(), Error> {
+        clarity_tx.connection().begin_savepoint();
+        match StacksChainState::process_transaction(clarity_tx, tx) {
+            Ok(_) => {
+                if clarity_tx.connection().cost_so_far().exceeds(&self.execution_cost_limit) {
+                    clarity_tx.connection().rollback_savepoint();
+                    return Err(Error::BlockCostExceeded);
+                }
+                clarity_tx.connection().commit_savepoint();
+                Ok(())
+            },
+            Err(e) => {
+                clarity_tx.connection().rollback_savepoint();
+                Err(e)
+            }
+        }
+    }
+
+    /// Fill the anchored block with transactions from the mempool, highest fee-rate first, and
+    /// then finish it.  Transactions that cannot be mined are skipped and reported alongside the
+    /// block, as are any later transactions from the same origin (whose nonces can no longer be
+    /// valid in this block).
+    pub fn mine_anchored_block_from_mempool<'a>(&mut self, clarity_tx: &mut ClarityTx<'a>, mempool: &MemPoolDB) -> Result<(StacksBlock, Vec<(Txid, Error)>), Error> {
+        assert!(!self.anchored_done);
+
+        let mut skipped = vec![];
+        let mut blocked_origins = HashSet::new();
+
+        for tx_info in mempool.iterate_candidates().map_err(Error::DBError)? {
+            let origin = tx_info.metadata.origin_address.to_string();
+            let txid = tx_info.metadata.txid.clone();
+            if blocked_origins.contains(&origin) {
+                debug!("Skip mempool transaction {}: an earlier transaction from {} was not mined", &txid, &origin);
+                skipped.push((txid, Error::InvalidStacksTransaction(format!("Earlier transaction from {} was not mined", &origin))));
+                continue;
+            }
+
+            match self.try_mine_tx(clarity_tx, &tx_info.tx) {
+                Ok(_) => {
+                    test_debug!("Mined mempool transaction {}", &txid);
+                },
+                Err(e) => {
+                    debug!("Skip mempool transaction {}: {:?}", &txid, &e);
+                    blocked_origins.insert(origin);
+                    skipped.push((txid, e));
+                }
+            }
+        }
+
+        let block = self.mine_anchored_block(clarity_tx);
+        Ok((block, skipped))
+    }
+
     /// Finish building the anchored block.
     /// TODO: expand to deny mining a block whose anchored static checks fail (and allow the caller
     /// to disable this, in order to test mining invalid blocks)
     pub fn mine_anchored_block<'a>(&mut self, clarity_tx: &mut ClarityTx<'a>) -> StacksBlock {
         assert!(!self.anchored_done);
 
+        // add vested genesis STX
+        StacksChainState::process_genesis_vesting_unlocks(clarity_tx, &self.genesis_vesting_unlocks);
+
+        // apply STX operations sent on the burnchain
+        StacksChainState::process_burnchain_stx_ops(clarity_tx, &self.burnchain_stx_ops)
+            .expect("FATAL: failed to process burnchain STX operations");
+
         // add miner payments
         if let Some(ref mature_miner_rewards) = self.miner_payouts {
             // grant in order by miner, then users
@@ -226,6 +340,7 @@ impl StacksBlockBuilder {
         };
 
         self.miner_payouts = matured_miner_rewards_opt;
+        self.genesis_vesting_unlocks = StacksChainState::get_genesis_vesting_unlocks(&chainstate.headers_db, self.header.total_work.work)?;
         
         // there's no way the miner can learn either the burn block hash or the stacks block hash,
         // so use a sentinel hash value for each that will never occur in practice.
@@ -249,6 +364,14 @@ impl StacksBlockBuilder {
 
         let mut tx = chainstate.block_begin(&parent_burn_header_hash, &parent_header_hash, &new_burn_hash, &new_block_hash);
 
+        // STX locks are measured in burnchain blocks
+        StacksChainState::set_burnchain_block_height(&mut tx, self.burn_block_height);
+
+        // find the PoX reward address our block commit must pay
+        if let Some(ref pox_reward_slot) = self.pox_reward_slot {
+            self.pox_reward_address = StacksChainState::process_pox_reward_slot(&mut tx, &parent_burn_header_hash, &parent_header_hash, pox_reward_slot)?;
+        }
+
         test_debug!("Miner {}: Apply {} parent microblocks", self.miner_id, parent_microblocks.len());
 
         if parent_microblocks.len() == 0 {
@@ -316,6 +439,8 @@ pub mod test {
     use chainstate::stacks::*;
     use chainstate::stacks::db::*;
     use chainstate::stacks::db::test::*;
+    use chainstate::stacks::db::blocks::ProposedBlockVerdict;
+    use chainstate::stacks::db::genesis::GenesisAllocation;
     use chainstate::burn::*;
     use chainstate::burn::db::burndb::*;
     use chainstate::burn::operations::*;
@@ -551,6 +676,27 @@ pub mod test {
             }
         }
         
+        pub fn new_with_allocations(mainnet: bool, chain_id: u32, test_name: &str, allocations: &Vec<GenesisAllocation>) -> TestStacksNode {
+            let path = chainstate_path(test_name);
+            match fs::metadata(&path) {
+                Ok(_) => {
+                    fs::remove_dir_all(&path).unwrap();
+                },
+                Err(_) => {}
+            };
+
+            let chainstate = StacksChainState::open_with_genesis_allocations(mainnet, chain_id, &path, allocations).unwrap();
+            TestStacksNode {
+                chainstate: chainstate,
+                prev_keys: vec![],
+                key_ops: HashMap::new(),
+                anchored_blocks: vec![],
+                microblocks: vec![],
+                commit_ops: HashMap::new(),
+                test_name: test_name.to_string()
+            }
+        }
+
         pub fn open(mainnet: bool, chain_id: u32, test_name: &str) -> TestStacksNode {
             let chainstate = open_chainstate(mainnet, chain_id, test_name);
             TestStacksNode {
@@ -600,11 +746,11 @@ pub mod test {
             key_register_op
         }
 
-        pub fn add_block_commit(burn_node: &mut TestBurnchainNode, burn_block: &mut TestBurnchainBlock, miner: &mut TestMiner, block_hash: &BlockHeaderHash, burn_amount: u64, key_op: &LeaderKeyRegisterOp, parent_block_snapshot: Option<&BlockSnapshot>) -> LeaderBlockCommitOp {
+        pub fn add_block_commit(burn_node: &mut TestBurnchainNode, burn_block: &mut TestBurnchainBlock, miner: &mut TestMiner, block_hash: &BlockHeaderHash, burn_amount: u64, commit_outs: Vec<StacksAddress>, key_op: &LeaderKeyRegisterOp, parent_block_snapshot: Option<&BlockSnapshot>) -> LeaderBlockCommitOp {
             let block_commit_op = {
                 let mut tx = burn_node.burndb.tx_begin().unwrap();
                 let parent_snapshot = burn_block.parent_snapshot.clone();
-                burn_block.add_leader_block_commit(&mut tx, miner, block_hash, burn_amount, key_op, Some(&parent_snapshot), parent_block_snapshot)
+                burn_block.add_leader_block_commit(&mut tx, miner, block_hash, burn_amount, commit_outs, key_op, Some(&parent_snapshot), parent_block_snapshot)
             };
             block_commit_op
         }
@@ -720,7 +866,7 @@ pub mod test {
             let proof = miner.make_proof(&miner_key.public_key, &burn_block.parent_snapshot.sortition_hash)
                 .expect(&format!("FATAL: no private key for {}", miner_key.public_key.to_hex()));
 
-            let (builder, parent_block_snapshot_opt) = match parent_stacks_block {
+            let (mut builder, parent_block_snapshot_opt) = match parent_stacks_block {
                 None => {
                     // first stacks block
                     let builder = StacksBlockBuilder::first(miner.id, &burn_block.parent_snapshot.burn_header_hash, burn_block.parent_snapshot.burn_header_timestamp, &proof, &miner.next_microblock_privkey());
@@ -748,6 +894,31 @@ pub mod test {
                 }
             };
 
+            // the block must apply the STX operations sent on the burnchain since its parent's sortition
+            let parent_burn_header_hash = match parent_block_snapshot_opt {
+                Some(ref sn) => sn.burn_header_hash.clone(),
+                None => FIRST_BURNCHAIN_BLOCK_HASH.clone()
+            };
+            let burnchain_stx_ops = BurnDB::get_burnchain_stx_ops(burn_node.burndb.conn(), &parent_burn_header_hash, &burn_block.parent_snapshot.burn_header_hash).unwrap();
+            builder.set_burnchain_stx_ops(burnchain_stx_ops);
+            builder.set_burn_block_height(burn_block.block_height);
+
+            // the block must record its PoX reward slot, and its commit must pay the slot's address
+            let pox_reward_slot = {
+                let mut tx = burn_node.burndb.tx_begin().unwrap();
+                self.chainstate.get_pox_reward_slot(&mut tx, burn_block.block_height, &burn_block.parent_snapshot.burn_header_hash).unwrap()
+            };
+            builder.set_pox_reward_slot(pox_reward_slot.clone());
+
+            let parent_block_hash = match parent_stacks_block {
+                Some(ref parent_stacks_block) => parent_stacks_block.block_hash(),
+                None => FIRST_STACKS_BLOCK_HASH.clone()
+            };
+            let commit_outs = match pox_reward_slot {
+                Some(ref slot) => self.chainstate.get_pox_reward_address(&parent_burn_header_hash, &parent_block_hash, burn_block.block_height, slot).unwrap().into_iter().collect(),
+                None => vec![]
+            };
+
             test_debug!("Miner {}: Assemble stacks block from {}", miner.id, miner.origin_address().unwrap().to_string());
 
             let (stacks_block, microblocks) = block_assembler(builder, miner);
@@ -757,7 +928,7 @@ pub mod test {
             test_debug!("Miner {}: Commit to stacks block {} (work {},{})", miner.id, stacks_block.block_hash(), stacks_block.header.total_work.burn, stacks_block.header.total_work.work);
 
             // send block commit for this block
-            let block_commit_op = TestStacksNode::add_block_commit(burn_node, burn_block, miner, &stacks_block.block_hash(), burn_amount, miner_key, parent_block_snapshot_opt.as_ref());
+            let block_commit_op = TestStacksNode::add_block_commit(burn_node, burn_block, miner, &stacks_block.block_hash(), burn_amount, commit_outs, miner_key, parent_block_snapshot_opt.as_ref());
             self.commit_ops.insert(block_commit_op.block_header_hash.clone(), self.anchored_blocks.len()-1);
 
             (stacks_block, microblocks, block_commit_op)
@@ -814,6 +985,37 @@ pub mod test {
         state_root == stacks_header.state_index_root
     }
 
+    /// Verify that each of the stacks block's transactions can be found in the fork it extends
+    fn check_block_transactions_indexed(chainstate: &mut StacksChainState, chain_tip: &StacksHeaderInfo, stacks_block: &StacksBlock) -> bool {
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+        for (i, stacks_tx) in stacks_block.txs.iter().enumerate() {
+            match StacksChainState::get_transaction_location_in_fork(&mut tx, &stacks_tx.txid(), &chain_tip.index_block_hash()).unwrap() {
+                Some(location) => {
+                    if location.index_block_hash != chain_tip.index_block_hash() || location.microblock_hash.is_some() || location.tx_index != (i as u32) {
+                        return false;
+                    }
+                },
+                None => {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Verify that each of the stacks block's transactions is in its sender's account history
+    fn check_block_account_history(chainstate: &mut StacksChainState, chain_tip: &StacksHeaderInfo, stacks_block: &StacksBlock) -> bool {
+        let mut tx = chainstate.headers_tx_begin().unwrap();
+        for stacks_tx in stacks_block.txs.iter() {
+            let sender = stacks_tx.origin_address().to_account_principal();
+            let history = StacksChainState::get_account_history(&mut tx, &sender, &chain_tip.index_block_hash(), 0, u64::max_value()).unwrap();
+            if history.iter().find(|entry| entry.txid == stacks_tx.txid() && entry.sender && entry.index_block_hash == chain_tip.index_block_hash()).is_none() {
+                return false;
+            }
+        }
+        true
+    }
+
     /// Verify that the miner got the expected block reward
     fn check_mining_reward<'a>(clarity_tx: &mut ClarityTx<'a>, miner: &mut TestMiner, block_height: u64, prev_block_rewards: &Vec<Vec<MinerPaymentSchedule>>) -> bool {
         let mut total : u128 = 0;
@@ -912,6 +1114,7 @@ pub mod test {
     {
         let full_test_name = format!("{}-1_fork_1_miner_1_burnchain", test_name);
         let mut node = TestStacksNode::new(false, 0x80000000, &full_test_name);
+        node.chainstate.set_account_history(true).unwrap();
         let mut burn_node = TestBurnchainNode::new();
         let mut miner_factory = TestMinerFactory::new();
         let mut miner = miner_factory.next_miner(&burn_node.burnchain, 1, 1, AddressHashMode::SerializeP2PKH); 
@@ -930,6 +1133,7 @@ pub mod test {
         burn_node.mine_fork(&mut fork);
 
         let mut miner_trace = vec![];
+        let mut parent_tip = StacksHeaderInfo::genesis();
 
         // next, build up some stacks blocks
         for i in 0..rounds {
@@ -941,6 +1145,10 @@ pub mod test {
             let last_key = node.get_last_key(&miner);
             let parent_block_opt = node.get_last_anchored_block(&miner);
             let last_microblock_header = get_last_microblock_header(&node, &miner, parent_block_opt.as_ref());
+            let parent_microblocks = match parent_block_opt {
+                Some(ref parent_block) => node.get_microblock_stream(&miner, &parent_block.block_hash()).unwrap_or(vec![]),
+                None => vec![]
+            };
             
             // next key
             node.add_key_register(&mut burn_block, &mut miner);
@@ -967,6 +1175,16 @@ pub mod test {
             // "discover" the stacks block and its microblocks
             preprocess_stacks_block_data(&mut node, &mut burn_node, &fork_snapshot, &stacks_block, &microblocks, &block_commit_op);
 
+            // the block validates before it is processed
+            let verdict = node.chainstate.validate_proposed_block(&parent_tip, &stacks_block, &parent_microblocks).unwrap();
+            assert!(verdict.is_valid());
+            assert_eq!(verdict.computed_state_index_root(), Some(&stacks_block.header.state_index_root));
+
+            // a copy with a different state root was never elected, so it can't be checked
+            let mut bad_stacks_block = stacks_block.clone();
+            bad_stacks_block.header.state_index_root = TrieHash([0xff; 32]);
+            assert_eq!(node.chainstate.validate_proposed_block(&parent_tip, &bad_stacks_block, &parent_microblocks).unwrap(), ProposedBlockVerdict::Unelected);
+
             // process all blocks
             test_debug!("Process Stacks block {} and {} microblocks", &stacks_block.block_hash(), microblocks.len());
             let tip_info_list = node.chainstate.process_blocks(1).unwrap();
@@ -979,6 +1197,7 @@ pub mod test {
             assert!(poison_opt.is_none());
 
             let chain_tip = chain_tip_opt.unwrap();
+            parent_tip = chain_tip.clone();
 
             assert_eq!(chain_tip.anchored_header.block_hash(), stacks_block.block_hash());
             assert_eq!(chain_tip.burn_header_hash, fork_snapshot.burn_header_hash);
@@ -986,6 +1205,10 @@ pub mod test {
             // MARF trie exists for the block header's chain state, so we can make merkle proofs on it
             assert!(check_block_state_index_root(&mut node.chainstate, &fork_snapshot.burn_header_hash, &chain_tip.anchored_header));
 
+            // block's transactions are indexed in this fork
+            assert!(check_block_transactions_indexed(&mut node.chainstate, &chain_tip, &stacks_block));
+            assert!(check_block_account_history(&mut node.chainstate, &chain_tip, &stacks_block));
+
             let mut next_miner_trace = TestMinerTracePoint::new();
             next_miner_trace.add(miner.id, full_test_name.clone(), fork_snapshot, stacks_block, microblocks, block_commit_op);
             miner_trace.push(next_miner_trace);
@@ -2361,6 +2584,102 @@ pub mod test {
         (stacks_block, vec![])
     }
     
+    fn make_mempool_for_block(burnchain_height: usize, tag: &str) -> MemPoolDB {
+        let path = format!("/tmp/blockstack-test-miner-mempool-{}-{}.db", tag, burnchain_height);
+        if fs::metadata(&path).is_ok() {
+            fs::remove_file(&path).unwrap();
+        }
+        MemPoolDB::open(&path).unwrap()
+    }
+
+    /// make a contract-call from a key that isn't the miner's, to a contract that does not exist
+    fn make_bad_contract_call(privk: &StacksPrivateKey, nonce: u64) -> StacksTransaction {
+        let addr = StacksAddress::from_public_keys(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, &AddressHashMode::SerializeP2PKH, 1, &vec![StacksPublicKey::from_private(privk)]).unwrap();
+        let mut tx_contract_call = StacksTransaction::new(TransactionVersion::Testnet,
+                                                          TransactionAuth::from_p2pkh(privk).unwrap(),
+                                                          TransactionPayload::new_contract_call(addr, "does-not-exist", "set-bar", vec![Value::Int(1), Value::Int(1)]).unwrap());
+
+        tx_contract_call.chain_id = 0x80000000;
+        tx_contract_call.auth.set_origin_nonce(nonce);
+        tx_contract_call.set_fee_rate(0);
+
+        let mut tx_signer = StacksTransactionSigner::new(&tx_contract_call);
+        tx_signer.sign_origin(privk).unwrap();
+        tx_signer.get_tx().unwrap()
+    }
+
+    /// mine a smart contract and a contract-call to it from the mempool, alongside a failing
+    /// transaction (and its successor) from another account, which must be left out of the block.
+    fn mine_smart_contract_contract_call_block_from_mempool<'a>(clarity_tx: &mut ClarityTx<'a>, builder: &mut StacksBlockBuilder, miner: &mut TestMiner, burnchain_height: usize, parent_microblock_header: Option<&StacksMicroblockHeader>) -> (StacksBlock, Vec<StacksMicroblock>) {
+        let miner_account = StacksChainState::get_account(clarity_tx, &miner.origin_address().unwrap().to_account_principal());
+        miner.set_nonce(miner_account.nonce);
+
+        let tx_coinbase_signed = mine_coinbase(clarity_tx, builder, miner, burnchain_height);
+        builder.try_mine_tx(clarity_tx, &tx_coinbase_signed).unwrap();
+        miner.set_nonce(miner_account.nonce + 1);
+
+        let mut mempool = make_mempool_for_block(burnchain_height, "contract-call");
+
+        let tx_contract_signed = make_smart_contract(clarity_tx, builder, miner, burnchain_height);
+        miner.set_nonce(miner_account.nonce + 2);
+        let tx_contract_call_signed = make_contract_call(clarity_tx, builder, miner, burnchain_height, 6, 2);
+
+        let other_privk = StacksPrivateKey::new();
+        let tx_bad_signed = make_bad_contract_call(&other_privk, 0);
+        let tx_chained_signed = make_bad_contract_call(&other_privk, 1);
+
+        for tx in [&tx_contract_signed, &tx_contract_call_signed, &tx_bad_signed, &tx_chained_signed].iter() {
+            mempool.try_add_tx(tx).unwrap();
+        }
+
+        let (stacks_block, skipped) = builder.mine_anchored_block_from_mempool(clarity_tx, &mempool).unwrap();
+
+        assert_eq!(stacks_block.txs.len(), 3);
+        assert_eq!(stacks_block.txs[1].txid(), tx_contract_signed.txid());
+        assert_eq!(stacks_block.txs[2].txid(), tx_contract_call_signed.txid());
+
+        let skipped_txids : Vec<Txid> = skipped.iter().map(|(txid, _)| txid.clone()).collect();
+        assert_eq!(skipped_txids, vec![tx_bad_signed.txid(), tx_chained_signed.txid()]);
+
+        // the failed transaction left no trace
+        let other_addr = StacksAddress::from_public_keys(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, &AddressHashMode::SerializeP2PKH, 1, &vec![StacksPublicKey::from_private(&other_privk)]).unwrap();
+        let other_account = StacksChainState::get_account(clarity_tx, &other_addr.to_account_principal());
+        assert_eq!(other_account.nonce, 0);
+
+        test_debug!("Produce anchored stacks block {} from mempool at burnchain height {} stacks height {}", stacks_block.block_hash(), burnchain_height, stacks_block.header.total_work.work);
+        (stacks_block, vec![])
+    }
+
+    /// mine a block from the mempool with an execution budget too small to fit anything but the
+    /// coinbase
+    fn mine_cost_limited_block_from_mempool<'a>(clarity_tx: &mut ClarityTx<'a>, builder: &mut StacksBlockBuilder, miner: &mut TestMiner, burnchain_height: usize, parent_microblock_header: Option<&StacksMicroblockHeader>) -> (StacksBlock, Vec<StacksMicroblock>) {
+        let miner_account = StacksChainState::get_account(clarity_tx, &miner.origin_address().unwrap().to_account_principal());
+        miner.set_nonce(miner_account.nonce);
+
+        let tx_coinbase_signed = mine_coinbase(clarity_tx, builder, miner, burnchain_height);
+        builder.try_mine_tx(clarity_tx, &tx_coinbase_signed).unwrap();
+        miner.set_nonce(miner_account.nonce + 1);
+
+        builder.set_execution_cost_limit(clarity_tx.connection().cost_so_far());
+
+        let mut mempool = make_mempool_for_block(burnchain_height, "cost-limited");
+
+        let tx_contract_signed = make_smart_contract(clarity_tx, builder, miner, burnchain_height);
+        mempool.try_add_tx(&tx_contract_signed).unwrap();
+
+        let (stacks_block, skipped) = builder.mine_anchored_block_from_mempool(clarity_tx, &mempool).unwrap();
+
+        assert_eq!(stacks_block.txs.len(), 1);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, tx_contract_signed.txid());
+        match skipped[0].1 {
+            ::chainstate::stacks::Error::BlockCostExceeded => {},
+            ref e => panic!("Unexpected error {:?}", e)
+        }
+
+        (stacks_block, vec![])
+    }
+
     /// mine a smart contract in an anchored block, and mine some contract-calls to it in a microblock tail
     fn mine_smart_contract_block_contract_call_microblock<'a>(clarity_tx: &mut ClarityTx<'a>, builder: &mut StacksBlockBuilder, miner: &mut TestMiner, burnchain_height: usize, parent_microblock_header: Option<&StacksMicroblockHeader>) -> (StacksBlock, Vec<StacksMicroblock>) {
         if burnchain_height > 0 && builder.chain_tip.anchored_header.total_work.work > 0 {
@@ -2525,6 +2844,16 @@ pub mod test {
     }
     */
 
+    #[test]
+    fn mine_anchored_smart_contract_contract_call_blocks_from_mempool() {
+        mine_stacks_blocks_1_fork_1_miner_1_burnchain(&"smart-contract-contract-call-blocks-from-mempool".to_string(), 5, mine_smart_contract_contract_call_block_from_mempool);
+    }
+
+    #[test]
+    fn mine_anchored_cost_limited_blocks_from_mempool() {
+        mine_stacks_blocks_1_fork_1_miner_1_burnchain(&"cost-limited-blocks-from-mempool".to_string(), 3, mine_cost_limited_block_from_mempool);
+    }
+
     #[test]
     fn mine_anchored_empty_blocks_single() {
         mine_stacks_blocks_1_fork_1_miner_1_burnchain(&"empty-anchored-blocks".to_string(), 10, mine_empty_anchored_block);
@@ -2745,6 +3074,309 @@ pub mod test {
         miner_trace_replay_randomized(&mut miner_trace);
     }
 
+    #[test]
+    fn mine_anchored_block_with_burnchain_stx_transfers() {
+        let test_name = "mine-anchored-block-with-burnchain-stx-transfers";
+        let sender = StacksAddress { version: 26, bytes: Hash160([0x01; 20]) };
+        let recipient = StacksAddress { version: 26, bytes: Hash160([0x02; 20]) };
+
+        // the sender can afford the transfers
+        let allocations = vec![GenesisAllocation { address: sender.clone(), amount: 1000, vesting: vec![] }];
+        let mut node = TestStacksNode::new_with_allocations(false, 0x80000000, test_name, &allocations);
+
+        let mut burn_node = TestBurnchainNode::new();
+        let mut miner_factory = TestMinerFactory::new();
+        let mut miner = miner_factory.next_miner(&burn_node.burnchain, 1, 1, AddressHashMode::SerializeP2PKH);
+
+        let first_snapshot = BurnDB::get_first_block_snapshot(burn_node.burndb.conn()).unwrap();
+        let mut fork = TestBurnchainFork::new(first_snapshot.block_height, &first_snapshot.burn_header_hash, &first_snapshot.index_root, 0);
+
+        // register a VRF key, and send a transfer before the first Stacks block is mined
+        let mut first_burn_block = TestStacksNode::next_burn_block(&mut burn_node, &mut fork);
+        node.add_key_register(&mut first_burn_block, &mut miner);
+        first_burn_block.add_transfer_stx(&sender, &recipient, 100);
+
+        fork.append_block(first_burn_block);
+        burn_node.mine_fork(&mut fork);
+
+        let mut expected_balance = 0;
+        let mut parent_tip = StacksHeaderInfo::genesis();
+        for i in 0..3 {
+            let mut burn_block = {
+                let mut tx = burn_node.burndb.tx_begin().unwrap();
+                fork.next_block(&mut tx)
+            };
+
+            let last_key = node.get_last_key(&miner);
+            let parent_block_opt = node.get_last_anchored_block(&miner);
+            node.add_key_register(&mut burn_block, &mut miner);
+
+            // each block applies the transfer sent before it (before the first block, or
+            // alongside the previous block's commit).  The last block's miner leaves it out.
+            let ignore_burnchain_stx_ops = i == 2;
+            if !ignore_burnchain_stx_ops {
+                expected_balance += 100;
+            }
+
+            let (stacks_block, microblocks, block_commit_op) = node.mine_stacks_block(&mut burn_node, &mut miner, &mut burn_block, &last_key, parent_block_opt.as_ref(), 1000, |mut builder, ref mut miner| {
+                if ignore_burnchain_stx_ops {
+                    builder.set_burnchain_stx_ops(vec![]);
+                }
+                let mut miner_chainstate = open_chainstate(false, 0x80000000, test_name);
+                let mut epoch = builder.epoch_begin(&mut miner_chainstate).unwrap();
+                let (stacks_block, microblocks) = mine_empty_anchored_block(&mut epoch, &mut builder, miner, i, None);
+                builder.epoch_finish(epoch);
+                (stacks_block, microblocks)
+            });
+
+            burn_block.add_transfer_stx(&sender, &recipient, 100);
+
+            fork.append_block(burn_block);
+            let fork_snapshot = burn_node.mine_fork(&mut fork);
+
+            // can't be checked until its sortition is known
+            assert_eq!(node.chainstate.validate_proposed_block(&parent_tip, &stacks_block, &microblocks).unwrap(), ProposedBlockVerdict::Unelected);
+
+            assert_eq!(preprocess_stacks_block_data(&mut node, &mut burn_node, &fork_snapshot, &stacks_block, &microblocks, &block_commit_op), Some(true));
+
+            // validation applies the transfers just like processing does
+            let verdict = node.chainstate.validate_proposed_block(&parent_tip, &stacks_block, &microblocks).unwrap();
+            let process_result = node.chainstate.process_blocks(1);
+
+            if ignore_burnchain_stx_ops {
+                match verdict {
+                    ProposedBlockVerdict::StateRootMismatch { expected, .. } => assert_eq!(expected, stacks_block.header.state_index_root),
+                    verdict => panic!("Expected a state root mismatch, got {:?}", &verdict)
+                }
+                match process_result {
+                    Err(::chainstate::stacks::Error::InvalidStacksBlock(_)) => {},
+                    res => panic!("Expected an invalid block, got {:?}", &res)
+                }
+                break;
+            }
+
+            let tip_info_list = process_result.unwrap();
+
+            assert!(verdict.is_valid());
+            assert_eq!(verdict.computed_state_index_root(), Some(&stacks_block.header.state_index_root));
+
+            // the block's state root accounts for the transfers, so it is accepted
+            assert_eq!(tip_info_list.len(), 1);
+
+            let (chain_tip_opt, poison_opt) = tip_info_list[0].clone();
+            assert!(poison_opt.is_none());
+
+            let chain_tip = chain_tip_opt.unwrap();
+            assert_eq!(chain_tip.anchored_header.block_hash(), stacks_block.block_hash());
+
+            let mut clarity_tx = node.chainstate.read_only_block_begin(&chain_tip.burn_header_hash, &stacks_block.block_hash()).unwrap();
+            assert_eq!(StacksChainState::get_account(&mut clarity_tx, &recipient.to_account_principal()).stx_balance, expected_balance);
+            assert_eq!(StacksChainState::get_account(&mut clarity_tx, &sender.to_account_principal()).stx_balance, 1000 - expected_balance);
+            clarity_tx.rollback_block();
+
+            parent_tip = chain_tip;
+        }
+    }
+
+    #[test]
+    fn mine_anchored_blocks_across_reward_cycles() {
+        let test_name = "mine-anchored-blocks-across-reward-cycles";
+        let stacker = StacksAddress { version: 26, bytes: Hash160([0x01; 20]) };
+        let reward_addr = StacksAddress { version: 26, bytes: Hash160([0x02; 20]) };
+
+        let allocations = vec![GenesisAllocation { address: stacker.clone(), amount: 2000, vesting: vec![] }];
+        let mut node = TestStacksNode::new_with_allocations(false, 0x80000000, test_name, &allocations);
+
+        let mut burn_node = TestBurnchainNode::new();
+        let mut miner_factory = TestMinerFactory::new();
+        let mut miner = miner_factory.next_miner(&burn_node.burnchain, 1, 1, AddressHashMode::SerializeP2PKH);
+
+        // reward cycles are measured on the test burnchain
+        node.chainstate.pox_first_block_height = burn_node.burnchain.first_block_height;
+        node.chainstate.pox_reward_cycle_length = burn_node.burnchain.reward_cycle_length;
+        let pox_first_block_height = node.chainstate.pox_first_block_height;
+        let pox_reward_cycle_length = node.chainstate.pox_reward_cycle_length;
+
+        let first_snapshot = BurnDB::get_first_block_snapshot(burn_node.burndb.conn()).unwrap();
+        let mut fork = TestBurnchainFork::new(first_snapshot.block_height, &first_snapshot.burn_header_hash, &first_snapshot.index_root, 0);
+
+        // register a VRF key, and stack for one reward cycle before the first Stacks block is
+        // mined.  The STX lock in reward cycle 0, and unlock when reward cycle 2 begins.
+        let mut first_burn_block = TestStacksNode::next_burn_block(&mut burn_node, &mut fork);
+        node.add_key_register(&mut first_burn_block, &mut miner);
+        first_burn_block.add_stack_stx(&stacker, &reward_addr, 1000, 1);
+
+        fork.append_block(first_burn_block);
+        burn_node.mine_fork(&mut fork);
+
+        let reward_cycle_1_start = burn_node.burnchain.reward_cycle_to_block_height(1);
+        let reward_cycle_2_start = burn_node.burnchain.reward_cycle_to_block_height(2);
+
+        let mut num_payouts = 0;
+        loop {
+            let mut burn_block = {
+                let mut tx = burn_node.burndb.tx_begin().unwrap();
+                fork.next_block(&mut tx)
+            };
+
+            if burn_block.block_height > reward_cycle_2_start {
+                break;
+            }
+
+            let last_key = node.get_last_key(&miner);
+            let parent_block_opt = node.get_last_anchored_block(&miner);
+            node.add_key_register(&mut burn_block, &mut miner);
+
+            let (stacks_block, microblocks, block_commit_op) = node.mine_stacks_block(&mut burn_node, &mut miner, &mut burn_block, &last_key, parent_block_opt.as_ref(), 1000, |mut builder, ref mut miner| {
+                let mut miner_chainstate = open_chainstate(false, 0x80000000, test_name);
+                miner_chainstate.pox_first_block_height = pox_first_block_height;
+                miner_chainstate.pox_reward_cycle_length = pox_reward_cycle_length;
+
+                let mut epoch = builder.epoch_begin(&mut miner_chainstate).unwrap();
+                let (stacks_block, microblocks) = mine_empty_anchored_block(&mut epoch, &mut builder, miner, 0, None);
+                builder.epoch_finish(epoch);
+                (stacks_block, microblocks)
+            });
+
+            // the commit pays the one reward address in the first slot of reward cycle 1, and
+            // burns everywhere else
+            if block_commit_op.block_height == reward_cycle_1_start {
+                assert_eq!(block_commit_op.commit_outs, vec![reward_addr.clone()]);
+            }
+            else {
+                assert_eq!(block_commit_op.commit_outs, vec![]);
+            }
+
+            fork.append_block(burn_block);
+            let fork_snapshot = burn_node.mine_fork(&mut fork);
+
+            // the commit was accepted
+            assert!(fork_snapshot.sortition);
+            assert_eq!(fork_snapshot.winning_stacks_block_hash, stacks_block.block_hash());
+
+            assert_eq!(preprocess_stacks_block_data(&mut node, &mut burn_node, &fork_snapshot, &stacks_block, &microblocks, &block_commit_op), Some(true));
+
+            let tip_info_list = node.chainstate.process_blocks(1).unwrap();
+            assert_eq!(tip_info_list.len(), 1);
+            assert!(tip_info_list[0].0.is_some());
+
+            if block_commit_op.commit_outs.len() > 0 {
+                num_payouts += 1;
+            }
+        }
+
+        assert_eq!(num_payouts, 1);
+    }
+
+    #[test]
+    fn mine_anchored_blocks_across_reward_cycles_process_late() {
+        let test_name = "mine-anchored-blocks-across-reward-cycles-process-late";
+        let stacker = StacksAddress { version: 26, bytes: Hash160([0x01; 20]) };
+        let reward_addr = StacksAddress { version: 26, bytes: Hash160([0x02; 20]) };
+
+        // node 1 mines and processes each Stacks block as soon as its sortition is known.  Node 2
+        // sees the same burnchain, but only processes the Stacks blocks once it is all mined.
+        let allocations = vec![GenesisAllocation { address: stacker.clone(), amount: 2000, vesting: vec![] }];
+        let mut node_1 = TestStacksNode::new_with_allocations(false, 0x80000000, &format!("{}-1", test_name), &allocations);
+        let mut node_2 = TestStacksNode::new_with_allocations(false, 0x80000000, &format!("{}-2", test_name), &allocations);
+
+        let mut burn_node_1 = TestBurnchainNode::new();
+        let mut burn_node_2 = TestBurnchainNode::new();
+        let mut miner_factory = TestMinerFactory::new();
+        let mut miner = miner_factory.next_miner(&burn_node_1.burnchain, 1, 1, AddressHashMode::SerializeP2PKH);
+
+        // reward cycles are measured on the test burnchain
+        for node in [&mut node_1, &mut node_2].iter_mut() {
+            node.chainstate.pox_first_block_height = burn_node_1.burnchain.first_block_height;
+            node.chainstate.pox_reward_cycle_length = burn_node_1.burnchain.reward_cycle_length;
+        }
+        let pox_first_block_height = node_1.chainstate.pox_first_block_height;
+        let pox_reward_cycle_length = node_1.chainstate.pox_reward_cycle_length;
+
+        let first_snapshot = BurnDB::get_first_block_snapshot(burn_node_1.burndb.conn()).unwrap();
+        let mut fork_1 = TestBurnchainFork::new(first_snapshot.block_height, &first_snapshot.burn_header_hash, &first_snapshot.index_root, 0);
+        let mut fork_2 = fork_1.clone();
+
+        let mut first_burn_block = TestStacksNode::next_burn_block(&mut burn_node_1, &mut fork_1);
+        node_1.add_key_register(&mut first_burn_block, &mut miner);
+        first_burn_block.add_stack_stx(&stacker, &reward_addr, 1000, 1);
+
+        fork_1.append_block(first_burn_block.clone());
+        fork_2.append_block(first_burn_block);
+        burn_node_1.mine_fork(&mut fork_1);
+        burn_node_2.mine_fork(&mut fork_2);
+
+        let reward_cycle_1_start = burn_node_1.burnchain.reward_cycle_to_block_height(1);
+        let reward_cycle_2_start = burn_node_1.burnchain.reward_cycle_to_block_height(2);
+
+        let mut mined_blocks = vec![];
+        let mut last_snapshot_2 = None;
+        loop {
+            let mut burn_block = TestStacksNode::next_burn_block(&mut burn_node_1, &mut fork_1);
+            if burn_block.block_height > reward_cycle_2_start {
+                break;
+            }
+
+            let last_key = node_1.get_last_key(&miner);
+            let parent_block_opt = node_1.get_last_anchored_block(&miner);
+            node_1.add_key_register(&mut burn_block, &mut miner);
+
+            let (stacks_block, microblocks, block_commit_op) = node_1.mine_stacks_block(&mut burn_node_1, &mut miner, &mut burn_block, &last_key, parent_block_opt.as_ref(), 1000, |mut builder, ref mut miner| {
+                let mut miner_chainstate = open_chainstate(false, 0x80000000, &format!("{}-1", test_name));
+                miner_chainstate.pox_first_block_height = pox_first_block_height;
+                miner_chainstate.pox_reward_cycle_length = pox_reward_cycle_length;
+
+                let mut epoch = builder.epoch_begin(&mut miner_chainstate).unwrap();
+                let (stacks_block, microblocks) = mine_empty_anchored_block(&mut epoch, &mut builder, miner, 0, None);
+                builder.epoch_finish(epoch);
+                (stacks_block, microblocks)
+            });
+
+            if block_commit_op.block_height == reward_cycle_1_start {
+                assert_eq!(block_commit_op.commit_outs, vec![reward_addr.clone()]);
+            }
+            else {
+                assert_eq!(block_commit_op.commit_outs, vec![]);
+            }
+
+            fork_1.append_block(burn_block.clone());
+            fork_2.append_block(burn_block);
+            let fork_snapshot_1 = burn_node_1.mine_fork(&mut fork_1);
+            let fork_snapshot_2 = burn_node_2.mine_fork(&mut fork_2);
+
+            // both nodes accept the same commits, no matter which Stacks blocks they processed
+            assert!(fork_snapshot_1.sortition);
+            assert_eq!(fork_snapshot_1.winning_stacks_block_hash, stacks_block.block_hash());
+            assert_eq!(fork_snapshot_1.consensus_hash, fork_snapshot_2.consensus_hash);
+            assert_eq!(fork_snapshot_1.burn_header_hash, fork_snapshot_2.burn_header_hash);
+
+            assert_eq!(preprocess_stacks_block_data(&mut node_1, &mut burn_node_1, &fork_snapshot_1, &stacks_block, &microblocks, &block_commit_op), Some(true));
+
+            let tip_info_list = node_1.chainstate.process_blocks(1).unwrap();
+            assert_eq!(tip_info_list.len(), 1);
+            assert!(tip_info_list[0].0.is_some());
+
+            mined_blocks.push((stacks_block, microblocks, block_commit_op));
+            last_snapshot_2 = Some(fork_snapshot_2);
+        }
+
+        // node 2 now discovers and processes all of the Stacks blocks, and arrives at the same
+        // chain tip as node 1
+        let last_snapshot_2 = last_snapshot_2.unwrap();
+        for (stacks_block, microblocks, block_commit_op) in mined_blocks.iter() {
+            assert_eq!(preprocess_stacks_block_data(&mut node_2, &mut burn_node_2, &last_snapshot_2, stacks_block, microblocks, block_commit_op), Some(true));
+        }
+
+        let tip_info_list = node_2.chainstate.process_blocks(mined_blocks.len()).unwrap();
+        assert_eq!(tip_info_list.len(), mined_blocks.len());
+        for ((chain_tip_opt, poison_opt), (stacks_block, _, _)) in tip_info_list.into_iter().zip(mined_blocks.iter()) {
+            assert!(poison_opt.is_none());
+            let chain_tip = chain_tip_opt.unwrap();
+            assert_eq!(chain_tip.anchored_header.block_hash(), stacks_block.block_hash());
+            assert!(check_block_state_index_root(&mut node_2.chainstate, &chain_tip.burn_header_hash, &stacks_block.header));
+        }
+    }
+
     // TODO: (BLOCKED) build off of different points in the same microblock stream
     // TODO; skipped blocks
     // TODO: missing blocks
@@ -22,6 +22,7 @@ pub mod auth;
 pub mod block;
 pub mod db;
 pub mod index;
+pub mod mempool;
 pub mod miner;
 pub mod transaction;
 
@@ -50,12 +51,19 @@ use burnchains::Txid;
 use burnchains::BurnchainHeaderHash;
 
 use chainstate::burn::BlockHeaderHash;
-use chainstate::burn::operations::LeaderBlockCommitOp;
+use chainstate::burn::operations::{
+    LeaderBlockCommitOp,
+    BlockstackOperationType,
+    TransferStxOp,
+    StackStxOp
+};
 
 use chainstate::stacks::index::{TrieHash, TRIEHASH_ENCODED_SIZE};
 use chainstate::stacks::index::Error as marf_error;
 use chainstate::stacks::db::StacksHeaderInfo;
 use chainstate::stacks::db::accounts::MinerReward;
+use chainstate::stacks::db::genesis::GenesisVestingUnlock;
+use chainstate::stacks::db::pox::PoxRewardSlot;
 
 use net::StacksMessageCodec;
 use net::codec::{read_next, write_next};
@@ -70,6 +78,7 @@ use vm::types::{
 
 use vm::representations::{ContractName, ClarityName};
 use vm::clarity::Error as clarity_error;
+use vm::costs::ExecutionCost;
 
 pub type StacksPublicKey = secp256k1::Secp256k1PublicKey;
 pub type StacksPrivateKey = secp256k1::Secp256k1PrivateKey;
@@ -115,10 +124,14 @@ pub enum Error {
     InvalidStacksTransaction(String),
     PostConditionFailed(String),
     NoSuchBlockError,
+    BlockPrunedError(u64, u64),
     InvalidChainstateDB,
     BlockTooBigError,
+    BlockCostExceeded,
     MicroblockStreamTooLongError,
     IncompatibleSpendingConditionError,
+    InvalidGenesisAllocation(String),
+    InvalidChainstateSnapshot(String),
     ClarityError(clarity_error),
     DBError(db_error),
     NetError(net_error),
@@ -134,10 +147,14 @@ impl fmt::Display for Error {
             Error::InvalidStacksTransaction(ref s) => fmt::Display::fmt(s, f),
             Error::PostConditionFailed(ref s) => fmt::Display::fmt(s, f),
             Error::NoSuchBlockError => write!(f, "No such Stacks block"),
+            Error::BlockPrunedError(ref height, ref horizon) => write!(f, "Stacks block at height {} was pruned (history is only kept from height {})", height, horizon),
             Error::InvalidChainstateDB => write!(f, "Invalid chainstate database"),
             Error::BlockTooBigError => write!(f, "Too much data in block"),
+            Error::BlockCostExceeded => write!(f, "Block execution budget exceeded"),
             Error::MicroblockStreamTooLongError => write!(f, "Too many microblocks in stream"),
             Error::IncompatibleSpendingConditionError => write!(f, "Spending condition is incompatible with this operation"),
+            Error::InvalidGenesisAllocation(ref s) => fmt::Display::fmt(s, f),
+            Error::InvalidChainstateSnapshot(ref s) => fmt::Display::fmt(s, f),
             Error::ClarityError(ref e) => fmt::Display::fmt(e, f),
             Error::DBError(ref e) => fmt::Display::fmt(e, f),
             Error::NetError(ref e) => fmt::Display::fmt(e, f),
@@ -155,10 +172,14 @@ impl error::Error for Error {
             Error::InvalidStacksTransaction(ref _s) => None,
             Error::PostConditionFailed(ref _s) => None,
             Error::NoSuchBlockError => None,
+            Error::BlockPrunedError(ref _height, ref _horizon) => None,
             Error::InvalidChainstateDB => None,
             Error::BlockTooBigError => None,
+            Error::BlockCostExceeded => None,
             Error::MicroblockStreamTooLongError => None,
             Error::IncompatibleSpendingConditionError => None,
+            Error::InvalidGenesisAllocation(ref _s) => None,
+            Error::InvalidChainstateSnapshot(ref _s) => None,
             Error::ClarityError(ref e) => Some(e),
             Error::DBError(ref e) => Some(e),
             Error::NetError(ref e) => Some(e),
@@ -182,7 +203,7 @@ impl Txid {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy, Serialize, Deserialize)]
 pub struct StacksAddress {
     pub version: u8,
     pub bytes: Hash160
@@ -670,6 +691,12 @@ pub struct StacksBlockBuilder {
     prev_microblock_header: StacksMicroblockHeader,
     miner_privkey: StacksPrivateKey,
     miner_payouts: Option<Vec<MinerReward>>,
+    genesis_vesting_unlocks: Vec<GenesisVestingUnlock>,
+    burnchain_stx_ops: Vec<BlockstackOperationType>,
+    burn_block_height: u64,
+    pox_reward_slot: Option<PoxRewardSlot>,
+    pox_reward_address: Option<StacksAddress>,
+    execution_cost_limit: ExecutionCost,
     miner_id: usize
 }
 
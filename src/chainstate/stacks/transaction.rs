@@ -22,6 +22,7 @@ use std::io;
 use std::io::{Read, Write};
 
 use std::convert::TryFrom;
+use std::fmt;
 
 use net::StacksMessageCodec;
 use net::Error as net_error;
@@ -36,10 +37,15 @@ use core::*;
 use net::StacksPublicKeyBuffer;
 use net::MAX_MESSAGE_LEN;
 
+use burnchains::Address;
+
 use util::hash::Sha512Trunc256Sum;
 use util::hash::to_hex;
 use util::secp256k1::MessageSignature;
 use util::retry::BoundReader;
+
+use serde_json::Value as JSONValue;
+use vm;
 use vm::{SymbolicExpression, SymbolicExpressionType, Value};
 use vm::ast::build_ast;
 use vm::types::{
@@ -232,6 +238,39 @@ impl TransactionPayload {
             (_, _) => None
         }
     }
+
+    /// Human-readable JSON encoding of this payload
+    pub fn to_json(&self) -> JSONValue {
+        match *self {
+            TransactionPayload::TokenTransfer(ref recipient, ref amount, ref memo) => json!({
+                "type": "token_transfer",
+                "recipient": recipient.to_string(),
+                "amount": amount,
+                "memo": memo.to_hex()
+            }),
+            TransactionPayload::ContractCall(ref cc) => json!({
+                "type": "contract_call",
+                "contract_address": cc.address.to_string(),
+                "contract_name": cc.contract_name.as_str(),
+                "function_name": cc.function_name.as_str(),
+                "function_args": cc.function_args.iter().map(|arg| arg.to_json()).collect::<Vec<JSONValue>>()
+            }),
+            TransactionPayload::SmartContract(ref sc) => json!({
+                "type": "smart_contract",
+                "name": sc.name.as_str(),
+                "code_body": sc.code_body.to_string()
+            }),
+            TransactionPayload::PoisonMicroblock(ref h1, ref h2) => json!({
+                "type": "poison_microblock",
+                "microblock_header_1": h1.to_json(),
+                "microblock_header_2": h2.to_json()
+            }),
+            TransactionPayload::Coinbase(ref payload) => json!({
+                "type": "coinbase",
+                "payload": payload.to_hex()
+            })
+        }
+    }
 }
 
 impl StacksMessageCodec for AssetInfo {
@@ -367,6 +406,188 @@ impl StacksMessageCodec for TransactionPostCondition {
     }
 }
 
+impl FungibleConditionCode {
+    /// Name of this condition code in the human-readable post-condition syntax
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FungibleConditionCode::SentEq => "eq",
+            FungibleConditionCode::SentGt => "gt",
+            FungibleConditionCode::SentGe => "ge",
+            FungibleConditionCode::SentLt => "lt",
+            FungibleConditionCode::SentLe => "le"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FungibleConditionCode> {
+        match name {
+            "eq" => Some(FungibleConditionCode::SentEq),
+            "gt" => Some(FungibleConditionCode::SentGt),
+            "ge" => Some(FungibleConditionCode::SentGe),
+            "lt" => Some(FungibleConditionCode::SentLt),
+            "le" => Some(FungibleConditionCode::SentLe),
+            _ => None
+        }
+    }
+}
+
+impl NonfungibleConditionCode {
+    /// Name of this condition code in the human-readable post-condition syntax
+    pub fn name(&self) -> &'static str {
+        match *self {
+            NonfungibleConditionCode::Sent => "sent",
+            NonfungibleConditionCode::NotSent => "not-sent"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<NonfungibleConditionCode> {
+        match name {
+            "sent" => Some(NonfungibleConditionCode::Sent),
+            "not-sent" => Some(NonfungibleConditionCode::NotSent),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for PostConditionPrincipal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PostConditionPrincipal::Origin => write!(f, "origin"),
+            PostConditionPrincipal::Standard(ref addr) => write!(f, "{}", addr.to_string()),
+            PostConditionPrincipal::Contract(ref addr, ref contract_name) => write!(f, "{}.{}", addr.to_string(), contract_name.as_str())
+        }
+    }
+}
+
+impl PostConditionPrincipal {
+    /// Parse `origin`, a standard principal's address, or a contract principal's `address.contract-name`
+    pub fn from_string(s: &str) -> Option<PostConditionPrincipal> {
+        if s == "origin" {
+            return Some(PostConditionPrincipal::Origin);
+        }
+        match s.find('.') {
+            Some(ix) => {
+                let addr = StacksAddress::from_string(&s[0..ix].to_string())?;
+                let contract_name = ContractName::try_from(s[ix+1..].to_string()).ok()?;
+                Some(PostConditionPrincipal::Contract(addr, contract_name))
+            },
+            None => {
+                let addr = StacksAddress::from_string(&s.to_string())?;
+                Some(PostConditionPrincipal::Standard(addr))
+            }
+        }
+    }
+}
+
+impl fmt::Display for AssetInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}::{}", self.contract_address.to_string(), self.contract_name.as_str(), self.asset_name.as_str())
+    }
+}
+
+impl AssetInfo {
+    /// Parse an asset identifier of the form `address.contract-name::asset-name`
+    pub fn from_string(s: &str) -> Option<AssetInfo> {
+        let ix_name = s.find("::")?;
+        let ix_contract = s[0..ix_name].find('.')?;
+        let contract_address = StacksAddress::from_string(&s[0..ix_contract].to_string())?;
+        let contract_name = ContractName::try_from(s[ix_contract+1..ix_name].to_string()).ok()?;
+        let asset_name = ClarityName::try_from(s[ix_name+2..].to_string()).ok()?;
+        Some(AssetInfo {
+            contract_address,
+            contract_name,
+            asset_name
+        })
+    }
+}
+
+/// Post-conditions are written as `principal asset condition-code amount-or-value`, e.g.:
+/// * `origin stx le 1000`
+/// * `SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7.foo ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.tokens::stackaroos gt 10`
+/// * `origin ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.names::name not-sent (tuple (name 0x616c696365) (namespace 0x626e73))`
+/// The asset `stx` denotes an STX post-condition.  A non-fungible asset's value is a Clarity
+/// expression, and takes up the rest of the string.
+impl fmt::Display for TransactionPostCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransactionPostCondition::STX(ref principal, ref condition_code, ref amount) => {
+                write!(f, "{} stx {} {}", principal, condition_code.name(), amount)
+            },
+            TransactionPostCondition::Fungible(ref principal, ref asset_info, ref condition_code, ref amount) => {
+                write!(f, "{} {} {} {}", principal, asset_info, condition_code.name(), amount)
+            },
+            TransactionPostCondition::Nonfungible(ref principal, ref asset_info, ref asset_value, ref condition_code) => {
+                write!(f, "{} {} {} {}", principal, asset_info, condition_code.name(), asset_value)
+            }
+        }
+    }
+}
+
+impl TransactionPostCondition {
+    /// Parse a post-condition written in the syntax of its Display implementation
+    pub fn from_string(s: &str) -> Result<TransactionPostCondition, net_error> {
+        let parts : Vec<&str> = s.trim().splitn(4, char::is_whitespace).collect();
+        if parts.len() != 4 {
+            return Err(net_error::DeserializeError(format!("Failed to parse post-condition '{}': expected `principal asset condition-code amount-or-value`", s)));
+        }
+
+        let principal = PostConditionPrincipal::from_string(parts[0])
+            .ok_or(net_error::DeserializeError(format!("Failed to parse post-condition principal '{}'", parts[0])))?;
+
+        if parts[1] == "stx" {
+            let condition_code = FungibleConditionCode::from_name(parts[2])
+                .ok_or(net_error::DeserializeError(format!("Failed to parse STX condition code '{}'", parts[2])))?;
+            let amount = parts[3].trim().parse::<u64>()
+                .map_err(|_e| net_error::DeserializeError(format!("Failed to parse STX amount '{}'", parts[3])))?;
+            return Ok(TransactionPostCondition::STX(principal, condition_code, amount));
+        }
+
+        let asset_info = AssetInfo::from_string(parts[1])
+            .ok_or(net_error::DeserializeError(format!("Failed to parse asset identifier '{}'", parts[1])))?;
+
+        if let Some(condition_code) = FungibleConditionCode::from_name(parts[2]) {
+            let amount = parts[3].trim().parse::<u64>()
+                .map_err(|_e| net_error::DeserializeError(format!("Failed to parse fungible asset amount '{}'", parts[3])))?;
+            Ok(TransactionPostCondition::Fungible(principal, asset_info, condition_code, amount))
+        }
+        else if let Some(condition_code) = NonfungibleConditionCode::from_name(parts[2]) {
+            let asset_value = vm::execute(parts[3])
+                .map_err(|e| net_error::DeserializeError(format!("Failed to evaluate non-fungible asset value '{}': {:?}", parts[3], &e)))?
+                .ok_or(net_error::DeserializeError(format!("Non-fungible asset value '{}' did not evaluate to a value", parts[3])))?;
+            Ok(TransactionPostCondition::Nonfungible(principal, asset_info, asset_value, condition_code))
+        }
+        else {
+            Err(net_error::DeserializeError(format!("Failed to parse condition code '{}'", parts[2])))
+        }
+    }
+
+    /// Human-readable JSON encoding of this post-condition.  Principals and assets are written
+    /// the same way as in the Display implementation.
+    pub fn to_json(&self) -> JSONValue {
+        match *self {
+            TransactionPostCondition::STX(ref principal, ref condition_code, ref amount) => json!({
+                "type": "stx",
+                "principal": principal.to_string(),
+                "condition_code": condition_code.name(),
+                "amount": amount
+            }),
+            TransactionPostCondition::Fungible(ref principal, ref asset_info, ref condition_code, ref amount) => json!({
+                "type": "fungible",
+                "principal": principal.to_string(),
+                "asset": asset_info.to_string(),
+                "condition_code": condition_code.name(),
+                "amount": amount
+            }),
+            TransactionPostCondition::Nonfungible(ref principal, ref asset_info, ref asset_value, ref condition_code) => json!({
+                "type": "nonfungible",
+                "principal": principal.to_string(),
+                "asset": asset_info.to_string(),
+                "condition_code": condition_code.name(),
+                "asset_value": asset_value.to_json()
+            })
+        }
+    }
+}
+
 impl StacksMessageCodec for StacksTransaction {
     fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), net_error> {
         write_next(fd, &(self.version as u8))?;
@@ -544,6 +765,22 @@ impl StacksTransaction {
         Txid::from_stacks_tx(&bytes)
     }
     
+    /// Human-readable JSON encoding of this transaction.  Signer addresses are encoded for
+    /// mainnet or testnet according to the transaction's version.
+    pub fn to_json(&self) -> JSONValue {
+        let mainnet = self.version == TransactionVersion::Mainnet;
+        json!({
+            "txid": self.txid().to_hex(),
+            "version": format!("{:?}", &self.version),
+            "chain_id": self.chain_id,
+            "auth": self.auth.to_json(mainnet),
+            "anchor_mode": format!("{:?}", &self.anchor_mode),
+            "post_condition_mode": format!("{:?}", &self.post_condition_mode),
+            "post_conditions": self.post_conditions.iter().map(|pc| pc.to_json()).collect::<Vec<JSONValue>>(),
+            "payload": self.payload.to_json()
+        })
+    }
+
     /// Get a mutable reference to the internal auth structure
     pub fn borrow_auth(&mut self) -> &mut TransactionAuth {
         &mut self.auth
@@ -749,6 +986,36 @@ impl StacksTransactionSigner {
         })
     }
 
+    /// Resume signing a transaction that already has some of its signatures.  This is how a
+    /// transaction gets signed by keys that are not available in-process -- e.g. in an HSM --
+    /// since each signer can take its turn separately.
+    pub fn new_partial(tx: &StacksTransaction) -> Result<StacksTransactionSigner, net_error> {
+        let initial_sighash = tx.sign_begin();
+        let (sighash, origin_done) = match tx.auth {
+            TransactionAuth::Standard(ref origin_condition) => {
+                (origin_condition.next_sighash(&initial_sighash, &TransactionAuthFlags::AuthStandard)?, false)
+            },
+            TransactionAuth::Sponsored(ref origin_condition, ref sponsor_condition) => {
+                let origin_sighash = origin_condition.next_sighash(&initial_sighash, &TransactionAuthFlags::AuthStandard)?;
+                if origin_condition.num_signatures() >= origin_condition.signatures_required() && *sponsor_condition != TransactionSpendingCondition::new_initial_sighash() {
+                    // origin is done, and the sponsor has been set
+                    (sponsor_condition.next_sighash(&origin_sighash, &TransactionAuthFlags::AuthSponsored)?, true)
+                }
+                else {
+                    (origin_sighash, false)
+                }
+            }
+        };
+
+        Ok(StacksTransactionSigner {
+            tx: tx.clone(),
+            sighash,
+            origin_done,
+            check_oversign: true,
+            check_overlap: true
+        })
+    }
+
     pub fn resume(&mut self, tx: &StacksTransaction) -> () {
         self.tx = tx.clone()
     }
@@ -811,6 +1078,60 @@ impl StacksTransactionSigner {
         self.tx.append_next_sponsor(pubk)
     }
 
+    /// Is the sponsor signing next (as opposed to the origin)?
+    pub fn is_sponsor_next(&self) -> bool {
+        self.origin_done
+    }
+
+    /// Get the exact hash that the next origin or sponsor key must sign, which commits to the
+    /// auth flag, fee rate and nonce of the spending condition being signed.
+    pub fn get_presign_sighash(&self) -> Txid {
+        let (condition, cond_code) = match self.tx.auth {
+            TransactionAuth::Sponsored(_, ref sponsor_condition) if self.origin_done => {
+                (sponsor_condition, TransactionAuthFlags::AuthSponsored)
+            },
+            _ => {
+                (self.tx.auth.origin(), TransactionAuthFlags::AuthStandard)
+            }
+        };
+        TransactionSpendingCondition::make_sighash_presign(&self.sighash, &cond_code, condition.fee_rate(), condition.nonce())
+    }
+
+    /// Append a signature over get_presign_sighash() that was made elsewhere by the origin key
+    /// whose public key hashes to `pubkey_hash`.
+    pub fn append_origin_signature(&mut self, sig: &MessageSignature, pubkey_hash: &Hash160) -> Result<(), net_error> {
+        if self.check_overlap && self.origin_done {
+            return Err(net_error::SigningError("Cannot sign origin after sponsor key".to_string()));
+        }
+
+        let next_sighash = match self.tx.auth {
+            TransactionAuth::Standard(ref mut origin_condition) => {
+                origin_condition.append_signature(&self.sighash, &TransactionAuthFlags::AuthStandard, sig, pubkey_hash)?
+            },
+            TransactionAuth::Sponsored(ref mut origin_condition, _) => {
+                origin_condition.append_signature(&self.sighash, &TransactionAuthFlags::AuthStandard, sig, pubkey_hash)?
+            }
+        };
+        self.sighash = next_sighash;
+        Ok(())
+    }
+
+    /// Append a signature over get_presign_sighash() that was made elsewhere by the sponsor key
+    /// whose public key hashes to `pubkey_hash`.
+    pub fn append_sponsor_signature(&mut self, sig: &MessageSignature, pubkey_hash: &Hash160) -> Result<(), net_error> {
+        let next_sighash = match self.tx.auth {
+            TransactionAuth::Sponsored(_, ref mut sponsor_condition) => {
+                sponsor_condition.append_signature(&self.sighash, &TransactionAuthFlags::AuthSponsored, sig, pubkey_hash)?
+            },
+            _ => {
+                return Err(net_error::SigningError("Cannot sign standard authorization with a sponsoring key".to_string()));
+            }
+        };
+        self.sighash = next_sighash;
+        self.origin_done = true;
+        Ok(())
+    }
+
     pub fn pop_origin_auth_field(&mut self) -> Option<TransactionAuthField> {
         match self.tx.auth {
             TransactionAuth::Standard(ref mut origin_condition) => StacksTransaction::pop_auth_field(origin_condition),
@@ -864,6 +1185,8 @@ mod test {
 
     use chainstate::stacks::StacksPublicKey as PubKey;
 
+    use burnchains::{PrivateKey, PublicKey};
+
     use util::log;
     use util::hash::*;
     use util::retry::BoundReader;
@@ -1775,6 +2098,54 @@ mod test {
         }
     }
 
+    #[test]
+    fn tx_stacks_postcondition_strings() {
+        let addr = StacksAddress { version: 26, bytes: Hash160([0xff; 20]) };
+        let contract_addr = StacksAddress { version: 22, bytes: Hash160([0x01; 20]) };
+        let asset_info = AssetInfo {
+            contract_address: contract_addr.clone(),
+            contract_name: ContractName::try_from("tokens").unwrap(),
+            asset_name: ClarityName::try_from("stackaroos").unwrap()
+        };
+
+        let pcs = vec![
+            TransactionPostCondition::STX(PostConditionPrincipal::Origin, FungibleConditionCode::SentLe, 1000),
+            TransactionPostCondition::Fungible(PostConditionPrincipal::Standard(addr.clone()), asset_info.clone(), FungibleConditionCode::SentGt, 10),
+            TransactionPostCondition::Fungible(PostConditionPrincipal::Contract(addr.clone(), ContractName::try_from("foo").unwrap()), asset_info.clone(), FungibleConditionCode::SentEq, 0),
+            TransactionPostCondition::Nonfungible(PostConditionPrincipal::Origin, asset_info.clone(), Value::UInt(1), NonfungibleConditionCode::NotSent),
+            TransactionPostCondition::Nonfungible(PostConditionPrincipal::Standard(addr.clone()), asset_info.clone(), Value::buff_from(vec![0, 1, 2, 3]).unwrap(), NonfungibleConditionCode::Sent),
+        ];
+
+        for pc in pcs.iter() {
+            let pc_str = format!("{}", pc);
+            assert_eq!(TransactionPostCondition::from_string(&pc_str).unwrap(), *pc);
+        }
+
+        assert_eq!(format!("{}", pcs[0]), "origin stx le 1000");
+        assert_eq!(format!("{}", pcs[1]), format!("{} {}.tokens::stackaroos gt 10", addr.to_string(), contract_addr.to_string()));
+
+        // non-fungible values are Clarity expressions
+        let pc = TransactionPostCondition::from_string(&format!("origin {} sent (tuple (a 1) (b u2))", &asset_info)).unwrap();
+        match pc {
+            TransactionPostCondition::Nonfungible(_, _, Value::Tuple(_), NonfungibleConditionCode::Sent) => {},
+            _ => panic!("Expected non-fungible tuple post-condition, got {:?}", &pc)
+        }
+
+        let invalid = vec![
+            "origin stx le".to_string(),
+            "nobody stx le 1000".to_string(),
+            "origin stx sent 1000".to_string(),
+            "origin stx le -1".to_string(),
+            format!("origin {}.tokens le 1000", contract_addr.to_string()),
+            format!("origin {} le u1", &asset_info),
+            format!("origin {} sent (+ 1", &asset_info),
+            format!("origin {} ne 1", &asset_info),
+        ];
+        for pc_str in invalid.iter() {
+            assert!(TransactionPostCondition::from_string(pc_str).is_err(), "parsed {}", pc_str);
+        }
+    }
+
     #[test]
     fn tx_stacks_postcondition_invalid() {
         let addr = StacksAddress { version: 1, bytes: Hash160([0xff; 20]) };
@@ -2790,6 +3161,126 @@ mod test {
         }
     } 
     
+    fn sign_offline(tx_signer: &mut StacksTransactionSigner, privk: &StacksPrivateKey) -> () {
+        let presign_sighash = tx_signer.get_presign_sighash();
+        let sig = privk.sign(presign_sighash.as_bytes()).unwrap();
+        let pubkey_hash = Hash160::from_data(&StacksPublicKey::from_private(privk).to_bytes());
+        if tx_signer.is_sponsor_next() {
+            tx_signer.append_sponsor_signature(&sig, &pubkey_hash).unwrap();
+        }
+        else {
+            tx_signer.append_origin_signature(&sig, &pubkey_hash).unwrap();
+        }
+    }
+
+    #[test]
+    fn tx_stacks_transaction_sign_offline() {
+        let origin_privk = StacksPrivateKey::from_hex("807bbe9e471ac976592cc35e3056592ecc0f778ee653fced3b491a122dd8d59701").unwrap();
+
+        let privk_1 = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let privk_2 = StacksPrivateKey::from_hex("2a584d899fed1d24e26b524f202763c8ab30260167429f157f1c119f550fa6af").unwrap();
+        let privk_3 = StacksPrivateKey::from_hex("d5200dee706ee53ae98a03fba6cf4fdcc5084c30cfa9e1b3462dcdeaa3e0f1d2").unwrap();
+
+        let pubk_1 = StacksPublicKey::from_private(&privk_1);
+        let pubk_2 = StacksPublicKey::from_private(&privk_2);
+        let pubk_3 = StacksPublicKey::from_private(&privk_3);
+
+        // single-sig origin
+        let auth = TransactionAuth::Standard(TransactionSpendingCondition::new_singlesig_p2pkh(StacksPublicKey::from_private(&origin_privk)).unwrap());
+        for tx in tx_stacks_transaction_test_txs(&auth) {
+            let mut tx_signer = StacksTransactionSigner::new(&tx);
+            tx_signer.sign_origin(&origin_privk).unwrap();
+            let signed_tx = tx_signer.get_tx().unwrap();
+
+            let mut offline_signer = StacksTransactionSigner::new_partial(&tx).unwrap();
+            assert!(!offline_signer.is_sponsor_next());
+
+            // signature must come from the expected key
+            let presign_sighash = offline_signer.get_presign_sighash();
+            let sig = privk_1.sign(presign_sighash.as_bytes()).unwrap();
+            assert!(offline_signer.append_origin_signature(&sig, &Hash160::from_data(&StacksPublicKey::from_private(&origin_privk).to_bytes())).is_err());
+            assert!(offline_signer.append_origin_signature(&sig, &Hash160::from_data(&pubk_1.to_bytes())).is_err());
+
+            sign_offline(&mut offline_signer, &origin_privk);
+            let offline_signed_tx = offline_signer.get_tx().unwrap();
+
+            assert_eq!(offline_signed_tx, signed_tx);
+            assert!(offline_signed_tx.verify().unwrap());
+        }
+
+        // multisig origin, with each signer resuming from the last one's partially signed transaction
+        let auth = TransactionAuth::Standard(TransactionSpendingCondition::new_multisig_p2sh(2, vec![pubk_1.clone(), pubk_2.clone(), pubk_3.clone()]).unwrap());
+        for tx in tx_stacks_transaction_test_txs(&auth) {
+            let mut tx_signer = StacksTransactionSigner::new(&tx);
+            tx_signer.sign_origin(&privk_1).unwrap();
+            tx_signer.append_origin(&pubk_2).unwrap();
+            tx_signer.sign_origin(&privk_3).unwrap();
+            let signed_tx = tx_signer.get_tx().unwrap();
+
+            let mut offline_signer = StacksTransactionSigner::new_partial(&tx).unwrap();
+            sign_offline(&mut offline_signer, &privk_1);
+            offline_signer.append_origin(&pubk_2).unwrap();
+
+            let mut offline_signer = StacksTransactionSigner::new_partial(&offline_signer.get_tx_incomplete()).unwrap();
+            assert!(offline_signer.get_tx().is_none());
+            sign_offline(&mut offline_signer, &privk_3);
+            let offline_signed_tx = offline_signer.get_tx().unwrap();
+
+            assert_eq!(offline_signed_tx, signed_tx);
+            assert!(offline_signed_tx.verify().unwrap());
+
+            // can't oversign
+            let mut offline_signer = StacksTransactionSigner::new_partial(&offline_signed_tx).unwrap();
+            let sig = privk_2.sign(offline_signer.get_presign_sighash().as_bytes()).unwrap();
+            assert!(offline_signer.append_origin_signature(&sig, &Hash160::from_data(&pubk_2.to_bytes())).is_err());
+        }
+
+        // sponsored, with a multisig sponsor
+        let random_sponsor = StacksPrivateKey::new();
+        let auth = TransactionAuth::Sponsored(
+            TransactionSpendingCondition::new_singlesig_p2pkh(StacksPublicKey::from_private(&origin_privk)).unwrap(),
+            TransactionSpendingCondition::new_singlesig_p2pkh(StacksPublicKey::from_private(&random_sponsor)).unwrap(),
+        );
+        let real_sponsor = TransactionSpendingCondition::new_multisig_p2sh(2, vec![pubk_1.clone(), pubk_2.clone(), pubk_3.clone()]).unwrap();
+
+        for mut tx in tx_stacks_transaction_test_txs(&auth) {
+            tx.auth = tx.auth.into_initial_sighash_auth();
+
+            let mut tx_signer = StacksTransactionSigner::new(&tx);
+            tx_signer.sign_origin(&origin_privk).unwrap();
+            let origin_tx = tx_signer.get_tx_incomplete();
+            let mut tx_signer = StacksTransactionSigner::new_sponsor(&origin_tx, real_sponsor.clone()).unwrap();
+            tx_signer.sign_sponsor(&privk_1).unwrap();
+            tx_signer.sign_sponsor(&privk_2).unwrap();
+            tx_signer.append_sponsor(&pubk_3).unwrap();
+            let signed_tx = tx_signer.get_tx().unwrap();
+
+            let mut offline_signer = StacksTransactionSigner::new_partial(&tx).unwrap();
+            assert!(!offline_signer.is_sponsor_next());
+            sign_offline(&mut offline_signer, &origin_privk);
+            let offline_origin_tx = offline_signer.get_tx_incomplete();
+            assert_eq!(offline_origin_tx, origin_tx);
+
+            // origin is done, but there's no sponsor yet
+            let offline_signer = StacksTransactionSigner::new_partial(&offline_origin_tx).unwrap();
+            assert!(!offline_signer.is_sponsor_next());
+
+            let sponsor_signer = StacksTransactionSigner::new_sponsor(&offline_origin_tx, real_sponsor.clone()).unwrap();
+            let mut offline_signer = StacksTransactionSigner::new_partial(&sponsor_signer.get_tx_incomplete()).unwrap();
+            assert!(offline_signer.is_sponsor_next());
+            sign_offline(&mut offline_signer, &privk_1);
+
+            let mut offline_signer = StacksTransactionSigner::new_partial(&offline_signer.get_tx_incomplete()).unwrap();
+            assert!(offline_signer.is_sponsor_next());
+            sign_offline(&mut offline_signer, &privk_2);
+            offline_signer.append_sponsor(&pubk_3).unwrap();
+            let offline_signed_tx = offline_signer.get_tx().unwrap();
+
+            assert_eq!(offline_signed_tx, signed_tx);
+            assert!(offline_signed_tx.verify().unwrap());
+        }
+    }
+
     #[test]
     fn tx_stacks_transaction_sign_verify_sponsored_p2sh_mixed() {
         let origin_privk = StacksPrivateKey::from_hex("807bbe9e471ac976592cc35e3056592ecc0f778ee653fced3b491a122dd8d59701").unwrap();
@@ -3208,6 +3699,63 @@ mod test {
         }
     } 
     
+    #[test]
+    fn tx_stacks_transaction_to_json() {
+        let privk = StacksPrivateKey::from_hex("6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001").unwrap();
+        let pubk_1 = StacksPublicKey::from_hex("02b30fafab3a12372c5d150d567034f37d60a91168009a779498168b0e9d8ec7f2").unwrap();
+        let pubk_2 = StacksPublicKey::from_hex("03ce61f1d155738a5e434fc8a61c3e104f891d1ec71576e8ad85abb68b34670d35").unwrap();
+
+        let origin_auth = TransactionSpendingCondition::new_singlesig_p2pkh(StacksPublicKey::from_private(&privk)).unwrap();
+        let sponsor_auth = TransactionSpendingCondition::new_multisig_p2sh(2, vec![pubk_1.clone(), pubk_2.clone()]).unwrap();
+
+        // every kind of transaction has an encoding
+        for auth in [TransactionAuth::Standard(origin_auth.clone()), TransactionAuth::Sponsored(origin_auth.clone(), sponsor_auth.clone())].iter() {
+            for tx in tx_stacks_transaction_test_txs(auth) {
+                let tx_json = tx.to_json();
+                assert_eq!(tx_json["txid"], json!(tx.txid().to_hex()));
+                assert_eq!(tx_json["post_conditions"].as_array().unwrap().len(), tx.post_conditions.len());
+            }
+        }
+
+        let recipient = StacksAddress { version: C32_ADDRESS_VERSION_TESTNET_SINGLESIG, bytes: Hash160([0x11; 20]) };
+        let mut tx = StacksTransaction::new(TransactionVersion::Testnet,
+                                            TransactionAuth::Sponsored(origin_auth.clone(), sponsor_auth.clone()),
+                                            TransactionPayload::TokenTransfer(recipient.clone(), 123, TokenTransferMemo([0u8; 34])));
+        tx.add_post_condition(TransactionPostCondition::STX(PostConditionPrincipal::Origin, FungibleConditionCode::SentLe, 123));
+
+        let tx_json = tx.to_json();
+        assert_eq!(tx_json["version"], json!("Testnet"));
+        assert_eq!(tx_json["auth"]["type"], json!("sponsored"));
+        assert_eq!(tx_json["auth"]["origin"]["address"], json!(origin_auth.address_testnet().to_string()));
+        assert_eq!(tx_json["auth"]["origin"]["hash_mode"], json!("P2PKH"));
+        assert_eq!(tx_json["auth"]["sponsor"]["address"], json!(sponsor_auth.address_testnet().to_string()));
+        assert_eq!(tx_json["auth"]["sponsor"]["signatures_required"], json!(2));
+        assert_eq!(tx_json["post_conditions"][0], json!({
+            "type": "stx",
+            "principal": "origin",
+            "condition_code": "le",
+            "amount": 123
+        }));
+        assert_eq!(tx_json["payload"], json!({
+            "type": "token_transfer",
+            "recipient": recipient.to_string(),
+            "amount": 123,
+            "memo": to_hex(&[0u8; 34])
+        }));
+
+        let contract_call = TransactionPayload::new_contract_call(recipient.clone(), "hello-world", "add", vec![Value::Int(1), Value::some(Value::UInt(2))]).unwrap();
+        assert_eq!(contract_call.to_json(), json!({
+            "type": "contract_call",
+            "contract_address": recipient.to_string(),
+            "contract_name": "hello-world",
+            "function_name": "add",
+            "function_args": [
+                { "type": "int", "value": "1" },
+                { "type": "(optional uint)", "value": { "type": "uint", "value": "2" } }
+            ]
+        }));
+    }
+
     // TODO: test with different tx versions 
     // TODO: test error values for signing and verifying
 }
@@ -49,7 +49,22 @@ pub const EMPTY_MICROBLOCK_PARENT_HASH : BlockHeaderHash = BlockHeaderHash([0u8;
 pub const BOOT_BLOCK_HASH : BlockHeaderHash = BlockHeaderHash([0xff; 32]);
 pub const BURNCHAIN_BOOT_BLOCK_HASH : BurnchainHeaderHash = BurnchainHeaderHash([0xff; 32]);
 
-pub const CHAINSTATE_VERSION: &'static str = "23.0.0.0";
+pub const CHAINSTATE_VERSION: &'static str = "23.0.0.2";
+
+// transaction fees: a transaction pays its fee rate once per byte of its encoding, and once per
+// unit of execution cost it consumes.  One unit of execution cost is one byte written, or this
+// much Clarity runtime.
+pub const TX_FEE_RUNTIME_COST_PER_UNIT : u64 = 1000;
+
+// minimum fee rate (microSTX per byte) a transaction must pay for the mempool to accept and relay it
+pub const MEMPOOL_MIN_RELAY_FEE_RATE : u64 = 1;
+
+// length of a PoX reward cycle, in burnchain blocks.  Each reward address gets one block per cycle.
+#[cfg(test)]
+pub const POX_REWARD_CYCLE_LENGTH : u32 = 5;       // small for testing purposes
+
+#[cfg(not(test))]
+pub const POX_REWARD_CYCLE_LENGTH : u32 = 2016;    // about two weeks of Bitcoin blocks
 
 /// Synchronize burn transactions from the Bitcoin blockchain 
 pub fn sync_burnchain_bitcoin(working_dir: &String, network_name: &String) -> Result<u64, burnchain_error> {
@@ -38,6 +38,9 @@ extern crate regex;
 extern crate time;
 extern crate byteorder;
 extern crate mio;
+extern crate aes_gcm;
+extern crate hmac;
+extern crate pbkdf2;
 
 #[macro_use] extern crate serde_derive;
 #[macro_use] extern crate serde_json;
@@ -60,9 +60,15 @@ use util::log;
 
 use net::StacksMessageCodec;
 use chainstate::stacks::*;
+use chainstate::stacks::db::MIN_PRUNE_DEPTH;
 use util::hash::{hex_bytes, to_hex};
 use util::retry::LogReader;
 
+/// Should a decode-* command print JSON instead of its debug representation?
+fn json_output(argv: &Vec<String>) -> bool {
+    argv.len() > 3 && argv[3] == "--json"
+}
+
 fn main() {
 
     log::set_loglevel(log::LOG_DEBUG).unwrap();
@@ -99,7 +105,7 @@ fn main() {
 
     if argv[1] == "decode-tx" {
         if argv.len() < 3 {
-            eprintln!("Usage: {} decode-tx TRANSACTION", argv[0]);
+            eprintln!("Usage: {} decode-tx TRANSACTION [--json]", argv[0]);
             process::exit(1);
         }
 
@@ -121,13 +127,18 @@ fn main() {
             process::exit(1);
         }).unwrap();
 
-        println!("{:#?}", &tx);
+        if json_output(&argv) {
+            println!("{}", serde_json::to_string_pretty(&tx.to_json()).unwrap());
+        }
+        else {
+            println!("{:#?}", &tx);
+        }
         process::exit(0);
     }
 
     if argv[1] == "decode-block" {
         if argv.len() < 3 {
-            eprintln!("Usage: {} decode-block BLOCK_PATH", argv[0]);
+            eprintln!("Usage: {} decode-block BLOCK_PATH [--json]", argv[0]);
             process::exit(1);
         }
 
@@ -139,13 +150,18 @@ fn main() {
             process::exit(1);
         }).unwrap();
 
-        println!("{:#?}", &block);
+        if json_output(&argv) {
+            println!("{}", serde_json::to_string_pretty(&block.to_json()).unwrap());
+        }
+        else {
+            println!("{:#?}", &block);
+        }
         process::exit(0);
     }
 
     if argv[1] == "decode-microblocks" {
         if argv.len() < 3 {
-            eprintln!("Usage: {} decode-microblocks MICROBLOCK_STREAM_PATH", argv[0]);
+            eprintln!("Usage: {} decode-microblocks MICROBLOCK_STREAM_PATH [--json]", argv[0]);
             process::exit(1);
         }
 
@@ -157,7 +173,13 @@ fn main() {
             process::exit(1);
         }).unwrap();
 
-        println!("{:#?}", &mblocks);
+        if json_output(&argv) {
+            let mblocks_json : Vec<serde_json::Value> = mblocks.iter().map(|mblock| mblock.to_json()).collect();
+            println!("{}", serde_json::to_string_pretty(&mblocks_json).unwrap());
+        }
+        else {
+            println!("{:#?}", &mblocks);
+        }
         process::exit(0);
     }
 
@@ -194,10 +216,32 @@ fn main() {
             chain: "bitcoin".to_string(),
             burnchain_path: format!("/tmp/{}/burnchain", testnet_id),
             burnchain_block_time: 2000,
+            genesis_allocation_path: argv.get(2).cloned(),
             node_config: vec![testnet::NodeConfig {
                 name: "L1".to_string(),
                 path: format!("/tmp/{}/L1", testnet_id),
-                mem_pool_path: format!("/tmp/{}/L1/mempool", testnet_id)
+                mem_pool_path: format!("/tmp/{}/L1/mempool", testnet_id),
+                microblock_frequency: 1000,
+                max_microblock_size: 65536,
+                poison_microblock_key: None,
+                mnemonic: None,
+                // the miner's account key can be loaded from a keystore made with `blockstack-cli keystore-create`
+                keystore: env::var("STACKS_NODE_KEYSTORE").ok()
+                    .map(|path| (path, env::var("STACKS_KEYSTORE_PASSPHRASE").unwrap_or_default())),
+                // ...and its first VRF key from one made with `blockstack-cli keystore-create-vrf`
+                vrf_keystore: env::var("STACKS_NODE_VRF_KEYSTORE").ok()
+                    .map(|path| (path, env::var("STACKS_KEYSTORE_PASSPHRASE").unwrap_or_default())),
+                // keep only this many recent blocks' data, if set
+                prune_depth: env::var("STACKS_NODE_PRUNE_DEPTH").ok()
+                    .map(|depth| {
+                        let depth : u64 = depth.parse().expect("FATAL: STACKS_NODE_PRUNE_DEPTH must be a number of blocks");
+                        if depth < MIN_PRUNE_DEPTH {
+                            panic!("FATAL: STACKS_NODE_PRUNE_DEPTH must be at least {} blocks, so a burnchain reorg can't need pruned blocks", MIN_PRUNE_DEPTH);
+                        }
+                        depth
+                    }),
+                // index each account's transaction history, if set to 1
+                account_history: env::var("STACKS_NODE_ACCOUNT_HISTORY").map(|v| v == "1").unwrap_or(false)
             }]
         };
         
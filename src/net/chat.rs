@@ -1017,6 +1017,7 @@ mod test {
             stable_confirmations: 7,
             first_block_height: 12300,
             first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5,
         };
 
         let mut chain_view = BurnchainView {
@@ -1123,6 +1124,7 @@ mod test {
             stable_confirmations: 7,
             first_block_height: 12300,
             first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5,
         };
 
         let mut chain_view = BurnchainView {
@@ -1215,6 +1217,7 @@ mod test {
             stable_confirmations: 7,
             first_block_height: 12300,
             first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5,
         };
 
         let mut chain_view = BurnchainView {
@@ -1298,6 +1301,7 @@ mod test {
             stable_confirmations: 7,
             first_block_height: 12300,
             first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5,
         };
 
         let mut chain_view = BurnchainView {
@@ -1390,6 +1394,7 @@ mod test {
             stable_confirmations: 7,
             first_block_height: 12300,
             first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5,
         };
 
         let mut chain_view = BurnchainView {
@@ -1486,6 +1491,7 @@ mod test {
             stable_confirmations: 7,
             first_block_height: 12300,
             first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5,
         };
 
         let mut chain_view = BurnchainView {
@@ -1628,6 +1634,7 @@ mod test {
             stable_confirmations: 7,
             first_block_height: 12300,
             first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5,
         };
 
         let mut chain_view = BurnchainView {
@@ -1716,6 +1723,7 @@ mod test {
             stable_confirmations: 7,
             first_block_height: 12300,
             first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5,
         };
 
         let mut chain_view = BurnchainView {
@@ -1006,11 +1006,12 @@ impl HttpRequestType {
 
     pub fn parse<R: Read>(protocol: &mut StacksHttp, preamble: &HttpRequestPreamble, fd: &mut R) -> Result<HttpRequestType, net_error> {
         // TODO: make this static somehow
-        let REQUEST_METHODS : [(&str, &Regex, &dyn Fn(&mut StacksHttp, &HttpRequestPreamble, &Regex, &mut R) -> Result<HttpRequestType, net_error>); 4] = [
+        let REQUEST_METHODS : [(&str, &Regex, &dyn Fn(&mut StacksHttp, &HttpRequestPreamble, &Regex, &mut R) -> Result<HttpRequestType, net_error>); 5] = [
             ("GET", &PATH_GETNEIGHBORS, &HttpRequestType::parse_getneighbors),
             ("GET", &PATH_GETBLOCK, &HttpRequestType::parse_getblock),
             ("GET", &PATH_GETMICROBLOCKS, &HttpRequestType::parse_getmicroblocks),
-            ("POST", &PATH_POSTTRANSACTION, &HttpRequestType::parse_posttransaction)
+            ("POST", &PATH_POSTTRANSACTION, &HttpRequestType::parse_posttransaction),
+            ("POST", &PATH_SIMULATETRANSACTION, &HttpRequestType::parse_simulatetransaction)
         ];
 
         for (verb, regex, parser) in REQUEST_METHODS.iter() {
@@ -1079,6 +1080,20 @@ impl HttpRequestType {
             return Err(net_error::DeserializeError("Invalid Http request: expected non-zero-length body for PostTransaction".to_string()));
         }
 
+        let tx = HttpRequestType::parse_transaction_body(preamble, fd)?;
+        Ok(HttpRequestType::PostTransaction(HttpRequestMetadata::from_preamble(preamble), tx))
+    }
+
+    fn parse_simulatetransaction<R: Read>(_protocol: &mut StacksHttp, preamble: &HttpRequestPreamble, _regex: &Regex, fd: &mut R) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() == 0 {
+            return Err(net_error::DeserializeError("Invalid Http request: expected non-zero-length body for SimulateTransaction".to_string()));
+        }
+
+        let tx = HttpRequestType::parse_transaction_body(preamble, fd)?;
+        Ok(HttpRequestType::SimulateTransaction(HttpRequestMetadata::from_preamble(preamble), tx))
+    }
+
+    fn parse_transaction_body<R: Read>(preamble: &HttpRequestPreamble, fd: &mut R) -> Result<StacksTransaction, net_error> {
         // content-type must be given, and must be application/octet-stream
         match preamble.content_type {
             None => {
@@ -1091,8 +1106,7 @@ impl HttpRequestType {
             }
         };
 
-        let tx = StacksTransaction::consensus_deserialize(fd)?;
-        Ok(HttpRequestType::PostTransaction(HttpRequestMetadata::from_preamble(preamble), tx))
+        StacksTransaction::consensus_deserialize(fd)
     }
 
     pub fn metadata(&self) -> &HttpRequestMetadata {
@@ -1101,6 +1115,7 @@ impl HttpRequestType {
             HttpRequestType::GetBlock(ref md, _) => md,
             HttpRequestType::GetMicroblocks(ref md, _) => md,
             HttpRequestType::PostTransaction(ref md, _) => md,
+            HttpRequestType::SimulateTransaction(ref md, _) => md,
         }
     }
 
@@ -1121,6 +1136,13 @@ impl HttpRequestType {
 
                 HttpRequestPreamble::new_serialized(fd, "POST", "/v2/transactions", &md.peer, md.request_id, Some(tx_bytes.len() as u32), Some(&HttpContentType::Bytes), empty_headers)?;
                 fd.write_all(&tx_bytes).map_err(net_error::WriteError)?;
+            },
+            HttpRequestType::SimulateTransaction(ref md, ref tx) => {
+                let mut tx_bytes = vec![];
+                write_next(&mut tx_bytes, tx)?;
+
+                HttpRequestPreamble::new_serialized(fd, "POST", "/v2/transactions/simulate", &md.peer, md.request_id, Some(tx_bytes.len() as u32), Some(&HttpContentType::Bytes), empty_headers)?;
+                fd.write_all(&tx_bytes).map_err(net_error::WriteError)?;
             }
         }
         Ok(())
@@ -1283,11 +1305,12 @@ impl HttpResponseType {
         }
 
         // TODO: make this static somehow
-        let RESPONSE_METHODS : [(&Regex, &dyn Fn(&mut StacksHttp, &HttpResponsePreamble, &mut R, Option<usize>) -> Result<HttpResponseType, net_error>); 4] = [
+        let RESPONSE_METHODS : [(&Regex, &dyn Fn(&mut StacksHttp, &HttpResponsePreamble, &mut R, Option<usize>) -> Result<HttpResponseType, net_error>); 5] = [
             (&PATH_GETNEIGHBORS, &HttpResponseType::parse_neighbors),
             (&PATH_GETBLOCK, &HttpResponseType::parse_block),
             (&PATH_GETMICROBLOCKS, &HttpResponseType::parse_microblocks),
-            (&PATH_POSTTRANSACTION, &HttpResponseType::parse_txid)
+            (&PATH_POSTTRANSACTION, &HttpResponseType::parse_txid),
+            (&PATH_SIMULATETRANSACTION, &HttpResponseType::parse_transaction_simulation)
         ];
 
         for (regex, parser) in RESPONSE_METHODS.iter() {
@@ -1336,6 +1359,11 @@ impl HttpResponseType {
         Ok(HttpResponseType::TransactionID(HttpResponseMetadata::from_preamble(preamble), Txid::from_bytes(&txid_bytes).unwrap()))
     }
 
+    fn parse_transaction_simulation<R: Read>(_protocol: &mut StacksHttp, preamble: &HttpResponsePreamble, fd: &mut R, len_hint: Option<usize>) -> Result<HttpResponseType, net_error> {
+        let simulation = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::TransactionSimulation(HttpResponseMetadata::from_preamble(preamble), simulation))
+    }
+
     fn error_reason(code: u16) -> &'static str {
         match code {
             400 => "Bad Request",
@@ -1362,6 +1390,7 @@ impl HttpResponseType {
             HttpResponseType::Block(ref md, _) => md,
             HttpResponseType::Microblocks(ref md, _) => md,
             HttpResponseType::TransactionID(ref md, _) => md,
+            HttpResponseType::TransactionSimulation(ref md, _) => md,
             // errors
             HttpResponseType::BadRequest(ref md, _) => md,
             HttpResponseType::Unauthorized(ref md, _) => md,
@@ -1431,6 +1460,10 @@ impl HttpResponseType {
                 HttpResponsePreamble::new_serialized(fd, 200, "OK", md.content_length.clone(), &HttpContentType::Text, md.request_id, &md.request_path, empty_headers)?;
                 HttpResponseType::send_text(protocol, md, fd, &txid_bytes)?;
             },
+            HttpResponseType::TransactionSimulation(ref md, ref simulation) => {
+                HttpResponsePreamble::new_serialized(fd, 200, "OK", md.content_length.clone(), &HttpContentType::JSON, md.request_id, &md.request_path, empty_headers)?;
+                HttpResponseType::send_json(protocol, md, fd, simulation)?;
+            },
             HttpResponseType::BadRequest(_, ref msg) => self.error_response(fd, 400, msg)?,
             HttpResponseType::Unauthorized(_, ref msg) => self.error_response(fd, 401, msg)?,
             HttpResponseType::PaymentRequired(_, ref msg) => self.error_response(fd, 402, msg)?,
@@ -1449,6 +1482,7 @@ lazy_static! {
     static ref PATH_GETBLOCK : Regex = Regex::new(r#"^/v2/blocks/([0-9a-f]{64})$"#).unwrap();
     static ref PATH_GETMICROBLOCKS : Regex = Regex::new(r#"^/v2/microblocks/([0-9a-f]{64})$"#).unwrap();
     static ref PATH_POSTTRANSACTION : Regex = Regex::new(r#"^/v2/transactions$"#).unwrap();
+    static ref PATH_SIMULATETRANSACTION : Regex = Regex::new(r#"^/v2/transactions/simulate$"#).unwrap();
 }
 
 impl StacksMessageCodec for StacksHttpPreamble {
@@ -1505,13 +1539,15 @@ impl MessageSequence for StacksHttpMessage {
                 HttpRequestType::GetNeighbors(_) => "HTTP(GetNeighbors)",
                 HttpRequestType::GetBlock(_, _) => "HTTP(GetBlock)",
                 HttpRequestType::GetMicroblocks(_, _) => "HTTP(GetMicroblocks)",
-                HttpRequestType::PostTransaction(_, _) => "HTTP(PostTransaction)"
+                HttpRequestType::PostTransaction(_, _) => "HTTP(PostTransaction)",
+                HttpRequestType::SimulateTransaction(_, _) => "HTTP(SimulateTransaction)"
             },
             StacksHttpMessage::Response(ref res) => match res {
                 HttpResponseType::Neighbors(_, _) => "HTTP(Neighbors)",
                 HttpResponseType::Block(_, _) => "HTTP(Block)",
                 HttpResponseType::Microblocks(_, _) => "HTTP(Microbloks)",
                 HttpResponseType::TransactionID(_, _) => "HTTP(Transaction)",
+                HttpResponseType::TransactionSimulation(_, _) => "HTTP(TransactionSimulation)",
                 HttpResponseType::BadRequest(_, _) => "HTTP(400)",
                 HttpResponseType::Unauthorized(_, _) => "HTTP(401)",
                 HttpResponseType::PaymentRequired(_, _) => "HTTP(402)",
@@ -2374,7 +2410,8 @@ mod test {
             HttpRequestType::GetNeighbors(http_request_metadata_ip.clone()),
             HttpRequestType::GetBlock(http_request_metadata_dns.clone(), BlockHeaderHash([2u8; 32])),
             HttpRequestType::GetMicroblocks(http_request_metadata_ip.clone(), BlockHeaderHash([3u8; 32])),
-            HttpRequestType::PostTransaction(http_request_metadata_dns.clone(), make_test_transaction())
+            HttpRequestType::PostTransaction(http_request_metadata_dns.clone(), make_test_transaction()),
+            HttpRequestType::SimulateTransaction(http_request_metadata_ip.clone(), make_test_transaction())
         ];
 
         let mut tx_body = vec![];
@@ -2384,18 +2421,24 @@ mod test {
         post_transaction_preamble.set_content_type(HttpContentType::Bytes);
         post_transaction_preamble.set_content_length(tx_body.len() as u32);
 
+        let mut simulate_transaction_preamble = HttpRequestPreamble::new("POST".to_string(), "/v2/transactions/simulate".to_string(), http_request_metadata_ip.peer.hostname(), http_request_metadata_ip.peer.port(), http_request_metadata_ip.request_id);
+        simulate_transaction_preamble.set_content_type(HttpContentType::Bytes);
+        simulate_transaction_preamble.set_content_length(tx_body.len() as u32);
+
         // all of these should parse
         let expected_http_preambles = vec![
             HttpRequestPreamble::new("GET".to_string(), "/v2/neighbors".to_string(), http_request_metadata_ip.peer.hostname(), http_request_metadata_ip.peer.port(), http_request_metadata_ip.request_id),
             HttpRequestPreamble::new("GET".to_string(), format!("/v2/blocks/{}", BlockHeaderHash([2u8; 32]).to_hex()), http_request_metadata_dns.peer.hostname(), http_request_metadata_dns.peer.port(), http_request_metadata_dns.request_id),
             HttpRequestPreamble::new("GET".to_string(), format!("/v2/microblocks/{}", BlockHeaderHash([3u8; 32]).to_hex()), http_request_metadata_ip.peer.hostname(), http_request_metadata_ip.peer.port(), http_request_metadata_ip.request_id),
             post_transaction_preamble,
+            simulate_transaction_preamble,
         ];
 
         let expected_http_bodies = vec![
             vec![],
             vec![],
             vec![],
+            tx_body.clone(),
             tx_body,
         ];
 
@@ -2480,18 +2523,23 @@ mod test {
         let mut test_microblock_info_bytes = vec![];
         test_microblock_info.consensus_serialize(&mut test_microblock_info_bytes).unwrap();
 
+        let test_simulation_info = json!({"txid": Txid([0x1; 32]).to_hex(), "status": "success", "post_conditions_passed": true});
+        let test_simulation_info_len = serde_json::to_string(&test_simulation_info).unwrap().len() as u32;
+
         let tests = vec![
             // length is known
             HttpResponseType::Neighbors(HttpResponseMetadata::new(123, "/v2/neighbors".to_string(), Some(serde_json::to_string(&test_neighbors_info).unwrap().len() as u32)), test_neighbors_info.clone()),
             HttpResponseType::Block(HttpResponseMetadata::new(123, format!("/v2/blocks/{}", test_block_info.block_hash().to_hex()), Some(test_block_info_bytes.len() as u32)), test_block_info.clone()),
             HttpResponseType::Microblocks(HttpResponseMetadata::new(123, format!("/v2/microblocks/{}", test_microblock_info[0].block_hash().to_hex()), Some(test_microblock_info_bytes.len() as u32)), test_microblock_info.clone()),
             HttpResponseType::TransactionID(HttpResponseMetadata::new(123, "/v2/transactions".to_string(), Some(Txid([0x1; 32]).to_hex().len() as u32)), Txid([0x1; 32])),
+            HttpResponseType::TransactionSimulation(HttpResponseMetadata::new(123, "/v2/transactions/simulate".to_string(), Some(test_simulation_info_len)), test_simulation_info.clone()),
             
             // length is unknown
             HttpResponseType::Neighbors(HttpResponseMetadata::new(123, "/v2/neighbors".to_string(), None), test_neighbors_info.clone()),
             HttpResponseType::Block(HttpResponseMetadata::new(123, format!("/v2/blocks/{}", test_block_info.block_hash().to_hex()), None), test_block_info.clone()),
             HttpResponseType::Microblocks(HttpResponseMetadata::new(123, format!("/v2/microblocks/{}", test_microblock_info[0].block_hash().to_hex()), None), test_microblock_info.clone()),
             HttpResponseType::TransactionID(HttpResponseMetadata::new(123, "/v2/transactions".to_string(), None), Txid([0x1; 32])),
+            HttpResponseType::TransactionSimulation(HttpResponseMetadata::new(123, "/v2/transactions/simulate".to_string(), None), test_simulation_info.clone()),
 
             // errors without error messages
             HttpResponseType::BadRequest(HttpResponseMetadata::new(123, "/v2/neighbors".to_string(), Some(0)), "".to_string()),
@@ -2520,12 +2568,14 @@ mod test {
             HttpResponsePreamble::new(200, "OK".to_string(), Some(test_block_info_bytes.len() as u32), HttpContentType::Bytes, 123, format!("/v2/blocks/{}", test_block_info.block_hash().to_hex())),
             HttpResponsePreamble::new(200, "OK".to_string(), Some(test_microblock_info_bytes.len() as u32), HttpContentType::Bytes, 123, format!("/v2/microblocks/{}", test_microblock_info[0].block_hash().to_hex())),
             HttpResponsePreamble::new(200, "OK".to_string(), Some(Txid([0x1; 32]).to_hex().len() as u32), HttpContentType::Text, 123, "/v2/transactions".to_string()),
+            HttpResponsePreamble::new(200, "OK".to_string(), Some(test_simulation_info_len), HttpContentType::JSON, 123, "/v2/transactions/simulate".to_string()),
             
             // length is unknown
             HttpResponsePreamble::new(200, "OK".to_string(), None, HttpContentType::JSON, 123, "/v2/neighbors".to_string()),
             HttpResponsePreamble::new(200, "OK".to_string(), None, HttpContentType::Bytes, 123, format!("/v2/blocks/{}", test_block_info.block_hash().to_hex())),
             HttpResponsePreamble::new(200, "OK".to_string(), None, HttpContentType::Bytes, 123, format!("/v2/microblocks/{}", test_microblock_info[0].block_hash().to_hex())),
             HttpResponsePreamble::new(200, "OK".to_string(), None, HttpContentType::Text, 123, "/v2/transactions".to_string()),
+            HttpResponsePreamble::new(200, "OK".to_string(), None, HttpContentType::JSON, 123, "/v2/transactions/simulate".to_string()),
 
             // errors
             HttpResponsePreamble::new_error(400, 123, "/v2/neighbors".to_string(), None),
@@ -2557,12 +2607,14 @@ mod test {
             test_block_info_bytes.clone(),
             test_microblock_info_bytes.clone(),
             Txid([0x1; 32]).to_hex().as_bytes().to_vec(),
+            serde_json::to_string(&test_simulation_info).unwrap().as_bytes().to_vec(),
             
             // with transfer-encoding: chunked
             serde_json::to_string(&test_neighbors_info).unwrap().as_bytes().to_vec(),
             test_block_info_bytes,
             test_microblock_info_bytes,
             Txid([0x1; 32]).to_hex().as_bytes().to_vec(),
+            serde_json::to_string(&test_simulation_info).unwrap().as_bytes().to_vec(),
 
             // errors
             vec![],
@@ -719,7 +719,8 @@ pub enum HttpRequestType {
     GetNeighbors(HttpRequestMetadata),
     GetBlock(HttpRequestMetadata, BlockHeaderHash),
     GetMicroblocks(HttpRequestMetadata, BlockHeaderHash),
-    PostTransaction(HttpRequestMetadata, StacksTransaction)
+    PostTransaction(HttpRequestMetadata, StacksTransaction),
+    SimulateTransaction(HttpRequestMetadata, StacksTransaction)
 }
 
 /// The fields that Actually Matter to http responses
@@ -758,6 +759,7 @@ pub enum HttpResponseType {
     Block(HttpResponseMetadata, StacksBlock),
     Microblocks(HttpResponseMetadata, Vec<StacksMicroblock>),
     TransactionID(HttpResponseMetadata, Txid),
+    TransactionSimulation(HttpResponseMetadata, serde_json::Value),
     
     // peer-given error responses
     BadRequest(HttpResponseMetadata, String),
@@ -1421,6 +1421,7 @@ mod test {
             stable_confirmations: 7,
             first_block_height: 50,
             first_block_hash: first_burn_hash.clone(),
+            reward_cycle_length: 5,
         };
 
         let mut burnchain_view = BurnchainView {
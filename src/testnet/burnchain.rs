@@ -105,6 +105,18 @@ impl BurnchainSimulator {
                     op.burn_header_hash = next_block_header.block_hash;
                     op.vtxindex = vtxindex;
                     op.txid = Txid(Sha256Sum::from_data(format!("{}::{}", op.block_height, vtxindex).as_bytes()).0);
+                },
+                BlockstackOperationType::TransferStx(ref mut op) => {
+                    op.block_height = next_block_header.block_height;
+                    op.burn_header_hash = next_block_header.block_hash;
+                    op.vtxindex = vtxindex;
+                    op.txid = Txid(Sha256Sum::from_data(format!("{}::{}", op.block_height, vtxindex).as_bytes()).0);
+                },
+                BlockstackOperationType::StackStx(ref mut op) => {
+                    op.block_height = next_block_header.block_height;
+                    op.burn_header_hash = next_block_header.block_hash;
+                    op.vtxindex = vtxindex;
+                    op.txid = Txid(Sha256Sum::from_data(format!("{}::{}", op.block_height, vtxindex).as_bytes()).0);
                 }
             }
             vtxindex += 1;
@@ -5,6 +5,8 @@ use address::AddressHashMode;
 use burnchains::{BurnchainSigner, BurnchainHeaderHash, PrivateKey};
 use util::vrf::{VRF, VRFProof, VRFPublicKey, VRFPrivateKey};
 use util::hash::{Sha256Sum};
+use util::hdkeys::{Mnemonic, ExtendedPrivateKey, DerivationPath, Error as hdkeys_error};
+use util::keystore::{Keystore, Error as keystore_error};
 
 pub struct Keychain {
     secret_keys: Vec<StacksPrivateKey>, 
@@ -14,6 +16,10 @@ pub struct Keychain {
     microblocks_secret_keys: Vec<StacksPrivateKey>,
     vrf_secret_keys: Vec<VRFPrivateKey>,
     vrf_map: HashMap<VRFPublicKey, VRFPrivateKey>,
+    // master key and account number, if keys are derived from a mnemonic
+    hd_account: Option<(ExtendedPrivateKey, u32)>,
+    // first VRF key, if loaded from a keystore
+    vrf_keystore_key: Option<VRFPrivateKey>,
 }
 
 impl Keychain {
@@ -36,9 +42,37 @@ impl Keychain {
             threshold,
             vrf_secret_keys: vec![],
             vrf_map: HashMap::new(),
+            hd_account: None,
+            vrf_keystore_key: None,
         }
     }
 
+    /// Make a single-sig keychain whose account, microblock and VRF keys are all derived from
+    /// a mnemonic, so the miner only needs to back up the mnemonic.
+    pub fn from_mnemonic(mnemonic: &Mnemonic, passphrase: &str, account: u32) -> Result<Keychain, hdkeys_error> {
+        let master = ExtendedPrivateKey::from_mnemonic(mnemonic, passphrase)?;
+        let secret_key = master.derive_path(&DerivationPath::stacks_account_key(account, 0)?)?
+            .to_secp256k1_private_key();
+
+        let mut keychain = Keychain::new(vec![secret_key], 1, AddressHashMode::SerializeP2PKH);
+        keychain.hd_account = Some((master, account));
+        Ok(keychain)
+    }
+
+    /// Make a single-sig keychain from the account key in a keystore file.  Microblock and VRF
+    /// keys are derived from the account key, as with `default()`.
+    pub fn from_keystore(path: &str, passphrase: &str) -> Result<Keychain, keystore_error> {
+        let secret_key = Keystore::load_secp256k1_private_key(path, passphrase)?;
+        Ok(Keychain::new(vec![secret_key], 1, AddressHashMode::SerializeP2PKH))
+    }
+
+    /// Use the VRF key in a keystore file as the first VRF key.  Later VRF keys are derived from
+    /// it by hashing, as with `default()`, regardless of how the account key was made.
+    pub fn load_vrf_keystore(&mut self, path: &str, passphrase: &str) -> Result<(), keystore_error> {
+        self.vrf_keystore_key = Some(Keystore::load_vrf_private_key(path, passphrase)?);
+        Ok(())
+    }
+
     pub fn default(seed: Vec<u8>) -> Keychain {
 
         let mut re_hashed_seed = seed;
@@ -56,6 +90,29 @@ impl Keychain {
     }
 
     pub fn rotate_vrf_keypair(&mut self) -> VRFPublicKey {
+        if let Some(ref sk) = self.vrf_keystore_key {
+            if self.vrf_secret_keys.is_empty() {
+                let pk = VRFPublicKey::from_private(sk);
+
+                self.vrf_secret_keys.push(sk.clone());
+                self.vrf_map.insert(pk.clone(), sk.clone());
+                return pk;
+            }
+        }
+
+        if let (&Some((ref master, account)), &None) = (&self.hd_account, &self.vrf_keystore_key) {
+            let index = self.vrf_secret_keys.len() as u32;
+            let sk = DerivationPath::stacks_vrf_key(account, index)
+                .and_then(|path| master.derive_path(&path))
+                .expect("FATAL: failed to derive VRF key")
+                .to_vrf_private_key();
+            let pk = VRFPublicKey::from_private(&sk);
+
+            self.vrf_secret_keys.push(sk.clone());
+            self.vrf_map.insert(pk.clone(), sk);
+            return pk;
+        }
+
         let mut seed = match self.vrf_secret_keys.last() {
             // First key is the hash of the secret state
             None => self.hashed_secret_state,
@@ -80,6 +137,17 @@ impl Keychain {
     }
 
     pub fn rotate_microblock_keypair(&mut self) -> StacksPrivateKey {
+        if let Some((ref master, account)) = self.hd_account {
+            let index = self.microblocks_secret_keys.len() as u32;
+            let sk = DerivationPath::stacks_microblock_key(account, index)
+                .and_then(|path| master.derive_path(&path))
+                .expect("FATAL: failed to derive microblock key")
+                .to_secp256k1_private_key();
+
+            self.microblocks_secret_keys.push(sk.clone());
+            return sk;
+        }
+
         let mut seed = match self.microblocks_secret_keys.last() {
             // First key is the hash of the secret state
             None => self.hashed_secret_state,
@@ -12,12 +12,16 @@ pub use self::node::{Node, SortitionedBlock};
 pub use self::burnchain::{BurnchainSimulator, BurnchainState};
 pub use self::tenure::{LeaderTenure};
 
+use chainstate::stacks::StacksPrivateKey;
+use util::hdkeys::Mnemonic;
+
 #[derive(Clone)]
 pub struct Config {
     pub testnet_name: String,
     pub chain: String,
     pub burnchain_path: String,
     pub burnchain_block_time: u64,
+    pub genesis_allocation_path: Option<String>,
     pub node_config: Vec<NodeConfig>
 }
 
@@ -26,6 +30,30 @@ pub struct NodeConfig {
     pub name: String,
     pub path: String,
     pub mem_pool_path: String,
+    /// How often to mine a microblock during a tenure, in milliseconds
+    pub microblock_frequency: u64,
+    /// Largest microblock to mine, in bytes (capped at MAX_MICROBLOCK_SIZE)
+    pub max_microblock_size: u32,
+    /// Key that signs and pays for PoisonMicroblock transactions reporting leaders that
+    /// equivocate on their microblock streams.  If None, equivocations are not reported.
+    pub poison_microblock_key: Option<StacksPrivateKey>,
+    /// Mnemonic (and its passphrase) from which the node derives its account, microblock and
+    /// VRF keys.  If None, the keys are derived from the node's name.
+    pub mnemonic: Option<(Mnemonic, String)>,
+    /// Path (and passphrase) of a keystore holding the node's account key.  The node's
+    /// microblock and VRF keys are derived from it.  Takes precedence over `mnemonic`.
+    pub keystore: Option<(String, String)>,
+    /// Path (and passphrase) of a keystore holding the node's first VRF key, made with
+    /// `blockstack-cli keystore-create-vrf`.  Later VRF keys are derived from it.  If None, the
+    /// VRF keys are derived from the account key or mnemonic.
+    pub vrf_keystore: Option<(String, String)>,
+    /// If set, only keep the block data and full Clarity state history of this many of the most
+    /// recent blocks, and refuse to read the Clarity state as of older blocks.  Must be at least
+    /// MIN_PRUNE_DEPTH.  If None, the node keeps all block data.
+    pub prune_depth: Option<u64>,
+    /// Whether or not to index each account's transaction history.  Can only be turned on for a
+    /// chain state that has not processed any blocks yet.
+    pub account_history: bool,
 }
 
 #[cfg(test)]
@@ -8,10 +8,14 @@ use address::AddressHashMode;
 use burnchains::{Burnchain, BurnchainHeaderHash, Txid};
 use chainstate::burn::db::burndb::{BurnDB};
 use chainstate::stacks::db::{StacksChainState, StacksHeaderInfo, ClarityTx};
-use chainstate::stacks::{StacksPrivateKey, StacksBlock, TransactionPayload, StacksWorkScore, StacksAddress, StacksTransactionSigner, StacksTransaction, TransactionVersion, StacksMicroblock, CoinbasePayload, StacksBlockBuilder, TransactionAnchorMode};
+use chainstate::stacks::db::genesis::GenesisAllocation;
+use chainstate::stacks::db::pox::PoxRewardSlot;
+use chainstate::stacks::{StacksPrivateKey, StacksBlock, TransactionAuth, TransactionPayload, StacksWorkScore, StacksAddress, StacksTransactionSigner, StacksTransaction, TransactionVersion, StacksMicroblock, CoinbasePayload, StacksBlockBuilder, TransactionAnchorMode};
 use chainstate::burn::operations::{BlockstackOperationType, LeaderKeyRegisterOp, LeaderBlockCommitOp};
 use chainstate::burn::{ConsensusHash, SortitionHash, BlockSnapshot, VRFSeed, BlockHeaderHash};
+use core::MEMPOOL_MIN_RELAY_FEE_RATE;
 use net::StacksMessageType;
+use net::StacksMessageCodec;
 use util::hash::Sha256Sum;
 use util::vrf::{VRFProof, VRFPublicKey};
 use util::get_epoch_time_secs;
@@ -66,20 +70,37 @@ pub struct Node {
     last_sortitioned_block: Option<SortitionedBlock>,
     mem_pool: MemPoolFS,
     nonce: u64,
+    poison_microblock_nonce: u64,
 }
 
 impl Node {
 
-    /// Instantiate and initialize a new node, given a config
-    pub fn new(config: NodeConfig, average_block_time: u64) -> Self {
+    /// Instantiate and initialize a new node, given a config and the initial STX balances
+    pub fn new(config: NodeConfig, average_block_time: u64, genesis_allocations: &Vec<GenesisAllocation>) -> Self {
         
-        let seed = Sha256Sum::from_data(format!("{}", config.name).as_bytes());
-        let keychain = Keychain::default(seed.as_bytes().to_vec());
+        let mut keychain = match (&config.keystore, &config.mnemonic) {
+            (Some((ref path, ref passphrase)), _) => Keychain::from_keystore(path, passphrase)
+                .expect(&format!("FATAL: failed to load keys from keystore {}", path)),
+            (None, Some((ref mnemonic, ref passphrase))) => Keychain::from_mnemonic(mnemonic, passphrase, 0)
+                .expect("FATAL: failed to derive keys from mnemonic"),
+            (None, None) => {
+                let seed = Sha256Sum::from_data(format!("{}", config.name).as_bytes());
+                Keychain::default(seed.as_bytes().to_vec())
+            }
+        };
 
-        let chain_state = match StacksChainState::open(false, TESTNET_CHAIN_ID, &config.path) {
+        if let Some((ref path, ref passphrase)) = config.vrf_keystore {
+            keychain.load_vrf_keystore(path, passphrase)
+                .expect(&format!("FATAL: failed to load VRF key from keystore {}", path));
+        }
+
+        let mut chain_state = match StacksChainState::open_with_genesis_allocations(false, TESTNET_CHAIN_ID, &config.path, genesis_allocations) {
             Ok(res) => res,
             Err(_) => panic!("Error while opening chain state at path {:?}", config.path)
         };
+        chain_state.prune_depth = config.prune_depth;
+        chain_state.set_account_history(config.account_history)
+            .expect("FATAL: failed to configure the account history index");
 
         let mem_pool = MemPoolFS::new(&config.mem_pool_path);
 
@@ -95,6 +116,7 @@ impl Node {
             average_block_time,
             burnchain_tip: None,
             nonce: 0,
+            poison_microblock_nonce: 0,
         }
     }
     
@@ -149,6 +171,9 @@ impl Node {
                 },
                 BlockstackOperationType::UserBurnSupport(_) => {
                     // no-op, UserBurnSupport ops are not supported / produced at this point.
+                },
+                BlockstackOperationType::TransferStx(_) | BlockstackOperationType::StackStx(_) => {
+                    // no-op, these are applied to the chainstate when the Stacks block is processed.
                 }
             }
         }
@@ -173,7 +198,7 @@ impl Node {
     /// Prepares the node to run a tenure consisting in bootstraping the chain.
     /// 
     /// Will internally call initiate_new_tenure().
-    pub fn initiate_genesis_tenure(&mut self, block: &BlockSnapshot) -> Option<LeaderTenure> {
+    pub fn initiate_genesis_tenure(&mut self, block: &BlockSnapshot, burndb: &mut BurnDB) -> Option<LeaderTenure> {
         // Set the `bootstraping_chain` flag, that will be unset once the 
         // bootstraping tenure ran successfully (process_tenure).
         self.bootstraping_chain = true;
@@ -192,13 +217,13 @@ impl Node {
 
         self.last_sortitioned_block = Some(block.clone());
 
-        self.initiate_new_tenure(&block)
+        self.initiate_new_tenure(&block, burndb)
     }
 
     /// Constructs and returns an instance of LeaderTenure, that can be run
     /// on an isolated thread and discarded or canceled without corrupting the
     /// chain state of the node.
-    pub fn initiate_new_tenure(&mut self, sortitioned_block: &SortitionedBlock) -> Option<LeaderTenure> {
+    pub fn initiate_new_tenure(&mut self, sortitioned_block: &SortitionedBlock, burndb: &mut BurnDB) -> Option<LeaderTenure> {
         // Get the latest registered key
         let registered_key = match &self.active_registered_key {
             None => {
@@ -219,18 +244,22 @@ impl Node {
         let microblock_secret_key = self.keychain.rotate_microblock_keypair();
 
         // Get the stack's chain tip
-        let chain_tip = match self.bootstraping_chain {
-            true => StacksHeaderInfo::genesis(),
-            false => match &self.chain_tip {
-                Some(chain_tip) => chain_tip.clone(),
-                None => unreachable!()
-            }
-        };
+        let chain_tip = self.get_tenure_chain_tip();
 
         // Constructs the coinbase transaction - 1st txn that should be handled and included in 
         // the upcoming tenure.
         let coinbase_tx = self.generate_coinbase_tx();
 
+        // The block will be committed to in the burnchain block after sortitioned_block, so it
+        // must apply the STX operations that were sent in sortitioned_block.
+        let burnchain_stx_ops = BurnDB::get_burnchain_stx_ops(burndb.conn(), &sortitioned_block.burn_header_hash, &sortitioned_block.burn_header_hash)
+            .expect("FATAL: failed to load burnchain STX operations");
+
+        // The block commit will be mined in the burnchain block after sortitioned_block, so the
+        // block must record the reward set for the commit's PoX reward slot if it is the first to
+        // see it.
+        let pox_reward_slot = self.get_pox_reward_slot(burndb, sortitioned_block.block_height as u64 + 1, &sortitioned_block.burn_header_hash);
+
         // Construct the upcoming tenure
         let tenure = LeaderTenure::new(
             chain_tip, 
@@ -240,16 +269,53 @@ impl Node {
             self.mem_pool.clone(),
             microblock_secret_key, 
             sortitioned_block.clone(),
-            vrf_proof);
+            vrf_proof,
+            burnchain_stx_ops,
+            pox_reward_slot);
 
         Some(tenure)
     }
 
-    pub fn receive_tenure_artifacts(&mut self, anchored_block_from_ongoing_tenure: &StacksBlock, parent_block: &SortitionedBlock) -> Vec<BlockstackOperationType> {
+    /// The Stacks block the next tenure builds on.
+    fn get_tenure_chain_tip(&self) -> StacksHeaderInfo {
+        match self.bootstraping_chain {
+            true => StacksHeaderInfo::genesis(),
+            false => match &self.chain_tip {
+                Some(chain_tip) => chain_tip.clone(),
+                None => unreachable!()
+            }
+        }
+    }
+
+    /// Find the PoX reward slot of a block commit mined in the burnchain block at the given
+    /// height, after the given burnchain block.
+    fn get_pox_reward_slot(&self, burndb: &mut BurnDB, burn_block_height: u64, parent_burn_header_hash: &BurnchainHeaderHash) -> Option<PoxRewardSlot> {
+        let mut tx = burndb.tx_begin().unwrap();
+        self.chain_state.get_pox_reward_slot(&mut tx, burn_block_height, parent_burn_header_hash)
+            .expect("FATAL: failed to load PoX reward slot")
+    }
+
+    pub fn receive_tenure_artifacts(&mut self, anchored_block_from_ongoing_tenure: &StacksBlock, parent_block: &SortitionedBlock, burndb: &mut BurnDB) -> Vec<BlockstackOperationType> {
         let mut ops = vec![];
         if self.active_registered_key.is_some() {
             let registered_key = self.active_registered_key.clone().unwrap();
 
+            // The commit will be mined in the burnchain block after the current tip, so it pays
+            // that block's PoX reward address (if any), as seen from the block's parent.
+            let commit_outs = {
+                let burnchain_tip = self.burnchain_tip.clone().unwrap();
+                let burn_block_height = burnchain_tip.block_height + 1;
+                match self.get_pox_reward_slot(burndb, burn_block_height, &burnchain_tip.burn_header_hash) {
+                    Some(reward_slot) => {
+                        let chain_tip = self.get_tenure_chain_tip();
+                        let reward_address = self.chain_state.get_pox_reward_address(&chain_tip.burn_header_hash, &chain_tip.anchored_header.block_hash(), burn_block_height, &reward_slot)
+                            .expect("FATAL: failed to load PoX reward address");
+                        reward_address.into_iter().collect()
+                    },
+                    None => vec![]
+                }
+            };
+
             let vrf_proof = self.keychain.generate_proof(
                 &registered_key.vrf_public_key, 
                 parent_block.sortition_hash.as_bytes()).unwrap();
@@ -260,7 +326,8 @@ impl Node {
                 burn_fee,
                 &registered_key, 
                 &parent_block,
-                VRFSeed::from_proof(&vrf_proof));
+                VRFSeed::from_proof(&vrf_proof),
+                commit_outs);
 
             ops.push(op);
         }
@@ -302,6 +369,9 @@ impl Node {
             }
         }
 
+        // Report any leader that equivocated on its microblock stream
+        self.report_microblock_conflicts();
+
         self.chain_tip = {
             // We are intentionally scoping this snippet, in order to limit the scope of the lock.
             // let db = burn_db.lock().unwrap();
@@ -327,6 +397,40 @@ impl Node {
         }
     }
 
+    /// Queue up a PoisonMicroblock transaction for each microblock conflict found while
+    /// preprocessing microblocks, signed with the configured poison microblock key.
+    fn report_microblock_conflicts(&mut self) {
+        let privk = match self.config.poison_microblock_key {
+            Some(ref privk) => privk.clone(),
+            None => return
+        };
+
+        let conflicts = StacksChainState::get_unreported_microblock_conflicts(&self.chain_state.blocks_db).unwrap();
+        if conflicts.is_empty() {
+            return;
+        }
+
+        // the reporter may have sent other transactions, so pick up from its nonce as of the
+        // chain tip (unless reports we already queued haven't been mined yet)
+        if let Some(ref chain_tip) = self.chain_tip {
+            let reporter = TransactionAuth::from_p2pkh(&privk).unwrap().origin().address_testnet();
+            let account = self.chain_state.get_account_at_block(&chain_tip.burn_header_hash, &chain_tip.anchored_header.block_hash(), &reporter.to_account_principal()).unwrap();
+            self.poison_microblock_nonce = std::cmp::max(self.poison_microblock_nonce, account.nonce);
+        }
+
+        for conflict in conflicts.iter() {
+            let tx = self.chain_state.make_poison_microblock_tx(conflict, &privk, self.poison_microblock_nonce, MEMPOOL_MIN_RELAY_FEE_RATE).unwrap();
+            info!("Reporting microblock equivocation by the leader of {}/{} in {}", conflict.burn_header_hash, conflict.anchored_block_hash, tx.txid());
+
+            let mut tx_bytes = vec![];
+            tx.consensus_serialize(&mut tx_bytes).unwrap();
+            self.mem_pool.submit(tx_bytes);
+
+            self.poison_microblock_nonce += 1;
+            self.chain_state.set_microblock_conflict_reported(conflict).unwrap();
+        }
+    }
+
     /// Returns the Stacks address of the node
     pub fn get_address(&self) -> StacksAddress {
         self.keychain.get_address()
@@ -355,7 +459,8 @@ impl Node {
                                 burn_fee: u64, 
                                 key: &RegisteredKey,
                                 parent_block: &SortitionedBlock,
-                                vrf_seed: VRFSeed) -> BlockstackOperationType {
+                                vrf_seed: VRFSeed,
+                                commit_outs: Vec<StacksAddress>) -> BlockstackOperationType {
         
         let (parent_block_ptr, parent_vtxindex) = match self.bootstraping_chain {
             true => (0, 0), // Expected references when mocking the initial sortition
@@ -366,6 +471,7 @@ impl Node {
             block_header_hash,
             burn_fee,
             input: self.keychain.get_burnchain_signer(),
+            commit_outs,
             key_block_ptr: key.block_height as u32,
             key_vtxindex: key.op_vtxindex as u16,
             memo: vec![],
@@ -2,6 +2,7 @@ use super::{Config, Node, BurnchainSimulator, BurnchainState, LeaderTenure};
 
 use chainstate::burn::{ConsensusHash};
 use chainstate::stacks::db::{StacksHeaderInfo, StacksChainState};
+use chainstate::stacks::db::genesis::GenesisAllocation;
 use chainstate::burn::{BlockHeaderHash};
 
 use util::sleep_ms;
@@ -20,11 +21,20 @@ impl RunLoop {
 
     /// Sets up a runloop and nodes, given a config.
     pub fn new(config: Config) -> Self {
+        // Load the initial STX balances, if given
+        let genesis_allocations = match config.genesis_allocation_path {
+            Some(ref path) => match GenesisAllocation::from_file(path) {
+                Ok(allocs) => allocs,
+                Err(e) => panic!("Error while loading genesis allocations from {}: {:?}", path, &e)
+            },
+            None => vec![]
+        };
+
         // Build a vec of nodes based on config
         let mut nodes = vec![]; 
         let mut nodes_confs = config.node_config.clone();
         for conf in nodes_confs.drain(..) {
-            let node = Node::new(conf, config.burnchain_block_time);
+            let node = Node::new(conf, config.burnchain_block_time, &genesis_allocations);
             nodes.push(node);
         }
 
@@ -77,7 +87,7 @@ impl RunLoop {
         // Bootstrap the chain: the first node (could be random) will start a new tenure,
         // using the sortition hash from block #1 for generating a VRF.
         let leader = &mut self.nodes[0];
-        let mut first_tenure = match leader.initiate_genesis_tenure(&state_1.chain_tip) {
+        let mut first_tenure = match leader.initiate_genesis_tenure(&state_1.chain_tip, burnchain.burndb_mut()) {
             Some(res) => res,
             None => panic!("Error while initiating genesis tenure")
         };
@@ -96,7 +106,7 @@ impl RunLoop {
         // while having the option of running multiple tenures concurrently and try different strategies.
         // As a result, once the tenure ran and we have the artifacts (anchored_blocks, microblocks),
         // we have the 1st node (leading) updating its chainstate with the artifacts from its tenure.
-        let block_ops_2 = leader.receive_tenure_artifacts(&anchored_block_1, &parent_block_1);
+        let block_ops_2 = leader.receive_tenure_artifacts(&anchored_block_1, &parent_block_1, burnchain.burndb_mut());
         
         let mut burnchain_state = burnchain.make_next_block(block_ops_2);
         RunLoop::handle_burnchain_state_cb(&self.new_burnchain_state_callback, round_index, &burnchain_state);
@@ -126,7 +136,7 @@ impl RunLoop {
 
             // If the node we're looping on won the sortition, initialize and configure the next tenure
             if won_sortition {
-                leader_tenure = node.initiate_new_tenure(&last_sortitioned_block);
+                leader_tenure = node.initiate_new_tenure(&last_sortitioned_block, burnchain.burndb_mut());
             }
         }
 
@@ -152,7 +162,7 @@ impl RunLoop {
                     // Have each node receive artifacts from the current tenure
                     let (anchored_block, _, parent_block) = artifacts;
                     for node in self.nodes.iter_mut() {
-                        let mut ops = node.receive_tenure_artifacts(&anchored_block, &parent_block);
+                        let mut ops = node.receive_tenure_artifacts(&anchored_block, &parent_block, burnchain.burndb_mut());
                         next_burn_ops.append(&mut ops);
                     }    
                 },
@@ -195,7 +205,7 @@ impl RunLoop {
                 
                 // If the node we're looping on won the sortition, initialize and configure the next tenure
                 if won_sortition {
-                    leader_tenure = node.initiate_new_tenure(&last_sortitioned_block);
+                    leader_tenure = node.initiate_new_tenure(&last_sortitioned_block, burnchain.burndb_mut());
                 } 
             }
             
@@ -6,10 +6,15 @@ use std::time;
 
 use burnchains::{BurnchainHeaderHash, Txid};
 use chainstate::stacks::db::{StacksChainState, StacksHeaderInfo, ClarityTx};
+use chainstate::stacks::db::pox::PoxRewardSlot;
 use chainstate::stacks::{StacksPrivateKey, StacksBlock, StacksWorkScore, StacksAddress, StacksTransactionSigner, StacksTransaction, TransactionVersion, StacksMicroblock, CoinbasePayload, StacksBlockBuilder, TransactionAnchorMode};
 use chainstate::stacks::{MINER_BLOCK_BURN_HEADER_HASH, MINER_BLOCK_HEADER_HASH};
+use chainstate::stacks::MAX_MICROBLOCK_SIZE;
 use chainstate::burn::{VRFSeed, BlockHeaderHash};
+use chainstate::burn::operations::BlockstackOperationType;
+use net::StacksMessageCodec;
 use util::vrf::{VRFProof};
+use util::sleep_ms;
 
 pub struct LeaderTenure {
     average_block_time: u64,
@@ -32,7 +37,9 @@ impl <'a> LeaderTenure {
                mem_pool: MemPoolFS,
                microblock_secret_key: StacksPrivateKey,  
                last_sortitioned_block: SortitionedBlock,
-               vrf_proof: VRFProof) -> LeaderTenure {
+               vrf_proof: VRFProof,
+               burnchain_stx_ops: Vec<BlockstackOperationType>,
+               pox_reward_slot: Option<PoxRewardSlot>) -> LeaderTenure {
 
         let now = time::Instant::now();
 
@@ -41,10 +48,15 @@ impl <'a> LeaderTenure {
             work: parent_block.anchored_header.total_work.work + 1,
         };
 
-        let block_builder = match last_sortitioned_block.block_height {
+        let mut block_builder = match last_sortitioned_block.block_height {
             1 => StacksBlockBuilder::first(1, &parent_block.burn_header_hash, parent_block.burn_header_timestamp, &vrf_proof, &microblock_secret_key),
             _ => StacksBlockBuilder::from_parent(1, &parent_block, &ratio, &vrf_proof, &microblock_secret_key)
         };
+        block_builder.set_burnchain_stx_ops(burnchain_stx_ops);
+        block_builder.set_pox_reward_slot(pox_reward_slot);
+
+        // this tenure's block commit goes into the next burnchain block
+        block_builder.set_burn_block_height(last_sortitioned_block.block_height as u64 + 1);
 
         Self {
             average_block_time,
@@ -69,6 +81,80 @@ impl <'a> LeaderTenure {
         }
     }
 
+    /// Once the anchored block is mined, keep pulling transactions that can be streamed out of
+    /// the mem pool and mine them into microblocks, one every `microblock_frequency` ms, until
+    /// the tenure is over.  `pending_txs` are off-chain-only transactions that were set aside
+    /// while mining the anchored block.
+    pub fn mine_microblocks(&mut self, clarity_tx: &mut ClarityTx<'a>, mut pending_txs: Vec<StacksTransaction>) -> Vec<StacksMicroblock> {
+        let tenure_duration = time::Duration::from_millis(self.average_block_time);
+        let max_microblock_size = std::cmp::min(self.config.max_microblock_size, MAX_MICROBLOCK_SIZE) as u64;
+        let mut microblocks = vec![];
+
+        // can't stream these -- they're left for the next anchored block
+        let mut on_chain_only_txs = vec![];
+
+        loop {
+            if self.started_at.elapsed() + time::Duration::from_millis(self.config.microblock_frequency) >= tenure_duration {
+                break;
+            }
+            sleep_ms(self.config.microblock_frequency);
+
+            for tx in self.mem_pool.poll() {
+                if tx.anchor_mode == TransactionAnchorMode::OnChainOnly {
+                    on_chain_only_txs.push(tx);
+                }
+                else {
+                    pending_txs.push(tx);
+                }
+            }
+
+            let mut microblock_size = 0;
+            let mut num_txs = 0;
+            let mut deferred_txs = vec![];
+            for tx in pending_txs.drain(..) {
+                let mut tx_bytes = vec![];
+                tx.consensus_serialize(&mut tx_bytes).expect("FATAL: failed to serialize to vec");
+                if microblock_size + (tx_bytes.len() as u64) > max_microblock_size {
+                    deferred_txs.push(tx);
+                    continue;
+                }
+
+                match self.block_builder.try_mine_tx(clarity_tx, &tx) {
+                    Ok(_) => {
+                        microblock_size += tx_bytes.len() as u64;
+                        num_txs += 1;
+                    },
+                    Err(e) => error!("Failed mining streamed transaction - {}", e)
+                }
+            }
+            pending_txs = deferred_txs;
+
+            if num_txs == 0 {
+                continue;
+            }
+
+            match self.block_builder.mine_next_microblock() {
+                Ok(microblock) => {
+                    info!("Mined microblock {} (seq {}) with {} transaction(s)", microblock.block_hash(), microblock.header.sequence, microblock.txs.len());
+                    microblocks.push(microblock);
+                },
+                Err(e) => {
+                    error!("Failed mining microblock - {}", e);
+                    break;
+                }
+            }
+        }
+
+        // anything left over goes back to the mem pool
+        for tx in pending_txs.into_iter().chain(on_chain_only_txs) {
+            let mut tx_bytes = vec![];
+            tx.consensus_serialize(&mut tx_bytes).expect("FATAL: failed to serialize to vec");
+            self.mem_pool.submit(tx_bytes);
+        }
+
+        microblocks
+    }
+
     pub fn run(&mut self) -> Option<(StacksBlock, Vec<StacksMicroblock>, SortitionedBlock)> {
 
         let mut chain_state = StacksChainState::open(false, TESTNET_CHAIN_ID, &self.config.path).unwrap();
@@ -77,13 +163,18 @@ impl <'a> LeaderTenure {
 
         self.handle_txs(&mut clarity_tx, vec![self.coinbase_tx.clone()]);
 
-        let txs = self.mem_pool.poll();
-        self.handle_txs(&mut clarity_tx, txs);
+        // off-chain-only transactions can't go into the anchored block; stream them instead
+        let (streamed_txs, anchored_txs) = self.mem_pool.poll()
+            .into_iter()
+            .partition(|tx| tx.anchor_mode == TransactionAnchorMode::OffChainOnly);
+        self.handle_txs(&mut clarity_tx, anchored_txs);
 
         let anchored_block = self.block_builder.mine_anchored_block(&mut clarity_tx);
 
+        let microblocks = self.mine_microblocks(&mut clarity_tx, streamed_txs);
+
         clarity_tx.rollback_block();
 
-        Some((anchored_block, vec![], self.last_sortitioned_block.clone()))
+        Some((anchored_block, microblocks, self.last_sortitioned_block.clone()))
     }
 }
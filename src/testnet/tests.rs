@@ -2,9 +2,24 @@ use testnet;
 use rand::RngCore;
 use util::hash::{to_hex, hex_bytes};
 use testnet::mem_pool::MemPool;
-use chainstate::stacks::db::{StacksChainState};
+use chainstate::stacks::db::{StacksChainState, StacksHeaderInfo};
+use chainstate::stacks::db::snapshot::ChainstateSnapshotManifest;
+use chainstate::stacks::Error as ChainstateError;
+use chainstate::burn::{BlockHeaderHash, ConsensusHash};
+use chainstate::burn::db::burndb::BurnDB;
+use burnchains::BurnchainHeaderHash;
+use chainstate::stacks::index::TrieHash;
+use util::db::query_rows;
+use vm::types::QualifiedContractIdentifier;
+use rusqlite::NO_PARAMS;
+use std::fs;
 use super::node::{TESTNET_CHAIN_ID};
-use chainstate::stacks::{TransactionPayload, CoinbasePayload};
+use super::Keychain;
+use util::keystore::{Keystore, KeystoreKey};
+use util::vrf::{VRFPrivateKey, VRFPublicKey};
+use chainstate::stacks::{TransactionPayload, CoinbasePayload, StacksBlock, StacksPrivateKey, StacksTransaction, StacksTransactionSigner, TransactionAuth, TransactionVersion, TransactionAnchorMode};
+use core::EMPTY_MICROBLOCK_PARENT_HASH;
+use net::StacksMessageCodec;
 
 pub fn new_test_conf() -> testnet::Config {
     // Testnet's name
@@ -18,10 +33,19 @@ pub fn new_test_conf() -> testnet::Config {
         chain: "bitcoin".to_string(),
         burnchain_path: format!("/tmp/{}/burnchain", testnet_id),
         burnchain_block_time: 500,
+        genesis_allocation_path: None,
         node_config: vec![testnet::NodeConfig {
             name: "L1".to_string(),
             path: format!("/tmp/{}/L1", testnet_id),
-            mem_pool_path: format!("/tmp/{}/L1/mempool", testnet_id)
+            mem_pool_path: format!("/tmp/{}/L1/mempool", testnet_id),
+            microblock_frequency: 100,
+            max_microblock_size: 65536,
+            poison_microblock_key: None,
+            mnemonic: None,
+            keystore: None,
+            vrf_keystore: None,
+            prune_depth: None,
+            account_history: false
         }]
     };
     conf
@@ -394,3 +418,227 @@ fn should_succeed_handling_malformed_and_valid_txs() {
     });
     run_loop.start(num_rounds);
 }
+
+/// A contract-publishing transaction from a fixed account, with the given anchor mode
+fn make_anchor_mode_tx(contract_name: &str, nonce: u64, anchor_mode: TransactionAnchorMode) -> StacksTransaction {
+    let privk = StacksPrivateKey::from_hex("043ff5004e3d695060fa48ac94c96049b8c14ef441c50a184a6a3875d2a000f301").unwrap();
+    let payload = TransactionPayload::new_smart_contract(&contract_name.to_string(), &"(define-data-var bar int 0)".to_string()).unwrap();
+    let mut tx = StacksTransaction::new(TransactionVersion::Testnet, TransactionAuth::from_p2pkh(&privk).unwrap(), payload);
+    tx.chain_id = TESTNET_CHAIN_ID;
+    tx.anchor_mode = anchor_mode;
+    tx.set_origin_nonce(nonce);
+
+    let mut signer = StacksTransactionSigner::new(&tx);
+    signer.sign_origin(&privk).unwrap();
+    signer.get_tx().unwrap()
+}
+
+/// All stored anchored blocks (and their burnchain header hashes), lowest first
+fn load_blocks_by_height(chain_state: &StacksChainState) -> Vec<(BurnchainHeaderHash, StacksBlock)> {
+    let mut blocks : Vec<(BurnchainHeaderHash, StacksBlock)> = StacksChainState::list_blocks(&chain_state.blocks_db, &chain_state.blocks_path).unwrap()
+        .into_iter()
+        .map(|(burn_header_hash, block_hash)| {
+            let block = StacksChainState::load_block(&chain_state.blocks_path, &burn_header_hash, &block_hash).unwrap().unwrap();
+            (burn_header_hash, block)
+        })
+        .collect();
+    blocks.sort_by_key(|(_, block)| block.header.total_work.work);
+    blocks
+}
+
+#[test]
+fn should_succeed_mining_off_chain_only_txs_in_microblocks() {
+    let conf = new_test_conf();
+
+    let num_rounds = 3;
+    let mut run_loop = testnet::RunLoop::new(conf);
+
+    // Use tenure's hook for submitting transactions
+    run_loop.apply_on_new_tenures(|round, tenure| {
+        match round {
+            1 => {
+                // On round 1, publish a contract that may only be anchored, and one that may
+                // only be streamed
+                for tx in [make_anchor_mode_tx("anchored", 0, TransactionAnchorMode::OnChainOnly),
+                           make_anchor_mode_tx("streamed", 1, TransactionAnchorMode::OffChainOnly)].iter() {
+                    let mut tx_bytes = vec![];
+                    tx.consensus_serialize(&mut tx_bytes).unwrap();
+                    tenure.mem_pool.submit(tx_bytes);
+                }
+            },
+            _ => {}
+        };
+        return
+    });
+
+    // Use block's hook for asserting expectations
+    run_loop.apply_on_new_chain_states(|round, chain_state, _| {
+        let anchored_txid = make_anchor_mode_tx("anchored", 0, TransactionAnchorMode::OnChainOnly).txid();
+        let streamed_txid = make_anchor_mode_tx("streamed", 1, TransactionAnchorMode::OffChainOnly).txid();
+
+        match round {
+            1 => {
+                // Block #2 has the coinbase and the on-chain-only contract, but not the
+                // off-chain-only one
+                let blocks = load_blocks_by_height(chain_state);
+                assert_eq!(blocks.len(), 2);
+
+                let (_, ref block) = blocks[1];
+                let txids : Vec<_> = block.txs.iter().map(|tx| tx.txid()).collect();
+                assert_eq!(txids.len(), 2);
+                assert_eq!(txids[1], anchored_txid);
+            },
+            2 => {
+                // Block #3 confirms the microblock stream Block #2's leader mined the
+                // off-chain-only contract into
+                let blocks = load_blocks_by_height(chain_state);
+                assert_eq!(blocks.len(), 3);
+
+                let (ref parent_burn_header_hash, ref parent_block) = blocks[1];
+                let (_, ref block) = blocks[2];
+                assert_eq!(block.header.parent_block, parent_block.block_hash());
+                assert_eq!(block.txs.len(), 1);
+                assert!(block.header.parent_microblock != EMPTY_MICROBLOCK_PARENT_HASH);
+                assert_eq!(block.header.parent_microblock_sequence, 0);
+
+                let microblocks = StacksChainState::load_staging_microblock_stream(&chain_state.blocks_db, &chain_state.blocks_path, parent_burn_header_hash, &parent_block.block_hash(), 0).unwrap().unwrap();
+                assert_eq!(microblocks.len(), 1);
+                let streamed_txids : Vec<_> = microblocks[0].txs.iter().map(|tx| tx.txid()).collect();
+                assert_eq!(streamed_txids, vec![streamed_txid]);
+            },
+            _ => {}
+        }
+    });
+    run_loop.start(num_rounds);
+}
+
+#[test]
+fn should_export_and_import_chainstate_snapshot() {
+    let conf = new_test_conf();
+
+    let num_rounds = 3;
+    let mut run_loop = testnet::RunLoop::new(conf.clone());
+
+    run_loop.apply_on_new_tenures(|round, tenure| {
+        if round == 1 {
+            // publish the KV contract (see should_succeed_mining_valid_txs)
+            let publish_contract = "8000000000040021a3c334fc0ee50359353799e8b2605ac6be1fe4000000000000000000000000000000000101d314ff08deb3c5349d97f454be72d026c4bd3b939799301eca062a9f8fd86c294dba97f27863d8bf99113a935e61f6ecb1ddb51cd5816e9887ac709244bc43b0030200000000010573746f72650000016228646566696e652d6d61702073746f72652028286b657920286275666620333229292920282876616c756520286275666620333229292929202020202020202020202020200a28646566696e652d7075626c696320286765742d76616c756520286b65792028627566662033322929290a20202020286d6174636820286d61702d6765743f2073746f72652028286b6579206b65792929290a2020202020202020656e74727920286f6b20286765742076616c756520656e74727929290a2020202020202020286f6b2022222929290a0a28646566696e652d7075626c696320287365742d76616c756520286b65792028627566662033322929202876616c75652028627566662033322929290a2020202028626567696e0a2020202020202020286d61702d7365742073746f72652028286b6579206b6579292920282876616c75652076616c75652929290a2020202020202020286f6b2027747275652929290a";
+            tenure.mem_pool.submit(hex_bytes(publish_contract).unwrap().to_vec());
+        }
+    });
+
+    run_loop.start(num_rounds);
+
+    let contract_id = QualifiedContractIdentifier::parse("STGT7GSMZG7EA0TS6MVSKT5JC1DCDFGZWJJZXN8A.store").unwrap();
+    let query = "(get-value \"foo\")";
+
+    // snapshot the node's chain tip
+    let mut chain_state = StacksChainState::open(false, TESTNET_CHAIN_ID, &conf.node_config[0].path).unwrap();
+    let tip = query_rows::<StacksHeaderInfo, _>(&chain_state.headers_db, &"SELECT * FROM block_headers ORDER BY block_height DESC LIMIT 1".to_string(), NO_PARAMS).unwrap()
+        .pop().unwrap();
+    let tip_block_hash = tip.anchored_header.block_hash();
+    let expected_value = chain_state.eval_read_only(&tip.burn_header_hash, &tip_block_hash, &contract_id, query).unwrap();
+
+    let snapshot_path = format!("{}/snapshot", &conf.node_config[0].path);
+    let manifest = chain_state.export_snapshot(&conf.burnchain_path, &tip.burn_header_hash, &tip_block_hash, &snapshot_path).unwrap();
+    assert_eq!(manifest.block_height, tip.block_height);
+    assert_eq!(manifest.index_block_hash, tip.index_block_hash());
+    assert_eq!(manifest.state_root_hash, tip.anchored_header.state_index_root);
+    assert_eq!(ChainstateSnapshotManifest::load(&snapshot_path).unwrap(), manifest);
+
+    // any processed block can be exported, and the snapshot only holds the state up to it
+    let parent_tip = query_rows::<StacksHeaderInfo, _>(&chain_state.headers_db, &"SELECT * FROM block_headers WHERE block_height = ?1".to_string(), &[&((tip.block_height - 1) as i64)]).unwrap()
+        .pop().unwrap();
+    let parent_block_hash = parent_tip.anchored_header.block_hash();
+    let expected_parent_value = chain_state.eval_read_only(&parent_tip.burn_header_hash, &parent_block_hash, &contract_id, query).unwrap();
+
+    let parent_snapshot_path = format!("{}/snapshot-parent", &conf.node_config[0].path);
+    let parent_manifest = chain_state.export_snapshot(&conf.burnchain_path, &parent_tip.burn_header_hash, &parent_block_hash, &parent_snapshot_path).unwrap();
+    assert_eq!(parent_manifest.block_height, parent_tip.block_height);
+    assert_eq!(parent_manifest.index_block_hash, parent_tip.index_block_hash());
+
+    let parent_import_conf = new_test_conf();
+    let (mut parent_chain_state, imported_parent_manifest) = StacksChainState::import_snapshot(&parent_snapshot_path, &parent_import_conf.node_config[0].path, &parent_import_conf.burnchain_path, &parent_tip.index_block_hash(), &parent_manifest.consensus_hash).unwrap();
+    assert_eq!(imported_parent_manifest, parent_manifest);
+    assert_eq!(parent_chain_state.eval_read_only(&parent_tip.burn_header_hash, &parent_block_hash, &contract_id, query).unwrap(), expected_parent_value);
+
+    // ...the tip and its burnchain block are not in it...
+    assert!(StacksChainState::get_anchored_block_header_info(&parent_chain_state.headers_db, &tip.burn_header_hash, &tip_block_hash).unwrap().is_none());
+    let parent_burndb = BurnDB::open(&parent_import_conf.burnchain_path, false).unwrap();
+    assert!(BurnDB::get_block_snapshot(parent_burndb.conn(), &tip.burn_header_hash).unwrap().is_none());
+    assert_eq!(BurnDB::get_canonical_burn_chain_tip(parent_burndb.conn()).unwrap().burn_header_hash, parent_tip.burn_header_hash);
+
+    // ...so the new node can process the tip itself
+    parent_chain_state.block_begin(&parent_tip.burn_header_hash, &parent_block_hash, &tip.burn_header_hash, &tip_block_hash)
+        .rollback_block();
+    parent_chain_state.headers_state_index.begin(&parent_tip.index_block_hash(), &tip.index_block_hash()).unwrap();
+    parent_chain_state.headers_state_index.drop_current();
+
+    // can't overwrite an existing snapshot
+    assert!(chain_state.export_snapshot(&conf.burnchain_path, &tip.burn_header_hash, &tip_block_hash, &snapshot_path).is_err());
+
+    // a new node can start from the snapshot
+    let import_conf = new_test_conf();
+    let (mut imported_chain_state, imported_manifest) = StacksChainState::import_snapshot(&snapshot_path, &import_conf.node_config[0].path, &import_conf.burnchain_path, &tip.index_block_hash(), &manifest.consensus_hash).unwrap();
+    assert_eq!(imported_manifest, manifest);
+    assert_eq!(imported_chain_state.eval_read_only(&tip.burn_header_hash, &tip_block_hash, &contract_id, query).unwrap(), expected_value);
+
+    // ...but its history before the snapshot is not available
+    assert_eq!(StacksChainState::get_pruned_horizon(&imported_chain_state.headers_db).unwrap(), tip.block_height);
+
+    // can't import over an existing node
+    assert!(StacksChainState::import_snapshot(&snapshot_path, &import_conf.node_config[0].path, &import_conf.burnchain_path, &tip.index_block_hash(), &manifest.consensus_hash).is_err());
+
+    // a snapshot is only imported if it is of the trusted block
+    let untrusted_import_conf = new_test_conf();
+    match StacksChainState::import_snapshot(&snapshot_path, &untrusted_import_conf.node_config[0].path, &untrusted_import_conf.burnchain_path, &tip.index_block_hash(), &ConsensusHash([0x11; 20])) {
+        Err(ChainstateError::InvalidChainstateSnapshot(_)) => {},
+        res => panic!("Unexpected result: {:?}", res.map(|(_, manifest)| manifest))
+    }
+    match StacksChainState::import_snapshot(&snapshot_path, &untrusted_import_conf.node_config[0].path, &untrusted_import_conf.burnchain_path, &BlockHeaderHash([0x11; 32]), &manifest.consensus_hash) {
+        Err(ChainstateError::InvalidChainstateSnapshot(_)) => {},
+        res => panic!("Unexpected result: {:?}", res.map(|(_, manifest)| manifest))
+    }
+    assert!(fs::metadata(&untrusted_import_conf.burnchain_path).is_err());
+
+    // a snapshot that doesn't match its manifest is not imported, even if it claims to be of
+    // the trusted block
+    let mut bad_manifest = manifest.clone();
+    bad_manifest.state_root_hash = TrieHash([0x11; 32]);
+    fs::write(format!("{}/manifest.json", &snapshot_path), bad_manifest.to_json()).unwrap();
+
+    let bad_import_conf = new_test_conf();
+    match StacksChainState::import_snapshot(&snapshot_path, &bad_import_conf.node_config[0].path, &bad_import_conf.burnchain_path, &tip.index_block_hash(), &manifest.consensus_hash) {
+        Err(ChainstateError::InvalidChainstateSnapshot(_)) => {},
+        res => panic!("Unexpected result: {:?}", res.map(|(_, manifest)| manifest))
+    }
+    assert!(fs::metadata(&bad_import_conf.burnchain_path).is_err());
+}
+
+#[test]
+fn should_load_first_vrf_key_from_keystore() {
+    let path = "/tmp/test_keychain_vrf_keystore.json";
+    if fs::metadata(path).is_ok() {
+        fs::remove_file(path).unwrap();
+    }
+
+    let vrf_sk = VRFPrivateKey::new();
+    Keystore::encrypt(&KeystoreKey::VRF(vrf_sk.clone()), "pass", 16).save(path).unwrap();
+
+    let mut keychain = Keychain::default(vec![0x01; 32]);
+    assert!(keychain.load_vrf_keystore(path, "wrong pass").is_err());
+    keychain.load_vrf_keystore(path, "pass").unwrap();
+
+    // the keystore's key is used first, and later keys differ from it
+    let first_pk = keychain.rotate_vrf_keypair();
+    assert_eq!(first_pk, VRFPublicKey::from_private(&vrf_sk));
+    let second_pk = keychain.rotate_vrf_keypair();
+    assert!(second_pk != first_pk);
+
+    // keys are deterministic, given the keystore
+    let mut other_keychain = Keychain::default(vec![0x02; 32]);
+    other_keychain.load_vrf_keystore(path, "pass").unwrap();
+    assert_eq!(other_keychain.rotate_vrf_keypair(), first_pk);
+
+    fs::remove_file(path).unwrap();
+}
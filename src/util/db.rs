@@ -74,7 +74,9 @@ pub enum Error {
     /// I/O error
     IOError(IOError),
     /// MARF index error
-    IndexError(MARFError)
+    IndexError(MARFError),
+    /// Database was created with an incompatible schema version
+    VersionMismatch
 }
 
 impl fmt::Display for Error {
@@ -93,6 +95,7 @@ impl fmt::Display for Error {
             Error::IOError(ref e) => fmt::Display::fmt(e, f),
             Error::SqliteError(ref e) => fmt::Display::fmt(e, f),
             Error::IndexError(ref e) => fmt::Display::fmt(e, f),
+            Error::VersionMismatch => write!(f, "Incompatible database schema version"),
         }
     }
 }
@@ -113,6 +116,7 @@ impl error::Error for Error {
             Error::SqliteError(ref e) => Some(e),
             Error::IOError(ref e) => Some(e),
             Error::IndexError(ref e) => Some(e),
+            Error::VersionMismatch => None,
         }
     }
 }
@@ -0,0 +1,527 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Hierarchical deterministic key derivation.
+//!
+//! Mnemonics are BIP39 phrases from the English word list, and keys are derived from the
+//! mnemonic's seed with BIP32.  Stacks keys live under the BIP44 purpose and the Stacks coin
+//! type, `m/44'/5757'/account'/chain/index`, where the chain selects what the key is for:
+//! * chain 0 holds account keys, which own STX and sign transactions
+//! * chain 1 holds microblock-signing keys
+//! * chain 2 holds VRF keys, which are the derived secp256k1 secret bytes used as Ed25519 secrets
+
+use std::fmt;
+use std::error;
+
+use secp256k1;
+use secp256k1::Secp256k1;
+use secp256k1::PublicKey as LibSecp256k1PublicKey;
+use secp256k1::SecretKey as LibSecp256k1PrivateKey;
+
+use sha2::{Sha256, Sha512, Digest};
+
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2;
+
+use rand::RngCore;
+use rand::thread_rng;
+
+use util::secp256k1::Secp256k1PrivateKey;
+use util::vrf::VRFPrivateKey;
+
+thread_local!(static _secp256k1: Secp256k1<secp256k1::All> = Secp256k1::new());
+
+pub const BIP39_PBKDF2_ROUNDS : u32 = 2048;
+pub const BIP32_HARDENED_INDEX : u32 = 0x80000000;
+pub const BIP44_PURPOSE : u32 = 44;
+pub const STACKS_BIP44_COIN_TYPE : u32 = 5757;
+
+pub const STACKS_ACCOUNT_KEY_CHAIN : u32 = 0;
+pub const STACKS_MICROBLOCK_KEY_CHAIN : u32 = 1;
+pub const STACKS_VRF_KEY_CHAIN : u32 = 2;
+
+lazy_static! {
+    static ref BIP39_ENGLISH_WORDS : Vec<&'static str> = include_str!("bip39_english.txt").lines().collect();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Entropy must be 16 to 32 bytes long, in multiples of 4
+    InvalidEntropyLength(usize),
+    /// Mnemonics must be 12 to 24 words long, in multiples of 3
+    InvalidWordCount(usize),
+    /// Word is not in the English word list
+    UnknownWord(String),
+    /// Mnemonic checksum does not match its entropy
+    InvalidChecksum,
+    /// Derivation path could not be parsed
+    InvalidPath(String),
+    /// Derived key is not a valid key (vanishingly unlikely; the caller should use the next index)
+    InvalidDerivedKey,
+    /// Account, chain or key index must be less than `BIP32_HARDENED_INDEX`
+    InvalidIndex(u32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidEntropyLength(len) => write!(f, "Invalid entropy length: {} bytes", len),
+            Error::InvalidWordCount(count) => write!(f, "Invalid mnemonic length: {} words", count),
+            Error::UnknownWord(ref word) => write!(f, "Unknown mnemonic word '{}'", word),
+            Error::InvalidChecksum => write!(f, "Invalid mnemonic checksum"),
+            Error::InvalidPath(ref path) => write!(f, "Invalid derivation path '{}'", path),
+            Error::InvalidDerivedKey => write!(f, "Derived key is invalid"),
+            Error::InvalidIndex(index) => write!(f, "Invalid index {}: must be less than {}", index, BIP32_HARDENED_INDEX),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut mac = Hmac::<Sha512>::new_varkey(key)
+        .expect("BUG: HMAC accepts keys of any length");
+    mac.input(data);
+
+    let mut ret = [0u8; 64];
+    ret.copy_from_slice(mac.result().code().as_slice());
+    ret
+}
+
+/// A BIP39 mnemonic phrase, and the entropy it encodes
+#[derive(Clone, PartialEq)]
+pub struct Mnemonic {
+    entropy: Vec<u8>,
+    words: Vec<&'static str>,
+}
+
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // don't leak the phrase into logs
+        write!(f, "Mnemonic({} words)", self.words.len())
+    }
+}
+
+impl Mnemonic {
+    /// Encode entropy as a mnemonic.  Entropy must be 16, 20, 24, 28 or 32 bytes.
+    pub fn from_entropy(entropy: &[u8]) -> Result<Mnemonic, Error> {
+        if entropy.len() < 16 || entropy.len() > 32 || entropy.len() % 4 != 0 {
+            return Err(Error::InvalidEntropyLength(entropy.len()));
+        }
+
+        let checksum = Sha256::digest(entropy);
+        let num_words = (entropy.len() * 8 + entropy.len() / 4) / 11;
+
+        let bit = |i: usize| -> u32 {
+            let byte = if i < entropy.len() * 8 { entropy[i / 8] } else { checksum[i / 8 - entropy.len()] };
+            ((byte >> (7 - (i % 8))) & 0x01) as u32
+        };
+
+        let mut words = Vec::with_capacity(num_words);
+        for w in 0..num_words {
+            let mut index = 0u32;
+            for i in 0..11 {
+                index = (index << 1) | bit(w * 11 + i);
+            }
+            words.push(BIP39_ENGLISH_WORDS[index as usize]);
+        }
+
+        Ok(Mnemonic {
+            entropy: entropy.to_vec(),
+            words
+        })
+    }
+
+    /// Generate a random mnemonic with the given number of words (12, 15, 18, 21 or 24)
+    pub fn generate(num_words: usize) -> Result<Mnemonic, Error> {
+        if num_words < 12 || num_words > 24 || num_words % 3 != 0 {
+            return Err(Error::InvalidWordCount(num_words));
+        }
+
+        let mut entropy = vec![0u8; num_words / 3 * 4];
+        thread_rng().fill_bytes(&mut entropy);
+        Mnemonic::from_entropy(&entropy)
+    }
+
+    /// Parse and check a mnemonic phrase.  Words may be separated by any whitespace.
+    pub fn from_phrase(phrase: &str) -> Result<Mnemonic, Error> {
+        let words : Vec<&str> = phrase.split_whitespace().collect();
+        if words.len() < 12 || words.len() > 24 || words.len() % 3 != 0 {
+            return Err(Error::InvalidWordCount(words.len()));
+        }
+
+        let mut bits = Vec::with_capacity(words.len() * 11);
+        for word in words.iter() {
+            let index = BIP39_ENGLISH_WORDS.binary_search(word)
+                .map_err(|_e| Error::UnknownWord(word.to_string()))?;
+            for i in (0..11).rev() {
+                bits.push(((index >> i) & 0x01) as u8);
+            }
+        }
+
+        let entropy_len = words.len() / 3 * 4;
+        let mut entropy = vec![0u8; entropy_len];
+        for i in 0..(entropy_len * 8) {
+            entropy[i / 8] |= bits[i] << (7 - (i % 8));
+        }
+
+        let mnemonic = Mnemonic::from_entropy(&entropy)?;
+        if mnemonic.words != words {
+            return Err(Error::InvalidChecksum);
+        }
+        Ok(mnemonic)
+    }
+
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    pub fn phrase(&self) -> String {
+        self.words.join(" ")
+    }
+
+    /// Compute the 64-byte BIP39 seed.  The passphrase is used as given; callers that accept
+    /// non-ASCII passphrases must NFKD-normalize them first.
+    pub fn to_seed(&self, passphrase: &str) -> [u8; 64] {
+        let salt = format!("mnemonic{}", passphrase);
+        let mut seed = [0u8; 64];
+        pbkdf2::<Hmac<Sha512>>(self.phrase().as_bytes(), salt.as_bytes(), BIP39_PBKDF2_ROUNDS as usize, &mut seed);
+        seed
+    }
+}
+
+/// A BIP32 derivation path, as a list of child indexes.  Hardened indexes are at least
+/// `BIP32_HARDENED_INDEX`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivationPath(pub Vec<u32>);
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "m")?;
+        for index in self.0.iter() {
+            if *index >= BIP32_HARDENED_INDEX {
+                write!(f, "/{}'", index - BIP32_HARDENED_INDEX)?;
+            }
+            else {
+                write!(f, "/{}", index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DerivationPath {
+    /// Parse a path like `m/44'/5757'/0'/0/0`.  Hardened indexes may be marked with `'` or `h`.
+    pub fn from_string(s: &str) -> Result<DerivationPath, Error> {
+        let mut parts = s.split('/');
+        if parts.next() != Some("m") {
+            return Err(Error::InvalidPath(s.to_string()));
+        }
+
+        let mut indexes = vec![];
+        for part in parts {
+            let (digits, hardened) =
+                if part.ends_with('\'') || part.ends_with('h') {
+                    (&part[0..part.len() - 1], true)
+                }
+                else {
+                    (part, false)
+                };
+
+            let index = digits.parse::<u32>()
+                .map_err(|_e| Error::InvalidPath(s.to_string()))?;
+            if index >= BIP32_HARDENED_INDEX {
+                return Err(Error::InvalidPath(s.to_string()));
+            }
+            indexes.push(if hardened { index + BIP32_HARDENED_INDEX } else { index });
+        }
+        Ok(DerivationPath(indexes))
+    }
+
+    /// Path to a Stacks key: `m/44'/5757'/account'/chain/index`.  The account, chain and index
+    /// must each be less than `BIP32_HARDENED_INDEX`.
+    pub fn stacks_key(account: u32, chain: u32, index: u32) -> Result<DerivationPath, Error> {
+        for i in [account, chain, index].iter() {
+            if *i >= BIP32_HARDENED_INDEX {
+                return Err(Error::InvalidIndex(*i));
+            }
+        }
+        Ok(DerivationPath(vec![BIP44_PURPOSE + BIP32_HARDENED_INDEX,
+                               STACKS_BIP44_COIN_TYPE + BIP32_HARDENED_INDEX,
+                               account + BIP32_HARDENED_INDEX,
+                               chain,
+                               index]))
+    }
+
+    pub fn stacks_account_key(account: u32, index: u32) -> Result<DerivationPath, Error> {
+        DerivationPath::stacks_key(account, STACKS_ACCOUNT_KEY_CHAIN, index)
+    }
+
+    pub fn stacks_microblock_key(account: u32, index: u32) -> Result<DerivationPath, Error> {
+        DerivationPath::stacks_key(account, STACKS_MICROBLOCK_KEY_CHAIN, index)
+    }
+
+    pub fn stacks_vrf_key(account: u32, index: u32) -> Result<DerivationPath, Error> {
+        DerivationPath::stacks_key(account, STACKS_VRF_KEY_CHAIN, index)
+    }
+}
+
+/// A BIP32 extended private key
+#[derive(Clone, PartialEq)]
+pub struct ExtendedPrivateKey {
+    secret_key: LibSecp256k1PrivateKey,
+    chain_code: [u8; 32],
+    depth: u8,
+    child_number: u32,
+}
+
+impl fmt::Debug for ExtendedPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // don't leak the key into logs
+        write!(f, "ExtendedPrivateKey(depth={}, child_number={})", self.depth, self.child_number)
+    }
+}
+
+impl ExtendedPrivateKey {
+    /// Derive the master key from a seed
+    pub fn from_seed(seed: &[u8]) -> Result<ExtendedPrivateKey, Error> {
+        let i = hmac_sha512(b"Bitcoin seed", seed);
+        ExtendedPrivateKey::from_hmac_output(&i, None, 0, 0)
+    }
+
+    /// Derive the master key from a mnemonic and its passphrase
+    pub fn from_mnemonic(mnemonic: &Mnemonic, passphrase: &str) -> Result<ExtendedPrivateKey, Error> {
+        ExtendedPrivateKey::from_seed(&mnemonic.to_seed(passphrase))
+    }
+
+    /// Build a key from an HMAC output.  The left half is the key (tweaking the parent key, if
+    /// given), and the right half is the chain code.
+    fn from_hmac_output(i: &[u8; 64], parent: Option<&LibSecp256k1PrivateKey>, depth: u8, child_number: u32) -> Result<ExtendedPrivateKey, Error> {
+        _secp256k1.with(|ctx| {
+            let mut secret_key = LibSecp256k1PrivateKey::from_slice(ctx, &i[0..32])
+                .map_err(|_e| Error::InvalidDerivedKey)?;
+            if let Some(parent_key) = parent {
+                secret_key.add_assign(ctx, parent_key)
+                    .map_err(|_e| Error::InvalidDerivedKey)?;
+            }
+
+            let mut chain_code = [0u8; 32];
+            chain_code.copy_from_slice(&i[32..64]);
+
+            Ok(ExtendedPrivateKey {
+                secret_key,
+                chain_code,
+                depth,
+                child_number
+            })
+        })
+    }
+
+    /// Derive the child key at the given index (hardened if the index is at least `BIP32_HARDENED_INDEX`)
+    pub fn derive_child(&self, index: u32) -> Result<ExtendedPrivateKey, Error> {
+        let mut data = Vec::with_capacity(37);
+        if index >= BIP32_HARDENED_INDEX {
+            data.push(0x00);
+            data.extend_from_slice(&self.secret_key[..]);
+        }
+        else {
+            let public_key = _secp256k1.with(|ctx| LibSecp256k1PublicKey::from_secret_key(ctx, &self.secret_key));
+            data.extend_from_slice(&public_key.serialize());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+
+        let i = hmac_sha512(&self.chain_code, &data);
+        let depth = self.depth.checked_add(1).ok_or(Error::InvalidDerivedKey)?;
+        ExtendedPrivateKey::from_hmac_output(&i, Some(&self.secret_key), depth, index)
+    }
+
+    /// Derive the descendant key along a path from this key
+    pub fn derive_path(&self, path: &DerivationPath) -> Result<ExtendedPrivateKey, Error> {
+        let mut key = self.clone();
+        for index in path.0.iter() {
+            key = key.derive_child(*index)?;
+        }
+        Ok(key)
+    }
+
+    pub fn secret_bytes(&self) -> [u8; 32] {
+        let mut ret = [0u8; 32];
+        ret.copy_from_slice(&self.secret_key[..]);
+        ret
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn child_number(&self) -> u32 {
+        self.child_number
+    }
+
+    /// This key as a secp256k1 private key with a compressed public key
+    pub fn to_secp256k1_private_key(&self) -> Secp256k1PrivateKey {
+        let mut bytes = self.secret_bytes().to_vec();
+        bytes.push(0x01);
+        Secp256k1PrivateKey::from_slice(&bytes[..])
+            .expect("BUG: derived key is not a valid secp256k1 key")
+    }
+
+    /// This key's secret bytes as a VRF (Ed25519) private key
+    pub fn to_vrf_private_key(&self) -> VRFPrivateKey {
+        VRFPrivateKey::from_bytes(&self.secret_bytes())
+            .expect("BUG: 32 bytes are not a valid Ed25519 private key")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use util::hash::{hex_bytes, to_hex};
+    use burnchains::PublicKey;
+    use util::secp256k1::Secp256k1PublicKey;
+
+    #[test]
+    fn test_mnemonic_vectors() {
+        // BIP39 test vectors (passphrase "TREZOR")
+        let vectors = vec![
+            ("00000000000000000000000000000000",
+             "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+             "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"),
+            ("7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f",
+             "legal winner thank year wave sausage worth useful legal winner thank yellow",
+             "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6fa457fe1296106559a3c80937a1c1069be3a3a5bd381ee6260e8d9739fce1f607"),
+            ("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+             "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+             "dd48c104698c30cfe2b6142103248622fb7bb0ff692eebb00089b32d22484e1613912f0a5b694407be899ffd31ed3992c456cdf60f5d4564b8ba3f05a69890ad"),
+        ];
+
+        for (entropy_hex, phrase, seed_hex) in vectors {
+            let entropy = hex_bytes(entropy_hex).unwrap();
+            let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+            assert_eq!(mnemonic.phrase(), phrase);
+            assert_eq!(to_hex(&mnemonic.to_seed("TREZOR")), seed_hex);
+
+            let parsed = Mnemonic::from_phrase(phrase).unwrap();
+            assert_eq!(parsed, mnemonic);
+            assert_eq!(parsed.entropy(), &entropy[..]);
+        }
+    }
+
+    #[test]
+    fn test_mnemonic_invalid() {
+        assert_eq!(Mnemonic::from_entropy(&[0u8; 15]), Err(Error::InvalidEntropyLength(15)));
+        assert_eq!(Mnemonic::from_entropy(&[0u8; 36]), Err(Error::InvalidEntropyLength(36)));
+        assert_eq!(Mnemonic::generate(13), Err(Error::InvalidWordCount(13)));
+        assert_eq!(Mnemonic::from_phrase("abandon abandon abandon"), Err(Error::InvalidWordCount(3)));
+        assert_eq!(Mnemonic::from_phrase("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon"),
+                   Err(Error::InvalidChecksum));
+        assert_eq!(Mnemonic::from_phrase("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon stacks"),
+                   Err(Error::UnknownWord("stacks".to_string())));
+
+        for num_words in [12, 15, 18, 21, 24].iter() {
+            let mnemonic = Mnemonic::generate(*num_words).unwrap();
+            assert_eq!(Mnemonic::from_phrase(&mnemonic.phrase()).unwrap(), mnemonic);
+        }
+    }
+
+    #[test]
+    fn test_derivation_path() {
+        let path = DerivationPath::from_string("m/44'/5757'/0'/0/1").unwrap();
+        assert_eq!(path, DerivationPath::stacks_account_key(0, 1).unwrap());
+        assert_eq!(format!("{}", path), "m/44'/5757'/0'/0/1");
+        assert_eq!(DerivationPath::from_string("m/0h/1").unwrap(), DerivationPath(vec![BIP32_HARDENED_INDEX, 1]));
+        assert_eq!(DerivationPath::from_string("m").unwrap(), DerivationPath(vec![]));
+
+        for bad in ["", "44'/0", "m/", "m/x", "m/-1", "m/2147483648"].iter() {
+            assert!(DerivationPath::from_string(bad).is_err(), "parsed {}", bad);
+        }
+
+        // accounts, chains and indexes that would overflow into (or already are) hardened indexes
+        assert_eq!(DerivationPath::stacks_account_key(BIP32_HARDENED_INDEX, 0), Err(Error::InvalidIndex(BIP32_HARDENED_INDEX)));
+        assert_eq!(DerivationPath::stacks_key(0, u32::max_value(), 0), Err(Error::InvalidIndex(u32::max_value())));
+        assert_eq!(DerivationPath::stacks_vrf_key(0, BIP32_HARDENED_INDEX), Err(Error::InvalidIndex(BIP32_HARDENED_INDEX)));
+        assert_eq!(format!("{}", DerivationPath::stacks_account_key(BIP32_HARDENED_INDEX - 1, 0).unwrap()), "m/44'/5757'/2147483647'/0/0");
+    }
+
+    #[test]
+    fn test_bip32_vector_1() {
+        let seed = hex_bytes("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = ExtendedPrivateKey::from_seed(&seed).unwrap();
+
+        // (path, chain code, private key)
+        let vectors = vec![
+            ("m", "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508", "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"),
+            ("m/0'", "47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141", "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea"),
+            ("m/0'/1", "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19", "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368"),
+            ("m/0'/1/2'", "04466b9cc8e161e966409ca52986c584f07e9dc81f735db683c3ff6ec7b1503f", "cbce0d719ecf7431d88e6a89fa1483e02e35092af60c042b1df2ff59fa424dca"),
+            ("m/0'/1/2'/2", "cfb71883f01676f587d023cc53a35bc7f88f724b1f8c2892ac1275ac822a3edd", "0f479245fb19a38a1954c5c7c0ebab2f9bdfd96a17563ef28a6a4b1a2a764ef4"),
+            ("m/0'/1/2'/2/1000000000", "c783e67b921d2beb8f6b389cc646d7263b4145701dadd2161548a8b078e65e9e", "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8"),
+        ];
+
+        for (path, chain_code, secret) in vectors {
+            let key = master.derive_path(&DerivationPath::from_string(path).unwrap()).unwrap();
+            assert_eq!(to_hex(key.chain_code()), chain_code, "chain code at {}", path);
+            assert_eq!(to_hex(&key.secret_bytes()), secret, "secret key at {}", path);
+        }
+    }
+
+    #[test]
+    fn test_stacks_keys() {
+        let mnemonic = Mnemonic::from_phrase("legal winner thank year wave sausage worth useful legal winner thank yellow").unwrap();
+        let master = ExtendedPrivateKey::from_mnemonic(&mnemonic, "").unwrap();
+
+        let account_key = master.derive_path(&DerivationPath::stacks_account_key(0, 0).unwrap()).unwrap();
+        assert_eq!(account_key.depth(), 5);
+        assert_eq!(account_key.child_number(), 0);
+
+        let sk = account_key.to_secp256k1_private_key();
+        assert!(sk.compress_public());
+        assert!(Secp256k1PublicKey::from_private(&sk).compressed());
+
+        // chains and indexes give distinct keys, and derivation is deterministic
+        let keys = vec![
+            master.derive_path(&DerivationPath::stacks_account_key(0, 0).unwrap()).unwrap(),
+            master.derive_path(&DerivationPath::stacks_account_key(0, 1).unwrap()).unwrap(),
+            master.derive_path(&DerivationPath::stacks_account_key(1, 0).unwrap()).unwrap(),
+            master.derive_path(&DerivationPath::stacks_microblock_key(0, 0).unwrap()).unwrap(),
+            master.derive_path(&DerivationPath::stacks_vrf_key(0, 0).unwrap()).unwrap(),
+        ];
+        for i in 0..keys.len() {
+            for j in (i+1)..keys.len() {
+                assert!(keys[i].secret_bytes() != keys[j].secret_bytes());
+            }
+        }
+        assert_eq!(keys[0], account_key);
+
+        let vrf_key = keys[4].to_vrf_private_key();
+        assert_eq!(vrf_key.to_hex(), to_hex(&keys[4].secret_bytes()));
+
+        // a passphrase gives a different wallet
+        let other_master = ExtendedPrivateKey::from_mnemonic(&mnemonic, "TREZOR").unwrap();
+        assert!(other_master.derive_path(&DerivationPath::stacks_account_key(0, 0).unwrap()).unwrap().secret_bytes() != account_key.secret_bytes());
+    }
+}
@@ -0,0 +1,420 @@
+/*
+ copyright: (c) 2013-2020 by Blockstack PBC, a public benefit corporation.
+
+ This file is part of Blockstack.
+
+ Blockstack is free software. You may redistribute or modify
+ it under the terms of the GNU General Public License as published by
+ the Free Software Foundation, either version 3 of the License or
+ (at your option) any later version.
+
+ Blockstack is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY, including without the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU General Public License for more details.
+
+ You should have received a copy of the GNU General Public License
+ along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
+*/
+
+//! Passphrase-encrypted keystore files for secp256k1 and VRF private keys.
+//!
+//! A keystore is a versioned JSON document.  In version 2, the passphrase is stretched with
+//! PBKDF2-HMAC-SHA512 into a 32-byte key, and the private key is encrypted with AES-256-GCM
+//! under a random 96-bit nonce.  The version, key type and public key are authenticated as
+//! associated data.  The public key is stored in the clear so a keystore can be identified
+//! without its passphrase.
+
+use std::fmt;
+use std::error;
+use std::fs;
+use std::io;
+
+use serde_json;
+
+use rand::RngCore;
+use rand::thread_rng;
+
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use hmac::Hmac;
+use pbkdf2::pbkdf2;
+use sha2::Sha512;
+
+use burnchains::PrivateKey;
+use util::hash::{hex_bytes, to_hex};
+use util::secp256k1::{Secp256k1PrivateKey, Secp256k1PublicKey};
+use util::vrf::{VRFPrivateKey, VRFPublicKey};
+
+pub const KEYSTORE_VERSION : u32 = 2;
+pub const KEYSTORE_KDF : &'static str = "pbkdf2-hmac-sha512";
+pub const KEYSTORE_CIPHER : &'static str = "aes-256-gcm";
+
+/// Default number of PBKDF2 rounds for new keystores
+pub const KEYSTORE_DEFAULT_ROUNDS : u32 = 100_000;
+
+/// Most PBKDF2 rounds a keystore may ask for, so a malicious keystore can't make decryption
+/// run (practically) forever
+pub const KEYSTORE_MAX_ROUNDS : u32 = 10_000_000;
+
+const KEYSTORE_SALT_LEN : usize = 32;
+const KEYSTORE_KEY_LEN : usize = 32;
+const KEYSTORE_NONCE_LEN : usize = 12;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Unsupported keystore version, or unsupported algorithm
+    Unsupported(String),
+    /// Keystore is malformed
+    Malformed(String),
+    /// Wrong passphrase, or the keystore was tampered with
+    BadPassphrase,
+    /// Could not read or write the keystore file
+    IOError(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Unsupported(ref s) => write!(f, "Unsupported keystore: {}", s),
+            Error::Malformed(ref s) => write!(f, "Malformed keystore: {}", s),
+            Error::BadPassphrase => write!(f, "Wrong keystore passphrase, or corrupt keystore"),
+            Error::IOError(ref e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::IOError(ref e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+/// A private key that can be stored in a keystore
+#[derive(Debug, Clone)]
+pub enum KeystoreKey {
+    Secp256k1(Secp256k1PrivateKey),
+    VRF(VRFPrivateKey),
+}
+
+impl KeystoreKey {
+    fn key_type(&self) -> &'static str {
+        match *self {
+            KeystoreKey::Secp256k1(_) => "secp256k1",
+            KeystoreKey::VRF(_) => "vrf",
+        }
+    }
+
+    fn public_key_hex(&self) -> String {
+        match *self {
+            KeystoreKey::Secp256k1(ref sk) => Secp256k1PublicKey::from_private(sk).to_hex(),
+            KeystoreKey::VRF(ref sk) => VRFPublicKey::from_private(sk).to_hex(),
+        }
+    }
+
+    /// Plaintext stored in the keystore.  A secp256k1 key keeps its compressed-public-key flag.
+    fn to_bytes(&self) -> Vec<u8> {
+        match *self {
+            KeystoreKey::Secp256k1(ref sk) => sk.to_bytes(),
+            KeystoreKey::VRF(ref sk) => sk.as_bytes().to_vec(),
+        }
+    }
+
+    fn from_bytes(key_type: &str, bytes: &[u8]) -> Result<KeystoreKey, Error> {
+        match key_type {
+            "secp256k1" => Secp256k1PrivateKey::from_slice(bytes)
+                .map(KeystoreKey::Secp256k1)
+                .map_err(|e| Error::Malformed(e.to_string())),
+            "vrf" => VRFPrivateKey::from_bytes(bytes)
+                .map(KeystoreKey::VRF)
+                .ok_or(Error::Malformed("invalid VRF private key".to_string())),
+            _ => Err(Error::Unsupported(format!("key type '{}'", key_type)))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeystoreKDFParams {
+    pub rounds: u32,
+    pub salt: String,
+}
+
+/// The JSON keystore document
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    pub key_type: String,
+    pub public_key: String,
+    pub kdf: String,
+    pub kdf_params: KeystoreKDFParams,
+    pub cipher: String,
+    pub nonce: String,
+    /// AES-256-GCM ciphertext, followed by its authentication tag
+    pub ciphertext: String,
+}
+
+/// Stretch the passphrase into the AES-256-GCM key
+fn derive_key(passphrase: &str, salt: &[u8], rounds: u32) -> [u8; KEYSTORE_KEY_LEN] {
+    let mut key = [0u8; KEYSTORE_KEY_LEN];
+    pbkdf2::<Hmac<Sha512>>(passphrase.as_bytes(), salt, rounds as usize, &mut key);
+    key
+}
+
+/// Associated data: everything the decryption depends on besides the nonce and ciphertext
+fn associated_data(version: u32, key_type: &str, public_key: &str) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&version.to_be_bytes());
+    for field in [key_type.as_bytes(), public_key.as_bytes()].iter() {
+        data.extend_from_slice(&(field.len() as u32).to_be_bytes());
+        data.extend_from_slice(field);
+    }
+    data
+}
+
+fn decode_hex(field: &str, value: &str) -> Result<Vec<u8>, Error> {
+    hex_bytes(value).map_err(|_e| Error::Malformed(format!("{} is not a hex string", field)))
+}
+
+impl Keystore {
+    /// Encrypt a private key with a passphrase, stretching the passphrase with `rounds` rounds of PBKDF2
+    pub fn encrypt(key: &KeystoreKey, passphrase: &str, rounds: u32) -> Keystore {
+        let mut salt = [0u8; KEYSTORE_SALT_LEN];
+        let mut nonce = [0u8; KEYSTORE_NONCE_LEN];
+        thread_rng().fill_bytes(&mut salt);
+        thread_rng().fill_bytes(&mut nonce);
+
+        let enc_key = derive_key(passphrase, &salt, rounds);
+
+        let key_type = key.key_type();
+        let public_key = key.public_key_hex();
+        let plaintext = key.to_bytes();
+        let aad = associated_data(KEYSTORE_VERSION, key_type, &public_key);
+        let ciphertext = Aes256Gcm::new(&enc_key.into())
+            .encrypt(&nonce.into(), Payload { msg: &plaintext, aad: &aad })
+            .expect("FATAL: failed to encrypt private key");
+
+        Keystore {
+            version: KEYSTORE_VERSION,
+            key_type: key_type.to_string(),
+            public_key,
+            kdf: KEYSTORE_KDF.to_string(),
+            kdf_params: KeystoreKDFParams {
+                rounds,
+                salt: to_hex(&salt),
+            },
+            cipher: KEYSTORE_CIPHER.to_string(),
+            nonce: to_hex(&nonce),
+            ciphertext: to_hex(&ciphertext),
+        }
+    }
+
+    /// Decrypt the private key.  Fails if the passphrase is wrong or the keystore was modified.
+    pub fn decrypt(&self, passphrase: &str) -> Result<KeystoreKey, Error> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(Error::Unsupported(format!("version {}", self.version)));
+        }
+        if self.kdf != KEYSTORE_KDF || self.cipher != KEYSTORE_CIPHER {
+            return Err(Error::Unsupported(format!("algorithms {}/{}", &self.kdf, &self.cipher)));
+        }
+        if self.kdf_params.rounds == 0 || self.kdf_params.rounds > KEYSTORE_MAX_ROUNDS {
+            return Err(Error::Malformed(format!("{} PBKDF2 rounds is not between 1 and {}", self.kdf_params.rounds, KEYSTORE_MAX_ROUNDS)));
+        }
+
+        let salt = decode_hex("salt", &self.kdf_params.salt)?;
+        let nonce_bytes = decode_hex("nonce", &self.nonce)?;
+        let ciphertext = decode_hex("ciphertext", &self.ciphertext)?;
+        if nonce_bytes.len() != KEYSTORE_NONCE_LEN {
+            return Err(Error::Malformed(format!("nonce is not {} bytes", KEYSTORE_NONCE_LEN)));
+        }
+        let mut nonce = [0u8; KEYSTORE_NONCE_LEN];
+        nonce.copy_from_slice(&nonce_bytes);
+
+        let enc_key = derive_key(passphrase, &salt, self.kdf_params.rounds);
+        let aad = associated_data(self.version, &self.key_type, &self.public_key);
+        let plaintext = Aes256Gcm::new(&enc_key.into())
+            .decrypt(&nonce.into(), Payload { msg: &ciphertext, aad: &aad })
+            .map_err(|_e| Error::BadPassphrase)?;
+
+        let key = KeystoreKey::from_bytes(&self.key_type, &plaintext)?;
+        if key.public_key_hex() != self.public_key {
+            return Err(Error::Malformed("public key does not match private key".to_string()));
+        }
+        Ok(key)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("BUG: failed to serialize keystore")
+    }
+
+    pub fn from_json(json: &str) -> Result<Keystore, Error> {
+        serde_json::from_str(json).map_err(|e| Error::Malformed(format!("{}", e)))
+    }
+
+    /// Write the keystore to a new file.  Fails if the file already exists, so a key is never
+    /// accidentally overwritten.
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        use std::io::Write;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut f = options.open(path).map_err(Error::IOError)?;
+        f.write_all(self.to_json().as_bytes()).map_err(Error::IOError)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Keystore, Error> {
+        let json = fs::read_to_string(path).map_err(Error::IOError)?;
+        Keystore::from_json(&json)
+    }
+
+    /// Load and decrypt a secp256k1 private key
+    pub fn load_secp256k1_private_key(path: &str, passphrase: &str) -> Result<Secp256k1PrivateKey, Error> {
+        match Keystore::load(path)?.decrypt(passphrase)? {
+            KeystoreKey::Secp256k1(sk) => Ok(sk),
+            _ => Err(Error::Malformed(format!("{} does not hold a secp256k1 private key", path)))
+        }
+    }
+
+    /// Load and decrypt a VRF private key
+    pub fn load_vrf_private_key(path: &str, passphrase: &str) -> Result<VRFPrivateKey, Error> {
+        match Keystore::load(path)?.decrypt(passphrase)? {
+            KeystoreKey::VRF(sk) => Ok(sk),
+            _ => Err(Error::Malformed(format!("{} does not hold a VRF private key", path)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // keep the tests fast
+    const TEST_ROUNDS : u32 = 16;
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let mut uncompressed_sk = Secp256k1PrivateKey::new();
+        uncompressed_sk.set_compress_public(false);
+
+        let keys = vec![
+            KeystoreKey::Secp256k1(Secp256k1PrivateKey::new()),
+            KeystoreKey::Secp256k1(uncompressed_sk),
+            KeystoreKey::VRF(VRFPrivateKey::new()),
+        ];
+
+        for key in keys.iter() {
+            let keystore = Keystore::encrypt(key, "correct horse battery staple", TEST_ROUNDS);
+            assert_eq!(keystore.version, KEYSTORE_VERSION);
+            assert_eq!(keystore.public_key, key.public_key_hex());
+
+            // the private key is not stored in the clear
+            let key_hex = to_hex(&key.to_bytes());
+            assert!(!keystore.to_json().contains(&key_hex));
+
+            let parsed = Keystore::from_json(&keystore.to_json()).unwrap();
+            assert_eq!(parsed, keystore);
+            assert_eq!(parsed.decrypt("correct horse battery staple").unwrap().to_bytes(), key.to_bytes());
+
+            match parsed.decrypt("wrong passphrase") {
+                Err(Error::BadPassphrase) => {},
+                x => panic!("Expected BadPassphrase, got {:?}", &x)
+            }
+        }
+
+        // same key and passphrase encrypt differently each time
+        let keystore_1 = Keystore::encrypt(&keys[0], "pass", TEST_ROUNDS);
+        let keystore_2 = Keystore::encrypt(&keys[0], "pass", TEST_ROUNDS);
+        assert!(keystore_1.ciphertext != keystore_2.ciphertext);
+    }
+
+    #[test]
+    fn test_keystore_tampering() {
+        let key = KeystoreKey::Secp256k1(Secp256k1PrivateKey::new());
+        let keystore = Keystore::encrypt(&key, "pass", TEST_ROUNDS);
+
+        let mut bad_ciphertext = keystore.clone();
+        let mut ciphertext = hex_bytes(&bad_ciphertext.ciphertext).unwrap();
+        ciphertext[0] ^= 0x01;
+        bad_ciphertext.ciphertext = to_hex(&ciphertext);
+
+        let mut bad_key_type = keystore.clone();
+        bad_key_type.key_type = "vrf".to_string();
+
+        let mut bad_public_key = keystore.clone();
+        bad_public_key.public_key = Secp256k1PublicKey::from_private(&Secp256k1PrivateKey::new()).to_hex();
+
+        let mut bad_rounds = keystore.clone();
+        bad_rounds.kdf_params.rounds += 1;
+
+        let mut bad_nonce = keystore.clone();
+        let mut nonce = hex_bytes(&bad_nonce.nonce).unwrap();
+        nonce[0] ^= 0x01;
+        bad_nonce.nonce = to_hex(&nonce);
+
+        for ks in [bad_ciphertext, bad_nonce, bad_key_type, bad_public_key, bad_rounds].iter() {
+            match ks.decrypt("pass") {
+                Err(Error::BadPassphrase) => {},
+                x => panic!("Expected BadPassphrase, got {:?}", &x)
+            }
+        }
+
+        // the KDF can't be made to run for too long
+        let mut too_many_rounds = keystore.clone();
+        too_many_rounds.kdf_params.rounds = KEYSTORE_MAX_ROUNDS + 1;
+        let mut no_rounds = keystore.clone();
+        no_rounds.kdf_params.rounds = 0;
+
+        for ks in [too_many_rounds, no_rounds].iter() {
+            match ks.decrypt("pass") {
+                Err(Error::Malformed(_)) => {},
+                x => panic!("Expected Malformed, got {:?}", &x)
+            }
+        }
+
+        let mut bad_version = keystore.clone();
+        bad_version.version = 1;
+        match bad_version.decrypt("pass") {
+            Err(Error::Unsupported(_)) => {},
+            x => panic!("Expected Unsupported, got {:?}", &x)
+        }
+
+        let mut bad_cipher = keystore.clone();
+        bad_cipher.cipher = "aes-128-ctr".to_string();
+        match bad_cipher.decrypt("pass") {
+            Err(Error::Unsupported(_)) => {},
+            x => panic!("Expected Unsupported, got {:?}", &x)
+        }
+
+        assert!(Keystore::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_keystore_files() {
+        let path = "/tmp/test_keystore_files.json";
+        if fs::metadata(path).is_ok() {
+            fs::remove_file(path).unwrap();
+        }
+
+        let sk = VRFPrivateKey::new();
+        let keystore = Keystore::encrypt(&KeystoreKey::VRF(sk.clone()), "pass", TEST_ROUNDS);
+        keystore.save(path).unwrap();
+
+        // never overwrite a keystore
+        assert!(keystore.save(path).is_err());
+
+        assert_eq!(Keystore::load_vrf_private_key(path, "pass").unwrap(), sk);
+        assert!(Keystore::load_secp256k1_private_key(path, "pass").is_err());
+        assert!(Keystore::load_vrf_private_key("/tmp/test_keystore_files.nonexistent", "pass").is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -28,6 +28,8 @@ pub mod secp256k1;
 pub mod uint;
 pub mod strings;
 pub mod vrf;
+pub mod hdkeys;
+pub mod keystore;
 
 use std::time;
 use std::thread;
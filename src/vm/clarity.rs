@@ -13,9 +13,11 @@ use vm::costs::{LimitedCostTracker, ExecutionCost};
 use chainstate::burn::BlockHeaderHash;
 use chainstate::stacks::index::marf::MARF;
 use chainstate::stacks::index::TrieHash;
+use chainstate::stacks::index::Error as MarfError;
 
 use std::error;
 use std::fmt;
+use std::collections::HashSet;
 
 ///
 /// A high-level interface for interacting with the Clarity VM.
@@ -41,7 +43,8 @@ pub struct ClarityBlockConnection<'a> {
     datastore: MarfedKV,
     parent: &'a mut ClarityInstance,
     header_db: &'a dyn HeadersDB,
-    cost_track: Option<LimitedCostTracker>
+    cost_track: Option<LimitedCostTracker>,
+    savepoint_cost_tracks: Vec<LimitedCostTracker>
 }
 
 #[derive(Debug)]
@@ -117,7 +120,8 @@ impl ClarityInstance {
             datastore,
             header_db,
             parent: self,
-            cost_track: Some(LimitedCostTracker::new_max_limit())
+            cost_track: Some(LimitedCostTracker::new_max_limit()),
+            savepoint_cost_tracks: vec![]
         }
     }
 
@@ -134,7 +138,8 @@ impl ClarityInstance {
             datastore,
             header_db,
             parent: self,
-            cost_track: Some(LimitedCostTracker::new(limit))
+            cost_track: Some(LimitedCostTracker::new(limit)),
+            savepoint_cost_tracks: vec![]
         }
     }
 
@@ -151,6 +156,14 @@ impl ClarityInstance {
             .map_err(Error::from)
     }
 
+    /// Discard the MARF trie nodes that only the tries of the given blocks still need (see
+    /// MARF::prune).  No block may be open.
+    pub fn prune_marf(&mut self, pruned: &HashSet<BlockHeaderHash>, already_pruned: &HashSet<BlockHeaderHash>) -> Result<u64, MarfError> {
+        let datastore = self.datastore.as_mut()
+            .expect("FAIL: attempt to prune the MARF while a block is still open");
+        datastore.get_marf().prune(pruned, already_pruned)
+    }
+
     pub fn destroy(mut self) -> MarfedKV {
         let datastore = self.datastore.take()
             .expect("FAIL: attempt to recover database connection from clarity instance which is still open");
@@ -209,6 +222,39 @@ impl <'a> ClarityBlockConnection <'a> {
         self.parent.datastore.replace(self.datastore);
     }
 
+    /// Begin a savepoint: the writes and execution costs of everything done from here on can be
+    /// kept with commit_savepoint() or undone with rollback_savepoint().  Savepoints nest: each
+    /// commit or rollback applies to the innermost open savepoint.
+    pub fn begin_savepoint(&mut self) {
+        let cost_track = self.cost_track.as_ref()
+            .expect("Failed to get ownership of cost tracker in ClarityBlockConnection")
+            .clone();
+        self.savepoint_cost_tracks.push(cost_track);
+        self.datastore.begin_savepoint();
+    }
+
+    /// Keep everything done since begin_savepoint()
+    pub fn commit_savepoint(&mut self) {
+        self.savepoint_cost_tracks.pop()
+            .expect("BUG: no savepoint open");
+        self.datastore.commit_savepoint();
+    }
+
+    /// Undo everything done since begin_savepoint(), including the execution costs it incurred
+    pub fn rollback_savepoint(&mut self) {
+        let cost_track = self.savepoint_cost_tracks.pop()
+            .expect("BUG: no savepoint open");
+        self.cost_track.replace(cost_track);
+        self.datastore.rollback_savepoint();
+    }
+
+    /// Get the total execution cost of this block so far
+    pub fn cost_so_far(&self) -> ExecutionCost {
+        self.cost_track.as_ref()
+            .expect("Failed to get ownership of cost tracker in ClarityBlockConnection")
+            .get_total()
+    }
+
     /// Get the MARF root hash
     pub fn get_root_hash(&mut self) -> TrieHash {
         self.datastore.get_root_hash()
@@ -219,6 +265,11 @@ impl <'a> ClarityBlockConnection <'a> {
         self.datastore.get_marf()
     }
 
+    /// Make `at-block` fail for blocks below the given height for the rest of this block
+    pub fn set_history_horizon(&mut self, horizon: u32) {
+        self.datastore.set_history_horizon(Some(horizon));
+    }
+
     /// Do something to the underlying DB that involves writing.
     pub fn with_clarity_db<F, R>(&mut self, to_do: F) -> Result<R, Error>
     where F: FnOnce(&mut ClarityDatabase) -> Result<R, Error> {
@@ -349,7 +400,7 @@ impl <'a> ClarityBlockConnection <'a> {
         Ok(result)
     }
 
-    #[cfg(test)]
+    /// Evaluate read-only Clarity code in the context of the given contract
     pub fn eval_read_only(&mut self, contract: &QualifiedContractIdentifier, code: &str) -> Result<Value, Error> {
         let (result, _) = self.with_abort_callback(
             |vm_env| { vm_env.eval_read_only(contract, code).map_err(Error::from) },
@@ -45,17 +45,29 @@ pub enum AssetMapEntry {
     Asset(Vec<Value>)
 }
 
+/// A single movement of an asset made by a native function, with both of its parties.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssetEvent {
+    STXTransfer { sender: PrincipalData, recipient: PrincipalData, amount: u128 },
+    STXBurn { sender: PrincipalData, amount: u128 },
+    TokenTransfer { sender: PrincipalData, recipient: PrincipalData, asset: AssetIdentifier, amount: u128 },
+    TokenMint { recipient: PrincipalData, asset: AssetIdentifier, amount: u128 },
+    AssetTransfer { sender: PrincipalData, recipient: PrincipalData, asset: AssetIdentifier, value: Value },
+    AssetMint { recipient: PrincipalData, asset: AssetIdentifier, value: Value }
+}
+
 /**
  The AssetMap is used to track which assets have been transfered from whom
- during the execution of a transaction.
+ during the execution of a transaction.  It also keeps the ordered list of
+ asset events, so that both sides of each transfer (and mints) can be reported.
  */
 #[derive(Debug, Clone)]
 pub struct AssetMap {
     stx_map: HashMap<PrincipalData, u128>,
     burn_map: HashMap<PrincipalData, u128>,
     token_map: HashMap<PrincipalData, HashMap<AssetIdentifier, u128>>,
-    asset_map: HashMap<PrincipalData, HashMap<AssetIdentifier, Vec<Value>>>
-        
+    asset_map: HashMap<PrincipalData, HashMap<AssetIdentifier, Vec<Value>>>,
+    events: Vec<AssetEvent>
 }
 
 /** GlobalContext represents the outermost context for a single transaction's
@@ -102,7 +114,8 @@ impl AssetMap {
             stx_map: HashMap::new(),
             burn_map: HashMap::new(),
             token_map: HashMap::new(),
-            asset_map: HashMap::new()
+            asset_map: HashMap::new(),
+            events: Vec::new()
         }
     }
     
@@ -175,6 +188,14 @@ impl AssetMap {
         Ok(())
     }
 
+    pub fn add_event(&mut self, event: AssetEvent) {
+        self.events.push(event);
+    }
+
+    pub fn into_events(self) -> Vec<AssetEvent> {
+        self.events
+    }
+
     // This will add any asset transfer data from other to self,
     //   aborting _all_ changes in the event of an error, leaving self unchanged
     pub fn commit_other(&mut self, mut other: AssetMap) -> Result<()> {
@@ -235,6 +256,8 @@ impl AssetMap {
             principal_map.insert(asset, amount);
         }
 
+        self.events.append(&mut other.events);
+
         Ok(())
     }
 
@@ -431,13 +454,21 @@ impl <'a> OwnedEnvironment <'a> {
                             }).unwrap();
     }
 
+    #[cfg(test)]
+    pub fn stx_lock(&mut self, principal: &PrincipalData, amount: u128, unlock_height: u64) {
+        self.execute_in_env(principal.clone().into(),
+                            |env| {
+                                env.global_context.database.set_account_stx_locked(principal, amount, unlock_height);
+                                Ok(())
+                            }).unwrap();
+    }
+
     #[cfg(test)]
     pub fn eval_raw(&mut self, program: &str) -> Result<(Value, AssetMap)> {
         self.execute_in_env(Value::from(QualifiedContractIdentifier::transient().issuer),
                             |exec_env| exec_env.eval_raw(program))
     }
 
-    #[cfg(test)]
     pub fn eval_read_only(&mut self, contract: &QualifiedContractIdentifier, program: &str) -> Result<(Value, AssetMap)>  {
         self.execute_in_env(Value::from(QualifiedContractIdentifier::transient().issuer),
                             |exec_env| exec_env.eval_read_only(contract, program))
@@ -684,28 +715,50 @@ impl <'a> GlobalContext<'a> {
             .expect("Failed to obtain asset map")
     }
 
-    pub fn log_asset_transfer(&mut self, sender: &PrincipalData, contract_identifier: &QualifiedContractIdentifier, asset_name: &ClarityName, transfered: Value) {
+    pub fn log_asset_transfer(&mut self, sender: &PrincipalData, recipient: &PrincipalData, contract_identifier: &QualifiedContractIdentifier, asset_name: &ClarityName, transfered: Value) {
         let asset_identifier = AssetIdentifier { contract_identifier: contract_identifier.clone(),
                                                  asset_name: asset_name.clone() };
-        self.get_asset_map()
-            .add_asset_transfer(sender, asset_identifier, transfered)
+        let asset_map = self.get_asset_map();
+        asset_map.add_asset_transfer(sender, asset_identifier.clone(), transfered.clone());
+        asset_map.add_event(AssetEvent::AssetTransfer { sender: sender.clone(), recipient: recipient.clone(), asset: asset_identifier, value: transfered });
     }
 
-    pub fn log_token_transfer(&mut self, sender: &PrincipalData, contract_identifier: &QualifiedContractIdentifier, asset_name: &ClarityName, transfered: u128) -> Result<()> {
+    pub fn log_token_transfer(&mut self, sender: &PrincipalData, recipient: &PrincipalData, contract_identifier: &QualifiedContractIdentifier, asset_name: &ClarityName, transfered: u128) -> Result<()> {
         let asset_identifier = AssetIdentifier { contract_identifier: contract_identifier.clone(),
                                                  asset_name: asset_name.clone() };
-        self.get_asset_map()
-            .add_token_transfer(sender, asset_identifier, transfered)
+        let asset_map = self.get_asset_map();
+        asset_map.add_token_transfer(sender, asset_identifier.clone(), transfered)?;
+        asset_map.add_event(AssetEvent::TokenTransfer { sender: sender.clone(), recipient: recipient.clone(), asset: asset_identifier, amount: transfered });
+        Ok(())
     }
 
-    pub fn log_stx_transfer(&mut self, sender: &PrincipalData, transfered: u128) -> Result<()> {
-        self.get_asset_map()
-            .add_stx_transfer(sender, transfered)
+    pub fn log_stx_transfer(&mut self, sender: &PrincipalData, recipient: &PrincipalData, transfered: u128) -> Result<()> {
+        let asset_map = self.get_asset_map();
+        asset_map.add_stx_transfer(sender, transfered)?;
+        asset_map.add_event(AssetEvent::STXTransfer { sender: sender.clone(), recipient: recipient.clone(), amount: transfered });
+        Ok(())
     }
 
     pub fn log_stx_burn(&mut self, sender: &PrincipalData, transfered: u128) -> Result<()> {
+        let asset_map = self.get_asset_map();
+        asset_map.add_stx_burn(sender, transfered)?;
+        asset_map.add_event(AssetEvent::STXBurn { sender: sender.clone(), amount: transfered });
+        Ok(())
+    }
+
+    // mints don't take assets from anyone, so they only show up as events
+    pub fn log_token_mint(&mut self, recipient: &PrincipalData, contract_identifier: &QualifiedContractIdentifier, asset_name: &ClarityName, minted: u128) {
+        let asset_identifier = AssetIdentifier { contract_identifier: contract_identifier.clone(),
+                                                 asset_name: asset_name.clone() };
+        self.get_asset_map()
+            .add_event(AssetEvent::TokenMint { recipient: recipient.clone(), asset: asset_identifier, amount: minted })
+    }
+
+    pub fn log_asset_mint(&mut self, recipient: &PrincipalData, contract_identifier: &QualifiedContractIdentifier, asset_name: &ClarityName, minted: Value) {
+        let asset_identifier = AssetIdentifier { contract_identifier: contract_identifier.clone(),
+                                                 asset_name: asset_name.clone() };
         self.get_asset_map()
-            .add_stx_burn(sender, transfered)
+            .add_event(AssetEvent::AssetMint { recipient: recipient.clone(), asset: asset_identifier, value: minted })
     }
 
     pub fn execute <F, T> (&mut self, f: F) -> Result<T> where F: FnOnce(&mut Self) -> Result<T>, {
@@ -937,14 +990,42 @@ mod test {
         am2.add_token_transfer(&p1, t1.clone(), 1).unwrap();
         am2.add_token_transfer(&p2, t1.clone(), 1).unwrap();
 
+        am1.add_event(AssetEvent::TokenMint { recipient: p1.clone(), asset: t1.clone(), amount: 1 });
+        am2.add_event(AssetEvent::TokenMint { recipient: p2.clone(), asset: t1.clone(), amount: 1 });
+
         am1.commit_other(am2).unwrap_err();
 
+        assert_eq!(am1.clone().into_events(), vec![AssetEvent::TokenMint { recipient: p1.clone(), asset: t1.clone(), amount: 1 }]);
+
         let table = am1.to_table();
 
         assert_eq!(table[&p2][&t1], AssetMapEntry::Token(u128::max_value()));
         assert_eq!(table[&p1][&t1], AssetMapEntry::Token(1));
     }
 
+    #[test]
+    fn test_asset_map_events() {
+        let p1 = PrincipalData::Contract(QualifiedContractIdentifier::local("a").unwrap());
+        let p2 = PrincipalData::Contract(QualifiedContractIdentifier::local("b").unwrap());
+        let t1 = AssetIdentifier { contract_identifier: QualifiedContractIdentifier::local("e").unwrap(), asset_name: "a".into() };
+
+        let mut am1 = AssetMap::new();
+        let mut am2 = AssetMap::new();
+
+        am1.add_event(AssetEvent::STXTransfer { sender: p1.clone(), recipient: p2.clone(), amount: 10 });
+        am2.add_event(AssetEvent::AssetMint { recipient: p1.clone(), asset: t1.clone(), value: Value::Int(1) });
+        am2.add_event(AssetEvent::AssetTransfer { sender: p1.clone(), recipient: p2.clone(), asset: t1.clone(), value: Value::Int(1) });
+        am1.commit_other(am2).unwrap();
+        am1.add_event(AssetEvent::STXBurn { sender: p2.clone(), amount: 5 });
+
+        // events keep the order in which they happened, across nested contexts
+        assert_eq!(am1.into_events(),
+                   vec![AssetEvent::STXTransfer { sender: p1.clone(), recipient: p2.clone(), amount: 10 },
+                        AssetEvent::AssetMint { recipient: p1.clone(), asset: t1.clone(), value: Value::Int(1) },
+                        AssetEvent::AssetTransfer { sender: p1.clone(), recipient: p2.clone(), asset: t1.clone(), value: Value::Int(1) },
+                        AssetEvent::STXBurn { sender: p2.clone(), amount: 5 }]);
+    }
+
     #[test]
     fn test_asset_map_combinations() {
         let a_contract_id = QualifiedContractIdentifier::local("a").unwrap();
@@ -36,6 +36,7 @@ impl CostTracker for () {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct LimitedCostTracker {
     total: ExecutionCost,
     limit: ExecutionCost
@@ -15,7 +15,7 @@ use vm::database::{MarfedKV, ClarityBackingStore};
 use vm::database::structures::{
     FungibleTokenMetadata, NonFungibleTokenMetadata, ContractMetadata,
     DataMapMetadata, DataVariableMetadata, ClaritySerializable, SimmedBlock,
-    ClarityDeserializable
+    ClarityDeserializable, AssetHoldings, AssetHolding
 };
 use vm::database::RollbackWrapper;
 use util::db::{DBConn, FromRow};
@@ -38,7 +38,11 @@ pub enum StoreType {
     SimmedBlock = 0x10,
     SimmedBlockHeight = 0x11,
     Nonce = 0x12,
-    STXBalance = 0x13
+    STXBalance = 0x13,
+    STXLocked = 0x14,
+    STXUnlockHeight = 0x15,
+    AssetHoldings = 0x16,
+    AssetHoldingSlot = 0x17
 }
 
 pub struct ClarityDatabase<'a> {
@@ -278,6 +282,21 @@ impl <'a> ClarityDatabase <'a> {
             .expect("Failed to get block data.")
             .into()
     }
+
+    /// Get the height of the burnchain block that elected the block being evaluated.  STX locks
+    /// are measured against this height, not the Stacks block height.  Each block inherits its
+    /// parent's value until the chainstate sets it (see set_burnchain_block_height()).
+    pub fn get_burnchain_block_height(&mut self) -> u64 {
+        let result = self.get("vm-burnchain::block-height");
+        match result {
+            None => 0,
+            Some(height) => height
+        }
+    }
+
+    pub fn set_burnchain_block_height(&mut self, burn_block_height: u64) {
+        self.put("vm-burnchain::block-height", &burn_block_height);
+    }
 }
 
 // this is used so that things like load_map, load_var, load_nft, etc.
@@ -505,6 +524,15 @@ impl <'a> ClarityDatabase <'a> {
         let key =  ClarityDatabase::make_key_for_quad(contract_identifier, StoreType::FungibleToken, token_name, principal.serialize());
         self.put(&key, &balance);
 
+        // keep the principal's asset index up-to-date
+        let holding = AssetHolding::FungibleToken(contract_identifier.clone(), token_name.to_string());
+        if balance > 0 {
+            self.insert_account_asset_holding(principal, &holding);
+        }
+        else {
+            self.remove_account_asset_holding(principal, &holding);
+        }
+
         Ok(())
     }
 
@@ -532,9 +560,19 @@ impl <'a> ClarityDatabase <'a> {
         }
 
         let key = ClarityDatabase::make_key_for_quad(contract_identifier, StoreType::NonFungibleToken, asset_name, asset.serialize());
+        let prev_owner : Option<PrincipalData> = self.get(&key);
 
         self.put(&key, principal);
 
+        // keep the old and new owners' asset indexes up-to-date
+        let holding = AssetHolding::NonFungibleToken(contract_identifier.clone(), asset_name.to_string(), asset.serialize());
+        if let Some(prev_owner) = prev_owner {
+            if prev_owner != *principal {
+                self.remove_account_asset_holding(&prev_owner, &holding);
+            }
+        }
+        self.insert_account_asset_holding(principal, &holding);
+
         Ok(())
     }
 }
@@ -567,6 +605,142 @@ impl<'a> ClarityDatabase<'a> {
         self.put(&key, &balance);
     }
 
+    pub fn make_key_for_account_stx_locked(principal: &PrincipalData) -> String {
+        ClarityDatabase::make_key_for_account(principal, StoreType::STXLocked)
+    }
+
+    pub fn make_key_for_account_unlock_height(principal: &PrincipalData) -> String {
+        ClarityDatabase::make_key_for_account(principal, StoreType::STXUnlockHeight)
+    }
+
+    /// Get the amount of STX locked in this account, and the burnchain block height at which it
+    /// unlocks.  Locked STX still count toward the account's balance, but cannot be spent.
+    /// Once the current burnchain block height reaches the unlock height, the lock no longer
+    /// applies and this returns (0, 0).
+    pub fn get_account_stx_locked(&mut self, principal: &PrincipalData) -> (u128, u64) {
+        let locked_key = ClarityDatabase::make_key_for_account_stx_locked(principal);
+        let locked : u128 = match self.get(&locked_key) {
+            None => 0,
+            Some(locked) => locked
+        };
+
+        if locked == 0 {
+            return (0, 0);
+        }
+
+        let unlock_height_key = ClarityDatabase::make_key_for_account_unlock_height(principal);
+        let unlock_height : u64 = match self.get(&unlock_height_key) {
+            None => 0,
+            Some(unlock_height) => unlock_height
+        };
+
+        if self.get_burnchain_block_height() >= unlock_height {
+            // lock expired
+            return (0, 0);
+        }
+
+        (locked, unlock_height)
+    }
+
+    pub fn set_account_stx_locked(&mut self, principal: &PrincipalData, locked: u128, unlock_height: u64) {
+        let locked_key = ClarityDatabase::make_key_for_account_stx_locked(principal);
+        let unlock_height_key = ClarityDatabase::make_key_for_account_unlock_height(principal);
+        self.put(&locked_key, &locked);
+        self.put(&unlock_height_key, &unlock_height);
+    }
+
+    /// Get the amount of STX this account can spend right now -- i.e. its balance, less any
+    /// STX that are still locked.
+    pub fn get_account_stx_unlocked_balance(&mut self, principal: &PrincipalData) -> u128 {
+        let balance = self.get_account_stx_balance(principal);
+        let (locked, _) = self.get_account_stx_locked(principal);
+        balance.checked_sub(locked).expect("FATAL: account has more STX locked than its balance")
+    }
+
+    pub fn make_key_for_account_asset_holdings(principal: &PrincipalData) -> String {
+        ClarityDatabase::make_key_for_account(principal, StoreType::AssetHoldings)
+    }
+
+    fn make_key_for_account_asset_holding(principal: &PrincipalData, slot: u64) -> String {
+        format!("{}::{}", ClarityDatabase::make_key_for_account_asset_holdings(principal), slot)
+    }
+
+    fn make_key_for_account_asset_holding_slot(principal: &PrincipalData, holding: &AssetHolding) -> String {
+        format!("{}::{}", ClarityDatabase::make_key_for_account(principal, StoreType::AssetHoldingSlot), holding.index_key())
+    }
+
+    /// Get the fungible and non-fungible tokens this principal holds, across all contracts.
+    pub fn get_account_asset_holdings(&mut self, principal: &PrincipalData) -> AssetHoldings {
+        let key = ClarityDatabase::make_key_for_account_asset_holdings(principal);
+        let num_holdings : u64 = self.get(&key).unwrap_or(0);
+
+        let mut holdings = AssetHoldings::default();
+        for slot in 0..num_holdings {
+            let holding_key = ClarityDatabase::make_key_for_account_asset_holding(principal, slot);
+            let holding : AssetHolding = self.get(&holding_key)
+                .expect("FATAL: asset index is missing a holding");
+
+            match holding {
+                AssetHolding::FungibleToken(contract_id, token_name) => holdings.fungible_tokens.push((contract_id, token_name)),
+                AssetHolding::NonFungibleToken(contract_id, asset_name, asset) => holdings.non_fungible_tokens.push((contract_id, asset_name, asset))
+            }
+        }
+        holdings
+    }
+
+    /// The index is a list of holdings, plus a map from each holding to its position in the list
+    /// (stored off by one, so 0 means not held).  Adding or removing a holding touches a constant
+    /// number of keys, no matter how many assets the principal holds.
+    fn get_account_asset_holding_slot(&mut self, principal: &PrincipalData, holding: &AssetHolding) -> Option<u64> {
+        let slot_key = ClarityDatabase::make_key_for_account_asset_holding_slot(principal, holding);
+        match self.get::<u64>(&slot_key) {
+            None | Some(0) => None,
+            Some(slot) => Some(slot - 1)
+        }
+    }
+
+    fn set_account_asset_holding(&mut self, principal: &PrincipalData, slot: u64, holding: &AssetHolding) {
+        let holding_key = ClarityDatabase::make_key_for_account_asset_holding(principal, slot);
+        let slot_key = ClarityDatabase::make_key_for_account_asset_holding_slot(principal, holding);
+        self.put(&holding_key, holding);
+        self.put(&slot_key, &(slot + 1));
+    }
+
+    fn insert_account_asset_holding(&mut self, principal: &PrincipalData, holding: &AssetHolding) {
+        if self.get_account_asset_holding_slot(principal, holding).is_some() {
+            return;
+        }
+
+        let key = ClarityDatabase::make_key_for_account_asset_holdings(principal);
+        let num_holdings : u64 = self.get(&key).unwrap_or(0);
+
+        self.set_account_asset_holding(principal, num_holdings, holding);
+        self.put(&key, &(num_holdings + 1));
+    }
+
+    fn remove_account_asset_holding(&mut self, principal: &PrincipalData, holding: &AssetHolding) {
+        let slot = match self.get_account_asset_holding_slot(principal, holding) {
+            Some(slot) => slot,
+            None => return
+        };
+
+        let key = ClarityDatabase::make_key_for_account_asset_holdings(principal);
+        let num_holdings : u64 = self.get(&key).expect("FATAL: asset index has a holding but no length");
+        let last_slot = num_holdings - 1;
+
+        // move the last holding into the vacated slot
+        if slot != last_slot {
+            let last_key = ClarityDatabase::make_key_for_account_asset_holding(principal, last_slot);
+            let last_holding : AssetHolding = self.get(&last_key)
+                .expect("FATAL: asset index is missing a holding");
+            self.set_account_asset_holding(principal, slot, &last_holding);
+        }
+
+        let slot_key = ClarityDatabase::make_key_for_account_asset_holding_slot(principal, holding);
+        self.put(&slot_key, &0u64);
+        self.put(&key, &last_slot);
+    }
+
     pub fn get_account_nonce(&mut self, principal: &PrincipalData) -> u64 {
         let key = ClarityDatabase::make_key_for_account_nonce(principal);
         let result = self.get(&key);
@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::collections::HashMap;
 
 use vm::types::{QualifiedContractIdentifier};
 use vm::errors::{InterpreterError, CheckErrors, InterpreterResult as Result, IncomparableError, RuntimeErrorType};
@@ -24,7 +25,22 @@ pub struct MarfedKV {
     marf: MARF,
     // Since the MARF only stores 32 bytes of value,
     //   we need another storage
-    side_store: SqliteConnection
+    side_store: SqliteConnection,
+    // Writes held back since each open begin_savepoint(), innermost last
+    savepoints: Vec<SavepointWrites>,
+    // Lowest block height that time-shifted evaluation may read, if history below it was pruned
+    history_horizon: Option<u32>
+}
+
+/// Writes to the open block made since a savepoint began.  They are visible to reads at the
+///   open chain tip, but only reach the MARF and side-storage if the savepoint is committed.
+///   MARF writes are replayed batch-by-batch, in their original order, so committing a savepoint
+///   produces exactly the same trie as writing through it would have.
+struct SavepointWrites {
+    batches: Vec<Vec<(String, String)>>,
+    lookup: HashMap<String, String>,
+    metadata: Vec<(BlockHeaderHash, String, String, String)>,
+    metadata_lookup: HashMap<(BlockHeaderHash, String, String), String>
 }
 
 pub struct MemoryBackingStore {
@@ -142,7 +158,7 @@ impl MarfedKV {
             None => TrieFileStorage::block_sentinel()
         };
 
-        Ok( MarfedKV { marf, chain_tip, side_store } )
+        Ok( MarfedKV { marf, chain_tip, side_store, savepoints: vec![], history_horizon: None } )
     }
 
     #[cfg(test)]
@@ -160,7 +176,7 @@ impl MarfedKV {
 
         let chain_tip = TrieFileStorage::block_sentinel();
 
-        MarfedKV { marf, chain_tip, side_store }
+        MarfedKV { marf, chain_tip, side_store, savepoints: vec![], history_horizon: None }
     }
 
     pub fn as_clarity_db<'a>(&'a mut self, headers_db: &'a dyn HeadersDB) -> ClarityDatabase<'a> {
@@ -187,8 +203,10 @@ impl MarfedKV {
             .expect("ERROR: Failed to get open MARF")
             .clone();
         self.side_store.begin(&self.chain_tip);
+        self.history_horizon = None;
     }
     pub fn rollback(&mut self) {
+        self.savepoints.clear();
         self.marf.drop_current();
         self.side_store.rollback(&self.chain_tip);
         self.chain_tip = TrieFileStorage::block_sentinel();
@@ -215,10 +233,60 @@ impl MarfedKV {
         self.marf.commit_to(final_bhh)
             .expect("ERROR: Failed to commit MARF block");
     }
+    /// Begin holding back writes to the open block, so they can be committed or discarded
+    ///   as a unit.  Savepoints nest.
+    pub fn begin_savepoint(&mut self) {
+        self.savepoints.push(SavepointWrites {
+            batches: vec![],
+            lookup: HashMap::new(),
+            metadata: vec![],
+            metadata_lookup: HashMap::new()
+        });
+    }
+
+    /// Write everything held back since the innermost begin_savepoint() to the enclosing
+    /// savepoint, or to the open block if there is none.
+    pub fn commit_savepoint(&mut self) {
+        let savepoint = self.savepoints.pop()
+            .expect("BUG: no savepoint open");
+
+        for batch in savepoint.batches.into_iter() {
+            self.put_all(batch);
+        }
+        for (bhh, contract, key, value) in savepoint.metadata.into_iter() {
+            match self.savepoints.last_mut() {
+                Some(outer) => {
+                    outer.metadata_lookup.insert((bhh, contract.clone(), key.clone()), value.clone());
+                    outer.metadata.push((bhh, contract, key, value));
+                },
+                None => {
+                    self.side_store.insert_metadata(&bhh, &contract, &key, &value);
+                }
+            }
+        }
+    }
+
+    /// Discard everything written since the innermost begin_savepoint().
+    pub fn rollback_savepoint(&mut self) {
+        self.savepoints.pop()
+            .expect("BUG: no savepoint open");
+    }
+
+    /// Are reads being serviced from the open chain tip (and thus should see held-back writes)?
+    fn reading_open_chain_tip(&self) -> bool {
+        self.marf.get_open_chain_tip() == Some(&self.chain_tip)
+    }
+
     pub fn get_chain_tip(&self) -> &BlockHeaderHash {
         &self.chain_tip
     }
 
+    /// Refuse to evaluate `at-block` against blocks below the given height, e.g. because their
+    ///   history was pruned.  Cleared when the next block begins.
+    pub fn set_history_horizon(&mut self, horizon: Option<u32>) {
+        self.history_horizon = horizon;
+    }
+
     #[cfg(test)]
     pub fn set_chain_tip(&mut self, bhh: &BlockHeaderHash) {
         self.chain_tip = bhh.clone();
@@ -263,6 +331,15 @@ impl ClarityBackingStore for MarfedKV {
             }
         })?;
 
+        if let Some(horizon) = self.history_horizon {
+            let height = self.marf.get_block_height_of(&bhh, &self.chain_tip)
+                .expect("Unexpected MARF failure.")
+                .expect("Failed to obtain block height of ancestor block.");
+            if height < horizon {
+                return Err(RuntimeErrorType::PrunedBlockHeaderHash(bhh).into());
+            }
+        }
+
         let result = Ok(self.chain_tip);
         self.chain_tip = bhh;
 
@@ -292,6 +369,14 @@ impl ClarityBackingStore for MarfedKV {
     }
 
     fn get(&mut self, key: &str) -> Option<String> {
+        if self.reading_open_chain_tip() {
+            for savepoint in self.savepoints.iter().rev() {
+                if let Some(value) = savepoint.lookup.get(key) {
+                    return Some(value.clone());
+                }
+            }
+        }
+
         self.marf.get(&self.chain_tip, key)
             .or_else(|e| {
                 match e {
@@ -308,7 +393,38 @@ impl ClarityBackingStore for MarfedKV {
             })
     }
 
+    fn insert_metadata(&mut self, contract: &QualifiedContractIdentifier, key: &str, value: &str) {
+        let bhh = self.get_open_chain_tip();
+        match self.savepoints.last_mut() {
+            Some(savepoint) => {
+                savepoint.metadata_lookup.insert((bhh.clone(), contract.to_string(), key.to_string()), value.to_string());
+                savepoint.metadata.push((bhh, contract.to_string(), key.to_string(), value.to_string()));
+            },
+            None => {
+                self.side_store.insert_metadata(&bhh, &contract.to_string(), key, value)
+            }
+        }
+    }
+
+    fn get_metadata(&mut self, contract: &QualifiedContractIdentifier, key: &str) -> Result<Option<String>> {
+        let (bhh, _) = self.get_contract_hash(contract)?;
+        for savepoint in self.savepoints.iter().rev() {
+            if let Some(value) = savepoint.metadata_lookup.get(&(bhh.clone(), contract.to_string(), key.to_string())) {
+                return Ok(Some(value.clone()));
+            }
+        }
+        Ok(self.side_store.get_metadata(&bhh, &contract.to_string(), key))
+    }
+
     fn put_all(&mut self, mut items: Vec<(String, String)>) {
+        if let Some(savepoint) = self.savepoints.last_mut() {
+            for (key, value) in items.iter() {
+                savepoint.lookup.insert(key.clone(), value.clone());
+            }
+            savepoint.batches.push(items);
+            return;
+        }
+
         let mut keys = Vec::new();
         let mut values = Vec::new();
         for (key, value) in items.drain(..) {
@@ -8,6 +8,6 @@ use std::collections::HashMap;
 
 pub use self::key_value_wrapper::{RollbackWrapper};
 pub use self::clarity_db::{ClarityDatabase, HeadersDB, NULL_HEADER_DB};
-pub use self::structures::{ClaritySerializable, ClarityDeserializable};
+pub use self::structures::{ClaritySerializable, ClarityDeserializable, AssetHoldings, AssetHolding};
 pub use self::sqlite::{SqliteConnection};
 pub use self::marf::{MemoryBackingStore, MarfedKV, ClarityBackingStore};
@@ -98,6 +98,9 @@ impl SqliteConnection {
     pub fn rollback(&mut self, key: &BlockHeaderHash) {
         self.conn.execute(&format!("ROLLBACK TO SAVEPOINT SP{};", key), NO_PARAMS)
             .expect(SQL_FAIL_MESSAGE);
+        // ROLLBACK TO leaves the savepoint open (and with it, the write transaction)
+        self.conn.execute(&format!("RELEASE SAVEPOINT SP{};", key), NO_PARAMS)
+            .expect(SQL_FAIL_MESSAGE);
     }
 
     pub fn commit(&mut self, key: &BlockHeaderHash) {
@@ -1,6 +1,6 @@
 use vm::contracts::Contract;
 use vm::errors::{Error, InterpreterError, RuntimeErrorType, InterpreterResult as Result, IncomparableError};
-use vm::types::{Value, OptionalData, TypeSignature, TupleTypeSignature, PrincipalData, NONE};
+use vm::types::{Value, OptionalData, TypeSignature, TupleTypeSignature, PrincipalData, QualifiedContractIdentifier, NONE};
 
 pub trait ClaritySerializable {
     fn serialize(&self) -> String;
@@ -76,6 +76,38 @@ pub struct SimmedBlock {
 
 clarity_serializable!(SimmedBlock);
 
+/// The tokens a principal holds, across all contracts.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AssetHoldings {
+    /// (contract, token name) of each fungible token with a non-zero balance
+    pub fungible_tokens: Vec<(QualifiedContractIdentifier, String)>,
+    /// (contract, asset name, serialized asset) of each non-fungible token owned
+    pub non_fungible_tokens: Vec<(QualifiedContractIdentifier, String, String)>
+}
+
+/// One entry in a principal's asset index.  The index is kept up-to-date as fungible token
+/// balances and non-fungible token owners change, so a principal's assets can be listed
+/// without scanning every contract.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AssetHolding {
+    /// (contract, token name) of a fungible token with a non-zero balance
+    FungibleToken(QualifiedContractIdentifier, String),
+    /// (contract, asset name, serialized asset) of an owned non-fungible token
+    NonFungibleToken(QualifiedContractIdentifier, String, String)
+}
+
+impl AssetHolding {
+    /// Identifies this holding within a principal's asset index
+    pub fn index_key(&self) -> String {
+        match *self {
+            AssetHolding::FungibleToken(ref contract_id, ref token_name) => format!("ft::{}::{}", contract_id, token_name),
+            AssetHolding::NonFungibleToken(ref contract_id, ref asset_name, ref asset) => format!("nft::{}::{}::{}", contract_id, asset_name, asset)
+        }
+    }
+}
+
+clarity_serializable!(AssetHolding);
+
 clarity_serializable!(PrincipalData);
 clarity_serializable!(i128);
 clarity_serializable!(u128);
@@ -45,9 +45,10 @@ pub fn special_stx_transfer(args: &[SymbolicExpression],
         }
 
         let from_bal = env.global_context.database.get_account_stx_balance(&from);
+        let from_unlocked_bal = env.global_context.database.get_account_stx_unlocked_balance(&from);
         let to_bal = env.global_context.database.get_account_stx_balance(&to);
 
-        if from_bal < amount {
+        if from_unlocked_bal < amount {
             return clarity_ecode!(StxErrorCodes::NOT_ENOUGH_BALANCE)
         }
 
@@ -58,7 +59,7 @@ pub fn special_stx_transfer(args: &[SymbolicExpression],
         env.global_context.database.set_account_stx_balance(&from, final_from_bal);
         env.global_context.database.set_account_stx_balance(&to,   final_to_bal);
 
-        env.global_context.log_stx_transfer(&from, amount)?;
+        env.global_context.log_stx_transfer(from, to, amount)?;
 
         Ok(Value::okay(Value::Bool(true)))
 
@@ -87,8 +88,9 @@ pub fn special_stx_burn(args: &[SymbolicExpression],
         }
 
         let from_bal = env.global_context.database.get_account_stx_balance(&from);
+        let from_unlocked_bal = env.global_context.database.get_account_stx_unlocked_balance(&from);
 
-        if from_bal < amount {
+        if from_unlocked_bal < amount {
             return clarity_ecode!(StxErrorCodes::NOT_ENOUGH_BALANCE)
         }
 
@@ -134,6 +136,8 @@ pub fn special_mint_token(args: &[SymbolicExpression],
 
         env.global_context.database.set_ft_balance(&env.contract_context.contract_identifier, token_name, to_principal, final_to_bal)?;
 
+        env.global_context.log_token_mint(to_principal, &env.contract_context.contract_identifier, token_name, amount);
+
         Ok(Value::okay(Value::Bool(true)))
     } else {
         Err(CheckErrors::BadMintFTArguments.into())
@@ -168,6 +172,8 @@ pub fn special_mint_asset(args: &[SymbolicExpression],
 
         env.global_context.database.set_nft_owner(&env.contract_context.contract_identifier, asset_name, &asset, to_principal)?;
 
+        env.global_context.log_asset_mint(to_principal, &env.contract_context.contract_identifier, asset_name, asset);
+
         Ok(Value::okay(Value::Bool(true)))
     } else {
         Err(CheckErrors::TypeValueError(TypeSignature::PrincipalType, to).into())
@@ -216,7 +222,7 @@ pub fn special_transfer_asset(args: &[SymbolicExpression],
 
         env.global_context.database.set_nft_owner(&env.contract_context.contract_identifier, asset_name, &asset, to_principal)?;
 
-        env.global_context.log_asset_transfer(from_principal, &env.contract_context.contract_identifier, asset_name, asset);
+        env.global_context.log_asset_transfer(from_principal, to_principal, &env.contract_context.contract_identifier, asset_name, asset);
 
         Ok(Value::okay(Value::Bool(true)))
     } else {
@@ -265,7 +271,7 @@ pub fn special_transfer_token(args: &[SymbolicExpression],
         env.global_context.database.set_ft_balance(&env.contract_context.contract_identifier, token_name, from_principal, final_from_bal)?;
         env.global_context.database.set_ft_balance(&env.contract_context.contract_identifier, token_name, to_principal, final_to_bal)?;
 
-        env.global_context.log_token_transfer(from_principal, &env.contract_context.contract_identifier, token_name, amount)?;
+        env.global_context.log_token_transfer(from_principal, to_principal, &env.contract_context.contract_identifier, token_name, amount)?;
 
         Ok(Value::okay(Value::Bool(true)))
     } else {
@@ -317,6 +317,40 @@ fn test_native_stx_ops(owned_env: &mut OwnedEnvironment) {
                .get(&AssetIdentifier::STX()).unwrap(),
                &AssetMapEntry::STX(100));
 
+    // locked STX can't be transferred or burned
+
+    let p4 = execute("'SP1A2K3ENNA6QQ7G8DVJXM24T6QMBDVS7D0TRTAR5");
+    let p4_principal = match p4 {
+        Value::Principal(ref data) => data.clone(),
+        _ => panic!()
+    };
+
+    owned_env.stx_faucet(&p4_principal, 100);
+    owned_env.stx_lock(&p4_principal, 60, 1000);
+
+    let (result, asset_map) = execute_transaction(
+        owned_env, p4.clone(), &token_contract_id, "xfer-stx",
+        &symbols_from_values(vec![Value::UInt(41), p4.clone(), p3.clone()])).unwrap();
+
+    assert!(is_err_code(&result, 1));
+    assert_eq!(asset_map.to_table().len(), 0);
+
+    let (result, asset_map) = execute_transaction(
+        owned_env, p4.clone(), &token_contract_id, "burn-stx",
+        &symbols_from_values(vec![Value::UInt(41), p4.clone()])).unwrap();
+
+    assert!(is_err_code(&result, 1));
+    assert_eq!(asset_map.to_table().len(), 0);
+
+    let (result, asset_map) = execute_transaction(
+        owned_env, p4.clone(), &token_contract_id, "xfer-stx",
+        &symbols_from_values(vec![Value::UInt(40), p4.clone(), p3.clone()])).unwrap();
+
+    assert!(is_committed(&result));
+    let table = asset_map.to_table();
+    assert_eq!(table.get(&p4_principal).unwrap()
+               .get(&AssetIdentifier::STX()).unwrap(),
+               &AssetMapEntry::STX(40));
 }
 
 fn test_simple_token_system(owned_env: &mut OwnedEnvironment) {
@@ -152,6 +152,51 @@ fn test_at_block_missing_defines() {
 
 }
 
+#[test]
+fn test_at_block_pruned() {
+    let c = QualifiedContractIdentifier::local("contract").unwrap();
+    let contract =
+        "(define-data-var datum int 1)
+         (define-read-only (get-at-1)
+           (at-block 0x0101010101010101010101010101010101010101010101010101010101010101 (var-get datum)))
+         (define-read-only (get-at-2)
+           (at-block 0x0202020202020202020202020202020202020202020202020202020202020202 (var-get datum)))";
+
+    let mut marf_kv = MarfedKV::temporary();
+    marf_kv.begin(&TrieFileStorage::block_sentinel(),
+                  &BlockHeaderHash::from_bytes(&[0 as u8; 32]).unwrap());
+    marf_kv.as_clarity_db(&NULL_HEADER_DB).initialize();
+    marf_kv.test_commit();
+
+    marf_kv.begin(&BlockHeaderHash::from_bytes(&[0 as u8; 32]).unwrap(),
+                  &BlockHeaderHash::from_bytes(&[1 as u8; 32]).unwrap());
+    {
+        let mut owned_env = OwnedEnvironment::new(marf_kv.as_clarity_db(&NULL_HEADER_DB));
+        owned_env.initialize_contract(c.clone(), &contract).unwrap();
+    }
+    marf_kv.test_commit();
+
+    marf_kv.begin(&BlockHeaderHash::from_bytes(&[1 as u8; 32]).unwrap(),
+                  &BlockHeaderHash::from_bytes(&[2 as u8; 32]).unwrap());
+    marf_kv.test_commit();
+
+    // only history from block 2 onwards may be read
+    marf_kv.begin(&BlockHeaderHash::from_bytes(&[2 as u8; 32]).unwrap(),
+                  &BlockHeaderHash::from_bytes(&[3 as u8; 32]).unwrap());
+    marf_kv.set_history_horizon(Some(2));
+    {
+        let mut owned_env = OwnedEnvironment::new(marf_kv.as_clarity_db(&NULL_HEADER_DB));
+        let mut env = owned_env.get_exec_environment(None);
+
+        assert_eq!(env.eval_read_only(&c, "(get-at-2)").unwrap(), Value::Int(1));
+        match env.eval_read_only(&c, "(get-at-1)").unwrap_err() {
+            Error::Runtime(x, _) =>
+                assert_eq!(x, RuntimeErrorType::PrunedBlockHeaderHash(BlockHeaderHash::from(vec![1 as u8; 32].as_slice()))),
+            e => panic!("Unexpected error: {}", e)
+        }
+    }
+}
+
 // execute:
 // f -> a -> z
 //    \--> b
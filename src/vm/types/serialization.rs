@@ -466,6 +466,48 @@ impl Value {
     }
 }
 
+impl Value {
+    /// Human-readable JSON encoding of this value.  Each value is encoded as an object
+    ///   carrying its Clarity type and its contents, so that the encoding is unambiguous:
+    ///   integers are strings (to avoid losing precision), buffers are 0x-prefixed hex,
+    ///   principals are c32 strings, optionals are either null or the inner value,
+    ///   and responses are {"ok": ..} or {"err": ..}.
+    pub fn to_json(&self) -> JSONValue {
+        json!({
+            "type": format!("{}", TypeSignature::type_of(self)),
+            "value": self.to_json_contents()
+        })
+    }
+
+    fn to_json_contents(&self) -> JSONValue {
+        match self {
+            Value::Int(value) => JSONValue::String(format!("{}", value)),
+            Value::UInt(value) => JSONValue::String(format!("{}", value)),
+            Value::Bool(value) => JSONValue::Bool(*value),
+            Value::Buffer(value) => JSONValue::String(format!("0x{}", to_hex(&value.data))),
+            Value::Principal(PrincipalData::Standard(p)) => JSONValue::String(format!("{}", p)),
+            Value::Principal(PrincipalData::Contract(p)) => JSONValue::String(format!("{}.{}", p.issuer, p.name.as_str())),
+            Value::List(list) => JSONValue::Array(list.data.iter().map(|v| v.to_json()).collect()),
+            Value::Tuple(tuple) => {
+                let mut fields = serde_json::Map::new();
+                for (name, value) in tuple.data_map.iter() {
+                    fields.insert(name.to_string(), value.to_json());
+                }
+                JSONValue::Object(fields)
+            },
+            Value::Optional(OptionalData { data: None }) => JSONValue::Null,
+            Value::Optional(OptionalData { data: Some(value) }) => value.to_json(),
+            Value::Response(response) => {
+                if response.committed {
+                    json!({ "ok": response.data.to_json() })
+                } else {
+                    json!({ "err": response.data.to_json() })
+                }
+            }
+        }
+    }
+}
+
 impl ClaritySerializable for Value {
     fn serialize(&self) -> String {
         let mut byte_serialization = Vec::new();
@@ -772,4 +814,36 @@ mod tests {
         test_bad_expectation(contract_p2, TypeSignature::BoolType);
         test_bad_expectation(standard_p, TypeSignature::BoolType);
     }
+
+    #[test]
+    fn test_to_json() {
+        let issuer = PrincipalData::parse_standard_principal("SM2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQVX8X0G").unwrap();
+        let contract_identifier = QualifiedContractIdentifier::new(issuer.clone(), "foo".into());
+
+        assert_eq!(Value::Int(-1).to_json(), json!({"type": "int", "value": "-1"}));
+        assert_eq!(Value::UInt(u128::max_value()).to_json(),
+                   json!({"type": "uint", "value": "340282366920938463463374607431768211455"}));
+        assert_eq!(Value::Bool(true).to_json(), json!({"type": "bool", "value": true}));
+        assert_eq!(Value::buff_from(vec![0xde, 0xad]).unwrap().to_json(),
+                   json!({"type": "(buff 2)", "value": "0xdead"}));
+        assert_eq!(Value::from(issuer).to_json(),
+                   json!({"type": "principal", "value": "SM2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQVX8X0G"}));
+        assert_eq!(Value::from(PrincipalData::Contract(contract_identifier)).to_json(),
+                   json!({"type": "principal", "value": "SM2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQVX8X0G.foo"}));
+        assert_eq!(Value::none().to_json(), json!({"type": "(optional UnknownType)", "value": null}));
+        assert_eq!(Value::some(Value::Int(1)).to_json(),
+                   json!({"type": "(optional int)", "value": {"type": "int", "value": "1"}}));
+        assert_eq!(Value::okay(Value::Bool(false)).to_json()["value"],
+                   json!({"ok": {"type": "bool", "value": false}}));
+        assert_eq!(Value::error(Value::UInt(2)).to_json()["value"],
+                   json!({"err": {"type": "uint", "value": "2"}}));
+        assert_eq!(Value::list_from(vec![Value::Int(1), Value::Int(2)]).unwrap().to_json(),
+                   json!({"type": "(list 2 int)",
+                          "value": [{"type": "int", "value": "1"}, {"type": "int", "value": "2"}]}));
+
+        let tuple = Value::from(TupleData::from_data(vec![("a".into(), Value::Int(1)),
+                                                          ("b".into(), Value::Bool(false))]).unwrap());
+        assert_eq!(tuple.to_json()["value"],
+                   json!({"a": {"type": "int", "value": "1"}, "b": {"type": "bool", "value": false}}));
+    }
 }